@@ -13,7 +13,7 @@ use image::DynamicImage;
 
 use crate::{
     error::MirajazzError,
-    images::convert_image_with_format,
+    images::convert_image_into,
     state::{DeviceState, DeviceStateReader},
     types::{DeviceInput, ImageFormat},
 };
@@ -121,6 +121,12 @@ struct ImageCache {
     image_data: Vec<u8>,
 }
 
+#[derive(Default)]
+struct ImageBuffers {
+    spare: Vec<Vec<u8>>,
+    rgb: Vec<u8>,
+}
+
 /// Interface for a device
 pub struct Device {
     /// Vendor ID of the device
@@ -150,6 +156,9 @@ pub struct Device {
     hid_device: Mutex<HidDevice>,
     /// Temporarily cache the image before sending it to the device
     image_cache: RwLock<Vec<ImageCache>>,
+    /// Buffers of flushed images and of flattened pixels, encoded into again by
+    /// `set_button_image` instead of allocating for every frame
+    image_buffers: Mutex<ImageBuffers>,
     /// Device needs to be initialized
     initialized: AtomicBool,
 }
@@ -196,6 +205,7 @@ impl Device {
             report_id,
             hid_device: Mutex::new(hid_device),
             image_cache: RwLock::new(vec![]),
+            image_buffers: Mutex::new(ImageBuffers::default()),
             initialized: false.into(),
         })
     }
@@ -537,9 +547,12 @@ impl Device {
     ) -> Result<(), MirajazzError> {
         self.initialize()?;
 
-        let image_data = convert_image_with_format(image_format, image)?;
+        let mut buffers = self.image_buffers.lock()?;
+        let mut image_data = buffers.spare.pop().unwrap_or_default();
+        convert_image_into(image_format, image, &mut image_data, &mut buffers.rgb)?;
+        drop(buffers);
 
-        self.write_image(key, &image_data)?;
+        self.image_cache.write()?.push(ImageCache { key, image_data });
 
         Ok(())
     }
@@ -715,14 +728,19 @@ impl Device {
         let mut buf = vec![self.report_id, 0x43, 0x52, 0x54, 0x00, 0x00, 0x53, 0x54, 0x50];
         self.write_extended_data(&mut buf)?;
 
-        self.image_cache.write()?.clear();
+        // Keep the buffers of one image per key for the next frames
+        let mut buffers = self.image_buffers.lock()?;
+        for image in self.image_cache.write()?.drain(..) {
+            if buffers.spare.len() < self.key_count {
+                buffers.spare.push(image.image_data);
+            }
+        }
 
         Ok(())
     }
 
     /// Returns button state reader for this device
     pub fn get_reader(self: &Arc<Self>) -> Arc<DeviceStateReader> {
-        #[allow(clippy::arc_with_non_send_sync)]
         Arc::new(DeviceStateReader {
             device: self.clone(),
             states: Mutex::new(DeviceState {
@@ -7,12 +7,19 @@ use image::{ColorType, DynamicImage, GenericImageView, ImageEncoder, ImageError,
 use crate::error::MirajazzError;
 use crate::types::{ImageFormat, ImageMirroring, ImageMode, ImageRotation};
 
-/// Composites RGBA image onto black background, returning RGB pixel data.
+/// Composites RGBA image onto black background, writing RGB pixel data into `rgb`.
 /// Properly handles alpha blending so transparent pixels become black.
-fn flatten_to_rgb(image: &DynamicImage) -> Vec<u8> {
-    let rgba = image.to_rgba8();
-    let (w, h) = (rgba.width(), rgba.height());
-    let mut rgb = Vec::with_capacity((w * h * 3) as usize);
+fn flatten_to_rgb(image: &DynamicImage, rgb: &mut Vec<u8>) {
+    let converted;
+    let rgba = match image.as_rgba8() {
+        Some(rgba) => rgba,
+        None => {
+            converted = image.to_rgba8();
+            &converted
+        }
+    };
+    rgb.clear();
+    rgb.reserve((rgba.width() * rgba.height() * 3) as usize);
     for pixel in rgba.pixels() {
         let Rgba([r, g, b, a]) = *pixel;
         let alpha = a as f32 / 255.0;
@@ -20,13 +27,17 @@ fn flatten_to_rgb(image: &DynamicImage) -> Vec<u8> {
         rgb.push((g as f32 * alpha) as u8);
         rgb.push((b as f32 * alpha) as u8);
     }
-    rgb
 }
 
 /// Applies resize, rotation and mirroring transformations to an image
 fn transform_image(image: DynamicImage, size: (usize, usize), rotation: ImageRotation, mirror: ImageMirroring) -> DynamicImage {
-    let (ws, hs) = size;
-    let image = image.resize_exact(ws as u32, hs as u32, FilterType::Nearest);
+    let (ws, hs) = (size.0 as u32, size.1 as u32);
+    // Images already drawn at the key size are used as they are
+    let image = if image.dimensions() == (ws, hs) {
+        image
+    } else {
+        image.resize_exact(ws, hs, FilterType::Nearest)
+    };
 
     let image = match rotation {
         ImageRotation::Rot0 => image,
@@ -48,30 +59,42 @@ pub fn convert_image_with_format(
     image_format: ImageFormat,
     image: DynamicImage,
 ) -> Result<Vec<u8>, ImageError> {
+    let mut buf = Vec::new();
+    convert_image_into(image_format, image, &mut buf, &mut Vec::new())?;
+    Ok(buf)
+}
+
+/// Converts image into image data like [`convert_image_with_format`], writing it into
+/// `buf`. `rgb` holds the flattened pixels of BMP and JPEG images; both are cleared
+/// first, so they can be reused across frames without allocating again.
+pub fn convert_image_into(
+    image_format: ImageFormat,
+    image: DynamicImage,
+    buf: &mut Vec<u8>,
+    rgb: &mut Vec<u8>,
+) -> Result<(), ImageError> {
     let image = transform_image(image, image_format.size, image_format.rotation, image_format.mirror);
     let (w, h) = image.dimensions();
+    buf.clear();
 
     match image_format.mode {
-        ImageMode::None => Ok(vec![]),
+        ImageMode::None => Ok(()),
         ImageMode::BMP | ImageMode::JPEG => {
             // Composite RGBA onto black background so transparent pixels become black
-            let image_data = flatten_to_rgb(&image);
-            let mut buf = Vec::new();
+            flatten_to_rgb(&image, rgb);
             if matches!(image_format.mode, ImageMode::BMP) {
-                let mut encoder = BmpEncoder::new(&mut buf);
-                encoder.encode(&image_data, w, h, ColorType::Rgb8.into())?;
+                let mut encoder = BmpEncoder::new(buf);
+                encoder.encode(rgb, w, h, ColorType::Rgb8.into())?;
             } else {
-                let mut encoder = JpegEncoder::new_with_quality(&mut buf, 90);
-                encoder.encode(&image_data, w, h, ColorType::Rgb8.into())?;
+                let mut encoder = JpegEncoder::new_with_quality(buf, 90);
+                encoder.encode(rgb, w, h, ColorType::Rgb8.into())?;
             }
-            Ok(buf)
+            Ok(())
         }
         ImageMode::PNG => {
-            let image_data = image.into_rgba8().to_vec();
-            let mut buf = Vec::new();
-            let encoder = PngEncoder::new(&mut buf);
-            encoder.write_image(&image_data, w, h, ColorType::Rgba8.into())?;
-            Ok(buf)
+            let image_data = image.into_rgba8();
+            let encoder = PngEncoder::new(buf);
+            encoder.write_image(&image_data, w, h, ColorType::Rgba8.into())
         }
     }
 }
@@ -106,3 +129,37 @@ impl ImageRect {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    fn key_format(mode: ImageMode) -> ImageFormat {
+        ImageFormat { mode, size: (4, 4), rotation: ImageRotation::Rot0, mirror: ImageMirroring::None }
+    }
+
+    #[test]
+    fn test_reused_buffers_encode_like_fresh_ones() {
+        let red = DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255])));
+        let blue = DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 8, Rgba([0, 0, 255, 128])));
+        let (mut buf, mut rgb) = (Vec::new(), Vec::new());
+        for mode in [ImageMode::BMP, ImageMode::JPEG, ImageMode::PNG] {
+            for image in [&blue, &red] {
+                convert_image_into(key_format(mode), image.clone(), &mut buf, &mut rgb).unwrap();
+                assert_eq!(buf, convert_image_with_format(key_format(mode), image.clone()).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_images_at_key_size_are_not_resized() {
+        let mut pixels = RgbaImage::new(4, 4);
+        pixels.put_pixel(1, 2, Rgba([10, 20, 30, 255]));
+        let image = DynamicImage::ImageRgba8(pixels);
+        let same = transform_image(image.clone(), (4, 4), ImageRotation::Rot0, ImageMirroring::None);
+        assert_eq!(same.as_bytes(), image.as_bytes());
+        let larger = transform_image(image, (8, 8), ImageRotation::Rot0, ImageMirroring::None);
+        assert_eq!(larger.dimensions(), (8, 8));
+    }
+}
@@ -1,8 +1,70 @@
-use super::definition::DeviceDefinition;
+use super::definition::{DeviceDefinition, HardwareId};
 use super::error::RegistryError;
+use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// Whether a file holds an override rather than a full definition
+fn is_override(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(".override.json"))
+}
+
+/// VID/PID of the `hardware` field of a definition or override
+fn hardware_id(value: &Value, file_path: &Path) -> Option<(u16, u16)> {
+    let hardware: HardwareId = match value.get("hardware").cloned().map(serde_json::from_value) {
+        Some(Ok(hardware)) => hardware,
+        Some(Err(e)) => {
+            eprintln!("Warning: Invalid hardware in {}: {}", file_path.display(), e);
+            return None;
+        }
+        None => {
+            eprintln!("Warning: Missing hardware in {}", file_path.display());
+            return None;
+        }
+    };
+
+    let vid = match hardware.vendor_id_u16() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Warning: Invalid vendor_id in {}: {}", file_path.display(), e);
+            return None;
+        }
+    };
+
+    let pid = match hardware.product_id_u16() {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Warning: Invalid product_id in {}: {}", file_path.display(), e);
+            return None;
+        }
+    };
+
+    Some((vid, pid))
+}
+
+/// Applies a JSON merge patch (RFC 7386) to `target`
+fn merge(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let Value::Object(target) = target else {
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            merge(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
 
 /// Device registry loaded from JSON files
 pub struct DeviceRegistry {
@@ -14,8 +76,15 @@ impl DeviceRegistry {
     /// Load device definitions from multiple directories in priority order.
     /// Later paths take precedence over earlier ones if duplicate VID/PID are found.
     /// Skips directories that don't exist.
+    ///
+    /// Files named `*.override.json` hold a partial definition with the `hardware` of
+    /// the device it corrects. After all definitions are read, each override is merged
+    /// into the definition with the same VID/PID as a JSON merge patch (RFC 7386):
+    /// objects are merged field by field, `null` removes a field and anything else
+    /// replaces it. Overrides apply in path order, then by file name.
     pub fn load_from_paths<P: AsRef<Path>>(paths: &[P]) -> Result<Self, RegistryError> {
-        let mut devices = HashMap::new();
+        let mut definitions: HashMap<(u16, u16), (PathBuf, Value)> = HashMap::new();
+        let mut overrides: Vec<(PathBuf, Value)> = Vec::new();
 
         for path in paths {
             let path = path.as_ref();
@@ -31,14 +100,14 @@ impl DeviceRegistry {
                 Err(_) => continue, // Skip directories we can't read
             };
 
-            for entry in dir {
-                let entry = match entry {
-                    Ok(e) => e,
-                    Err(_) => continue, // Skip unreadable entries
-                };
-
-                let file_path = entry.path();
+            // Skip unreadable entries; sort so overrides apply in a stable order
+            let mut files: Vec<PathBuf> = dir
+                .filter_map(|entry| entry.ok())
+                .map(|e| e.path())
+                .collect();
+            files.sort();
 
+            for file_path in files {
                 // Only process .json files
                 if file_path.extension().and_then(|s| s.to_str()) != Some("json") {
                     continue;
@@ -50,33 +119,48 @@ impl DeviceRegistry {
                     Err(_) => continue, // Skip unreadable files
                 };
 
-                let def: DeviceDefinition = match serde_json::from_str(&content) {
-                    Ok(d) => d,
-                    Err(e) => {
-                        eprintln!("Warning: Failed to parse {}: {}", file_path.display(), e);
-                        continue;
-                    }
-                };
-
-                // Parse VID/PID
-                let vid = match def.hardware.vendor_id_u16() {
+                let value: Value = match serde_json::from_str(&content) {
                     Ok(v) => v,
                     Err(e) => {
-                        eprintln!("Warning: Invalid vendor_id in {}: {}", file_path.display(), e);
+                        eprintln!("Warning: Failed to parse {}: {}", file_path.display(), e);
                         continue;
                     }
                 };
 
-                let pid = match def.hardware.product_id_u16() {
-                    Ok(p) => p,
-                    Err(e) => {
-                        eprintln!("Warning: Invalid product_id in {}: {}", file_path.display(), e);
-                        continue;
-                    }
-                };
+                if is_override(&file_path) {
+                    overrides.push((file_path, value));
+                    continue;
+                }
 
                 // Later paths override earlier ones (insert replaces existing)
-                devices.insert((vid, pid), def);
+                if let Some(id) = hardware_id(&value, &file_path) {
+                    definitions.insert(id, (file_path, value));
+                }
+            }
+        }
+
+        for (file_path, patch) in overrides {
+            let Some(id) = hardware_id(&patch, &file_path) else {
+                continue;
+            };
+            match definitions.get_mut(&id) {
+                Some((_, definition)) => merge(definition, patch),
+                None => eprintln!(
+                    "Warning: {} overrides unknown device {:04x}:{:04x}",
+                    file_path.display(),
+                    id.0,
+                    id.1
+                ),
+            }
+        }
+
+        let mut devices = HashMap::new();
+        for (id, (file_path, value)) in definitions {
+            match serde_json::from_value::<DeviceDefinition>(value) {
+                Ok(def) => {
+                    devices.insert(id, def);
+                }
+                Err(e) => eprintln!("Warning: Failed to parse {}: {}", file_path.display(), e),
             }
         }
 
@@ -198,6 +282,30 @@ mod tests {
         assert_eq!(found.unwrap().info.human_name, "Test Device");
     }
 
+    #[test]
+    fn test_override_merges_into_definition() {
+        let mut definition = serde_json::to_value(create_test_device(0x5548, 0x6674)).unwrap();
+        merge(
+            &mut definition,
+            serde_json::json!({
+                "hardware": { "vendor_id": "0x5548", "product_id": "0x6674" },
+                "input_mapping": { "button_remap": [1, 0] },
+                "quirks": { "needs_button_remapping": true }
+            }),
+        );
+        let def: DeviceDefinition = serde_json::from_value(definition).unwrap();
+        assert_eq!(def.input_mapping.button_remap, Some(vec![1, 0]));
+        assert!(def.quirks.needs_button_remapping);
+        assert_eq!(def.layout.cols, 6);
+        assert_eq!(def.image_format.default_size, [85, 85]);
+
+        let mut value = serde_json::json!({ "a": { "b": 1, "c": [1, 2] }, "d": 2 });
+        merge(&mut value, serde_json::json!({ "a": { "c": [3] }, "d": null }));
+        assert_eq!(value, serde_json::json!({ "a": { "b": 1, "c": [3] } }));
+        assert!(is_override(Path::new("devices/AKP153.override.json")));
+        assert!(!is_override(Path::new("devices/Ajazz-AKP153.json")));
+    }
+
     #[cfg(test)]
     fn create_test_device(vid: u16, pid: u16) -> DeviceDefinition {
         DeviceDefinition {
@@ -213,6 +321,8 @@ mod tests {
             },
             protocol: ProtocolConfig {
                 protocol_version: 1,
+                device_mode: None,
+                report_id: None,
             },
             layout: Layout {
                 rows: 3,
@@ -227,7 +337,9 @@ mod tests {
                 per_button_overrides: HashMap::new(),
             },
             input_mapping: InputMapping::default(),
+            background: None,
             quirks: Quirks::default(),
+            led: None,
         }
     }
 }
@@ -0,0 +1,64 @@
+/* SPDX-License-Identifier: GPL-3.0-or-later */
+/* Copyright (C) 2025 Panayotis Katsaloulis */
+
+/*
+ * C interface of the KeyDeck library, for frontends in other languages.
+ *
+ * Build the shared library with
+ *     cargo build --lib --release --features capi
+ * and link against target/release/libkeydeck.so (keydeck.dll, libkeydeck.dylib).
+ *
+ * Texts are UTF-8, structured values JSON. A function that fails returns NULL or -1,
+ * and keydeck_last_error() tells why. Strings returned are freed with
+ * keydeck_string_free(), configurations with keydeck_config_free().
+ */
+
+#ifndef KEYDECK_H
+#define KEYDECK_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct KeydeckConfig KeydeckConfig;
+
+/* The error of the last call that failed on this thread, NULL when none did. Owned by
+ * the library, valid until the next failing call. */
+const char *keydeck_last_error(void);
+
+/* Frees a string returned by the library. */
+void keydeck_string_free(char *value);
+
+/* Loads and resolves the configuration at path as the daemon does, or the one of the
+ * user when path is NULL. */
+KeydeckConfig *keydeck_config_load(const char *path);
+
+/* The resolved configuration as JSON. */
+char *keydeck_config_json(const KeydeckConfig *config);
+
+void keydeck_config_free(KeydeckConfig *config);
+
+/* Draws a button ("button3" or "3") of a page of a page group into rgba, as width x
+ * height RGBA pixels row by row; rgba_len must be at least width * height * 4. Values
+ * of services and dynamic parameters are left as written. Returns 0, or -1. */
+int keydeck_render_button(const KeydeckConfig *config, const char *group, const char *page,
+                          const char *button, uint32_t width, uint32_t height, uint8_t *rgba,
+                          size_t rgba_len);
+
+/* The devices connected to the running daemon, as a JSON array of objects with their
+ * sn, model, page, brightness and pinned. */
+char *keydeck_devices(void);
+
+/* Sends a control command to the running daemon ("setpage AL1 Media", "status", or its
+ * JSON form) and returns its answer, an empty string for commands it does not answer.
+ * Unix only. */
+char *keydeck_send_command(const char *command);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* KEYDECK_H */
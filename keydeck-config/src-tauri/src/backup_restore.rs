@@ -12,7 +12,7 @@ use zip::CompressionMethod;
 ///
 /// # Behavior
 /// - Includes: config.yaml, devices/ folder, symlinks (with absolute/relative paths preserved), and all other files
-/// - Excludes: config.TIMESTAMP.yaml (timestamped backups), .service_prompt_count (internal state)
+/// - Excludes: config.TIMESTAMP.yaml and other timestamped backups, .service_prompt_count (internal state)
 /// - Symlinks are stored as symlinks in the ZIP with proper Unix file type bits
 ///
 /// # Errors
@@ -51,11 +51,8 @@ pub fn backup_config_directory(zip_path: &str) -> Result<(), String> {
         if let Some(filename) = path.file_name() {
             let filename_str = filename.to_string_lossy();
 
-            // Skip timestamped config backup files (config.TIMESTAMP.yaml)
-            if filename_str.starts_with("config.")
-                && filename_str.ends_with(".yaml")
-                && filename_str != "config.yaml"
-            {
+            // Skip timestamped backup files (config.TIMESTAMP.yaml, stats.TIMESTAMP.json.gz)
+            if keydeck_types::is_backup_file(&filename_str) {
                 continue;
             }
 
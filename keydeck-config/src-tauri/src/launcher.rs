@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Launcher buttons for the entries of the application list: the application's icon
+//! and actions that bring its window forward, or start it when it is not running.
+
+use keydeck_types::{Action, Button, FocusSpec, FocusTarget};
+use serde::Serialize;
+
+/// A button made by [`launcher_button`]
+#[derive(Debug, Serialize)]
+pub struct LauncherButton {
+    /// Icon copied to the icon dir
+    pub icon: String,
+    pub button: Button,
+    /// The button as YAML, to paste into the configuration
+    pub yaml: String,
+}
+
+/// A button showing `icon` that focuses the application's windows, found by their
+/// exact `class` or through its `desktop` file, and runs `exec` when there are none
+pub fn launcher_button(
+    icon: String,
+    name: &str,
+    exec: Option<&str>,
+    class: Option<&str>,
+    desktop: Option<&str>,
+) -> Result<LauncherButton, String> {
+    let exec = exec.ok_or_else(|| format!("No command found to start {}", name))?;
+    let launch = Action::Exec {
+        exec: exec.to_string(),
+        wait: None,
+        run_as: None,
+        scope: None,
+        timeout: None,
+        tag: None,
+        kill_on_exit: None,
+    };
+    let focus = match (class, desktop) {
+        (Some(class), _) => Some(FocusSpec {
+            class: Some(class.to_string()),
+            exact: true,
+            ..Default::default()
+        }),
+        (None, Some(desktop)) => Some(FocusSpec {
+            desktop: Some(desktop.to_string()),
+            ..Default::default()
+        }),
+        (None, None) => None,
+    };
+    let action = match focus {
+        Some(spec) => Action::Try {
+            try_actions: vec![Action::Focus {
+                focus: FocusTarget::Detailed(spec),
+            }],
+            else_actions: Some(vec![launch]),
+        },
+        None => launch,
+    };
+
+    let button: Button = serde_json::from_value(serde_json::json!({
+        "icon": icon,
+        "actions": [action],
+    }))
+    .map_err(|e| format!("Failed to create the button: {}", e))?;
+    let yaml = serde_yaml_ng::to_string(&button)
+        .map_err(|e| format!("Failed to serialize the button: {}", e))?;
+    Ok(LauncherButton { icon, button, yaml })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn launcher_focuses_before_starting() {
+        let launcher = launcher_button(
+            "firefox.png".to_string(),
+            "Firefox",
+            Some("firefox"),
+            None,
+            Some("org.mozilla.firefox"),
+        )
+        .unwrap();
+        assert_eq!(
+            launcher.yaml,
+            "icon: firefox.png\nactions:\n- try:\n  - focus:\n      desktop: org.mozilla.firefox\n  else:\n  - exec: firefox\n"
+        );
+        assert!(launcher_button(String::new(), "Broken", None, None, None).is_err());
+    }
+}
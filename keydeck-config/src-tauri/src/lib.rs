@@ -2,8 +2,10 @@
 // Copyright (C) 2025 Panayotis Katsaloulis
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::process::Command;
+use keydeck_types::IconPaths;
 use tauri::{Emitter, Manager};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -17,9 +19,21 @@ struct DaemonStatus {
     running: bool,
     pid: Option<i32>,
     timestamp: i64,
+    config_generation: Option<u64>,
+    last_reload_error: Option<String>,
+    /// Version of the running daemon, when it answers on its control socket
+    version: Option<String>,
+    gui_version: String,
+    /// The daemon runs a different version of keydeck than this GUI
+    version_mismatch: bool,
+    /// Seconds since the daemon started
+    uptime: Option<u64>,
+    devices: Vec<keydeck_types::api::DeviceState>,
 }
 
 mod backup_restore;
+mod launcher;
+mod onboarding;
 mod windows;
 
 #[cfg(target_os = "linux")]
@@ -33,8 +47,8 @@ mod macos_icon_finder;
 
 // Re-export keydeck types and functions for frontend
 pub use keydeck_types::{
-    get_config_dir, get_config_path, get_icon_dir, get_log_path, DeviceInfo, KeyDeckConf,
-    DEFAULT_ICON_DIR_REL,
+    get_config_dir, get_config_path, get_icon_dir, get_log_path, Action, DeviceInfo, KeyDeckConf,
+    Macro, DEFAULT_ICON_DIR_REL,
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -108,6 +122,193 @@ fn get_device_info(device_id: String) -> Result<DeviceInfo, String> {
     serde_yaml_ng::from_str(&stdout).map_err(|e| format!("Failed to parse device info: {}", e))
 }
 
+/// A device serial number and the page group it is shown
+#[derive(Debug, Serialize, Deserialize)]
+struct DevicePageGroup {
+    serial: String,
+    /// Only known for connected devices
+    model: Option<String>,
+    connected: bool,
+    /// Whether the device has a page group of its own, instead of the `default` one
+    own_pages: bool,
+}
+
+/// List the connected devices and the page groups of devices that are not connected,
+/// so the UI can copy pages between devices or move the pages of a replaced one
+#[tauri::command]
+fn list_device_page_groups(config: KeyDeckConf) -> Result<Vec<DevicePageGroup>, String> {
+    let devices = list_devices()?;
+    let mut groups: Vec<DevicePageGroup> = devices
+        .iter()
+        .map(|device| DevicePageGroup {
+            serial: device.serial.clone(),
+            model: Some(device.model.clone()),
+            connected: true,
+            own_pages: config.page_groups.contains_key(&device.serial),
+        })
+        .collect();
+    for serial in config.page_groups.keys() {
+        if serial != "default" && !devices.iter().any(|device| &device.serial == serial) {
+            groups.push(DevicePageGroup {
+                serial: serial.clone(),
+                model: None,
+                connected: false,
+                own_pages: true,
+            });
+        }
+    }
+    Ok(groups)
+}
+
+/// Give device `to` a copy of the page group `from`; returns the updated configuration
+#[tauri::command]
+fn clone_page_group(
+    mut config: KeyDeckConf,
+    from: String,
+    to: String,
+) -> Result<KeyDeckConf, String> {
+    config.clone_page_group(&from, &to)?;
+    Ok(config)
+}
+
+/// Move the page group `from` and its hotkeys and webhooks to the serial `to`, for a replaced
+/// device; returns the updated configuration
+#[tauri::command]
+fn rekey_page_group(
+    mut config: KeyDeckConf,
+    from: String,
+    to: String,
+) -> Result<KeyDeckConf, String> {
+    config.rekey_page_group(&from, &to)?;
+    Ok(config)
+}
+
+/// A problem of a macro definition, with its position in the YAML when known
+#[derive(Debug, Serialize)]
+struct MacroProblem {
+    message: String,
+    line: Option<usize>,
+    column: Option<usize>,
+}
+
+impl MacroProblem {
+    fn new(message: String) -> Self {
+        MacroProblem {
+            message,
+            line: None,
+            column: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct MacroValidation {
+    valid: bool,
+    /// The `${param}` placeholders of the actions
+    params: Vec<String>,
+    /// The actions of a call with the default parameters
+    actions: Option<Vec<Action>>,
+    errors: Vec<MacroProblem>,
+    warnings: Vec<MacroProblem>,
+}
+
+/// Check the YAML of a macro definition (`params` and `actions`) as the daemon would
+/// read it: syntax errors with their position, actions that do not parse once the
+/// default parameters are substituted, and parameters without a default value
+#[tauri::command]
+fn validate_macro(yaml: String) -> MacroValidation {
+    let mut validation = MacroValidation {
+        valid: false,
+        params: vec![],
+        actions: None,
+        errors: vec![],
+        warnings: vec![],
+    };
+    let definition: Macro = match serde_yaml_ng::from_str(&yaml) {
+        Ok(definition) => definition,
+        Err(e) => {
+            let location = e.location();
+            validation.errors.push(MacroProblem {
+                message: e.to_string(),
+                line: location.as_ref().map(|l| l.line()),
+                column: location.as_ref().map(|l| l.column()),
+            });
+            return validation;
+        }
+    };
+
+    let defaults = definition.params.clone().unwrap_or_default();
+    let missing: Vec<String> = definition
+        .used_params()
+        .into_iter()
+        .filter(|param| !defaults.contains_key(param))
+        .collect();
+    for param in &missing {
+        validation.warnings.push(MacroProblem::new(format!(
+            "Parameter '{}' has no default value; every call must provide it",
+            param
+        )));
+    }
+    validation.params = definition.used_params().into_iter().collect();
+
+    match definition.expand("macro", &Default::default()) {
+        Ok(actions) => validation.actions = Some(actions),
+        // A placeholder left in e.g. a number may be fine once a call provides it
+        Err(e) if !missing.is_empty() => validation.warnings.push(MacroProblem::new(e)),
+        Err(e) => validation.errors.push(MacroProblem::new(e)),
+    }
+    validation.valid = validation.errors.is_empty();
+    validation
+}
+
+/// Nested macro calls followed by a preview before giving up, against call loops
+const MAX_MACRO_DEPTH: usize = 16;
+
+/// The actions a call of macro `name` with `params` runs, nested macro calls included.
+/// Uses the configuration being edited, or the saved one when none is given.
+#[tauri::command]
+fn expand_macro_preview(
+    name: String,
+    params: HashMap<String, String>,
+    config: Option<KeyDeckConf>,
+) -> Result<Vec<Action>, String> {
+    let config = match config {
+        Some(config) => config,
+        None => load_config(None)?,
+    };
+    expand_macro_call(&config, &name, &params, 0)
+}
+
+fn expand_macro_call(
+    config: &KeyDeckConf,
+    name: &str,
+    params: &HashMap<String, String>,
+    depth: usize,
+) -> Result<Vec<Action>, String> {
+    if depth >= MAX_MACRO_DEPTH {
+        return Err(format!(
+            "Macro '{}' is nested more than {} levels deep",
+            name, MAX_MACRO_DEPTH
+        ));
+    }
+    let definition = config
+        .macros
+        .as_ref()
+        .and_then(|macros| macros.get(name))
+        .ok_or_else(|| format!("Macro '{}' not found", name))?;
+    let mut actions = vec![];
+    for action in definition.expand(name, params)? {
+        match action {
+            Action::Macro(call) => {
+                let nested = expand_macro_call(config, &call.name, &call.params, depth + 1)?;
+                actions.extend(nested);
+            }
+            action => actions.push(action),
+        }
+    }
+    Ok(actions)
+}
+
 /// Load keydeck configuration from a file path (or default ~/.config/keydeck.yaml if path is None)
 /// If the config file doesn't exist, returns a default empty configuration instead of an error.
 /// This is expected behavior for first-time app launch.
@@ -132,8 +333,8 @@ fn load_config(path: Option<String>) -> Result<KeyDeckConf, String> {
         return Ok(KeyDeckConf::default());
     }
 
-    let mut conf: KeyDeckConf =
-        serde_yaml_ng::from_str(&content).map_err(|e| format!("Failed to parse config: {}", e))?;
+    let mut conf: KeyDeckConf = keydeck_types::parse_config(&config_path, &content)
+        .map_err(|e| format!("Failed to parse config: {}", e))?;
     // Upgrade legacy `window_name` into the unified `when` structure so the UI only ever
     // deals with `when`; saving then rewrites the config in the new shape (auto-migration).
     conf.migrate_legacy_window_name();
@@ -158,86 +359,28 @@ async fn list_window_classes() -> Result<Vec<String>, String> {
         .map_err(|e| format!("window class lookup task failed: {}", e))?
 }
 
-/// Save keydeck configuration to ~/.config/keydeck/config.yaml atomically with timestamped backup
+/// Save keydeck configuration to ~/.config/keydeck/config.yaml (or config.json) atomically
+/// with timestamped backup. A config.toml is only read: it is edited by hand.
 #[tauri::command]
 fn save_config(config: KeyDeckConf) -> Result<(), String> {
-    use std::fs;
-    use std::time::SystemTime;
-
-    let config_dir = get_config_dir();
-    let config_path = get_config_path();
-
-    // Ensure the directory exists
-    fs::create_dir_all(&config_dir)
-        .map_err(|e| format!("Failed to create config directory: {}", e))?;
-
-    // Serialize config to YAML
-    let yaml = serde_yaml_ng::to_string(&config)
-        .map_err(|e| format!("Failed to serialize config: {}", e))?;
-
-    // Step 1: Write to temporary file
-    let temp_path = config_dir.join("config.tmp.yaml");
-    fs::write(&temp_path, &yaml).map_err(|e| format!("Failed to write temp config file: {}", e))?;
-
-    // Step 2: If current config exists, create timestamped backup
-    if config_path.exists() {
-        let timestamp = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        let backup_name = format!(
-            "config.{}.yaml",
-            chrono::DateTime::from_timestamp(timestamp as i64, 0)
-                .unwrap()
-                .format("%Y%m%d_%H%M%S")
-        );
-        let backup_path = config_dir.join(&backup_name);
-
-        fs::copy(&config_path, &backup_path)
-            .map_err(|e| format!("Failed to create backup: {}", e))?;
-    }
-
-    // Step 3: Atomically replace config file (rename is atomic on Unix)
-    fs::rename(&temp_path, &config_path)
-        .map_err(|e| format!("Failed to save config file: {}", e))?;
-
-    // Step 4: Cleanup old backups (keep only 10 most recent)
-    cleanup_old_backups(&config_dir)?;
-
-    Ok(())
-}
-
-/// Remove old backup files, keeping only the 10 most recent
-fn cleanup_old_backups(config_dir: &PathBuf) -> Result<(), String> {
-    use std::fs;
-
-    // Read all backup files
-    let entries =
-        fs::read_dir(config_dir).map_err(|e| format!("Failed to read config directory: {}", e))?;
-
-    let mut backups: Vec<_> = entries
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| {
-            entry.file_name().to_string_lossy().starts_with("config.")
-                && entry.file_name().to_string_lossy().ends_with(".yaml")
-                && entry.file_name() != "config.yaml"
-                && entry.file_name() != "config.tmp.yaml"
-        })
-        .collect();
-
-    // Sort by modification time (newest first)
-    backups.sort_by_key(|entry| {
-        entry
-            .metadata()
-            .and_then(|m| m.modified())
-            .unwrap_or(std::time::SystemTime::UNIX_EPOCH)
-    });
-    backups.reverse();
+    use keydeck_types::ConfigFormat;
+
+    let path = get_config_path();
+    let content = match ConfigFormat::of(&path) {
+        ConfigFormat::Yaml => serde_yaml_ng::to_string(&config)
+            .map_err(|e| format!("Failed to serialize config: {}", e))?,
+        ConfigFormat::Json => serde_json::to_string_pretty(&config)
+            .map_err(|e| format!("Failed to serialize config: {}", e))?,
+        ConfigFormat::Toml => {
+            return Err(format!(
+                "{} is TOML, which the configuration UI does not write; edit it by hand, or convert it to config.yaml",
+                path.display()
+            ))
+        }
+    };
 
-    // Remove all but the 10 most recent
-    for backup in backups.iter().skip(10) {
-        let _ = fs::remove_file(backup.path());
-    }
+    // Same save as the daemon: backup, temp file + fsync + rename, keep the newest backups
+    keydeck_types::save_config_file(&path, &content)?;
 
     Ok(())
 }
@@ -248,6 +391,11 @@ struct DaemonStatusJson {
     running: bool,
     pid: Option<u32>,
     enabled: bool,
+    /// Only reported by a running daemon that answers on its control socket
+    #[serde(default)]
+    config_generation: Option<u64>,
+    #[serde(default)]
+    last_reload_error: Option<String>,
 }
 
 /// Query the daemon lifecycle status by invoking `keydeck --daemon status`.
@@ -300,12 +448,36 @@ fn run_daemon(verb: &str) -> Result<(), String> {
     Err(msg)
 }
 
-/// Check if keydeck daemon is running (delegates to `keydeck --daemon status`)
+/// Ask the daemon for its status over the control socket.
 ///
-/// Async + `spawn_blocking` on purpose: this is polled every few seconds, and
-/// `query_daemon_status` spawns a `keydeck --daemon status` subprocess that can
-/// occasionally stall (e.g. the daemon busy holding its lock). Running it inline
-/// as a sync command would block the WebKitGTK main thread and freeze the UI.
+/// `Ok(None)` when nothing listens on the socket, i.e. no daemon is running, and an
+/// error when a daemon is there but gives no answer this GUI understands, as older
+/// daemons do.
+#[cfg(unix)]
+fn ping_daemon() -> Result<Option<keydeck_types::api::DaemonStatus>, String> {
+    use keydeck_types::api::{self, Command};
+    use std::io::ErrorKind;
+
+    match api::request(&Command::Status, std::time::Duration::from_secs(2)) {
+        Ok(answer) => serde_json::from_str(&answer)
+            .map(Some)
+            .map_err(|e| format!("Unexpected daemon status: {} ({})", e, answer)),
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+            Ok(None)
+        }
+        Err(e) => Err(format!("Failed to query the daemon: {}", e)),
+    }
+}
+
+/// Check if keydeck daemon is running, with its version, uptime and devices
+///
+/// On Unix the daemon is asked directly over its control socket; a daemon too old to
+/// answer, and Windows, fall back to `keydeck --daemon status`.
+///
+/// Async + `spawn_blocking` on purpose: this is polled every few seconds, and both the
+/// socket request and the `keydeck --daemon status` subprocess can occasionally stall
+/// (e.g. the daemon busy holding its lock). Running it inline as a sync command would
+/// block the WebKitGTK main thread and freeze the UI.
 #[tauri::command]
 async fn check_daemon_status() -> DaemonStatus {
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -314,18 +486,48 @@ async fn check_daemon_status() -> DaemonStatus {
         .duration_since(UNIX_EPOCH)
         .map(|d| d.as_secs() as i64)
         .unwrap_or(0);
+    let gui_version = env!("CARGO_PKG_VERSION").to_string();
+    let not_running = DaemonStatus {
+        running: false,
+        pid: None,
+        timestamp,
+        config_generation: None,
+        last_reload_error: None,
+        version: None,
+        gui_version: gui_version.clone(),
+        version_mismatch: false,
+        uptime: None,
+        devices: vec![],
+    };
+
+    #[cfg(unix)]
+    match tokio::task::spawn_blocking(ping_daemon).await {
+        Ok(Ok(Some(s))) => {
+            return DaemonStatus {
+                running: true,
+                pid: Some(s.pid as i32),
+                config_generation: Some(s.config_generation),
+                last_reload_error: s.last_reload_error,
+                version_mismatch: s.keydeck != gui_version,
+                version: Some(s.keydeck),
+                uptime: Some(s.uptime),
+                devices: s.devices,
+                ..not_running
+            }
+        }
+        Ok(Ok(None)) => return not_running,
+        _ => {}
+    }
 
     match tokio::task::spawn_blocking(query_daemon_status).await {
         Ok(Ok(s)) => DaemonStatus {
             running: s.running,
             pid: s.pid.map(|p| p as i32),
-            timestamp,
-        },
-        _ => DaemonStatus {
-            running: false,
-            pid: None,
-            timestamp,
+            config_generation: s.config_generation,
+            last_reload_error: s.last_reload_error,
+            ..not_running
         },
+        _ => not_running,
     }
 }
 
@@ -343,6 +545,41 @@ async fn check_service_enabled() -> bool {
         .unwrap_or(false)
 }
 
+/// A KeyDeck daemon found on the network, as printed by `keydeck --discover --json`.
+#[derive(Debug, Serialize, Deserialize)]
+struct RemoteDaemon {
+    name: String,
+    host: String,
+    port: u16,
+    addresses: Vec<String>,
+    url: String,
+}
+
+/// Search the local network for daemons advertising their web deck (delegates to
+/// `keydeck --discover --json`, which listens for a few seconds, hence off the main
+/// thread).
+#[tauri::command]
+async fn discover_daemons() -> Result<Vec<RemoteDaemon>, String> {
+    tokio::task::spawn_blocking(|| {
+        let keydeck_bin = find_keydeck_binary()?;
+        let output = Command::new(&keydeck_bin)
+            .args(["--discover", "--json"])
+            .output()
+            .map_err(|e| format!("Failed to execute keydeck: {}", e))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        serde_json::from_str(stdout.trim()).map_err(|e| {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.trim().is_empty() {
+                format!("Failed to parse discovered daemons: {}", e)
+            } else {
+                stderr.trim().to_string()
+            }
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
 /// Check if we should show the service prompt (shows for first 3 launches)
 #[tauri::command]
 fn should_show_service_prompt() -> bool {
@@ -405,6 +642,92 @@ fn set_service_prompt_count(count: u32) -> Result<(), String> {
     Ok(())
 }
 
+/// Everything the first-run wizard checks, in the order of its steps
+#[derive(Debug, Serialize)]
+struct OnboardingStatus {
+    devices: Vec<DeviceListItem>,
+    /// Why the devices could not be listed
+    devices_error: Option<String>,
+    permissions: onboarding::PermissionCheck,
+    config_exists: bool,
+    daemon_running: bool,
+    autostart: bool,
+}
+
+/// Run the checks of the first-run wizard: connected devices, permissions to open them,
+/// an existing configuration and the daemon autostart. Enabling autostart is left to
+/// `start_daemon_service`.
+#[tauri::command]
+async fn onboarding_status() -> Result<OnboardingStatus, String> {
+    tokio::task::spawn_blocking(|| {
+        // Listing first also extracts the device files the permission check reads
+        let (devices, devices_error) = match list_devices() {
+            Ok(devices) => (devices, None),
+            Err(e) => (vec![], Some(e)),
+        };
+        let daemon = query_daemon_status().ok();
+        OnboardingStatus {
+            devices,
+            devices_error,
+            permissions: onboarding::check_permissions(),
+            config_exists: get_config_path().exists(),
+            daemon_running: daemon.as_ref().is_some_and(|d| d.running),
+            autostart: daemon.as_ref().is_some_and(|d| d.enabled),
+        }
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))
+}
+
+/// The udev rules the wizard installs, for users who prefer to install them by hand
+#[tauri::command]
+fn suggested_udev_rules() -> String {
+    onboarding::udev_rules()
+}
+
+/// Install the udev rules for the supported decks, asking for administrator rights
+#[tauri::command]
+async fn install_udev_rules() -> Result<(), String> {
+    tokio::task::spawn_blocking(onboarding::install_udev_rules)
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Show the numbered test pattern on a device, or on all of them, through the running
+/// daemon (delegates to `keydeck --test-pattern`)
+#[tauri::command]
+fn show_test_pattern(serial: Option<String>) -> Result<(), String> {
+    let keydeck_bin = find_keydeck_binary()?;
+    let mut command = Command::new(&keydeck_bin);
+    command.arg("--test-pattern");
+    if let Some(serial) = &serial {
+        command.arg(serial);
+    }
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to execute keydeck: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr)
+            .trim()
+            .trim_start_matches("Error: ")
+            .to_string())
+    }
+}
+
+/// Save the starter configuration of the wizard and return it. An existing
+/// configuration is only replaced (after a backup) with `overwrite`.
+#[tauri::command]
+fn create_starter_config(overwrite: bool) -> Result<KeyDeckConf, String> {
+    if !overwrite && load_config(None).is_ok_and(|conf| !conf.page_groups.is_empty()) {
+        return Err("A configuration already exists".to_string());
+    }
+    let config = onboarding::starter_config();
+    save_config(config.clone())?;
+    Ok(config)
+}
+
 /// Reload the running daemon's configuration (delegates to `keydeck --daemon reload`)
 #[tauri::command]
 fn reload_keydeck() -> Result<(), String> {
@@ -538,12 +861,118 @@ fn restore_config_directory(path: String) -> Result<(), String> {
 }
 
 /// Get the full path to an image file from the hard-coded icon directory
+/// Run `keydeck <args> --json` and parse the JSON it prints; its stderr is the error
+fn run_keydeck_json<T: serde::de::DeserializeOwned>(args: &[&str]) -> Result<T, String> {
+    let keydeck_bin = find_keydeck_binary()?;
+    let output = Command::new(&keydeck_bin)
+        .args(args)
+        .arg("--json")
+        .output()
+        .map_err(|e| format!("Failed to execute keydeck: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.trim().trim_start_matches("Error: ").to_string());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    serde_json::from_str(stdout.trim())
+        .map_err(|e| format!("Failed to parse keydeck output: {}", e))
+}
+
+/// Result of `keydeck --export`
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportReport {
+    icons: usize,
+    missing: Vec<String>,
+}
+
+/// Result of `keydeck --import`
+#[derive(Debug, Serialize, Deserialize)]
+struct ImportReport {
+    icons: usize,
+    renamed: std::collections::BTreeMap<String, String>,
+    skipped: Vec<String>,
+    backup: Option<String>,
+}
+
+/// Export the saved configuration and the icons it uses as a portable `.keydeck`
+/// archive (delegates to `keydeck --export`)
+#[tauri::command]
+async fn export_config(path: String) -> Result<ExportReport, String> {
+    tokio::task::spawn_blocking(move || run_keydeck_json(&["--export", &path]))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Restore a `.keydeck` archive: its icons go to the icon dir and its configuration
+/// replaces the current one. `on_conflict` (rename, skip or overwrite) decides about
+/// icons that exist with other content (delegates to `keydeck --import`).
+#[tauri::command]
+async fn import_config(path: String, on_conflict: String) -> Result<ImportReport, String> {
+    tokio::task::spawn_blocking(move || {
+        run_keydeck_json(&["--import", &path, "--on-conflict", &on_conflict])
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Result of `keydeck --fetch-icon`
+#[derive(Debug, Serialize, Deserialize)]
+struct FetchedIcon {
+    icon: String,
+    source: String,
+    width: u32,
+    height: u32,
+}
+
+/// Save the favicon or app icon of a website in the icon dir, as a key-sized PNG named
+/// `name` or after the site (delegates to `keydeck --fetch-icon`)
+#[tauri::command]
+async fn fetch_icon(url: String, name: Option<String>) -> Result<FetchedIcon, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut args = vec!["--fetch-icon", url.as_str()];
+        if let Some(name) = name.as_deref().filter(|name| !name.is_empty()) {
+            args.extend(["--name", name]);
+        }
+        run_keydeck_json(&args)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Result of `keydeck --recolor-icons`
+#[derive(Debug, Serialize, Deserialize)]
+struct RecolorReport {
+    directory: String,
+    recolored: Vec<String>,
+    skipped: Vec<String>,
+}
+
+/// Redraw the PNG icons of the icon dir in one color and style (filled, outline or
+/// shaded) into its `recolored` subdirectory (delegates to `keydeck --recolor-icons`)
+#[tauri::command]
+async fn recolor_icons(
+    tint: Option<String>,
+    style: Option<String>,
+) -> Result<RecolorReport, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut args = vec!["--recolor-icons"];
+        if let Some(tint) = tint.as_deref().filter(|tint| !tint.is_empty()) {
+            args.extend(["--tint", tint]);
+        }
+        if let Some(style) = style.as_deref().filter(|style| !style.is_empty()) {
+            args.extend(["--style", style]);
+        }
+        run_keydeck_json(&args)
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?
+}
+
 #[tauri::command]
 fn get_image_path(filename: String) -> Result<String, String> {
-    let base_dir = PathBuf::from(get_icon_dir());
-    let image_path = base_dir.join(&filename);
+    let image_path = config_icon_paths().locate(&filename);
 
-    if !image_path.exists() {
+    if !image_path.is_file() {
         return Err(format!("Image not found: {}", image_path.display()));
     }
 
@@ -560,40 +989,52 @@ fn check_directory_exists(path: String) -> Result<bool, String> {
     Ok(dir_path.exists() && dir_path.is_dir())
 }
 
+/// Icons of every icon directory of the configuration on disk, by file name. A name
+/// found in more than one directory is the one of the first, as the daemon uses it.
 #[tauri::command]
-fn list_icons() -> Result<Vec<IconInfo>, String> {
-    let base_dir = PathBuf::from(get_icon_dir());
-
-    if !base_dir.exists() {
-        return Ok(Vec::new()); // Return empty list if directory doesn't exist
-    }
-
-    let entries = std::fs::read_dir(&base_dir)
-        .map_err(|e| format!("Failed to read directory {}: {}", base_dir.display(), e))?;
-
-    let mut icons = Vec::new();
+fn list_icons(app: tauri::AppHandle) -> Result<Vec<IconInfo>, String> {
+    let mut icons: Vec<IconInfo> = Vec::new();
 
     // Common image extensions
     let valid_extensions = ["png", "jpg", "jpeg", "gif", "bmp", "svg", "webp"];
 
-    for entry in entries {
-        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-        let path = entry.path();
-
-        if path.is_file() {
-            if let Some(ext) = path.extension() {
-                if let Some(ext_str) = ext.to_str() {
-                    if valid_extensions.contains(&ext_str.to_lowercase().as_str()) {
-                        if let Some(filename) = path.file_name() {
-                            if let Some(filename_str) = filename.to_str() {
-                                // Return the absolute path; the frontend loads it
-                                // via the Tauri asset protocol (convertFileSrc)
-                                // instead of a base64 data URL, avoiding a large
-                                // IPC payload that stalls the webview main thread.
-                                icons.push(IconInfo {
-                                    filename: filename_str.to_string(),
-                                    path: path.to_string_lossy().to_string(),
-                                });
+    let default_dir = PathBuf::from(get_icon_dir());
+    for base_dir in config_icon_paths().dirs() {
+        if !base_dir.exists() {
+            continue; // Directories that don't exist have no icons
+        }
+        // The asset protocol only serves the default icon dir unless told otherwise
+        if *base_dir != default_dir {
+            if let Err(e) = app.asset_protocol_scope().allow_directory(base_dir, true) {
+                eprintln!("Failed to allow icons of {}: {}", base_dir.display(), e);
+            }
+        }
+
+        let entries = std::fs::read_dir(base_dir)
+            .map_err(|e| format!("Failed to read directory {}: {}", base_dir.display(), e))?;
+
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+            let path = entry.path();
+
+            if path.is_file() {
+                if let Some(ext) = path.extension() {
+                    if let Some(ext_str) = ext.to_str() {
+                        if valid_extensions.contains(&ext_str.to_lowercase().as_str()) {
+                            if let Some(filename) = path.file_name() {
+                                if let Some(filename_str) = filename.to_str() {
+                                    if icons.iter().any(|icon| icon.filename == filename_str) {
+                                        continue;
+                                    }
+                                    // Return the absolute path; the frontend loads it
+                                    // via the Tauri asset protocol (convertFileSrc)
+                                    // instead of a base64 data URL, avoiding a large
+                                    // IPC payload that stalls the webview main thread.
+                                    icons.push(IconInfo {
+                                        filename: filename_str.to_string(),
+                                        path: path.to_string_lossy().to_string(),
+                                    });
+                                }
                             }
                         }
                     }
@@ -607,6 +1048,13 @@ fn list_icons() -> Result<Vec<IconInfo>, String> {
     Ok(icons)
 }
 
+/// Where the configuration on disk looks up its icons: its `icon_dirs`, then the default
+/// icon dir
+fn config_icon_paths() -> IconPaths {
+    let icon_dirs = load_config(None).ok().and_then(|config| config.icon_dirs);
+    IconPaths::new(&get_config_path(), icon_dirs.as_deref())
+}
+
 // Helper functions
 
 fn find_keydeck_binary() -> Result<PathBuf, String> {
@@ -710,6 +1158,24 @@ fn select_app_icon(app_name: String, icon_path: String) -> Result<String, String
     linux_icon_finder::copy_app_icon(app_name, icon_path, icon_dir)
 }
 
+/// Create a launch button for an application of the list: its icon is copied to the
+/// icon dir, and the button focuses its window or starts it (Linux only)
+#[cfg(target_os = "linux")]
+#[tauri::command]
+fn create_launcher_button(
+    app: linux_icon_finder::AppInfo,
+) -> Result<launcher::LauncherButton, String> {
+    let icon_dir = get_icon_dir();
+    let icon = linux_icon_finder::copy_app_icon(app.name.clone(), app.icon_path.clone(), icon_dir)?;
+    launcher::launcher_button(
+        icon,
+        &app.name,
+        app.exec.as_deref(),
+        app.class.as_deref(),
+        app.desktop.as_deref(),
+    )
+}
+
 /// List all installed applications (Windows only)
 #[cfg(target_os = "windows")]
 #[tauri::command]
@@ -728,6 +1194,25 @@ fn select_app_icon(app_name: String, icon_path: String) -> Result<String, String
     windows_icon_finder::copy_app_icon(app_name, icon_path, icon_dir)
 }
 
+/// Create a launch button for an application of the list: its icon is copied to the
+/// icon dir, and the button focuses its window or starts it (Windows only)
+#[cfg(target_os = "windows")]
+#[tauri::command]
+fn create_launcher_button(
+    app: windows_icon_finder::AppInfo,
+) -> Result<launcher::LauncherButton, String> {
+    let icon_dir = get_icon_dir();
+    let icon =
+        windows_icon_finder::copy_app_icon(app.name.clone(), app.icon_path.clone(), icon_dir)?;
+    launcher::launcher_button(
+        icon,
+        &app.name,
+        app.exec.as_deref(),
+        app.class.as_deref(),
+        None,
+    )
+}
+
 /// List all installed applications (macOS only)
 #[cfg(target_os = "macos")]
 #[tauri::command]
@@ -760,6 +1245,24 @@ fn select_app_icon(app_name: String, icon_path: String) -> Result<String, String
     macos_icon_finder::copy_app_icon(app_name, icon_path, icon_dir)
 }
 
+/// Create a launch button for an application of the list: its icon is copied to the
+/// icon dir, and the button focuses its window or starts it (macOS only)
+#[cfg(target_os = "macos")]
+#[tauri::command]
+fn create_launcher_button(
+    app: macos_icon_finder::AppInfo,
+) -> Result<launcher::LauncherButton, String> {
+    let icon_dir = get_icon_dir();
+    let icon = macos_icon_finder::copy_app_icon(app.name.clone(), app.icon_path.clone(), icon_dir)?;
+    launcher::launcher_button(
+        icon,
+        &app.name,
+        app.exec.as_deref(),
+        app.class.as_deref(),
+        None,
+    )
+}
+
 /// Result of icon cleanup preview, categorizing icons by usage
 #[derive(Debug, Serialize, Deserialize)]
 struct IconCleanupPreview {
@@ -771,7 +1274,21 @@ struct IconCleanupPreview {
     unused: Vec<String>,
 }
 
-/// Preview which icons will be deleted by the cleanup process
+/// Empty the icon cache (~/.cache/keydeck/icons on Linux) of application icons and
+/// icons scaled by the daemon; both are made again when needed
+#[tauri::command]
+fn clear_icon_cache() -> Result<keydeck_types::IconCacheStats, String> {
+    let removed = keydeck_types::clear_icon_cache()?;
+    #[cfg(target_os = "linux")]
+    linux_icon_finder::forget_applications();
+    #[cfg(target_os = "macos")]
+    macos_icon_finder::forget_applications();
+    Ok(removed)
+}
+
+/// Preview which icons will be deleted by the cleanup process. Only the default icon dir
+/// is cleaned up; an icon of it is in use when an icon of the configuration names it, by
+/// name, by path or relative to the configuration, and no other icon dir shadows it.
 #[tauri::command]
 fn preview_icon_cleanup() -> Result<IconCleanupPreview, String> {
     let icon_dir = PathBuf::from(get_icon_dir());
@@ -804,6 +1321,7 @@ fn preview_icon_cleanup() -> Result<IconCleanupPreview, String> {
     // Load the actual config from disk to find which icons are in use and protected patterns
     let config_path = get_config_path();
     let mut used_icons = std::collections::HashSet::new();
+    let mut used_files = std::collections::HashSet::new();
     let mut config_protected_patterns = Vec::new();
 
     if config_path.exists() {
@@ -813,6 +1331,13 @@ fn preview_icon_cleanup() -> Result<IconCleanupPreview, String> {
             .map_err(|e| format!("Failed to parse config file: {}", e))?;
 
         collect_used_icons(&config, &mut used_icons);
+        let icon_paths = IconPaths::new(&config_path, config.icon_dirs.as_deref());
+        used_files.extend(
+            used_icons
+                .iter()
+                .filter_map(|icon| icon_paths.resolve(icon))
+                .filter_map(|path| std::fs::canonicalize(path).ok()),
+        );
 
         // Get protected patterns from config (prefer config over parameter)
         if let Some(protected) = config.protected_icons {
@@ -829,7 +1354,9 @@ fn preview_icon_cleanup() -> Result<IconCleanupPreview, String> {
     let mut unused = Vec::new();
 
     for icon in all_icons {
-        if used_icons.contains(&icon) {
+        let in_use_file = std::fs::canonicalize(icon_dir.join(&icon))
+            .is_ok_and(|path| used_files.contains(&path));
+        if in_use_file {
             in_use.push(icon);
         } else if is_protected(&icon, &final_protected_patterns) {
             protected.push(icon);
@@ -883,7 +1410,7 @@ fn collect_used_icons(config: &KeyDeckConf, used_icons: &mut std::collections::H
     if let Some(buttons) = &config.buttons {
         for button in buttons.values() {
             if let Some(icon) = &button.icon {
-                used_icons.insert(icon.clone());
+                used_icons.extend(icon.images().into_iter().map(str::to_string));
             }
         }
     }
@@ -908,6 +1435,9 @@ fn collect_icons_from_page(
     page: &keydeck_types::Page,
     used_icons: &mut std::collections::HashSet<String>,
 ) {
+    if let Some(wallpaper) = &page.wallpaper {
+        used_icons.insert(wallpaper.image().to_string());
+    }
     for button_config in page.buttons.values() {
         collect_icons_from_button_config(button_config, used_icons);
     }
@@ -923,7 +1453,7 @@ fn collect_icons_from_button_config(
         }
         keydeck_types::ButtonConfig::Detailed(button) => {
             if let Some(icon) = &button.icon {
-                used_icons.insert(icon.clone());
+                used_icons.extend(icon.images().into_iter().map(str::to_string));
             }
         }
     }
@@ -977,6 +1507,52 @@ fn get_icon_data_url(file_path: String) -> Result<String, String> {
     Ok(format!("data:{};base64,{}", mime_type, base64_data))
 }
 
+/// Forward the key presses and page changes of the running daemon to the frontend as
+/// `live-event` events (from `keydeck --watch --json`), so the editor can highlight
+/// them. A single watcher serves every caller, and it reconnects when the daemon
+/// restarts.
+#[cfg(unix)]
+#[tauri::command]
+fn watch_live_events(window: tauri::Window) -> Result<(), String> {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static WATCHING: AtomicBool = AtomicBool::new(false);
+
+    let keydeck_bin = find_keydeck_binary()?;
+    if WATCHING.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+    std::thread::spawn(move || loop {
+        let child = Command::new(&keydeck_bin)
+            .args(["--watch", "--json"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        if let Ok(mut process) = child {
+            if let Some(stdout) = process.stdout.take() {
+                for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                    if let Ok(event) = serde_json::from_str::<serde_json::Value>(&line) {
+                        let _ = window.emit("live-event", event);
+                    }
+                }
+            }
+            let _ = process.wait();
+        }
+        // The daemon is not running, or restarting
+        std::thread::sleep(std::time::Duration::from_secs(2));
+    });
+    Ok(())
+}
+
+/// The daemon has no control socket on Windows and macOS to watch
+#[cfg(not(unix))]
+#[tauri::command]
+fn watch_live_events(_window: tauri::Window) -> Result<(), String> {
+    Err("Live events are not supported on this platform".to_string())
+}
+
 /// Stream daemon logs to the frontend.
 ///
 /// On Linux the daemon runs as a systemd user service, so logs are read from
@@ -1313,10 +1889,21 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             list_devices,
             get_device_info,
+            list_device_page_groups,
+            clone_page_group,
+            rekey_page_group,
+            validate_macro,
+            expand_macro_preview,
             load_config,
             save_config,
             check_daemon_status,
             check_service_enabled,
+            discover_daemons,
+            onboarding_status,
+            suggested_udev_rules,
+            install_udev_rules,
+            show_test_pattern,
+            create_starter_config,
             should_show_service_prompt,
             increment_service_prompt_count,
             set_service_prompt_count,
@@ -1331,18 +1918,25 @@ pub fn run() {
             reload_keydeck,
             backup_config_directory,
             restore_config_directory,
+            export_config,
+            import_config,
+            fetch_icon,
+            recolor_icons,
             get_image_path,
             check_directory_exists,
             list_icons,
             ensure_default_icon_dir,
             list_applications,
             select_app_icon,
+            create_launcher_button,
             preview_icon_cleanup,
             execute_icon_cleanup,
+            clear_icon_cache,
             get_icon_data_url,
             upload_custom_icon,
             upload_custom_icon_bytes,
             stream_journal_logs,
+            watch_live_events,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
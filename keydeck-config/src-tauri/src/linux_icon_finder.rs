@@ -16,6 +16,15 @@ pub struct AppInfo {
     pub icon_path: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub icon_data_url: Option<String>,
+    /// `Exec` of the desktop file, without its field codes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exec: Option<String>,
+    /// `StartupWMClass` of the desktop file: the class of its windows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    /// Desktop file id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desktop: Option<String>,
 }
 
 // Cache for application list
@@ -31,6 +40,9 @@ pub fn find_applications() -> Result<Vec<AppInfo>, String> {
         }
     }
 
+    // Keep the icon cache within its limits before filling it
+    keydeck_types::prune_icon_cache(keydeck_types::IconCacheLimits::default());
+
     // Scan desktop files
     let apps = scan_desktop_files()?;
 
@@ -43,6 +55,11 @@ pub fn find_applications() -> Result<Vec<AppInfo>, String> {
     Ok(apps)
 }
 
+/// Forgets the application list, whose icons point into the icon cache
+pub fn forget_applications() {
+    *APP_CACHE.lock().unwrap() = None;
+}
+
 /// Scan .desktop file directories and parse them
 fn scan_desktop_files() -> Result<Vec<AppInfo>, String> {
     // Desktop file locations
@@ -75,11 +92,6 @@ fn scan_desktop_files() -> Result<Vec<AppInfo>, String> {
     let apps: Vec<AppInfo> = desktop_files
         .par_iter()
         .filter_map(|path| parse_desktop_file(path))
-        .map(|(name, icon_path)| AppInfo {
-            name,
-            icon_path,
-            icon_data_url: None,
-        })
         .collect();
 
     // Sort by name
@@ -89,14 +101,16 @@ fn scan_desktop_files() -> Result<Vec<AppInfo>, String> {
     Ok(apps)
 }
 
-/// Parse a .desktop file and extract name and icon path
-fn parse_desktop_file(path: &Path) -> Option<(String, String)> {
+/// Parse a .desktop file and extract name, icon path and how to launch it
+fn parse_desktop_file(path: &Path) -> Option<AppInfo> {
     let content = fs::read_to_string(path).ok()?;
 
     let mut in_desktop_entry = false;
     let mut app_type = None;
     let mut name = None;
     let mut icon = None;
+    let mut exec = None;
+    let mut class = None;
     let mut no_display = false;
     let mut hidden = false;
 
@@ -119,6 +133,8 @@ fn parse_desktop_file(path: &Path) -> Option<(String, String)> {
                 "Type" => app_type = Some(value.trim().to_string()),
                 "Name" => name = Some(value.trim().to_string()),
                 "Icon" => icon = Some(value.trim().to_string()),
+                "Exec" => exec = Some(strip_field_codes(value.trim())),
+                "StartupWMClass" => class = Some(value.trim().to_string()),
                 "NoDisplay" => no_display = value.trim() == "true",
                 "Hidden" => hidden = value.trim() == "true",
                 _ => {}
@@ -142,14 +158,34 @@ fn parse_desktop_file(path: &Path) -> Option<(String, String)> {
     // Resolve icon path
     let icon_path = resolve_icon_path(&icon_field)?;
 
-    Some((name, icon_path))
+    Some(AppInfo {
+        name,
+        icon_path,
+        icon_data_url: None,
+        exec: exec.filter(|exec| !exec.is_empty()),
+        class: class.filter(|class| !class.is_empty()),
+        desktop: path.file_stem().map(|id| id.to_string_lossy().to_string()),
+    })
+}
+
+/// Removes the `%f`, `%U`... field codes of an `Exec` line, which the launcher fills in
+/// with files to open; `%%` stands for a percent sign
+fn strip_field_codes(exec: &str) -> String {
+    let mut result = String::new();
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+        } else if let Some('%') = chars.next() {
+            result.push('%');
+        }
+    }
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
 /// Get or create cache directory for normalized icons
 fn get_cache_icons_dir() -> Result<PathBuf, String> {
-    let home =
-        std::env::var("HOME").map_err(|_| "HOME environment variable not set".to_string())?;
-    let cache_dir = PathBuf::from(home).join(".cache/keydeck/icons");
+    let cache_dir = keydeck_types::get_icon_cache_dir();
 
     if !cache_dir.exists() {
         fs::create_dir_all(&cache_dir)
@@ -163,29 +199,14 @@ fn get_cache_icons_dir() -> Result<PathBuf, String> {
 /// Processes ALL image formats, scales to max 256x256, and caches to ~/.cache/keydeck/icons/
 fn cache_and_normalize_icon(source_path: &Path) -> Result<PathBuf, String> {
     // Create cache directory
-    let cache_dir = get_cache_icons_dir()?;
+    get_cache_icons_dir()?;
 
     // Convert source path to safe filename: /usr/share/icons/audacity.xpm -> _usr_share_icons_audacity.xpm.png
-    let safe_name = source_path
-        .to_string_lossy()
-        .replace('/', "_")
-        .replace('\\', "_");
-    let cache_path = cache_dir.join(format!("{}.png", safe_name));
+    let cache_path = keydeck_types::icon_cache::cache_entry(source_path, "");
 
     // Check if cache is valid (exists and is newer than source)
-    if cache_path.exists() {
-        if let (Ok(source_meta), Ok(cache_meta)) =
-            (fs::metadata(source_path), fs::metadata(&cache_path))
-        {
-            if let (Ok(source_mtime), Ok(cache_mtime)) =
-                (source_meta.modified(), cache_meta.modified())
-            {
-                if cache_mtime >= source_mtime {
-                    // Cache is valid, return cached path
-                    return Ok(cache_path);
-                }
-            }
-        }
+    if keydeck_types::icon_cache::is_fresh(&cache_path, source_path) {
+        return Ok(cache_path);
     }
 
     // Cache miss or invalid - need to regenerate
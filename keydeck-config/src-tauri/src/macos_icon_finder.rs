@@ -23,6 +23,12 @@ pub struct AppInfo {
     pub icon_path: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub icon_data_url: Option<String>,
+    /// Command line that opens the bundle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exec: Option<String>,
+    /// Bundle identifier, which identifies its windows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
 }
 
 /// Cache for the application list to avoid repeated filesystem scanning.
@@ -42,11 +48,6 @@ fn app_search_dirs() -> Vec<PathBuf> {
     dirs
 }
 
-/// Directory where decoded PNG icons are cached.
-fn icon_cache_dir() -> PathBuf {
-    keydeck_types::get_config_dir().join(".app_icon_cache")
-}
-
 /// Find all installed applications, decoding each icon to a cached PNG.
 pub fn find_applications() -> Result<Vec<AppInfo>, String> {
     // Check cache first
@@ -57,8 +58,9 @@ pub fn find_applications() -> Result<Vec<AppInfo>, String> {
         }
     }
 
-    let cache_dir = icon_cache_dir();
-    let _ = fs::create_dir_all(&cache_dir);
+    // Keep the icon cache within its limits before filling it
+    keydeck_types::prune_icon_cache(keydeck_types::IconCacheLimits::default());
+    let _ = fs::create_dir_all(keydeck_types::get_icon_cache_dir());
 
     let mut apps: Vec<AppInfo> = Vec::new();
     let mut seen = HashSet::new();
@@ -81,11 +83,13 @@ pub fn find_applications() -> Result<Vec<AppInfo>, String> {
             }
 
             // Skip apps whose icon can't be located/decoded rather than failing.
-            if let Ok(png_path) = extract_app_icon_png(&path, &name, &cache_dir) {
+            if let Ok(png_path) = extract_app_icon_png(&path) {
                 apps.push(AppInfo {
                     name,
                     icon_path: png_path.to_string_lossy().into_owned(),
                     icon_data_url: None,
+                    exec: Some(format!("open -a \"{}\"", path.display())),
+                    class: bundle_identifier(&path),
                 });
             }
         }
@@ -97,6 +101,11 @@ pub fn find_applications() -> Result<Vec<AppInfo>, String> {
     Ok(apps)
 }
 
+/// Forgets the application list, whose icons point into the icon cache.
+pub fn forget_applications() {
+    *APP_CACHE.lock().unwrap() = None;
+}
+
 /// Copy a previously-decoded PNG icon into the keydeck icon directory,
 /// returning the stored filename.
 pub fn copy_app_icon(
@@ -149,6 +158,13 @@ fn app_display_name(app: &Path) -> String {
         .to_string()
 }
 
+/// Read `CFBundleIdentifier` from `Info.plist`.
+fn bundle_identifier(app: &Path) -> Option<String> {
+    let value = plist::Value::from_file(app.join("Contents/Info.plist")).ok()?;
+    let dict = value.as_dictionary()?;
+    Some(dict.get("CFBundleIdentifier")?.as_string()?.to_string())
+}
+
 /// Locate the `.icns` icon file inside a `.app` bundle.
 fn locate_icns(app: &Path) -> Result<PathBuf, String> {
     let resources = app.join("Contents/Resources");
@@ -184,16 +200,15 @@ fn locate_icns(app: &Path) -> Result<PathBuf, String> {
     Err(format!("No .icns icon found in {}", app.display()))
 }
 
-/// Decode the app's `.icns` icon to a PNG in the cache directory and return
-/// the PNG path. Reuses an already-decoded cache entry when present.
-fn extract_app_icon_png(app: &Path, name: &str, cache_dir: &Path) -> Result<PathBuf, String> {
-    let out = cache_dir.join(format!("{}.png", sanitize_filename(name)));
-    if out.exists() {
+/// Decode the app's `.icns` icon to a PNG in the icon cache and return
+/// the PNG path. Reuses the cache entry while it is newer than the `.icns`.
+fn extract_app_icon_png(app: &Path) -> Result<PathBuf, String> {
+    let icns_path = locate_icns(app)?;
+    let out = keydeck_types::icon_cache::cache_entry(&icns_path, "");
+    if keydeck_types::icon_cache::is_fresh(&out, &icns_path) {
         return Ok(out);
     }
 
-    let icns_path = locate_icns(app)?;
-
     let file = fs::File::open(&icns_path).map_err(|e| format!("Failed to open icns: {}", e))?;
     let family = icns::IconFamily::read(std::io::BufReader::new(file))
         .map_err(|e| format!("Failed to read icns: {}", e))?;
@@ -0,0 +1,227 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Backend of the first-run wizard: checks that the user may open the connected decks
+//! (udev rules on Linux), installs the rules when missing and writes a starter
+//! configuration. Finding the decks, drawing the test pattern and enabling autostart
+//! go through the keydeck binary, like the rest of the UI.
+
+use keydeck_types::{get_config_dir, KeyDeckConf};
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// Vendor id of the Elgato Stream Decks; the other decks are listed in the device files
+const ELGATO_VENDOR: u16 = 0x0fd9;
+
+/// Where the wizard installs its udev rules
+#[cfg(target_os = "linux")]
+const UDEV_RULES_PATH: &str = "/etc/udev/rules.d/70-keydeck.rules";
+
+/// A device node of a supported deck
+#[derive(Debug, Serialize)]
+pub struct DeckNode {
+    /// `VID:PID`, as `keydeck --list` shows it
+    pub id: String,
+    pub path: String,
+    /// Whether this user can open it for reading and writing
+    pub accessible: bool,
+}
+
+/// Whether keydeck may talk to the connected decks
+#[derive(Debug, Serialize)]
+pub struct PermissionCheck {
+    /// Every deck found can be opened
+    pub ok: bool,
+    pub nodes: Vec<DeckNode>,
+    /// Installed udev rule files that mention a supported vendor
+    pub udev_rules: Vec<String>,
+}
+
+/// Vendor ids of the supported decks: Elgato and those of the device files, which the
+/// daemon extracts to the config dir the first time it lists the devices
+fn supported_vendors() -> BTreeSet<u16> {
+    let mut vendors = BTreeSet::from([ELGATO_VENDOR]);
+    let dirs = [
+        get_config_dir().join("devices"),
+        "/usr/share/keydeck/devices".into(),
+    ];
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let vendor = std::fs::read_to_string(entry.path())
+                .ok()
+                .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+                .and_then(|device| {
+                    let id = device["hardware"]["vendor_id"].as_str()?.to_lowercase();
+                    u16::from_str_radix(id.trim_start_matches("0x"), 16).ok()
+                });
+            vendors.extend(vendor);
+        }
+    }
+    vendors
+}
+
+/// Finds the hidraw nodes of the supported decks and whether they can be opened
+#[cfg(target_os = "linux")]
+pub fn check_permissions() -> PermissionCheck {
+    let vendors = supported_vendors();
+    let mut nodes = vec![];
+    if let Ok(entries) = std::fs::read_dir("/sys/class/hidraw") {
+        for entry in entries.flatten() {
+            let uevent = std::fs::read_to_string(entry.path().join("device/uevent"));
+            // HID_ID=<bus>:<vendor>:<product>, each in hex
+            let Some((vendor, product)) = uevent.ok().and_then(|uevent| {
+                let id = uevent.lines().find_map(|l| l.strip_prefix("HID_ID="))?;
+                let mut parts = id.split(':').skip(1);
+                let vendor = u32::from_str_radix(parts.next()?, 16).ok()?;
+                let product = u32::from_str_radix(parts.next()?, 16).ok()?;
+                Some((vendor as u16, product as u16))
+            }) else {
+                continue;
+            };
+            if !vendors.contains(&vendor) {
+                continue;
+            }
+            let path = format!("/dev/{}", entry.file_name().to_string_lossy());
+            let accessible = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&path)
+                .is_ok();
+            nodes.push(DeckNode {
+                id: format!("{:04X}:{:04X}", vendor, product),
+                path,
+                accessible,
+            });
+        }
+    }
+    nodes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut udev_rules = vec![];
+    for dir in [
+        "/etc/udev/rules.d",
+        "/usr/lib/udev/rules.d",
+        "/lib/udev/rules.d",
+    ] {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let content = std::fs::read_to_string(entry.path())
+                .unwrap_or_default()
+                .to_lowercase();
+            let mentions_deck = vendors
+                .iter()
+                .any(|vendor| content.contains(&format!("\"{:04x}\"", vendor)));
+            if mentions_deck {
+                udev_rules.push(entry.path().to_string_lossy().to_string());
+            }
+        }
+    }
+
+    PermissionCheck {
+        ok: nodes.iter().all(|node| node.accessible),
+        nodes,
+        udev_rules,
+    }
+}
+
+/// Only Linux needs permissions to open the decks
+#[cfg(not(target_os = "linux"))]
+pub fn check_permissions() -> PermissionCheck {
+    PermissionCheck {
+        ok: true,
+        nodes: vec![],
+        udev_rules: vec![],
+    }
+}
+
+/// udev rules that let the user at the seat open every supported deck
+pub fn udev_rules() -> String {
+    let mut rules = String::from("# KeyDeck: let the logged-in user open the supported decks\n");
+    for vendor in supported_vendors() {
+        for subsystem in ["hidraw", "usb"] {
+            rules.push_str(&format!(
+                "SUBSYSTEM==\"{}\", ATTRS{{idVendor}}==\"{:04x}\", TAG+=\"uaccess\"\n",
+                subsystem, vendor
+            ));
+        }
+    }
+    rules
+}
+
+/// Installs [`udev_rules`] with administrator rights (through `pkexec`) and applies them
+/// to the decks already plugged in
+#[cfg(target_os = "linux")]
+pub fn install_udev_rules() -> Result<(), String> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let script = format!(
+        "cat > {} && udevadm control --reload-rules && udevadm trigger",
+        UDEV_RULES_PATH
+    );
+    let mut child = Command::new("pkexec")
+        .args(["sh", "-c", &script])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run pkexec: {}", e))?;
+    child
+        .stdin
+        .take()
+        .ok_or("Failed to write the udev rules")?
+        .write_all(udev_rules().as_bytes())
+        .map_err(|e| format!("Failed to write the udev rules: {}", e))?;
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run pkexec: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(match stderr.trim() {
+            "" => format!("Failed to install {}", UDEV_RULES_PATH),
+            message => message.to_string(),
+        })
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn install_udev_rules() -> Result<(), String> {
+    Err("udev rules are only needed on Linux".to_string())
+}
+
+/// A configuration to start from: a clock and a date on the `default` page group,
+/// shown by every device
+pub fn starter_config() -> KeyDeckConf {
+    let yaml = r#"
+default:
+  main_page: Main
+  Main:
+    button1:
+      text: "Key\nDeck"
+      background: "0x0E639C"
+    button2:
+      text: "${time:%H:%M}"
+    button3:
+      text: "${time:%a}\n${time:%d/%m}"
+"#;
+    serde_yaml_ng::from_str(yaml).expect("The starter configuration is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starter_config_has_a_main_page() {
+        let conf = starter_config();
+        let group = &conf.page_groups["default"];
+        assert_eq!(group.main_page.as_deref(), Some("Main"));
+        assert!(group.pages["Main"].buttons.contains_key("button2"));
+        assert!(udev_rules().contains("ATTRS{idVendor}==\"0fd9\""));
+    }
+}
@@ -2,17 +2,23 @@
 // Copyright (C) 2025 Panayotis Katsaloulis
 
 use std::sync::LazyLock;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
 /// Represents a Windows application with its name and icon path
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppInfo {
     pub name: String,
     pub icon_path: String, // Path to extracted .ico or .png file
+    /// Command line that starts the shortcut's target
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exec: Option<String>,
+    /// Process name of the target, which identifies its windows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
 }
 
 /// Cache for application list to avoid repeated filesystem scanning
@@ -154,9 +160,14 @@ fn parse_shortcut(lnk_path: &Path) -> Result<AppInfo, String> {
         .unwrap_or(&icon_path)
         .to_string();
 
+    let class = Path::new(&target_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string());
     Ok(AppInfo {
         name: app_name,
         icon_path,
+        exec: Some(format!("start \"\" \"{}\"", target_path)),
+        class,
     })
 }
 
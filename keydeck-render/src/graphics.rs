@@ -0,0 +1,896 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Colors, graphics and effects drawn on the keys
+
+use image::{Rgba, RgbaImage};
+use imageproc::drawing::{draw_filled_circle_mut, draw_filled_rect_mut};
+use imageproc::rect::Rect;
+use qrcode::{EcLevel, QrCode};
+use std::f32::consts::PI;
+
+/// Parse a color string into RGB components. Accepted formats:
+/// - "0xRRGGBB" and "0xAARRGGBB" (alpha first)
+/// - "#RGB", "#RRGGBB" and "#RRGGBBAA" (CSS, alpha last)
+/// - "rgb(r, g, b)", "rgba(r, g, b, a)", with 0-255 or percent channels and 0-1 or percent alpha
+/// - "hsl(h, s%, l%)" and "hsla(h, s%, l%, a)"
+/// - CSS color names, case-insensitive ("orange", "SteelBlue", ...)
+///
+/// Keys have no background to blend with, so translucent colors are premultiplied over black.
+pub fn parse_color(color: &str) -> Result<(u8, u8, u8), String> {
+    let color = color.trim();
+    let invalid = || format!("Invalid color format: {}", color);
+    let (r, g, b, a) = if let Some(hex) = color.strip_prefix("0x").filter(|h| h.is_ascii()) {
+        match hex.len() {
+            6 => {
+                let (r, g, b) = parse_hex_rgb(hex).ok_or_else(invalid)?;
+                (r, g, b, 255)
+            }
+            8 => {
+                let a = u8::from_str_radix(&hex[0..2], 16).map_err(|_| invalid())?;
+                let (r, g, b) = parse_hex_rgb(&hex[2..]).ok_or_else(invalid)?;
+                (r, g, b, a)
+            }
+            _ => return Err(invalid()),
+        }
+    } else if let Some(hex) = color.strip_prefix('#').filter(|h| h.is_ascii()) {
+        match hex.len() {
+            3 => {
+                let expanded: String = hex.chars().flat_map(|c| [c, c]).collect();
+                let (r, g, b) = parse_hex_rgb(&expanded).ok_or_else(invalid)?;
+                (r, g, b, 255)
+            }
+            6 => {
+                let (r, g, b) = parse_hex_rgb(hex).ok_or_else(invalid)?;
+                (r, g, b, 255)
+            }
+            8 => {
+                let (r, g, b) = parse_hex_rgb(&hex[0..6]).ok_or_else(invalid)?;
+                let a = u8::from_str_radix(&hex[6..8], 16).map_err(|_| invalid())?;
+                (r, g, b, a)
+            }
+            _ => return Err(invalid()),
+        }
+    } else if let Some((name, args)) = color.strip_suffix(')').and_then(|c| c.split_once('(')) {
+        let args: Vec<&str> = args.split(',').map(str::trim).collect();
+        let alpha = match args.get(3) {
+            Some(a) => (parse_fraction(a).ok_or_else(invalid)? * 255.0).round() as u8,
+            None => 255,
+        };
+        match (name.trim().to_ascii_lowercase().as_str(), args.len()) {
+            ("rgb", 3) | ("rgba", 4) => {
+                let channel = |c: &str| match c.strip_suffix('%') {
+                    Some(p) => p.trim().parse::<f32>().ok().map(|p| p * 2.55),
+                    None => c.parse::<f32>().ok(),
+                };
+                let mut rgb = [0u8; 3];
+                for (out, arg) in rgb.iter_mut().zip(&args) {
+                    *out = channel(arg).ok_or_else(invalid)?.round().clamp(0.0, 255.0) as u8;
+                }
+                (rgb[0], rgb[1], rgb[2], alpha)
+            }
+            ("hsl", 3) | ("hsla", 4) => {
+                let hue: f32 = args[0]
+                    .trim_end_matches("deg")
+                    .parse()
+                    .map_err(|_| invalid())?;
+                let percent = |c: &str| -> Option<f32> {
+                    let p: f32 = c.strip_suffix('%')?.trim().parse().ok()?;
+                    Some((p / 100.0).clamp(0.0, 1.0))
+                };
+                let saturation = percent(args[1]).ok_or_else(invalid)?;
+                let lightness = percent(args[2]).ok_or_else(invalid)?;
+                let (r, g, b) = hsl_to_rgb(hue, saturation, lightness);
+                (r, g, b, alpha)
+            }
+            _ => return Err(invalid()),
+        }
+    } else {
+        let (r, g, b) = CSS_COLORS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(color))
+            .map(|&(_, rgb)| ((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+            .ok_or_else(|| format!("Unknown color '{}'", color))?;
+        (r, g, b, 255)
+    };
+
+    let premultiply = |c: u8| (c as f32 * a as f32 / 255.0).round() as u8;
+    Ok((premultiply(r), premultiply(g), premultiply(b)))
+}
+
+fn parse_hex_rgb(hex: &str) -> Option<(u8, u8, u8)> {
+    if hex.len() != 6 {
+        return None;
+    }
+    let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+    let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+    let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+    Some((r, g, b))
+}
+
+/// Parses an alpha value given as "0.5" or "50%" into 0.0-1.0
+fn parse_fraction(value: &str) -> Option<f32> {
+    let fraction = match value.strip_suffix('%') {
+        Some(p) => p.trim().parse::<f32>().ok()? / 100.0,
+        None => value.parse::<f32>().ok()?,
+    };
+    Some(fraction.clamp(0.0, 1.0))
+}
+
+/// Converts hue (degrees), saturation and lightness (0.0-1.0) to RGB
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (u8, u8, u8) {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |c: f32| ((c + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (channel(r), channel(g), channel(b))
+}
+
+/// Names of the CSS colors [`parse_color`] accepts
+pub fn css_color_names<'a>() -> impl Iterator<Item = &'a str> {
+    CSS_COLORS.iter().map(|(name, _)| *name)
+}
+
+/// The CSS named colors (CSS Color Module Level 4), as 0xRRGGBB
+#[rustfmt::skip]
+const CSS_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xF0F8FF), ("antiquewhite", 0xFAEBD7), ("aqua", 0x00FFFF),
+    ("aquamarine", 0x7FFFD4), ("azure", 0xF0FFFF), ("beige", 0xF5F5DC),
+    ("bisque", 0xFFE4C4), ("black", 0x000000), ("blanchedalmond", 0xFFEBCD),
+    ("blue", 0x0000FF), ("blueviolet", 0x8A2BE2), ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887), ("cadetblue", 0x5F9EA0), ("chartreuse", 0x7FFF00),
+    ("chocolate", 0xD2691E), ("coral", 0xFF7F50), ("cornflowerblue", 0x6495ED),
+    ("cornsilk", 0xFFF8DC), ("crimson", 0xDC143C), ("cyan", 0x00FFFF),
+    ("darkblue", 0x00008B), ("darkcyan", 0x008B8B), ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9), ("darkgreen", 0x006400), ("darkgrey", 0xA9A9A9),
+    ("darkkhaki", 0xBDB76B), ("darkmagenta", 0x8B008B), ("darkolivegreen", 0x556B2F),
+    ("darkorange", 0xFF8C00), ("darkorchid", 0x9932CC), ("darkred", 0x8B0000),
+    ("darksalmon", 0xE9967A), ("darkseagreen", 0x8FBC8F), ("darkslateblue", 0x483D8B),
+    ("darkslategray", 0x2F4F4F), ("darkslategrey", 0x2F4F4F), ("darkturquoise", 0x00CED1),
+    ("darkviolet", 0x9400D3), ("deeppink", 0xFF1493), ("deepskyblue", 0x00BFFF),
+    ("dimgray", 0x696969), ("dimgrey", 0x696969), ("dodgerblue", 0x1E90FF),
+    ("firebrick", 0xB22222), ("floralwhite", 0xFFFAF0), ("forestgreen", 0x228B22),
+    ("fuchsia", 0xFF00FF), ("gainsboro", 0xDCDCDC), ("ghostwhite", 0xF8F8FF),
+    ("gold", 0xFFD700), ("goldenrod", 0xDAA520), ("gray", 0x808080),
+    ("green", 0x008000), ("greenyellow", 0xADFF2F), ("grey", 0x808080),
+    ("honeydew", 0xF0FFF0), ("hotpink", 0xFF69B4), ("indianred", 0xCD5C5C),
+    ("indigo", 0x4B0082), ("ivory", 0xFFFFF0), ("khaki", 0xF0E68C),
+    ("lavender", 0xE6E6FA), ("lavenderblush", 0xFFF0F5), ("lawngreen", 0x7CFC00),
+    ("lemonchiffon", 0xFFFACD), ("lightblue", 0xADD8E6), ("lightcoral", 0xF08080),
+    ("lightcyan", 0xE0FFFF), ("lightgoldenrodyellow", 0xFAFAD2), ("lightgray", 0xD3D3D3),
+    ("lightgreen", 0x90EE90), ("lightgrey", 0xD3D3D3), ("lightpink", 0xFFB6C1),
+    ("lightsalmon", 0xFFA07A), ("lightseagreen", 0x20B2AA), ("lightskyblue", 0x87CEFA),
+    ("lightslategray", 0x778899), ("lightslategrey", 0x778899), ("lightsteelblue", 0xB0C4DE),
+    ("lightyellow", 0xFFFFE0), ("lime", 0x00FF00), ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6), ("magenta", 0xFF00FF), ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66CDAA), ("mediumblue", 0x0000CD), ("mediumorchid", 0xBA55D3),
+    ("mediumpurple", 0x9370DB), ("mediumseagreen", 0x3CB371), ("mediumslateblue", 0x7B68EE),
+    ("mediumspringgreen", 0x00FA9A), ("mediumturquoise", 0x48D1CC), ("mediumvioletred", 0xC71585),
+    ("midnightblue", 0x191970), ("mintcream", 0xF5FFFA), ("mistyrose", 0xFFE4E1),
+    ("moccasin", 0xFFE4B5), ("navajowhite", 0xFFDEAD), ("navy", 0x000080),
+    ("oldlace", 0xFDF5E6), ("olive", 0x808000), ("olivedrab", 0x6B8E23),
+    ("orange", 0xFFA500), ("orangered", 0xFF4500), ("orchid", 0xDA70D6),
+    ("palegoldenrod", 0xEEE8AA), ("palegreen", 0x98FB98), ("paleturquoise", 0xAFEEEE),
+    ("palevioletred", 0xDB7093), ("papayawhip", 0xFFEFD5), ("peachpuff", 0xFFDAB9),
+    ("peru", 0xCD853F), ("pink", 0xFFC0CB), ("plum", 0xDDA0DD),
+    ("powderblue", 0xB0E0E6), ("purple", 0x800080), ("rebeccapurple", 0x663399),
+    ("red", 0xFF0000), ("rosybrown", 0xBC8F8F), ("royalblue", 0x4169E1),
+    ("saddlebrown", 0x8B4513), ("salmon", 0xFA8072), ("sandybrown", 0xF4A460),
+    ("seagreen", 0x2E8B57), ("seashell", 0xFFF5EE), ("sienna", 0xA0522D),
+    ("silver", 0xC0C0C0), ("skyblue", 0x87CEEB), ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090), ("slategrey", 0x708090), ("snow", 0xFFFAFA),
+    ("springgreen", 0x00FF7F), ("steelblue", 0x4682B4), ("tan", 0xD2B48C),
+    ("teal", 0x008080), ("thistle", 0xD8BFD8), ("tomato", 0xFF6347),
+    ("turquoise", 0x40E0D0), ("violet", 0xEE82EE), ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF), ("whitesmoke", 0xF5F5F5), ("yellow", 0xFFFF00),
+    ("yellowgreen", 0x9ACD32),
+];
+
+/// Interpolate between two colors based on a factor (0.0 = color1, 1.0 = color2)
+pub fn interpolate_color(color1: (u8, u8, u8), color2: (u8, u8, u8), factor: f32) -> (u8, u8, u8) {
+    let factor = factor.clamp(0.0, 1.0);
+    let r = (color1.0 as f32 + (color2.0 as f32 - color1.0 as f32) * factor) as u8;
+    let g = (color1.1 as f32 + (color2.1 as f32 - color1.1 as f32) * factor) as u8;
+    let b = (color1.2 as f32 + (color2.2 as f32 - color1.2 as f32) * factor) as u8;
+    (r, g, b)
+}
+
+/// Calculate color from a color map based on value percentage
+/// Color map format: [(threshold, color), ...] where threshold is in range [0, 100]
+/// Returns interpolated color with smooth transitions
+pub fn calculate_color_from_map(
+    value_percent: f32,
+    color_map: &[(f32, (u8, u8, u8))],
+) -> (u8, u8, u8) {
+    if color_map.is_empty() {
+        return (255, 255, 255); // Default to white
+    }
+
+    if color_map.len() == 1 {
+        return color_map[0].1;
+    }
+
+    // Find the two color stops to interpolate between
+    for i in 0..color_map.len() - 1 {
+        let (threshold1, color1) = color_map[i];
+        let (threshold2, color2) = color_map[i + 1];
+
+        if value_percent >= threshold1 && value_percent <= threshold2 {
+            // Interpolate between these two colors
+            let range = threshold2 - threshold1;
+            if range <= 0.0 {
+                return color1;
+            }
+            let factor = (value_percent - threshold1) / range;
+            return interpolate_color(color1, color2, factor);
+        }
+    }
+
+    // If value is below first threshold, use first color
+    if value_percent < color_map[0].0 {
+        return color_map[0].1;
+    }
+
+    // If value is above last threshold, use last color
+    color_map[color_map.len() - 1].1
+}
+
+/// Direction for bar rendering
+#[derive(Debug, Clone, Copy)]
+pub enum BarDirection {
+    LeftToRight,
+    RightToLeft,
+    TopToBottom,
+    BottomToTop,
+}
+
+/// Render a progress bar directly onto canvas with support for all four directions
+pub fn render_bar(
+    canvas: &mut RgbaImage,
+    x: i64,
+    y: i64,
+    value: f32,
+    range: (f32, f32),
+    width: u32,
+    height: u32,
+    color: (u8, u8, u8),
+    segments: Option<u32>,
+    direction: BarDirection,
+) {
+    // Calculate percentage
+    let (min, max) = range;
+    let value = value.max(min).min(max);
+    let percent = if max > min {
+        (value - min) / (max - min)
+    } else {
+        0.0
+    };
+
+    let color_rgba = Rgba([color.0, color.1, color.2, 255]);
+
+    match direction {
+        BarDirection::LeftToRight | BarDirection::RightToLeft => {
+            // Horizontal bar
+            if let Some(seg_count) = segments {
+                // Segmented bar
+                if seg_count > 0 {
+                    let segment_spacing = 2;
+                    let total_spacing = (seg_count - 1) * segment_spacing;
+                    let segment_width = (width - total_spacing) / seg_count;
+
+                    // Calculate remaining space and distribute as padding
+                    let used_width = seg_count * segment_width + total_spacing;
+                    let remaining = width - used_width;
+                    let offset_x = remaining / 2;
+
+                    let filled_segments = (percent * seg_count as f32).floor() as u32;
+
+                    for i in 0..filled_segments {
+                        let seg_x = if matches!(direction, BarDirection::LeftToRight) {
+                            // Fill from left
+                            x + offset_x as i64 + (i * (segment_width + segment_spacing)) as i64
+                        } else {
+                            // Fill from right
+                            x + (width - offset_x - ((i + 1) * (segment_width + segment_spacing)))
+                                as i64
+                        };
+
+                        draw_filled_rect_mut(
+                            canvas,
+                            Rect::at(seg_x as i32, y as i32).of_size(segment_width, height),
+                            color_rgba,
+                        );
+                    }
+                }
+            } else {
+                // Continuous bar
+                let filled_width = (width as f32 * percent) as u32;
+                if filled_width > 0 {
+                    let bar_x = if matches!(direction, BarDirection::LeftToRight) {
+                        x
+                    } else {
+                        x + (width - filled_width) as i64
+                    };
+
+                    draw_filled_rect_mut(
+                        canvas,
+                        Rect::at(bar_x as i32, y as i32).of_size(filled_width, height),
+                        color_rgba,
+                    );
+                }
+            }
+        }
+        BarDirection::TopToBottom | BarDirection::BottomToTop => {
+            // Vertical bar
+            if let Some(seg_count) = segments {
+                // Segmented bar
+                if seg_count > 0 {
+                    let segment_spacing = 2;
+                    let total_spacing = (seg_count - 1) * segment_spacing;
+                    let segment_height = (height - total_spacing) / seg_count;
+
+                    // Calculate remaining space and distribute as padding
+                    let used_height = seg_count * segment_height + total_spacing;
+                    let remaining = height - used_height;
+                    let offset_y = remaining / 2;
+
+                    let filled_segments = (percent * seg_count as f32).floor() as u32;
+
+                    for i in 0..filled_segments {
+                        let seg_y = if matches!(direction, BarDirection::BottomToTop) {
+                            // Fill from bottom
+                            y + (height - offset_y - ((i + 1) * (segment_height + segment_spacing)))
+                                as i64
+                        } else {
+                            // Fill from top
+                            y + offset_y as i64 + (i * (segment_height + segment_spacing)) as i64
+                        };
+
+                        draw_filled_rect_mut(
+                            canvas,
+                            Rect::at(x as i32, seg_y as i32).of_size(width, segment_height),
+                            color_rgba,
+                        );
+                    }
+                }
+            } else {
+                // Continuous bar
+                let filled_height = (height as f32 * percent) as u32;
+                if filled_height > 0 {
+                    let bar_y = if matches!(direction, BarDirection::BottomToTop) {
+                        y + (height - filled_height) as i64
+                    } else {
+                        y
+                    };
+
+                    draw_filled_rect_mut(
+                        canvas,
+                        Rect::at(x as i32, bar_y as i32).of_size(width, filled_height),
+                        color_rgba,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Render a circular gauge (arc from bottom going clockwise) directly onto canvas
+pub fn render_gauge(
+    canvas: &mut RgbaImage,
+    x: i64,
+    y: i64,
+    value: f32,
+    range: (f32, f32),
+    width: u32,
+    height: u32,
+    color: (u8, u8, u8),
+) {
+    // Calculate percentage
+    let (min, max) = range;
+    let value = value.max(min).min(max);
+    let percent = if max > min {
+        (value - min) / (max - min)
+    } else {
+        0.0
+    };
+
+    let color_rgba = Rgba([color.0, color.1, color.2, 255]);
+
+    // Calculate center and radius
+    let center_x = x + (width / 2) as i64;
+    let center_y = y + (height / 2) as i64;
+    let radius = (width.min(height) / 2).saturating_sub(5);
+
+    // Draw arc from 7:30 position (bottom-left) to 4:30 position (bottom-right) at 100%
+    let start_angle = 135.0 * PI / 180.0;
+    let arc_range = 270.0 * PI / 180.0;
+    let end_angle = start_angle + (arc_range * percent);
+
+    // Draw the arc with thick line (using multiple circles)
+    let thickness = (radius / 4).max(3);
+    let steps = (radius * 2) as i32;
+
+    for step in 0..steps {
+        let angle = start_angle + (end_angle - start_angle) * (step as f32 / steps as f32);
+        let px = center_x + (radius as f32 * angle.cos()) as i64;
+        let py = center_y + (radius as f32 * angle.sin()) as i64;
+
+        draw_filled_circle_mut(canvas, (px as i32, py as i32), thickness as i32, color_rgba);
+    }
+}
+
+/// Render a ring filled clockwise from the top by `fraction` (0.0-1.0), over a dimmed
+/// track of the full circle, centered on the canvas
+pub fn render_ring(canvas: &mut RgbaImage, fraction: f32, color: (u8, u8, u8)) {
+    let (width, height) = canvas.dimensions();
+    let center_x = (width / 2) as f32;
+    let center_y = (height / 2) as f32;
+    let radius = (width.min(height) / 2).saturating_sub(6) as f32;
+    let thickness = (radius / 12.0).max(2.0) as i32;
+    let track = interpolate_color(color, (0, 0, 0), 0.75);
+    let steps = (radius * 8.0) as i32;
+    let filled = (steps as f32 * fraction.clamp(0.0, 1.0)).round() as i32;
+
+    for step in 0..steps {
+        // Clockwise from 12 o'clock
+        let angle = -PI / 2.0 + 2.0 * PI * step as f32 / steps as f32;
+        let (r, g, b) = if step < filled { color } else { track };
+        let px = center_x + radius * angle.cos();
+        let py = center_y + radius * angle.sin();
+        draw_filled_circle_mut(
+            canvas,
+            (px as i32, py as i32),
+            thickness,
+            Rgba([r, g, b, 255]),
+        );
+    }
+}
+
+/// Render `data` as a QR code centered in the given area, dark modules in `color` on a
+/// white square with a one-module quiet zone. Returns an error if the data does not fit
+/// in a QR code or the area is too small for one pixel per module.
+pub fn render_qr_code(
+    canvas: &mut RgbaImage,
+    x: i64,
+    y: i64,
+    data: &str,
+    width: u32,
+    height: u32,
+    color: (u8, u8, u8),
+) -> Result<(), String> {
+    let code = QrCode::with_error_correction_level(data, EcLevel::L)
+        .map_err(|e| format!("Cannot encode QR code: {}", e))?;
+    let modules = code.width() as u32;
+    let module_size = width.min(height) / (modules + 2);
+    if module_size == 0 {
+        return Err(format!(
+            "QR code of {} modules does not fit in {}x{} pixels",
+            modules, width, height
+        ));
+    }
+
+    // Quiet zone and modules, centered in the area
+    let side = module_size * (modules + 2);
+    let left = x + ((width - side) / 2) as i64;
+    let top = y + ((height - side) / 2) as i64;
+    draw_filled_rect_mut(
+        canvas,
+        Rect::at(left as i32, top as i32).of_size(side, side),
+        Rgba([255, 255, 255, 255]),
+    );
+
+    let color_rgba = Rgba([color.0, color.1, color.2, 255]);
+    for (i, module) in code.to_colors().into_iter().enumerate() {
+        if module != qrcode::Color::Dark {
+            continue;
+        }
+        let col = (i as u32 % modules) + 1;
+        let row = (i as u32 / modules) + 1;
+        draw_filled_rect_mut(
+            canvas,
+            Rect::at(
+                (left + (col * module_size) as i64) as i32,
+                (top + (row * module_size) as i64) as i32,
+            )
+            .of_size(module_size, module_size),
+            color_rgba,
+        );
+    }
+    Ok(())
+}
+
+/// Render multiple bars with individual colors directly onto canvas
+/// Supports all 4 directions: bars can be arranged horizontally or vertically,
+/// and each bar can fill in any of the 4 directions
+pub fn render_multi_bar(
+    canvas: &mut RgbaImage,
+    x: i64,
+    y: i64,
+    values: &[f32],
+    range: (f32, f32),
+    width: u32,
+    height: u32,
+    colors: &[(u8, u8, u8)],
+    bar_spacing: u32,
+    segments: Option<u32>,
+    direction: BarDirection,
+) {
+    if values.is_empty() {
+        return;
+    }
+
+    let bar_count = values.len() as u32;
+
+    match direction {
+        BarDirection::LeftToRight | BarDirection::RightToLeft => {
+            // Horizontal bars stacked vertically
+            let total_spacing = (bar_count - 1) * bar_spacing;
+            let bar_height = (height - total_spacing) / bar_count;
+
+            for (i, &value) in values.iter().enumerate() {
+                let bar_y = y + (i as u32 * (bar_height + bar_spacing)) as i64;
+                let color = colors.get(i).copied().unwrap_or((255, 255, 255));
+                render_bar(
+                    canvas, x, bar_y, value, range, width, bar_height, color, segments, direction,
+                );
+            }
+        }
+        BarDirection::TopToBottom | BarDirection::BottomToTop => {
+            // Vertical bars side-by-side
+            let total_spacing = (bar_count - 1) * bar_spacing;
+            let bar_width = (width - total_spacing) / bar_count;
+
+            for (i, &value) in values.iter().enumerate() {
+                let bar_x = x + (i as u32 * (bar_width + bar_spacing)) as i64;
+                let color = colors.get(i).copied().unwrap_or((255, 255, 255));
+                render_bar(
+                    canvas, bar_x, y, value, range, bar_width, height, color, segments, direction,
+                );
+            }
+        }
+    }
+}
+
+/// Luma of a color, as used for grayscale and tint
+fn luma(pixel: &Rgba<u8>) -> f32 {
+    0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+}
+
+/// Removes all color from the canvas
+pub fn grayscale(canvas: &mut RgbaImage) {
+    for pixel in canvas.pixels_mut() {
+        let value = luma(pixel).round() as u8;
+        pixel[0] = value;
+        pixel[1] = value;
+        pixel[2] = value;
+    }
+}
+
+/// Recolors the canvas with a single color, keeping the brightness of every pixel
+pub fn tint(canvas: &mut RgbaImage, color: (u8, u8, u8)) {
+    let color = [color.0, color.1, color.2];
+    for pixel in canvas.pixels_mut() {
+        let value = luma(pixel) / 255.0;
+        for channel in 0..3 {
+            pixel[channel] = (value * color[channel] as f32).round() as u8;
+        }
+    }
+}
+
+/// How [`recolor`] draws an icon
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IconStyle {
+    /// The whole shape of the icon in the color
+    #[default]
+    Filled,
+    /// Only the edge of the shape, about a twentieth of the icon wide
+    Outline,
+    /// The color in the light and dark areas of the icon, as [`tint`]
+    Shaded,
+}
+
+impl std::str::FromStr for IconStyle {
+    type Err = String;
+
+    fn from_str(style: &str) -> Result<Self, Self::Err> {
+        match style.trim().to_ascii_lowercase().as_str() {
+            "filled" => Ok(IconStyle::Filled),
+            "outline" => Ok(IconStyle::Outline),
+            "shaded" => Ok(IconStyle::Shaded),
+            _ => Err(format!(
+                "Unknown icon style '{}', expected filled, outline or shaded",
+                style
+            )),
+        }
+    }
+}
+
+/// Redraws an icon in a single color, keeping its transparent background. The shape of
+/// the icon is made of its pixels that are at least half opaque, so an icon without
+/// transparency is a single square.
+pub fn recolor(image: &mut RgbaImage, color: (u8, u8, u8), style: IconStyle) {
+    match style {
+        IconStyle::Shaded => return tint(image, color),
+        IconStyle::Filled => {}
+        IconStyle::Outline => {
+            let (width, height) = image.dimensions();
+            let reach = (width.min(height) / 24).max(1) as i64;
+            let solid = |x: i64, y: i64| {
+                x >= 0
+                    && y >= 0
+                    && x < width as i64
+                    && y < height as i64
+                    && image.get_pixel(x as u32, y as u32)[3] >= 128
+            };
+            // Pixels with nothing but the shape around them are inside the outline
+            let inner: Vec<bool> = (0..height as i64)
+                .flat_map(|y| (0..width as i64).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    (-reach..=reach).all(|dy| (-reach..=reach).all(|dx| solid(x + dx, y + dy)))
+                })
+                .collect();
+            for (pixel, inner) in image.pixels_mut().zip(inner) {
+                if inner {
+                    pixel[3] = 0;
+                }
+            }
+        }
+    }
+    for pixel in image.pixels_mut() {
+        pixel[0] = color.0;
+        pixel[1] = color.1;
+        pixel[2] = color.2;
+    }
+}
+
+/// Scales the transparency of the canvas; 0.0 makes it fully transparent
+pub fn fade(canvas: &mut RgbaImage, opacity: f32) {
+    let opacity = opacity.clamp(0.0, 1.0);
+    for pixel in canvas.pixels_mut() {
+        pixel[3] = (pixel[3] as f32 * opacity).round() as u8;
+    }
+}
+
+/// Covers the canvas with a color at the given opacity, as if painted over it
+pub fn blend(canvas: &mut RgbaImage, color: (u8, u8, u8), opacity: f32) {
+    let opacity = opacity.clamp(0.0, 1.0);
+    let color = [color.0, color.1, color.2];
+    for pixel in canvas.pixels_mut() {
+        let below = pixel[3] as f32 / 255.0 * (1.0 - opacity);
+        let alpha = opacity + below;
+        if alpha <= 0.0 {
+            continue;
+        }
+        for channel in 0..3 {
+            let mixed = color[channel] as f32 * opacity + pixel[channel] as f32 * below;
+            pixel[channel] = (mixed / alpha).round() as u8;
+        }
+        pixel[3] = (alpha * 255.0).round() as u8;
+    }
+}
+
+/// Heatmap color of a usage ratio: blue for 0.0 through green to red for 1.0
+pub fn heat_color(ratio: f32) -> (u8, u8, u8) {
+    hsl_to_rgb(240.0 * (1.0 - ratio.clamp(0.0, 1.0)), 1.0, 0.5)
+}
+
+/// Paints a round badge in the top-right corner of the canvas, e.g. the pin indicator.
+/// Its diameter is a quarter of the canvas width.
+pub fn badge(canvas: &mut RgbaImage, color: (u8, u8, u8)) {
+    let (width, _) = canvas.dimensions();
+    let radius = width as f32 / 8.0;
+    let margin = radius / 2.0;
+    let (cx, cy) = (width as f32 - margin - radius, margin + radius);
+    for (x, y, pixel) in canvas.enumerate_pixels_mut() {
+        let distance = (x as f32 + 0.5 - cx).hypot(y as f32 + 0.5 - cy);
+        let coverage = (radius - distance + 0.5).clamp(0.0, 1.0);
+        if coverage <= 0.0 {
+            continue;
+        }
+        let below = pixel[3] as f32 / 255.0 * (1.0 - coverage);
+        let alpha = coverage + below;
+        for (channel, value) in [color.0, color.1, color.2].into_iter().enumerate() {
+            let mixed = value as f32 * coverage + pixel[channel] as f32 * below;
+            pixel[channel] = (mixed / alpha).round() as u8;
+        }
+        pixel[3] = (alpha * 255.0).round() as u8;
+    }
+}
+
+/// Rounds the corners of the canvas (making them transparent) and optionally draws a
+/// border of the given color and width along its edge. Edges are anti-aliased.
+pub fn frame(canvas: &mut RgbaImage, corner_radius: u32, border: Option<((u8, u8, u8), u32)>) {
+    let (width, height) = canvas.dimensions();
+    let half_w = width as f32 / 2.0;
+    let half_h = height as f32 / 2.0;
+    let radius = (corner_radius as f32).min(half_w).min(half_h);
+
+    for (x, y, pixel) in canvas.enumerate_pixels_mut() {
+        // Distance from the pixel center to the edge of the rounded rectangle, inwards
+        let qx = (x as f32 + 0.5 - half_w).abs() - (half_w - radius);
+        let qy = (y as f32 + 0.5 - half_h).abs() - (half_h - radius);
+        let outside = qx.max(0.0).hypot(qy.max(0.0)) + qx.max(qy).min(0.0) - radius;
+        let inside = -outside;
+
+        if let Some(((r, g, b), border_width)) = border {
+            let coverage = (border_width as f32 - inside + 0.5).clamp(0.0, 1.0);
+            if coverage > 0.0 {
+                let blend = |from: u8, to: u8| {
+                    (from as f32 + (to as f32 - from as f32) * coverage).round() as u8
+                };
+                let alpha = pixel[3];
+                *pixel = Rgba([
+                    blend(pixel[0], r),
+                    blend(pixel[1], g),
+                    blend(pixel[2], b),
+                    blend(alpha, 255),
+                ]);
+            }
+        }
+
+        let coverage = (inside + 0.5).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+}
+
+/// Night mode filter applied to the final key images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NightFilter {
+    /// Warm tint strength, 0.0 - 1.0
+    pub warmth: f32,
+    /// Gamma exponent; above 1.0 darkens mid-tones
+    pub gamma: f32,
+}
+
+impl NightFilter {
+    /// Tints the canvas warmer (less blue, slightly less green) and applies the gamma curve
+    pub fn apply(&self, canvas: &mut RgbaImage) {
+        let warmth = self.warmth.clamp(0.0, 1.0);
+        let gains = [1.0, 1.0 - 0.25 * warmth, 1.0 - 0.6 * warmth];
+        let gamma = if self.gamma > 0.0 { self.gamma } else { 1.0 };
+        let lut: Vec<[u8; 256]> = gains
+            .iter()
+            .map(|gain| {
+                let mut table = [0u8; 256];
+                for (v, out) in table.iter_mut().enumerate() {
+                    let scaled = (v as f32 / 255.0 * gain).powf(gamma);
+                    *out = (scaled * 255.0).round().clamp(0.0, 255.0) as u8;
+                }
+                table
+            })
+            .collect();
+        for pixel in canvas.pixels_mut() {
+            for channel in 0..3 {
+                pixel[channel] = lut[channel][pixel[channel] as usize];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_all_color_formats() {
+        assert_eq!(parse_color("0x3080FF"), Ok((0x30, 0x80, 0xFF)));
+        assert_eq!(parse_color("0x80FF0000"), Ok((128, 0, 0)));
+        assert_eq!(parse_color("#3080ff"), Ok((0x30, 0x80, 0xFF)));
+        assert_eq!(parse_color("#f80"), Ok((0xFF, 0x88, 0x00)));
+        assert_eq!(parse_color("rgb(255, 128, 0)"), Ok((255, 128, 0)));
+        assert_eq!(parse_color("rgba(255, 0, 0, 0.5)"), Ok((128, 0, 0)));
+        assert_eq!(parse_color("hsl(120, 100%, 50%)"), Ok((0, 255, 0)));
+        assert_eq!(parse_color("SteelBlue"), Ok((0x46, 0x82, 0xB4)));
+        assert!(parse_color("#12345").is_err());
+        assert!(parse_color("background").is_err());
+    }
+
+    #[test]
+    fn frame_rounds_corners_and_draws_border() {
+        let mut canvas = RgbaImage::from_pixel(40, 40, Rgba([0, 0, 255, 255]));
+        frame(&mut canvas, 10, Some(((255, 0, 0), 2)));
+        assert_eq!(canvas.get_pixel(0, 0)[3], 0);
+        assert_eq!(*canvas.get_pixel(20, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(*canvas.get_pixel(20, 20), Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn heatmap_blends_over_transparent_keys() {
+        assert_eq!(heat_color(0.0), (0, 0, 255));
+        assert_eq!(heat_color(1.0), (255, 0, 0));
+        let mut canvas = RgbaImage::from_pixel(2, 1, Rgba([0, 0, 0, 0]));
+        canvas.put_pixel(1, 0, Rgba([255, 255, 255, 255]));
+        blend(&mut canvas, (255, 0, 0), 0.5);
+        assert_eq!(*canvas.get_pixel(0, 0), Rgba([255, 0, 0, 128]));
+        assert_eq!(*canvas.get_pixel(1, 0), Rgba([255, 128, 128, 255]));
+    }
+
+    #[test]
+    fn badge_marks_top_right_corner() {
+        let mut canvas = RgbaImage::from_pixel(72, 72, Rgba([0, 0, 255, 255]));
+        badge(&mut canvas, (255, 0, 0));
+        assert_eq!(*canvas.get_pixel(58, 13), Rgba([255, 0, 0, 255]));
+        assert_eq!(*canvas.get_pixel(71, 0), Rgba([0, 0, 255, 255]));
+        assert_eq!(*canvas.get_pixel(13, 58), Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn bars_tolerate_ranges_out_of_order() {
+        let mut canvas = RgbaImage::from_pixel(20, 4, Rgba([0, 0, 0, 255]));
+        for range in [(100.0, 0.0), (f32::NAN, 100.0)] {
+            render_bar(
+                &mut canvas,
+                0,
+                0,
+                50.0,
+                range,
+                20,
+                4,
+                (255, 0, 0),
+                None,
+                BarDirection::LeftToRight,
+            );
+        }
+    }
+
+    #[test]
+    fn ring_fills_clockwise_from_the_top() {
+        let mut canvas = RgbaImage::from_pixel(72, 72, Rgba([0, 0, 0, 255]));
+        render_ring(&mut canvas, 0.25, (255, 0, 0));
+        // A quarter reaches from 12 to 3 o'clock; the rest is the dimmed track
+        assert_eq!(canvas.get_pixel(57, 15)[0], 255);
+        let track = canvas.get_pixel(6, 36)[0];
+        assert!(track > 0 && track < 255);
+        assert_eq!(canvas.get_pixel(36, 36)[0], 0);
+    }
+
+    #[test]
+    fn icons_are_recolored_in_one_style() {
+        // An opaque red square in the middle of a transparent icon
+        let mut icon = RgbaImage::new(48, 48);
+        draw_filled_rect_mut(
+            &mut icon,
+            Rect::at(8, 8).of_size(32, 32),
+            Rgba([255, 0, 0, 255]),
+        );
+
+        let mut filled = icon.clone();
+        recolor(&mut filled, (255, 255, 255), IconStyle::Filled);
+        assert_eq!(*filled.get_pixel(24, 24), Rgba([255, 255, 255, 255]));
+        assert_eq!(filled.get_pixel(2, 2)[3], 0);
+
+        let mut outline = icon.clone();
+        recolor(&mut outline, (255, 255, 255), "Outline".parse().unwrap());
+        assert_eq!(*outline.get_pixel(8, 24), Rgba([255, 255, 255, 255]));
+        assert_eq!(outline.get_pixel(24, 24)[3], 0);
+        assert_eq!(outline.get_pixel(2, 2)[3], 0);
+
+        let mut shaded = icon;
+        recolor(&mut shaded, (0, 0, 255), IconStyle::Shaded);
+        assert_eq!(*shaded.get_pixel(24, 24), Rgba([0, 0, 76, 255]));
+
+        assert!("dotted".parse::<IconStyle>().is_err());
+    }
+
+    #[test]
+    fn qr_code_fits_or_fails() {
+        let mut canvas = RgbaImage::from_pixel(72, 72, Rgba([0, 0, 255, 255]));
+        render_qr_code(&mut canvas, 0, 0, "https://example.com", 72, 72, (0, 0, 0)).unwrap();
+        // Version 2 (25 modules) plus quiet zone at 2 pixels per module, centered
+        assert_eq!(*canvas.get_pixel(10, 10), Rgba([255, 255, 255, 255]));
+        assert_eq!(*canvas.get_pixel(11, 11), Rgba([0, 0, 0, 255]));
+        assert_eq!(*canvas.get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+        assert!(
+            render_qr_code(&mut canvas, 0, 0, "https://example.com", 20, 20, (0, 0, 0)).is_err()
+        );
+    }
+}
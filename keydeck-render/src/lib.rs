@@ -0,0 +1,365 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Rendering of KeyDeck buttons into images
+//!
+//! The daemon, previews and tests all draw keys through [`render_button`], from a
+//! [`ButtonSpec`] that is already resolved: colors parsed, dynamic parameters substituted
+//! and icons loaded. The [`text`] and [`graphics`] modules draw the single parts.
+//!
+//! ```
+//! use keydeck_render::{render_button, ButtonSpec, Label};
+//!
+//! let spec = ButtonSpec {
+//!     background: Some((32, 32, 32)),
+//!     label: Some(Label::new("Mute")),
+//!     ..Default::default()
+//! };
+//! let image = render_button(&spec, (72, 72));
+//! assert_eq!(image.dimensions(), (72, 72));
+//! ```
+
+use graphics::BarDirection;
+use image::imageops::overlay;
+use image::{Rgba, RgbaImage};
+use std::sync::Arc;
+
+pub mod graphics;
+pub mod shortcodes;
+pub mod text;
+
+/// Opacity of a button while it counts down its cooldown
+pub const COUNTDOWN_OPACITY: f32 = 0.35;
+
+/// How strongly the heatmap color covers a key
+pub const HEATMAP_OPACITY: f32 = 0.6;
+
+/// A color as red, green and blue
+pub type Rgb = (u8, u8, u8);
+
+/// Everything drawn on a key, in the order it is drawn
+#[derive(Debug, Clone, Default)]
+pub struct ButtonSpec {
+    /// Fill of the key, transparent without one
+    pub background: Option<Rgb>,
+    /// Part of a wallpaper, shown when the key has no background
+    pub wallpaper: Option<RgbaImage>,
+    /// Icon images, bottom layer first
+    pub icons: Vec<IconLayer>,
+    /// Graphics, first one drawn first
+    pub graphics: Vec<Graphic>,
+    /// Ring of a timer, filled by the fraction (0.0-1.0) in the color
+    pub ring: Option<(f32, Rgb)>,
+    pub label: Option<Label>,
+    pub effects: Effects,
+    /// Seconds left of a cooldown, counted down over the dimmed key
+    pub countdown: Option<u64>,
+    /// Usage of the key, from 0.0 (blue) to 1.0 (red)
+    pub heat: Option<f32>,
+    /// Color of a badge in the top right corner
+    pub badge: Option<Rgb>,
+}
+
+/// An icon image, drawn at its own size
+#[derive(Debug, Clone)]
+pub struct IconLayer {
+    pub image: Arc<RgbaImage>,
+    /// Top left corner of the image; centered when none
+    pub position: Option<(i64, i64)>,
+}
+
+/// Where a graphic is drawn on the key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Area {
+    pub x: i64,
+    pub y: i64,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A graphic with its values and colors
+#[derive(Debug, Clone)]
+pub enum Graphic {
+    Bar {
+        area: Area,
+        value: f32,
+        range: (f32, f32),
+        color: Rgb,
+        segments: Option<u32>,
+        direction: BarDirection,
+    },
+    Gauge {
+        area: Area,
+        value: f32,
+        range: (f32, f32),
+        color: Rgb,
+    },
+    /// Bars side by side, each in its own color
+    MultiBar {
+        area: Area,
+        values: Vec<f32>,
+        range: (f32, f32),
+        colors: Vec<Rgb>,
+        spacing: u32,
+        segments: Option<u32>,
+        direction: BarDirection,
+    },
+    QrCode {
+        area: Area,
+        data: String,
+        color: Rgb,
+    },
+}
+
+/// Text of a key, sized to fit unless it scrolls
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Label {
+    pub text: String,
+    /// Largest size of the text; it is made smaller to fit
+    pub font_size: Option<f32>,
+    /// Color of the text; white when none
+    pub color: Option<Rgb>,
+    pub outline: Option<Rgb>,
+    /// Font family; the default font of the system when none or not installed
+    pub font: Option<String>,
+    /// Pixels the text has scrolled by, to show it as a single scrolling line
+    pub marquee: Option<f64>,
+}
+
+impl Label {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Changes applied to the finished key
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Effects {
+    pub grayscale: bool,
+    pub tint: Option<Rgb>,
+    pub opacity: Option<f32>,
+    pub corner_radius: u32,
+    /// Color and width of a border
+    pub border: Option<(Rgb, u32)>,
+}
+
+/// Renders a key of `size` pixels. Parts that cannot be drawn are left out; use
+/// [`render_into`] to learn about them.
+pub fn render_button(spec: &ButtonSpec, size: (u32, u32)) -> RgbaImage {
+    let mut canvas = RgbaImage::new(size.0, size.1);
+    render_into(spec, &mut canvas);
+    canvas
+}
+
+/// Renders a key over the whole of `canvas`, e.g. the previous image of the key to spare
+/// an allocation. Returns the problems met, such as data too long for a QR code; the rest
+/// of the key is drawn anyway.
+pub fn render_into(spec: &ButtonSpec, canvas: &mut RgbaImage) -> Vec<String> {
+    let mut problems = Vec::new();
+    let (width, height) = canvas.dimensions();
+
+    // Transparent when no background; flattened to black for JPEG/BMP
+    let fill = spec
+        .background
+        .map_or(Rgba([0, 0, 0, 0]), |(r, g, b)| Rgba([r, g, b, 255]));
+    canvas.pixels_mut().for_each(|pixel| *pixel = fill);
+    if let (None, Some(wallpaper)) = (spec.background, &spec.wallpaper) {
+        overlay(canvas, wallpaper, 0, 0);
+    }
+
+    for icon in &spec.icons {
+        let (x, y) = icon.position.unwrap_or((
+            (width as i64 - icon.image.width() as i64) / 2,
+            (height as i64 - icon.image.height() as i64) / 2,
+        ));
+        overlay(canvas, &*icon.image, x, y);
+    }
+
+    for graphic in &spec.graphics {
+        if let Err(e) = draw_graphic(canvas, graphic) {
+            problems.push(e);
+        }
+    }
+
+    // The ring of a timer goes under its time
+    if let Some((fraction, color)) = spec.ring {
+        graphics::render_ring(canvas, fraction, color);
+    }
+
+    if let Some(label) = &spec.label {
+        let color = label.color.map(|(r, g, b)| Rgba([r, g, b, 255]));
+        let outline = label.outline.map(|(r, g, b)| [r, g, b]);
+        let font = label.font.as_deref();
+        match label.marquee {
+            Some(scroll) => text::render_marquee_on_canvas(
+                canvas,
+                &label.text,
+                label.font_size,
+                color,
+                outline,
+                font,
+                scroll,
+            ),
+            None => text::render_text_on_canvas_with_font(
+                canvas,
+                &label.text,
+                label.font_size,
+                color,
+                outline,
+                font,
+            ),
+        }
+    }
+
+    apply_effects(canvas, &spec.effects);
+
+    if let Some(seconds) = spec.countdown {
+        graphics::fade(canvas, COUNTDOWN_OPACITY);
+        text::render_text_on_canvas(canvas, &seconds.to_string(), None, None, Some([0, 0, 0]));
+    }
+    if let Some(heat) = spec.heat {
+        graphics::blend(canvas, graphics::heat_color(heat), HEATMAP_OPACITY);
+    }
+    if let Some(color) = spec.badge {
+        graphics::badge(canvas, color);
+    }
+    problems
+}
+
+fn draw_graphic(canvas: &mut RgbaImage, graphic: &Graphic) -> Result<(), String> {
+    match graphic {
+        Graphic::Bar {
+            area,
+            value,
+            range,
+            color,
+            segments,
+            direction,
+        } => graphics::render_bar(
+            canvas,
+            area.x,
+            area.y,
+            *value,
+            *range,
+            area.width,
+            area.height,
+            *color,
+            *segments,
+            *direction,
+        ),
+        Graphic::Gauge {
+            area,
+            value,
+            range,
+            color,
+        } => graphics::render_gauge(
+            canvas,
+            area.x,
+            area.y,
+            *value,
+            *range,
+            area.width,
+            area.height,
+            *color,
+        ),
+        Graphic::MultiBar {
+            area,
+            values,
+            range,
+            colors,
+            spacing,
+            segments,
+            direction,
+        } => graphics::render_multi_bar(
+            canvas,
+            area.x,
+            area.y,
+            values,
+            *range,
+            area.width,
+            area.height,
+            colors,
+            *spacing,
+            *segments,
+            *direction,
+        ),
+        Graphic::QrCode { area, data, color } => {
+            return graphics::render_qr_code(
+                canvas,
+                area.x,
+                area.y,
+                data,
+                area.width,
+                area.height,
+                *color,
+            )
+        }
+    }
+    Ok(())
+}
+
+fn apply_effects(canvas: &mut RgbaImage, effects: &Effects) {
+    if effects.grayscale {
+        graphics::grayscale(canvas);
+    }
+    if let Some(color) = effects.tint {
+        graphics::tint(canvas, color);
+    }
+    if let Some(opacity) = effects.opacity {
+        graphics::fade(canvas, opacity);
+    }
+    if effects.corner_radius > 0 || effects.border.is_some() {
+        graphics::frame(canvas, effects.corner_radius, effects.border);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_drawn_bottom_to_top() {
+        let icon = RgbaImage::from_pixel(10, 10, Rgba([0, 255, 0, 255]));
+        let spec = ButtonSpec {
+            background: Some((0, 0, 255)),
+            wallpaper: Some(RgbaImage::from_pixel(72, 72, Rgba([255, 255, 0, 255]))),
+            icons: vec![IconLayer {
+                image: Arc::new(icon),
+                position: None,
+            }],
+            badge: Some((255, 0, 0)),
+            ..Default::default()
+        };
+        let image = render_button(&spec, (72, 72));
+        // The background hides the wallpaper, the icon is centered and the badge on top
+        assert_eq!(*image.get_pixel(2, 70), Rgba([0, 0, 255, 255]));
+        assert_eq!(*image.get_pixel(36, 36), Rgba([0, 255, 0, 255]));
+        assert_eq!(*image.get_pixel(58, 13), Rgba([255, 0, 0, 255]));
+
+        // A canvas drawn over again shows nothing of what it showed before
+        let mut canvas = image;
+        let spec = ButtonSpec {
+            graphics: vec![Graphic::QrCode {
+                area: Area {
+                    x: 0,
+                    y: 0,
+                    width: 10,
+                    height: 10,
+                },
+                data: "https://example.com".to_string(),
+                color: (0, 0, 0),
+            }],
+            effects: Effects {
+                grayscale: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let problems = render_into(&spec, &mut canvas);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(*canvas.get_pixel(36, 36), Rgba([0, 0, 0, 0]));
+    }
+}
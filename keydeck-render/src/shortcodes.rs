@@ -0,0 +1,252 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Emoji shortcodes such as `:rocket:` in key texts, so configurations don't need the raw
+//! emoji that some editors and terminals mangle.
+//!
+//! The names are the common ones of GitHub and Slack. A shortcode that is not in the
+//! table stays as it is written, so times like `12:30:45` are left alone.
+
+use std::borrow::Cow;
+
+/// Shortcode names and their emoji, sorted by name
+const SHORTCODES: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("-1", "👎"),
+    ("100", "💯"),
+    ("alarm_clock", "⏰"),
+    ("angry", "😠"),
+    ("apple", "🍎"),
+    ("arrow_down", "⬇️"),
+    ("arrow_left", "⬅️"),
+    ("arrow_right", "➡️"),
+    ("arrow_up", "⬆️"),
+    ("arrows_counterclockwise", "🔄"),
+    ("art", "🎨"),
+    ("baby", "👶"),
+    ("bank", "🏦"),
+    ("battery", "🔋"),
+    ("beer", "🍺"),
+    ("bell", "🔔"),
+    ("bike", "🚲"),
+    ("birthday", "🎂"),
+    ("blush", "😊"),
+    ("bomb", "💣"),
+    ("book", "📖"),
+    ("books", "📚"),
+    ("brain", "🧠"),
+    ("briefcase", "💼"),
+    ("bug", "🐛"),
+    ("bulb", "💡"),
+    ("bus", "🚌"),
+    ("cake", "🍰"),
+    ("calendar", "📆"),
+    ("camera", "📷"),
+    ("car", "🚗"),
+    ("cat", "🐱"),
+    ("chart_with_upwards_trend", "📈"),
+    ("check", "✔️"),
+    ("checkered_flag", "🏁"),
+    ("clapper", "🎬"),
+    ("clipboard", "📋"),
+    ("clock", "🕒"),
+    ("cloud", "☁️"),
+    ("coffee", "☕"),
+    ("computer", "💻"),
+    ("cool", "🆒"),
+    ("crown", "👑"),
+    ("cry", "😢"),
+    ("desktop_computer", "🖥️"),
+    ("dog", "🐶"),
+    ("door", "🚪"),
+    ("email", "📧"),
+    ("envelope", "✉️"),
+    ("eyes", "👀"),
+    ("file_folder", "📁"),
+    ("film_strip", "🎞️"),
+    ("fire", "🔥"),
+    ("floppy_disk", "💾"),
+    ("gear", "⚙️"),
+    ("ghost", "👻"),
+    ("gift", "🎁"),
+    ("globe_with_meridians", "🌐"),
+    ("green_heart", "💚"),
+    ("grin", "😁"),
+    ("hammer", "🔨"),
+    ("hammer_and_wrench", "🛠️"),
+    ("hash", "#️⃣"),
+    ("headphones", "🎧"),
+    ("heart", "❤️"),
+    ("heavy_check_mark", "✔️"),
+    ("heavy_minus_sign", "➖"),
+    ("heavy_plus_sign", "➕"),
+    ("hourglass", "⌛"),
+    ("house", "🏠"),
+    ("information_source", "ℹ️"),
+    ("joy", "😂"),
+    ("key", "🔑"),
+    ("keyboard", "⌨️"),
+    ("laptop", "💻"),
+    ("laughing", "😆"),
+    ("link", "🔗"),
+    ("lock", "🔒"),
+    ("loud_sound", "🔊"),
+    ("loudspeaker", "📢"),
+    ("mag", "🔍"),
+    ("mailbox", "📫"),
+    ("memo", "📝"),
+    ("microphone", "🎤"),
+    ("money_with_wings", "💸"),
+    ("moon", "🌙"),
+    ("mouse", "🐭"),
+    ("movie_camera", "🎥"),
+    ("muscle", "💪"),
+    ("musical_note", "🎵"),
+    ("mute", "🔇"),
+    ("new", "🆕"),
+    ("no_entry", "⛔"),
+    ("no_entry_sign", "🚫"),
+    ("notes", "🎶"),
+    ("ok", "🆗"),
+    ("ok_hand", "👌"),
+    ("package", "📦"),
+    ("paperclip", "📎"),
+    ("pause_button", "⏸️"),
+    ("pencil", "📝"),
+    ("pencil2", "✏️"),
+    ("phone", "☎️"),
+    ("pizza", "🍕"),
+    ("play_or_pause_button", "⏯️"),
+    ("point_down", "👇"),
+    ("point_left", "👈"),
+    ("point_right", "👉"),
+    ("point_up", "☝️"),
+    ("pray", "🙏"),
+    ("printer", "🖨️"),
+    ("pushpin", "📌"),
+    ("question", "❓"),
+    ("radio", "📻"),
+    ("rainbow", "🌈"),
+    ("record_button", "⏺️"),
+    ("recycle", "♻️"),
+    ("red_circle", "🔴"),
+    ("repeat", "🔁"),
+    ("rewind", "⏪"),
+    ("robot", "🤖"),
+    ("rocket", "🚀"),
+    ("rotating_light", "🚨"),
+    ("satellite", "📡"),
+    ("scissors", "✂️"),
+    ("see_no_evil", "🙈"),
+    ("shield", "🛡️"),
+    ("shopping_cart", "🛒"),
+    ("skull", "💀"),
+    ("sleeping", "😴"),
+    ("smile", "😄"),
+    ("smiley", "😃"),
+    ("snowflake", "❄️"),
+    ("sound", "🔉"),
+    ("sparkles", "✨"),
+    ("speaker", "🔈"),
+    ("speech_balloon", "💬"),
+    ("star", "⭐"),
+    ("stop_button", "⏹️"),
+    ("stopwatch", "⏱️"),
+    ("sun_with_face", "🌞"),
+    ("sunglasses", "😎"),
+    ("sunny", "☀️"),
+    ("tada", "🎉"),
+    ("telephone_receiver", "📞"),
+    ("thinking", "🤔"),
+    ("thumbsdown", "👎"),
+    ("thumbsup", "👍"),
+    ("timer_clock", "⏲️"),
+    ("toolbox", "🧰"),
+    ("trash", "🗑️"),
+    ("trophy", "🏆"),
+    ("tv", "📺"),
+    ("umbrella", "☔"),
+    ("unlock", "🔓"),
+    ("video_camera", "📹"),
+    ("video_game", "🎮"),
+    ("warning", "⚠️"),
+    ("watch", "⌚"),
+    ("wave", "👋"),
+    ("white_check_mark", "✅"),
+    ("wink", "😉"),
+    ("world_map", "🗺️"),
+    ("wrench", "🔧"),
+    ("x", "❌"),
+    ("zap", "⚡"),
+    ("zzz", "💤"),
+];
+
+/// The emoji of the shortcode `name`, written without its colons
+pub fn emoji(name: &str) -> Option<&'static str> {
+    SHORTCODES
+        .binary_search_by(|(code, _)| (*code).cmp(name))
+        .ok()
+        .map(|index| SHORTCODES[index].1)
+}
+
+/// `text` with its known shortcodes replaced by their emoji
+pub fn expand_shortcodes(text: &str) -> Cow<'_, str> {
+    if !text.contains(':') {
+        return Cow::Borrowed(text);
+    }
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut changed = false;
+    while let Some(start) = rest.find(':') {
+        let after = &rest[start + 1..];
+        let found = after
+            .find(':')
+            .map(|end| &after[..end])
+            .filter(|name| is_shortcode_name(name))
+            .and_then(|name| Some((name, emoji(name)?)));
+        match found {
+            Some((name, emoji)) => {
+                expanded.push_str(&rest[..start]);
+                expanded.push_str(emoji);
+                rest = &after[name.len() + 1..];
+                changed = true;
+            }
+            None => {
+                // The colon may still open the next shortcode, as in `12::rocket:`
+                expanded.push_str(&rest[..=start]);
+                rest = after;
+            }
+        }
+    }
+    if !changed {
+        return Cow::Borrowed(text);
+    }
+    expanded.push_str(rest);
+    Cow::Owned(expanded)
+}
+
+fn is_shortcode_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '+' | '-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcodes_are_expanded_and_the_rest_is_left_alone() {
+        assert!(SHORTCODES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(expand_shortcodes("Launch :rocket:"), "Launch 🚀");
+        assert_eq!(expand_shortcodes(":+1::tada:!"), "👍🎉!");
+        assert_eq!(expand_shortcodes("12:30:45 :zzz:"), "12:30:45 💤");
+        assert_eq!(expand_shortcodes("ratio 1::fire:"), "ratio 1:🔥");
+        assert_eq!(
+            expand_shortcodes(":no_such_code: :Rocket:"),
+            ":no_such_code: :Rocket:"
+        );
+        assert!(matches!(expand_shortcodes("a: b :c"), Cow::Borrowed(_)));
+    }
+}
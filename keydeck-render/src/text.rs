@@ -0,0 +1,680 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Text of the keys, laid out and shaped with the fonts of the system
+
+use cosmic_text::{
+    Align, Attrs, Buffer, Color, Family, FontSystem, Metrics, Shaping, SwashCache, Wrap,
+};
+use image::{Rgba, RgbaImage};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+/// Padding around text when auto-sizing (percentage of image dimension)
+const AUTO_SIZE_PADDING: f32 = 0.1;
+
+/// Line spacing as multiple of font size (baseline-to-baseline)
+const LINE_SPACING_FACTOR: f32 = 1.3;
+
+/// Default maximum font size when no user preference is specified
+const DEFAULT_FONT_SIZE: f32 = 28.0;
+
+/// Marquee text only scrolls when fitting it would need a smaller font than this
+const MARQUEE_MIN_FONT_SIZE: f32 = 14.0;
+
+/// Gap between the end of scrolling text and its next repetition, in font sizes
+const MARQUEE_GAP_FACTOR: f32 = 2.0;
+
+/// Drawn instead of an emoji when no font has it
+const MISSING_EMOJI: char = '□';
+
+/// Cache for the detected emoji font
+static EMOJI_FONT: OnceLock<EmojiFont> = OnceLock::new();
+
+/// Emoji are drawn with the emoji font, unless `emoji: false` is configured
+static EMOJI_SUBSTITUTION: AtomicBool = AtomicBool::new(true);
+
+// Thread-local FontSystem - initialized once per thread (main event loop thread)
+thread_local! {
+    static FONT_SYSTEM: RefCell<FontSystem> = RefCell::new(FontSystem::new());
+}
+
+/// Get platform-specific color emoji font names in order of preference
+fn get_emoji_font_candidates() -> &'static [&'static str] {
+    #[cfg(target_os = "windows")]
+    return &["Segoe UI Emoji", "Noto Color Emoji"];
+
+    #[cfg(target_os = "macos")]
+    return &["Apple Color Emoji", "Noto Color Emoji"];
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    return &[
+        "Noto Color Emoji",
+        "Twitter Color Emoji",
+        "Twemoji",
+        "JoyPixels",
+        "OpenMoji",
+        "Blobmoji",
+    ];
+}
+
+/// Fonts with emoji outlines, drawn in the text color, for systems without a color
+/// emoji font
+const MONOCHROME_EMOJI_FONTS: &[&str] = &["Noto Emoji", "Symbola", "Segoe UI Symbol"];
+
+/// The font emoji are drawn with
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmojiFont {
+    Color(String),
+    /// Outlines in the text color
+    Monochrome(String),
+    /// No font has emoji: those beyond the symbols of the text font are drawn as boxes
+    Missing,
+}
+
+/// Find the first available font from the candidates list
+fn find_available_font(font_system: &FontSystem, candidates: &[&str]) -> Option<String> {
+    let db = font_system.db();
+
+    for candidate in candidates {
+        for face in db.faces() {
+            for family in &face.families {
+                if family.0.eq_ignore_ascii_case(candidate) {
+                    return Some(candidate.to_string());
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// The best emoji font installed
+fn find_emoji_font(font_system: &FontSystem) -> EmojiFont {
+    if let Some(name) = find_available_font(font_system, get_emoji_font_candidates()) {
+        EmojiFont::Color(name)
+    } else if let Some(name) = find_available_font(font_system, MONOCHROME_EMOJI_FONTS) {
+        EmojiFont::Monochrome(name)
+    } else {
+        EmojiFont::Missing
+    }
+}
+
+/// Get the emoji font (cached after first lookup)
+fn get_emoji_font(font_system: &FontSystem) -> &'static EmojiFont {
+    EMOJI_FONT.get_or_init(|| {
+        let font = find_emoji_font(font_system);
+        match &font {
+            EmojiFont::Color(_) => {}
+            EmojiFont::Monochrome(name) => {
+                eprintln!(
+                    "WARNING: No color emoji font found, drawing emoji with {}",
+                    name
+                )
+            }
+            EmojiFont::Missing => {
+                eprintln!(
+                    "WARNING: No emoji font found, drawing emoji as boxes; install Noto Color Emoji"
+                )
+            }
+        }
+        font
+    })
+}
+
+/// The font emoji are drawn with on this system
+pub fn emoji_font() -> EmojiFont {
+    FONT_SYSTEM.with(|fs| find_emoji_font(&fs.borrow()))
+}
+
+/// Switches drawing emoji with the emoji font on or off (`emoji` setting)
+pub fn set_emoji_substitution(enabled: bool) {
+    EMOJI_SUBSTITUTION.store(enabled, Ordering::Relaxed);
+}
+
+/// Check if a character is an emoji
+pub fn is_emoji(c: char) -> bool {
+    matches!(c as u32,
+        0x1F300..=0x1F9FF | // Misc Symbols and Pictographs, Emoticons, etc.
+        0x2600..=0x26FF |   // Misc symbols
+        0x2700..=0x27BF |   // Dingbats
+        0xFE00..=0xFE0F |   // Variation Selectors
+        0x1F1E6..=0x1F1FF   // Regional Indicator Symbols (flags)
+    )
+}
+
+/// Emoji no text font is expected to have: the pictographs and flags beyond the symbols
+/// of the Basic Multilingual Plane
+fn is_pictograph(c: char) -> bool {
+    c as u32 >= 0x1F000
+}
+
+/// The text to shape: without an emoji font, pictographs become boxes, and the joiners
+/// and variation selectors that only combine them are dropped
+fn substitute_missing_emoji<'a>(text: &'a str, font_system: &FontSystem) -> Cow<'a, str> {
+    if !EMOJI_SUBSTITUTION.load(Ordering::Relaxed)
+        || !text.chars().any(is_pictograph)
+        || *get_emoji_font(font_system) != EmojiFont::Missing
+    {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(box_pictographs(text))
+}
+
+fn box_pictographs(text: &str) -> String {
+    text.chars()
+        .filter(|c| !matches!(*c as u32, 0x200D | 0xFE00..=0xFE0F))
+        .map(|c| if is_pictograph(c) { MISSING_EMOJI } else { c })
+        .collect()
+}
+
+/// Base text attributes: the requested font family, or the system default
+fn base_attrs(family: Option<&str>) -> Attrs<'_> {
+    match family {
+        Some(name) => Attrs::new().family(Family::Name(name)),
+        None => Attrs::new(),
+    }
+}
+
+/// Build rich text spans with proper font family for emoji
+fn build_rich_text_spans<'a>(
+    text: &'a str,
+    font_system: &FontSystem,
+    family: Option<&'a str>,
+) -> Vec<(&'a str, Attrs<'a>)> {
+    let mut spans = Vec::new();
+    let emoji_font = match get_emoji_font(font_system) {
+        _ if !EMOJI_SUBSTITUTION.load(Ordering::Relaxed) => None,
+        EmojiFont::Color(name) | EmojiFont::Monochrome(name) => Some(name.as_str()),
+        // The symbols left are drawn with the text font, the pictographs are boxes
+        EmojiFont::Missing => None,
+    };
+    let emoji_attrs = || match emoji_font {
+        Some(name) => Attrs::new().family(Family::Name(name)),
+        None => base_attrs(family),
+    };
+
+    let mut current_start = 0;
+    let mut in_emoji = false;
+    let mut emoji_start = 0;
+
+    for (i, c) in text.char_indices() {
+        let is_emoji_char = is_emoji(c);
+
+        if is_emoji_char && !in_emoji {
+            if i > current_start {
+                spans.push((&text[current_start..i], base_attrs(family)));
+            }
+            in_emoji = true;
+            emoji_start = i;
+        } else if !is_emoji_char && in_emoji {
+            spans.push((&text[emoji_start..i], emoji_attrs()));
+            in_emoji = false;
+            current_start = i;
+        }
+    }
+
+    // Handle final section
+    let text_len = text.len();
+    if in_emoji {
+        spans.push((&text[emoji_start..text_len], emoji_attrs()));
+    } else if current_start < text_len {
+        spans.push((&text[current_start..text_len], base_attrs(family)));
+    }
+
+    if spans.is_empty() {
+        spans.push((text, base_attrs(family)));
+    }
+
+    spans
+}
+
+/// Calculate optimal font size using binary search
+/// Works for both single-line and multi-line text
+fn calculate_optimal_font_size(
+    font_system: &mut FontSystem,
+    lines: &[&str],
+    width: u32,
+    height: u32,
+    preferred_size: f32,
+    family: Option<&str>,
+) -> f32 {
+    let target_width = width as f32 * (1.0 - AUTO_SIZE_PADDING);
+    let target_height = height as f32 * (1.0 - AUTO_SIZE_PADDING);
+
+    // Find the longest line (measured at an arbitrary test size)
+    let test_size = 16.0;
+    let test_metrics = Metrics::new(test_size, test_size * LINE_SPACING_FACTOR);
+
+    let mut longest_line = lines[0];
+    let mut max_width = 0.0f32;
+
+    for line in lines.iter() {
+        let mut buffer = Buffer::new(font_system, test_metrics);
+        buffer.set_wrap(font_system, Wrap::None);
+        buffer.set_size(font_system, Some(width as f32), None);
+
+        let spans = build_rich_text_spans(line, font_system, family);
+        buffer.set_rich_text(
+            font_system,
+            spans,
+            &base_attrs(family),
+            Shaping::Advanced,
+            Some(Align::Center),
+        );
+        buffer.shape_until_scroll(font_system, false);
+
+        for run in buffer.layout_runs() {
+            if run.line_w > max_width {
+                max_width = run.line_w;
+                longest_line = line;
+            }
+        }
+    }
+
+    // Binary search for optimal font size using only the longest line
+    // Start from user's preferred size (or default if none)
+    let mut min_size = 6.0;
+    let mut max_size = preferred_size;
+    let mut best_size = min_size;
+
+    while max_size - min_size > 0.5 {
+        let test_size = (min_size + max_size) / 2.0;
+        let line_height = test_size * LINE_SPACING_FACTOR;
+        let total_height_needed = lines.len() as f32 * line_height;
+
+        // Check if total height fits
+        if total_height_needed > target_height {
+            max_size = test_size;
+            continue;
+        }
+
+        // Check if the longest line fits horizontally
+        let metrics = Metrics::new(test_size, line_height);
+        let mut buffer = Buffer::new(font_system, metrics);
+        buffer.set_wrap(font_system, Wrap::None);
+        buffer.set_size(font_system, Some(target_width), None);
+
+        let spans = build_rich_text_spans(longest_line, font_system, family);
+        buffer.set_rich_text(
+            font_system,
+            spans,
+            &base_attrs(family),
+            Shaping::Advanced,
+            Some(Align::Center),
+        );
+        buffer.shape_until_scroll(font_system, false);
+
+        let mut line_width = 0.0f32;
+        for run in buffer.layout_runs() {
+            if run.line_w > line_width {
+                line_width = run.line_w;
+            }
+        }
+
+        if line_width <= target_width {
+            best_size = test_size;
+            min_size = test_size;
+        } else {
+            max_size = test_size;
+        }
+    }
+
+    best_size
+}
+
+/// Render text directly onto a canvas
+pub fn render_text_on_canvas(
+    canvas: &mut RgbaImage,
+    text: &str,
+    font_size: Option<f32>,
+    text_color: Option<Rgba<u8>>,
+    outline_color: Option<[u8; 3]>,
+) {
+    render_text_on_canvas_with_font(canvas, text, font_size, text_color, outline_color, None);
+}
+
+/// Render text directly onto a canvas using the given font family.
+/// Falls back to the system default font when `family` is `None` or not installed.
+pub fn render_text_on_canvas_with_font(
+    canvas: &mut RgbaImage,
+    text: &str,
+    font_size: Option<f32>,
+    text_color: Option<Rgba<u8>>,
+    outline_color: Option<[u8; 3]>,
+    family: Option<&str>,
+) {
+    FONT_SYSTEM.with(|fs| {
+        let mut font_system = fs.borrow_mut();
+        render_text_on_canvas_internal(
+            canvas,
+            text,
+            font_size,
+            text_color,
+            outline_color,
+            family,
+            &mut font_system,
+        );
+    });
+}
+
+/// Internal rendering function that works with a FontSystem reference
+fn render_text_on_canvas_internal(
+    canvas: &mut RgbaImage,
+    text: &str,
+    font_size: Option<f32>,
+    text_color: Option<Rgba<u8>>,
+    outline_color: Option<[u8; 3]>,
+    family: Option<&str>,
+    font_system: &mut FontSystem,
+) {
+    let width = canvas.width();
+    let height = canvas.height();
+    let text = substitute_missing_emoji(text, font_system);
+
+    // Split text into lines
+    let lines: Vec<&str> = text.split('\n').collect();
+
+    // Determine final font size - always run auto-scaling
+    // Use user's font size as preferred (or default if not specified)
+    let preferred_size = font_size.unwrap_or(DEFAULT_FONT_SIZE);
+    let final_font_size =
+        calculate_optimal_font_size(font_system, &lines, width, height, preferred_size, family);
+
+    // Calculate line height and total block height
+    let line_height = final_font_size * LINE_SPACING_FACTOR;
+    let total_block_height = lines.len() as f32 * line_height;
+
+    // Center the block vertically
+    let mut y_offset = ((height as f32 - total_block_height) / 2.0).max(0.0);
+
+    // Render each line
+    for line in lines.iter() {
+        let line_canvas_height = (line_height.ceil() as u32).min(height);
+        let mut line_canvas = RgbaImage::new(width, line_canvas_height);
+
+        // Render the line onto its own canvas
+        render_line_on_canvas(
+            &mut line_canvas,
+            line,
+            final_font_size,
+            text_color,
+            outline_color,
+            family,
+            font_system,
+        );
+
+        // Composite onto main canvas
+        let dst_y_start = y_offset as u32;
+        for y in 0..line_canvas_height {
+            let dst_y = dst_y_start + y;
+            if dst_y >= height {
+                break;
+            }
+
+            for x in 0..width {
+                let src = line_canvas.get_pixel(x, y);
+                let dst = canvas.get_pixel_mut(x, dst_y);
+
+                // Alpha blend
+                let alpha = src[3] as f32 / 255.0;
+                let inv_alpha = 1.0 - alpha;
+                dst[0] = ((src[0] as f32 * alpha + dst[0] as f32 * inv_alpha) as u8).min(255);
+                dst[1] = ((src[1] as f32 * alpha + dst[1] as f32 * inv_alpha) as u8).min(255);
+                dst[2] = ((src[2] as f32 * alpha + dst[2] as f32 * inv_alpha) as u8).min(255);
+                dst[3] = ((src[3] as f32 + dst[3] as f32 * inv_alpha) as u8).min(255);
+            }
+        }
+
+        y_offset += line_height;
+    }
+}
+
+/// Render a single line of text onto a canvas
+fn render_line_on_canvas(
+    canvas: &mut RgbaImage,
+    text: &str,
+    font_size: f32,
+    text_color: Option<Rgba<u8>>,
+    outline_color: Option<[u8; 3]>,
+    family: Option<&str>,
+    font_system: &mut FontSystem,
+) {
+    let width = canvas.width();
+    let height = canvas.height();
+
+    // Create metrics and buffer
+    let line_height = font_size * LINE_SPACING_FACTOR;
+    let metrics = Metrics::new(font_size, line_height);
+    let mut buffer = Buffer::new(font_system, metrics);
+
+    buffer.set_wrap(font_system, Wrap::None);
+    buffer.set_size(font_system, Some(width as f32), Some(height as f32));
+
+    // Set text with emoji-aware rich text and center alignment
+    let spans = build_rich_text_spans(text, font_system, family);
+    buffer.set_rich_text(
+        font_system,
+        spans,
+        &base_attrs(family),
+        Shaping::Advanced,
+        Some(Align::Center),
+    );
+    buffer.shape_until_scroll(font_system, false);
+
+    // Calculate vertical centering
+    let layout_runs: Vec<_> = buffer.layout_runs().collect();
+    let total_height = if let Some(last_run) = layout_runs.last() {
+        last_run.line_top + last_run.line_height
+    } else {
+        0.0
+    };
+
+    let y_offset = ((height as f32 - total_height) / 2.0).max(0.0);
+
+    draw_buffer(
+        canvas,
+        &mut buffer,
+        &[(0.0, y_offset)],
+        text_color,
+        outline_color,
+        font_system,
+    );
+}
+
+/// Draws a shaped buffer onto the canvas once per (x, y) offset, outline first
+fn draw_buffer(
+    canvas: &mut RgbaImage,
+    buffer: &mut Buffer,
+    offsets: &[(f32, f32)],
+    text_color: Option<Rgba<u8>>,
+    outline_color: Option<[u8; 3]>,
+    font_system: &mut FontSystem,
+) {
+    // Text color (default to white)
+    let text_color = text_color.unwrap_or(Rgba([255u8, 255u8, 255u8, 255u8]));
+    let cosmic_color = Color::rgba(text_color[0], text_color[1], text_color[2], text_color[3]);
+
+    let mut swash_cache = SwashCache::new();
+
+    for &(x_offset, y_offset) in offsets {
+        // Draw outline if specified
+        if let Some(outline_rgb) = outline_color {
+            let outline_color = Color::rgba(outline_rgb[0], outline_rgb[1], outline_rgb[2], 255);
+            let outline_offsets = [(0.0, -1.0), (0.0, 1.0), (-1.0, 0.0), (1.0, 0.0)];
+
+            for &(dx, dy) in &outline_offsets {
+                buffer.draw(
+                    font_system,
+                    &mut swash_cache,
+                    outline_color,
+                    |x, y, w, h, color| {
+                        let final_x = (x as f32 + x_offset + dx) as i32;
+                        let final_y = (y as f32 + y_offset + dy) as i32;
+                        draw_glyph(canvas, final_x, final_y, w, h, color);
+                    },
+                );
+            }
+        }
+
+        // Draw main text
+        buffer.draw(
+            font_system,
+            &mut swash_cache,
+            cosmic_color,
+            |x, y, w, h, color| {
+                let final_x = (x as f32 + x_offset) as i32;
+                let final_y = (y as f32 + y_offset) as i32;
+                draw_glyph(canvas, final_x, final_y, w, h, color);
+            },
+        );
+    }
+}
+
+/// Tells whether text only fits a `width`x`height` key below a readable font size, or
+/// below `min_size` when given, in which case it should be rendered as a marquee instead
+pub fn needs_marquee(
+    width: u32,
+    height: u32,
+    text: &str,
+    font_size: Option<f32>,
+    family: Option<&str>,
+    min_size: Option<f32>,
+) -> bool {
+    FONT_SYSTEM.with(|fs| {
+        let mut font_system = fs.borrow_mut();
+        let text = substitute_missing_emoji(text, &font_system);
+        let lines: Vec<&str> = text.split('\n').collect();
+        let preferred_size = font_size.unwrap_or(DEFAULT_FONT_SIZE);
+        let fitting_size = calculate_optimal_font_size(
+            &mut font_system,
+            &lines,
+            width,
+            height,
+            preferred_size,
+            family,
+        );
+        fitting_size < min_size.unwrap_or(MARQUEE_MIN_FONT_SIZE.min(preferred_size))
+    })
+}
+
+/// Renders text as a single line scrolling right to left, `scroll` pixels into its loop.
+/// Multiple lines are joined into one.
+pub fn render_marquee_on_canvas(
+    canvas: &mut RgbaImage,
+    text: &str,
+    font_size: Option<f32>,
+    text_color: Option<Rgba<u8>>,
+    outline_color: Option<[u8; 3]>,
+    family: Option<&str>,
+    scroll: f64,
+) {
+    FONT_SYSTEM.with(|fs| {
+        let mut font_system = fs.borrow_mut();
+        let font_system = &mut *font_system;
+        let width = canvas.width();
+        let height = canvas.height();
+        let text = substitute_missing_emoji(text, font_system);
+        let lines: Vec<&str> = text.split('\n').collect();
+        let preferred_size = font_size.unwrap_or(DEFAULT_FONT_SIZE);
+
+        // One line at the preferred size, as far as the key height allows
+        let line = lines.join("   ");
+        let size = preferred_size
+            .min(height as f32 * (1.0 - AUTO_SIZE_PADDING) / LINE_SPACING_FACTOR)
+            .max(MARQUEE_MIN_FONT_SIZE.min(preferred_size));
+        let line_height = size * LINE_SPACING_FACTOR;
+        let mut buffer = Buffer::new(font_system, Metrics::new(size, line_height));
+        buffer.set_wrap(font_system, Wrap::None);
+        buffer.set_size(font_system, None, Some(height as f32));
+        let spans = build_rich_text_spans(&line, font_system, family);
+        buffer.set_rich_text(
+            font_system,
+            spans,
+            &base_attrs(family),
+            Shaping::Advanced,
+            Some(Align::Left),
+        );
+        buffer.shape_until_scroll(font_system, false);
+
+        let line_width = buffer
+            .layout_runs()
+            .map(|run| run.line_w)
+            .fold(0.0f32, f32::max);
+        let cycle = line_width + size * MARQUEE_GAP_FACTOR;
+        // Start just inside the right edge, then keep looping
+        let x = width as f32 * (1.0 - AUTO_SIZE_PADDING) - scroll.rem_euclid(cycle as f64) as f32;
+        let y = ((height as f32 - line_height) / 2.0).max(0.0);
+        let offsets = [(x, y), (x + cycle, y)];
+        draw_buffer(
+            canvas,
+            &mut buffer,
+            &offsets,
+            text_color,
+            outline_color,
+            font_system,
+        );
+    })
+}
+
+/// Helper function to draw a single glyph onto the canvas
+fn draw_glyph(canvas: &mut RgbaImage, x: i32, y: i32, w: u32, h: u32, color: Color) {
+    let canvas_width = canvas.width() as i32;
+    let canvas_height = canvas.height() as i32;
+
+    for row in 0..h {
+        for col in 0..w {
+            let px = x + col as i32;
+            let py = y + row as i32;
+
+            if px < 0 || py < 0 || px >= canvas_width || py >= canvas_height {
+                continue;
+            }
+
+            let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+
+            // Alpha compositing
+            let alpha = color.a() as f32 / 255.0;
+            let inv_alpha = 1.0 - alpha;
+
+            pixel[0] = ((color.r() as f32 * alpha + pixel[0] as f32 * inv_alpha) as u8).min(255);
+            pixel[1] = ((color.g() as f32 * alpha + pixel[1] as f32 * inv_alpha) as u8).min(255);
+            pixel[2] = ((color.b() as f32 * alpha + pixel[2] as f32 * inv_alpha) as u8).min(255);
+            pixel[3] = ((color.a() as f32 + pixel[3] as f32 * inv_alpha) as u8).min(255);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_emoji_become_boxes() {
+        // A family joined by zero width joiners is one box per person
+        assert_eq!(
+            box_pictographs("Hi 👋 \u{1F468}\u{200D}\u{1F469}"),
+            "Hi □ □□"
+        );
+        // Symbols most text fonts have are kept, with their variation selector dropped
+        assert_eq!(box_pictographs("✓ Done ☀\u{FE0F}"), "✓ Done ☀");
+    }
+
+    #[test]
+    fn only_text_too_long_for_the_key_scrolls() {
+        let long = "Supercalifragilisticexpialidocious";
+        assert!(!needs_marquee(72, 72, "Play", None, None, None));
+        assert!(needs_marquee(72, 72, long, None, None, None));
+
+        let frame = |scroll| {
+            let mut canvas = RgbaImage::new(72, 72);
+            let white = Some(Rgba([255, 255, 255, 255]));
+            render_marquee_on_canvas(&mut canvas, long, None, white, None, None, scroll);
+            canvas
+        };
+        assert_ne!(frame(0.0), frame(12.0));
+        assert!(frame(0.0).pixels().any(|pixel| pixel[3] > 0));
+    }
+}
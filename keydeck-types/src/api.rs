@@ -0,0 +1,564 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! The payloads of the daemon's external interfaces, versioned.
+//!
+//! The configuration UI, scripts and other programs talk to the daemon through the
+//! control socket, the `keydeck --watch` stream and the web deck. They use these types
+//! rather than the daemon's internal events, which change from release to release.
+//!
+//! Within one [`API_VERSION`], payloads only gain optional fields and new kinds of
+//! events and commands:
+//!
+//! - unknown fields are ignored, and an unknown event reads as [`Event::Unknown`]
+//! - a JSON payload without its `"v"` is of version 1
+//! - commands are still accepted as the plain text lines of the first daemons
+//!   (`setvar mode quiet`), besides JSON (`{"v":1,"command":"set_var","key":"mode",...}`)
+//!
+//! A change that would break a client raises the version. A client asks for the version
+//! it speaks (`watch 2`), and the daemon keeps answering every version down to
+//! [`MIN_API_VERSION`]; a client that names none gets version 1.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::time::Duration;
+
+/// The version of the payloads the daemon sends by default and newest it understands
+pub const API_VERSION: u32 = 1;
+
+/// The oldest version the daemon still answers
+pub const MIN_API_VERSION: u32 = 1;
+
+fn first_version() -> u32 {
+    1
+}
+
+/// A payload along with the version of the API it belongs to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    #[serde(default = "first_version")]
+    pub v: u32,
+    #[serde(flatten)]
+    pub payload: T,
+}
+
+impl<T> Versioned<T> {
+    pub fn new(payload: T) -> Self {
+        Versioned {
+            v: API_VERSION,
+            payload,
+        }
+    }
+}
+
+/// Path of the control socket: `$XDG_RUNTIME_DIR/keydeck.sock`, or a per-user name
+/// in the system temp dir when `XDG_RUNTIME_DIR` is unset.
+pub fn control_socket_path() -> PathBuf {
+    if let Ok(dir) = std::env::var("XDG_RUNTIME_DIR") {
+        if !dir.is_empty() {
+            return PathBuf::from(dir).join("keydeck.sock");
+        }
+    }
+    let user = std::env::var("USER").unwrap_or_else(|_| "user".to_string());
+    std::env::temp_dir().join(format!("keydeck-{}.sock", user))
+}
+
+/// Sends a command that is answered to the running daemon, and returns the answer.
+/// Fails with [`std::io::ErrorKind::NotFound`] or `ConnectionRefused` when no daemon
+/// runs, and with `TimedOut` or `WouldBlock` when it does not answer within `timeout`.
+#[cfg(unix)]
+pub fn request(command: &Command, timeout: Duration) -> std::io::Result<String> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(control_socket_path())?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.write_all(command.to_line().as_bytes())?;
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer)?;
+    Ok(answer.trim_end().to_string())
+}
+
+/// Sends a command to the running daemon, and returns the answer of the commands that
+/// are answered (see [`Command::is_answered`]). Fails like [`request`].
+#[cfg(unix)]
+pub fn send(command: &Command, timeout: Duration) -> std::io::Result<Option<String>> {
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    if command.is_answered() {
+        return request(command, timeout).map(Some);
+    }
+    let mut stream = UnixStream::connect(control_socket_path())?;
+    stream.write_all(command.to_line().as_bytes())?;
+    Ok(None)
+}
+
+/// The version to speak with a client asking for `requested`, or why there is none
+pub fn negotiate(requested: Option<u32>) -> Result<u32, String> {
+    match requested {
+        None => Ok(first_version()),
+        Some(v) if (MIN_API_VERSION..=API_VERSION).contains(&v) => Ok(v),
+        Some(v) => Err(format!(
+            "API version {} is not supported, this daemon speaks {} to {}",
+            v, MIN_API_VERSION, API_VERSION
+        )),
+    }
+}
+
+/// Something a watcher is told about; buttons are numbered as in the configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    ButtonDown {
+        sn: String,
+        page: Option<String>,
+        button: u8,
+    },
+    ButtonUp {
+        sn: String,
+        page: Option<String>,
+        button: u8,
+    },
+    Page {
+        sn: String,
+        page: String,
+    },
+    /// Key images of a refresh that could not be written even after retries
+    KeyImagesFailed {
+        sn: String,
+        buttons: Vec<u8>,
+        /// Images of the refresh that were written
+        written: usize,
+        /// Whether the failed keys were cleared instead of keeping their old images
+        cleared: bool,
+        error: String,
+    },
+    /// An event of a newer daemon than this client
+    #[serde(other)]
+    Unknown,
+}
+
+impl Event {
+    /// The version that introduced this kind of event; watchers of older versions are
+    /// not sent it
+    pub fn since(&self) -> u32 {
+        1
+    }
+
+    /// The JSON line telling a watcher of `version` about this event, if it may be sent
+    pub fn to_line(&self, version: u32) -> Option<String> {
+        if self.since() > version || *self == Event::Unknown {
+            return None;
+        }
+        let versioned = Versioned {
+            v: version,
+            payload: self,
+        };
+        serde_json::to_string(&versioned)
+            .ok()
+            .map(|json| json + "\n")
+    }
+
+    /// Reads a line of the `watch` stream, of any version
+    pub fn from_line(line: &str) -> Option<Event> {
+        serde_json::from_str::<Versioned<Event>>(line)
+            .ok()
+            .map(|versioned| versioned.payload)
+    }
+}
+
+/// A request to the daemon on its control socket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    /// Sets a context variable, or clears it without a value
+    SetVar {
+        key: String,
+        #[serde(default)]
+        value: Option<String>,
+    },
+    Hotkey {
+        combination: String,
+    },
+    /// Acts as if the window of `class` and `title` got the focus, until the next real
+    /// focus change
+    Focus {
+        class: String,
+        #[serde(default)]
+        title: String,
+    },
+    Reload,
+    /// Answered with the last `count` (default 20) history entries
+    History {
+        #[serde(default)]
+        count: Option<usize>,
+    },
+    /// Answered with the usage statistics
+    Stats,
+    /// Answered with the outcome of the last configuration load
+    ReloadStatus,
+    /// Answered with the [`ApiInfo`] of the daemon
+    Version,
+    /// Answered with the [`DaemonStatus`]
+    Status,
+    /// Turns the connection into a stream of [`Event`] lines of that version
+    Watch {
+        #[serde(default)]
+        version: Option<u32>,
+    },
+    TestPattern {
+        #[serde(default)]
+        sn: Option<String>,
+    },
+    Diagnostics {
+        #[serde(default)]
+        sn: Option<String>,
+    },
+    /// Answered with the bench reports of the pages redrawn
+    Bench {
+        #[serde(default)]
+        sn: Option<String>,
+    },
+    /// Answered with the reports of the configured services
+    Services,
+    /// Changes the fields of a button, as `GROUP/PAGE/BUTTON`; answered with `ok` or
+    /// `error: ` and the reason
+    SetButton {
+        target: String,
+        patch: serde_json::Map<String, serde_json::Value>,
+    },
+    /// Shows a page on the devices of a page group, or on the device with that serial
+    SetPage {
+        target: String,
+        page: String,
+    },
+    /// Writes the keys of a device as a PNG at an absolute path
+    Screenshot {
+        sn: String,
+        file: PathBuf,
+    },
+    /// Presses and releases a button of the device with that serial, numbered as in the
+    /// configuration, as if it was pressed on the device
+    Press {
+        sn: String,
+        button: u8,
+    },
+}
+
+impl Command {
+    /// Reads a command line, in JSON or as plain text
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        if line.starts_with('{') {
+            let versioned: Versioned<Command> =
+                serde_json::from_str(line).map_err(|e| format!("Invalid command: {}", e))?;
+            negotiate(Some(versioned.v))?;
+            return Ok(versioned.payload);
+        }
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let (first, second) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let second = second.trim();
+        let optional = |text: &str| (!text.is_empty()).then(|| text.to_string());
+        let required = |text: &str, what: &str| {
+            optional(text).ok_or_else(|| format!("'{}' requires {}", word, what))
+        };
+        Ok(match word {
+            "setvar" => Command::SetVar {
+                key: required(first, "a key")?,
+                value: optional(second),
+            },
+            "clearvar" => Command::SetVar {
+                key: required(first, "a key")?,
+                value: None,
+            },
+            "hotkey" => Command::Hotkey {
+                combination: required(rest, "a key combination")?,
+            },
+            "focus" => Command::Focus {
+                class: required(first, "a window class")?,
+                title: second.to_string(),
+            },
+            "reload" => Command::Reload,
+            "history" => Command::History {
+                count: first.parse().ok(),
+            },
+            "stats" => Command::Stats,
+            "reloadstatus" => Command::ReloadStatus,
+            "version" => Command::Version,
+            "status" => Command::Status,
+            "watch" => Command::Watch {
+                version: match optional(first) {
+                    Some(version) => Some(
+                        version
+                            .parse()
+                            .map_err(|_| format!("Invalid API version: {}", version))?,
+                    ),
+                    None => None,
+                },
+            },
+            "testpattern" => Command::TestPattern {
+                sn: optional(first),
+            },
+            "diagnostics" => Command::Diagnostics {
+                sn: optional(first),
+            },
+            "bench" => Command::Bench {
+                sn: optional(first),
+            },
+            "services" => Command::Services,
+            "setbutton" => {
+                let mut patch: serde_json::Map<String, serde_json::Value> =
+                    serde_json::from_str(rest)
+                        .map_err(|e| format!("Invalid setbutton command: {}", e))?;
+                let target = match patch.remove("target") {
+                    Some(serde_json::Value::String(target)) => target,
+                    _ => return Err("setbutton requires a target".to_string()),
+                };
+                Command::SetButton { target, patch }
+            }
+            "setpage" => match (optional(first), optional(second)) {
+                (Some(target), Some(page)) => Command::SetPage { target, page },
+                _ => return Err("setpage requires a page group or serial and a page".to_string()),
+            },
+            "screenshot-device" => match (optional(first), PathBuf::from(second)) {
+                (Some(sn), file) if file.is_absolute() => Command::Screenshot { sn, file },
+                _ => {
+                    return Err(
+                        "screenshot-device requires a serial and an absolute file path".to_string(),
+                    )
+                }
+            },
+            "press" => match (optional(first), second.parse()) {
+                (Some(sn), Ok(button)) if button > 0 => Command::Press { sn, button },
+                _ => return Err("press requires a serial and a button number".to_string()),
+            },
+            "" => return Err("Empty command".to_string()),
+            other => return Err(format!("Unknown control command: {:?}", other)),
+        })
+    }
+
+    /// The command as a plain text line, which daemons of every version understand
+    pub fn to_line(&self) -> String {
+        let with = |word: &str, args: &[&str]| {
+            let mut line = word.to_string();
+            for arg in args.iter().filter(|arg| !arg.is_empty()) {
+                line.push(' ');
+                line.push_str(arg.trim());
+            }
+            line + "\n"
+        };
+        match self {
+            Command::SetVar { key, value: None } => with("clearvar", &[key]),
+            Command::SetVar {
+                key,
+                value: Some(value),
+            } => with("setvar", &[key, value]),
+            Command::Hotkey { combination } => with("hotkey", &[combination]),
+            // Words split the class from the title
+            Command::Focus { class, .. } if class.contains(char::is_whitespace) => {
+                serde_json::to_string(&Versioned::new(self)).unwrap_or_default() + "\n"
+            }
+            Command::Focus { class, title } => with("focus", &[class, title]),
+            Command::Reload => with("reload", &[]),
+            Command::History { count } => with(
+                "history",
+                &[&count.map(|count| count.to_string()).unwrap_or_default()],
+            ),
+            Command::Stats => with("stats", &[]),
+            Command::ReloadStatus => with("reloadstatus", &[]),
+            Command::Version => with("version", &[]),
+            Command::Status => with("status", &[]),
+            Command::Watch { version } => with(
+                "watch",
+                &[&version
+                    .map(|version| version.to_string())
+                    .unwrap_or_default()],
+            ),
+            Command::TestPattern { sn } => with("testpattern", &[sn.as_deref().unwrap_or("")]),
+            Command::Diagnostics { sn } => with("diagnostics", &[sn.as_deref().unwrap_or("")]),
+            Command::Bench { sn } => with("bench", &[sn.as_deref().unwrap_or("")]),
+            Command::Services => with("services", &[]),
+            Command::SetButton { target, patch } => {
+                let mut change = serde_json::Map::new();
+                change.insert("target".to_string(), target.clone().into());
+                change.extend(patch.clone());
+                with(
+                    "setbutton",
+                    &[&serde_json::Value::Object(change).to_string()],
+                )
+            }
+            Command::SetPage { target, page } => with("setpage", &[target, page]),
+            Command::Screenshot { sn, file } => {
+                with("screenshot-device", &[sn, &file.to_string_lossy()])
+            }
+            Command::Press { sn, button } => with("press", &[sn, &button.to_string()]),
+        }
+    }
+
+    /// Whether the daemon answers the command with a line; the others are only carried
+    /// out, and [`Command::Watch`] is answered with a stream of lines
+    pub fn is_answered(&self) -> bool {
+        !matches!(
+            self,
+            Command::SetVar { .. }
+                | Command::Hotkey { .. }
+                | Command::Focus { .. }
+                | Command::Reload
+                | Command::TestPattern { .. }
+                | Command::Diagnostics { .. }
+                | Command::Press { .. }
+                | Command::Watch { .. }
+        )
+    }
+}
+
+/// The answer to [`Command::Version`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiInfo {
+    /// The version of keydeck
+    pub keydeck: String,
+    /// The newest API version the daemon speaks
+    pub api: u32,
+    /// The oldest API version the daemon still answers
+    pub min_api: u32,
+}
+
+/// The answer to [`Command::Status`]: what the running daemon is and does
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    /// The version of keydeck
+    pub keydeck: String,
+    /// The newest API version the daemon speaks
+    pub api: u32,
+    pub pid: u32,
+    /// Seconds since the daemon started
+    pub uptime: u64,
+    /// Number of configurations applied so far (1 = the startup configuration)
+    pub config_generation: u64,
+    /// Error of the most recent reload attempt, cleared by the next successful one
+    #[serde(default)]
+    pub last_reload_error: Option<String>,
+    #[serde(default)]
+    pub devices: Vec<DeviceState>,
+}
+
+/// A device connected to the daemon
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceState {
+    pub sn: String,
+    pub model: String,
+    /// The page the device shows
+    pub page: String,
+    pub brightness: u8,
+    pub pinned: bool,
+}
+
+/// Web deck, server to browser: the device shown and its keys; `keys` is 0 without a
+/// device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeckLayout {
+    pub device: String,
+    pub rows: usize,
+    pub cols: usize,
+    pub keys: usize,
+}
+
+/// Web deck, server to browser: a key of the device shown, counted from 0, pressed or
+/// released
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyPress {
+    pub key: u8,
+    pub pressed: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payloads_stay_readable_across_versions() {
+        // Lines of the first daemons, without a version
+        let event = Event::from_line(r#"{"event":"page","sn":"AL1","page":"Media"}"#);
+        assert_eq!(
+            event,
+            Some(Event::Page {
+                sn: "AL1".to_string(),
+                page: "Media".to_string()
+            })
+        );
+        let newer = Event::from_line(r#"{"v":1,"event":"dial_turned","sn":"AL1","by":3}"#);
+        assert_eq!(newer, Some(Event::Unknown));
+        let line = event.unwrap().to_line(1).unwrap();
+        assert_eq!(
+            line,
+            "{\"v\":1,\"event\":\"page\",\"sn\":\"AL1\",\"page\":\"Media\"}\n"
+        );
+        assert_eq!(Event::Unknown.to_line(1), None);
+
+        assert_eq!(negotiate(None), Ok(1));
+        assert!(Command::Status.is_answered());
+        assert!(!Command::Reload.is_answered());
+        assert!(negotiate(Some(API_VERSION + 1)).is_err());
+
+        let commands = [
+            Command::SetVar {
+                key: "mode".to_string(),
+                value: Some("quiet hours".to_string()),
+            },
+            Command::SetVar {
+                key: "mode".to_string(),
+                value: None,
+            },
+            Command::Hotkey {
+                combination: "ctrl+alt+k".to_string(),
+            },
+            Command::Focus {
+                class: "firefox".to_string(),
+                title: "GitHub - Mozilla Firefox".to_string(),
+            },
+            Command::Focus {
+                class: "VirtualBox Machine".to_string(),
+                title: String::new(),
+            },
+            Command::History { count: Some(5) },
+            Command::Status,
+            Command::Watch { version: None },
+            Command::TestPattern { sn: None },
+            Command::SetButton {
+                target: "Main/Main/button3".to_string(),
+                patch: serde_json::from_str(r#"{"icon":"mute.png"}"#).unwrap(),
+            },
+            Command::SetPage {
+                target: "AL1".to_string(),
+                page: "Media".to_string(),
+            },
+            Command::Screenshot {
+                sn: "AL1".to_string(),
+                file: PathBuf::from("/tmp/my keys.png"),
+            },
+            Command::Press {
+                sn: "AL1".to_string(),
+                button: 12,
+            },
+        ];
+        for command in commands {
+            assert_eq!(Command::parse(&command.to_line()), Ok(command.clone()));
+            let json = serde_json::to_string(&Versioned::new(&command)).unwrap();
+            assert_eq!(Command::parse(&json), Ok(command));
+        }
+        assert_eq!(
+            Command::parse(r#"{"command":"set_var","key":"mode"}"#),
+            Ok(Command::SetVar {
+                key: "mode".to_string(),
+                value: None
+            })
+        );
+        assert!(Command::parse(r#"{"v":99,"command":"stats"}"#).is_err());
+        assert!(Command::parse("setpage AL1").is_err());
+        assert!(Command::parse("screenshot-device AL1 keys.png").is_err());
+        assert!(Command::parse("teleport").is_err());
+    }
+}
@@ -0,0 +1,475 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Building configurations in code.
+//!
+//! Tools that generate or import configurations assemble them with these builders
+//! instead of templating YAML text, and write them with [`KeyDeckConf::to_yaml`] or
+//! [`KeyDeckConf::save`]. Every builder ends in the plain configuration type, whose
+//! public fields remain open for anything the builders do not cover.
+//!
+//! ```
+//! use keydeck_types::builder::{ActionBuilder, ButtonBuilder, PageBuilder};
+//! use keydeck_types::KeyDeckConf;
+//!
+//! let browser = ButtonBuilder::new()
+//!     .icon("firefox.png")
+//!     .text("Web")
+//!     .actions(ActionBuilder::new().focus("firefox").key("Ctrl+T"));
+//! let page = PageBuilder::new()
+//!     .button(1, browser)
+//!     .button(2, ButtonBuilder::new().text("Media").actions(ActionBuilder::new().jump("Media")));
+//!
+//! let mut conf = KeyDeckConf::default();
+//! conf.add_page("default", "Main", page);
+//! let yaml = conf.to_yaml().unwrap();
+//! assert_eq!(KeyDeckConf::from_yaml(&yaml).unwrap(), conf);
+//! ```
+
+use crate::pages::{
+    Action, Button, ButtonBuiltin, ButtonConfig, Chord, ColorConfig, FocusTarget, Icon,
+    KeyDeckConf, MacroCall, Page, TextConfig, Wallpaper, When,
+};
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A sequence of actions, run one after the other
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActionBuilder {
+    actions: Vec<Action>,
+}
+
+impl ActionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds any action, for those without a method of their own
+    pub fn action(mut self, action: Action) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    pub fn jump(self, page: impl Into<String>) -> Self {
+        self.action(Action::Jump { jump: page.into() })
+    }
+
+    pub fn auto_jump(self) -> Self {
+        self.action(Action::AutoJump { auto_jump: () })
+    }
+
+    /// Focuses a window whose class or title contains `name`
+    pub fn focus(self, name: impl Into<String>) -> Self {
+        self.action(Action::Focus {
+            focus: FocusTarget::Name(name.into()),
+            fallback_exec: None,
+            wait_for_window: None,
+        })
+    }
+
+    /// Presses a key combination, such as `Ctrl+T`
+    pub fn key(self, key: impl Into<String>) -> Self {
+        self.action(Action::Key {
+            key: key.into(),
+            target: None,
+        })
+    }
+
+    /// Types `text`
+    pub fn text(self, text: impl Into<String>) -> Self {
+        self.action(Action::Text {
+            text: text.into(),
+            echo: None,
+            target: None,
+        })
+    }
+
+    pub fn wait(self, seconds: f32) -> Self {
+        self.action(Action::Wait { wait: seconds })
+    }
+
+    pub fn notify(self, message: impl Into<String>) -> Self {
+        self.action(Action::Notify {
+            notify: message.into(),
+        })
+    }
+
+    /// Starts a command without waiting for it
+    pub fn exec(self, command: impl Into<String>) -> Self {
+        self.action(exec(command.into(), None))
+    }
+
+    /// Runs a command and fails when it does
+    pub fn exec_wait(self, command: impl Into<String>) -> Self {
+        self.action(exec(command.into(), Some(true)))
+    }
+
+    pub fn open(self, target: impl Into<String>) -> Self {
+        self.action(Action::Open {
+            open: target.into(),
+        })
+    }
+
+    /// Sets the context variable `key` to `value`, or clears it when `value` is empty
+    pub fn set(self, key: &str, value: &str) -> Self {
+        self.action(Action::Set {
+            set: format!("{}={}", key, value),
+        })
+    }
+
+    /// Calls the macro `name` with `params`
+    pub fn call<'a>(
+        self,
+        name: impl Into<String>,
+        params: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Self {
+        let params = params
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+        self.action(Action::Macro(MacroCall {
+            name: name.into(),
+            params,
+        }))
+    }
+
+    /// Runs `then` when the [condition](crate::condition) holds, `otherwise` if given
+    pub fn if_then(
+        self,
+        condition: impl Into<String>,
+        then: ActionBuilder,
+        otherwise: Option<ActionBuilder>,
+    ) -> Self {
+        self.action(Action::If {
+            condition: condition.into(),
+            then: then.build(),
+            else_actions: otherwise.map(ActionBuilder::build),
+        })
+    }
+
+    /// Runs `actions` until one fails, then `otherwise` if given
+    pub fn try_else(self, actions: ActionBuilder, otherwise: Option<ActionBuilder>) -> Self {
+        self.action(Action::Try {
+            try_actions: actions.build(),
+            else_actions: otherwise.map(ActionBuilder::build),
+        })
+    }
+
+    /// Runs one of `actions`, picked at random each time
+    pub fn random(self, actions: ActionBuilder) -> Self {
+        self.action(Action::Random {
+            random_actions: actions.build(),
+        })
+    }
+
+    pub fn build(self) -> Vec<Action> {
+        self.actions
+    }
+}
+
+fn exec(command: String, wait: Option<bool>) -> Action {
+    Action::Exec {
+        exec: command,
+        wait,
+        run_as: None,
+        scope: None,
+        timeout: None,
+        tag: None,
+        kill_on_exit: None,
+    }
+}
+
+impl From<ActionBuilder> for Vec<Action> {
+    fn from(builder: ActionBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// A button, shown on a page or defined under `buttons`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ButtonBuilder {
+    button: Button,
+}
+
+impl ButtonBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes every field left unset from the button definition `name`
+    pub fn extends(mut self, name: impl Into<String>) -> Self {
+        self.button.extends = Some(name.into());
+        self
+    }
+
+    /// Icon file, found like the icons of the configuration are
+    pub fn icon(mut self, icon: impl Into<String>) -> Self {
+        self.button.icon = Some(Icon::File(icon.into()));
+        self
+    }
+
+    /// Background color, such as `#202020`, or the name of a color of the configuration
+    pub fn background(mut self, color: impl Into<String>) -> Self {
+        self.button.background = Some(ColorConfig::Fixed(color.into()));
+        self
+    }
+
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.button.text = Some(TextConfig::Simple(text.into()));
+        self
+    }
+
+    pub fn text_color(mut self, color: impl Into<String>) -> Self {
+        self.button.text_color = Some(ColorConfig::Fixed(color.into()));
+        self
+    }
+
+    pub fn outline(mut self, color: impl Into<String>) -> Self {
+        self.button.outline = Some(color.into());
+        self
+    }
+
+    /// Sound played on a press: a sound file, `click`, or `none`
+    pub fn sound(mut self, sound: impl Into<String>) -> Self {
+        self.button.sound = Some(sound.into());
+        self
+    }
+
+    /// Shows the layer `name` of the page while the button is held
+    pub fn modifier(mut self, name: impl Into<String>) -> Self {
+        self.button.modifier = Some(name.into());
+        self
+    }
+
+    /// What a press does on its own, before the actions
+    pub fn builtin(mut self, builtin: ButtonBuiltin) -> Self {
+        self.button.builtin = Some(builtin);
+        self
+    }
+
+    /// Actions run on a press, replacing any set before
+    pub fn actions(mut self, actions: impl Into<Vec<Action>>) -> Self {
+        self.button.actions = Some(actions.into());
+        self
+    }
+
+    pub fn build(self) -> Button {
+        self.button
+    }
+}
+
+impl From<ButtonBuilder> for Button {
+    fn from(builder: ButtonBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// A page of a page group
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageBuilder {
+    page: Page,
+}
+
+impl PageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shows `button` on the key `index`, counted from 1
+    pub fn button(mut self, index: u8, button: impl Into<Button>) -> Self {
+        self.page.buttons.insert(
+            format!("button{}", index),
+            ButtonConfig::Detailed(button.into()),
+        );
+        self
+    }
+
+    /// Shows `button` on the key at `row` and `column`, both counted from 1
+    pub fn button_at(mut self, row: usize, column: usize, button: impl Into<Button>) -> Self {
+        self.page.buttons.insert(
+            format!("r{}c{}", row, column),
+            ButtonConfig::Detailed(button.into()),
+        );
+        self
+    }
+
+    /// Shows the button definition `name` on the key `index`, counted from 1
+    pub fn button_named(mut self, index: u8, name: impl Into<String>) -> Self {
+        self.page.buttons.insert(
+            format!("button{}", index),
+            ButtonConfig::Template(name.into()),
+        );
+        self
+    }
+
+    /// Adds the buttons of the template `name`, after those of the templates added before
+    pub fn inherits(mut self, name: impl Into<String>) -> Self {
+        self.page
+            .inherits
+            .get_or_insert_with(Vec::new)
+            .push(name.into());
+        self
+    }
+
+    /// Shows the page while a window whose class or title matches `pattern` has the focus
+    pub fn when_window(mut self, pattern: impl Into<String>) -> Self {
+        self.page.when = Some(When::window(pattern.into()));
+        self
+    }
+
+    pub fn theme(mut self, theme: impl Into<String>) -> Self {
+        self.page.theme = Some(theme.into());
+        self
+    }
+
+    /// One image spread across the keys
+    pub fn wallpaper(mut self, image: impl Into<String>) -> Self {
+        self.page.wallpaper = Some(Wallpaper::File(image.into()));
+        self
+    }
+
+    /// Keeps the page shown when the focus changes
+    pub fn lock(mut self, lock: bool) -> Self {
+        self.page.lock = Some(lock);
+        self
+    }
+
+    /// Actions run on every tick while the page is shown
+    pub fn on_tick(mut self, actions: impl Into<Vec<Action>>) -> Self {
+        self.page.on_tick = Some(actions.into());
+        self
+    }
+
+    /// Seconds between ticks while the page is shown, between 1 and 60
+    pub fn tick(mut self, seconds: f64) -> Self {
+        self.page.tick = Some(seconds);
+        self
+    }
+
+    /// Actions run by pressing `keys` (`button#` or `r#c#`) together, within the default
+    /// window
+    pub fn chord(mut self, keys: &[&str], actions: impl Into<Vec<Action>>) -> Self {
+        let chord = Chord {
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+            window: Chord::DEFAULT_WINDOW,
+            actions: actions.into(),
+        };
+        self.page.chords.get_or_insert_with(Vec::new).push(chord);
+        self
+    }
+
+    /// Shows `button` on the key `index`, counted from 1, while a modifier of the layer
+    /// `name` is held
+    pub fn layer_button(
+        mut self,
+        name: impl Into<String>,
+        index: u8,
+        button: impl Into<Button>,
+    ) -> Self {
+        self.page
+            .layers
+            .get_or_insert_with(IndexMap::new)
+            .entry(name.into())
+            .or_default()
+            .insert(
+                format!("button{}", index),
+                ButtonConfig::Detailed(button.into()),
+            );
+        self
+    }
+
+    pub fn build(self) -> Page {
+        self.page
+    }
+}
+
+impl From<PageBuilder> for Page {
+    fn from(builder: PageBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl KeyDeckConf {
+    /// Adds `page` as `name` to the page group `group` (a device serial or `default`),
+    /// creating the group if needed. The first page of a group becomes its main page.
+    pub fn add_page(&mut self, group: &str, name: &str, page: impl Into<Page>) {
+        let pages = self.page_groups.entry(group.to_string()).or_default();
+        pages.main_page.get_or_insert_with(|| name.to_string());
+        pages.pages.insert(name.to_string(), page.into());
+    }
+
+    /// Adds `button` to the `buttons` section, for pages to show by name or extend
+    pub fn add_button(&mut self, name: &str, button: impl Into<Button>) {
+        self.buttons
+            .get_or_insert_with(Default::default)
+            .insert(name.to_string(), button.into());
+    }
+
+    /// The configuration as the YAML of a configuration file
+    pub fn to_yaml(&self) -> Result<String, String> {
+        serde_yaml_ng::to_string(self).map_err(|e| format!("Failed to serialize config: {}", e))
+    }
+
+    /// Parses the YAML of a configuration file. Templates, button definitions and
+    /// defaults are left as written, to be resolved by the daemon when it loads it.
+    pub fn from_yaml(yaml: &str) -> Result<Self, String> {
+        let mut conf: KeyDeckConf =
+            serde_yaml_ng::from_str(yaml).map_err(|e| format!("Failed to parse config: {}", e))?;
+        conf.migrate_legacy_window_name();
+        Ok(conf)
+    }
+
+    /// Writes the configuration to `path`, keeping the file there as a backup like the
+    /// configuration UI does. Returns the backup, if there was a file to back up.
+    pub fn save(&self, path: &Path) -> Result<Option<PathBuf>, String> {
+        crate::save_config_file(path, &self.to_yaml()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_configurations_read_back_the_same() {
+        let mut conf = KeyDeckConf::default();
+        conf.add_button(
+            "app",
+            ButtonBuilder::new()
+                .background("#202040")
+                .actions(ActionBuilder::new().exec("launch")),
+        );
+        let page = PageBuilder::new()
+            .inherits("common")
+            .button(1, ButtonBuilder::new().extends("app").text("Mail"))
+            .button_at(2, 3, ButtonBuilder::new().icon("vol.png"))
+            .button_named(4, "app")
+            .on_tick(ActionBuilder::new().call("poll", [("host", "nas")]))
+            .tick(5.0);
+        conf.add_page("default", "Main", page);
+        conf.add_page(
+            "default",
+            "Media",
+            PageBuilder::new().when_window("vlc").button(
+                1,
+                ButtonBuilder::new().actions(
+                    ActionBuilder::new()
+                        .try_else(
+                            ActionBuilder::new().focus("vlc"),
+                            Some(ActionBuilder::new().exec("vlc")),
+                        )
+                        .set("mode", "media"),
+                ),
+            ),
+        );
+
+        let group = &conf.page_groups["default"];
+        assert_eq!(group.main_page.as_deref(), Some("Main"));
+        let yaml = conf.to_yaml().unwrap();
+        assert!(yaml.contains("set: mode=media"), "{}", yaml);
+        assert!(yaml.contains("r2c3:"), "{}", yaml);
+        assert_eq!(KeyDeckConf::from_yaml(&yaml).unwrap(), conf);
+        assert!(KeyDeckConf::from_yaml("brightness: [").is_err());
+    }
+}
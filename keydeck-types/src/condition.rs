@@ -0,0 +1,417 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Condition expressions, shared by the `if` action, the `enabled` state of buttons,
+//! the `visible_when` value of pages and the `blink`/`pulse` gates of buttons.
+//!
+//! A condition compares values, which are words, quoted strings or dynamic parameters
+//! such as `${service:cpu}`, and joins the comparisons with boolean logic:
+//!
+//! ```text
+//! ${service:cpu} > 80 && ${var:mode} != quiet
+//! not (${focus:class} contains firefox or ${focus:class} matches "*chrom*")
+//! ```
+//!
+//! - `==` and `!=` compare numbers as numbers and anything else as text, ignoring case
+//! - `<`, `<=`, `>`, `>=` compare numbers as numbers and anything else as text, so
+//!   times such as `${time:%H:%M} >= 09:00` compare as expected
+//! - `contains` looks for text within a value, `matches` compares a whole value with a
+//!   pattern where `*` stands for any text and `?` for any one character; both ignore case
+//! - `&&`/`and`, `||`/`or`, `!`/`not` and parentheses, with `and` before `or`
+//! - A value on its own holds when it is set, see [`is_truthy`]
+//!
+//! Dynamic parameters are replaced by their values only after the condition has been
+//! parsed, so a value can never change the meaning of a condition.
+
+use crate::pages::is_truthy;
+
+/// How deep parentheses and negations may nest
+const MAX_DEPTH: usize = 32;
+
+/// A parsed condition
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition(Expr);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Vec<Expr>),
+    And(Vec<Expr>),
+    Not(Box<Expr>),
+    Compare(String, Op, String),
+    Value(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    Matches,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Not,
+    And,
+    Or,
+    Op(Op),
+    /// A word or quoted string, possibly with dynamic parameters
+    Value(String),
+}
+
+impl Condition {
+    /// Parses a condition, telling what is wrong with it otherwise
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let tokens = tokenize(text)?;
+        if tokens.is_empty() {
+            return Err("Empty condition".to_string());
+        }
+        let mut parser = Parser { tokens, next: 0 };
+        let expr = parser.or(0)?;
+        match parser.tokens.get(parser.next) {
+            None => Ok(Condition(expr)),
+            Some(Token::Close) => Err("Unexpected ')'".to_string()),
+            Some(_) => Err(format!("Expected an operator between values in '{}'", text)),
+        }
+    }
+
+    /// Whether the condition holds. `substitute` replaces the dynamic parameters in a
+    /// value with what they currently are.
+    pub fn holds(&self, substitute: &dyn Fn(&str) -> String) -> bool {
+        self.0.holds(substitute)
+    }
+}
+
+/// Parses and checks `text` at once
+pub fn holds(text: &str, substitute: &dyn Fn(&str) -> String) -> Result<bool, String> {
+    Ok(Condition::parse(text)?.holds(substitute))
+}
+
+impl Expr {
+    fn holds(&self, substitute: &dyn Fn(&str) -> String) -> bool {
+        let value = |text: &str| {
+            if text.contains("${") {
+                substitute(text).trim().to_string()
+            } else {
+                text.to_string()
+            }
+        };
+        match self {
+            Expr::Or(exprs) => exprs.iter().any(|expr| expr.holds(substitute)),
+            Expr::And(exprs) => exprs.iter().all(|expr| expr.holds(substitute)),
+            Expr::Not(expr) => !expr.holds(substitute),
+            Expr::Compare(left, op, right) => compare(&value(left), *op, &value(right)),
+            Expr::Value(text) => is_truthy(&value(text)),
+        }
+    }
+}
+
+fn compare(left: &str, op: Op, right: &str) -> bool {
+    let (left_text, right_text) = (left.to_lowercase(), right.to_lowercase());
+    let ordering = match (left.parse::<f64>(), right.parse::<f64>()) {
+        (Ok(left), Ok(right)) => left.partial_cmp(&right),
+        _ => Some(left_text.cmp(&right_text)),
+    };
+    match op {
+        Op::Contains => left_text.contains(&right_text),
+        Op::Matches => {
+            let text: Vec<char> = left_text.chars().collect();
+            let pattern: Vec<char> = right_text.chars().collect();
+            wildcard_match(&text, &pattern)
+        }
+        Op::Ne => !ordering.is_some_and(|ordering| ordering.is_eq()),
+        Op::Eq => ordering.is_some_and(|ordering| ordering.is_eq()),
+        Op::Lt => ordering.is_some_and(|ordering| ordering.is_lt()),
+        Op::Le => ordering.is_some_and(|ordering| ordering.is_le()),
+        Op::Gt => ordering.is_some_and(|ordering| ordering.is_gt()),
+        Op::Ge => ordering.is_some_and(|ordering| ordering.is_ge()),
+    }
+}
+
+/// Whether `text` is all of `pattern`, where `*` is any text and `?` any one character
+fn wildcard_match(text: &[char], pattern: &[char]) -> bool {
+    // Position in the pattern after the last `*`, and in the text it was tried at
+    let mut star: Option<(usize, usize)> = None;
+    let (mut t, mut p) = (0, 0);
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                t += 1;
+                p += 1;
+            }
+            _ => match star {
+                // Let the last `*` take one more character
+                Some((after, tried)) => {
+                    star = Some((after, tried + 1));
+                    p = after;
+                    t = tried + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Splits a condition into its tokens
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, length) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Op(Op::Eq), 2),
+            ('!', Some('=')) => (Token::Op(Op::Ne), 2),
+            ('<', Some('=')) => (Token::Op(Op::Le), 2),
+            ('>', Some('=')) => (Token::Op(Op::Ge), 2),
+            ('<', _) => (Token::Op(Op::Lt), 1),
+            ('>', _) => (Token::Op(Op::Gt), 1),
+            ('!', _) => (Token::Not, 1),
+            ('&' | '|' | '=', _) => {
+                return Err(format!("Unknown operator '{}', use '{}{}'", c, c, c));
+            }
+            ('"' | '\'', _) => {
+                let (value, length) = quoted(&chars[i..])?;
+                (Token::Value(value), length)
+            }
+            _ => {
+                let (word, length) = word(&chars[i..])?;
+                let token = match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "contains" => Token::Op(Op::Contains),
+                    "matches" => Token::Op(Op::Matches),
+                    _ => Token::Value(word),
+                };
+                (token, length)
+            }
+        };
+        tokens.push(token);
+        i += length;
+    }
+    Ok(tokens)
+}
+
+/// The string a quote starts, without its quotes, and the characters it takes.
+/// A backslash escapes the next character.
+fn quoted(chars: &[char]) -> Result<(String, usize), String> {
+    let quote = chars[0];
+    let mut value = String::new();
+    let mut i = 1;
+    while let Some(&c) = chars.get(i) {
+        match c {
+            '\\' if i + 1 < chars.len() => {
+                value.push(chars[i + 1]);
+                i += 2;
+            }
+            c if c == quote => return Ok((value, i + 1)),
+            c => {
+                value.push(c);
+                i += 1;
+            }
+        }
+    }
+    Err(format!("Missing closing {} in condition", quote))
+}
+
+/// The word that starts `chars`, and the characters it takes. Dynamic parameters are
+/// taken whole, spaces and operators within them included.
+fn word(chars: &[char]) -> Result<(String, usize), String> {
+    let mut word = String::new();
+    let mut i = 0;
+    while let Some(&c) = chars.get(i) {
+        if c == '$' && chars.get(i + 1) == Some(&'{') {
+            let mut depth = 0;
+            loop {
+                let Some(&c) = chars.get(i) else {
+                    return Err("Missing closing '}' of a dynamic parameter".to_string());
+                };
+                word.push(c);
+                i += 1;
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 && c == '}' {
+                    break;
+                }
+            }
+            continue;
+        }
+        if c.is_whitespace() || "()&|=!<>\"'".contains(c) {
+            break;
+        }
+        word.push(c);
+        i += 1;
+    }
+    Ok((word, i))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn or(&mut self, depth: usize) -> Result<Expr, String> {
+        let mut exprs = vec![self.and(depth)?];
+        while self.peek() == Some(&Token::Or) {
+            self.next += 1;
+            exprs.push(self.and(depth)?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Or(exprs)
+        })
+    }
+
+    fn and(&mut self, depth: usize) -> Result<Expr, String> {
+        let mut exprs = vec![self.unary(depth)?];
+        while self.peek() == Some(&Token::And) {
+            self.next += 1;
+            exprs.push(self.unary(depth)?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::And(exprs)
+        })
+    }
+
+    fn unary(&mut self, depth: usize) -> Result<Expr, String> {
+        if depth > MAX_DEPTH {
+            return Err("Condition is nested too deeply".to_string());
+        }
+        match self.peek() {
+            Some(Token::Not) => {
+                self.next += 1;
+                Ok(Expr::Not(Box::new(self.unary(depth + 1)?)))
+            }
+            Some(Token::Open) => {
+                self.next += 1;
+                let expr = self.or(depth + 1)?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err("Missing closing ')' in condition".to_string());
+                }
+                self.next += 1;
+                Ok(expr)
+            }
+            _ => self.comparison(),
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.value()?;
+        let Some(&Token::Op(op)) = self.peek() else {
+            return Ok(Expr::Value(left));
+        };
+        self.next += 1;
+        Ok(Expr::Compare(left, op, self.value()?))
+    }
+
+    fn value(&mut self) -> Result<String, String> {
+        match self.tokens.get(self.next) {
+            Some(Token::Value(value)) => {
+                self.next += 1;
+                Ok(value.clone())
+            }
+            Some(token) => Err(format!("Expected a value, found {}", describe(token))),
+            None => Err("Condition ends where a value is expected".to_string()),
+        }
+    }
+}
+
+fn describe(token: &Token) -> &'static str {
+    match token {
+        Token::Open => "'('",
+        Token::Close => "')'",
+        Token::Not => "'not'",
+        Token::And => "'and'",
+        Token::Or => "'or'",
+        Token::Op(_) => "an operator",
+        Token::Value(_) => "a value",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(text: &str) -> bool {
+        let substitute = |text: &str| {
+            text.replace("${service:cpu}", "85\n")
+                .replace("${var:mode}", "Work")
+                .replace("${focus:class}", "org.mozilla.firefox")
+                .replace("${time:%H:%M}", "09:30")
+                .replace("${var:unset}", "")
+        };
+        holds(text, &substitute).unwrap()
+    }
+
+    #[test]
+    fn conditions_compare_values_and_join_them() {
+        assert!(check("${service:cpu} > 80"));
+        assert!(!check("${service:cpu} >= 100"));
+        assert!(check("${var:mode} == work"));
+        assert!(check("${var:mode} == 'Work' && ${service:cpu} != 10"));
+        assert!(check("${var:unset} or ${var:mode}"));
+        assert!(!check("${var:unset}"));
+        assert!(check("!${var:unset}"));
+        assert!(check("not (${var:mode} == home || ${service:cpu} < 50)"));
+        assert!(check("${focus:class} contains FIREFOX"));
+        assert!(check("${focus:class} matches \"org.*.fire?ox\""));
+        assert!(!check("${focus:class} matches mozilla"));
+        assert!(check("${time:%H:%M} >= 09:00 and ${time:%H:%M} < 17:00"));
+        // `and` binds closer than `or`
+        assert!(check("${var:mode} == home and 0 or 1"));
+        // 10 is more than 9 as a number, not as text
+        assert!(check("10 > 9"));
+    }
+
+    #[test]
+    fn values_cannot_change_a_condition() {
+        let substitute = |_: &str| "1 || 1".to_string();
+        assert!(!holds("${var:x} == 1", &substitute).unwrap());
+    }
+
+    #[test]
+    fn mistakes_are_reported() {
+        assert!(Condition::parse("").is_err());
+        assert!(Condition::parse("${var:mode} = work").is_err());
+        assert!(Condition::parse("(${var:mode} == work").is_err());
+        assert!(Condition::parse("${var:mode} == work)").is_err());
+        assert!(Condition::parse("${var:mode} ==").is_err());
+        assert!(Condition::parse("a b").is_err());
+        assert!(Condition::parse("'unclosed").is_err());
+        assert!(Condition::parse("${var:mode").is_err());
+        assert!(Condition::parse(&"(".repeat(100)).is_err());
+        assert!(Condition::parse("${exec:echo a && b} == 'a && b'").is_ok());
+    }
+}
@@ -0,0 +1,363 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Crash-safe writes of the configuration and state files, shared by the daemon and the
+//! configuration UI so both save the same way.
+//!
+//! A file is written to a temporary file next to it, flushed to disk and renamed over
+//! the old one, so a crash or a full disk leaves either the old or the new content,
+//! never a truncated file. A [`BackupManager`] first keeps a timestamped copy of the
+//! previous content, `config.YYYYMMDD_HHMMSS.yaml`, and bounds the backups by count and
+//! age, gzip-compressing all but the newest few (`config.YYYYMMDD_HHMMSS.yaml.gz`). Later
+//! backups of the same second are counted on, `config.YYYYMMDD_HHMMSS_2.yaml`.
+//! The configuration is saved with [`BackupManager::CONFIG`], the state files of the
+//! daemon with [`BackupManager::STATE`].
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Most backups of the configuration kept next to it
+pub const MAX_CONFIG_BACKUPS: usize = BackupManager::CONFIG.max_count;
+
+/// Format of the timestamp in the name of a backup
+const BACKUP_TIMESTAMP: &str = "%Y%m%d_%H%M%S";
+
+/// Extension added to compressed backups
+const COMPRESSED_EXTENSION: &str = ".gz";
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Temporary file `path` is written through: `config.tmp.yaml` for `config.yaml`
+fn temp_path(path: &Path) -> PathBuf {
+    let (stem, extension) = name_parts(path);
+    path.with_file_name(format!("{}.tmp{}", stem, extension))
+}
+
+/// Stem and extension (with its dot, or empty) of the file name of `path`
+fn name_parts(path: &Path) -> (String, String) {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    (stem, extension)
+}
+
+/// Directory of `path`, `.` for a bare file name
+fn dir_of(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Replaces the content of `path` atomically, creating its directory if needed
+pub fn write_atomic(path: &Path, content: impl AsRef<[u8]>) -> io::Result<()> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    if let Some(dir) = dir {
+        fs::create_dir_all(dir)?;
+    }
+    let temp = temp_path(path);
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(content.as_ref())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result?;
+    // The rename itself is only durable once the directory is flushed
+    #[cfg(unix)]
+    if let Some(dir) = dir {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Saves the configuration file `path` with [`BackupManager::CONFIG`]. Returns the
+/// backup, if there was a file to back up.
+pub fn save_config_file(path: &Path, yaml: &str) -> Result<Option<PathBuf>, String> {
+    BackupManager::CONFIG.save(path, yaml)
+}
+
+/// How the previous versions of a file are kept when it is saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupManager {
+    /// Most backups kept
+    pub max_count: usize,
+    /// Backups older than this are removed, whatever their count
+    pub max_age: Option<Duration>,
+    /// Backups beyond the newest this many are compressed
+    pub keep_uncompressed: usize,
+    /// No new backup is taken while the newest is younger than this, for files that are
+    /// written every few seconds
+    pub min_interval: Option<Duration>,
+}
+
+impl BackupManager {
+    /// The configuration: every save is backed up
+    pub const CONFIG: BackupManager = BackupManager {
+        max_count: 10,
+        max_age: Some(Duration::from_secs(90 * DAY)),
+        keep_uncompressed: 3,
+        min_interval: None,
+    };
+
+    /// State files the daemon rewrites as it runs: at most one backup an hour
+    pub const STATE: BackupManager = BackupManager {
+        max_count: 5,
+        max_age: Some(Duration::from_secs(7 * DAY)),
+        keep_uncompressed: 1,
+        min_interval: Some(Duration::from_secs(60 * 60)),
+    };
+
+    /// Saves `content` to `path` atomically: the current file is backed up first, and
+    /// the backups are pruned after. Returns the backup, if one was taken.
+    pub fn save(&self, path: &Path, content: impl AsRef<[u8]>) -> Result<Option<PathBuf>, String> {
+        let backup = self.backup(path)?;
+        write_atomic(path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        self.prune(path);
+        Ok(backup)
+    }
+
+    /// Copies `path` to a timestamped backup next to it, unless it does not exist or the
+    /// newest backup is within [`min_interval`](Self::min_interval)
+    pub fn backup(&self, path: &Path) -> Result<Option<PathBuf>, String> {
+        if !path.exists() || self.max_count == 0 {
+            return Ok(None);
+        }
+        let now = chrono::Local::now().naive_local();
+        if let (Some(interval), Some((_, newest))) = (self.min_interval, backups_of(path).first()) {
+            if (now - *newest).to_std().is_ok_and(|age| age < interval) {
+                return Ok(None);
+            }
+        }
+        let (stem, extension) = name_parts(path);
+        let stamp = now.format(BACKUP_TIMESTAMP);
+        let backup = (1..)
+            .map(|count| match count {
+                1 => format!("{}.{}{}", stem, stamp, extension),
+                _ => format!("{}.{}_{}{}", stem, stamp, count, extension),
+            })
+            .map(|name| path.with_file_name(name))
+            .find(|backup| !backup.exists() && !compressed(backup).exists())
+            .expect("a free backup name");
+        fs::copy(path, &backup).map_err(|e| format!("Failed to create backup: {}", e))?;
+        Ok(Some(backup))
+    }
+
+    /// Removes the backups of `path` beyond [`max_count`](Self::max_count) or older than
+    /// [`max_age`](Self::max_age), and compresses the ones past
+    /// [`keep_uncompressed`](Self::keep_uncompressed). Failures are ignored: a backup
+    /// left behind is retried on the next save.
+    pub fn prune(&self, path: &Path) {
+        let now = chrono::Local::now().naive_local();
+        for (index, (backup, stamp)) in backups_of(path).into_iter().enumerate() {
+            let expired = self
+                .max_age
+                .is_some_and(|max_age| (now - stamp).to_std().is_ok_and(|age| age > max_age));
+            if index >= self.max_count || expired {
+                let _ = fs::remove_file(&backup);
+            } else if index >= self.keep_uncompressed && !is_compressed(&backup) {
+                let _ = compress(&backup);
+            }
+        }
+    }
+}
+
+fn is_compressed(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(COMPRESSED_EXTENSION)
+}
+
+/// `path.gz`
+fn compressed(path: &Path) -> PathBuf {
+    let mut target = path.as_os_str().to_owned();
+    target.push(COMPRESSED_EXTENSION);
+    PathBuf::from(target)
+}
+
+/// Replaces `path` with its gzip-compressed `path.gz`
+fn compress(path: &Path) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&fs::read(path)?)?;
+    write_atomic(&compressed(path), encoder.finish()?)?;
+    fs::remove_file(path)
+}
+
+/// Timestamp of `name` if it is a backup of the file with the given stem and extension,
+/// and its count among the backups of that second
+fn backup_stamp(name: &str, stem: &str, extension: &str) -> Option<(chrono::NaiveDateTime, u32)> {
+    let name = name.strip_suffix(COMPRESSED_EXTENSION).unwrap_or(name);
+    let stamp = name
+        .strip_prefix(stem)?
+        .strip_prefix('.')?
+        .strip_suffix(extension)?;
+    let (stamp, count) =
+        chrono::NaiveDateTime::parse_and_remainder(stamp, BACKUP_TIMESTAMP).ok()?;
+    match count.strip_prefix('_') {
+        None if count.is_empty() => Some((stamp, 1)),
+        Some(count) => count
+            .parse()
+            .ok()
+            .filter(|count| *count > 1)
+            .map(|count| (stamp, count)),
+        None => None,
+    }
+}
+
+/// Whether the file `name` is a backup kept by a [`BackupManager`], of any file
+pub fn is_backup_file(name: &str) -> bool {
+    let plain = name.strip_suffix(COMPRESSED_EXTENSION).unwrap_or(name);
+    let (stem, extension) = name_parts(Path::new(plain));
+    let stem = match stem.rsplit_once('.') {
+        Some((stem, _)) => stem.to_string(),
+        None => return false,
+    };
+    backup_stamp(name, &stem, &extension).is_some()
+}
+
+/// Backups of `path`, newest first
+fn backups_of(path: &Path) -> Vec<(PathBuf, chrono::NaiveDateTime)> {
+    let dir = dir_of(path);
+    let (stem, extension) = name_parts(path);
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            backup_stamp(&name, &stem, &extension).map(|stamp| (dir.join(name), stamp))
+        })
+        .collect();
+    // A compressed and a plain copy of the same backup sort the plain one first
+    backups.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    backups
+        .into_iter()
+        .map(|(backup, (stamp, _))| (backup, stamp))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn names_in(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn saves_keep_the_newest_backups() {
+        let dir = std::env::temp_dir().join(format!("keydeck-config-file-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("config.yaml");
+        let stamp = |hours_ago: i64| {
+            (chrono::Local::now() - chrono::Duration::hours(hours_ago))
+                .format(BACKUP_TIMESTAMP)
+                .to_string()
+        };
+
+        assert_eq!(save_config_file(&path, "first: 1\n"), Ok(None));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first: 1\n");
+        for hours in 1..16 {
+            fs::write(dir.join(format!("config.{}.yaml", stamp(hours))), "old").unwrap();
+        }
+        fs::write(
+            dir.join(format!("config.{}.yaml", stamp(24 * 100))),
+            "expired",
+        )
+        .unwrap();
+        fs::write(dir.join("config.mine.yaml"), "kept").unwrap();
+
+        let backup = save_config_file(&path, "second: 2\n").unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "first: 1\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second: 2\n");
+        assert!(!temp_path(&path).exists());
+
+        let names = names_in(&dir);
+        assert_eq!(names.len(), MAX_CONFIG_BACKUPS + 2);
+        assert!(!names.contains(&format!("config.{}.yaml", stamp(24 * 100))));
+        assert!(!names.contains(&format!("config.{}.yaml", stamp(10))));
+        assert!(names.contains(&format!("config.{}.yaml", stamp(1))));
+        assert!(names.contains(&"config.mine.yaml".to_string()));
+        let backups: Vec<_> = names.iter().filter(|name| is_backup_file(name)).collect();
+        assert_eq!(backups.len(), MAX_CONFIG_BACKUPS);
+        assert_eq!(
+            backups.iter().filter(|name| name.ends_with(".gz")).count(),
+            7
+        );
+
+        let compressed = dir.join(format!("config.{}.yaml.gz", stamp(9)));
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(File::open(compressed).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "old");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn state_files_are_backed_up_at_most_once_an_interval() {
+        let dir = std::env::temp_dir().join(format!("keydeck-state-file-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("state.json");
+
+        assert_eq!(BackupManager::STATE.save(&path, "{}"), Ok(None));
+        assert!(BackupManager::STATE
+            .save(&path, "{\"a\": 1}")
+            .unwrap()
+            .is_some());
+        assert_eq!(BackupManager::STATE.save(&path, "{\"a\": 2}"), Ok(None));
+        assert_eq!(names_in(&dir).len(), 2);
+
+        assert!(is_backup_file("stats.20250101_120000.json.gz"));
+        assert!(is_backup_file("config.20250101_120000_2.yaml"));
+        assert!(!is_backup_file("config.20250101_120000_1.yaml"));
+        assert!(!is_backup_file("state.json"));
+        assert!(!is_backup_file("config.mine.yaml"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backups_of_the_same_second_are_kept_apart() {
+        let dir = std::env::temp_dir().join(format!("keydeck-same-second-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("config.yaml");
+        let mut taken = Vec::new();
+        for save in 0..4 {
+            if let Some(backup) = save_config_file(&path, &format!("save: {}\n", save)).unwrap() {
+                taken.push(backup);
+            }
+        }
+        assert_eq!(taken.len(), 3);
+        for (save, backup) in taken.iter().enumerate() {
+            assert_eq!(
+                fs::read_to_string(backup).unwrap(),
+                format!("save: {}\n", save)
+            );
+        }
+        let newest_first: Vec<_> = backups_of(&path)
+            .into_iter()
+            .map(|(backup, _)| backup)
+            .collect();
+        taken.reverse();
+        assert_eq!(newest_first, taken);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! The formats a configuration file may be written in: YAML, TOML or JSON.
+//!
+//! The format follows the extension of the file (`config.toml`, `config.json`), and
+//! anything else is read as YAML. All three deserialize into the same types, so a
+//! configuration means the same whatever it is written in. The daemon and the
+//! configuration UI parse through [`parse_config`], so both report errors alike.
+
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::path::Path;
+
+/// File names of the configuration in the config dir, in the order they are looked for
+pub const CONFIG_FILE_NAMES: [&str; 3] = ["config.yaml", "config.toml", "config.json"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// The format of the file `path`, by its extension
+    pub fn of(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("toml") => ConfigFormat::Toml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Json => "JSON",
+        }
+    }
+}
+
+/// Fails for configuration files that are not YAML, for the commands that rewrite the
+/// file in place and would otherwise turn a `config.toml` into YAML
+pub fn require_yaml(path: &Path, what: &str) -> Result<(), String> {
+    match ConfigFormat::of(path) {
+        ConfigFormat::Yaml => Ok(()),
+        format => Err(format!(
+            "{} only works with a YAML configuration, and {} is {}",
+            what,
+            path.display(),
+            format.name()
+        )),
+    }
+}
+
+/// A configuration that does not parse
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigParseError {
+    /// The field the error is about, as `page_groups.main.Home.button1`
+    pub path: String,
+    pub message: String,
+    /// Line and column of the error, counted from 1, when the parser knows them
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl fmt::Display for ConfigParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Parses `data`, the content of the configuration file `path`, in the format of the file
+pub fn parse_config<T: DeserializeOwned>(path: &Path, data: &str) -> Result<T, ConfigParseError> {
+    match ConfigFormat::of(path) {
+        ConfigFormat::Yaml => {
+            let deserializer = serde_yaml_ng::Deserializer::from_str(data);
+            serde_path_to_error::deserialize(deserializer).map_err(|e| {
+                let field = e.path().to_string();
+                let error = e.into_inner();
+                let location = error.location();
+                ConfigParseError {
+                    path: field,
+                    message: error.to_string(),
+                    line: location.as_ref().map(|l| l.line()),
+                    column: location.as_ref().map(|l| l.column()),
+                }
+            })
+        }
+        ConfigFormat::Toml => {
+            let deserializer = toml::Deserializer::new(data);
+            serde_path_to_error::deserialize(deserializer).map_err(|e| {
+                let field = e.path().to_string();
+                let error = e.into_inner();
+                let (line, column) = error
+                    .span()
+                    .map(|span| line_and_column(data, span.start))
+                    .unzip();
+                ConfigParseError {
+                    path: field,
+                    message: error.message().to_string(),
+                    line,
+                    column,
+                }
+            })
+        }
+        ConfigFormat::Json => {
+            let mut deserializer = serde_json::Deserializer::from_str(data);
+            serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+                let field = e.path().to_string();
+                let error = e.into_inner();
+                ConfigParseError {
+                    path: field,
+                    message: error.to_string(),
+                    line: Some(error.line()),
+                    column: Some(error.column()),
+                }
+            })
+        }
+    }
+}
+
+/// Line and column, counted from 1, of the byte `offset` of `data`
+fn line_and_column(data: &str, offset: usize) -> (usize, usize) {
+    let before = &data[..offset.min(data.len())];
+    let line_start = before.rfind('\n').map_or(0, |at| at + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyDeckConf;
+
+    #[test]
+    fn the_same_configuration_parses_alike_in_every_format() {
+        let yaml = "tick_time: 2\nmain:\n  Home:\n    button1:\n      text: Hi\n      actions:\n        - jump: Other\n";
+        let toml = "tick_time = 2\n\n[main.Home.button1]\ntext = \"Hi\"\nactions = [{ jump = \"Other\" }]\n";
+        let json = r#"{"tick_time": 2, "main": {"Home": {"button1": {"text": "Hi", "actions": [{"jump": "Other"}]}}}}"#;
+        let from_yaml: KeyDeckConf = parse_config(Path::new("config.yaml"), yaml).unwrap();
+        let from_toml: KeyDeckConf = parse_config(Path::new("config.toml"), toml).unwrap();
+        let from_json: KeyDeckConf = parse_config(Path::new("config.json"), json).unwrap();
+        let as_json = |conf: &KeyDeckConf| serde_json::to_value(conf).unwrap();
+        assert_eq!(as_json(&from_toml), as_json(&from_yaml));
+        assert_eq!(as_json(&from_json), as_json(&from_yaml));
+        assert_eq!(from_yaml.tick_time, 2.0);
+        assert!(from_toml.page_groups["main"].pages.contains_key("Home"));
+
+        let error =
+            parse_config::<KeyDeckConf>(Path::new("config.TOML"), "tick_time = 2\ntick_time = 3\n")
+                .unwrap_err();
+        assert_eq!((error.line, error.column), (Some(2), Some(1)));
+        let error =
+            parse_config::<KeyDeckConf>(Path::new("config.json"), "{\n  \"tick_time\": \"x\"}")
+                .unwrap_err();
+        assert_eq!(error.path, "tick_time");
+        assert_eq!(error.line, Some(2));
+
+        assert_eq!(
+            ConfigFormat::of(Path::new("keydeck.conf")),
+            ConfigFormat::Yaml
+        );
+        assert!(require_yaml(Path::new("config.yml"), "Patching").is_ok());
+        assert!(require_yaml(Path::new("config.json"), "Patching").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! The icon cache: PNGs derived from other images, shared by the configuration UI
+//! (application icons, normalized for the browser) and the daemon (icons scaled to the
+//! key size of a device). Every entry can be made again from its source, so the cache
+//! is pruned by age and size and can be cleared at any time.
+
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// When entries are evicted from the icon cache
+#[derive(Debug, Clone, Copy)]
+pub struct IconCacheLimits {
+    /// Total size the cache is brought under, evicting the least recently used first
+    pub max_size: u64,
+    /// Entries not used for this long are evicted
+    pub max_age: Duration,
+}
+
+impl Default for IconCacheLimits {
+    fn default() -> Self {
+        IconCacheLimits {
+            max_size: 64 * 1024 * 1024,
+            max_age: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// What the icon cache holds, or what was removed from it
+#[derive(Debug, Default, Clone, Serialize, PartialEq)]
+pub struct IconCacheStats {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// The KeyDeck cache directory: `~/.cache/keydeck` on Linux, the platform cache
+/// directory elsewhere.
+pub fn get_cache_dir() -> PathBuf {
+    if let Some(dir) = dirs::cache_dir() {
+        dir.join("keydeck")
+    } else if let Some(home) = dirs::home_dir() {
+        home.join(".cache").join("keydeck")
+    } else {
+        PathBuf::from(".cache").join("keydeck")
+    }
+}
+
+/// Directory of the icon cache
+pub fn get_icon_cache_dir() -> PathBuf {
+    get_cache_dir().join("icons")
+}
+
+/// The cache entry for an image derived from `source`, named after its path and
+/// `variant` (such as `@72x72`)
+pub fn cache_entry(source: &Path, variant: &str) -> PathBuf {
+    let safe_name = source.to_string_lossy().replace(['/', '\\', ':'], "_");
+    get_icon_cache_dir().join(format!("{}{}.png", safe_name, variant))
+}
+
+/// Whether `entry` exists and is newer than its `source`. A valid entry is marked as
+/// used, so that pruning keeps it.
+pub fn is_fresh(entry: &Path, source: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified());
+    let fresh = match (modified(entry), modified(source)) {
+        (Ok(entry_time), Ok(source_time)) => entry_time >= source_time,
+        _ => false,
+    };
+    if fresh {
+        // The modification time doubles as the time of last use
+        let _ = fs::File::options()
+            .write(true)
+            .open(entry)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+    }
+    fresh
+}
+
+/// Files of the icon cache with their size and time of last use
+fn entries(dir: &Path) -> Vec<(PathBuf, u64, SystemTime)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            if !meta.is_file() {
+                return None;
+            }
+            let used = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            Some((entry.path(), meta.len(), used))
+        })
+        .collect()
+}
+
+/// What the icon cache holds
+pub fn icon_cache_stats() -> IconCacheStats {
+    entries(&get_icon_cache_dir())
+        .iter()
+        .fold(IconCacheStats::default(), |stats, (_, size, _)| {
+            IconCacheStats {
+                files: stats.files + 1,
+                bytes: stats.bytes + size,
+            }
+        })
+}
+
+/// Evicts the entries of `dir` older than the limits allow, then the least recently
+/// used ones until the rest fit in the size limit
+fn prune_dir(dir: &Path, limits: IconCacheLimits, now: SystemTime) -> IconCacheStats {
+    let mut entries = entries(dir);
+    entries.sort_by_key(|(_, _, used)| std::cmp::Reverse(*used));
+    let mut kept = 0;
+    let mut removed = IconCacheStats::default();
+    for (path, size, used) in entries {
+        let age = now.duration_since(used).unwrap_or_default();
+        if age <= limits.max_age && kept + size <= limits.max_size {
+            kept += size;
+        } else if fs::remove_file(&path).is_ok() {
+            removed.files += 1;
+            removed.bytes += size;
+        }
+    }
+    removed
+}
+
+/// Brings the icon cache within `limits`, returning what was evicted
+pub fn prune_icon_cache(limits: IconCacheLimits) -> IconCacheStats {
+    prune_dir(&get_icon_cache_dir(), limits, SystemTime::now())
+}
+
+/// Empties the icon cache, returning what was removed
+pub fn clear_icon_cache() -> Result<IconCacheStats, String> {
+    let dir = get_icon_cache_dir();
+    let removed = icon_cache_stats();
+    match fs::remove_dir_all(&dir) {
+        Ok(()) => Ok(removed),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(IconCacheStats::default()),
+        Err(e) => Err(format!("Failed to clear {}: {}", dir.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pruning_evicts_old_then_least_recently_used() {
+        let dir = std::env::temp_dir().join(format!("keydeck-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        for (name, days) in [("recent", 0), ("older", 2), ("oldest", 3), ("stale", 40)] {
+            let path = dir.join(name);
+            fs::write(&path, [0u8; 100]).unwrap();
+            let file = fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(now - day * days).unwrap();
+        }
+
+        let limits = IconCacheLimits {
+            max_size: 250,
+            max_age: day * 30,
+        };
+        let removed = prune_dir(&dir, limits, now);
+        assert_eq!(
+            removed,
+            IconCacheStats {
+                files: 2,
+                bytes: 200
+            }
+        );
+        assert!(dir.join("recent").exists() && dir.join("older").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Where the `icon` of a button is found, shared by the daemon, `keydeck --validate` and
+//! the configuration UI so all of them find the same file.
+//!
+//! An icon is one of:
+//! - an absolute path, or one starting with `~/` for the home directory;
+//! - a path starting with `./` or `../`, taken from the directory of the configuration
+//!   file, so a configuration and its icons can be moved together;
+//! - a name, looked up in the `icon_dirs` of the configuration in order, then in the
+//!   default icon dir and last next to the configuration file.
+//!
+//! Backslashes count as slashes, so configurations written on Windows work elsewhere.
+
+use std::path::{Component, Path, PathBuf};
+
+/// The places icons are looked up in, for one configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct IconPaths {
+    /// Directory of the configuration file
+    config_dir: PathBuf,
+    /// Directories names are looked up in, in order, ending with the default icon dir
+    dirs: Vec<PathBuf>,
+}
+
+impl IconPaths {
+    /// Icon paths of the configuration file at `config_path` with the given `icon_dirs`
+    pub fn new(config_path: &Path, icon_dirs: Option<&[String]>) -> Self {
+        Self::with_default_dir(config_path, icon_dirs, crate::get_icon_dir_path())
+    }
+
+    /// Icon paths as [`Self::new`], with `default_dir` in place of the default icon dir
+    pub fn with_default_dir(
+        config_path: &Path,
+        icon_dirs: Option<&[String]>,
+        default_dir: PathBuf,
+    ) -> Self {
+        let config_dir = config_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let mut dirs: Vec<PathBuf> = icon_dirs
+            .unwrap_or_default()
+            .iter()
+            .filter(|dir| !dir.trim().is_empty())
+            .map(|dir| absolute(&config_dir, dir))
+            .collect();
+        if !dirs.contains(&default_dir) {
+            dirs.push(default_dir);
+        }
+        IconPaths { config_dir, dirs }
+    }
+
+    /// Directories names are looked up in, in order; the last one is the default icon dir
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Directory of the configuration file, that `./` icons are taken from
+    pub fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    /// The file `icon` names, when it exists
+    pub fn resolve(&self, icon: &str) -> Option<PathBuf> {
+        self.candidates(icon)
+            .into_iter()
+            .find(|path| path.is_file())
+    }
+
+    /// The file `icon` names, or the first place it was looked for when it does not exist
+    pub fn locate(&self, icon: &str) -> PathBuf {
+        let candidates = self.candidates(icon);
+        candidates
+            .iter()
+            .find(|path| path.is_file())
+            .or(candidates.first())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Whether `icon` is a name looked up in the icon dirs, rather than a path
+    pub fn is_name(icon: &str) -> bool {
+        let icon = icon.replace('\\', "/");
+        !icon.starts_with("~/")
+            && Path::new(&icon)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+    }
+
+    /// Every place `icon` is looked for, in order
+    fn candidates(&self, icon: &str) -> Vec<PathBuf> {
+        if icon.trim().is_empty() {
+            return vec![];
+        }
+        if Self::is_name(icon) {
+            let icon = icon.replace('\\', "/");
+            self.dirs
+                .iter()
+                .chain(std::iter::once(&self.config_dir))
+                .map(|dir| dir.join(&icon))
+                .collect()
+        } else {
+            vec![absolute(&self.config_dir, icon)]
+        }
+    }
+}
+
+/// `path` with `~/` taken from the home directory and relative paths from `base`
+fn absolute(base: &Path, path: &str) -> PathBuf {
+    let path = path.trim().replace('\\', "/");
+    match path.strip_prefix("~/").zip(dirs::home_dir()) {
+        Some((rest, home)) => home.join(rest),
+        None => base.join(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn icons_are_found_in_order() {
+        let root = std::env::temp_dir().join(format!("keydeck-icon-paths-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["conf/mine", "default", "themes"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "conf/mine/a.png",
+            "default/a.png",
+            "default/b.png",
+            "conf/c.png",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        let themes = root.join("themes").to_string_lossy().into_owned();
+        let paths = IconPaths::with_default_dir(
+            &root.join("conf/config.yaml"),
+            Some(&["mine".to_string(), themes]),
+            root.join("default"),
+        );
+
+        assert_eq!(paths.dirs().len(), 3);
+        assert_eq!(paths.resolve("a.png"), Some(root.join("conf/mine/a.png")));
+        assert_eq!(paths.resolve("b.png"), Some(root.join("default/b.png")));
+        assert_eq!(paths.resolve("c.png"), Some(root.join("conf/c.png")));
+        assert_eq!(paths.resolve("./c.png"), Some(root.join("conf/./c.png")));
+        assert_eq!(
+            paths.resolve("..\\default\\b.png"),
+            Some(root.join("conf/../default/b.png"))
+        );
+        let absolute = root.join("default/a.png").to_string_lossy().into_owned();
+        assert_eq!(paths.resolve(&absolute), Some(root.join("default/a.png")));
+        assert_eq!(paths.resolve("d.png"), None);
+        assert_eq!(paths.locate("d.png"), root.join("conf/mine/d.png"));
+        assert!(IconPaths::is_name("apps/term.png"));
+        assert!(!IconPaths::is_name("./term.png"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -10,15 +10,51 @@
 use std::path::PathBuf;
 
 pub mod pages;
+pub mod api;
+pub mod builder;
+pub mod condition;
 pub mod device_info;
+pub mod icon_cache;
+pub mod icon_paths;
+pub mod config_file;
+pub mod config_format;
 
 // Re-export commonly used types from pages module
 pub use pages::{
-    KeyDeckConf, Pages, Page, Button, ButtonConfig, Action, TextConfig, DrawConfig,
+    KeyDeckConf, Pages, Page, Button, ButtonBuiltin, ButtonConfig, Action, TextConfig, DrawConfig, Icon, IconLayer,
     ServiceConfig, Macro, MacroCall, FocusChangeRestorePolicy, GraphicType, Direction,
-    ColorMapEntry, RefreshTarget, PressEffectConfig, Encoder,
+    ColorMap, ColorMapEntry, ColorConfig, Gradient, Easing, Chord, RefreshTarget, PressEffectConfig, Encoder, EncoderBuiltin, EncoderWidget, OnError,
+    FocusTarget, FocusSpec, NightMode, NightModeSwitch, GameMode, Theme, ButtonDefaults, Animation, Effects,
+    Enabled, Cooldown, Repeat, Hotkey, Webhook, WebDeck, WebToken, WebScope, WebTls, Snapshots, Switch,
+    Orientation, ExecScope, Chats, TelegramChat, MatrixChat, Calendar, OpenRgb, ConfigTest, TestButton, Accessibility,
+    PressSound, Visibility, Generator, GeneratorSource, PowerAction, AudioDeviceSwitch, WaitForEvents, WaitForEvent,
+    WaitMode, WindowWait, FocusBackend, PageProvider, Timer, TimerStyle, Wallpaper, ShutdownState,
+    ShutdownMode, InputPolling,
+    is_button_key, is_truthy, parse_grid_key,
 };
 
+// Re-export the builders of configurations
+pub use builder::{ActionBuilder, ButtonBuilder, PageBuilder};
+
+// Re-export crash-safe file writes
+pub use config_file::{
+    is_backup_file, save_config_file, write_atomic, BackupManager, MAX_CONFIG_BACKUPS,
+};
+
+// Re-export the configuration formats
+pub use config_format::{
+    parse_config, require_yaml, ConfigFormat, ConfigParseError, CONFIG_FILE_NAMES,
+};
+
+// Re-export icon cache management
+pub use icon_cache::{
+    clear_icon_cache, get_cache_dir, get_icon_cache_dir, icon_cache_stats, prune_icon_cache,
+    IconCacheLimits, IconCacheStats,
+};
+
+// Re-export icon lookup
+pub use icon_paths::IconPaths;
+
 // Re-export device info types
 pub use device_info::{
     DeviceInfo, ButtonLayout, ButtonImage, LcdStrip,
@@ -45,9 +81,15 @@ pub fn get_config_dir() -> PathBuf {
     }
 }
 
-/// Absolute path to the configuration file (`config.yaml`) in the config dir.
+/// Absolute path to the configuration file in the config dir: the first of
+/// [`CONFIG_FILE_NAMES`] that exists, `config.yaml` when none does.
 pub fn get_config_path() -> PathBuf {
-    get_config_dir().join("config.yaml")
+    let dir = get_config_dir();
+    CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| dir.join(CONFIG_FILE_NAMES[0]))
 }
 
 /// Absolute path to the daemon log file (`keydeck.log`) in the config dir.
@@ -62,6 +104,35 @@ pub fn get_log_path() -> PathBuf {
     get_config_dir().join("keydeck.log")
 }
 
+/// Absolute path to the action history (`history.jsonl`) in the config dir: one JSON
+/// object per action sequence run by the daemon, read by `keydeck --history` and the
+/// config UI.
+pub fn get_history_path() -> PathBuf {
+    get_config_dir().join("history.jsonl")
+}
+
+/// Absolute path to the usage statistics (`stats.json`) in the config dir: press counts
+/// and page dwell times per device, kept across restarts.
+pub fn get_stats_path() -> PathBuf {
+    get_config_dir().join("stats.json")
+}
+
+/// Returns the KeyDeck state directory: `$XDG_STATE_HOME/keydeck` (by default
+/// `~/.local/state/keydeck`) on Linux, the config dir on platforms without one.
+pub fn get_state_dir() -> PathBuf {
+    match dirs::state_dir() {
+        Some(dir) => dir.join("keydeck"),
+        None => get_config_dir(),
+    }
+}
+
+/// Absolute path to the runtime state (`state.json`) in the state dir: the page, pin
+/// and heatmap of each device and the context variables, restored when the daemon
+/// starts again.
+pub fn get_state_path() -> PathBuf {
+    get_state_dir().join("state.json")
+}
+
 /// Get the absolute path to the default icon directory.
 pub fn get_icon_dir() -> String {
     get_icon_dir_path().to_string_lossy().into_owned()
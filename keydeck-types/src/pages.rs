@@ -3,9 +3,9 @@
 
 use indexmap::IndexMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct Macro {
     /// Optional default parameter values for the macro.
@@ -17,7 +17,7 @@ pub struct Macro {
     pub actions: serde_yaml_ng::Value,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct MacroCall {
     /// Name of the macro to call.
     #[serde(rename = "macro")]
@@ -29,7 +29,80 @@ pub struct MacroCall {
     pub params: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Macro {
+    /// Expands a call of this macro into its actions: `${param}` placeholders are
+    /// replaced by the given parameters, falling back to the default ones.
+    /// `name` is only used in the error message.
+    pub fn expand(
+        &self,
+        name: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<Vec<Action>, String> {
+        let mut final_params = self.params.clone().unwrap_or_default();
+        final_params.extend(params.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        let mut actions = self.actions.clone();
+        substitute_params(&mut actions, &final_params);
+        serde_yaml_ng::from_value(actions).map_err(|e| {
+            format!(
+                "Failed to parse macro '{}' actions after parameter substitution: {}",
+                name, e
+            )
+        })
+    }
+
+    /// Names of the `${param}` placeholders found in the actions
+    pub fn used_params(&self) -> BTreeSet<String> {
+        let mut params = BTreeSet::new();
+        collect_params(&self.actions, &mut params);
+        params
+    }
+}
+
+/// Recursively substitutes ${param} placeholders in a YAML Value with provided parameters.
+fn substitute_params(value: &mut serde_yaml_ng::Value, params: &HashMap<String, String>) {
+    match value {
+        serde_yaml_ng::Value::String(s) => {
+            for (key, val) in params {
+                *s = s.replace(&format!("${{{}}}", key), val);
+            }
+        }
+        serde_yaml_ng::Value::Sequence(seq) => {
+            for item in seq {
+                substitute_params(item, params);
+            }
+        }
+        serde_yaml_ng::Value::Mapping(map) => {
+            for (_, v) in map {
+                substitute_params(v, params);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_params(value: &serde_yaml_ng::Value, params: &mut BTreeSet<String>) {
+    match value {
+        serde_yaml_ng::Value::String(s) => {
+            let mut rest = s.as_str();
+            while let Some(start) = rest.find("${") {
+                rest = &rest[start + 2..];
+                let Some(end) = rest.find('}') else { break };
+                if end > 0 {
+                    params.insert(rest[..end].to_string());
+                }
+                rest = &rest[end + 1..];
+            }
+        }
+        serde_yaml_ng::Value::Sequence(seq) => {
+            seq.iter().for_each(|item| collect_params(item, params))
+        }
+        serde_yaml_ng::Value::Mapping(map) => map.values().for_each(|v| collect_params(v, params)),
+        _ => {}
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct KeyDeckConf {
     /// Map of template layouts, where each template can define a reusable page layout.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -40,10 +113,14 @@ pub struct KeyDeckConf {
     pub buttons: Option<IndexMap<String, Button>>,
 
     /// Map of color settings, allowing configuration of colors (e.g., background) by name.
-    /// The color format is either "0xRRGGBB" or "0xAARRGGBB".
+    /// Accepts "0xRRGGBB"/"0xAARRGGBB", "#RRGGBB", rgb()/rgba(), hsl()/hsla() and CSS names.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub colors: Option<IndexMap<String, String>>,
 
+    /// Map of named color maps, usable by name wherever a `color_map` is accepted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gradients: Option<IndexMap<String, ColorMap>>,
+
     /// Map of services with external commands that can be executed in background threads.
     /// Services provide cached data that can be referenced in button text via ${service:name}.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -67,6 +144,12 @@ pub struct KeyDeckConf {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub background_image: Option<String>,
 
+    /// Directories searched for icons named by a plain file name, in order, before the
+    /// default icon dir. Relative directories are taken from the directory of the
+    /// configuration file, `~/` from the home directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_dirs: Option<Vec<String>>,
+
     /// List of glob patterns for icons that should be protected from cleanup.
     /// Icons matching these patterns won't be deleted even if unused.
     /// This is useful for icons used by dynamic content or button state switching.
@@ -87,6 +170,123 @@ pub struct KeyDeckConf {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub konsole_apps: Option<Vec<String>>,
 
+    /// Focus tracking backends in the order they are tried (Linux only). When the running
+    /// one dies, the next ready one takes over, and a preferred one that becomes ready
+    /// again replaces it. Defaults to `[kwin, x11]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus_backend: Option<Vec<FocusBackend>>,
+
+    /// Global policy applied when an action sequence fails. Buttons can override it
+    /// with their own `on_error`. When unset, failures are only logged.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_error: Option<OnError>,
+
+    /// Warm-tint filter applied to every rendered key, enabled by schedule or by the
+    /// `night_mode` action.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub night_mode: Option<NightMode>,
+
+    /// Game mode: while on, pages stop following the focus, animations and marquees stop
+    /// and input is read with low latency. Turned on by the `game_mode` action or while a
+    /// window of one of its `classes` is focused.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game_mode: Option<GameMode>,
+
+    /// Aids for low-vision users: spoken page and key announcements, high-contrast keys.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accessibility: Option<Accessibility>,
+
+    /// Sound played on every key press, for decks mounted out of direct view.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub press_sound: Option<PressSound>,
+
+    /// Draws emoji in key texts with a color emoji font, or a monochrome one when none
+    /// is installed (default true). When false, emoji are left to the text font like any
+    /// other character.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<bool>,
+
+    /// Workarounds for misbehaving devices, by device serial number. Applied when the
+    /// device is connected; a reload that changes them reconnects the devices.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quirks: Option<IndexMap<String, DeviceQuirks>>,
+
+    /// Seconds the devices get to reach their `shutdown` state when the daemon exits,
+    /// after which it exits anyway (default 5).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shutdown_timeout: Option<f32>,
+
+    /// Seconds the daemon waits at start for other software driving the decks, such as
+    /// OpenDeck or streamdeck-ui, to quit before it opens them. Without it, it only warns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wait_for_release: Option<f32>,
+
+    /// Rejects the configuration when a page has a key that is neither one of its fields
+    /// nor a button (`button#` or `r#c#`), such as a misspelled field; without it such
+    /// keys are only warned about. `keydeck --validate` rejects them unless this is `false`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+
+    /// Map of named themes: sets of colors, font and default button styling that can be
+    /// swapped as a whole (e.g. "light" and "dark").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub themes: Option<IndexMap<String, Theme>>,
+
+    /// Theme active at startup. Pages can override it with their own `theme`, and the
+    /// `theme` action switches it at runtime.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+
+    /// Style filled into every button that doesn't set the same field itself.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub defaults: Option<ButtonDefaults>,
+
+    /// Locale of `strings` used by `${i18n:key}` (e.g. "de" or "pt_BR"). When unset, the
+    /// locale of the session (`LC_ALL`, `LC_MESSAGES`, `LANG`) is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+
+    /// Translated texts by locale and then by key, shown with `${i18n:key}`. Keys missing
+    /// in the locale are taken from its language (`de` for `de_AT`), then from `en`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strings: Option<IndexMap<String, IndexMap<String, String>>>,
+
+    /// System-wide keyboard shortcuts (e.g. "ctrl+alt+1") mapped to actions, so they
+    /// can be triggered without touching the deck.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hotkeys: Option<IndexMap<String, Hotkey>>,
+
+    /// Named webhooks mapped to actions, run when `/hook/<name>` of the web deck gets a
+    /// POST, so external systems (a finished CI build, a doorbell) can drive the deck.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhooks: Option<IndexMap<String, Webhook>>,
+
+    /// Companion deck served to browsers, mirroring the keys of a connected device.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_deck: Option<WebDeck>,
+
+    /// Keeps a PNG image of each device's keys up to date, for overlays and streaming.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshots: Option<Snapshots>,
+
+    /// Chat services whose unread message counts keys can show.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chats: Option<Chats>,
+
+    /// Calendars whose events keys can show, and whose meetings `join_meeting` opens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calendars: Option<Vec<Calendar>>,
+
+    /// The OpenRGB server lighting profiles are loaded on, by the `openrgb` action and
+    /// the `openrgb` field of pages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub openrgb: Option<OpenRgb>,
+
+    /// Expectations about buttons, checked by `keydeck --test-config`. The daemon
+    /// ignores them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tests: Option<Vec<ConfigTest>>,
+
     /// A collection of pages, each group identified by the device serial number. When a
     /// device is connected, the corresponding page group is loaded.
     /// When no specific page group is found, the "default" page group is used.
@@ -95,6 +295,41 @@ pub struct KeyDeckConf {
 }
 
 impl KeyDeckConf {
+    /// Paths of the keys of pages, templates and layers that are neither a field nor a
+    /// button, e.g. `["default", "Home", "thme"]`. Unknown keys of a page are taken for
+    /// buttons, which are never shown, so a misspelled field would go unnoticed.
+    pub fn unknown_fields(&self) -> Vec<Vec<String>> {
+        fn collect(page: &Page, path: &[&str], unknown: &mut Vec<Vec<String>>) {
+            let at = |rest: &[&str]| path.iter().chain(rest).map(|s| s.to_string()).collect();
+            let mut found: Vec<Vec<String>> = page
+                .buttons
+                .keys()
+                .filter(|key| !is_button_key(key))
+                .map(|key| at(&[key]))
+                .collect();
+            for (layer_name, layer) in page.layers.iter().flatten() {
+                found.extend(
+                    layer
+                        .keys()
+                        .filter(|key| !is_button_key(key))
+                        .map(|key| at(&["layers", layer_name, key])),
+                );
+            }
+            found.sort();
+            unknown.extend(found);
+        }
+        let mut unknown = Vec::new();
+        for (name, template) in self.templates.iter().flatten() {
+            collect(template, &["templates", name], &mut unknown);
+        }
+        for (group_name, group) in &self.page_groups {
+            for (page_name, page) in &group.pages {
+                collect(page, &[group_name, page_name], &mut unknown);
+            }
+        }
+        unknown
+    }
+
     /// Migrates the legacy `window_name` field on every page and template into the
     /// unified [`When`] structure. Applied right after deserialization (daemon loader
     /// and config UI load), so old configs keep auto-switching and get rewritten as
@@ -118,9 +353,257 @@ impl KeyDeckConf {
             }
         }
     }
+
+    /// Fills the `defaults` block into every button definition and page button that
+    /// lacks those fields. Used by the daemon after template resolution; the config UI
+    /// never calls it, so saved buttons keep only what the user wrote.
+    pub fn apply_button_defaults(&mut self) {
+        let Some(defaults) = self.defaults.clone() else {
+            return;
+        };
+        if let Some(buttons) = &mut self.buttons {
+            for (_, button) in buttons.iter_mut() {
+                defaults.apply(button);
+            }
+        }
+        for (_, pages) in self.page_groups.iter_mut() {
+            for (_, page) in pages.pages.iter_mut() {
+                for (_, config) in page.all_buttons_mut() {
+                    if let ButtonConfig::Detailed(button) = config {
+                        defaults.apply(button);
+                    }
+                }
+                if let Some(generator) = &mut page.generate {
+                    for button in generator.buttons.iter_mut() {
+                        defaults.apply(button);
+                    }
+                }
+                if let Some(provider) = &mut page.provider {
+                    for button in provider.buttons.iter_mut() {
+                        defaults.apply(button);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replaces every gradient name in the color maps of buttons and `defaults` by the
+    /// color map of the `gradients` section. Fails on an unknown name. Used by the daemon
+    /// after [`apply_button_defaults`](Self::apply_button_defaults).
+    pub fn resolve_gradients(&mut self) -> Result<(), String> {
+        let gradients = self.gradients.clone().unwrap_or_default();
+        let resolve = |color_map: &mut ColorMap| {
+            let ColorMap::Named(name) = color_map else {
+                return Ok(());
+            };
+            match gradients.get(name.as_str()) {
+                Some(ColorMap::Named(other)) => Err(format!(
+                    "Gradient '{}' names another gradient, '{}'",
+                    name, other
+                )),
+                Some(gradient) => {
+                    *color_map = gradient.clone();
+                    Ok(())
+                }
+                None => Err(format!("Unknown gradient '{}'", name)),
+            }
+        };
+        let resolve_color = |color: &mut Option<ColorConfig>| match color {
+            Some(ColorConfig::Mapped { color_map, .. }) => resolve(color_map),
+            _ => Ok(()),
+        };
+
+        if let Some(defaults) = &mut self.defaults {
+            resolve_color(&mut defaults.background)?;
+            resolve_color(&mut defaults.text_color)?;
+        }
+        let definitions = self
+            .buttons
+            .iter_mut()
+            .flat_map(|buttons| buttons.values_mut());
+        let pages = self
+            .templates
+            .iter_mut()
+            .flat_map(|templates| templates.values_mut())
+            .chain(
+                self.page_groups
+                    .values_mut()
+                    .flat_map(|group| group.pages.values_mut()),
+            );
+        let mut buttons: Vec<&mut Button> = definitions.collect();
+        for page in pages {
+            let layers = page.layers.iter_mut().flatten();
+            let configs = page
+                .buttons
+                .values_mut()
+                .chain(layers.flat_map(|(_, layer)| layer.values_mut()));
+            buttons.extend(configs.filter_map(|config| match config {
+                ButtonConfig::Detailed(button) => Some(button),
+                ButtonConfig::Template(_) => None,
+            }));
+            buttons.extend(page.generate.iter_mut().flat_map(|g| g.buttons.iter_mut()));
+            buttons.extend(page.provider.iter_mut().flat_map(|p| p.buttons.iter_mut()));
+        }
+        for button in buttons {
+            resolve_color(&mut button.background)?;
+            resolve_color(&mut button.text_color)?;
+            for draw in button.draw.iter_mut().flatten() {
+                if let Some(color_map) = &mut draw.color_map {
+                    resolve(color_map)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves `extends` in the button definitions and the page buttons: each takes the
+    /// fields it leaves unset from the definition it names, which may extend another in
+    /// turn. Fails on an unknown name or a cycle. Used by the daemon after template
+    /// resolution, before [`apply_button_defaults`](Self::apply_button_defaults).
+    pub fn resolve_button_extends(&mut self) -> Result<(), String> {
+        let definitions = self.buttons.clone().unwrap_or_default();
+        let mut resolved = IndexMap::new();
+        for name in definitions.keys() {
+            let button = resolve_button(name, &definitions, &mut Vec::new())?;
+            resolved.insert(name.clone(), button);
+        }
+        for (group, pages) in self.page_groups.iter_mut() {
+            for (page_name, page) in pages.pages.iter_mut() {
+                for (button_name, config) in page.all_buttons_mut() {
+                    let ButtonConfig::Detailed(button) = config else {
+                        continue;
+                    };
+                    let Some(parent) = button.extends.take() else {
+                        continue;
+                    };
+                    let parent = resolved.get(&parent).ok_or_else(|| {
+                        format!(
+                            "Button '{}' of page '{}.{}' extends unknown button '{}'",
+                            button_name, group, page_name, parent
+                        )
+                    })?;
+                    button.inherit(parent);
+                }
+            }
+        }
+        if self.buttons.is_some() {
+            self.buttons = Some(resolved);
+        }
+        Ok(())
+    }
+
+    /// Gives the device `to` a copy of the page group `from`, e.g. a second deck starting
+    /// from the pages of the first one. Fails when `to` already has its own page group.
+    pub fn clone_page_group(&mut self, from: &str, to: &str) -> Result<(), String> {
+        let to = Self::free_page_group_key(&self.page_groups, to)?;
+        let pages = self
+            .page_groups
+            .get(from)
+            .ok_or_else(|| format!("No page group '{}'", from))?
+            .clone();
+        self.page_groups.insert(to, pages);
+        Ok(())
+    }
+
+    /// Moves the page group `from` to `to` in place, along with the hotkeys and webhooks
+    /// bound to `from`: for a device replaced by one with another serial number.
+    pub fn rekey_page_group(&mut self, from: &str, to: &str) -> Result<(), String> {
+        if from == "default" {
+            return Err(
+                "The default page group applies to every device; clone it instead".to_string(),
+            );
+        }
+        let to = Self::free_page_group_key(&self.page_groups, to)?;
+        let (index, _, pages) = self
+            .page_groups
+            .shift_remove_full(from)
+            .ok_or_else(|| format!("No page group '{}'", from))?;
+        self.page_groups.shift_insert(index, to.clone(), pages);
+        for hotkey in self
+            .hotkeys
+            .iter_mut()
+            .chain(self.webhooks.iter_mut())
+            .flat_map(|hotkeys| hotkeys.values_mut())
+        {
+            if let Hotkey::Detailed { device, .. } = hotkey {
+                if device.as_deref() == Some(from) {
+                    *device = Some(to.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Icon images named by the buttons, every layer of layered icons: button definitions,
+    /// templates and pages
+    pub fn icons(&self) -> BTreeSet<String> {
+        let buttons = self.buttons.iter().flat_map(|buttons| buttons.values());
+        let pages = self
+            .templates
+            .iter()
+            .flat_map(|templates| templates.values())
+            .chain(
+                self.page_groups
+                    .values()
+                    .flat_map(|group| group.pages.values()),
+            )
+            .flat_map(|page| page.all_buttons().map(|(_, config)| config))
+            .filter_map(|config| match config {
+                ButtonConfig::Detailed(button) => Some(button),
+                ButtonConfig::Template(_) => None,
+            });
+        buttons
+            .chain(pages)
+            .filter_map(|button| button.icon.as_ref())
+            .flat_map(Icon::images)
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Every icon name of [`Self::icons`], to point them to other files
+    pub fn icons_mut(&mut self) -> Vec<&mut String> {
+        let buttons = self
+            .buttons
+            .iter_mut()
+            .flat_map(|buttons| buttons.values_mut());
+        let pages = self
+            .templates
+            .iter_mut()
+            .flat_map(|templates| templates.values_mut())
+            .chain(
+                self.page_groups
+                    .values_mut()
+                    .flat_map(|group| group.pages.values_mut()),
+            )
+            .flat_map(|page| page.all_buttons_mut().map(|(_, config)| config))
+            .filter_map(|config| match config {
+                ButtonConfig::Detailed(button) => Some(button),
+                ButtonConfig::Template(_) => None,
+            });
+        buttons
+            .chain(pages)
+            .filter_map(|button| button.icon.as_mut())
+            .flat_map(Icon::images_mut)
+            .collect()
+    }
+
+    /// `key` trimmed, when no page group uses it yet
+    fn free_page_group_key(
+        page_groups: &IndexMap<String, Pages>,
+        key: &str,
+    ) -> Result<String, String> {
+        let key = key.trim();
+        if key.is_empty() {
+            return Err("A page group needs a device serial number".to_string());
+        }
+        if page_groups.contains_key(key) {
+            return Err(format!("'{}' already has its own page group", key));
+        }
+        Ok(key.to_string())
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Pages {
     /// Optional main page name; if provided, used as the default page in the group. Defaults
     /// to the first page in the group if not specified.
@@ -136,13 +619,188 @@ pub struct Pages {
     #[serde(default)]
     pub press_effect: PressEffectConfig,
 
+    /// Highest number of key image updates sent to the device per second; faster
+    /// redraws are coalesced. 0 sends every update at once. Default: 30.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_fps: Option<u32>,
+
+    /// How the device is mounted; keys and their images are turned to match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<Orientation>,
+
+    /// Seconds a newly focused window must keep the focus before pages follow it, so
+    /// windows passed while alt-tabbing are ignored. Default: 0.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_debounce: Option<f32>,
+
+    /// How often the keys are read, trading CPU for latency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_polling: Option<InputPolling>,
+
+    /// Button (from 1) that shows a badge while the page is pinned with the `pin` action.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin_indicator: Option<u8>,
+
+    /// Pages shown in turn, for a deck used as a dashboard.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<Rotation>,
+
+    /// Actions executed once per daemon run, when the device shows its first page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_start: Option<Vec<Action>>,
+
+    /// Actions executed when the device is connected, also when it is found at startup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_connect: Option<Vec<Action>>,
+
+    /// Actions executed after the device was disconnected; its keys are no longer usable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_disconnect: Option<Vec<Action>>,
+
+    /// What the keys show once the daemon exits. When unset, the device is shut down.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shutdown: Option<ShutdownState>,
+
     /// Individual pages within the page group, each identified by a title.
     #[serde(flatten)]
     pub pages: IndexMap<String, Page>,
 }
 
+impl Default for Pages {
+    fn default() -> Self {
+        Pages {
+            main_page: None,
+            restore_mode: default_restore_mode(),
+            press_effect: PressEffectConfig::default(),
+            max_fps: None,
+            orientation: None,
+            focus_debounce: None,
+            input_polling: None,
+            pin_indicator: None,
+            rotation: None,
+            on_start: None,
+            on_connect: None,
+            on_disconnect: None,
+            shutdown: None,
+            pages: IndexMap::new(),
+        }
+    }
+}
+
+impl Pages {
+    /// Renames the `r2c3` style button keys of all pages to `buttonN`, for a device with
+    /// the given number of rows and columns. Keys that cannot be translated are dropped,
+    /// and a message describing each of them is returned.
+    pub fn resolve_grid_keys(&mut self, rows: usize, columns: usize) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (page_name, page) in self.pages.iter_mut() {
+            let place = format!("Page '{}'", page_name);
+            resolve_button_keys(&mut page.buttons, &place, rows, columns, &mut problems);
+            for (layer_name, layer) in page.layers.iter_mut().flatten() {
+                let place = format!("Page '{}', layer '{}'", page_name, layer_name);
+                resolve_button_keys(layer, &place, rows, columns, &mut problems);
+            }
+            for chord in page.chords.iter_mut().flatten() {
+                for key in chord.keys.iter_mut() {
+                    let Some((row, column)) = parse_grid_key(key) else {
+                        continue;
+                    };
+                    if row > rows || column > columns {
+                        problems.push(format!(
+                            "Page '{}': chord key '{}' is outside the {}x{} layout of the device",
+                            page_name, key, rows, columns
+                        ));
+                        continue;
+                    }
+                    *key = format!("button{}", (row - 1) * columns + column);
+                }
+            }
+        }
+        problems
+    }
+
+    /// Whether any page addresses a button by row and column
+    pub fn has_grid_keys(&self) -> bool {
+        self.pages.values().any(|page| {
+            let chord_keys = page.chords.iter().flatten().flat_map(|chord| &chord.keys);
+            page.all_buttons()
+                .map(|(key, _)| key)
+                .chain(chord_keys)
+                .any(|key| parse_grid_key(key).is_some())
+        })
+    }
+}
+
+/// Renames the `r2c3` style keys of `buttons` to `buttonN`, see
+/// [`Pages::resolve_grid_keys`]. `place` names the buttons in the problems.
+fn resolve_button_keys(
+    buttons: &mut HashMap<String, ButtonConfig>,
+    place: &str,
+    rows: usize,
+    columns: usize,
+    problems: &mut Vec<String>,
+) {
+    let grid_keys: Vec<(String, usize, usize)> = buttons
+        .keys()
+        .filter_map(|key| parse_grid_key(key).map(|(row, column)| (key.clone(), row, column)))
+        .collect();
+    for (key, row, column) in grid_keys {
+        let Some(config) = buttons.remove(&key) else {
+            continue;
+        };
+        if row > rows || column > columns {
+            problems.push(format!(
+                "{}: button '{}' is outside the {}x{} layout of the device",
+                place, key, rows, columns
+            ));
+            continue;
+        }
+        let index_key = format!("button{}", (row - 1) * columns + column);
+        if buttons.contains_key(&index_key) {
+            problems.push(format!(
+                "{}: '{}' and '{}' are the same button, using '{}'",
+                place, key, index_key, index_key
+            ));
+            continue;
+        }
+        buttons.insert(index_key, config);
+    }
+}
+
+/// Whether `key` names a button of a page: `button#` from 1, or `r#c#`
+pub fn is_button_key(key: &str) -> bool {
+    let numbered = key
+        .strip_prefix("button")
+        .and_then(|index| index.parse::<u8>().ok())
+        .is_some_and(|index| index > 0);
+    numbered || parse_grid_key(key).is_some()
+}
+
+/// Parses a button key given by row and column, like `r2c3`, into the one-based row and
+/// column
+pub fn parse_grid_key(key: &str) -> Option<(usize, usize)> {
+    let (row, column) = key.strip_prefix('r')?.split_once('c')?;
+    let row: usize = row.parse().ok()?;
+    let column: usize = column.parse().ok()?;
+    (row > 0 && column > 0).then_some((row, column))
+}
+
+/// How a device is mounted, relative to its normal position
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    #[default]
+    Normal,
+    /// Turned a quarter clockwise
+    Rotate90,
+    /// Upside down
+    Rotate180,
+    /// Turned a quarter counter-clockwise
+    Rotate270,
+}
+
 /// Configuration for the visual effect applied to buttons when pressed.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum PressEffectConfig {
     /// Shrink the content when pressed (Lanczos resize). No canvas size reduction.
@@ -215,7 +873,7 @@ fn default_emboss_pixels() -> u32 {
     2
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct ServiceConfig {
     /// Command to execute via bash
@@ -264,20 +922,46 @@ impl Default for KeyDeckConf {
             templates: None,
             buttons: None,
             colors: None,
+            gradients: None,
             services: None,
             macros: None,
             tick_time: default_tick_time(),
             brightness: default_brightness(),
             background_image: None,
+            icon_dirs: None,
             protected_icons: None,
             konsole_context: false,
             konsole_apps: None,
+            focus_backend: None,
+            on_error: None,
+            night_mode: None,
+            game_mode: None,
+            accessibility: None,
+            press_sound: None,
+            emoji: None,
+            quirks: None,
+            shutdown_timeout: None,
+            wait_for_release: None,
+            strict: None,
+            themes: None,
+            theme: None,
+            defaults: None,
+            locale: None,
+            strings: None,
+            hotkeys: None,
+            webhooks: None,
+            web_deck: None,
+            snapshots: None,
+            chats: None,
+            calendars: None,
+            openrgb: None,
+            tests: None,
             page_groups: IndexMap::new(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "lowercase", deny_unknown_fields)]
 pub enum FocusChangeRestorePolicy {
     /// Keeps the current page when changing focus between configurations.
@@ -297,7 +981,7 @@ fn default_restore_mode() -> FocusChangeRestorePolicy {
 
 /// A single filter value inside a `when` group, or a list of them.
 /// A list means OR: the filter matches if ANY listed value matches.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(untagged)]
 pub enum WhenValue {
     One(String),
@@ -320,12 +1004,13 @@ impl WhenValue {
 /// matches, and a group matches when ALL its key/value filters match. Each value may
 /// itself be a list (OR among values). Reserved keys `window`/`class`/`title` match the
 /// focused window (case-insensitive substring); any other key matches an external
-/// context variable (exact match, set via `keydeck --set key=value`).
+/// context variable (exact match, set via `keydeck --set key=value`). Under KWin the
+/// focus listener keeps the `desktop` and `activity` variables up to date.
 ///
 /// In YAML this accepts either a single mapping (one group) or a list of mappings
 /// (many groups), and is serialized back in the same shape. Values must be strings
 /// (quote numbers, e.g. `git: "1"`).
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct When {
     pub groups: Vec<IndexMap<String, WhenValue>>,
 }
@@ -378,7 +1063,7 @@ impl<'de> Deserialize<'de> for When {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct Page {
     /// Legacy per-page window pattern. Superseded by `when`; still accepted on read and
     /// migrated into `when` (see [`KeyDeckConf::migrate_legacy_window_name`]), but never
@@ -395,121 +1080,1461 @@ pub struct Page {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub lock: Option<bool>,
 
-    /// List of templates this page/template inherits from. Buttons are merged in order (parent first, child overrides).
-    /// Templates can also inherit from other templates, enabling multi-level inheritance.
+    /// Seconds `when` must keep matching before this page is shown automatically.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub inherits: Option<Vec<String>>,
+    pub switch_delay: Option<f32>,
 
-    /// Actions to execute on each tick event (fires every 1 second).
-    /// Useful for periodic updates, status checks, or time-based automations.
+    /// Seconds this page may stay unused before the main page is shown again, so pages
+    /// shown for a moment (volume, confirmations) clear up after themselves.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub on_tick: Option<Vec<Action>>,
+    pub auto_return: Option<f32>,
 
-    /// Map of encoder configurations for this page, referenced by encoder index in the form
-    /// of "encoder#", where "#" is the encoder index starting from 1.
-    /// Encoders support twist (left/right rotation) and press actions.
+    /// Theme used while this page is shown, overriding the globally active theme.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub encoders: Option<IndexMap<String, Encoder>>,
+    pub theme: Option<String>,
 
-    /// Map of button configurations for this page, referenced by button index in the form
-    /// of "button#", where "#" is the button index starting from 1.
-    #[serde(flatten)]
-    pub buttons: HashMap<String, ButtonConfig>,
-}
-
-/// Configuration for a rotary encoder (knob).
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(deny_unknown_fields)]
-pub struct Encoder {
-    /// Actions to execute when the encoder is twisted clockwise (right).
+    /// Image spread across all keys of the page, behind the content of each button.
+    /// See [`Wallpaper`].
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub twist_right: Option<Vec<Action>>,
+    pub wallpaper: Option<Wallpaper>,
 
-    /// Actions to execute when the encoder is twisted counter-clockwise (left).
+    /// OpenRGB lighting profile loaded whenever this page is shown, so the rest of the
+    /// desk follows the deck.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub twist_left: Option<Vec<Action>>,
+    pub openrgb: Option<String>,
 
-    /// Actions to execute when the encoder is pressed (pushed down and released).
+    /// While these conditions don't hold the page is hidden: `when` doesn't select it and
+    /// `jump` refuses it. See [`Visibility`].
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub press: Option<Vec<Action>>,
-}
+    pub visible_when: Option<Visibility>,
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(deny_unknown_fields)]
-pub struct Button {
-    /// Icon image filename for the button display.
+    /// Creates a button for every entry of a directory or playlist when the configuration
+    /// is loaded, continuing on more pages when they don't fit. See [`Generator`].
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub icon: Option<String>,
+    pub generate: Option<Generator>,
 
-    /// Background color (in the format "0xRRGGBB" or "0xAARRGGBB") for the button display,
-    /// or a color reference to a named color in the configuration.
+    /// Buttons provided by an external program or web address, laid out like generated
+    /// buttons and replaced whenever the provider sends new ones. See [`PageProvider`].
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub background: Option<String>,
+    pub provider: Option<PageProvider>,
 
-    /// Graphics drawing configuration for the button. An array of graphics that will be drawn
-    /// in order (first item drawn first, last item on top).
+    /// List of templates this page/template inherits from. Buttons are merged in order (parent first, child overrides).
+    /// Templates can also inherit from other templates, enabling multi-level inheritance.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub draw: Option<Vec<DrawConfig>>,
+    pub inherits: Option<Vec<String>>,
 
-    /// Text configuration for the button label or caption.
+    /// Actions to execute on each tick event (fires every 1 second).
+    /// Useful for periodic updates, status checks, or time-based automations.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub text: Option<TextConfig>,
+    pub on_tick: Option<Vec<Action>>,
 
-    /// Outline color (in the format "0xRRGGBB") for text rendering.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub outline: Option<String>,
+    /// Seconds between ticks while this page is shown, instead of the global `tick_time`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tick: Option<f64>,
 
-    /// Text color (in the format "0xRRGGBB") for text rendering. Defaults to white (0xFFFFFF).
+    /// Actions run by pressing several keys of this page together. See [`Chord`].
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub text_color: Option<String>,
+    pub chords: Option<Vec<Chord>>,
 
-    /// Whether this button should be refreshed automatically by the `refresh:` action (no parameters).
-    /// When true, the button will be included in automatic refresh cycles (e.g., on_tick).
-    /// When None, automatic detection is used (see is_dynamic_computed).
+    /// Named sets of buttons shown over the page while a button with the layer as its
+    /// `modifier` is held, keyed like the buttons of the page. Keys a layer leaves out
+    /// keep the button of the page.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub dynamic: Option<bool>,
-
-    /// Computed at configuration load time: whether this button contains dynamic parameters.
-    /// Automatically detected by scanning text, draw.value, and actions for ${provider:arg} patterns.
-    /// This field is not serialized (not part of YAML config).
-    /// Used as fallback when `dynamic` is None.
-    #[serde(skip, default)]
-    pub is_dynamic_computed: bool,
+    pub layers: Option<IndexMap<String, HashMap<String, ButtonConfig>>>,
 
-    /// List of actions that will be executed when the button is pressed.
+    /// Map of encoder configurations for this page, referenced by encoder index in the form
+    /// of "encoder#", where "#" is the encoder index starting from 1.
+    /// Encoders support twist (left/right rotation) and press actions.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub actions: Option<Vec<Action>>,
+    pub encoders: Option<IndexMap<String, Encoder>>,
+
+    /// Map of button configurations for this page, referenced by button index in the form
+    /// of "button#", where "#" is the button index starting from 1.
+    #[serde(flatten)]
+    pub buttons: HashMap<String, ButtonConfig>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(untagged, deny_unknown_fields)]
-pub enum ButtonConfig {
-    /// Reference to a template name to use as the button configuration.
-    Template(String),
+impl Page {
+    /// The buttons of the page, then those of its `layers`, with their keys
+    pub fn all_buttons(&self) -> impl Iterator<Item = (&String, &ButtonConfig)> {
+        let layers = self.layers.iter().flat_map(|layers| layers.values());
+        self.buttons.iter().chain(layers.flatten())
+    }
 
-    /// Detailed configuration for a button, including icon, background, and actions.
-    Detailed(Button),
+    /// The buttons of [`Self::all_buttons`], to change them
+    pub fn all_buttons_mut(&mut self) -> impl Iterator<Item = (&String, &mut ButtonConfig)> {
+        let layers = self
+            .layers
+            .iter_mut()
+            .flat_map(|layers| layers.values_mut());
+        self.buttons.iter_mut().chain(layers.flatten())
+    }
+
+    /// Checks that the `modifier` of every button names one of the `layers` of the page
+    pub fn validate_layers(&self) -> Result<(), String> {
+        for (key, config) in self.all_buttons() {
+            let ButtonConfig::Detailed(Button {
+                modifier: Some(layer),
+                ..
+            }) = config
+            else {
+                continue;
+            };
+            if !self
+                .layers
+                .as_ref()
+                .is_some_and(|layers| layers.contains_key(layer))
+            {
+                return Err(format!(
+                    "Button '{}' is the modifier of unknown layer '{}'",
+                    key, layer
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// One image sliced across the key grid of a page. The image is scaled to cover the
+/// keys and the gaps between them, so the keys show it like a window frame would.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(untagged, deny_unknown_fields)]
-pub enum TextConfig {
-    /// Simple text string displayed on the button.
-    Simple(String),
+pub enum Wallpaper {
+    /// Image filename, found like icons are.
+    File(String),
 
-    /// Detailed configuration for text, with optional font size.
+    /// Image with the gap between keys given.
     Detailed {
-        /// Text to display on the button.
-        value: String,
+        /// Image filename.
+        image: String,
 
-        /// Font size for the text, optional.
+        /// Space between two keys, relative to the key width (default: 0.2)
         #[serde(skip_serializing_if = "Option::is_none")]
-        font_size: Option<f32>,
+        gap: Option<f32>,
     },
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(deny_unknown_fields)]
+impl Wallpaper {
+    /// Space between two keys assumed when none is given, relative to the key width
+    pub const DEFAULT_GAP: f32 = 0.2;
+
+    /// Image filename of the wallpaper
+    pub fn image(&self) -> &str {
+        match self {
+            Wallpaper::File(image) => image,
+            Wallpaper::Detailed { image, .. } => image,
+        }
+    }
+
+    /// Space between two keys, relative to the key width
+    pub fn gap(&self) -> f32 {
+        match self {
+            Wallpaper::Detailed { gap: Some(gap), .. } => gap.max(0.0),
+            _ => Self::DEFAULT_GAP,
+        }
+    }
+}
+
+/// Keys pressed together to run actions, a second layer of functions on a page. A chord
+/// runs as soon as all its keys are held, if they were pressed within `window` of each
+/// other; its keys then skip their own actions when released.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Chord {
+    /// Keys of the chord, as `button#` or by row and column as `r#c#`
+    pub keys: Vec<String>,
+
+    /// Seconds from the press of the first key to the last. Default: 0.2.
+    #[serde(default = "default_chord_window")]
+    pub window: f32,
+
+    pub actions: Vec<Action>,
+}
+
+impl Chord {
+    /// Seconds from the first key pressed to the last when no `window` is given
+    pub const DEFAULT_WINDOW: f32 = 0.2;
+
+    /// Checks that the chord has at least two different keys, named as buttons are.
+    pub fn validate(&self) -> Result<(), String> {
+        for (index, key) in self.keys.iter().enumerate() {
+            if !is_button_key(key) {
+                return Err(format!(
+                    "Invalid chord key '{}', expected 'button#' or 'r#c#'",
+                    key
+                ));
+            }
+            if self.keys[..index].contains(key) {
+                return Err(format!("Chord key '{}' is listed twice", key));
+            }
+        }
+        if self.keys.len() < 2 {
+            return Err(format!(
+                "A chord needs at least two keys, got [{}]",
+                self.keys.join(", ")
+            ));
+        }
+        if self.window.is_nan() || self.window <= 0.0 {
+            return Err(format!(
+                "Chord window must be positive, got {}",
+                self.window
+            ));
+        }
+        Ok(())
+    }
+
+    /// The buttons (from 1) of the keys given as `button#`, which all are once the
+    /// `r#c#` keys are resolved for a device
+    pub fn buttons(&self) -> impl Iterator<Item = u8> + '_ {
+        self.keys
+            .iter()
+            .filter_map(|key| key.strip_prefix("button")?.parse().ok())
+            .filter(|button| *button > 0)
+    }
+}
+
+fn default_chord_window() -> f32 {
+    Chord::DEFAULT_WINDOW
+}
+
+/// The final state of the keys of a device, when the daemon exits: `clear`, `keep` or
+/// an image.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ShutdownState {
+    Mode(ShutdownMode),
+
+    /// An image spread across all keys, like the `wallpaper` of a page.
+    Image(Wallpaper),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShutdownMode {
+    /// Keys are cleared.
+    Clear,
+
+    /// Keys keep showing the last page.
+    Keep,
+}
+
+/// Source of the entries of a generated page
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GeneratorSource {
+    /// The files and directories in `path`
+    Dir,
+    /// The entries of the `.m3u` or `.pls` playlist at `path`
+    Playlist,
+    /// The bookmarks in the folder `path` of a web browser
+    Bookmarks,
+    /// The items of a checklist: `items`, or the lines of the text file at `path`
+    Checklist,
+}
+
+/// Buttons generated from the entries of a source. In `button` and `actions`, `${name}`
+/// is replaced by the name of the entry, `${file}` by its file name (the site of a
+/// bookmark), `${path}` by its full path or URL and `${icon}` by the favicon of a bookmark.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Generator {
+    /// Kind of source
+    pub from: GeneratorSource,
+
+    /// Directory, playlist or text file; a leading `~` is the home directory. For
+    /// bookmarks, the folder as names separated by `/`, starting from `toolbar`, `menu`,
+    /// `other` or `mobile`. Not needed by a checklist with `items`.
+    #[serde(default)]
+    pub path: String,
+
+    /// Items of a checklist, in place of a text file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<String>>,
+
+    /// Browser whose bookmarks are read: `firefox`, `chrome`, `chromium`, `brave` or
+    /// `edge`. Default: the first of them that has bookmarks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub browser: Option<String>,
+
+    /// Only entries whose file name matches this pattern (`*` and `?` wildcards,
+    /// case-insensitive), e.g. "*.m3u".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+
+    /// Button created for each entry. Default: the name of the entry as text, with the
+    /// favicon and an `open` of the address for bookmarks. Stored as raw YAML to allow
+    /// substitution before parsing into a [`Button`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub button: Option<serde_yaml_ng::Value>,
+
+    /// Actions of each button, in place of the `actions` of `button`.
+    #[serde(alias = "action", skip_serializing_if = "Option::is_none")]
+    pub actions: Option<serde_yaml_ng::Value>,
+
+    /// Buttons created from the entries when the configuration is loaded, laid out on the
+    /// keys of each device when it shows the page. Not part of the YAML config.
+    #[serde(skip)]
+    pub buttons: Vec<Button>,
+}
+
+/// Source of the buttons of a page: a command printing them or a web address answering
+/// with them, as a JSON array of buttons written like in the configuration, e.g.
+/// `[{"text": "web-1", "icon": "pod.png", "actions": [{"exec": "..."}]}]`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PageProvider {
+    /// Shell command printing the buttons
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exec: Option<String>,
+
+    /// Web address answering a GET with the buttons
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    /// Seconds between two reads. Default: the buttons are read when the configuration is
+    /// loaded only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<f64>,
+
+    /// The command keeps running and prints the buttons again, on one line, whenever they
+    /// change. Default: false.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+
+    /// Longest wait for the command or the web address, in seconds. Default: 10.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<f64>,
+
+    /// Buttons last provided. Not part of the YAML config.
+    #[serde(skip)]
+    pub buttons: Vec<Button>,
+}
+
+/// Default button of a checklist item: its label, struck through and grayed out once
+/// done, toggled by a press
+const CHECKLIST_BUTTON: &str = r#"
+text: "${checklist:label:${path}}"
+text_color:
+  value: "${checklist:done:${path}}"
+  range: [0, 1]
+  color_map: [[0, "0xFFFFFF"], [100, "0x808080"]]
+actions:
+  - check: "${path}"
+"#;
+
+impl Generator {
+    /// The button of an entry, given the values of its placeholders
+    pub fn button_for(&self, entry: &HashMap<String, String>) -> Result<Button, String> {
+        let default = match self.from {
+            GeneratorSource::Checklist => serde_yaml_ng::from_str(CHECKLIST_BUTTON).ok(),
+            _ => None,
+        };
+        let mut button = self.button.clone().or(default).unwrap_or_else(|| {
+            let mut default = serde_yaml_ng::Mapping::new();
+            default.insert("text".into(), "${name}".into());
+            if self.from == GeneratorSource::Bookmarks {
+                if entry.get("icon").is_some_and(|icon| !icon.is_empty()) {
+                    default.insert("icon".into(), "${icon}".into());
+                }
+                let mut open = serde_yaml_ng::Mapping::new();
+                open.insert("open".into(), "${path}".into());
+                default.insert(
+                    "actions".into(),
+                    serde_yaml_ng::Value::Sequence(vec![open.into()]),
+                );
+            }
+            serde_yaml_ng::Value::Mapping(default)
+        });
+        if let (Some(actions), serde_yaml_ng::Value::Mapping(map)) = (&self.actions, &mut button) {
+            map.insert("actions".into(), actions.clone());
+        }
+        substitute_params(&mut button, entry);
+        serde_yaml_ng::from_value(button).map_err(|e| format!("Invalid generated button: {}", e))
+    }
+}
+
+/// Configuration for a rotary encoder (knob).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Encoder {
+    /// Actions to execute when the encoder is twisted clockwise (right).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twist_right: Option<Vec<Action>>,
+
+    /// Actions to execute when the encoder is twisted counter-clockwise (left).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twist_left: Option<Vec<Action>>,
+
+    /// Actions to execute when the encoder is pressed (pushed down and released).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub press: Option<Vec<Action>>,
+
+    /// Actions to execute when the encoder is twisted clockwise while held down, instead
+    /// of the plain twist. Releasing it then runs no `press` actions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub press_twist_right: Option<Vec<Action>>,
+
+    /// Actions to execute when the encoder is twisted counter-clockwise while held down,
+    /// instead of the plain twist.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub press_twist_left: Option<Vec<Action>>,
+
+    /// Value widget controlled by this encoder and shown on the LCD strip segment above it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub widget: Option<EncoderWidget>,
+
+    /// Built-in behavior of twisting the encoder, needing no actions. Twist actions, if
+    /// any, run after it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub builtin: Option<EncoderBuiltin>,
+}
+
+impl Encoder {
+    /// Whether twisting the encoder while it is held down does something of its own
+    pub fn has_press_twist(&self) -> bool {
+        self.press_twist_right.is_some() || self.press_twist_left.is_some()
+    }
+}
+
+/// What twisting an encoder does on its own.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EncoderBuiltin {
+    /// Makes the screen of the device brighter (clockwise) or darker, until the
+    /// configuration is loaded again.
+    Brightness,
+    /// Goes to the next page (clockwise) or the previous one, skipping hidden pages and
+    /// wrapping around.
+    PageScroll,
+}
+
+/// A "knob with readout": the encoder adjusts a value and the LCD strip segment above
+/// it renders a live bar. Exactly one of `var` or `audio` selects the bound value.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct EncoderWidget {
+    /// Context variable adjusted by the encoder (readable as `${var:NAME}`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub var: Option<String>,
+
+    /// Audio sink whose volume is adjusted; use "default" for the default output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<String>,
+
+    /// Lower bound of the value.
+    #[serde(default = "default_widget_min")]
+    pub min: f32,
+
+    /// Upper bound of the value.
+    #[serde(default = "default_widget_max")]
+    pub max: f32,
+
+    /// Amount added or removed per encoder tick.
+    #[serde(default = "default_widget_step")]
+    pub step: f32,
+
+    /// Caption shown above the bar. Supports dynamic parameters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// Bar color (hex or named color). Defaults to white.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+fn default_widget_min() -> f32 {
+    0.0
+}
+
+fn default_widget_max() -> f32 {
+    100.0
+}
+
+fn default_widget_step() -> f32 {
+    5.0
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Button {
+    /// Name of a button definition this button is based on: every field left unset here
+    /// is taken from it. Resolved at load time, see [`KeyDeckConf::resolve_button_extends`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+
+    /// Icon image filename for the button display, or a list of images drawn over
+    /// each other.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<Icon>,
+
+    /// Background color (in the format "0xRRGGBB" or "0xAARRGGBB") for the button display,
+    /// or a color reference to a named color in the configuration. Can also be a color map
+    /// driven by a dynamic value, see [`ColorConfig`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<ColorConfig>,
+
+    /// Graphics drawing configuration for the button. An array of graphics that will be drawn
+    /// in order (first item drawn first, last item on top).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub draw: Option<Vec<DrawConfig>>,
+
+    /// Text configuration for the button label or caption.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<TextConfig>,
+
+    /// Outline color (in the format "0xRRGGBB") for text rendering.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outline: Option<String>,
+
+    /// Text color (in the format "0xRRGGBB") for text rendering. Defaults to white (0xFFFFFF).
+    /// Like `background`, it also accepts a color map.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_color: Option<ColorConfig>,
+
+    /// Blinks the background, switching between colors at a fixed rate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blink: Option<Animation>,
+
+    /// Pulses the background, fading smoothly between colors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pulse: Option<Animation>,
+
+    /// Visual effects applied to the finished button image (grayscale, tint, ...).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effects: Option<Effects>,
+
+    /// Whether the button reacts to presses: `true`/`false`, or a dynamic value checked
+    /// whenever the button is drawn. Disabled buttons are drawn dimmed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<Enabled>,
+
+    /// Time after a press during which further presses are ignored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooldown: Option<Cooldown>,
+
+    /// Runs the actions on press, then again repeatedly while the button is held.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<Repeat>,
+
+    /// Turns the button into a countdown timer: a press starts or pauses it, holding it
+    /// resets it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timer: Option<Timer>,
+
+    /// Sound played when the button is pressed, instead of the `press_sound` one: a sound
+    /// file, `click`, or `none` for silence.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+
+    /// Whether this button should be refreshed automatically by the `refresh:` action (no parameters).
+    /// When true, the button will be included in automatic refresh cycles (e.g., on_tick).
+    /// When None, automatic detection is used (see is_dynamic_computed).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamic: Option<bool>,
+
+    /// Computed at configuration load time: whether this button contains dynamic parameters.
+    /// Automatically detected by scanning text, draw.value, and actions for ${provider:arg} patterns.
+    /// This field is not serialized (not part of YAML config).
+    /// Used as fallback when `dynamic` is None.
+    #[serde(skip, default)]
+    pub is_dynamic_computed: bool,
+
+    /// Name of a layer of the page, shown over the other keys while this button is held.
+    /// A modifier runs no actions. See [`Page::layers`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modifier: Option<String>,
+
+    /// Built-in behavior of pressing the button, needing no actions, such as going to the
+    /// next page. Actions, if any, run after it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub builtin: Option<ButtonBuiltin>,
+
+    /// List of actions that will be executed when the button is pressed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actions: Option<Vec<Action>>,
+
+    /// Error policy for this button's actions, replacing the global `on_error`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_error: Option<OnError>,
+}
+
+impl Button {
+    /// Takes every field `self` leaves unset from `parent`. A field is taken whole, so
+    /// the `actions` of a button replace those of its parent.
+    fn inherit(&mut self, parent: &Button) {
+        fn fill<T: Clone>(field: &mut Option<T>, parent: &Option<T>) {
+            if field.is_none() {
+                *field = parent.clone();
+            }
+        }
+        // Destructured, so that a new field is not forgotten here
+        let Button {
+            extends: _,
+            icon,
+            background,
+            draw,
+            text,
+            outline,
+            text_color,
+            blink,
+            pulse,
+            effects,
+            enabled,
+            cooldown,
+            repeat,
+            timer,
+            sound,
+            dynamic,
+            is_dynamic_computed: _,
+            modifier,
+            builtin,
+            actions,
+            on_error,
+        } = parent;
+        fill(&mut self.icon, icon);
+        fill(&mut self.background, background);
+        fill(&mut self.draw, draw);
+        fill(&mut self.text, text);
+        fill(&mut self.outline, outline);
+        fill(&mut self.text_color, text_color);
+        fill(&mut self.blink, blink);
+        fill(&mut self.pulse, pulse);
+        fill(&mut self.effects, effects);
+        fill(&mut self.enabled, enabled);
+        fill(&mut self.cooldown, cooldown);
+        fill(&mut self.repeat, repeat);
+        fill(&mut self.timer, timer);
+        fill(&mut self.sound, sound);
+        fill(&mut self.dynamic, dynamic);
+        fill(&mut self.modifier, modifier);
+        fill(&mut self.builtin, builtin);
+        fill(&mut self.actions, actions);
+        fill(&mut self.on_error, on_error);
+    }
+}
+
+/// What pressing a button does on its own.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonBuiltin {
+    /// Goes to the next page, skipping hidden pages and wrapping around.
+    NextPage,
+    /// Goes to the previous page, skipping hidden pages and wrapping around.
+    PrevPage,
+    /// Goes to the `main_page`, or the first page without one.
+    MainPage,
+    /// Goes back to the page shown before the current one, and further back on every
+    /// press.
+    Back,
+    /// Pins the shown page, or unpins it, like the `pin: toggle` action.
+    TogglePin,
+    /// Makes the screen brighter, until the configuration is loaded again.
+    BrightnessUp,
+    /// Makes the screen darker, until the configuration is loaded again.
+    BrightnessDown,
+    /// Turns the screen off until the next key press, which only turns it on again.
+    Sleep,
+}
+
+/// The button definition `name` with its `extends` chain resolved
+fn resolve_button(
+    name: &str,
+    definitions: &IndexMap<String, Button>,
+    visited: &mut Vec<String>,
+) -> Result<Button, String> {
+    if visited.iter().any(|seen| seen == name) {
+        visited.push(name.to_string());
+        return Err(format!(
+            "Circular button inheritance detected: {}",
+            visited.join(" → ")
+        ));
+    }
+    let mut button = definitions
+        .get(name)
+        .ok_or_else(|| format!("Button '{}' not found", name))?
+        .clone();
+    if let Some(parent) = button.extends.take() {
+        if !definitions.contains_key(&parent) {
+            return Err(format!(
+                "Button '{}' extends unknown button '{}'",
+                name, parent
+            ));
+        }
+        visited.push(name.to_string());
+        let parent = resolve_button(&parent, definitions, visited)?;
+        visited.pop();
+        button.inherit(&parent);
+    }
+    Ok(button)
+}
+
+/// Window selection for the `focus` action and the `target` of `key` and `text`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum FocusTarget {
+    /// Matched against both window class and title (either may match).
+    Name(String),
+    /// Structured query; all given criteria must match.
+    Detailed(FocusSpec),
+}
+
+/// Structured window query for the `focus` action.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FocusSpec {
+    /// Window class (or process/application name on Windows and macOS).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+
+    /// Window title.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    /// Compare class/title for equality (case-insensitive) instead of substring.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exact: bool,
+
+    /// Desktop file id or path (e.g. "org.kde.konsole"); its `StartupWMClass` is used as class.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub desktop: Option<String>,
+
+    /// Process id owning the window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+
+    /// Focus the Nth matching window (1-based, in window-manager order).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nth: Option<usize>,
+
+    /// Focus the next matching window on every repeated press.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cycle: bool,
+}
+
+impl FocusTarget {
+    /// Human-readable description used in logs and error messages.
+    pub fn describe(&self) -> String {
+        match self {
+            FocusTarget::Name(name) => name.clone(),
+            FocusTarget::Detailed(spec) => {
+                let mut parts = Vec::new();
+                if let Some(class) = &spec.class {
+                    parts.push(format!("class={}", class));
+                }
+                if let Some(title) = &spec.title {
+                    parts.push(format!("title={}", title));
+                }
+                if let Some(desktop) = &spec.desktop {
+                    parts.push(format!("desktop={}", desktop));
+                }
+                if let Some(pid) = spec.pid {
+                    parts.push(format!("pid={}", pid));
+                }
+                parts.join(" ")
+            }
+        }
+    }
+}
+
+/// How a failed action sequence is surfaced to the user.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OnError {
+    /// Write the error to the log (default: true).
+    #[serde(default = "default_on_error_log")]
+    pub log: bool,
+
+    /// Show a desktop notification with the error message.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub notify: bool,
+
+    /// Briefly flash the failing button red.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub flash: bool,
+
+    /// Actions to run after the failure. The error message is available as `${var:last_error}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actions: Option<Vec<Action>>,
+}
+
+fn default_on_error_log() -> bool {
+    true
+}
+
+/// Accessibility aids, for decks used without a clear view of their keys.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Accessibility {
+    /// Speak the name of every page shown and the label of every key pressed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub announce: bool,
+
+    /// Outline every label and never shrink it below `min_font_size`; labels that do
+    /// not fit scroll instead.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub high_contrast: bool,
+
+    /// Smallest label size in high-contrast mode. Default: 20.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_font_size: Option<f32>,
+}
+
+/// The sound of key presses.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PressSound {
+    /// Sound file to play, relative to the config directory, or `click` for the built-in
+    /// click. Default: `click`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample: Option<String>,
+
+    /// Volume from 0.0 to 1.0. Default: 0.5.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+}
+
+/// Workarounds for the firmware bugs of a device, mostly of clones.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceQuirks {
+    /// Flushes the device after every key image instead of once per refresh, for
+    /// devices that drop images left waiting.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub flush_after_image: bool,
+
+    /// Milliseconds to wait between two key images, for devices that lose images sent
+    /// too fast.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_delay: Option<u64>,
+
+    /// Brightness sent to the device for a configured brightness, as `[configured, sent]`
+    /// points in percent joined by straight lines. `[[0, 20], [100, 100]]` keeps a
+    /// device that goes dark below 20 lit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness_curve: Option<Vec<[u8; 2]>>,
+
+    /// Number of input events ignored after the device is connected, for devices that
+    /// report phantom presses when they start.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore_first_events: Option<u32>,
+
+    /// How often the device is told that the daemon is still there, instead of on every
+    /// tick: never, for devices that flicker on it, or every given seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<KeepAlive>,
+}
+
+/// `keep_alive` of a device: `off`, or seconds between two keep-alive messages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeepAlive {
+    Off,
+    Every(f32),
+}
+
+impl Serialize for KeepAlive {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            KeepAlive::Off => serializer.serialize_str("off"),
+            KeepAlive::Every(seconds) => serializer.serialize_f32(*seconds),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for KeepAlive {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Seconds(f32),
+            Word(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Seconds(seconds) if seconds > 0.0 && seconds.is_finite() => {
+                Ok(KeepAlive::Every(seconds))
+            }
+            Repr::Word(word) if word == "off" => Ok(KeepAlive::Off),
+            _ => Err(serde::de::Error::custom(
+                "keep_alive should be `off` or a number of seconds above 0",
+            )),
+        }
+    }
+}
+
+impl DeviceQuirks {
+    /// Brightness to send for a configured brightness, following `brightness_curve`.
+    /// Below the first point and above the last the curve stays flat.
+    pub fn scale_brightness(&self, brightness: u8) -> u8 {
+        let Some(curve) = self.brightness_curve.as_ref().filter(|c| !c.is_empty()) else {
+            return brightness;
+        };
+        let mut points = curve.clone();
+        points.sort_by_key(|point| point[0]);
+        let [first_in, first_out] = points[0];
+        if brightness <= first_in {
+            return first_out.min(100);
+        }
+        for pair in points.windows(2) {
+            let ([from_in, from_out], [to_in, to_out]) = (pair[0], pair[1]);
+            if brightness <= to_in {
+                let span = (to_in - from_in).max(1) as f32;
+                let t = (brightness - from_in) as f32 / span;
+                let out = from_out as f32 + (to_out as f32 - from_out as f32) * t;
+                return (out.round() as u8).min(100);
+            }
+        }
+        points[points.len() - 1][1].min(100)
+    }
+}
+
+/// How the keys, encoders and touch screen of a device are read.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct InputPolling {
+    /// Milliseconds between two reads where input is polled (Windows). Default: 8.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u32>,
+
+    /// Milliseconds between reads that polls slow down to while the device is not used.
+    /// Default: 32; the same as `interval` keeps polling at full speed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_interval: Option<u32>,
+
+    /// Seconds a blocking read waits for input before the listener checks whether it
+    /// should stop (Linux, macOS). Default: 10.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_timeout: Option<f32>,
+}
+
+/// Pages a device shows in turn, each for a while, like a kiosk.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Rotation {
+    /// Pages shown in turn, in this order.
+    pub pages: Vec<String>,
+
+    /// Seconds each page is shown. Default: 30.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<f32>,
+
+    /// Seconds the rotation waits after a key, encoder or touch screen was used. Default:
+    /// 0, using the deck does not hold the rotation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pause: Option<f32>,
+}
+
+impl Rotation {
+    /// The page shown after `current`: the next page of the rotation that is `available`,
+    /// going round to the first. None when `current` is not part of the rotation.
+    pub fn next_after<'a>(
+        &'a self,
+        current: &str,
+        available: impl Fn(&str) -> bool,
+    ) -> Option<&'a str> {
+        let position = self.pages.iter().position(|page| page == current)?;
+        self.pages
+            .iter()
+            .cycle()
+            .skip(position + 1)
+            .take(self.pages.len())
+            .map(String::as_str)
+            .find(|page| available(page))
+    }
+}
+
+/// Game mode: steady, low-latency keys while playing.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GameMode {
+    /// Window classes (case-insensitive substring) that turn game mode on when they gain
+    /// the focus, and off again when they lose it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<String>,
+
+    /// Page shown and pinned on every device that has it while game mode is on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<String>,
+}
+
+impl GameMode {
+    /// Whether a window of this class turns game mode on
+    pub fn matches_class(&self, class: &str) -> bool {
+        let class = class.to_lowercase();
+        !class.is_empty()
+            && self
+                .classes
+                .iter()
+                .any(|pattern| !pattern.is_empty() && class.contains(&pattern.to_lowercase()))
+    }
+}
+
+/// Night mode filter: a warm tint plus gamma adjustment on the final key images, so
+/// keys match redshift/night-light on the monitors.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct NightMode {
+    /// Strength of the warm tint, from 0.0 (none) to 1.0 (strongest). Default: 0.5.
+    #[serde(default = "default_night_warmth")]
+    pub warmth: f32,
+
+    /// Gamma applied after tinting; values above 1.0 darken mid-tones. Default: 1.0.
+    #[serde(default = "default_night_gamma")]
+    pub gamma: f32,
+
+    /// Daily start time ("HH:MM"). Together with `until` enables the filter automatically.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+
+    /// Daily end time ("HH:MM"). May be earlier than `from` to span midnight.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+}
+
+fn default_night_warmth() -> f32 {
+    0.5
+}
+
+fn default_night_gamma() -> f32 {
+    1.0
+}
+
+impl Default for NightMode {
+    fn default() -> Self {
+        NightMode {
+            warmth: default_night_warmth(),
+            gamma: default_night_gamma(),
+            from: None,
+            until: None,
+        }
+    }
+}
+
+impl NightMode {
+    /// Checks that `from`/`until` are valid "HH:MM" times and given together.
+    pub fn validate(&self) -> Result<(), String> {
+        match (&self.from, &self.until) {
+            (Some(from), Some(until)) => {
+                parse_minute_of_day(from)?;
+                parse_minute_of_day(until)?;
+                Ok(())
+            }
+            (None, None) => Ok(()),
+            _ => Err("night_mode needs both 'from' and 'until' for a schedule".to_string()),
+        }
+    }
+
+    /// Whether the schedule covers `minute` (minutes since midnight, local time).
+    /// Always false without a schedule.
+    pub fn is_scheduled(&self, minute: u32) -> bool {
+        let (Some(from), Some(until)) = (&self.from, &self.until) else {
+            return false;
+        };
+        in_daily_window(from, until, minute)
+    }
+}
+
+/// Whether `minute` (minutes since midnight) falls within the "HH:MM" times `from` and
+/// `until`, which span midnight when `until` is earlier. False if either is invalid.
+fn in_daily_window(from: &str, until: &str, minute: u32) -> bool {
+    let (Ok(from), Ok(until)) = (parse_minute_of_day(from), parse_minute_of_day(until)) else {
+        return false;
+    };
+    if from <= until {
+        minute >= from && minute < until
+    } else {
+        minute >= from || minute < until
+    }
+}
+
+/// Days of the week as accepted by `visible_when`, Monday first
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// The `visible_when` conditions of a page. All given conditions must hold for the page
+/// to be visible.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Visibility {
+    /// Days of the week ("mon" to "sun") the page is visible on. Default: every day.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub days: Option<Vec<String>>,
+
+    /// Daily start time ("HH:MM"), given together with `until`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+
+    /// Daily end time ("HH:MM"). May be earlier than `from` to span midnight.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+
+    /// Context variables that must have one of the given values (case-insensitive).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vars: Option<IndexMap<String, WhenValue>>,
+
+    /// [Condition](crate::condition) that must hold, e.g. "${service:vpn}" (set, see
+    /// [`is_truthy`]) or "${service:battery} < 20".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+impl Visibility {
+    /// Checks the day names and that `from`/`until` are valid times given together.
+    pub fn validate(&self) -> Result<(), String> {
+        for day in self.days.iter().flatten() {
+            if !WEEKDAYS.contains(&day.to_lowercase().as_str()) {
+                return Err(format!(
+                    "Invalid day '{}', expected one of {}",
+                    day,
+                    WEEKDAYS.join(", ")
+                ));
+            }
+        }
+        match (&self.from, &self.until) {
+            (Some(from), Some(until)) => {
+                parse_minute_of_day(from)?;
+                parse_minute_of_day(until)?;
+                Ok(())
+            }
+            (None, None) => Ok(()),
+            _ => Err("visible_when needs both 'from' and 'until' for a time window".to_string()),
+        }
+    }
+
+    /// Whether the `days` and `from`/`until` conditions hold on `weekday` (0 is Monday)
+    /// at `minute` (minutes since midnight, local time).
+    pub fn is_scheduled(&self, weekday: u32, minute: u32) -> bool {
+        let today = WEEKDAYS.get(weekday as usize).copied().unwrap_or_default();
+        let day_matches = self
+            .days
+            .as_ref()
+            .is_none_or(|days| days.iter().any(|day| day.eq_ignore_ascii_case(today)));
+        let time_matches = match (&self.from, &self.until) {
+            (Some(from), Some(until)) => in_daily_window(from, until, minute),
+            _ => true,
+        };
+        day_matches && time_matches
+    }
+}
+
+/// Parses "HH:MM" into minutes since midnight
+fn parse_minute_of_day(time: &str) -> Result<u32, String> {
+    let invalid = || format!("Invalid time '{}', expected HH:MM", time);
+    let (h, m) = time.trim().split_once(':').ok_or_else(invalid)?;
+    let h: u32 = h.parse().map_err(|_| invalid())?;
+    let m: u32 = m.parse().map_err(|_| invalid())?;
+    if h > 23 || m > 59 {
+        return Err(invalid());
+    }
+    Ok(h * 60 + m)
+}
+
+/// Global default button style, see [`KeyDeckConf::apply_button_defaults`].
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ButtonDefaults {
+    /// Background for buttons without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<ColorConfig>,
+
+    /// Text color for buttons without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_color: Option<ColorConfig>,
+
+    /// Text outline color for buttons without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outline: Option<String>,
+
+    /// Font size for button text that doesn't specify one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<f32>,
+
+    /// Padding for draw graphics that don't specify one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub padding: Option<u32>,
+}
+
+impl ButtonDefaults {
+    /// Copies every default into `button` where the button leaves the field unset
+    pub fn apply(&self, button: &mut Button) {
+        fn fill<T: Clone>(field: &mut Option<T>, default: &Option<T>) {
+            if field.is_none() {
+                *field = default.clone();
+            }
+        }
+        fill(&mut button.background, &self.background);
+        fill(&mut button.text_color, &self.text_color);
+        fill(&mut button.outline, &self.outline);
+        if let Some(size) = self.font_size {
+            match &mut button.text {
+                Some(TextConfig::Simple(value)) => {
+                    button.text = Some(TextConfig::Detailed {
+                        value: std::mem::take(value),
+                        font_size: Some(size),
+                        marquee: None,
+                    });
+                }
+                Some(TextConfig::Detailed { font_size, .. }) => {
+                    font_size.get_or_insert(size);
+                }
+                None => {}
+            }
+        }
+        if let Some(draws) = &mut button.draw {
+            for draw in draws.iter_mut() {
+                fill(&mut draw.padding, &self.padding);
+            }
+        }
+    }
+}
+
+/// A named style set. Every field is optional; anything left unset falls back to the
+/// global configuration.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    /// Named colors, overriding entries of the global `colors` map with the same name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colors: Option<IndexMap<String, String>>,
+
+    /// Default background for buttons that don't set one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+
+    /// Default text color for buttons that don't set one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_color: Option<String>,
+
+    /// Default text outline color for buttons that don't set one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outline: Option<String>,
+
+    /// Font family used for button text (e.g. "DejaVu Sans"). Falls back to the system
+    /// default font when not installed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+}
+
+/// The events of a `wait_for` action
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum WaitForEvents {
+    /// A single event
+    Single(WaitForEvent),
+
+    /// Several events, combined by the action's `mode`
+    Multiple(Vec<WaitForEvent>),
+}
+
+impl WaitForEvents {
+    pub fn events(&self) -> &[WaitForEvent] {
+        match self {
+            WaitForEvents::Single(event) => std::slice::from_ref(event),
+            WaitForEvents::Multiple(events) => events,
+        }
+    }
+}
+
+/// An event waited for by a `wait_for` action
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum WaitForEvent {
+    /// Event type, e.g. "focus", waited for until the action's timeout
+    Event(String),
+
+    /// Event type with a timeout of its own, in seconds from the start of the wait
+    Detailed {
+        event: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timeout: Option<f64>,
+    },
+
+    /// Occurs once this many seconds have passed, e.g. to continue after a focus change
+    /// or after 3 seconds, whichever comes first
+    After { after: f64 },
+}
+
+/// The window a `wait_for_window` action waits for
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WindowWait {
+    /// Window class (case-insensitive substring).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+
+    /// Window title (case-insensitive substring).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    /// Seconds to wait for the window. Default: 10.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<f64>,
+}
+
+/// How the events of a `wait_for` action combine
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WaitMode {
+    /// Continue once any of the events occurred
+    #[default]
+    Any,
+    /// Continue once all of the events occurred
+    All,
+}
+
+/// State requested by the `night_mode` action
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NightModeSwitch {
+    On,
+    Off,
+    Toggle,
+    /// Follow the configured schedule again
+    Auto,
+}
+
+/// Power state requested by the `power` action
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerAction {
+    Suspend,
+    Hibernate,
+    Reboot,
+    Poweroff,
+}
+
+/// Default audio devices set by the `audio_device` action. Each is a device name or part
+/// of its description; several separated by commas are switched through in turn, and
+/// `next` switches through all devices.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct AudioDeviceSwitch {
+    /// Output to make the default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set_default_sink: Option<String>,
+    /// Input to make the default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set_default_source: Option<String>,
+}
+
+/// Source of focused window changes, see [`KeyDeckConf::focus_backend`]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FocusBackend {
+    /// Script loaded into KWin, on Plasma X11 and Wayland sessions
+    #[serde(rename = "kwin")]
+    KWin,
+    /// `_NET_ACTIVE_WINDOW` of the X server; under Wayland it only sees XWayland windows
+    #[serde(rename = "x11")]
+    X11,
+}
+
+/// State requested by an on/off action such as `heatmap`, `pin` or `game_mode`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Switch {
+    On,
+    Off,
+    Toggle,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum ButtonConfig {
+    /// Reference to a template name to use as the button configuration.
+    Template(String),
+
+    /// Detailed configuration for a button, including icon, background, and actions.
+    Detailed(Button),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum TextConfig {
+    /// Simple text string displayed on the button.
+    Simple(String),
+
+    /// Detailed configuration for text, with optional font size.
+    Detailed {
+        /// Text to display on the button.
+        value: String,
+
+        /// Font size for the text, optional.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        font_size: Option<f32>,
+
+        /// Scroll speed in pixels per second. When set, text too long to stay readable
+        /// scrolls across the key on one line instead of shrinking.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        marquee: Option<f32>,
+    },
+}
+
+/// Icon of a button: one image, or images drawn over each other in order.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Icon {
+    /// Image filename, fitted to the key.
+    File(String),
+
+    /// Layers drawn in order, the first at the bottom, e.g. a base icon, a badge and a
+    /// frame.
+    Layers(Vec<IconLayer>),
+}
+
+/// One image of a layered icon.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum IconLayer {
+    /// Image filename, fitted to the key and centered.
+    File(String),
+
+    /// Image with its own size and place on the key.
+    Detailed {
+        /// Image filename.
+        image: String,
+
+        /// Position [x, y] of its top-left corner (default: centered)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        position: Option<[i32; 2]>,
+
+        /// Size relative to the key it is fitted to (default: 1.0, the whole key)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        scale: Option<f32>,
+    },
+}
+
+impl IconLayer {
+    /// Image filename of the layer
+    pub fn image(&self) -> &str {
+        match self {
+            IconLayer::File(image) | IconLayer::Detailed { image, .. } => image,
+        }
+    }
+
+    fn image_mut(&mut self) -> &mut String {
+        match self {
+            IconLayer::File(image) | IconLayer::Detailed { image, .. } => image,
+        }
+    }
+}
+
+impl Icon {
+    /// Image filenames of the icon, bottom layer first
+    pub fn images(&self) -> Vec<&str> {
+        match self {
+            Icon::File(image) => vec![image],
+            Icon::Layers(layers) => layers.iter().map(IconLayer::image).collect(),
+        }
+    }
+
+    /// Every image filename of the icon, to point them to other files
+    pub fn images_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            Icon::File(image) => vec![image],
+            Icon::Layers(layers) => layers.iter_mut().map(IconLayer::image_mut).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
 pub struct DrawConfig {
     /// Type of graphic to draw
     #[serde(rename = "type")]
@@ -517,9 +2542,11 @@ pub struct DrawConfig {
 
     /// Data source - single ${...} expression that evaluates to number(s)
     /// For multi_bar types, evaluates to space-separated numbers
+    /// For qr_code, the text to encode
     pub value: String,
 
-    /// Value range [min, max]
+    /// Value range [min, max] (default: [0, 100], unused by qr_code)
+    #[serde(default = "default_draw_range")]
     pub range: [f32; 2],
 
     /// Single solid color (hex format: "#RRGGBB" or "0xRRGGBB")
@@ -527,11 +2554,10 @@ pub struct DrawConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub color: Option<String>,
 
-    /// Color gradient map with smooth interpolation
-    /// Format: [[threshold, color], ...] sorted by threshold
+    /// Color gradient map with smooth interpolation, see [`ColorMap`]
     /// Mutually exclusive with color
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub color_map: Option<Vec<ColorMapEntry>>,
+    pub color_map: Option<ColorMap>,
 
     /// Width in pixels (default: button width - 2*padding)
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -562,22 +2588,660 @@ pub struct DrawConfig {
     pub bar_spacing: Option<u32>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(untagged)]
-pub enum ColorMapEntry {
-    /// Array format: [threshold, color]
-    Array([serde_yaml_ng::Value; 2]),
+/// Actions bound to a global keyboard shortcut.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Hotkey {
+    /// Actions run on the first connected device.
+    Actions(Vec<Action>),
+
+    /// Actions run on the device with the given serial number.
+    Detailed {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        device: Option<String>,
+        actions: Vec<Action>,
+    },
+}
+
+impl Hotkey {
+    pub fn actions(&self) -> &[Action] {
+        match self {
+            Hotkey::Actions(actions) | Hotkey::Detailed { actions, .. } => actions,
+        }
+    }
+
+    pub fn device(&self) -> Option<&str> {
+        match self {
+            Hotkey::Actions(_) => None,
+            Hotkey::Detailed { device, .. } => device.as_deref(),
+        }
+    }
+
+    /// Canonical form of a key combination, so "Ctrl + Alt + K" and "ctrl+alt+k" match
+    pub fn normalize(combination: &str) -> String {
+        combination
+            .split('+')
+            .map(|part| part.trim().to_lowercase())
+            .collect::<Vec<_>>()
+            .join("+")
+    }
+}
+
+/// Actions run when a webhook is called. Takes the same forms as a hotkey: a list of
+/// actions for the first connected device, or a `device` and its `actions`.
+pub type Webhook = Hotkey;
+
+/// Embedded web server showing a device's keys as a touch grid, so a phone or tablet
+/// can be used as a secondary deck.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WebDeck {
+    /// Address to listen on. Default: "127.0.0.1" (this computer only); use "0.0.0.0"
+    /// to reach it from other devices on the network.
+    #[serde(default = "default_web_deck_bind")]
+    pub bind: String,
+
+    /// TCP port to listen on. Default: 8765.
+    #[serde(default = "default_web_deck_port")]
+    pub port: u16,
+
+    /// Announces the web deck on the local network (zeroconf), so other machines find
+    /// it. Default: when `bind` reaches beyond this computer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub advertise: Option<bool>,
+
+    /// Access tokens. When any is defined, every request must carry one of them, and
+    /// its scope decides what the client may do. Without tokens anyone reaching the
+    /// web deck may view the keys, and press them only when `bind` is this computer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<WebToken>,
+
+    /// Serves the web deck over HTTPS/WSS with this certificate.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<WebTls>,
+}
+
+/// An access token of the web deck.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WebToken {
+    /// The secret, sent as `?token=` or as an `Authorization: Bearer` header.
+    pub token: String,
+
+    /// What clients with this token may do. Default: control.
+    #[serde(default)]
+    pub scope: WebScope,
+
+    /// Label shown in the log instead of the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// What a web deck client may do, each scope including the ones before it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WebScope {
+    /// View the keys.
+    Readonly,
+    /// Press the keys.
+    #[default]
+    Control,
+    /// Reload the configuration, trigger hotkeys and set context variables.
+    Admin,
+}
+
+/// Certificate of the web deck, as PEM files.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WebTls {
+    /// Certificate chain, leaf first.
+    pub cert: String,
+
+    /// Private key of the certificate.
+    pub key: String,
+}
+
+fn default_web_deck_bind() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_web_deck_port() -> u16 {
+    8765
+}
+
+/// Snapshot images of the devices, written as `<serial>.png` whenever a key changes.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Snapshots {
+    /// Directory of the images. Default: `keydeck-mirror` in `$XDG_RUNTIME_DIR`, or in
+    /// the temp directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+}
+
+/// Chat services followed for `${telegram:...}` and `${matrix:...}`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Chats {
+    /// A Telegram bot, counting the messages sent to it or to the groups it is in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telegram: Option<TelegramChat>,
+
+    /// A Matrix account, following the unread notifications of its rooms.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<MatrixChat>,
+}
+
+/// The Telegram bot followed for unread messages.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TelegramChat {
+    /// Token of the bot, as given by @BotFather.
+    pub token: String,
+}
+
+/// The Matrix account followed for unread messages.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MatrixChat {
+    /// Address of the homeserver, e.g. `https://matrix.org`.
+    pub homeserver: String,
+
+    /// Access token of the account.
+    pub token: String,
+}
+
+/// A calendar followed for `${calendar:...}` and `join_meeting`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Calendar {
+    /// Address of an iCalendar feed (`https://` or `webcal://`), of a CalDAV calendar
+    /// collection with `caldav`, or path of an `.ics` file.
+    pub url: String,
+
+    /// Query `url` as a CalDAV collection, with recurring events expanded by the server.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub caldav: bool,
+
+    /// User name for HTTP basic authentication.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    /// Password for HTTP basic authentication, e.g. an app password.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+/// The OpenRGB server, reached through its SDK server (Settings → SDK Server in OpenRGB).
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OpenRgb {
+    /// Host name or address of the server. Default: `localhost`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+
+    /// Port of the server. Default: 6742.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+}
+
+/// What a button of a page is expected to show and do, with the values dynamic
+/// parameters take in the test. Fields left out are not checked.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigTest {
+    /// Name shown in the report. Default: the page and the button.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Page group of the page. Default: `default`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    pub page: String,
+
+    /// The button, by number (`3`) or by key (`button3`, `r1c2`).
+    pub button: TestButton,
+
+    /// Values of the dynamic parameters, by expression (`service:cpu`) or by provider
+    /// (`time`). Parameters without one show the error indicator, as when a provider
+    /// fails, so the test does not depend on the computer it runs on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stubs: Option<IndexMap<String, String>>,
+
+    /// The text shown, with the dynamic parameters replaced.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+
+    /// Regular expression the text shown must match, such as `.+` for any text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_matches: Option<String>,
+
+    /// The icon file shown, with the dynamic parameters replaced.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+
+    /// The actions a press runs, with macros expanded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actions: Option<Vec<Action>>,
+}
+
+/// A button named by a test.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum TestButton {
+    Index(u8),
+    Key(String),
+}
+
+impl TestButton {
+    /// Key of the button in its page
+    pub fn key(&self) -> String {
+        match self {
+            TestButton::Index(index) => format!("button{}", index),
+            TestButton::Key(key) => key.clone(),
+        }
+    }
+}
+
+/// Settings of a `blink` or `pulse` background animation.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Animation {
+    /// Cycles per second. Default: 1.0.
+    #[serde(default = "default_animation_rate")]
+    pub rate: f32,
+
+    /// Colors to switch (blink) or fade (pulse) between. A single color alternates with
+    /// the button's own background. Default: ["0xC00000"].
+    #[serde(default = "default_animation_colors")]
+    pub colors: Vec<String>,
+
+    /// Blink only: fraction of each cycle showing the first color. Default: 0.5.
+    #[serde(default = "default_animation_duty")]
+    pub duty: f32,
+
+    /// Optional data source (e.g. "${service:cpu}") gating the animation with `above`
+    /// and `below`, or without them a [condition](crate::condition) the animation runs
+    /// while it holds (e.g. "${service:cpu} > 90 && ${var:mode} != quiet"). Without it
+    /// the animation always runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+
+    /// Animate only while `value` is above this threshold.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub above: Option<f32>,
+
+    /// Animate only while `value` is below this threshold.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub below: Option<f32>,
+}
+
+/// Effects applied to the whole rendered button, after background, icon, graphics and text.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Effects {
+    /// Removes all color, e.g. to show a button as unavailable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grayscale: Option<bool>,
+
+    /// Opacity from 0.0 (invisible) to 1.0 (unchanged).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f32>,
+
+    /// Color the button is tinted with, keeping its brightness.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tint: Option<String>,
+
+    /// Radius in pixels of rounded corners.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corner_radius: Option<u32>,
+
+    /// Color of a border drawn along the edge of the button.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border: Option<String>,
+
+    /// Border width in pixels. Default: 2.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_width: Option<u32>,
+}
+
+fn default_animation_rate() -> f32 {
+    1.0
+}
+
+fn default_animation_colors() -> Vec<String> {
+    vec!["0xC00000".to_string()]
+}
+
+fn default_animation_duty() -> f32 {
+    0.5
+}
+
+impl Animation {
+    /// Whether the animation runs, with `substitute` replacing the dynamic parameters of
+    /// `value`. A value that is not a number stops a thresholded animation, and a
+    /// condition that is not valid stops the animation.
+    pub fn is_active(&self, substitute: &dyn Fn(&str) -> String) -> bool {
+        let Some(value) = &self.value else {
+            return true;
+        };
+        if self.above.is_none() && self.below.is_none() {
+            return crate::condition::holds(value, substitute).unwrap_or(false);
+        }
+        let Ok(number) = substitute(value).trim().parse::<f32>() else {
+            return false;
+        };
+        self.above.is_none_or(|above| number > above) && self.below.is_none_or(|below| number < below)
+    }
+}
+
+/// The `scope` of an `exec` action: a transient systemd user scope (Linux), so the
+/// command gets its own cgroup instead of living in the daemon's, optionally with
+/// resource limits.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum ExecScope {
+    /// `true` runs the command in its own scope without limits.
+    Enabled(bool),
+
+    /// A scope with resource limits, in the syntax of the systemd properties.
+    Limits {
+        /// Memory limit, e.g. `2G` (`MemoryMax`).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        memory_max: Option<String>,
+
+        /// CPU time limit, e.g. `50%` of one CPU (`CPUQuota`).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cpu_quota: Option<String>,
+
+        /// Most processes and threads the command may start (`TasksMax`).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tasks_max: Option<u32>,
+    },
+}
+
+impl ExecScope {
+    /// systemd properties of the scope; None when no scope is wanted
+    pub fn properties(&self) -> Option<Vec<String>> {
+        match self {
+            ExecScope::Enabled(enabled) => enabled.then(Vec::new),
+            ExecScope::Limits {
+                memory_max,
+                cpu_quota,
+                tasks_max,
+            } => Some(
+                [
+                    memory_max.as_ref().map(|v| format!("MemoryMax={}", v)),
+                    cpu_quota.as_ref().map(|v| format!("CPUQuota={}", v)),
+                    tasks_max.map(|v| format!("TasksMax={}", v)),
+                ]
+                .into_iter()
+                .flatten()
+                .collect(),
+            ),
+        }
+    }
+}
+
+/// The `cooldown` of a button: seconds, or seconds with a visual countdown.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Cooldown {
+    /// Seconds during which presses are ignored.
+    Seconds(f32),
+
+    /// Detailed configuration, optionally showing the remaining seconds on the key.
+    Detailed {
+        /// Seconds during which presses are ignored.
+        seconds: f32,
+
+        /// Draws the remaining seconds over the dimmed button. Default: false.
+        #[serde(default)]
+        countdown: bool,
+    },
+}
+
+impl Cooldown {
+    pub fn seconds(&self) -> f32 {
+        match self {
+            Cooldown::Seconds(seconds) | Cooldown::Detailed { seconds, .. } => *seconds,
+        }
+    }
+
+    pub fn countdown(&self) -> bool {
+        matches!(self, Cooldown::Detailed { countdown: true, .. })
+    }
+}
+
+/// Key repeat of a button that is held down.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Repeat {
+    /// Seconds from the press to the first repetition. Default: 0.5.
+    #[serde(default = "default_repeat_delay")]
+    pub delay: f32,
+
+    /// Seconds between repetitions. Default: 0.1.
+    #[serde(default = "default_repeat_interval")]
+    pub interval: f32,
+}
+
+/// A countdown `timer` on a button, drawn live on the key.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Timer {
+    /// Seconds counted down.
+    pub duration: f32,
+
+    /// How the time left is drawn. Default: ring.
+    #[serde(default)]
+    pub style: TimerStyle,
+
+    /// Color of the ring and of the alarm blink. Default: orange.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+
+    /// Sound file played when the time is up, or `click`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+
+    /// Blinks the key once the time is up, until it is pressed. Default: true.
+    #[serde(default = "default_timer_blink")]
+    pub blink: bool,
+
+    /// Actions run when the time is up, such as `notify`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alarm: Option<Vec<Action>>,
+}
+
+fn default_timer_blink() -> bool {
+    true
+}
+
+/// How a `timer` draws the time left.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TimerStyle {
+    /// A ring around the minutes and seconds left, shrinking as time passes.
+    #[default]
+    Ring,
+
+    /// The minutes and seconds left only.
+    Numeric,
+}
+
+fn default_repeat_delay() -> f32 {
+    0.5
+}
+
+fn default_repeat_interval() -> f32 {
+    0.1
+}
+
+/// Whether a dynamic value counts as set: anything but empty, `0` or `false`.
+pub fn is_truthy(evaluated: &str) -> bool {
+    let evaluated = evaluated.trim();
+    !evaluated.is_empty() && evaluated != "0" && evaluated != "false"
+}
+
+/// The `enabled` state of a button.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Enabled {
+    /// Always enabled or always disabled.
+    Fixed(bool),
+
+    /// Enabled while the [condition](crate::condition) holds, e.g. "${service:recorder}"
+    /// (set, see [`is_truthy`]) or "${service:battery} >= 20".
+    Condition(String),
+}
+
+/// A button background or text color.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ColorConfig {
+    /// Hex color or a reference to a named color.
+    Fixed(String),
+
+    /// Color picked from `color_map` by where `value` falls within `range`, like the
+    /// `color_map` of draw configs.
+    Mapped {
+        /// Data source - single ${...} expression that evaluates to a number
+        value: String,
+
+        /// Value range [min, max]
+        range: [f32; 2],
+
+        /// Color gradient map, thresholds in percent of the range
+        color_map: ColorMap,
+    },
+}
+
+impl ColorConfig {
+    /// The color string of a fixed color, `None` for a color map
+    pub fn as_fixed(&self) -> Option<&str> {
+        match self {
+            ColorConfig::Fixed(color) => Some(color),
+            ColorConfig::Mapped { .. } => None,
+        }
+    }
+}
+
+/// Colors picked by where a value falls within its range.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ColorMap {
+    /// [[threshold, color], ...] sorted by threshold, thresholds in percent of the range.
+    Thresholds(Vec<ColorMapEntry>),
+
+    /// Colors spread evenly over the range.
+    Gradient(Gradient),
+
+    /// Name of a color map of the `gradients` section, replaced by it at load time, see
+    /// [`KeyDeckConf::resolve_gradients`].
+    Named(String),
+}
+
+impl ColorMap {
+    /// The `(threshold, color)` stops of the map, thresholds in percent of the range.
+    /// Empty for a name not resolved yet.
+    pub fn stops(&self) -> Vec<(f32, &str)> {
+        match self {
+            ColorMap::Thresholds(entries) => entries
+                .iter()
+                .filter_map(|ColorMapEntry::Array([threshold, color])| {
+                    Some((threshold.as_f64()? as f32, color.as_str()?))
+                })
+                .collect(),
+            ColorMap::Gradient(gradient) => {
+                let last = gradient.gradient.len().saturating_sub(1).max(1) as f32;
+                gradient
+                    .gradient
+                    .iter()
+                    .enumerate()
+                    .map(|(index, color)| (index as f32 * 100.0 / last, color.as_str()))
+                    .collect()
+            }
+            ColorMap::Named(_) => vec![],
+        }
+    }
+
+    /// Where `percent` of the range falls on the stops, after the easing of a gradient
+    pub fn ease(&self, percent: f32) -> f32 {
+        match self {
+            ColorMap::Gradient(gradient) => gradient.easing.apply(percent / 100.0) * 100.0,
+            _ => percent,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ColorMapEntry {
+    /// Array format: [threshold, color]
+    Array([serde_yaml_ng::Value; 2]),
+}
+
+/// A color map of evenly spaced colors, e.g.
+/// `gradient: ["#00ff00", "#ffff00", "#ff0000"]` puts yellow at 50%.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Gradient {
+    /// Colors from the start to the end of the range
+    pub gradient: Vec<String>,
+
+    /// How the value moves along the colors (default: linear)
+    #[serde(default, skip_serializing_if = "Easing::is_linear")]
+    pub easing: Easing,
+}
+
+/// Easing curve of a [`Gradient`]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Stays near the first colors longer, then moves quickly to the last
+    EaseIn,
+    /// Moves quickly from the first colors, then stays near the last
+    EaseOut,
+    /// Slow at both ends, quick in the middle
+    EaseInOut,
+}
+
+impl Easing {
+    /// The eased position of `t`, both from 0 to 1
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut if t < 0.5 => 2.0 * t * t,
+            Easing::EaseInOut => 1.0 - 2.0 * (1.0 - t) * (1.0 - t),
+        }
+    }
+
+    fn is_linear(&self) -> bool {
+        *self == Easing::Linear
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum GraphicType {
     Gauge,
     Bar,
     MultiBar,
+    QrCode,
+}
+
+fn default_draw_range() -> [f32; 2] {
+    [0.0, 100.0]
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Direction {
     LeftToRight,
@@ -586,7 +3250,7 @@ pub enum Direction {
     BottomToTop,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "lowercase", untagged)]
 pub enum RefreshTarget {
     /// Refresh all dynamic buttons (explicit "dynamic" string)
@@ -603,7 +3267,7 @@ fn default_refresh_target() -> RefreshTarget {
     RefreshTarget::Dynamic("dynamic".to_string())
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(untagged, deny_unknown_fields)]
 pub enum Action {
     /// Jumps to a specified page.
@@ -612,19 +3276,44 @@ pub enum Action {
     /// Automatically returns to the predefined page, based on the focus change policy.
     AutoJump { auto_jump: () },
 
-    /// Focuses on an application by matching window class OR title.
-    /// The focus string is checked against both window class and title (case-insensitive substring match).
+    /// Focuses on an application window. A plain string is checked against both window
+    /// class and title (case-insensitive substring match); a structured target allows
+    /// exact matching, desktop-file or PID lookup, and picking/cycling between matches.
     /// Returns error if no matching window is found (can be caught with try/else).
-    Focus { focus: String },
+    /// With `fallback_exec`, that command is launched instead, and `wait_for_window`
+    /// then holds the following actions until the launched window has the focus: the
+    /// raise-or-launch pattern in one action.
+    Focus {
+        focus: FocusTarget,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fallback_exec: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        wait_for_window: Option<WindowWait>,
+    },
 
     /// Sends a keyboard shortcut event. Some examples include "LCtrl+LShift+z" or "F12".
     /// The value is case-insensitive and can be a single character or a key name.
-    Key { key: String },
+    /// With a `target`, the window it finds gets the focus for the keys, and the focus
+    /// goes back to the window that had it.
+    Key {
+        key: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target: Option<FocusTarget>,
+    },
 
     /// Sends a string of ASCII characters as individual keystrokes.
     /// Each character in the string is sent as a separate key press/release event.
     /// Supports escape sequences: \n (Enter), \t (Tab), \r (Enter), \\ (backslash), \e (Escape)
-    Text { text: String },
+    /// With `echo: true` the text is first shown across the keys, and a key press cancels
+    /// it before it is typed; when unset, only long texts are shown. A `target` is typed
+    /// into like for `key`.
+    Text {
+        text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        echo: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target: Option<FocusTarget>,
+    },
 
     /// Waits for a specified time in seconds before executing the next action.
     Wait { wait: f32 },
@@ -632,25 +3321,118 @@ pub enum Action {
     /// Waits for a specific event to occur, with optional timeout.
     /// If the event doesn't occur within the timeout, returns an error.
     /// Can be caught with try/else for error handling.
-    /// Timeout defaults to 1.0 second if not specified.
+    /// Timeout defaults to 1.0 second if not specified, or to the longest timeout or
+    /// delay of its events when longer.
+    /// A list of events continues once any of them occurred, or with `mode: all` once
+    /// all of them did.
     WaitFor {
         #[serde(rename = "wait_for")]
-        wait_for_event: String,
+        wait_for_event: WaitForEvents,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        mode: Option<WaitMode>,
 
         #[serde(skip_serializing_if = "Option::is_none")]
         timeout: Option<f64>,
     },
 
+    /// Waits until a window matching `class` and `title` has the focus, as a window
+    /// does when it opens, e.g. to send keys to an application just launched. Continues
+    /// at once if it already has the focus. `timeout` defaults to 10 seconds.
+    WaitForWindow { wait_for_window: WindowWait },
+
+    /// Shows a desktop notification with the given message.
+    /// Returns error if the notification could not be delivered.
+    Notify { notify: String },
+
     /// Executes an external command.
     /// By default, spawns the command asynchronously (fire-and-forget).
     /// Set `wait: true` to wait for the command to complete and check its exit status.
     /// When `wait: true`, returns error if command fails (exit code != 0), allowing use with try/else.
+    /// `run_as` runs it as another user through `sudo`, `scope` in a transient systemd scope.
+    /// With `wait: true`, `timeout` (seconds) kills the command and everything it started
+    /// once it runs longer, and the action fails. Without waiting, `tag` names the command
+    /// for `${proc:running:TAG}`, and `kill_on_exit` kills it when the page is left or the
+    /// daemon exits.
     Exec {
         exec: String,
         #[serde(skip_serializing_if = "Option::is_none")]
         wait: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        run_as: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        scope: Option<ExecScope>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timeout: Option<f32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tag: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        kill_on_exit: Option<bool>,
+    },
+
+    /// Sends an HTTP request to the `http` address, e.g. a Home Assistant or CI webhook.
+    /// `method` defaults to POST when there is a `body`, GET otherwise; `timeout` is in
+    /// seconds (default 10). Returns error if the request fails or its status is not a
+    /// success. `capture` stores the response body in a context variable.
+    Http {
+        http: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        method: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        headers: Option<IndexMap<String, String>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        body: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        capture: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timeout: Option<f32>,
+    },
+
+    /// Marks the messages of a chat service under `chats` as read, e.g. `telegram`, or
+    /// of one of its chats, e.g. `matrix:#team:example.org`. Matrix rooms also get a
+    /// read receipt on the homeserver.
+    ChatRead { chat_read: String },
+
+    /// Opens the conferencing link (Zoom, Meet, Teams, Jitsi, ...) of the meeting in
+    /// progress or next to start in the `calendars`: `next`, or the next meeting whose
+    /// title contains the given text. Returns error if there is none or it has no link.
+    JoinMeeting { join_meeting: String },
+
+    /// Opens a web address, or a file or directory, with the default application of the
+    /// desktop. Supports dynamic parameters.
+    Open { open: String },
+
+    /// Wakes a machine on the network with a Wake-on-LAN magic packet, sent to its MAC
+    /// address (`aa:bb:cc:dd:ee:ff`). The packet is broadcast on the local network, or
+    /// sent to `broadcast` (the broadcast address of another subnet). Supports dynamic
+    /// parameters.
+    Wol {
+        wol: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        broadcast: Option<String>,
     },
 
+    /// Loads a lighting profile saved in OpenRGB, on the server of the `openrgb` settings
+    /// or the local one. Supports dynamic parameters.
+    OpenRgb { openrgb: String },
+
+    /// Suspends, hibernates, reboots or powers off this machine. On Linux it goes through
+    /// logind, which asks for authorization through polkit when needed.
+    Power { power: PowerAction },
+
+    /// Switches the keyboard layout of the session: `next` cycles through the configured
+    /// layouts, a short layout name (`us`, `de`) selects that one. Supports dynamic
+    /// parameters.
+    KbdLayout { kbd_layout: String },
+
+    /// Switches the default audio output and/or input, e.g. between headphones and
+    /// speakers. Supports dynamic parameters.
+    AudioDevice { audio_device: AudioDeviceSwitch },
+
+    /// Marks the checklist item `LIST/ITEM` done, or not done again. The state is kept
+    /// across restarts, see `${checklist:...}`.
+    Check { check: String },
+
     /// Sets a context variable directly in the daemon (no shell/subprocess), the
     /// native equivalent of `exec: keydeck --set key=value`. Same `key=value`
     /// grammar; an empty value clears the variable. Pages re-evaluate immediately,
@@ -658,10 +3440,42 @@ pub enum Action {
     /// Works on every platform, unlike the `--set` control socket (Unix-only).
     Set { set: String },
 
+    /// Turns the night mode filter on or off on all devices, toggles it, or returns
+    /// it to the configured schedule (`auto`).
+    NightMode { night_mode: NightModeSwitch },
+
+    /// Switches the active theme on all devices. Pages with their own `theme` keep it.
+    Theme { theme: String },
+
+    /// Tints the keys of this device by how often they were pressed, from blue (rarely)
+    /// to red (most used), to help rearrange a layout. Usage is counted all the time.
+    Heatmap { heatmap: Switch },
+
+    /// Pins the page shown on this device, so focus changes do not switch it until it is
+    /// unpinned. Unlike the static `lock` field it applies to whatever page is shown.
+    Pin { pin: Switch },
+
+    /// Turns game mode on or off on all devices, or toggles it: pages stop following the
+    /// focus and show the `game_mode` page, animations stop, input is read faster.
+    GameMode { game_mode: Switch },
+
     /// Calls a macro with optional parameters.
     /// Parameters are substituted in the macro's actions before execution.
     Macro(MacroCall),
 
+    /// Runs `then` when the [condition](crate::condition) holds, `else` otherwise. A
+    /// condition that is not valid fails the action.
+    If {
+        #[serde(rename = "if")]
+        condition: String,
+
+        then: Vec<Action>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(rename = "else")]
+        else_actions: Option<Vec<Action>>,
+    },
+
     /// Try/else block for error handling.
     /// Executes try_actions sequentially, stopping on first error.
     /// If try fails and else_actions is present, executes else block.
@@ -675,6 +3489,25 @@ pub enum Action {
         else_actions: Option<Vec<Action>>,
     },
 
+    /// Asks on the keys whether to go on: the message is shown with a key to confirm and
+    /// one to cancel. Confirming runs `then` and the actions after this one; cancelling,
+    /// or leaving it unanswered for `timeout` seconds (default 10), runs `else` and stops
+    /// the sequence. Needs a device with a screen. Supports dynamic parameters.
+    Confirm {
+        #[serde(rename = "confirm")]
+        message: String,
+
+        #[serde(rename = "then")]
+        confirm_actions: Vec<Action>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(rename = "else")]
+        cancel_actions: Option<Vec<Action>>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timeout: Option<f32>,
+    },
+
     /// Returns successfully from the current action sequence.
     /// Stops execution of remaining actions without triggering error handlers.
     Return {
@@ -710,6 +3543,13 @@ pub enum Action {
         not_action: Box<Action>,
     },
 
+    /// Runs one of the actions, picked at random each time, such as one sound of a
+    /// soundboard or one of several greetings. Nothing runs when the list is empty.
+    Random {
+        #[serde(rename = "random")]
+        random_actions: Vec<Action>,
+    },
+
     /// Refreshes button(s) to update their visual content.
     /// - "dynamic": refreshes all buttons marked with `dynamic: true`
     /// - Single number: refreshes that specific button
@@ -814,3 +3654,698 @@ mod when_tests {
             .any(|v| v == "kitty"));
     }
 }
+
+#[cfg(test)]
+mod color_tests {
+    use super::*;
+
+    #[test]
+    fn background_accepts_color_map() {
+        let button: Button = serde_yaml_ng::from_str(
+            "background:\n  value: \"${service:cpu}\"\n  range: [0, 100]\n  color_map: [[0, \"#00FF00\"], [100, \"#FF0000\"]]\ntext_color: white\n",
+        )
+        .unwrap();
+        assert!(matches!(button.background, Some(ColorConfig::Mapped { ref color_map, .. }) if color_map.stops().len() == 2));
+        assert_eq!(button.text_color.as_ref().and_then(ColorConfig::as_fixed), Some("white"));
+    }
+
+    #[test]
+    fn gradients_are_evenly_spaced_and_named() {
+        let mut conf: KeyDeckConf = serde_yaml_ng::from_str(
+            "gradients:\n  heat:\n    gradient: [\"#00ff00\", \"#ffff00\", \"#ff0000\"]\n    easing: ease_in\nMain:\n  main_page: P\n  P:\n    button1:\n      draw:\n        - type: bar\n          value: \"50\"\n          color_map: heat\n    button2:\n      background:\n        value: \"50\"\n        range: [0, 100]\n        color_map:\n          gradient: [red, blue]\n",
+        )
+        .unwrap();
+        conf.resolve_gradients().unwrap();
+        let page = &conf.page_groups["Main"].pages["P"];
+        let ButtonConfig::Detailed(bar) = &page.buttons["button1"] else {
+            panic!("detailed button expected");
+        };
+        let heat = bar.draw.as_ref().unwrap()[0].color_map.as_ref().unwrap();
+        assert_eq!(
+            heat.stops(),
+            [(0.0, "#00ff00"), (50.0, "#ffff00"), (100.0, "#ff0000")]
+        );
+        assert_eq!(heat.ease(50.0), 25.0);
+        let ButtonConfig::Detailed(key) = &page.buttons["button2"] else {
+            panic!("detailed button expected");
+        };
+        let Some(ColorConfig::Mapped { color_map, .. }) = &key.background else {
+            panic!("color map expected");
+        };
+        assert_eq!(color_map.stops(), [(0.0, "red"), (100.0, "blue")]);
+        assert_eq!(color_map.ease(50.0), 50.0);
+
+        let mut unknown: KeyDeckConf = serde_yaml_ng::from_str(
+            "Main:\n  main_page: P\n  P:\n    button1:\n      draw:\n        - type: bar\n          value: \"50\"\n          color_map: cold\n",
+        )
+        .unwrap();
+        assert_eq!(
+            unknown.resolve_gradients().unwrap_err(),
+            "Unknown gradient 'cold'"
+        );
+    }
+}
+
+#[cfg(test)]
+mod animation_tests {
+    use super::*;
+
+    #[test]
+    fn animation_follows_thresholds() {
+        let blink: Animation = serde_yaml_ng::from_str("{ value: \"${service:cpu}\", above: 90 }").unwrap();
+        assert_eq!(blink.colors, vec!["0xC00000".to_string()]);
+        assert!(blink.is_active(&|_| "95".to_string()));
+        assert!(!blink.is_active(&|_| "50".to_string()));
+        assert!(!blink.is_active(&|_| "n/a".to_string()));
+        let flag: Animation = serde_yaml_ng::from_str("{ value: \"${env:ALERT}\" }").unwrap();
+        assert!(flag.is_active(&|_| "yes".to_string()));
+        assert!(!flag.is_active(&|_| "".to_string()));
+        let always: Animation = serde_yaml_ng::from_str("{ rate: 2 }").unwrap();
+        assert!(always.is_active(&|_| "".to_string()));
+        let gate: Animation =
+            serde_yaml_ng::from_str("{ value: \"${service:cpu} > 90 && ${var:mode} != quiet\" }")
+                .unwrap();
+        let busy = |text: &str| {
+            text.replace("${service:cpu}", "95")
+                .replace("${var:mode}", "work")
+        };
+        assert!(gate.is_active(&busy));
+        assert!(!gate.is_active(&|text| busy(text).replace("work", "quiet")));
+    }
+}
+
+#[cfg(test)]
+mod timer_tests {
+    use super::*;
+
+    #[test]
+    fn timer_defaults_to_a_blinking_ring() {
+        let button: Button = serde_yaml_ng::from_str(
+            "timer:\n  duration: 300\n  alarm:\n    - notify: \"Tea is ready\"\n",
+        )
+        .unwrap();
+        let timer = button.timer.unwrap();
+        assert_eq!(timer.style, TimerStyle::Ring);
+        assert!(timer.blink);
+        assert!(matches!(timer.alarm.as_deref(), Some([Action::Notify { .. }])));
+        assert!(serde_yaml_ng::from_str::<Timer>("{ duration: 60, style: numeric }").is_ok());
+        assert!(serde_yaml_ng::from_str::<Timer>("{ duration: 60, ring: true }").is_err());
+    }
+}
+
+#[cfg(test)]
+mod button_tests {
+    use super::*;
+
+    #[test]
+    fn defaults_fill_only_missing_fields() {
+        let mut conf: KeyDeckConf = serde_yaml_ng::from_str(
+            "defaults:\n  background: bg\n  font_size: 14\n  padding: 2\npages:\n  Main:\n    button1:\n      text: Hi\n      draw:\n        - { type: gauge, value: \"1\", range: [0, 1] }\n    button2:\n      background: red\n      text: { value: Big, font_size: 30 }\n",
+        )
+        .unwrap();
+        conf.apply_button_defaults();
+        let page = &conf.page_groups["pages"].pages["Main"];
+        let ButtonConfig::Detailed(first) = &page.buttons["button1"] else { panic!() };
+        assert_eq!(first.background.as_ref().and_then(ColorConfig::as_fixed), Some("bg"));
+        assert!(matches!(first.text, Some(TextConfig::Detailed { font_size: Some(s), .. }) if s == 14.0));
+        assert_eq!(first.draw.as_ref().unwrap()[0].padding, Some(2));
+        let ButtonConfig::Detailed(second) = &page.buttons["button2"] else { panic!() };
+        assert_eq!(second.background.as_ref().and_then(ColorConfig::as_fixed), Some("red"));
+        assert!(matches!(second.text, Some(TextConfig::Detailed { font_size: Some(s), .. }) if s == 30.0));
+    }
+
+    #[test]
+    fn buttons_extend_button_definitions() {
+        let yaml = "buttons:\n  app:\n    background: blue\n    text: App\n    actions:\n      - exec: launch\n  browser:\n    extends: app\n    icon: firefox.png\n  mail:\n    extends: browser\n    text: Mail\npages:\n  Main:\n    button1:\n      extends: mail\n      background: red\n";
+        let mut conf: KeyDeckConf = serde_yaml_ng::from_str(yaml).unwrap();
+        conf.resolve_button_extends().unwrap();
+        let buttons = conf.buttons.as_ref().unwrap();
+        let mail = &buttons["mail"];
+        assert_eq!(mail.extends, None);
+        assert_eq!(mail.text, Some(TextConfig::Simple("Mail".to_string())));
+        assert_eq!(mail.icon, buttons["browser"].icon);
+        assert_eq!(mail.actions, buttons["app"].actions);
+        let page = &conf.page_groups["pages"].pages["Main"];
+        let ButtonConfig::Detailed(button) = &page.buttons["button1"] else {
+            panic!()
+        };
+        assert_eq!(
+            button.background.as_ref().and_then(ColorConfig::as_fixed),
+            Some("red")
+        );
+        assert_eq!(button.actions, buttons["app"].actions);
+
+        let cycle = "buttons:\n  a: { extends: b }\n  b: { extends: a }\n";
+        let mut conf: KeyDeckConf = serde_yaml_ng::from_str(cycle).unwrap();
+        let error = conf.resolve_button_extends().unwrap_err();
+        assert_eq!(error, "Circular button inheritance detected: a → b → a");
+        let unknown = "buttons:\n  a: { extends: c }\n";
+        let mut conf: KeyDeckConf = serde_yaml_ng::from_str(unknown).unwrap();
+        let error = conf.resolve_button_extends().unwrap_err();
+        assert!(error.contains("unknown button 'c'"));
+    }
+}
+
+#[cfg(test)]
+mod grid_key_tests {
+    use super::*;
+
+    #[test]
+    fn grid_keys_follow_the_device_layout() {
+        assert_eq!(parse_grid_key("r2c3"), Some((2, 3)));
+        assert_eq!(parse_grid_key("r0c1"), None);
+        assert_eq!(parse_grid_key("button8"), None);
+
+        let mut pages: Pages = serde_yaml_ng::from_str(
+            "Main:\n  r2c3: { text: a }\n  r1c1: { text: b }\n  button1: { text: c }\n  r4c1: { text: d }\n",
+        )
+        .unwrap();
+        assert!(pages.has_grid_keys());
+        let problems = pages.resolve_grid_keys(3, 5);
+        assert_eq!(problems.len(), 2);
+        let buttons = &pages.pages["Main"].buttons;
+        let mut keys: Vec<&String> = buttons.keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["button1", "button8"]);
+        assert!(!pages.has_grid_keys());
+    }
+}
+
+#[cfg(test)]
+mod chord_tests {
+    use super::*;
+
+    #[test]
+    fn chords_take_grid_keys_and_need_two_keys() {
+        let mut pages: Pages = serde_yaml_ng::from_str(
+            "Main:\n  chords:\n    - keys: [button1, r2c3]\n      actions:\n        - jump: Other\n  button1: { text: a }\n",
+        )
+        .unwrap();
+        let chord = &pages.pages["Main"].chords.as_ref().unwrap()[0];
+        assert_eq!(chord.window, 0.2);
+        assert!(chord.validate().is_ok());
+        assert!(!pages.pages["Main"].buttons.contains_key("chords"));
+        assert!(pages.has_grid_keys());
+        assert!(pages.resolve_grid_keys(3, 5).is_empty());
+        let chord = &pages.pages["Main"].chords.as_ref().unwrap()[0];
+        assert_eq!(chord.buttons().collect::<Vec<_>>(), [1, 8]);
+
+        let single: Chord = serde_yaml_ng::from_str("{ keys: [button1], actions: [] }").unwrap();
+        assert!(single.validate().unwrap_err().contains("at least two keys"));
+        let twice: Chord =
+            serde_yaml_ng::from_str("{ keys: [button1, button1], actions: [] }").unwrap();
+        assert!(twice.validate().unwrap_err().contains("listed twice"));
+        let named: Chord =
+            serde_yaml_ng::from_str("{ keys: [button1, play], actions: [] }").unwrap();
+        assert!(named.validate().unwrap_err().contains("'play'"));
+    }
+}
+
+#[cfg(test)]
+mod layer_tests {
+    use super::*;
+
+    #[test]
+    fn layers_take_grid_keys_and_need_their_modifier() {
+        let mut pages: Pages = serde_yaml_ng::from_str(
+            "Main:\n  button1: { modifier: fn }\n  button2: { text: a }\n  layers:\n    fn:\n      r1c2: { text: b }\n",
+        )
+        .unwrap();
+        let page = &pages.pages["Main"];
+        assert!(!page.buttons.contains_key("layers"));
+        assert!(page.validate_layers().is_ok());
+        assert_eq!(page.all_buttons().count(), 3);
+        assert!(pages.has_grid_keys());
+        assert!(pages.resolve_grid_keys(3, 5).is_empty());
+        let layer = &pages.pages["Main"].layers.as_ref().unwrap()["fn"];
+        assert!(layer.contains_key("button2"));
+
+        let unknown: Page = serde_yaml_ng::from_str("button1: { modifier: shift }").unwrap();
+        assert!(unknown.validate_layers().unwrap_err().contains("'shift'"));
+    }
+}
+
+#[cfg(test)]
+mod night_mode_tests {
+    use super::*;
+
+    #[test]
+    fn night_mode_schedule_spans_midnight() {
+        let night: NightMode =
+            serde_yaml_ng::from_str("{ from: \"21:30\", until: \"06:00\" }").unwrap();
+        assert!(night.validate().is_ok());
+        assert_eq!(night.warmth, 0.5);
+        assert!(night.is_scheduled(22 * 60));
+        assert!(night.is_scheduled(5 * 60));
+        assert!(!night.is_scheduled(12 * 60));
+        let broken: NightMode = serde_yaml_ng::from_str("{ from: \"25:00\", until: \"06:00\" }").unwrap();
+        assert!(broken.validate().is_err());
+    }
+}
+
+#[cfg(test)]
+mod theme_tests {
+    use super::*;
+
+    #[test]
+    fn themes_are_not_parsed_as_pages_or_buttons() {
+        let conf: KeyDeckConf = serde_yaml_ng::from_str(
+            "themes:\n  dark:\n    background: \"0x202020\"\n    font: DejaVu Sans\ntheme: dark\npages:\n  Main:\n    theme: dark\n    button1:\n      actions:\n        - theme: light\n",
+        )
+        .unwrap();
+        assert_eq!(conf.theme.as_deref(), Some("dark"));
+        assert_eq!(conf.themes.unwrap()["dark"].font.as_deref(), Some("DejaVu Sans"));
+        assert!(!conf.page_groups.contains_key("themes"));
+        let page = &conf.page_groups["pages"].pages["Main"];
+        assert_eq!(page.theme.as_deref(), Some("dark"));
+        assert!(!page.buttons.contains_key("theme"));
+    }
+}
+
+#[cfg(test)]
+mod action_tests {
+    use super::*;
+
+
+    #[test]
+    fn rotation_goes_round_the_available_pages() {
+        let pages: Pages = serde_yaml_ng::from_str(
+            "rotation: { pages: [System, Weather, Calendar], interval: 20 }\nSystem: {}\n",
+        )
+        .unwrap();
+        let rotation = pages.rotation.unwrap();
+        assert_eq!(rotation.interval, Some(20.0));
+        assert!(!pages.pages.contains_key("rotation"));
+        let all = |_: &str| true;
+        assert_eq!(rotation.next_after("System", all), Some("Weather"));
+        assert_eq!(rotation.next_after("Calendar", all), Some("System"));
+        assert_eq!(
+            rotation.next_after("Weather", |page| page != "Calendar"),
+            Some("System")
+        );
+        assert_eq!(rotation.next_after("Media", all), None);
+        assert_eq!(rotation.next_after("System", |_| false), None);
+    }
+
+    #[test]
+    fn shutdown_state_is_a_mode_or_an_image() {
+        let shutdown = |yaml| serde_yaml_ng::from_str::<Pages>(yaml).unwrap().shutdown;
+        assert_eq!(
+            shutdown("shutdown: keep"),
+            Some(ShutdownState::Mode(ShutdownMode::Keep))
+        );
+        let logo = Wallpaper::File("logo.png".to_string());
+        assert_eq!(
+            shutdown("shutdown: logo.png"),
+            Some(ShutdownState::Image(logo))
+        );
+        assert!(matches!(
+            shutdown("shutdown: { image: logo.png, gap: 0 }"),
+            Some(ShutdownState::Image(Wallpaper::Detailed { .. }))
+        ));
+    }
+
+    #[test]
+    fn game_mode_matches_class_substrings() {
+        let game: GameMode = serde_yaml_ng::from_str("{ classes: [steam_app, Factorio] }").unwrap();
+        assert!(game.matches_class("steam_app_1091500"));
+        assert!(game.matches_class("factorio"));
+        assert!(!game.matches_class("firefox"));
+        assert!(!game.matches_class(""));
+        let action: Action = serde_yaml_ng::from_str("game_mode: toggle").unwrap();
+        assert!(matches!(
+            action,
+            Action::GameMode {
+                game_mode: Switch::Toggle
+            }
+        ));
+    }
+
+    #[test]
+    fn quirks_scale_brightness_along_the_curve() {
+        let conf: KeyDeckConf = serde_yaml_ng::from_str(
+            "quirks:\n  AB12:\n    brightness_curve: [[100, 100], [0, 20]]\n    image_delay: 5\n",
+        )
+        .unwrap();
+        let quirks = &conf.quirks.unwrap()["AB12"];
+        assert_eq!(quirks.image_delay, Some(5));
+        assert_eq!(quirks.scale_brightness(0), 20);
+        assert_eq!(quirks.scale_brightness(50), 60);
+        assert_eq!(quirks.scale_brightness(100), 100);
+        assert_eq!(DeviceQuirks::default().scale_brightness(42), 42);
+        assert!(serde_yaml_ng::from_str::<DeviceQuirks>("flush: true").is_err());
+
+        let keep_alive = |yaml| serde_yaml_ng::from_str::<DeviceQuirks>(yaml).map(|q| q.keep_alive);
+        assert_eq!(keep_alive("keep_alive: off").unwrap(), Some(KeepAlive::Off));
+        assert_eq!(
+            keep_alive("keep_alive: 5").unwrap(),
+            Some(KeepAlive::Every(5.0))
+        );
+        assert!(keep_alive("keep_alive: 0").is_err());
+        assert!(keep_alive("keep_alive: never").is_err());
+    }
+
+    #[test]
+    fn icons_can_be_layered() {
+        let button: Button = serde_yaml_ng::from_str(
+            "icon: [mic.png, { image: muted.png, position: [48, 0], scale: 0.5 }]",
+        )
+        .unwrap();
+        let icon = button.icon.unwrap();
+        assert_eq!(icon.images(), ["mic.png", "muted.png"]);
+        assert!(matches!(
+            &icon,
+            Icon::Layers(layers) if matches!(
+                layers[1],
+                IconLayer::Detailed { position: Some([48, 0]), scale: Some(scale), .. } if scale == 0.5
+            )
+        ));
+        let button: Button = serde_yaml_ng::from_str("icon: mic.png").unwrap();
+        assert_eq!(button.icon, Some(Icon::File("mic.png".to_string())));
+        assert!(serde_yaml_ng::from_str::<Button>("icon: [{ image: a.png, size: 2 }]").is_err());
+    }
+
+    #[test]
+    fn focus_backends_in_configured_order() {
+        let conf: KeyDeckConf = serde_yaml_ng::from_str("focus_backend: [x11, kwin]").unwrap();
+        assert_eq!(
+            conf.focus_backend,
+            Some(vec![FocusBackend::X11, FocusBackend::KWin])
+        );
+        assert!(serde_yaml_ng::from_str::<KeyDeckConf>("focus_backend: [gnome]").is_err());
+    }
+
+    #[test]
+    fn wait_for_accepts_a_list_of_events() {
+        let wait_for = |yaml: &str| match serde_yaml_ng::from_str(yaml).unwrap() {
+            Action::WaitFor {
+                wait_for_event,
+                mode,
+                ..
+            } => (wait_for_event, mode),
+            _ => panic!("not a wait_for"),
+        };
+        let (events, mode) = wait_for("{ wait_for: focus, timeout: 2.0 }");
+        assert!(matches!(events.events(), [WaitForEvent::Event(e)] if e == "focus"));
+        assert_eq!(mode, None);
+        let (events, mode) =
+            wait_for("{ wait_for: [focus, { event: page, timeout: 5 }, { after: 3 }], mode: all }");
+        assert_eq!(mode, Some(WaitMode::All));
+        assert!(matches!(
+            events.events(),
+            [
+                WaitForEvent::Event(_),
+                WaitForEvent::Detailed {
+                    timeout: Some(_),
+                    ..
+                },
+                WaitForEvent::After { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn generated_button_fills_placeholders() {
+        let generator: Generator = serde_yaml_ng::from_str(
+            "{ from: dir, path: ~/Music, action: [ { exec: \"mpc load '${name}'\" } ] }",
+        )
+        .unwrap();
+        let entry = HashMap::from([("name".to_string(), "Chill".to_string())]);
+        let button = generator.button_for(&entry).unwrap();
+        assert!(matches!(button.text, Some(TextConfig::Simple(ref t)) if t == "Chill"));
+        assert!(
+            matches!(button.actions.as_deref(), Some([Action::Exec { exec, .. }]) if exec == "mpc load 'Chill'")
+        );
+    }
+
+    #[test]
+    fn checklist_items_toggle_on_press() {
+        let generator: Generator =
+            serde_yaml_ng::from_str("{ from: checklist, items: [Milk, Eggs] }").unwrap();
+        let entry = HashMap::from([("path".to_string(), "Groceries/Milk".to_string())]);
+        let button = generator.button_for(&entry).unwrap();
+        assert!(
+            matches!(button.text, Some(TextConfig::Simple(ref t)) if t == "${checklist:label:Groceries/Milk}")
+        );
+        assert!(
+            matches!(button.actions.as_deref(), Some([Action::Check { check }]) if check == "Groceries/Milk")
+        );
+    }
+
+    #[test]
+    fn visibility_limits_days_and_hours() {
+        let work: Visibility = serde_yaml_ng::from_str(
+            "{ days: [mon, tue, wed, thu, Fri], from: \"08:00\", until: \"18:00\" }",
+        )
+        .unwrap();
+        assert!(work.validate().is_ok());
+        assert!(work.is_scheduled(0, 9 * 60));
+        assert!(work.is_scheduled(4, 17 * 60 + 59));
+        assert!(!work.is_scheduled(5, 9 * 60));
+        assert!(!work.is_scheduled(2, 20 * 60));
+        assert!(Visibility::default().is_scheduled(6, 0));
+        let broken: Visibility = serde_yaml_ng::from_str("{ days: [someday] }").unwrap();
+        assert!(broken.validate().is_err());
+    }
+
+    #[test]
+    fn focus_accepts_string_and_structured_target() {
+        let simple: Action = serde_yaml_ng::from_str("focus: firefox\n").unwrap();
+        assert!(matches!(simple, Action::Focus { focus: FocusTarget::Name(ref n), .. } if n == "firefox"));
+
+        let detailed: Action =
+            serde_yaml_ng::from_str("focus: { class: konsole, exact: true, cycle: true }\n").unwrap();
+        match detailed {
+            Action::Focus { focus: FocusTarget::Detailed(spec), .. } => {
+                assert_eq!(spec.class.as_deref(), Some("konsole"));
+                assert!(spec.exact && spec.cycle);
+            }
+            other => panic!("unexpected action: {:?}", other),
+        }
+
+        let launching: Action = serde_yaml_ng::from_str(
+            "{ focus: firefox, fallback_exec: firefox, wait_for_window: { class: firefox } }",
+        )
+        .unwrap();
+        match launching {
+            Action::Focus {
+                fallback_exec,
+                wait_for_window,
+                ..
+            } => {
+                assert_eq!(fallback_exec.as_deref(), Some("firefox"));
+                assert_eq!(wait_for_window.unwrap().class.as_deref(), Some("firefox"));
+            }
+            other => panic!("unexpected action: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn keys_and_text_can_target_a_window() {
+        let key: Action =
+            serde_yaml_ng::from_str("{ key: space, target: { class: spotify } }").unwrap();
+        match key {
+            Action::Key {
+                target: Some(FocusTarget::Detailed(spec)),
+                ..
+            } => assert_eq!(spec.class.as_deref(), Some("spotify")),
+            other => panic!("unexpected action: {:?}", other),
+        }
+
+        let text: Action = serde_yaml_ng::from_str("{ text: \"ls\\n\", target: konsole }").unwrap();
+        assert!(
+            matches!(text, Action::Text { target: Some(FocusTarget::Name(ref n)), .. } if n == "konsole")
+        );
+
+        let plain: Action = serde_yaml_ng::from_str("key: space\n").unwrap();
+        assert!(matches!(plain, Action::Key { target: None, .. }));
+    }
+
+    #[test]
+    fn confirm_action_asks_before_running() {
+        let action: Action = serde_yaml_ng::from_str(
+            "confirm: Shut down?\nthen: [{ exec: poweroff }]\nelse: [{ notify: Kept running }]\ntimeout: 5\n",
+        )
+        .unwrap();
+        match action {
+            Action::Confirm {
+                message,
+                confirm_actions,
+                cancel_actions: Some(cancel),
+                timeout: Some(timeout),
+            } => {
+                assert_eq!(message, "Shut down?");
+                assert!(matches!(confirm_actions[..], [Action::Exec { .. }]));
+                assert!(matches!(cancel[..], [Action::Notify { .. }]));
+                assert_eq!(timeout, 5.0);
+            }
+            other => panic!("unexpected action: {:?}", other),
+        }
+
+        let plain: Action = serde_yaml_ng::from_str("confirm: Sure?\nthen: []\n").unwrap();
+        assert!(matches!(
+            plain,
+            Action::Confirm {
+                cancel_actions: None,
+                timeout: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn http_action_keeps_its_options() {
+        let action: Action = serde_yaml_ng::from_str(
+            "http: https://ha.local/api/webhook/lamp\nbody: '{}'\nheaders: { X-Token: abc }\ncapture: lamp\n",
+        )
+        .unwrap();
+        match action {
+            Action::Http {
+                http,
+                headers,
+                capture,
+                method,
+                ..
+            } => {
+                assert_eq!(http, "https://ha.local/api/webhook/lamp");
+                assert_eq!(headers.unwrap()["X-Token"], "abc");
+                assert_eq!(capture.as_deref(), Some("lamp"));
+                assert!(method.is_none());
+            }
+            other => panic!("unexpected action: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn audio_device_action_names_its_devices() {
+        let action: Action =
+            serde_yaml_ng::from_str("audio_device: { set_default_sink: \"Headset, Speakers\" }\n")
+                .unwrap();
+        match action {
+            Action::AudioDevice { audio_device } => {
+                assert_eq!(
+                    audio_device.set_default_sink.as_deref(),
+                    Some("Headset, Speakers")
+                );
+                assert!(audio_device.set_default_source.is_none());
+            }
+            other => panic!("unexpected action: {:?}", other),
+        }
+        assert!(serde_yaml_ng::from_str::<Action>("audio_device: { sink: Headset }\n").is_err());
+    }
+
+    #[test]
+    fn unknown_page_fields_are_found() {
+        let conf: KeyDeckConf = serde_yaml_ng::from_str(
+            "templates:\n  Base:\n    button1: { text: A }\n    thme: dark\ndefault:\n  Home:\n    on_tick: []\n    r2c1: { text: B }\n    backgroud: \"0xff0000\"\n    layers:\n      fn:\n        buton2: { text: C }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            conf.unknown_fields(),
+            [
+                vec!["templates", "Base", "thme"],
+                vec!["default", "Home", "backgroud"],
+                vec!["default", "Home", "layers", "fn", "buton2"],
+            ]
+        );
+        assert!(is_button_key("button12") && is_button_key("r1c3"));
+        assert!(!is_button_key("button0") && !is_button_key("buttons"));
+    }
+
+    #[test]
+    fn buttons_take_a_builtin_behavior() {
+        let button: Button = serde_yaml_ng::from_str("text: Next\nbuiltin: next_page\n").unwrap();
+        assert_eq!(button.builtin, Some(ButtonBuiltin::NextPage));
+        assert!(button.actions.is_none());
+        let button: Button = serde_yaml_ng::from_str("builtin: brightness_down\n").unwrap();
+        assert_eq!(button.builtin, Some(ButtonBuiltin::BrightnessDown));
+        assert!(serde_yaml_ng::from_str::<Button>("builtin: page_scroll\n").is_err());
+    }
+
+    #[test]
+    fn encoders_take_a_builtin_binding() {
+        let encoder: Encoder = serde_yaml_ng::from_str("builtin: page_scroll\n").unwrap();
+        assert_eq!(encoder.builtin, Some(EncoderBuiltin::PageScroll));
+        assert!(encoder.twist_right.is_none());
+        assert!(serde_yaml_ng::from_str::<Encoder>("builtin: volume\n").is_err());
+    }
+
+    #[test]
+    fn encoders_bind_twists_while_pressed() {
+        let encoder: Encoder = serde_yaml_ng::from_str(
+            "twist_right: [{ key: volumeup }]\npress_twist_right: [{ key: brightnessup }]\n",
+        )
+        .unwrap();
+        assert!(encoder.has_press_twist());
+        assert!(encoder.press_twist_left.is_none());
+        let plain: Encoder = serde_yaml_ng::from_str("twist_right: [{ key: volumeup }]\n").unwrap();
+        assert!(!plain.has_press_twist());
+    }
+
+    #[test]
+    fn wallpapers_are_an_image_with_an_optional_gap() {
+        let page: Page =
+            serde_yaml_ng::from_str("wallpaper: beach.jpg\nbutton1:\n  text: A\n").unwrap();
+        assert_eq!(
+            page.wallpaper,
+            Some(Wallpaper::File("beach.jpg".to_string()))
+        );
+        assert_eq!(page.buttons.len(), 1);
+        let page: Page =
+            serde_yaml_ng::from_str("wallpaper: { image: beach.jpg, gap: 0.1 }\n").unwrap();
+        let wallpaper = page.wallpaper.unwrap();
+        assert_eq!((wallpaper.image(), wallpaper.gap()), ("beach.jpg", 0.1));
+        assert!(serde_yaml_ng::from_str::<Wallpaper>("{ image: a.png, scale: 2 }").is_err());
+    }
+
+    #[test]
+    fn replaced_device_keeps_its_pages_and_hotkeys() {
+        let mut conf: KeyDeckConf = serde_yaml_ng::from_str(
+            "hotkeys:\n  ctrl+1:\n    device: OLD\n    actions: [ { text: hi } ]\nwebhooks:\n  door: { device: OLD, actions: [ { jump: Main } ] }\nOLD:\n  Main: {}\ndefault:\n  Home: {}\n",
+        )
+        .unwrap();
+        assert!(conf.rekey_page_group("default", "NEW").is_err());
+        assert!(conf.rekey_page_group("OLD", "default").is_err());
+        conf.rekey_page_group("OLD", " NEW ").unwrap();
+        let keys: Vec<_> = conf.page_groups.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["NEW", "default"]);
+        assert_eq!(
+            conf.hotkeys.as_ref().unwrap()["ctrl+1"].device(),
+            Some("NEW")
+        );
+        assert_eq!(conf.webhooks.as_ref().unwrap()["door"].device(), Some("NEW"));
+
+        conf.clone_page_group("default", "SECOND").unwrap();
+        assert!(conf.page_groups["SECOND"].pages.contains_key("Home"));
+        assert!(conf.clone_page_group("NEW", "SECOND").is_err());
+    }
+
+    #[test]
+    fn macro_params_override_defaults() {
+        let m: Macro = serde_yaml_ng::from_str(
+            "params: { app: firefox }\nactions: [ { exec: \"${app} ${url}\" }, { key: \"${key}\" } ]",
+        )
+        .unwrap();
+        let used: Vec<_> = m.used_params().into_iter().collect();
+        assert_eq!(used, ["app", "key", "url"]);
+
+        let params = HashMap::from([("url".to_string(), "example.org".to_string())]);
+        let actions = m.expand("open", &params).unwrap();
+        assert!(matches!(&actions[0], Action::Exec { exec, .. } if exec == "firefox example.org"));
+        assert!(matches!(&actions[1], Action::Key { key, .. } if key == "${key}"));
+    }
+
+    #[test]
+    fn if_actions_take_a_condition() {
+        let actions: Vec<Action> = serde_yaml_ng::from_str(
+            "- if: \"${var:mode} == work\"\n  then:\n    - jump: Work\n  else:\n    - jump: Home\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            &actions[0],
+            Action::If { condition, then, else_actions: Some(_) }
+                if condition == "${var:mode} == work" && then.len() == 1
+        ));
+        assert!(serde_yaml_ng::from_str::<Action>("{ if: x, then: [], els: [] }").is_err());
+    }
+}
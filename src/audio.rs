@@ -0,0 +1,442 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Audio sink volume access for encoder widgets and the `${audio:...}` provider, the
+//! default output and input switched by the `audio_device` action, and the sounds played
+//! on key presses.
+//!
+//! Uses `wpctl` (PipeWire/WirePlumber) and falls back to `pactl` (PulseAudio or
+//! pipewire-pulse). Volumes are always expressed as percentages (0-100+). Default
+//! devices go through `pactl`, which pipewire-pulse answers as well. Sounds are played
+//! with `pw-play`, falling back to `paplay`.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Sink name that refers to the system default output
+pub const DEFAULT_SINK: &str = "default";
+
+/// How long a volume read stays current. Encoder widgets redraw on every tick, and each
+/// read runs a process; changes made outside keydeck show up after at most this long.
+const VOLUME_TTL: Duration = Duration::from_secs(3);
+
+/// Last volume read or set of each sink, with when it was
+static VOLUMES: LazyLock<Mutex<HashMap<String, (Instant, f32)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Returns the current volume of `sink` as a percentage, or None if no backend answered.
+pub fn get_volume(sink: &str) -> Option<f32> {
+    cached_volume(sink, || read_volume(sink))
+}
+
+/// The volume of `sink` known for less than [`VOLUME_TTL`], or the one `read` finds
+fn cached_volume(sink: &str, read: impl FnOnce() -> Option<f32>) -> Option<f32> {
+    if let Some((at, volume)) = VOLUMES.lock().unwrap().get(sink) {
+        if at.elapsed() < VOLUME_TTL {
+            return Some(*volume);
+        }
+    }
+    let volume = read()?;
+    remember_volume(sink, volume);
+    Some(volume)
+}
+
+fn remember_volume(sink: &str, volume: f32) {
+    VOLUMES
+        .lock()
+        .unwrap()
+        .insert(sink.to_string(), (Instant::now(), volume));
+}
+
+fn read_volume(sink: &str) -> Option<f32> {
+    if let Some(out) = run("wpctl", &["get-volume", wpctl_sink(sink)]) {
+        if let Some(volume) = parse_wpctl_volume(&out) {
+            return Some(volume);
+        }
+    }
+    run("pactl", &["get-sink-volume", pactl_sink(sink)]).and_then(|out| parse_pactl_volume(&out))
+}
+
+/// Returns whether `sink` is muted, or None if no backend answered.
+pub fn is_muted(sink: &str) -> Option<bool> {
+    if let Some(out) = run("wpctl", &["get-volume", wpctl_sink(sink)]) {
+        if parse_wpctl_volume(&out).is_some() {
+            return Some(out.contains("[MUTED]"));
+        }
+    }
+    run("pactl", &["get-sink-mute", pactl_sink(sink)]).map(|out| out.contains("yes"))
+}
+
+/// Sets the volume of `sink` to `percent`.
+pub fn set_volume(sink: &str, percent: f32) -> Result<(), String> {
+    let percent = percent.max(0.0);
+    let wpctl_value = format!("{:.2}", percent / 100.0);
+    let pactl_value = format!("{:.0}%", percent);
+    if run("wpctl", &["set-volume", wpctl_sink(sink), &wpctl_value]).is_none()
+        && run(
+            "pactl",
+            &["set-sink-volume", pactl_sink(sink), &pactl_value],
+        )
+        .is_none()
+    {
+        return Err(format!("Failed to set volume of audio sink '{}'", sink));
+    }
+    // As the backends report it back
+    remember_volume(sink, percent.round());
+    Ok(())
+}
+
+fn wpctl_sink(sink: &str) -> &str {
+    if sink == DEFAULT_SINK {
+        "@DEFAULT_AUDIO_SINK@"
+    } else {
+        sink
+    }
+}
+
+fn pactl_sink(sink: &str) -> &str {
+    if sink == DEFAULT_SINK {
+        "@DEFAULT_SINK@"
+    } else {
+        sink
+    }
+}
+
+/// Outputs or inputs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeviceKind {
+    Sink,
+    Source,
+}
+
+impl DeviceKind {
+    /// The word pactl uses for these devices
+    fn pactl_name(self) -> &'static str {
+        match self {
+            DeviceKind::Sink => "sink",
+            DeviceKind::Source => "source",
+        }
+    }
+}
+
+/// An audio output or input
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioDevice {
+    /// Node name, e.g. `alsa_output.usb-Logitech_G435-00.analog-stereo`
+    pub name: String,
+    /// Name shown by the desktop, e.g. `G435 Headset Analog Stereo`
+    pub description: String,
+}
+
+/// Outputs or inputs of the system. Monitors of outputs are no inputs of their own and
+/// are left out.
+pub fn devices(kind: DeviceKind) -> Result<Vec<AudioDevice>, String> {
+    let list = format!("{}s", kind.pactl_name());
+    run_c("pactl", &["list", &list])
+        .map(|out| parse_pactl_devices(&out))
+        .ok_or_else(|| format!("Failed to list audio {} with pactl", list))
+}
+
+/// The default output or input
+pub fn default_device(kind: DeviceKind) -> Option<AudioDevice> {
+    let command = format!("get-default-{}", kind.pactl_name());
+    let name = match run_c("pactl", &[&command]) {
+        Some(name) => name.trim().to_string(),
+        // pactl before 15 only tells it in its summary
+        None => {
+            let label = match kind {
+                DeviceKind::Sink => "Default Sink:",
+                DeviceKind::Source => "Default Source:",
+            };
+            run_c("pactl", &["info"])?
+                .lines()
+                .find_map(|line| line.trim().strip_prefix(label))?
+                .trim()
+                .to_string()
+        }
+    };
+    let description = devices(kind)
+        .ok()
+        .and_then(|devices| devices.into_iter().find(|device| device.name == name))
+        .map_or_else(|| name.clone(), |device| device.description);
+    Some(AudioDevice { name, description })
+}
+
+/// Makes the device `target` names the default output or input, and returns it. `target`
+/// is a device name or part of a description; several of them separated by commas are
+/// switched through in turn, and `next` switches through all devices. Streams that follow
+/// the default move along.
+pub fn set_default_device(kind: DeviceKind, target: &str) -> Result<AudioDevice, String> {
+    let devices = devices(kind)?;
+    let current = default_device(kind).map(|device| device.name);
+    let device = pick_device(&devices, current.as_deref(), target)
+        .ok_or_else(|| format!("No audio {} matches '{}'", kind.pactl_name(), target))?;
+    let command = format!("set-default-{}", kind.pactl_name());
+    run("pactl", &[&command, &device.name])
+        .map(|_| device.clone())
+        .ok_or_else(|| {
+            format!(
+                "Failed to switch the audio {} to {}",
+                kind.pactl_name(),
+                device.name
+            )
+        })
+}
+
+/// The device to switch to from `current`: the one after it among those `target` names
+fn pick_device<'a>(
+    devices: &'a [AudioDevice],
+    current: Option<&str>,
+    target: &str,
+) -> Option<&'a AudioDevice> {
+    let candidates: Vec<&AudioDevice> = if target.trim() == "next" {
+        devices.iter().collect()
+    } else {
+        let mut candidates = Vec::new();
+        for wanted in target.split(',').map(str::trim).filter(|w| !w.is_empty()) {
+            let wanted_lower = wanted.to_lowercase();
+            let found = devices
+                .iter()
+                .find(|device| device.name == wanted)
+                .or_else(|| {
+                    devices
+                        .iter()
+                        .find(|device| device.description.to_lowercase().contains(&wanted_lower))
+                });
+            if let Some(device) = found.filter(|device| !candidates.contains(device)) {
+                candidates.push(device);
+            }
+        }
+        candidates
+    };
+    let position = candidates
+        .iter()
+        .position(|device| Some(device.name.as_str()) == current);
+    match position {
+        Some(position) => candidates.get((position + 1) % candidates.len()).copied(),
+        None => candidates.first().copied(),
+    }
+}
+
+/// Parses the blocks of `pactl list sinks` or `pactl list sources`
+fn parse_pactl_devices(output: &str) -> Vec<AudioDevice> {
+    let mut devices: Vec<AudioDevice> = Vec::new();
+    let mut name: Option<String> = None;
+    for line in output.lines() {
+        let line = line.trim();
+        if let Some(value) = line.strip_prefix("Name:") {
+            name = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("Description:") {
+            if let Some(name) = name.take().filter(|name| !name.ends_with(".monitor")) {
+                devices.push(AudioDevice {
+                    name,
+                    description: value.trim().to_string(),
+                });
+            }
+        }
+    }
+    devices
+}
+
+/// Name of the built-in key click sample
+pub const CLICK_SAMPLE: &str = "click";
+
+/// Plays a sound file, or the built-in click, at `volume` (0.0-1.0) without waiting for
+/// it to end. Relative paths are looked up in the configuration directory.
+pub fn play_sample(sample: &str, volume: f32) -> Result<(), String> {
+    let path = match sample.trim() {
+        CLICK_SAMPLE => click_path()?,
+        sample => keydeck::get_config_dir().join(sample),
+    };
+    if !path.is_file() {
+        return Err(format!("Sound file not found: {}", path.display()));
+    }
+    let mut child = player(&path.to_string_lossy(), volume.clamp(0.0, 1.0))?;
+    // Reaped by a thread of its own, so presses don't wait for the sound
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn player(path: &str, volume: f32) -> Result<Child, String> {
+    Command::new("pw-play")
+        .args(["--volume", &format!("{:.2}", volume), path])
+        .spawn()
+        .or_else(|_| {
+            let volume = format!("--volume={}", (volume * 65536.0) as u32);
+            Command::new("paplay").args([volume.as_str(), path]).spawn()
+        })
+        .map_err(|e| format!("Failed to play {} with pw-play or paplay: {}", path, e))
+}
+
+#[cfg(target_os = "macos")]
+fn player(path: &str, volume: f32) -> Result<Child, String> {
+    Command::new("afplay")
+        .args(["-v", &format!("{:.2}", volume), path])
+        .spawn()
+        .map_err(|e| format!("Failed to run afplay: {}", e))
+}
+
+/// The Windows sound player has no volume of its own
+#[cfg(target_os = "windows")]
+fn player(path: &str, _volume: f32) -> Result<Child, String> {
+    let script = format!(
+        "(New-Object Media.SoundPlayer '{}').PlaySync()",
+        path.replace('\'', "''")
+    );
+    Command::new("powershell")
+        .args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", &script])
+        .spawn()
+        .map_err(|e| format!("Failed to play {}: {}", path, e))
+}
+
+/// The built-in click, written once to the temp directory
+fn click_path() -> Result<PathBuf, String> {
+    static CLICK: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+    CLICK
+        .get_or_init(|| {
+            let path = std::env::temp_dir().join("keydeck-click.wav");
+            fs::write(&path, click_wav())
+                .map(|_| path)
+                .map_err(|e| format!("Failed to write the click sound: {}", e))
+        })
+        .clone()
+}
+
+/// A short, quickly fading tick as a 16-bit mono WAV file
+fn click_wav() -> Vec<u8> {
+    const RATE: u32 = 44100;
+    let samples: Vec<i16> = (0..RATE / 40)
+        .map(|i| {
+            let t = i as f32 / RATE as f32;
+            let tone = (2.0 * PI * 2200.0 * t).sin() * 0.6 + (2.0 * PI * 3700.0 * t).sin() * 0.4;
+            (tone * (-t * 400.0).exp() * i16::MAX as f32 * 0.8) as i16
+        })
+        .collect();
+    let data_len = samples.len() as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&RATE.to_le_bytes());
+    wav.extend_from_slice(&(RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    samples
+        .iter()
+        .for_each(|sample| wav.extend_from_slice(&sample.to_le_bytes()));
+    wav
+}
+
+/// Runs a command and returns its stdout if it exited successfully
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Runs a command as [`run`] does, with untranslated output to parse
+fn run_c(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .env("LC_ALL", "C")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parses `wpctl get-volume` output, e.g. "Volume: 0.45 [MUTED]"
+fn parse_wpctl_volume(output: &str) -> Option<f32> {
+    let value = output
+        .trim()
+        .strip_prefix("Volume:")?
+        .split_whitespace()
+        .next()?;
+    value.parse::<f32>().ok().map(|v| (v * 100.0).round())
+}
+
+/// Parses `pactl get-sink-volume` output, taking the first channel's percentage
+fn parse_pactl_volume(output: &str) -> Option<f32> {
+    output
+        .split_whitespace()
+        .find_map(|token| token.strip_suffix('%'))
+        .and_then(|value| value.parse::<f32>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_wpctl_output() {
+        assert_eq!(parse_wpctl_volume("Volume: 0.45\n"), Some(45.0));
+        assert_eq!(parse_wpctl_volume("Volume: 1.00 [MUTED]\n"), Some(100.0));
+        assert_eq!(parse_wpctl_volume("garbage"), None);
+    }
+
+    #[test]
+    fn volumes_are_read_once_a_while() {
+        let reads = std::cell::Cell::new(0);
+        let read = || {
+            reads.set(reads.get() + 1);
+            Some(30.0)
+        };
+        assert_eq!(cached_volume("test-sink", read), Some(30.0));
+        assert_eq!(cached_volume("test-sink", read), Some(30.0));
+        assert_eq!(reads.get(), 1);
+        remember_volume("test-sink", 55.0);
+        assert_eq!(cached_volume("test-sink", read), Some(55.0));
+        assert_eq!(cached_volume("other-sink", || None), None);
+    }
+
+    #[test]
+    fn click_is_a_complete_wav() {
+        let wav = click_wav();
+        assert_eq!(&wav[..4], b"RIFF");
+        let riff_len = u32::from_le_bytes(wav[4..8].try_into().unwrap());
+        assert_eq!(riff_len as usize, wav.len() - 8);
+    }
+
+    #[test]
+    fn parse_pactl_output() {
+        let out = "Volume: front-left: 29491 /  45% / -20.81 dB,   front-right: 29491 /  45% / -20.81 dB\n";
+        assert_eq!(parse_pactl_volume(out), Some(45.0));
+        assert_eq!(parse_pactl_volume("Volume: n/a"), None);
+    }
+
+    #[test]
+    fn devices_are_switched_in_turn() {
+        let out = "Sink #55\n\tState: RUNNING\n\tName: alsa_output.pci.analog-stereo\n\tDescription: Built-in Audio Analog Stereo\n\tDriver: PipeWire\nSink #61\n\tName: alsa_output.usb-G435.analog-stereo\n\tDescription: G435 Headset\nSource #62\n\tName: alsa_output.usb-G435.analog-stereo.monitor\n\tDescription: Monitor of G435 Headset\n";
+        let devices = parse_pactl_devices(out);
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[1].description, "G435 Headset");
+
+        let speakers = "alsa_output.pci.analog-stereo";
+        let headset = "alsa_output.usb-G435.analog-stereo";
+        let pick =
+            |current, target| pick_device(&devices, current, target).map(|d| d.name.as_str());
+        assert_eq!(pick(Some(speakers), "headset"), Some(headset));
+        assert_eq!(pick(Some(headset), "headset, built-in"), Some(speakers));
+        assert_eq!(pick(Some(speakers), "headset, built-in"), Some(headset));
+        assert_eq!(pick(None, "built-in, headset"), Some(speakers));
+        assert_eq!(pick(Some(headset), "next"), Some(speakers));
+        assert_eq!(pick(Some(headset), speakers), Some(speakers));
+        assert_eq!(pick(Some(headset), "hdmi"), None);
+    }
+}
@@ -0,0 +1,244 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Timings of the rendering hot path, for `keydeck --bench`.
+//!
+//! The client sends `bench [serial]` on the control socket; the daemon redraws every
+//! page of its devices a few times with the frame limit lifted and answers with one
+//! JSON line holding a [`BenchReport`] per device. While a bench runs, the renderer
+//! records how long each key took to draw and each flush took to reach the device;
+//! otherwise [`record`] costs a single atomic load.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Times every page is redrawn
+pub const ROUNDS: usize = 5;
+
+/// Keys listed as the slowest in a report
+const SLOWEST: usize = 5;
+
+/// A point of the hot path that is timed
+#[derive(Debug, Clone, Copy)]
+pub enum Probe {
+    /// Drawing a key, numbered as in the configuration
+    Render(u8),
+    /// Sending the drawn keys to the device
+    Flush,
+}
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static SAMPLES: Mutex<Vec<(Probe, Duration)>> = Mutex::new(Vec::new());
+
+/// Where the reports of the bench in progress are sent
+static PENDING: Mutex<Option<Sender<Vec<BenchReport>>>> = Mutex::new(None);
+
+/// Records the time since `started` for `probe`, when a bench is running
+pub fn record(probe: Probe, started: Instant) {
+    if RECORDING.load(Ordering::Relaxed) {
+        SAMPLES.lock().unwrap().push((probe, started.elapsed()));
+    }
+}
+
+/// Starts or stops recording samples
+pub fn set_recording(recording: bool) {
+    SAMPLES.lock().unwrap().clear();
+    RECORDING.store(recording, Ordering::Relaxed);
+}
+
+/// Samples recorded since the last call
+pub fn take_samples() -> Vec<(Probe, Duration)> {
+    std::mem::take(&mut *SAMPLES.lock().unwrap())
+}
+
+/// Sends the reports of the next bench to `reply`
+#[cfg(unix)]
+pub fn await_reports(reply: Sender<Vec<BenchReport>>) {
+    PENDING.lock().unwrap().replace(reply);
+}
+
+/// Hands the reports to the client waiting for them
+pub fn deliver(reports: Vec<BenchReport>) {
+    if let Some(reply) = PENDING.lock().unwrap().take() {
+        let _ = reply.send(reports);
+    }
+}
+
+/// Spread of a set of durations, in milliseconds
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Timing {
+    pub samples: usize,
+    pub avg_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Timing {
+    pub fn of(durations: &[Duration]) -> Self {
+        let ms: Vec<f64> = durations.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        if ms.is_empty() {
+            return Timing::default();
+        }
+        Timing {
+            samples: ms.len(),
+            avg_ms: ms.iter().sum::<f64>() / ms.len() as f64,
+            min_ms: ms.iter().copied().fold(f64::INFINITY, f64::min),
+            max_ms: ms.iter().copied().fold(0.0, f64::max),
+        }
+    }
+}
+
+/// Average time a key of a page took to draw
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyTiming {
+    pub page: String,
+    pub button: u8,
+    pub avg_ms: f64,
+}
+
+/// What a bench measured on one device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub sn: String,
+    pub model: String,
+    pub pages: usize,
+    /// Drawing one key
+    pub render: Timing,
+    /// Showing a page: drawing all of its keys and sending them
+    pub page_switch: Timing,
+    /// Sending the drawn keys of a page to the device
+    pub flush: Timing,
+    /// Keys that took the longest to draw
+    pub slowest: Vec<KeyTiming>,
+}
+
+impl BenchReport {
+    /// The report of `renders` (page, key and time), page `switches` and `flushes`
+    pub fn new(
+        sn: String,
+        model: String,
+        pages: usize,
+        renders: Vec<(String, u8, Duration)>,
+        switches: &[Duration],
+        flushes: &[Duration],
+    ) -> Self {
+        let mut keys: Vec<(String, u8, Vec<Duration>)> = vec![];
+        for (page, button, time) in &renders {
+            match keys.iter_mut().find(|(p, b, _)| p == page && b == button) {
+                Some((_, _, times)) => times.push(*time),
+                None => keys.push((page.clone(), *button, vec![*time])),
+            }
+        }
+        let mut slowest: Vec<KeyTiming> = keys
+            .into_iter()
+            .map(|(page, button, times)| KeyTiming {
+                page,
+                button,
+                avg_ms: Timing::of(&times).avg_ms,
+            })
+            .collect();
+        slowest.sort_by(|a, b| b.avg_ms.total_cmp(&a.avg_ms));
+        slowest.truncate(SLOWEST);
+
+        let render_times: Vec<Duration> = renders.iter().map(|(_, _, time)| *time).collect();
+        BenchReport {
+            sn,
+            model,
+            pages,
+            render: Timing::of(&render_times),
+            page_switch: Timing::of(switches),
+            flush: Timing::of(flushes),
+            slowest,
+        }
+    }
+}
+
+/// `keydeck --bench [SERIAL]`: runs a bench on the devices of the running daemon
+#[cfg(unix)]
+pub fn print_bench(serial: Option<&str>, json: bool) {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = match UnixStream::connect(crate::listener_context::control_socket_path()) {
+        Ok(stream) => stream,
+        Err(_) => {
+            eprintln!("Error: the keydeck daemon is not running");
+            std::process::exit(1);
+        }
+    };
+    let line = match serial {
+        Some(serial) => format!("bench {}\n", serial.trim()),
+        None => "bench\n".to_string(),
+    };
+    if let Err(e) = stream.write_all(line.as_bytes()) {
+        eprintln!("Error: failed to write to control socket: {}", e);
+        std::process::exit(1);
+    }
+    let mut reply = String::new();
+    let _ = BufReader::new(stream).read_line(&mut reply);
+    if json {
+        println!("{}", reply.trim());
+        return;
+    }
+    let reports: Vec<BenchReport> = serde_json::from_str(reply.trim()).unwrap_or_default();
+    if reports.is_empty() {
+        eprintln!("Error: no device with a screen to measure");
+        std::process::exit(1);
+    }
+    let row = |name: &str, timing: &Timing| {
+        println!(
+            "  {:<14} avg {:>7.2} ms  min {:>7.2} ms  max {:>7.2} ms  ({} samples)",
+            name, timing.avg_ms, timing.min_ms, timing.max_ms, timing.samples
+        );
+    };
+    for report in reports {
+        println!(
+            "{} ({}), {} pages, {} rounds",
+            report.model, report.sn, report.pages, ROUNDS
+        );
+        row("key render", &report.render);
+        row("page switch", &report.page_switch);
+        row("USB flush", &report.flush);
+        if !report.slowest.is_empty() {
+            println!("  slowest keys:");
+            for key in &report.slowest {
+                println!(
+                    "    {:>7.2} ms  {} button{}",
+                    key.avg_ms, key.page, key.button
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_averages_keys_and_lists_the_slowest() {
+        let ms = Duration::from_millis;
+        let renders = vec![
+            ("Main".to_string(), 1, ms(2)),
+            ("Main".to_string(), 2, ms(6)),
+            ("Main".to_string(), 1, ms(4)),
+        ];
+        let report = BenchReport::new(
+            "SN".to_string(),
+            "Deck".to_string(),
+            1,
+            renders,
+            &[ms(20), ms(30)],
+            &[],
+        );
+        assert_eq!(report.render.samples, 3);
+        assert_eq!(report.render.avg_ms, 4.0);
+        assert_eq!(report.page_switch.max_ms, 30.0);
+        assert_eq!(report.flush.samples, 0);
+        assert_eq!(report.slowest[0].button, 2);
+        assert_eq!(report.slowest[1].avg_ms, 3.0);
+    }
+}
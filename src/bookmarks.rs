@@ -0,0 +1,396 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Bookmarks of Firefox and of the Chromium family (Chrome, Chromium, Brave, Edge), for
+//! pages generated from a bookmark folder.
+//!
+//! Chromium browsers keep the bookmarks of a profile in a JSON file. Firefox keeps them
+//! in a database that it locks while running, so they are read from the newest of its
+//! daily backups in `bookmarkbackups` (JSON compressed as `mozlz4`); bookmarks added
+//! since then show after the next backup.
+
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Browsers that can be read, in the order they are tried when none is configured
+pub const BROWSERS: [&str; 5] = ["firefox", "chrome", "chromium", "brave", "edge"];
+
+/// Largest decompressed Firefox backup accepted
+const MAX_BACKUP_SIZE: usize = 64 * 1024 * 1024;
+
+/// A bookmarked address
+#[derive(Debug)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+}
+
+/// A folder or bookmark of the bookmark tree
+struct Node {
+    title: String,
+    /// Browser-independent name of a root folder: `toolbar`, `menu`, `other` or `mobile`
+    alias: Option<&'static str>,
+    url: Option<String>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn is_named(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.title.to_lowercase() == name || self.alias == Some(name.as_str())
+    }
+}
+
+/// The bookmarks directly in `folder` ("toolbar/Work"), of `browser` or of the first
+/// browser having that folder
+pub fn read(browser: Option<&str>, folder: &str) -> Result<Vec<Bookmark>, String> {
+    let browsers = match browser {
+        Some(browser) if BROWSERS.contains(&browser) => vec![browser],
+        Some(browser) => {
+            return Err(format!(
+                "Unknown browser '{}', expected one of {}",
+                browser,
+                BROWSERS.join(", ")
+            ))
+        }
+        None => BROWSERS.to_vec(),
+    };
+    let mut searched = Vec::new();
+    for browser in browsers {
+        let Some(roots) = roots(browser)? else {
+            continue;
+        };
+        searched.push(browser);
+        if let Some(found) = find_folder(&roots, folder) {
+            return Ok(found
+                .children
+                .iter()
+                .filter_map(|node| {
+                    let url = node.url.as_ref().filter(|url| !url.starts_with("place:"))?;
+                    Some(Bookmark {
+                        title: node.title.clone(),
+                        url: url.clone(),
+                    })
+                })
+                .collect());
+        }
+    }
+    match searched.is_empty() {
+        true => Err("No browser bookmarks found".to_string()),
+        false => Err(format!(
+            "No bookmark folder '{}' in {}",
+            folder,
+            searched.join(", ")
+        )),
+    }
+}
+
+/// Follows the `/` separated folder names from a root folder; an empty path is the
+/// toolbar
+fn find_folder<'a>(roots: &'a [Node], path: &str) -> Option<&'a Node> {
+    let mut names = path
+        .split('/')
+        .map(str::trim)
+        .filter(|name| !name.is_empty());
+    let first = names.next().unwrap_or("toolbar");
+    let mut folder = roots.iter().find(|node| node.is_named(first))?;
+    for name in names {
+        folder = folder
+            .children
+            .iter()
+            .find(|node| node.url.is_none() && node.is_named(name))?;
+    }
+    Some(folder)
+}
+
+/// The root folders of a browser; None when it has no bookmarks on this system
+fn roots(browser: &str) -> Result<Option<Vec<Node>>, String> {
+    if browser == "firefox" {
+        let Some(backup) = firefox_dirs().iter().find_map(|dir| newest_backup(dir)) else {
+            return Ok(None);
+        };
+        let data =
+            fs::read(&backup).map_err(|e| format!("Failed to read {}: {}", backup.display(), e))?;
+        let json = decompress_mozlz4(&data)
+            .map_err(|e| format!("Failed to read {}: {}", backup.display(), e))?;
+        let tree: Value = serde_json::from_slice(&json)
+            .map_err(|e| format!("Invalid bookmark backup {}: {}", backup.display(), e))?;
+        return Ok(Some(firefox_node(&tree).children));
+    }
+    let Some(file) = chromium_dirs(browser)
+        .into_iter()
+        .map(|dir| dir.join("Default").join("Bookmarks"))
+        .find(|file| file.is_file())
+    else {
+        return Ok(None);
+    };
+    let content = fs::read_to_string(&file)
+        .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+    let tree: Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid bookmarks file {}: {}", file.display(), e))?;
+    let roots = [
+        ("bookmark_bar", "toolbar"),
+        ("other", "other"),
+        ("synced", "mobile"),
+    ];
+    Ok(Some(
+        roots
+            .iter()
+            .filter_map(|(key, alias)| {
+                let mut node = chromium_node(tree.get("roots")?.get(key)?);
+                node.alias = Some(alias);
+                Some(node)
+            })
+            .collect(),
+    ))
+}
+
+fn home() -> PathBuf {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_default()
+}
+
+/// Directories that may hold the Firefox `profiles.ini`
+fn firefox_dirs() -> Vec<PathBuf> {
+    let home = home();
+    if cfg!(target_os = "windows") {
+        env::var_os("APPDATA")
+            .map(|appdata| PathBuf::from(appdata).join("Mozilla").join("Firefox"))
+            .into_iter()
+            .collect()
+    } else if cfg!(target_os = "macos") {
+        vec![home.join("Library/Application Support/Firefox")]
+    } else {
+        vec![
+            home.join(".mozilla/firefox"),
+            home.join("snap/firefox/common/.mozilla/firefox"),
+            home.join(".var/app/org.mozilla.firefox/.mozilla/firefox"),
+        ]
+    }
+}
+
+/// User data directories of a Chromium browser, holding its `Default` profile
+fn chromium_dirs(browser: &str) -> Vec<PathBuf> {
+    let home = home();
+    if cfg!(target_os = "windows") {
+        let Some(local) = env::var_os("LOCALAPPDATA").map(PathBuf::from) else {
+            return Vec::new();
+        };
+        let dir = match browser {
+            "chrome" => "Google/Chrome",
+            "chromium" => "Chromium",
+            "brave" => "BraveSoftware/Brave-Browser",
+            _ => "Microsoft/Edge",
+        };
+        vec![local.join(dir).join("User Data")]
+    } else if cfg!(target_os = "macos") {
+        let dir = match browser {
+            "chrome" => "Google/Chrome",
+            "chromium" => "Chromium",
+            "brave" => "BraveSoftware/Brave-Browser",
+            _ => "Microsoft Edge",
+        };
+        vec![home.join("Library/Application Support").join(dir)]
+    } else {
+        let config = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".config"));
+        match browser {
+            "chrome" => vec![config.join("google-chrome")],
+            "chromium" => vec![
+                config.join("chromium"),
+                home.join("snap/chromium/common/chromium"),
+            ],
+            "brave" => vec![config.join("BraveSoftware/Brave-Browser")],
+            _ => vec![config.join("microsoft-edge")],
+        }
+    }
+}
+
+/// The newest bookmark backup of the default Firefox profile in `dir`
+fn newest_backup(dir: &Path) -> Option<PathBuf> {
+    let profile = firefox_profile(dir)?;
+    fs::read_dir(profile.join("bookmarkbackups"))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jsonlz4"))
+        // Named after their date, so the newest sorts last
+        .max()
+}
+
+/// The default profile listed in `profiles.ini`: the one of the installation, else the
+/// one marked as default, else the first
+fn firefox_profile(dir: &Path) -> Option<PathBuf> {
+    let ini = fs::read_to_string(dir.join("profiles.ini")).ok()?;
+    let mut sections: Vec<(String, Vec<(String, String)>)> = Vec::new();
+    for line in ini.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((name.to_string(), Vec::new()));
+        } else if let (Some((key, value)), Some((_, entries))) =
+            (line.split_once('='), sections.last_mut())
+        {
+            entries.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let get = |entries: &[(String, String)], key: &str| {
+        entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    };
+    let installed = sections
+        .iter()
+        .filter(|(name, _)| name.starts_with("Install"))
+        .find_map(|(_, entries)| get(entries, "Default"));
+    if let Some(path) = installed {
+        return Some(dir.join(path));
+    }
+    let profiles: Vec<&Vec<(String, String)>> = sections
+        .iter()
+        .filter(|(name, _)| name.starts_with("Profile"))
+        .map(|(_, entries)| entries)
+        .collect();
+    let profile = profiles
+        .iter()
+        .find(|entries| get(entries, "Default").as_deref() == Some("1"))
+        .or(profiles.first())?;
+    let path = get(profile, "Path")?;
+    match get(profile, "IsRelative").as_deref() {
+        Some("0") => Some(PathBuf::from(path)),
+        _ => Some(dir.join(path)),
+    }
+}
+
+fn firefox_node(value: &Value) -> Node {
+    let text = |key: &str| value.get(key).and_then(Value::as_str);
+    let alias = match text("root") {
+        Some("toolbarFolder") => Some("toolbar"),
+        Some("bookmarksMenuFolder") => Some("menu"),
+        Some("unfiledBookmarksFolder") => Some("other"),
+        Some("mobileFolder") => Some("mobile"),
+        _ => None,
+    };
+    Node {
+        title: text("title").unwrap_or_default().to_string(),
+        alias,
+        url: text("uri").map(str::to_string),
+        children: value
+            .get("children")
+            .and_then(Value::as_array)
+            .map(|children| children.iter().map(firefox_node).collect())
+            .unwrap_or_default(),
+    }
+}
+
+fn chromium_node(value: &Value) -> Node {
+    let text = |key: &str| value.get(key).and_then(Value::as_str);
+    Node {
+        title: text("name").unwrap_or_default().to_string(),
+        alias: None,
+        url: text("url")
+            .filter(|_| text("type") == Some("url"))
+            .map(str::to_string),
+        children: value
+            .get("children")
+            .and_then(Value::as_array)
+            .map(|children| children.iter().map(chromium_node).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// Decompresses a Mozilla `mozlz4` file: a magic number, the decompressed size and an
+/// LZ4 block
+fn decompress_mozlz4(data: &[u8]) -> Result<Vec<u8>, String> {
+    let body = data.strip_prefix(b"mozLz40\0").ok_or("not a mozlz4 file")?;
+    let size = body
+        .get(..4)
+        .map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize)
+        .ok_or("truncated file")?;
+    if size > MAX_BACKUP_SIZE {
+        return Err(format!("too large ({} bytes)", size));
+    }
+    lz4_block(&body[4..], size)
+}
+
+/// Decompresses an LZ4 block: sequences of literal bytes, each followed by a copy of
+/// earlier output
+fn lz4_block(input: &[u8], size: usize) -> Result<Vec<u8>, String> {
+    let corrupt = || "corrupt LZ4 data".to_string();
+    // Lengths of 15 continue in the following bytes, until one is not 255
+    let length = |pos: &mut usize, mut length: usize| -> Result<usize, String> {
+        if length == 15 {
+            loop {
+                let byte = *input.get(*pos).ok_or_else(corrupt)?;
+                *pos += 1;
+                length += byte as usize;
+                if byte != 255 {
+                    break;
+                }
+            }
+        }
+        Ok(length)
+    };
+    let mut output = Vec::with_capacity(size);
+    let mut pos = 0;
+    while pos < input.len() {
+        let token = input[pos];
+        pos += 1;
+        let literals = length(&mut pos, (token >> 4) as usize)?;
+        let literals = input.get(pos..pos + literals).ok_or_else(corrupt)?;
+        output.extend_from_slice(literals);
+        pos += literals.len();
+        if pos >= input.len() {
+            break;
+        }
+        let offset = input
+            .get(pos..pos + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+            .ok_or_else(corrupt)?;
+        pos += 2;
+        let matched = length(&mut pos, (token & 15) as usize)? + 4;
+        if offset == 0 || offset > output.len() || output.len() + matched > size {
+            return Err(corrupt());
+        }
+        // The copy may overlap what it produces, so it goes byte by byte
+        let start = output.len() - offset;
+        for i in 0..matched {
+            output.push(output[start + i]);
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backups_decompress_and_folders_resolve() {
+        // "abc" as literals, then 9 bytes copied from 3 back
+        let mut data = b"mozLz40\0".to_vec();
+        data.extend_from_slice(&12u32.to_le_bytes());
+        data.extend_from_slice(&[0x35, b'a', b'b', b'c', 3, 0]);
+        assert_eq!(decompress_mozlz4(&data).unwrap(), b"abcabcabcabc");
+
+        let tree: Value = serde_json::from_str(
+            r#"{"children": [{"title": "toolbar", "root": "toolbarFolder", "children": [
+                {"title": "Work", "children": [
+                    {"title": "CI", "uri": "https://ci.example.org/"},
+                    {"title": "Recent", "uri": "place:sort=8"}]}]}]}"#,
+        )
+        .unwrap();
+        let roots = firefox_node(&tree).children;
+        let work = find_folder(&roots, "Toolbar/work").unwrap();
+        assert_eq!(work.children.len(), 2);
+        assert_eq!(
+            work.children[0].url.as_deref(),
+            Some("https://ci.example.org/")
+        );
+        assert!(find_folder(&roots, "menu").is_none());
+    }
+}
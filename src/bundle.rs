@@ -0,0 +1,409 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Portable `.keydeck` archives: the configuration together with every icon its buttons
+//! name, so a setup moves to another computer, or is backed up, as a single file.
+//!
+//! The archive is a ZIP holding `config.yaml` and the icons under `icons/`, found as the
+//! daemon finds them. Icons named by a path, absolute or relative to the configuration,
+//! are stored by their file name and the configuration is pointed to them; icon names
+//! with placeholders, such as `cpu_${service:cpu}.png`, bring every icon file they can
+//! name. Importing writes the icons to the default icon dir and replaces the
+//! configuration, after keeping a timestamped copy of it; an icon that already exists with
+//! other content is renamed, skipped or overwritten, as asked.
+
+use crate::pages::KeyDeckConf;
+use keydeck_types::{ConfigFormat, IconPaths};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const CONFIG_ENTRY: &str = "config.yaml";
+const ICONS_PREFIX: &str = "icons/";
+
+/// What to do with an icon of the archive when the icon dir has another file by its name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Conflict {
+    /// Import it under a free name and point the configuration to it
+    Rename,
+    /// Keep the existing file
+    Skip,
+    Overwrite,
+}
+
+impl std::str::FromStr for Conflict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "rename" => Ok(Conflict::Rename),
+            "skip" => Ok(Conflict::Skip),
+            "overwrite" => Ok(Conflict::Overwrite),
+            _ => Err(format!(
+                "Unknown conflict policy '{}' (use rename, skip or overwrite)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ExportReport {
+    /// Icons stored in the archive
+    pub icons: usize,
+    /// Icons named by the configuration that were not found
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    /// Icons written to the icon dir
+    pub icons: usize,
+    /// Icons imported under another name, by their name in the archive
+    pub renamed: BTreeMap<String, String>,
+    /// Icons that kept the existing file of their name
+    pub skipped: Vec<String>,
+    /// Copy of the configuration that was replaced
+    pub backup: Option<String>,
+}
+
+/// Writes the configuration at `config_path` and its icons, looked up in its `icon_dirs`
+/// and then `icon_dir`, to the archive `archive`
+pub fn export(config_path: &Path, icon_dir: &Path, archive: &Path) -> Result<ExportReport, String> {
+    let data = fs::read_to_string(config_path)
+        .map_err(|e| format!("Failed to read {}: {}", config_path.display(), e))?;
+    let mut conf: KeyDeckConf = keydeck_types::parse_config(config_path, &data)
+        .map_err(|e| format!("Failed to parse {}: {}", config_path.display(), e))?;
+    let icon_paths = IconPaths::with_default_dir(
+        config_path,
+        conf.icon_dirs.as_deref(),
+        icon_dir.to_path_buf(),
+    );
+
+    let mut report = ExportReport::default();
+    // Archive name of every icon, by its name in the configuration
+    let mut stored: BTreeMap<String, String> = BTreeMap::new();
+    let mut files: Vec<(String, PathBuf)> = vec![];
+    for icon in conf.icons() {
+        if icon.contains("${") {
+            let mut found = false;
+            for dir in icon_paths.dirs() {
+                for name in dynamic_icon_files(dir, &icon) {
+                    found = true;
+                    if !files.iter().any(|(stored, _)| *stored == name) {
+                        files.push((name.clone(), dir.join(&name)));
+                    }
+                }
+            }
+            if !found {
+                report.missing.push(icon);
+            }
+            continue;
+        }
+        let Some(path) = icon_paths.resolve(&icon) else {
+            report.missing.push(icon);
+            continue;
+        };
+        let name = if IconPaths::is_name(&icon) {
+            icon.replace('\\', "/")
+        } else {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            free_name(&file_name, |name| {
+                files.iter().any(|(stored, _)| stored == name)
+            })
+        };
+        files.push((name.clone(), path));
+        stored.insert(icon, name);
+    }
+
+    // A YAML configuration is kept as written, unless icons moved
+    let moved = stored.iter().any(|(icon, name)| icon != name);
+    let yaml = if moved || ConfigFormat::of(config_path) != ConfigFormat::Yaml {
+        for icon in conf.icons_mut() {
+            if let Some(name) = stored.get(icon.as_str()) {
+                *icon = name.clone();
+            }
+        }
+        serde_yaml_ng::to_string(&conf).map_err(|e| format!("Failed to serialize config: {}", e))?
+    } else {
+        data
+    };
+
+    let file = fs::File::create(archive)
+        .map_err(|e| format!("Failed to create {}: {}", archive.display(), e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let zip_error = |e: zip::result::ZipError| format!("Failed to write archive: {}", e);
+    zip.start_file(CONFIG_ENTRY, options).map_err(zip_error)?;
+    zip.write_all(yaml.as_bytes())
+        .map_err(|e| format!("Failed to write archive: {}", e))?;
+    for (name, path) in &files {
+        let data =
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        zip.start_file(format!("{}{}", ICONS_PREFIX, name), options)
+            .map_err(zip_error)?;
+        zip.write_all(&data)
+            .map_err(|e| format!("Failed to write archive: {}", e))?;
+    }
+    zip.finish().map_err(zip_error)?;
+    report.icons = files.len();
+    Ok(report)
+}
+
+/// Restores the archive `archive`: its icons to `icon_dir`, its configuration to
+/// `config_path`
+pub fn import(
+    archive: &Path,
+    config_path: &Path,
+    icon_dir: &Path,
+    conflict: Conflict,
+) -> Result<ImportReport, String> {
+    keydeck_types::require_yaml(config_path, "Importing an archive")?;
+    let file = fs::File::open(archive)
+        .map_err(|e| format!("Failed to open {}: {}", archive.display(), e))?;
+    let mut zip = ZipArchive::new(file)
+        .map_err(|e| format!("{} is not a KeyDeck archive: {}", archive.display(), e))?;
+
+    let yaml = read_entry(&mut zip, CONFIG_ENTRY)
+        .map_err(|_| format!("{} holds no {}", archive.display(), CONFIG_ENTRY))?;
+    let yaml = String::from_utf8(yaml).map_err(|e| format!("Invalid {}: {}", CONFIG_ENTRY, e))?;
+    let mut conf: KeyDeckConf =
+        serde_yaml_ng::from_str(&yaml).map_err(|e| format!("Invalid {}: {}", CONFIG_ENTRY, e))?;
+
+    let mut report = ImportReport::default();
+    let names: Vec<String> = zip
+        .file_names()
+        .filter_map(|name| name.strip_prefix(ICONS_PREFIX))
+        .filter(|name| !name.is_empty() && !name.ends_with('/'))
+        .map(str::to_string)
+        .collect();
+    for name in names {
+        if !is_plain_relative(&name) {
+            return Err(format!("Invalid icon name in archive: {}", name));
+        }
+        let data = read_entry(&mut zip, &format!("{}{}", ICONS_PREFIX, name))?;
+        let mut target = name.clone();
+        let existing = fs::read(icon_dir.join(&target)).ok();
+        if existing.as_ref().is_some_and(|existing| *existing != data) {
+            match conflict {
+                Conflict::Skip => {
+                    report.skipped.push(name);
+                    continue;
+                }
+                Conflict::Overwrite => {}
+                Conflict::Rename => {
+                    target = free_name(&name, |candidate| {
+                        fs::read(icon_dir.join(candidate)).is_ok_and(|existing| existing != data)
+                    });
+                    report.renamed.insert(name.clone(), target.clone());
+                }
+            }
+        }
+        let path = icon_dir.join(&target);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(&path, &data)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        report.icons += 1;
+    }
+
+    let yaml = if report.renamed.is_empty() {
+        yaml
+    } else {
+        for icon in conf.icons_mut() {
+            if let Some(target) = report.renamed.get(icon.as_str()) {
+                *icon = target.clone();
+            }
+        }
+        serde_yaml_ng::to_string(&conf).map_err(|e| format!("Failed to serialize config: {}", e))?
+    };
+
+    report.backup = keydeck_types::save_config_file(config_path, &yaml)?
+        .map(|backup| backup.to_string_lossy().to_string());
+    Ok(report)
+}
+
+/// Files of the icon dir that an icon name with `${...}` placeholders can name
+fn dynamic_icon_files(icon_dir: &Path, icon: &str) -> Vec<String> {
+    let (dir, pattern) = icon.rsplit_once('/').unwrap_or(("", icon));
+    if dir.contains("${") {
+        return vec![];
+    }
+    // The literal parts around the placeholders
+    let mut parts = vec![];
+    let mut rest = pattern;
+    while let Some(start) = rest.find("${") {
+        parts.push(&rest[..start]);
+        rest = rest[start..]
+            .find('}')
+            .map_or("", |end| &rest[start + end + 1..]);
+    }
+    parts.push(rest);
+
+    let matches = |name: &str| {
+        let (first, last) = (parts[0], parts[parts.len() - 1]);
+        if name.len() < first.len() + last.len()
+            || !name.starts_with(first)
+            || !name.ends_with(last)
+        {
+            return false;
+        }
+        let mut middle = &name[first.len()..name.len() - last.len()];
+        parts[1..parts.len() - 1].iter().all(|part| {
+            middle
+                .find(part)
+                .map(|at| middle = &middle[at + part.len()..])
+                .is_some()
+        })
+    };
+    let Ok(entries) = fs::read_dir(icon_dir.join(dir)) else {
+        return vec![];
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| matches(name))
+        .map(|name| match dir {
+            "" => name,
+            dir => format!("{}/{}", dir, name),
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+fn read_entry(zip: &mut ZipArchive<fs::File>, name: &str) -> Result<Vec<u8>, String> {
+    let mut entry = zip
+        .by_name(name)
+        .map_err(|e| format!("Failed to read {} from archive: {}", name, e))?;
+    let mut data = Vec::new();
+    entry
+        .read_to_end(&mut data)
+        .map_err(|e| format!("Failed to read {} from archive: {}", name, e))?;
+    Ok(data)
+}
+
+/// A name inside the icon dir, that cannot point out of it
+fn is_plain_relative(name: &str) -> bool {
+    Path::new(name)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// `name`, or `stem-2.ext`, `stem-3.ext`... when `taken(name)`
+pub fn free_name(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(name) {
+        return name.to_string();
+    }
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    (2..)
+        .map(|n| format!("{}-{}{}", stem, n, extension))
+        .find(|candidate| !taken(candidate))
+        .unwrap()
+}
+
+/// `keydeck --export <FILE>`
+pub fn print_export(archive: &str, json: bool) {
+    let result = export(
+        &keydeck::get_config_path(),
+        &keydeck::get_icon_dir_path(),
+        Path::new(archive),
+    );
+    match result {
+        Ok(report) if json => println!("{}", serde_json::to_string(&report).unwrap_or_default()),
+        Ok(report) => {
+            println!(
+                "Exported the configuration and {} icons to {}",
+                report.icons, archive
+            );
+            for icon in report.missing {
+                println!("  missing icon: {}", icon);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `keydeck --import <FILE>`
+pub fn print_import(archive: &str, conflict: Conflict, json: bool) {
+    let result = import(
+        Path::new(archive),
+        &keydeck::get_config_path(),
+        &keydeck::get_icon_dir_path(),
+        conflict,
+    );
+    match result {
+        Ok(report) if json => println!("{}", serde_json::to_string(&report).unwrap_or_default()),
+        Ok(report) => {
+            println!(
+                "Imported the configuration and {} icons from {}",
+                report.icons, archive
+            );
+            for (icon, target) in &report.renamed {
+                println!("  renamed icon: {} -> {}", icon, target);
+            }
+            for icon in &report.skipped {
+                println!("  kept existing icon: {}", icon);
+            }
+            if let Some(backup) = &report.backup {
+                println!("The previous configuration was saved as {}", backup);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_renames_conflicting_icons() {
+        let dir = std::env::temp_dir().join(format!("keydeck-bundle-{}", std::process::id()));
+        let (home, icons, archive) = (dir.join("home"), dir.join("icons"), dir.join("a.keydeck"));
+        fs::create_dir_all(&icons).unwrap();
+        fs::create_dir_all(&home).unwrap();
+        let config = home.join("config.yaml");
+        fs::write(&config, "default:\n  Main:\n    button1:\n      icon: a.png\n    button2:\n      icon: gone.png\n    button3:\n      icon: cpu_${service:cpu}.png\n").unwrap();
+        for (name, content) in [
+            ("a.png", "exported"),
+            ("cpu_0.png", "0"),
+            ("cpu_50.png", "50"),
+        ] {
+            fs::write(icons.join(name), content).unwrap();
+        }
+
+        let exported = export(&config, &icons, &archive).unwrap();
+        assert_eq!(exported.icons, 3);
+        assert_eq!(exported.missing, ["gone.png"]);
+
+        fs::write(icons.join("a.png"), "local").unwrap();
+        let imported = import(&archive, &config, &icons, Conflict::Rename).unwrap();
+        assert_eq!(imported.renamed["a.png"], "a-2.png");
+        assert_eq!(
+            fs::read_to_string(icons.join("a-2.png")).unwrap(),
+            "exported"
+        );
+        assert!(fs::read_to_string(&config)
+            .unwrap()
+            .contains("icon: a-2.png"));
+        assert!(imported.backup.is_some());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! What a key shows that follows from the configuration alone: its colors, resolved
+//! through the theme and the named colors, its icon layers and its label. The daemon
+//! draws its keys from these, adding what only it knows (values of services and
+//! dynamic parameters, animations, timers); the C interface draws previews from them.
+
+use crate::icon_cache;
+use crate::pages::{TextConfig, Theme};
+use crate::utils::process_escape_sequences;
+#[allow(unused_imports)]
+use crate::warn_log;
+use image::ImageResult;
+use indexmap::IndexMap;
+use keydeck_render::graphics;
+use keydeck_render::shortcodes::expand_shortcodes;
+use keydeck_types::pages::{Icon, IconLayer};
+use keydeck_types::IconPaths;
+
+/// Resolves a color, letting the named colors of `theme` shadow the global ones. Theme
+/// color values may themselves name a global color.
+pub fn resolve_color(
+    color: &str,
+    theme: Option<&Theme>,
+    named_colors: &Option<IndexMap<String, String>>,
+) -> Result<(u8, u8, u8), String> {
+    let themed = theme
+        .and_then(|theme| theme.colors.as_ref())
+        .and_then(|colors| colors.get(color));
+    string_to_color(themed.map_or(color, String::as_str), named_colors)
+}
+
+/// Resolves a configured color: a name from the `colors` map (resolved recursively), or any
+/// format understood by [`graphics::parse_color`].
+fn string_to_color(
+    color: &str,
+    named_colors: &Option<IndexMap<String, String>>,
+) -> Result<(u8, u8, u8), String> {
+    let named = named_colors.as_ref().and_then(|colors| colors.get(color));
+    if let Some(idx_color) = named.filter(|named| *named != color) {
+        return string_to_color(idx_color, named_colors);
+    }
+    graphics::parse_color(color)
+}
+
+/// One image of the icon of a key, found in the icon dirs
+pub struct IconImage {
+    pub path: String,
+    /// Top-left corner on the key, centered when None
+    pub position: Option<[i32; 2]>,
+    /// Size relative to the key
+    pub scale: f32,
+}
+
+impl IconImage {
+    /// The image scaled for a key of `width`x`height`, once per size through the icon
+    /// atlas
+    pub fn layer(&self, width: u32, height: u32) -> ImageResult<keydeck_render::IconLayer> {
+        let layer_width = ((width as f32 * self.scale) as u32).max(1);
+        let layer_height = ((height as f32 * self.scale) as u32).max(1);
+        Ok(keydeck_render::IconLayer {
+            image: icon_cache::atlas_icon(&self.path, layer_width, layer_height)?,
+            position: self.position.map(|[x, y]| (x as i64, y as i64)),
+        })
+    }
+}
+
+/// The images of `icon` found in the icon dirs, bottom layer first. `substitute` fills
+/// in the dynamic parameters of their names.
+pub fn icon_layers(
+    icon: Option<&Icon>,
+    icon_paths: &IconPaths,
+    substitute: impl Fn(&str) -> String,
+) -> Vec<IconImage> {
+    let layers: Vec<(&str, Option<[i32; 2]>, f32)> = match icon {
+        Some(Icon::File(image)) => vec![(image, None, 1.0)],
+        Some(Icon::Layers(layers)) => layers
+            .iter()
+            .map(|layer| match layer {
+                IconLayer::File(image) => (image.as_str(), None, 1.0),
+                IconLayer::Detailed {
+                    image,
+                    position,
+                    scale,
+                } => (image.as_str(), *position, scale.unwrap_or(1.0).max(0.0)),
+            })
+            .collect(),
+        None => vec![],
+    };
+    layers
+        .into_iter()
+        .map(|(image, position, scale)| {
+            // Names such as `badge_${service:mic}.png` follow the state they show
+            let image = match image.contains("${") {
+                true => substitute(image),
+                false => image.to_string(),
+            };
+            (image, position, scale)
+        })
+        .filter(|(image, _, _)| !image.is_empty())
+        .filter_map(
+            |(image, position, scale)| match icon_paths.resolve(&image) {
+                Some(path) => Some(IconImage {
+                    path: path.to_string_lossy().into_owned(),
+                    position,
+                    scale,
+                }),
+                None => {
+                    warn_log!("Image not found: {}", image);
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
+/// The label of a key: shortcodes such as :rocket: become emoji, `substitute` fills in
+/// the dynamic parameters (shortcodes in their values are left as they are), then the
+/// escape sequences are processed
+pub fn label_text(text: &TextConfig, substitute: impl Fn(&str) -> String) -> String {
+    let text = match text {
+        TextConfig::Simple(text) => text,
+        TextConfig::Detailed { value, .. } => value,
+    };
+    if text.is_empty() {
+        return String::new();
+    }
+    let text = substitute(&expand_shortcodes(text));
+    process_escape_sequences(&text).into_iter().collect()
+}
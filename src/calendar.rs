@@ -0,0 +1,603 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Events of the configured `calendars`, for `${calendar:next_title}`,
+//! `${calendar:next_time}` and `${calendar:in_meeting}`, and the `join_meeting` action
+//! that opens the conferencing link of the next meeting.
+//!
+//! Calendars are iCalendar feeds, `.ics` files or CalDAV collections, read again every
+//! few minutes by a thread of their own. Feeds are expanded here for the common
+//! recurrence rules; CalDAV servers expand recurring events themselves. Times with a
+//! time zone other than UTC are taken as local time. Once a minute the thread checks
+//! whether the next meeting changed, started or ended, and redraws the keys showing it.
+
+use crate::event::{send, DeviceEvent};
+use crate::pages::Calendar;
+use crate::{error_log, info_log, verbose_log};
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday,
+};
+use regex::Regex;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration as StdDuration, Instant};
+
+/// Time between two reads of the calendars
+const FETCH_INTERVAL: StdDuration = StdDuration::from_secs(5 * 60);
+
+/// Time between two checks of the next meeting
+const CHECK_INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+/// Days of events kept ahead of now
+const DAYS_AHEAD: i64 = 14;
+
+/// Known conferencing services, looked for in the location, address and description
+const MEETING_LINK: &str = r#"https://[^\s<>"'\\]*(zoom\.us/(j|my|w)/|meet\.google\.com/|teams\.microsoft\.com/l/meetup-join/|teams\.live\.com/meet/|webex\.com/|whereby\.com/|meet\.jit\.si/|gotomeet|bluejeans\.com/|chime\.aws/)[^\s<>"'\\]*"#;
+
+/// An occurrence of a calendar event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub title: String,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub all_day: bool,
+    /// Conferencing link of the event, if any
+    pub link: Option<String>,
+}
+
+/// Events of all calendars, sorted by start; None while no calendar is configured
+static EVENTS: RwLock<Option<Vec<Event>>> = RwLock::new(None);
+
+/// Starts following the configured calendars
+pub fn start(calendars: &[Calendar], tx: &Sender<DeviceEvent>, still_active: &Arc<AtomicBool>) {
+    *EVENTS.write().unwrap() = Some(vec![]);
+    let (calendars, tx, still_active) = (calendars.to_vec(), tx.clone(), still_active.clone());
+    thread::spawn(move || follow(&calendars, &tx, &still_active));
+}
+
+fn follow(calendars: &[Calendar], tx: &Sender<DeviceEvent>, still_active: &AtomicBool) {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(StdDuration::from_secs(30)))
+        .user_agent(concat!("keydeck/", env!("CARGO_PKG_VERSION")))
+        .allow_non_standard_methods(true)
+        .build()
+        .into();
+    info_log!("Following {} calendar(s)", calendars.len());
+    // Events of each calendar, kept when a later read fails
+    let mut fetched: Vec<Vec<Event>> = vec![vec![]; calendars.len()];
+    let mut last_fetch: Option<Instant> = None;
+    let mut shown = String::new();
+    while still_active.load(Ordering::Relaxed) {
+        if last_fetch.is_none_or(|fetch| fetch.elapsed() >= FETCH_INTERVAL) {
+            last_fetch = Some(Instant::now());
+            for (calendar, events) in calendars.iter().zip(fetched.iter_mut()) {
+                match fetch(&agent, calendar) {
+                    Ok(text) => *events = parse_ics(&text, Local::now()),
+                    Err(e) => error_log!("Calendar {} could not be read: {}", calendar.url, e),
+                }
+            }
+            let mut all: Vec<Event> = fetched.iter().flatten().cloned().collect();
+            all.sort_by_key(|event| event.start);
+            verbose_log!("Read {} upcoming calendar event(s)", all.len());
+            *EVENTS.write().unwrap() = Some(all);
+        }
+        let now = ["next_title", "next_time", "in_meeting"]
+            .map(|arg| value(arg).unwrap_or_default())
+            .join("\n");
+        if now != shown {
+            shown = now;
+            send(
+                tx,
+                DeviceEvent::ProviderUpdated {
+                    provider: "calendar".to_string(),
+                },
+            );
+        }
+        thread::sleep(CHECK_INTERVAL);
+    }
+}
+
+/// Text of a calendar: the response of its address, or the content of its file
+fn fetch(agent: &ureq::Agent, calendar: &Calendar) -> Result<String, String> {
+    let url = match calendar.url.trim() {
+        url if url.starts_with("webcal://") => url.replacen("webcal://", "https://", 1),
+        url if url.starts_with("http://") || url.starts_with("https://") => url.to_string(),
+        path => return fs::read_to_string(path).map_err(|e| e.to_string()),
+    };
+    let auth = calendar.username.as_ref().map(|user| {
+        let password = calendar.password.as_deref().unwrap_or_default();
+        format!(
+            "Basic {}",
+            base64(format!("{}:{}", user, password).as_bytes())
+        )
+    });
+    let response = if calendar.caldav {
+        let now = Utc::now();
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><c:calendar-data><c:expand start="{start}" end="{end}"/></c:calendar-data></d:prop>
+  <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT">
+    <c:time-range start="{start}" end="{end}"/>
+  </c:comp-filter></c:comp-filter></c:filter>
+</c:calendar-query>"#,
+            start = (now - Duration::days(1)).format("%Y%m%dT%H%M%SZ"),
+            end = (now + Duration::days(DAYS_AHEAD)).format("%Y%m%dT%H%M%SZ"),
+        );
+        let mut request = ureq::http::Request::builder()
+            .method("REPORT")
+            .uri(url.as_str())
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8");
+        if let Some(auth) = &auth {
+            request = request.header("Authorization", auth.as_str());
+        }
+        let request = request.body(body).map_err(|e| e.to_string())?;
+        agent.run(request)
+    } else {
+        let mut request = agent.get(&url);
+        if let Some(auth) = &auth {
+            request = request.header("Authorization", auth.as_str());
+        }
+        request.call()
+    };
+    let text = response
+        .map_err(|e| e.to_string())?
+        .body_mut()
+        .with_config()
+        .limit(16 * 1024 * 1024)
+        .read_to_string()
+        .map_err(|e| e.to_string())?;
+    Ok(match calendar.caldav {
+        true => calendar_data(&text),
+        false => text,
+    })
+}
+
+/// The calendars inside a CalDAV multistatus response, one after the other
+fn calendar_data(xml: &str) -> String {
+    let mut calendars = String::new();
+    let mut rest = xml;
+    while let Some(begin) = rest.find("BEGIN:VCALENDAR") {
+        let end = rest[begin..]
+            .find("END:VCALENDAR")
+            .map_or(rest.len(), |end| begin + end + "END:VCALENDAR".len());
+        let data = rest[begin..end]
+            .replace("&#13;", "")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&");
+        calendars.push_str(&data);
+        calendars.push('\n');
+        rest = &rest[end..];
+    }
+    calendars
+}
+
+/// Value of `${calendar:ARG}`, None when no calendar is configured or ARG is unknown
+pub fn value(arg: &str) -> Option<String> {
+    let events = EVENTS.read().unwrap();
+    let events = events.as_ref()?;
+    let now = Local::now();
+    let next = next_meeting(events, now, None);
+    match arg {
+        "next_title" => Some(next.map(|event| event.title.clone()).unwrap_or_default()),
+        "next_time" => Some(next.map_or(String::new(), |event| {
+            match event.start.date_naive() == now.date_naive() {
+                true => event.start.format("%H:%M").to_string(),
+                false => event.start.format("%a %H:%M").to_string(),
+            }
+        })),
+        "in_meeting" => {
+            let busy = next.is_some_and(|event| event.start <= now && now < event.end);
+            Some(if busy { "yes" } else { "no" }.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// The meeting in progress or next to start, optionally the next one whose title
+/// contains `title`. All-day events are not meetings.
+fn next_meeting<'a>(
+    events: &'a [Event],
+    now: DateTime<Local>,
+    title: Option<&str>,
+) -> Option<&'a Event> {
+    let title = title.map(str::to_lowercase);
+    events.iter().find(|event| {
+        !event.all_day
+            && (event.end > now || event.start >= now)
+            && title
+                .as_ref()
+                .is_none_or(|title| event.title.to_lowercase().contains(title))
+    })
+}
+
+/// Conferencing link of the meeting chosen by a `join_meeting` target: `next`, or text
+/// in the title of the meeting
+pub fn meeting_link(target: &str) -> Result<String, String> {
+    let events = EVENTS.read().unwrap();
+    let events = events
+        .as_ref()
+        .ok_or_else(|| "No calendars configured".to_string())?;
+    let title = match target.trim() {
+        "" | "next" => None,
+        title => Some(title),
+    };
+    let meeting = next_meeting(events, Local::now(), title).ok_or_else(|| match title {
+        Some(title) => format!("No upcoming meeting matching '{}'", title),
+        None => "No upcoming meeting".to_string(),
+    })?;
+    meeting
+        .link
+        .clone()
+        .ok_or_else(|| format!("Meeting '{}' has no conferencing link", meeting.title))
+}
+
+/// A content line: name, parameters and value
+struct Property<'a> {
+    name: String,
+    params: Vec<(String, String)>,
+    value: &'a str,
+}
+
+impl Property<'_> {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn property(line: &str) -> Option<Property<'_>> {
+    let mut quoted = false;
+    let (colon, _) = line.char_indices().find(|&(_, c)| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        c == ':' && !quoted
+    })?;
+    let mut head = line[..colon].split(';');
+    let name = head.next()?.to_ascii_uppercase();
+    let params = head
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| {
+            (
+                key.to_ascii_uppercase(),
+                value.trim_matches('"').to_string(),
+            )
+        })
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value: &line[colon + 1..],
+    })
+}
+
+/// Lines of an iCalendar text, with folded lines joined
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => result.push('\n'),
+                Some(other) => result.push(other),
+                None => {}
+            },
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// A time of the calendar and whether it is a whole day
+fn parse_time(value: &str, property: Option<&Property>) -> Option<(DateTime<Local>, bool)> {
+    let value = value.trim();
+    let is_date = property.and_then(|p| p.param("VALUE")) == Some("DATE") || value.len() == 8;
+    if is_date {
+        let date = NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()?;
+        let midnight = Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?);
+        return Some((midnight.earliest()?, true));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((Utc.from_utc_datetime(&time).with_timezone(&Local), false));
+    }
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some((Local.from_local_datetime(&time).earliest()?, false))
+}
+
+/// An iCalendar duration, e.g. `PT1H30M`
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim().trim_start_matches('+').strip_prefix('P')?;
+    let (mut seconds, mut number) = (0, String::new());
+    for c in value.chars() {
+        let unit = match c {
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            }
+            'T' => continue,
+            'W' => 7 * 86400,
+            'D' => 86400,
+            'H' => 3600,
+            'M' => 60,
+            'S' => 1,
+            _ => return None,
+        };
+        seconds += number.parse::<i64>().ok()? * unit;
+        number.clear();
+    }
+    Some(Duration::seconds(seconds))
+}
+
+/// An event as written in the calendar, before its recurrences are expanded
+#[derive(Default)]
+struct RawEvent {
+    uid: String,
+    title: String,
+    start: Option<(DateTime<Local>, bool)>,
+    end: Option<DateTime<Local>>,
+    duration: Option<Duration>,
+    rule: Option<String>,
+    exceptions: Vec<DateTime<Local>>,
+    recurrence_id: Option<DateTime<Local>>,
+    cancelled: bool,
+    /// Places a conferencing link may be in, the likeliest first
+    link_sources: Vec<String>,
+}
+
+/// Occurrences of the events of an iCalendar text that have not ended a day before
+/// `now`, up to [`DAYS_AHEAD`] days after it, sorted by start
+fn parse_ics(text: &str, now: DateTime<Local>) -> Vec<Event> {
+    let mut raw_events = vec![];
+    let mut current: Option<RawEvent> = None;
+    // Depth of components inside the event, e.g. alarms, whose properties are skipped
+    let mut nested = 0;
+    for line in unfold(text) {
+        let Some(prop) = property(&line) else {
+            continue;
+        };
+        let value = prop.value;
+        match (prop.name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                current = Some(RawEvent::default())
+            }
+            ("BEGIN", Some(_)) => nested += 1,
+            ("END", Some(_)) if nested > 0 => nested -= 1,
+            ("END", Some(_)) => raw_events.extend(current.take()),
+            (_, Some(_)) if nested > 0 => {}
+            ("UID", Some(event)) => event.uid = value.to_string(),
+            ("SUMMARY", Some(event)) => event.title = unescape(value),
+            ("DTSTART", Some(event)) => event.start = parse_time(value, Some(&prop)),
+            ("DTEND", Some(event)) => event.end = parse_time(value, Some(&prop)).map(|t| t.0),
+            ("DURATION", Some(event)) => event.duration = parse_duration(value),
+            ("RRULE", Some(event)) => event.rule = Some(value.to_ascii_uppercase()),
+            ("EXDATE", Some(event)) => event.exceptions.extend(
+                value
+                    .split(',')
+                    .filter_map(|time| parse_time(time, Some(&prop)).map(|t| t.0)),
+            ),
+            ("RECURRENCE-ID", Some(event)) => {
+                event.recurrence_id = parse_time(value, Some(&prop)).map(|t| t.0)
+            }
+            ("STATUS", Some(event)) => event.cancelled = value.eq_ignore_ascii_case("CANCELLED"),
+            ("X-GOOGLE-CONFERENCE" | "CONFERENCE", Some(event)) => {
+                event.link_sources.insert(0, value.to_string())
+            }
+            ("LOCATION" | "URL" | "DESCRIPTION", Some(event)) => {
+                event.link_sources.push(unescape(value))
+            }
+            _ => {}
+        }
+    }
+
+    // Occurrences moved or cancelled by an event of their own are not generated
+    let moved: Vec<(String, DateTime<Local>)> = raw_events
+        .iter()
+        .filter_map(|event| Some((event.uid.clone(), event.recurrence_id?)))
+        .collect();
+    let (from, to) = (now - Duration::days(1), now + Duration::days(DAYS_AHEAD));
+    let link_pattern = Regex::new(MEETING_LINK).unwrap();
+    let mut events = vec![];
+    for raw in raw_events.iter().filter(|event| !event.cancelled) {
+        let Some((start, all_day)) = raw.start else {
+            continue;
+        };
+        let length = match (raw.end, raw.duration) {
+            (Some(end), _) => end - start,
+            (None, Some(duration)) => duration,
+            (None, None) if all_day => Duration::days(1),
+            (None, None) => Duration::zero(),
+        };
+        let starts = match (&raw.rule, raw.recurrence_id) {
+            (Some(rule), None) => occurrences(start, rule, to),
+            _ => vec![start],
+        };
+        let link = meeting_link_in(&raw.link_sources, &link_pattern);
+        for start in starts {
+            let skipped = raw.exceptions.contains(&start)
+                || (raw.recurrence_id.is_none()
+                    && raw.rule.is_some()
+                    && moved
+                        .iter()
+                        .any(|(uid, time)| *uid == raw.uid && *time == start));
+            if skipped || start + length < from || start > to {
+                continue;
+            }
+            events.push(Event {
+                title: raw.title.clone(),
+                start,
+                end: start + length,
+                all_day,
+                link: link.clone(),
+            });
+        }
+    }
+    events.sort_by_key(|event| event.start);
+    events
+}
+
+/// Starts of the occurrences of a recurring event up to `to`, for DAILY, WEEKLY,
+/// MONTHLY and YEARLY rules with INTERVAL, COUNT, UNTIL and plain BYDAY days. Rules
+/// beyond that only yield the first occurrence.
+fn occurrences(start: DateTime<Local>, rule: &str, to: DateTime<Local>) -> Vec<DateTime<Local>> {
+    let parts: Vec<(&str, &str)> = rule.split(';').filter_map(|p| p.split_once('=')).collect();
+    let part = |name: &str| parts.iter().find(|(key, _)| *key == name).map(|(_, v)| *v);
+    let interval = part("INTERVAL")
+        .and_then(|i| i.parse::<i64>().ok())
+        .unwrap_or(1)
+        .max(1);
+    let count = part("COUNT").and_then(|c| c.parse::<usize>().ok());
+    let until = part("UNTIL").and_then(|u| parse_time(u, None)).map(|t| t.0);
+    let freq = part("FREQ").unwrap_or_default();
+    let mut by_day = vec![];
+    for day in part("BYDAY").into_iter().flat_map(|days| days.split(',')) {
+        match weekday(day) {
+            Some(day) => by_day.push(day),
+            None => return vec![start],
+        }
+    }
+    let unsupported = (!by_day.is_empty() && !matches!(freq, "DAILY" | "WEEKLY"))
+        || ["BYMONTH", "BYMONTHDAY", "BYSETPOS"]
+            .iter()
+            .any(|name| part(name).is_some());
+    if unsupported {
+        return vec![start];
+    }
+
+    let first = start.date_naive();
+    let time = start.naive_local().time();
+    let week =
+        |date: NaiveDate| date - Duration::days(date.weekday().num_days_from_monday() as i64);
+    let months = |date: NaiveDate| {
+        (date.year() - first.year()) as i64 * 12 + date.month() as i64 - first.month() as i64
+    };
+    let mut starts = vec![];
+    let mut counted = 0;
+    for date in first.iter_days() {
+        if date > to.date_naive() {
+            break;
+        }
+        let on_day = match by_day.is_empty() {
+            true => freq != "WEEKLY" || date.weekday() == first.weekday(),
+            false => by_day.contains(&date.weekday()),
+        };
+        let matches = on_day
+            && match freq {
+                "DAILY" => (date - first).num_days() % interval == 0,
+                "WEEKLY" => ((week(date) - week(first)).num_days() / 7) % interval == 0,
+                "MONTHLY" => date.day() == first.day() && months(date) % interval == 0,
+                "YEARLY" => date.day() == first.day() && months(date) % (12 * interval) == 0,
+                _ => date == first,
+            };
+        if !matches {
+            continue;
+        }
+        let Some(occurrence) = Local.from_local_datetime(&date.and_time(time)).earliest() else {
+            continue;
+        };
+        counted += 1;
+        if until.is_some_and(|until| occurrence > until) || count.is_some_and(|c| counted > c) {
+            break;
+        }
+        starts.push(occurrence);
+    }
+    starts
+}
+
+fn weekday(day: &str) -> Option<Weekday> {
+    Some(match day {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+/// First conferencing link in the places of an event: a known service, or else a
+/// location that is itself an address
+fn meeting_link_in(sources: &[String], pattern: &Regex) -> Option<String> {
+    sources
+        .iter()
+        .find_map(|source| pattern.find(source).map(|m| m.as_str().to_string()))
+        .or_else(|| {
+            sources
+                .iter()
+                .map(|source| source.trim())
+                .find(|source| {
+                    source.starts_with("https://") && !source.contains(char::is_whitespace)
+                })
+                .map(str::to_string)
+        })
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = ((bytes[0] as u32) << 16) | ((bytes[1] as u32) << 8) | bytes[2] as u32;
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(ALPHABET[((bits >> (18 - 6 * i)) & 63) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weekly_meetings_are_expanded_with_their_links() {
+        let now = Local.with_ymd_and_hms(2025, 3, 5, 8, 0, 0).unwrap();
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:standup\r\nSUMMARY:Stand\\, up\r\n\
+                   DTSTART:20250303T093000\r\nDURATION:PT15M\r\n\
+                   RRULE:FREQ=WEEKLY;BYDAY=MO,WE,FR\r\nEXDATE:20250307T093000\r\n\
+                   DESCRIPTION:Join: https://meet.google.com/abc-defg-hij\\n\r\n\
+                   BEGIN:VALARM\r\nDESCRIPTION:Reminder\r\nEND:VALARM\r\n\
+                   END:VEVENT\r\nEND:VCALENDAR\r\n";
+        let events = parse_ics(ics, now);
+        let days: Vec<u32> = events.iter().map(|event| event.start.day()).collect();
+        assert_eq!(&days[..4], &[5, 10, 12, 14]);
+        assert_eq!(events[0].title, "Stand, up");
+        assert_eq!(events[0].end - events[0].start, Duration::minutes(15));
+        assert_eq!(
+            events[0].link.as_deref(),
+            Some("https://meet.google.com/abc-defg-hij")
+        );
+        assert_eq!(next_meeting(&events, now, Some("STAND")), events.first());
+        assert_eq!(base64(b"user:pass"), "dXNlcjpwYXNz");
+    }
+}
@@ -0,0 +1,432 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! C interface of the library, so frontends in other languages (GTK, Qt, a TUI) can be
+//! built on KeyDeck without the Tauri stack of the configuration UI (`capi` feature).
+//!
+//! Built as a shared library with `cargo build --lib --release --features capi`, and
+//! declared for C
+//! in `include/keydeck.h`. Texts go in and out as UTF-8 C strings, structured values as
+//! JSON. A function that fails, or panics, returns null or -1, and
+//! [`keydeck_last_error`] tells why. Strings returned are freed with
+//! [`keydeck_string_free`] and configurations with [`keydeck_config_free`].
+
+use crate::button_look;
+use crate::pages::{ButtonConfig, ColorConfig, KeyDeckConf, KeyDeckConfLoader, TextConfig};
+use keydeck_render::{ButtonSpec, Label};
+use keydeck_types::IconPaths;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+
+/// Longest wait for the answer of the daemon; benches of every page take the longest
+#[cfg(unix)]
+const ANSWER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A loaded configuration, opaque to C
+pub struct KeydeckConfig {
+    conf: KeyDeckConf,
+    icons: IconPaths,
+}
+
+fn set_error(error: impl Into<String>) {
+    let error = CString::new(error.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error));
+}
+
+/// The text of `value`, or an error naming the argument `what`
+///
+/// # Safety
+/// `value` is null or a valid C string.
+unsafe fn text<'a>(value: *const c_char, what: &str) -> Result<&'a str, String> {
+    if value.is_null() {
+        return Err(format!("{} is missing", what));
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map_err(|_| format!("{} is not UTF-8", what))
+}
+
+/// Runs the body of an exported function, returning `failed` and keeping the message as
+/// the last error when it panics, as unwinding into C is undefined behavior
+fn guard<T>(failed: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown cause".to_string());
+        set_error(format!("Internal error: {}", message));
+        failed
+    })
+}
+
+/// Hands `value` over to C, null and the error on failure
+fn to_c(value: Result<String, String>) -> *mut c_char {
+    match value.and_then(|value| CString::new(value).map_err(|e| e.to_string())) {
+        Ok(value) => value.into_raw(),
+        Err(e) => {
+            set_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// The error of the last call that failed on this thread, null when none did. The text
+/// belongs to the library and is valid until the next failing call.
+#[no_mangle]
+pub extern "C" fn keydeck_last_error() -> *const c_char {
+    guard(ptr::null(), || {
+        LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+    })
+}
+
+/// Frees a string returned by the library
+///
+/// # Safety
+/// `value` is null or a string returned by the library, not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn keydeck_string_free(value: *mut c_char) {
+    guard((), || {
+        if !value.is_null() {
+            drop(CString::from_raw(value));
+        }
+    })
+}
+
+/// Loads and resolves the configuration at `path` as the daemon does, or the one of the
+/// user when `path` is null
+///
+/// # Safety
+/// `path` is null or a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn keydeck_config_load(path: *const c_char) -> *mut KeydeckConfig {
+    guard(ptr::null_mut(), || {
+        let path = if path.is_null() {
+            keydeck_types::get_config_path()
+        } else {
+            match text(path, "The path") {
+                Ok(path) => PathBuf::from(path),
+                Err(e) => {
+                    set_error(e);
+                    return ptr::null_mut();
+                }
+            }
+        };
+        match KeyDeckConfLoader::try_load_from(&path) {
+            Ok(conf) => {
+                let icons = IconPaths::new(&path, conf.icon_dirs.as_deref());
+                Box::into_raw(Box::new(KeydeckConfig { conf, icons }))
+            }
+            Err(e) => {
+                set_error(e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// The resolved configuration as JSON
+///
+/// # Safety
+/// `config` is a configuration returned by [`keydeck_config_load`], not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn keydeck_config_json(config: *const KeydeckConfig) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let Some(config) = config.as_ref() else {
+            set_error("The configuration is missing");
+            return ptr::null_mut();
+        };
+        to_c(serde_json::to_string(&config.conf).map_err(|e| e.to_string()))
+    })
+}
+
+/// Frees a configuration
+///
+/// # Safety
+/// `config` is null or a configuration returned by [`keydeck_config_load`], not freed
+/// before.
+#[no_mangle]
+pub unsafe extern "C" fn keydeck_config_free(config: *mut KeydeckConfig) {
+    guard((), || {
+        if !config.is_null() {
+            drop(Box::from_raw(config));
+        }
+    })
+}
+
+/// The key `button` of a page as a key of `size`, drawn as the daemon draws it from
+/// what does not depend on the running daemon: background, icons and text, in the
+/// theme of the page. Values of services and dynamic parameters are left as written.
+fn render(
+    config: &KeydeckConfig,
+    group: &str,
+    page: &str,
+    button: &str,
+    size: (u32, u32),
+) -> Result<image::RgbaImage, String> {
+    let conf = &config.conf;
+    let page_config = conf
+        .page_groups
+        .get(group)
+        .ok_or_else(|| format!("No page group '{}'", group))?
+        .pages
+        .get(page)
+        .ok_or_else(|| format!("No page '{}' in page group '{}'", page, group))?;
+    // Buttons are given as `button3`, or by their number
+    let key = match button.parse::<u8>() {
+        Ok(number) => format!("button{}", number),
+        Err(_) => button.to_string(),
+    };
+    let button = match page_config.buttons.get(&key) {
+        None => return Ok(keydeck_render::render_button(&ButtonSpec::default(), size)),
+        Some(ButtonConfig::Detailed(button)) => button,
+        Some(ButtonConfig::Template(name)) => conf
+            .buttons
+            .as_ref()
+            .and_then(|buttons| buttons.get(name))
+            .ok_or_else(|| format!("No button definition '{}'", name))?,
+    };
+
+    let theme = page_config
+        .theme
+        .as_ref()
+        .or(conf.theme.as_ref())
+        .and_then(|name| conf.themes.as_ref()?.get(name));
+    let color = |color: &str| button_look::resolve_color(color, theme, &conf.colors);
+    // Colors computed by the daemon, from a service or a map, are left out
+    let fixed = |config: &Option<ColorConfig>| match config {
+        Some(ColorConfig::Fixed(value)) => Some(value.clone()),
+        _ => None,
+    };
+    let background = fixed(&button.background)
+        .or_else(|| theme.and_then(|theme| theme.background.clone()))
+        .map(|background| color(&background))
+        .transpose()?;
+    let mut spec = ButtonSpec {
+        background,
+        ..Default::default()
+    };
+    let keep = |value: &str| value.to_string();
+    for layer in button_look::icon_layers(button.icon.as_ref(), &config.icons, keep) {
+        let icon = layer
+            .layer(size.0, size.1)
+            .map_err(|e| format!("Failed to read {}: {}", layer.path, e))?;
+        spec.icons.push(icon);
+    }
+    let text = button
+        .text
+        .as_ref()
+        .map(|text| button_look::label_text(text, keep))
+        .unwrap_or_default();
+    if !text.is_empty() {
+        let font_size = match &button.text {
+            Some(TextConfig::Detailed { font_size, .. }) => *font_size,
+            _ => None,
+        };
+        let text_color =
+            fixed(&button.text_color).or_else(|| theme.and_then(|theme| theme.text_color.clone()));
+        let outline = button
+            .outline
+            .clone()
+            .or_else(|| theme.and_then(|theme| theme.outline.clone()));
+        spec.label = Some(Label {
+            text,
+            font_size,
+            color: text_color.map(|c| color(&c)).transpose()?,
+            outline: outline.map(|c| color(&c)).transpose()?,
+            font: theme.and_then(|theme| theme.font.clone()),
+            ..Default::default()
+        });
+    }
+    Ok(keydeck_render::render_button(&spec, size))
+}
+
+/// Draws the button `button` (`button3`, or `3`) of the page `page` of the page group
+/// `group` into `rgba`, as `width` x `height` RGBA pixels, row by row. Values of
+/// services and dynamic parameters are left as written. Returns 0, or -1 when the
+/// button cannot be drawn or `rgba` holds less than `width * height * 4` bytes.
+///
+/// # Safety
+/// `config` is a configuration returned by [`keydeck_config_load`], the texts are valid
+/// C strings and `rgba` points to `rgba_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn keydeck_render_button(
+    config: *const KeydeckConfig,
+    group: *const c_char,
+    page: *const c_char,
+    button: *const c_char,
+    width: u32,
+    height: u32,
+    rgba: *mut u8,
+    rgba_len: usize,
+) -> c_int {
+    guard(-1, || {
+        let result = (|| {
+            let config = config
+                .as_ref()
+                .ok_or_else(|| "The configuration is missing".to_string())?;
+            let needed = width as usize * height as usize * 4;
+            if width == 0 || height == 0 || rgba.is_null() || rgba_len < needed {
+                return Err(format!(
+                    "A {}x{} key needs a buffer of {} bytes",
+                    width, height, needed
+                ));
+            }
+            let image = render(
+                config,
+                text(group, "The page group")?,
+                text(page, "The page")?,
+                text(button, "The button")?,
+                (width, height),
+            )?;
+            ptr::copy_nonoverlapping(image.as_raw().as_ptr(), rgba, needed);
+            Ok(())
+        })();
+        match result {
+            Ok(()) => 0,
+            Err(e) => {
+                set_error(e);
+                -1
+            }
+        }
+    })
+}
+
+/// Sends a control command (as `keydeck --set` and the others do, e.g. `setpage AL1
+/// Media` or its JSON form) to the running daemon, and returns its answer: a JSON line
+/// or `ok`, or an empty string for commands the daemon does not answer. `watch` is not
+/// supported, as it never ends.
+///
+/// # Safety
+/// `command` is a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn keydeck_send_command(command: *const c_char) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        to_c(text(command, "The command").and_then(send_command))
+    })
+}
+
+#[cfg(unix)]
+fn send_command(line: &str) -> Result<String, String> {
+    use keydeck_types::api::Command;
+
+    let command = Command::parse(line)?;
+    if matches!(command, Command::Watch { .. }) {
+        return Err("'watch' streams events and cannot be sent this way".to_string());
+    }
+    keydeck_types::api::send(&command, ANSWER_TIMEOUT)
+        .map(Option::unwrap_or_default)
+        .map_err(|e| format!("The daemon is not running: {}", e))
+}
+
+#[cfg(not(unix))]
+fn send_command(_line: &str) -> Result<String, String> {
+    Err("The control socket of the daemon is only available on Unix".to_string())
+}
+
+/// The devices connected to the running daemon, as a JSON array of objects with their
+/// `sn`, `model`, `page`, `brightness` and `pinned`
+#[no_mangle]
+pub extern "C" fn keydeck_devices() -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let devices = send_command("status").and_then(|status| {
+            let status: keydeck_types::api::DaemonStatus =
+                serde_json::from_str(&status).map_err(|e| e.to_string())?;
+            serde_json::to_string(&status.devices).map_err(|e| e.to_string())
+        });
+        to_c(devices)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buttons_of_a_loaded_configuration_are_drawn() {
+        let dir = std::env::temp_dir().join(format!("keydeck-capi-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        std::fs::write(
+            &path,
+            "colors:\n  accent: 0x3366FF\nthemes:\n  dark:\n    colors:\n      accent: 0x112233\n\
+             main:\n  Home:\n    button1:\n      background: accent\n  \
+             Dark:\n    theme: dark\n    button1:\n      background: accent\n",
+        )
+        .unwrap();
+        let path = CString::new(path.to_string_lossy().as_ref()).unwrap();
+
+        unsafe {
+            let config = keydeck_config_load(path.as_ptr());
+            assert!(!config.is_null());
+            let json = keydeck_config_json(config);
+            assert!(CStr::from_ptr(json).to_str().unwrap().contains("accent"));
+            keydeck_string_free(json);
+
+            let mut rgba = vec![0u8; 8 * 8 * 4];
+            let (group, page) = (c"main", c"Home");
+            let drawn = keydeck_render_button(
+                config,
+                group.as_ptr(),
+                page.as_ptr(),
+                c"1".as_ptr(),
+                8,
+                8,
+                rgba.as_mut_ptr(),
+                rgba.len(),
+            );
+            assert_eq!(drawn, 0);
+            assert_eq!(&rgba[..4], &[0x33, 0x66, 0xFF, 255]);
+
+            // Named colors of the theme of the page win, as on the device
+            let drawn = keydeck_render_button(
+                config,
+                group.as_ptr(),
+                c"Dark".as_ptr(),
+                c"button1".as_ptr(),
+                8,
+                8,
+                rgba.as_mut_ptr(),
+                rgba.len(),
+            );
+            assert_eq!(drawn, 0);
+            assert_eq!(&rgba[..4], &[0x11, 0x22, 0x33, 255]);
+
+            let missing = keydeck_render_button(
+                config,
+                group.as_ptr(),
+                c"Other".as_ptr(),
+                c"1".as_ptr(),
+                8,
+                8,
+                rgba.as_mut_ptr(),
+                rgba.len(),
+            );
+            assert_eq!(missing, -1);
+            assert_eq!(
+                CStr::from_ptr(keydeck_last_error()).to_str(),
+                Ok("No page 'Other' in page group 'main'")
+            );
+            keydeck_config_free(config);
+
+            assert!(keydeck_send_command(c"no-such-command".as_ptr()).is_null());
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn panics_do_not_reach_c() {
+        assert_eq!(guard(-1, || panic!("no keys")), -1);
+        assert_eq!(
+            unsafe { CStr::from_ptr(keydeck_last_error()) }.to_str(),
+            Ok("Internal error: no keys")
+        );
+    }
+}
@@ -0,0 +1,355 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Unread message counts of chat services (`chats` in the configuration), shown on keys
+//! with `${telegram:unread}` and `${matrix:unread}`.
+//!
+//! Each service is followed by a thread of its own with long polling, so counts change
+//! as soon as a message arrives: Telegram through the `getUpdates` of a bot, counting
+//! the messages it receives until they are marked read with `chat_read`, and Matrix
+//! through `/sync`, taking the unread notification counts of the homeserver, which
+//! also drop when the room is read in another client. When a count changes, the keys
+//! showing it are redrawn.
+
+use crate::event::{send, DeviceEvent};
+use crate::pages::{Chats, MatrixChat, TelegramChat};
+use crate::{error_log, info_log, verbose_log};
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+/// Seconds a long poll waits for new messages before it is repeated
+const POLL_SECONDS: u64 = 30;
+
+/// Pause after a failed poll, so an unreachable service is not hammered
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Only the rooms, their last event and their names are needed from a Matrix sync
+const MATRIX_FILTER: &str = r#"{"presence":{"not_types":["*"]},"account_data":{"not_types":["*"]},"room":{"timeline":{"limit":1},"state":{"types":["m.room.name","m.room.canonical_alias"]},"ephemeral":{"not_types":["*"]},"account_data":{"not_types":["*"]}}}"#;
+
+/// A chat of a service, with the messages not read yet
+#[derive(Debug, Clone, Default)]
+struct Room {
+    id: String,
+    /// Names the chat answers to besides its id: title, `@username` or room alias
+    names: Vec<String>,
+    unread: u64,
+    /// Latest event of a Matrix room, the one a read receipt points at
+    last_event: Option<String>,
+}
+
+impl Room {
+    fn is(&self, name: &str) -> bool {
+        self.id == name || self.names.iter().any(|n| n.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Chats of a service, None while the service is not followed
+type Rooms = RwLock<Option<Vec<Room>>>;
+
+static TELEGRAM: Rooms = RwLock::new(None);
+static MATRIX: Rooms = RwLock::new(None);
+
+/// The configured Matrix account, for read receipts
+static MATRIX_ACCOUNT: RwLock<Option<MatrixChat>> = RwLock::new(None);
+
+fn rooms_of(service: &str) -> Option<&'static Rooms> {
+    match service {
+        "telegram" => Some(&TELEGRAM),
+        "matrix" => Some(&MATRIX),
+        _ => None,
+    }
+}
+
+fn agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(POLL_SECONDS + 15)))
+        .user_agent(concat!("keydeck/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .into()
+}
+
+/// Starts following the configured chat services
+pub fn start(conf: &Chats, tx: &Sender<DeviceEvent>, still_active: &Arc<AtomicBool>) {
+    if let Some(telegram) = &conf.telegram {
+        *TELEGRAM.write().unwrap() = Some(vec![]);
+        let (telegram, tx, still_active) = (telegram.clone(), tx.clone(), still_active.clone());
+        thread::spawn(move || follow_telegram(&telegram, &tx, &still_active));
+    }
+    if let Some(matrix) = &conf.matrix {
+        *MATRIX.write().unwrap() = Some(vec![]);
+        *MATRIX_ACCOUNT.write().unwrap() = Some(matrix.clone());
+        let (matrix, tx, still_active) = (matrix.clone(), tx.clone(), still_active.clone());
+        thread::spawn(move || follow_matrix(&matrix, &tx, &still_active));
+    }
+}
+
+/// Unread messages of a service, or of one of its chats by id or name; None when the
+/// service is not followed. Chats without messages so far have none unread.
+pub fn unread(service: &str, chat: Option<&str>) -> Option<u64> {
+    let rooms = rooms_of(service)?.read().unwrap();
+    let rooms = rooms.as_ref()?;
+    let chats = rooms
+        .iter()
+        .filter(|room| chat.is_none_or(|chat| room.is(chat)));
+    Some(chats.map(|room| room.unread).sum())
+}
+
+/// Marks a service (`telegram`), or one of its chats (`matrix:#room:example.org`), as
+/// read. Matrix rooms are marked on the homeserver too.
+pub fn mark_read(target: &str) -> Result<(), String> {
+    let (service, chat) = match target.split_once(':') {
+        Some((service, chat)) => (service.trim(), Some(chat.trim())),
+        None => (target.trim(), None),
+    };
+    let rooms = rooms_of(service).ok_or_else(|| format!("Unknown chat service '{}'", service))?;
+    let mut read = vec![];
+    {
+        let mut rooms = rooms.write().unwrap();
+        let rooms = rooms
+            .as_mut()
+            .ok_or_else(|| format!("No {} account configured under chats", service))?;
+        for room in rooms.iter_mut() {
+            if chat.is_none_or(|chat| room.is(chat)) && room.unread > 0 {
+                room.unread = 0;
+                read.push(room.clone());
+            }
+        }
+    }
+    if service == "matrix" {
+        let account = MATRIX_ACCOUNT.read().unwrap().clone();
+        if let Some(account) = account {
+            for room in read {
+                send_read_receipt(&account, &room)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Redraws the keys showing the counts of `service`
+fn counts_changed(service: &str, tx: &Sender<DeviceEvent>) {
+    send(
+        tx,
+        DeviceEvent::ProviderUpdated {
+            provider: service.to_string(),
+        },
+    );
+}
+
+fn follow_telegram(conf: &TelegramChat, tx: &Sender<DeviceEvent>, still_active: &AtomicBool) {
+    let agent = agent();
+    let mut offset: i64 = 0;
+    info_log!("Following Telegram messages");
+    while still_active.load(Ordering::Relaxed) {
+        let url = format!(
+            "https://api.telegram.org/bot{}/getUpdates?timeout={}&offset={}&allowed_updates=%5B%22message%22%5D",
+            conf.token.trim(),
+            POLL_SECONDS,
+            offset
+        );
+        let updates = match get_json(&agent, &url, None) {
+            Ok(updates) => updates,
+            Err(e) => {
+                // The address holds the token, which is kept out of the log
+                error_log!(
+                    "Telegram updates failed: {}",
+                    e.replace(conf.token.trim(), "…")
+                );
+                thread::sleep(RETRY_DELAY);
+                continue;
+            }
+        };
+        let updates = updates["result"].as_array().cloned().unwrap_or_default();
+        let mut changed = false;
+        for update in updates {
+            offset = offset.max(update["update_id"].as_i64().unwrap_or(0) + 1);
+            let chat = &update["message"]["chat"];
+            let Some(id) = chat["id"].as_i64() else {
+                continue;
+            };
+            let names: Vec<String> = [
+                chat["title"].as_str().map(str::to_string),
+                chat["username"].as_str().map(|name| format!("@{}", name)),
+                chat["first_name"].as_str().map(str::to_string),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            let mut rooms = TELEGRAM.write().unwrap();
+            let rooms = rooms.get_or_insert_with(Vec::new);
+            let id = id.to_string();
+            match rooms.iter_mut().find(|room| room.id == id) {
+                Some(room) => {
+                    room.unread += 1;
+                    room.names = names;
+                }
+                None => rooms.push(Room {
+                    id,
+                    names,
+                    unread: 1,
+                    last_event: None,
+                }),
+            }
+            changed = true;
+        }
+        if changed {
+            counts_changed("telegram", tx);
+        }
+    }
+}
+
+fn follow_matrix(conf: &MatrixChat, tx: &Sender<DeviceEvent>, still_active: &AtomicBool) {
+    let agent = agent();
+    let homeserver = conf.homeserver.trim().trim_end_matches('/');
+    let mut since: Option<String> = None;
+    info_log!("Following Matrix rooms on {}", homeserver);
+    while still_active.load(Ordering::Relaxed) {
+        let mut url = format!(
+            "{}/_matrix/client/v3/sync?timeout={}&filter={}",
+            homeserver,
+            POLL_SECONDS * 1000,
+            encode(MATRIX_FILTER)
+        );
+        if let Some(since) = &since {
+            url.push_str(&format!("&since={}", encode(since)));
+        }
+        let sync = match get_json(&agent, &url, Some(conf.token.trim())) {
+            Ok(sync) => sync,
+            Err(e) => {
+                error_log!("Matrix sync with {} failed: {}", homeserver, e);
+                thread::sleep(RETRY_DELAY);
+                continue;
+            }
+        };
+        since = sync["next_batch"].as_str().map(str::to_string);
+        if apply_matrix_sync(&sync) {
+            counts_changed("matrix", tx);
+        }
+    }
+}
+
+/// Takes the counts, names and latest events of the rooms in a sync; true when a count
+/// changed
+fn apply_matrix_sync(sync: &Value) -> bool {
+    let mut rooms = MATRIX.write().unwrap();
+    let rooms = rooms.get_or_insert_with(Vec::new);
+    let mut changed = false;
+    if let Some(left) = sync["rooms"]["leave"].as_object() {
+        let before = rooms.len();
+        rooms.retain(|room| !left.contains_key(&room.id));
+        changed |= rooms.len() != before;
+    }
+    let Some(joined) = sync["rooms"]["join"].as_object() else {
+        return changed;
+    };
+    for (id, joined) in joined {
+        let index = match rooms.iter().position(|room| &room.id == id) {
+            Some(index) => index,
+            None => {
+                rooms.push(Room {
+                    id: id.clone(),
+                    ..Room::default()
+                });
+                rooms.len() - 1
+            }
+        };
+        let room = &mut rooms[index];
+        let events = joined["state"]["events"]
+            .as_array()
+            .into_iter()
+            .chain(joined["timeline"]["events"].as_array())
+            .flatten();
+        for event in events {
+            let name = match event["type"].as_str() {
+                Some("m.room.name") => event["content"]["name"].as_str(),
+                Some("m.room.canonical_alias") => event["content"]["alias"].as_str(),
+                _ => None,
+            };
+            if let Some(name) = name.filter(|name| !room.names.iter().any(|n| n == name)) {
+                room.names.push(name.to_string());
+            }
+            if let Some(event_id) = event["event_id"].as_str() {
+                room.last_event = Some(event_id.to_string());
+            }
+        }
+        if let Some(unread) = joined["unread_notifications"]["notification_count"].as_u64() {
+            changed |= room.unread != unread;
+            room.unread = unread;
+        }
+    }
+    changed
+}
+
+/// Moves the read marker of a Matrix room to its latest event
+fn send_read_receipt(account: &MatrixChat, room: &Room) -> Result<(), String> {
+    let Some(event) = &room.last_event else {
+        return Ok(());
+    };
+    let url = format!(
+        "{}/_matrix/client/v3/rooms/{}/read_markers",
+        account.homeserver.trim().trim_end_matches('/'),
+        encode(&room.id)
+    );
+    let body = serde_json::json!({ "m.fully_read": event, "m.read": event }).to_string();
+    agent()
+        .post(&url)
+        .header("Authorization", format!("Bearer {}", account.token.trim()))
+        .header("Content-Type", "application/json")
+        .send(body.as_bytes())
+        .map_err(|e| format!("Failed to mark Matrix room {} as read: {}", room.id, e))?;
+    verbose_log!("Marked Matrix room {} as read", room.id);
+    Ok(())
+}
+
+fn get_json(agent: &ureq::Agent, url: &str, bearer: Option<&str>) -> Result<Value, String> {
+    let mut request = agent.get(url);
+    if let Some(token) = bearer {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+    let text = request
+        .call()
+        .map_err(|e| e.to_string())?
+        .body_mut()
+        .read_to_string()
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
+
+/// Percent-encodes a query or path component
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matrix_sync_tracks_counts_and_names() {
+        let sync: Value = serde_json::from_str(
+            r##"{"rooms":{"join":{"!a:x.org":{
+                "state":{"events":[{"type":"m.room.canonical_alias","content":{"alias":"#team:x.org"}}]},
+                "timeline":{"events":[{"type":"m.room.message","event_id":"$1"}]},
+                "unread_notifications":{"notification_count":3}}}}}"##,
+        )
+        .unwrap();
+        assert!(apply_matrix_sync(&sync));
+        assert_eq!(unread("matrix", Some("#TEAM:x.org")), Some(3));
+        assert_eq!(unread("matrix", None), Some(3));
+        assert!(!apply_matrix_sync(&sync));
+        let room = MATRIX.read().unwrap().as_ref().unwrap()[0].clone();
+        assert_eq!(room.last_event.as_deref(), Some("$1"));
+        assert_eq!(encode("#team:x.org"), "%23team%3Ax.org");
+    }
+}
@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Done state of checklist items, for the `check` action and the `${checklist:...}`
+//! provider.
+//!
+//! Items are addressed as `LIST/ITEM`; generated checklist pages use the page name as
+//! list. The items done are kept in `checklists.json` in the state dir, written on every
+//! change, so they survive restarts and reloads. Items that left the list stay in the
+//! file until they are toggled again.
+
+use crate::error_log;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Mark shown before the label of an item done
+const DONE_MARK: &str = "✓ ";
+
+/// Combining long stroke overlay, drawn through the character before it
+const STRIKE: char = '\u{0336}';
+
+/// Items done, by list
+type Done = BTreeMap<String, BTreeSet<String>>;
+
+/// Items done, read from the state file on first use
+static DONE: Mutex<Option<Done>> = Mutex::new(None);
+
+fn state_path() -> PathBuf {
+    keydeck_types::get_state_dir().join("checklists.json")
+}
+
+fn load() -> Done {
+    let path = state_path();
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Done::new();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        error_log!("Ignoring unreadable checklists {}: {}", path.display(), e);
+        Done::new()
+    })
+}
+
+fn save(done: &Done) -> Result<(), String> {
+    let path = state_path();
+    let json = serde_json::to_string_pretty(done)
+        .map_err(|e| format!("Failed to serialize checklists: {}", e))?;
+    keydeck_types::BackupManager::STATE
+        .save(&path, json)
+        .map(|_| ())
+}
+
+/// List and item of `LIST/ITEM`. Items may contain `/`, lists may not.
+fn parse_item(target: &str) -> Option<(&str, &str)> {
+    target
+        .trim()
+        .split_once('/')
+        .filter(|(list, item)| !list.is_empty() && !item.is_empty())
+}
+
+/// The label of an item done: checked and struck through
+fn strike(item: &str) -> String {
+    let mut label = DONE_MARK.to_string();
+    for c in item.chars() {
+        label.push(c);
+        if !c.is_whitespace() {
+            label.push(STRIKE);
+        }
+    }
+    label
+}
+
+/// Value of `done:LIST/ITEM` (`1` or `0`) or `label:LIST/ITEM` (the item, struck through
+/// once done). None for anything else.
+pub fn value(arg: &str) -> Option<String> {
+    let (kind, target) = arg.split_once(':')?;
+    let (list, item) = parse_item(target)?;
+    let mut done = DONE.lock().unwrap();
+    let is_done = done
+        .get_or_insert_with(load)
+        .get(list)
+        .is_some_and(|items| items.contains(item));
+    match kind {
+        "done" => Some(if is_done { "1" } else { "0" }.to_string()),
+        "label" if is_done => Some(strike(item)),
+        "label" => Some(item.to_string()),
+        _ => None,
+    }
+}
+
+/// Toggles the item `LIST/ITEM` and saves the checklists. Returns whether it is done now.
+pub fn toggle(target: &str) -> Result<bool, String> {
+    let (list, item) = parse_item(target)
+        .ok_or_else(|| format!("Invalid checklist item '{}', expected LIST/ITEM", target))?;
+    let mut done = DONE.lock().unwrap();
+    let done = done.get_or_insert_with(load);
+    let now_done = toggle_in(done, list, item);
+    save(done)?;
+    Ok(now_done)
+}
+
+fn toggle_in(done: &mut Done, list: &str, item: &str) -> bool {
+    let items = done.entry(list.to_string()).or_default();
+    let now_done = items.insert(item.to_string()) || !items.remove(item);
+    if items.is_empty() {
+        done.remove(list);
+    }
+    now_done
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items_toggle_and_strike_through() {
+        let mut done = Done::new();
+        assert!(toggle_in(&mut done, "Groceries", "Milk"));
+        assert!(done["Groceries"].contains("Milk"));
+        assert!(!toggle_in(&mut done, "Groceries", "Milk"));
+        assert!(done.is_empty());
+
+        assert_eq!(strike("a b"), "✓ a\u{336} b\u{336}");
+        assert_eq!(
+            parse_item("Trip/Tickets/Train"),
+            Some(("Trip", "Tickets/Train"))
+        );
+        assert_eq!(parse_item("Trip"), None);
+        assert_eq!(parse_item("/Milk"), None);
+    }
+}
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! What a configuration reload changes on the page a device shows.
+//!
+//! A reload used to redraw every key of every device. The page shown is now compared
+//! with the one it replaces, so only keys whose button changed (directly or through the
+//! button template it refers to) are drawn again, and a device whose page did not change
+//! is left alone. Settings of the page itself, such as its theme, redraw the whole page.
+
+use crate::pages::{Button, ButtonConfig, Page, Pages};
+use indexmap::IndexMap;
+use std::collections::HashMap;
+
+/// How the page a device shows changed with a reload
+#[derive(Debug, PartialEq)]
+pub enum PageChange {
+    Unchanged,
+    /// Only these keys, numbered from 1, changed
+    Keys(Vec<u8>),
+    /// The page was replaced or its own settings changed
+    Page,
+}
+
+/// Templates of buttons referred to by name
+type Templates = Option<IndexMap<String, Button>>;
+
+/// Compares the page shown before and after a reload, with the button templates of each
+pub fn page_change(
+    old: Option<&Page>,
+    new: Option<&Page>,
+    old_templates: &Templates,
+    new_templates: &Templates,
+    button_count: u8,
+) -> PageChange {
+    let (Some(old), Some(new)) = (old, new) else {
+        return PageChange::Page;
+    };
+    if !same_settings(old, new) {
+        return PageChange::Page;
+    }
+    let keys: Vec<u8> = (1..=button_count)
+        .filter(|&key| button(old, old_templates, key) != button(new, new_templates, key))
+        .collect();
+    if keys.is_empty() {
+        PageChange::Unchanged
+    } else {
+        PageChange::Keys(keys)
+    }
+}
+
+/// Whether two page groups have the same settings, such as the press effect, that every
+/// key depends on
+pub fn same_group_settings(old: &Pages, new: &Pages) -> bool {
+    let settings = |pages: &Pages| Pages {
+        pages: IndexMap::new(),
+        ..pages.clone()
+    };
+    settings(old) == settings(new)
+}
+
+/// Whether two pages are the same apart from their buttons
+fn same_settings(old: &Page, new: &Page) -> bool {
+    let settings = |page: &Page| Page {
+        buttons: HashMap::new(),
+        ..page.clone()
+    };
+    settings(old) == settings(new)
+}
+
+/// The button on `key` of a page, with a template reference resolved
+fn button<'a>(page: &'a Page, templates: &'a Templates, key: u8) -> Option<&'a Button> {
+    match page.buttons.get(&format!("button{}", key))? {
+        ButtonConfig::Template(name) => templates.as_ref()?.get(name),
+        ButtonConfig::Detailed(button) => Some(button),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(yaml: &str) -> Page {
+        serde_yaml_ng::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn only_changed_keys_are_redrawn() {
+        let templates: Templates =
+            serde_yaml_ng::from_str("mail:\n  text: Mail\nweb:\n  text: Web\n").unwrap();
+        let renamed: Templates =
+            serde_yaml_ng::from_str("mail:\n  text: Inbox\nweb:\n  text: Web\n").unwrap();
+        let old = page("button1:\n  text: A\nbutton2: mail\nbutton3: web\n");
+        let change = |new: &Page, new_templates: &Templates| {
+            page_change(Some(&old), Some(new), &templates, new_templates, 6)
+        };
+
+        assert_eq!(change(&old, &templates), PageChange::Unchanged);
+        assert_eq!(change(&old, &renamed), PageChange::Keys(vec![2]));
+        assert_eq!(
+            change(
+                &page("button1:\n  text: B\nbutton2: mail\nbutton3: web\nbutton6:\n  text: C\n"),
+                &templates
+            ),
+            PageChange::Keys(vec![1, 6])
+        );
+        assert_eq!(
+            change(
+                &page("theme: dark\nbutton1:\n  text: A\nbutton2: mail\nbutton3: web\n"),
+                &templates
+            ),
+            PageChange::Page
+        );
+        assert_eq!(
+            page_change(Some(&old), None, &templates, &templates, 6),
+            PageChange::Page
+        );
+    }
+}
@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Where a configuration error is, and what was probably meant.
+//!
+//! Errors found after the configuration is parsed, such as a jump to a page that does
+//! not exist, only know the names leading to the mistake (page group, page, button).
+//! [`locate`] finds the line and column of those names in the YAML text, and
+//! [`did_you_mean`] picks the defined name closest to a misspelled one. Errors of the
+//! parser already know their place; [`unknown_name_hint`] adds the suggestion for an
+//! unknown field or variant.
+
+/// Line and column, both from 1, of the deepest part of `path` found in `yaml`: each
+/// element is a mapping key within the previous one. Only block style mappings are
+/// followed, so a key inside `{ ... }` is located at the mapping that holds it.
+pub fn locate(yaml: &str, path: &[&str]) -> Option<(usize, usize)> {
+    let mut found = None;
+    // Indentation of the key matched last, and of its children once known
+    let mut parent_indent: Option<usize> = None;
+    let mut child_indent: Option<usize> = None;
+    let mut depth = 0;
+    for (number, line) in yaml.lines().enumerate() {
+        if depth == path.len() {
+            break;
+        }
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with("---") {
+            continue;
+        }
+        // A key after `- ` starts further in
+        let mut indent = line.len() - trimmed.len();
+        let mut content = trimmed;
+        while let Some(rest) = content.strip_prefix("- ") {
+            let rest_trimmed = rest.trim_start();
+            indent += content.len() - rest_trimmed.len();
+            content = rest_trimmed;
+        }
+        if parent_indent.is_some_and(|parent| indent <= parent) {
+            // The block of the key matched last ended without the next key
+            break;
+        }
+        let child = *child_indent.get_or_insert(indent);
+        if indent != child {
+            continue;
+        }
+        if key_of(content) == Some(path[depth]) {
+            found = Some((number + 1, indent + 1));
+            parent_indent = Some(indent);
+            child_indent = None;
+            depth += 1;
+        }
+    }
+    found
+}
+
+/// The mapping key a line starts with, unquoted
+fn key_of(content: &str) -> Option<&str> {
+    let (key, _) = content.split_once(':')?;
+    let key = key.trim_end();
+    let unquoted = key
+        .strip_prefix('"')
+        .and_then(|k| k.strip_suffix('"'))
+        .or_else(|| key.strip_prefix('\'').and_then(|k| k.strip_suffix('\'')));
+    Some(unquoted.unwrap_or(key))
+}
+
+/// The candidate closest to `name`, when it is close enough to be what was meant
+pub fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edits turning `a` into `b`, a swap of two neighbouring letters counting as one
+fn distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // Rows of the edit table: the one before the previous, the previous and the current
+    let mut before: Vec<usize> = vec![];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 0..a.len() {
+        let mut current = vec![i + 1];
+        for j in 0..b.len() {
+            let mut edits = (previous[j] + usize::from(a[i] != b[j]))
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                edits = edits.min(before[j - 1] + 1);
+            }
+            current.push(edits);
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+/// The suggestion for a parser error about an unknown field or variant, as in
+/// ``unknown field `backgrund`, expected one of `text`, `background` ``
+pub fn unknown_name_hint(message: &str) -> Option<String> {
+    let rest = message
+        .split_once("unknown field `")
+        .or_else(|| message.split_once("unknown variant `"))?
+        .1;
+    let (name, expected) = rest.split_once('`')?;
+    let candidates: Vec<&str> = expected.split('`').skip(1).step_by(2).collect();
+    did_you_mean(name, candidates).map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_are_located_and_names_suggested() {
+        let yaml = "brightness: 80\ndefault:\n  main_page: Main\n  Main:\n    button1:\n      text: A\n      actions:\n        - jump: Mian\n  Media:\n    # volume\n    button2: { text: B }\n";
+        assert_eq!(locate(yaml, &["default", "Main", "button1"]), Some((5, 5)));
+        assert_eq!(locate(yaml, &["default", "main_page"]), Some((3, 3)));
+        assert_eq!(
+            locate(yaml, &["default", "Media", "button2"]),
+            Some((11, 5))
+        );
+        // The deepest part found
+        assert_eq!(locate(yaml, &["default", "Main", "button9"]), Some((4, 3)));
+        assert_eq!(locate(yaml, &["default", "Main", "text"]), Some((4, 3)));
+        assert_eq!(locate(yaml, &["Main"]), None);
+
+        assert_eq!(did_you_mean("Mian", ["Main", "Media"]), Some("Main"));
+        assert_eq!(did_you_mean("main", ["Main"]), Some("Main"));
+        assert_eq!(did_you_mean("Settings", ["Main", "Media"]), None);
+        assert_eq!(
+            unknown_name_hint("unknown field `backgrund`, expected one of `text`, `background`")
+                .as_deref(),
+            Some("background")
+        );
+        assert_eq!(unknown_name_hint("invalid type: string"), None);
+    }
+}
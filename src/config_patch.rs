@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Changes to the configuration of the running daemon, for `keydeck --set-button`.
+//!
+//! A patch replaces the text, icon or colors of one button, addressed as
+//! `GROUP/PAGE/BUTTON`, in the configuration held in memory; only the devices showing
+//! that page group are redrawn, and services keep running. With `save`, the same fields
+//! are also written to the configuration file, otherwise the change lasts until the next
+//! reload.
+
+use crate::pages::{Button, ButtonConfig, ColorConfig, Macro, Pages, TextConfig};
+use indexmap::IndexMap;
+use keydeck_types::pages::Icon;
+use serde::{Deserialize, Serialize};
+use serde_yaml_ng::{Mapping, Value};
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+
+/// Fields of a button to replace. An empty value removes the field.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ButtonPatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_color: Option<String>,
+    /// Also write the change to the configuration file
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub save: bool,
+}
+
+impl ButtonPatch {
+    pub fn is_empty(&self) -> bool {
+        self.text.is_none()
+            && self.icon.is_none()
+            && self.background.is_none()
+            && self.text_color.is_none()
+    }
+
+    /// The fields to replace, by their name in the configuration
+    fn fields(&self) -> impl Iterator<Item = (&'static str, &String)> {
+        [
+            ("text", &self.text),
+            ("icon", &self.icon),
+            ("background", &self.background),
+            ("text_color", &self.text_color),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_ref().map(|value| (name, value)))
+    }
+
+    /// Applies the patch to a button. A detailed text keeps its font settings.
+    fn apply_to(&self, button: &mut Button) {
+        let value = |value: &String| Some(value.clone()).filter(|value| !value.is_empty());
+        if let Some(text) = &self.text {
+            button.text = match (button.text.take(), value(text)) {
+                (
+                    Some(TextConfig::Detailed {
+                        font_size, marquee, ..
+                    }),
+                    Some(text),
+                ) => Some(TextConfig::Detailed {
+                    value: text,
+                    font_size,
+                    marquee,
+                }),
+                (_, text) => text.map(TextConfig::Simple),
+            };
+        }
+        if let Some(icon) = &self.icon {
+            button.icon = value(icon).map(Icon::File);
+        }
+        if let Some(background) = &self.background {
+            button.background = value(background).map(ColorConfig::Fixed);
+        }
+        if let Some(text_color) = &self.text_color {
+            button.text_color = value(text_color).map(ColorConfig::Fixed);
+        }
+    }
+}
+
+/// Where a patch landed
+#[derive(Debug, PartialEq)]
+pub struct Patched {
+    /// Page group holding the button
+    pub group: String,
+    /// The page had no such button, so it was added
+    pub created: bool,
+}
+
+/// Client waiting for the result of a change
+static PENDING: Mutex<Option<Sender<Result<(), String>>>> = Mutex::new(None);
+
+/// Page group, button name and page of `GROUP/PAGE/BUTTON`. The button may be given by
+/// its number alone.
+fn parse_target(target: &str) -> Result<(&str, &str, String), String> {
+    let mut parts = target.splitn(3, '/');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(group), Some(page), Some(button))
+            if !group.is_empty() && !page.is_empty() && !button.is_empty() =>
+        {
+            let button = if button.chars().all(|c| c.is_ascii_digit()) {
+                format!("button{}", button)
+            } else {
+                button.to_string()
+            };
+            Ok((group, page, button))
+        }
+        _ => Err(format!(
+            "Invalid button '{}', expected GROUP/PAGE/BUTTON",
+            target
+        )),
+    }
+}
+
+/// The page group a device with `serial` uses: its own, or else `default`
+pub fn group_of<'a>(page_groups: &IndexMap<String, Pages>, serial: &'a str) -> &'a str {
+    if page_groups.contains_key(serial) {
+        serial
+    } else {
+        "default"
+    }
+}
+
+/// Applies `patch` to the button `target` of `page_groups`. A button referring to a
+/// button template becomes a copy of it; a missing button is added.
+pub fn apply(
+    page_groups: &mut IndexMap<String, Pages>,
+    button_templates: &Option<IndexMap<String, Button>>,
+    macros: &Option<IndexMap<String, Macro>>,
+    target: &str,
+    patch: &ButtonPatch,
+) -> Result<Patched, String> {
+    if patch.is_empty() {
+        return Err(
+            "Nothing to change, expected --text, --icon, --background or --text-color".to_string(),
+        );
+    }
+    let (group, page, button_name) = parse_target(target)?;
+    let page = page_groups
+        .get_mut(group)
+        .ok_or_else(|| format!("Page group not found: {}", group))?
+        .pages
+        .get_mut(page)
+        .ok_or_else(|| format!("Page not found: {}/{}", group, page))?;
+    let created = !page.buttons.contains_key(&button_name);
+    let mut button = match page.buttons.remove(&button_name) {
+        Some(ButtonConfig::Detailed(button)) => button,
+        Some(ButtonConfig::Template(name)) => button_templates
+            .as_ref()
+            .and_then(|templates| templates.get(&name))
+            .cloned()
+            .ok_or_else(|| format!("Button template not found: {}", name))?,
+        None => serde_yaml_ng::from_str("{}").map_err(|e| e.to_string())?,
+    };
+    patch.apply_to(&mut button);
+    button.is_dynamic_computed = crate::dynamic_detection::is_button_dynamic(&button, macros);
+    page.buttons
+        .insert(button_name, ButtonConfig::Detailed(button));
+    Ok(Patched {
+        group: group.to_string(),
+        created,
+    })
+}
+
+/// Writes `patch` to the button `target` of the configuration file, keeping a backup of
+/// the previous one. Comments of the file are not kept.
+pub fn save(target: &str, patch: &ButtonPatch, created: bool) -> Result<(), String> {
+    let path = keydeck_types::get_config_path();
+    keydeck_types::require_yaml(&path, "Saving button changes")?;
+    let yaml = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut conf: Value = serde_yaml_ng::from_str(&yaml)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    patch_yaml(&mut conf, target, patch, created)?;
+    let yaml = serde_yaml_ng::to_string(&conf)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+    keydeck_types::save_config_file(&path, &yaml).map(|_| ())
+}
+
+/// Applies `patch` to the button `target` of a configuration file, leaving the rest as
+/// it is. Buttons that only come from a template are not in the file, so they can only
+/// be changed in memory.
+fn patch_yaml(
+    conf: &mut Value,
+    target: &str,
+    patch: &ButtonPatch,
+    created: bool,
+) -> Result<(), String> {
+    let (group, page, button_name) = parse_target(target)?;
+    let page = conf
+        .get_mut(group)
+        .and_then(|group| group.get_mut(page))
+        .and_then(Value::as_mapping_mut)
+        .ok_or_else(|| format!("Page {}/{} is not in the configuration file", group, page))?;
+    let key = Value::String(button_name.clone());
+    if !page.contains_key(&key) {
+        if !created {
+            return Err(format!(
+                "Button {} comes from a template and is not in the configuration file",
+                target
+            ));
+        }
+        page.insert(key.clone(), Value::Mapping(Mapping::new()));
+    }
+    let button = page
+        .get_mut(&key)
+        .and_then(Value::as_mapping_mut)
+        .ok_or_else(|| {
+            format!(
+                "Button {} refers to a button template, change the template instead",
+                target
+            )
+        })?;
+    for (field, value) in patch.fields() {
+        let field = Value::String(field.to_string());
+        if value.is_empty() {
+            button.remove(&field);
+        } else if let Some(Value::Mapping(text)) = button.get_mut(&field) {
+            // Detailed text: keep the font settings
+            text.insert(
+                Value::String("value".to_string()),
+                Value::String(value.clone()),
+            );
+        } else {
+            button.insert(field, Value::String(value.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Registers the client waiting for the result of the next change
+pub fn await_result(reply: Sender<Result<(), String>>) {
+    PENDING.lock().unwrap().replace(reply);
+}
+
+/// Hands the result of a change to the client waiting for it
+pub fn deliver_result(result: Result<(), String>) {
+    if let Some(reply) = PENDING.lock().unwrap().take() {
+        let _ = reply.send(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONF: &str = "
+default:
+  main:
+    button1:
+      text:
+        value: Old
+        font_size: 20
+      background: \"0xff0000\"
+    button2: mute
+";
+
+    fn patch(text: &str) -> ButtonPatch {
+        ButtonPatch {
+            text: Some(text.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn patches_address_one_button_of_the_file() {
+        let mut conf: Value = serde_yaml_ng::from_str(CONF).unwrap();
+        let mut new = patch("Hello");
+        new.background = Some(String::new());
+        patch_yaml(&mut conf, "default/main/1", &new, false).unwrap();
+        let button = &conf["default"]["main"]["button1"];
+        assert_eq!(button["text"]["value"].as_str(), Some("Hello"));
+        assert_eq!(button["text"]["font_size"].as_u64(), Some(20));
+        assert!(button.get("background").is_none());
+
+        patch_yaml(&mut conf, "default/main/button3", &patch("New"), true).unwrap();
+        assert_eq!(
+            conf["default"]["main"]["button3"]["text"].as_str(),
+            Some("New")
+        );
+
+        assert!(patch_yaml(&mut conf, "default/main/button2", &patch("x"), false).is_err());
+        assert!(patch_yaml(&mut conf, "default/main/button4", &patch("x"), false).is_err());
+        assert!(patch_yaml(&mut conf, "default/other/button1", &patch("x"), true).is_err());
+        assert!(parse_target("default/main").is_err());
+    }
+}
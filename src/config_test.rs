@@ -0,0 +1,319 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! `keydeck --test-config`: checks the expectations of the `tests` section, and of test
+//! files, against the configuration as the daemon loads it.
+//!
+//! Each test names a button of a page and states what it should show (its text and
+//! icon, with the dynamic parameters replaced) and what a press should run (its actions,
+//! with macros expanded). Dynamic parameters take the values the test stubs, and the
+//! error indicator without one, so a test gives the same result on every computer and
+//! never runs a service.
+
+use crate::dynamic_params::ERROR_INDICATOR;
+use crate::locale_format;
+use crate::pages::{
+    Action, Button, ButtonConfig, ConfigTest, Icon, KeyDeckConf, KeyDeckConfLoader, TextConfig,
+};
+use indexmap::IndexMap;
+use regex::Regex;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Deepest chain of macros calling macros that is expanded
+const MAX_MACRO_DEPTH: usize = 16;
+
+/// A `${...}` dynamic parameter
+static PARAM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$\{([^}]+)\}").unwrap());
+
+#[derive(Debug, Serialize)]
+pub struct TestResult {
+    pub name: String,
+    /// What differed from the expectations, empty when the test passed
+    pub failures: Vec<String>,
+}
+
+/// Runs `tests` against `conf`, a configuration loaded as the daemon loads it
+pub fn run_tests(conf: &KeyDeckConf, tests: &[ConfigTest]) -> Vec<TestResult> {
+    let (locale, _) = crate::i18n::translations(conf.strings.as_ref(), conf.locale.as_deref());
+    locale_format::set_locale(&locale);
+    tests
+        .iter()
+        .map(|test| {
+            let group = test.group.as_deref().unwrap_or("default");
+            let key = test.button.key();
+            TestResult {
+                name: test
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("{} {} {}", group, test.page, key)),
+                failures: check(conf, test, group, &key),
+            }
+        })
+        .collect()
+}
+
+/// Where the button of `test` differs from its expectations
+fn check(conf: &KeyDeckConf, test: &ConfigTest, group: &str, key: &str) -> Vec<String> {
+    let button = match find_button(conf, group, &test.page, key) {
+        Ok(button) => button,
+        Err(e) => return vec![e],
+    };
+    let no_stubs = IndexMap::new();
+    let stubs = test.stubs.as_ref().unwrap_or(&no_stubs);
+    let mut failures = vec![];
+
+    let text = match &button.text {
+        Some(TextConfig::Simple(text)) => substitute(text, stubs),
+        Some(TextConfig::Detailed { value, .. }) => substitute(value, stubs),
+        None => String::new(),
+    };
+    if let Some(expected) = &test.text {
+        if text != *expected {
+            failures.push(format!("text is {:?}, expected {:?}", text, expected));
+        }
+    }
+    if let Some(pattern) = &test.text_matches {
+        match Regex::new(&format!("^(?:{})$", pattern)) {
+            Ok(regex) if !regex.is_match(&text) => {
+                failures.push(format!("text {:?} does not match {:?}", text, pattern));
+            }
+            Ok(_) => {}
+            Err(e) => failures.push(format!("invalid text_matches {:?}: {}", pattern, e)),
+        }
+    }
+
+    if let Some(expected) = &test.icon {
+        match &button.icon {
+            Some(Icon::File(icon)) => {
+                let icon = substitute(icon, stubs);
+                if icon != *expected {
+                    failures.push(format!("icon is {:?}, expected {:?}", icon, expected));
+                }
+            }
+            Some(Icon::Layers(_)) => failures.push("icon has layers".to_string()),
+            None => failures.push("button has no icon".to_string()),
+        }
+    }
+
+    if let Some(expected) = &test.actions {
+        let actions = button.actions.as_deref().unwrap_or_default();
+        match expand_macros(conf, actions, 0) {
+            Ok(actions) => {
+                let (actions, expected) = (as_yaml(&actions), as_yaml(expected));
+                if actions != expected {
+                    failures.push(format!(
+                        "actions are\n{}expected\n{}",
+                        indent(&actions),
+                        indent(&expected)
+                    ));
+                }
+            }
+            Err(e) => failures.push(e),
+        }
+    }
+    failures
+}
+
+/// The button `key` of a page, with a reference to a button definition followed
+fn find_button<'a>(
+    conf: &'a KeyDeckConf,
+    group: &str,
+    page: &str,
+    key: &str,
+) -> Result<&'a Button, String> {
+    let pages = conf
+        .page_groups
+        .get(group)
+        .ok_or_else(|| format!("page group '{}' not found", group))?;
+    let page = pages
+        .pages
+        .get(page)
+        .ok_or_else(|| format!("page '{}' not found in '{}'", page, group))?;
+    match page.buttons.get(key) {
+        Some(ButtonConfig::Detailed(button)) => Ok(button),
+        Some(ButtonConfig::Template(name)) => conf
+            .buttons
+            .as_ref()
+            .and_then(|buttons| buttons.get(name))
+            .ok_or_else(|| format!("button definition '{}' not found", name)),
+        None => Err(format!("{} not found", key)),
+    }
+}
+
+/// `text` with every `${...}` replaced by its stub, through the filters that follow it
+fn substitute(text: &str, stubs: &IndexMap<String, String>) -> String {
+    PARAM
+        .replace_all(text, |param: &regex::Captures| {
+            let (expression, filters) = locale_format::split_filters(&param[1]);
+            let provider = expression.split_once(':').map_or(expression, |(p, _)| p);
+            let value = stubs
+                .get(expression)
+                .or_else(|| stubs.get(provider))
+                .map_or(ERROR_INDICATOR.to_string(), Clone::clone);
+            locale_format::apply_filters(value, &filters)
+        })
+        .into_owned()
+}
+
+/// `actions` with every macro call replaced by the actions of the macro, as they run
+fn expand_macros(
+    conf: &KeyDeckConf,
+    actions: &[Action],
+    depth: usize,
+) -> Result<Vec<Action>, String> {
+    let mut expanded = vec![];
+    for action in actions {
+        let Action::Macro(call) = action else {
+            expanded.push(action.clone());
+            continue;
+        };
+        if depth >= MAX_MACRO_DEPTH {
+            return Err(format!("macro '{}' calls itself", call.name));
+        }
+        let actions = conf
+            .macros
+            .as_ref()
+            .and_then(|macros| macros.get(&call.name))
+            .ok_or_else(|| format!("macro '{}' not found", call.name))?
+            .expand(&call.name, &call.params)?;
+        expanded.extend(expand_macros(conf, &actions, depth + 1)?);
+    }
+    Ok(expanded)
+}
+
+fn as_yaml(actions: &[Action]) -> String {
+    serde_yaml_ng::to_string(actions).unwrap_or_default()
+}
+
+fn indent(text: &str) -> String {
+    text.lines().map(|line| format!("    {}\n", line)).collect()
+}
+
+/// The tests of the configuration at `config_path` and of the test files `files`, each
+/// a YAML list of tests like the `tests` section
+pub fn load_and_run(config_path: &Path, files: &[&str]) -> Result<Vec<TestResult>, String> {
+    let conf = KeyDeckConfLoader::try_load_from(config_path)?;
+    let mut tests = conf.tests.clone().unwrap_or_default();
+    for file in files {
+        let yaml =
+            std::fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {}", file, e))?;
+        let more: Vec<ConfigTest> = serde_yaml_ng::from_str(&yaml)
+            .map_err(|e| format!("Failed to parse {}: {}", file, e))?;
+        tests.extend(more);
+    }
+    Ok(run_tests(&conf, &tests))
+}
+
+/// `keydeck --test-config [CONFIG] [--tests FILE]...`, exiting with 1 when a test fails
+pub fn print_test_config(config_path: Option<&str>, files: &[&str], json: bool) {
+    let config_path = config_path
+        .map(Into::into)
+        .unwrap_or_else(keydeck_types::get_config_path);
+    let results = match load_and_run(&config_path, files) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let failed = results.iter().filter(|r| !r.failures.is_empty()).count();
+    if json {
+        println!("{}", serde_json::to_string(&results).unwrap_or_default());
+    } else {
+        for result in &results {
+            if result.failures.is_empty() {
+                println!("ok    {}", result.name);
+            } else {
+                println!("FAIL  {}", result.name);
+                for failure in &result.failures {
+                    println!("{}", indent(failure).trim_end());
+                }
+            }
+        }
+        println!(
+            "{} tests, {} passed, {} failed",
+            results.len(),
+            results.len() - failed,
+            failed
+        );
+    }
+    std::process::exit(if failed == 0 { 0 } else { 1 });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buttons_are_checked_with_stubbed_parameters() {
+        let dir = std::env::temp_dir().join(format!("keydeck-config-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        std::fs::write(
+            &path,
+            r#"
+macros:
+  term:
+    params:
+      app: konsole
+    actions:
+      - focus: ${app}
+      - macro: notify
+  notify:
+    actions:
+      - notify: opened
+buttons:
+  memory:
+    text: "RAM ${service:mem|bytes}"
+default:
+  Main:
+    button1: memory
+    button2:
+      text: "${time:%H:%M}"
+      icon: "cpu_${service:cpu}.png"
+      actions:
+        - macro: term
+          app: kitty
+        - exec: ls
+tests:
+  - page: Main
+    button: 1
+    stubs:
+      service:mem: "1503238553"
+    text: RAM 1.4 GiB
+  - name: Clock and terminal
+    page: Main
+    button: button2
+    stubs:
+      time: "12:00"
+      service:cpu: "50"
+    text_matches: "\\d+:\\d+"
+    icon: cpu_50.png
+    actions:
+      - focus: kitty
+      - notify: opened
+      - exec: ls
+  - page: Main
+    button: 2
+    text: "12:00"
+    actions:
+      - exec: ls
+  - page: Other
+    button: 1
+"#,
+        )
+        .unwrap();
+
+        let results = load_and_run(&path, &[]).unwrap();
+        let failures: Vec<_> = results.iter().map(|r| r.failures.len()).collect();
+        assert_eq!(failures, [0, 0, 2, 1]);
+        assert_eq!(results[1].name, "Clock and terminal");
+        assert_eq!(results[2].name, "default Main button2");
+        assert_eq!(results[2].failures[0], "text is \"⚠\", expected \"12:00\"");
+        assert!(results[2].failures[1].starts_with("actions are\n    - focus: kitty\n"));
+        assert_eq!(results[3].failures, ["page 'Other' not found in 'default'"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Crash reports kept on this machine.
+//!
+//! When keydeck panics, a report is written to `$XDG_STATE_HOME/keydeck/crashes`
+//! (`~/.local/state/keydeck/crashes` by default) and its path printed: the panic and its
+//! backtrace, an outline of the configuration without its commands, addresses or
+//! secrets, the devices connected and the last log lines. Nothing is sent anywhere; whether to attach the report to an
+//! issue is up to the user. The newest [`KEPT_REPORTS`] reports are kept.
+
+use crate::device_trait::KeydeckDevice;
+use crate::pages::KeyDeckConf;
+use crate::utils::recent_log_lines;
+use serde_yaml_ng::Value;
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::{fs, panic, thread};
+
+/// Reports kept in the crash directory, older ones are removed
+const KEPT_REPORTS: usize = 10;
+
+const ISSUES_URL: &str = "https://github.com/teras/keydeck/issues";
+
+/// Summary of the configuration running, see [`remember_config`]
+static CONFIG: Mutex<String> = Mutex::new(String::new());
+
+/// Serial and description of each device connected
+static DEVICES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Writes a crash report on every panic, after the usual panic message
+pub fn install() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        let report = report(&info.to_string(), &Backtrace::force_capture());
+        match write_report(&crashes_dir(), &report) {
+            Ok(path) => eprintln!(
+                "KeyDeck crashed. A report was written to {}\nNothing was sent; if you report the crash at {}, attaching it helps.",
+                path.display(),
+                ISSUES_URL
+            ),
+            Err(e) => eprintln!("KeyDeck crashed, and the crash report could not be written: {}", e),
+        }
+    }));
+}
+
+fn crashes_dir() -> PathBuf {
+    keydeck_types::get_state_dir().join("crashes")
+}
+
+/// Keeps a summary of `conf` for crash reports, when it is loaded or reloaded
+pub fn remember_config(conf: &KeyDeckConf) {
+    *lock(&CONFIG) = config_summary(conf);
+}
+
+/// Keeps the list of connected devices for crash reports, when it changed
+pub fn remember_devices<'a>(devices: impl Iterator<Item = (&'a str, &'a dyn KeydeckDevice)>) {
+    let devices: Vec<_> = devices.collect();
+    let mut known = lock(&DEVICES);
+    let unchanged = known.len() == devices.len()
+        && known
+            .iter()
+            .zip(&devices)
+            .all(|((known, _), (serial, _))| known == serial);
+    if unchanged {
+        return;
+    }
+    *known = devices
+        .into_iter()
+        .map(|(serial, device)| {
+            let (rows, columns) = device.button_layout();
+            let description = format!(
+                "{} {}, {} keys ({}x{}), {} encoders",
+                device.manufacturer(),
+                device.kind_name(),
+                device.button_count(),
+                rows,
+                columns,
+                device.encoder_count()
+            );
+            (serial.to_string(), description)
+        })
+        .collect();
+}
+
+/// A crash must not be lost to a lock poisoned by an earlier one
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The structure of `conf`: the values of its top-level settings, the names of the
+/// others, and the pages of each page group. Settings whose name suggests a secret, and
+/// everything deeper, such as commands and web addresses, are left out.
+fn config_summary(conf: &KeyDeckConf) -> String {
+    let mut summary = String::new();
+    if let Ok(Value::Mapping(settings)) = serde_yaml_ng::to_value(conf) {
+        for (key, value) in &settings {
+            let Some(key) = key.as_str() else { continue };
+            if conf.page_groups.contains_key(key) {
+                continue;
+            }
+            let shown = match value {
+                _ if is_secret(key) => "(hidden)".to_string(),
+                Value::Mapping(map) => {
+                    let names: Vec<&str> = map.keys().filter_map(Value::as_str).collect();
+                    format!("{{ {} }}", names.join(", "))
+                }
+                Value::Sequence(items) => format!("[{} items]", items.len()),
+                Value::String(text) => text.clone(),
+                Value::Bool(flag) => flag.to_string(),
+                Value::Number(number) => number.to_string(),
+                _ => continue,
+            };
+            let _ = writeln!(summary, "{}: {}", key, shown);
+        }
+    }
+    for (group, pages) in &conf.page_groups {
+        let pages: Vec<String> = pages
+            .pages
+            .iter()
+            .map(|(name, page)| format!("{} ({} buttons)", name, page.buttons.len()))
+            .collect();
+        let _ = writeln!(summary, "page group {}: {}", group, pages.join(", "));
+    }
+    summary
+}
+
+fn is_secret(key: &str) -> bool {
+    let key = key.to_lowercase();
+    ["token", "secret", "password", "auth"]
+        .iter()
+        .any(|word| key.contains(word))
+}
+
+fn report(panic: &str, backtrace: &Backtrace) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "KeyDeck crash report");
+    let _ = writeln!(report, "Version: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "Time: {}", chrono::Local::now().to_rfc3339());
+    let _ = writeln!(
+        report,
+        "System: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(
+        report,
+        "Thread: {}",
+        thread::current().name().unwrap_or("unnamed")
+    );
+    let _ = writeln!(report, "\n== Panic ==\n{}", panic);
+    let _ = writeln!(report, "\n== Backtrace ==\n{}", backtrace);
+
+    let config = lock(&CONFIG);
+    let config = if config.is_empty() {
+        "(not loaded)\n"
+    } else {
+        config.as_str()
+    };
+    let _ = write!(report, "\n== Configuration ==\n{}", config);
+
+    let _ = writeln!(report, "\n== Devices ==");
+    let devices = lock(&DEVICES);
+    if devices.is_empty() {
+        let _ = writeln!(report, "(none)");
+    }
+    for (serial, description) in devices.iter() {
+        let _ = writeln!(report, "{}: {}", serial, description);
+    }
+
+    let lines = recent_log_lines();
+    let _ = writeln!(report, "\n== Log (last {} lines) ==", lines.len());
+    for line in lines {
+        let _ = writeln!(report, "{}", line);
+    }
+    report
+}
+
+/// Writes `report` to a new file in `dir`, removing the oldest reports beyond
+/// [`KEPT_REPORTS`]
+fn write_report(dir: &Path, report: &str) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let name = chrono::Local::now().format("crash-%Y%m%d-%H%M%S-%3f.txt");
+    let path = dir.join(name.to_string());
+    fs::write(&path, report)?;
+
+    let mut reports: Vec<PathBuf> = fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("crash-") && name.ends_with(".txt"))
+        })
+        .collect();
+    // Named by time, so the oldest sort first
+    reports.sort();
+    let excess = reports.len().saturating_sub(KEPT_REPORTS);
+    for old in &reports[..excess] {
+        let _ = fs::remove_file(old);
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_leave_out_secrets_and_old_reports() {
+        let conf: KeyDeckConf = serde_yaml_ng::from_str(
+            "brightness: 70\nweb_deck:\n  port: 8080\n  tokens:\n    - token: hunter2\nMain1234:\n  main_page: Main\n  Main:\n    button1:\n      actions:\n        - exec: \"curl -u me:pass123 example.org\"\n",
+        )
+        .unwrap();
+        remember_config(&conf);
+        crate::info_log!("Device connected");
+        let report = report(
+            "panicked at src/server.rs:1:1:\nboom",
+            &Backtrace::disabled(),
+        );
+        assert!(report.contains("brightness: 70"));
+        assert!(report.contains("web_deck: { bind, port, tokens }"));
+        assert!(report.contains("page group Main1234: Main (1 buttons)"));
+        assert!(!report.contains("hunter2"));
+        assert!(!report.contains("pass123"));
+        assert!(report.contains("boom"));
+        assert!(report.contains("Device connected"));
+        assert!(is_secret("api_token") && !is_secret("tick_time"));
+
+        let dir = std::env::temp_dir().join(format!("keydeck-crashes-{}", std::process::id()));
+        for index in 0..KEPT_REPORTS + 2 {
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(format!("crash-2020{:04}.txt", index)), "").unwrap();
+        }
+        let path = write_report(&dir, &report).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), report);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), KEPT_REPORTS);
+        assert!(!dir.join("crash-20200002.txt").exists());
+        assert!(dir.join("crash-20200003.txt").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! The state of the daemon as D-Bus properties, for desktop widgets.
+//!
+//! On Linux the daemon owns `onl.ycode.keydeck.State` on the session bus and serves the
+//! `onl.ycode.keydeck.State` interface at `/onl/ycode/keydeck/State`. Its read-only
+//! properties are keyed by device serial, and every change is announced with
+//! `org.freedesktop.DBus.Properties.PropertiesChanged`, so Plasma applets and GNOME
+//! extensions can bind to them instead of polling:
+//!
+//! - `Devices` (`as`): serials of the connected devices
+//! - `Pages` (`a{ss}`): page each device shows
+//! - `Brightness` (`a{sy}`): brightness of each device
+//! - `Pinned` (`a{sb}`): whether the page of each device is pinned
+//!
+//! ```text
+//! busctl --user get-property onl.ycode.keydeck.State /onl/ycode/keydeck/State \
+//!     onl.ycode.keydeck.State Pages
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// What is published of one device
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceStatus {
+    /// Not published on D-Bus, but answered on the control socket
+    pub model: String,
+    pub page: String,
+    pub brightness: u8,
+    pub pinned: bool,
+}
+
+/// A property of the interface
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Property {
+    Devices,
+    Pages,
+    Brightness,
+    Pinned,
+}
+
+/// The connected devices by serial
+static DEVICES: Mutex<BTreeMap<String, DeviceStatus>> = Mutex::new(BTreeMap::new());
+
+/// Where changed properties are sent to be announced, once the service runs
+static CHANGES: Mutex<Option<UnboundedSender<Property>>> = Mutex::new(None);
+
+/// Properties that differ between two states of a device; a device that comes or goes
+/// changes all of them
+fn changed(old: Option<&DeviceStatus>, new: Option<&DeviceStatus>) -> Vec<Property> {
+    let (old, new) = match (old, new) {
+        (Some(old), Some(new)) => (old, new),
+        (None, None) => return vec![],
+        _ => {
+            return vec![
+                Property::Devices,
+                Property::Pages,
+                Property::Brightness,
+                Property::Pinned,
+            ]
+        }
+    };
+    let mut properties = Vec::new();
+    if old.page != new.page {
+        properties.push(Property::Pages);
+    }
+    if old.brightness != new.brightness {
+        properties.push(Property::Brightness);
+    }
+    if old.pinned != new.pinned {
+        properties.push(Property::Pinned);
+    }
+    properties
+}
+
+/// Announces the properties that changed
+fn notify(properties: Vec<Property>) {
+    if let Some(changes) = CHANGES.lock().unwrap().as_ref() {
+        for property in properties {
+            let _ = changes.send(property);
+        }
+    }
+}
+
+/// Changes what is published of the device `sn`, adding it when it is new
+pub fn update(sn: &str, change: impl FnOnce(&mut DeviceStatus)) {
+    let properties = {
+        let mut devices = DEVICES.lock().unwrap();
+        let old = devices.get(sn).cloned();
+        let status = devices.entry(sn.to_string()).or_default();
+        change(status);
+        changed(old.as_ref(), Some(status))
+    };
+    notify(properties);
+}
+
+/// Stops publishing the device `sn`
+pub fn remove(sn: &str) {
+    let old = DEVICES.lock().unwrap().remove(sn);
+    notify(changed(old.as_ref(), None));
+}
+
+/// The published devices, by serial
+pub fn devices() -> Vec<(String, DeviceStatus)> {
+    DEVICES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(sn, status)| (sn.clone(), status.clone()))
+        .collect()
+}
+
+/// One property of every device
+#[cfg(target_os = "linux")]
+fn by_device<T>(value: impl Fn(&DeviceStatus) -> T) -> std::collections::HashMap<String, T> {
+    DEVICES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(sn, status)| (sn.clone(), value(status)))
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+mod service {
+    use super::*;
+    use crate::{runtime, verbose_log, warn_log};
+    use std::collections::HashMap;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    const BUS_NAME: &str = "onl.ycode.keydeck.State";
+    const OBJECT_PATH: &str = "/onl/ycode/keydeck/State";
+
+    /// The properties, read from the published devices
+    struct StateInterface;
+
+    #[zbus::interface(name = "onl.ycode.keydeck.State")]
+    impl StateInterface {
+        #[zbus(property)]
+        fn devices(&self) -> Vec<String> {
+            DEVICES.lock().unwrap().keys().cloned().collect()
+        }
+
+        #[zbus(property)]
+        fn pages(&self) -> HashMap<String, String> {
+            by_device(|status| status.page.clone())
+        }
+
+        #[zbus(property)]
+        fn brightness(&self) -> HashMap<String, u8> {
+            by_device(|status| status.brightness)
+        }
+
+        #[zbus(property)]
+        fn pinned(&self) -> HashMap<String, bool> {
+            by_device(|status| status.pinned)
+        }
+    }
+
+    /// Serves the properties on the session bus and announces their changes
+    pub fn serve() {
+        let (changes, received) = tokio::sync::mpsc::unbounded_channel();
+        CHANGES.lock().unwrap().replace(changes);
+        runtime::spawn(move |_| async move {
+            if let Err(e) = announce(received).await {
+                warn_log!("State is not published on D-Bus: {}", e);
+            }
+        });
+    }
+
+    async fn announce(mut changes: UnboundedReceiver<Property>) -> Result<(), String> {
+        let conn = zbus::connection::Builder::session()
+            .and_then(|builder| builder.name(BUS_NAME))
+            .and_then(|builder| builder.serve_at(OBJECT_PATH, StateInterface))
+            .map_err(|e| format!("Failed to set up the session bus connection: {}", e))?
+            .build()
+            .await
+            .map_err(|e| format!("Failed to connect to the session bus: {}", e))?;
+        let iface = conn
+            .object_server()
+            .interface::<_, StateInterface>(OBJECT_PATH)
+            .await
+            .map_err(|e| format!("Failed to find the state interface: {}", e))?;
+        let emitter = iface.signal_emitter();
+        while let Some(property) = changes.recv().await {
+            let result = match property {
+                Property::Devices => StateInterface.devices_changed(emitter).await,
+                Property::Pages => StateInterface.pages_changed(emitter).await,
+                Property::Brightness => StateInterface.brightness_changed(emitter).await,
+                Property::Pinned => StateInterface.pinned_changed(emitter).await,
+            };
+            if let Err(e) = result {
+                verbose_log!("Failed to announce the change of {:?}: {}", property, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use service::serve;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changed_properties_are_announced() {
+        let shown = DeviceStatus {
+            page: "Main".to_string(),
+            brightness: 80,
+            pinned: false,
+            ..Default::default()
+        };
+        let pinned = DeviceStatus {
+            pinned: true,
+            ..shown.clone()
+        };
+        assert_eq!(changed(Some(&shown), Some(&shown)), []);
+        assert_eq!(changed(Some(&shown), Some(&pinned)), [Property::Pinned]);
+        assert_eq!(changed(None, Some(&shown)).len(), 4);
+        assert_eq!(changed(Some(&shown), None)[0], Property::Devices);
+        assert_eq!(changed(None, None), []);
+    }
+}
@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Whether the connected decks can be used, for `keydeck --check-devices`.
+//!
+//! Installation scripts and the onboarding of the configuration UI branch on the exit
+//! code instead of parsing `--list`: every supported device found is opened once, and
+//! one JSON object lists them with their serials. The devices are only opened, nothing
+//! is sent to them, so this works while the daemon is running.
+
+use crate::device_manager::new_hidapi_configured;
+use crate::elgato_device::ElgatoDevice;
+use crate::mirajazz_device::MirajazzDevice;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::ffi::CString;
+
+/// Exit code when all devices found can be opened
+pub const EXIT_READY: i32 = 0;
+/// Exit code when the HID devices could not be listed at all
+pub const EXIT_ERROR: i32 = 1;
+/// Exit code when no supported device is connected
+pub const EXIT_NO_DEVICES: i32 = 2;
+/// Exit code when a device is connected but cannot be opened, usually for lack of
+/// permission (missing udev rules on Linux)
+pub const EXIT_NO_PERMISSION: i32 = 3;
+
+/// Outcome of the check
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ready,
+    Error,
+    NoDevices,
+    PermissionDenied,
+}
+
+impl Status {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Status::Ready => EXIT_READY,
+            Status::Error => EXIT_ERROR,
+            Status::NoDevices => EXIT_NO_DEVICES,
+            Status::PermissionDenied => EXIT_NO_PERMISSION,
+        }
+    }
+}
+
+/// A supported device found on the system
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckedDevice {
+    /// Serial the configuration refers to the device by
+    pub serial: String,
+    /// `VID:PID`, as `keydeck --list` shows it
+    pub device_id: String,
+    pub model: String,
+    /// Whether it could be opened
+    pub accessible: bool,
+    /// Why it could not be opened
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What `keydeck --check-devices` prints
+#[derive(Debug, Serialize)]
+pub struct DeviceCheck {
+    pub status: Status,
+    pub devices: Vec<CheckedDevice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Status of a check that found `devices`
+fn status(devices: &[CheckedDevice]) -> Status {
+    if devices.is_empty() {
+        Status::NoDevices
+    } else if devices.iter().all(|device| device.accessible) {
+        Status::Ready
+    } else {
+        Status::PermissionDenied
+    }
+}
+
+/// Finds the supported devices and tries to open each of them
+pub fn check_devices() -> DeviceCheck {
+    let hidapi = match new_hidapi_configured() {
+        Ok(hidapi) => hidapi,
+        Err(e) => {
+            return DeviceCheck {
+                status: Status::Error,
+                devices: vec![],
+                error: Some(format!("Failed to list HID devices: {}", e)),
+            }
+        }
+    };
+
+    // A device has several HID interfaces; it is usable when one of them opens
+    let mut interfaces: BTreeMap<(u16, u16, String), (String, Vec<CString>)> = BTreeMap::new();
+    for info in hidapi.device_list() {
+        let (vid, pid) = (info.vendor_id(), info.product_id());
+        if !MirajazzDevice::is_supported(vid, pid) && !ElgatoDevice::is_supported(vid, pid) {
+            continue;
+        }
+        let serial = info.serial_number().unwrap_or_default().to_string();
+        let (_, paths) = interfaces.entry((vid, pid, serial)).or_insert_with(|| {
+            let product = info.product_string().unwrap_or_default().to_string();
+            (product, vec![])
+        });
+        paths.push(info.path().to_owned());
+    }
+
+    let devices: Vec<CheckedDevice> = interfaces
+        .into_iter()
+        .map(|((vid, pid, usb_serial), (product, paths))| {
+            let mut error = None;
+            let accessible = paths.iter().any(|path| match hidapi.open_path(path) {
+                Ok(_) => true,
+                Err(e) => {
+                    error = Some(e.to_string());
+                    false
+                }
+            });
+            let (serial, model) =
+                MirajazzDevice::identify(vid, pid, &usb_serial).unwrap_or((usb_serial, product));
+            CheckedDevice {
+                serial,
+                device_id: format!("{:04X}:{:04X}", vid, pid),
+                model,
+                accessible,
+                error: error.filter(|_| !accessible),
+            }
+        })
+        .collect();
+    DeviceCheck {
+        status: status(&devices),
+        devices,
+        error: None,
+    }
+}
+
+/// Prints the check as JSON and exits with the code of its status
+pub fn print_and_exit() -> ! {
+    let check = check_devices();
+    match serde_json::to_string_pretty(&check) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Error: failed to serialize the device check: {}", e),
+    }
+    std::process::exit(check.status.exit_code());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(accessible: bool) -> CheckedDevice {
+        CheckedDevice {
+            serial: "CL12345".to_string(),
+            device_id: "0FD9:006C".to_string(),
+            model: "Stream Deck XL".to_string(),
+            accessible,
+            error: (!accessible).then(|| "Permission denied".to_string()),
+        }
+    }
+
+    #[test]
+    fn status_follows_the_devices() {
+        assert_eq!(status(&[]), Status::NoDevices);
+        assert_eq!(status(&[device(true)]), Status::Ready);
+        assert_eq!(
+            status(&[device(true), device(false)]),
+            Status::PermissionDenied
+        );
+        assert_eq!(Status::NoDevices.exit_code(), EXIT_NO_DEVICES);
+        let json = serde_json::to_value(DeviceCheck {
+            status: Status::PermissionDenied,
+            devices: vec![device(false)],
+            error: None,
+        })
+        .unwrap();
+        assert_eq!(json["status"], "permission_denied");
+        assert_eq!(json["devices"][0]["serial"], "CL12345");
+        assert!(json.get("error").is_none());
+    }
+}
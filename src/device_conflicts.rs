@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Other software driving the decks, such as OpenDeck or streamdeck-ui.
+//!
+//! Two programs sending images to the same deck fight over its keys, and the second one
+//! to open it often only gets a bare hidapi error. So the daemon looks for such software
+//! when it starts and names it: every process running a known deck program, and on Linux
+//! every process holding the HID device of a deck open, whatever it is. With
+//! `wait_for_release` it waits for them to quit before opening the decks.
+
+use crate::elgato_device::ElgatoDevice;
+use crate::mirajazz_device::MirajazzDevice;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+/// Programs that drive decks, with the names their processes and scripts run as.
+/// The official software only runs under Wine on Linux, as `StreamDeck.exe`.
+const KNOWN_SOFTWARE: &[(&str, &[&str])] = &[
+    ("OpenDeck", &["opendeck"]),
+    (
+        "streamdeck-ui",
+        &["streamdeck", "streamdeck-ui", "streamdeck_ui"],
+    ),
+    ("StreamController", &["streamcontroller"]),
+    ("Boatswain", &["boatswain"]),
+    ("deckmaster", &["deckmaster"]),
+    ("Elgato Stream Deck", &["streamdeck.exe"]),
+];
+
+/// How often the processes are looked at again while waiting for them to quit
+const WAIT_STEP: Duration = Duration::from_secs(1);
+
+/// A process that drives, or may drive, the decks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub pid: u32,
+    /// The program, or the process name when it is not a known one
+    pub name: String,
+    /// Serials of the decks it holds open; only known on Linux
+    pub decks: Vec<String>,
+}
+
+impl Conflict {
+    pub fn describe(&self) -> String {
+        if self.decks.is_empty() {
+            format!(
+                "{} (pid {}) is running and may drive the decks",
+                self.name, self.pid
+            )
+        } else {
+            format!(
+                "{} (pid {}) holds deck {} open",
+                self.name,
+                self.pid,
+                self.decks.join(", ")
+            )
+        }
+    }
+}
+
+/// The known program a process is running, from its name and its command line, so that
+/// scripts run by an interpreter (`python3 /usr/bin/streamdeck`) are found too
+fn known_software<'a>(name: &str, args: impl IntoIterator<Item = &'a str>) -> Option<&'static str> {
+    let program = |path: &str| {
+        let base = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        base.to_lowercase()
+    };
+    // The program, and the script an interpreter runs, as in `python3 -m streamdeck_ui`
+    let mut args = args.into_iter();
+    let mut names = vec![program(name)];
+    names.extend(args.next().map(program));
+    names.extend(args.find(|arg| !arg.starts_with('-')).map(program));
+    KNOWN_SOFTWARE
+        .iter()
+        .find(|(_, processes)| names.iter().any(|name| processes.contains(&name.as_str())))
+        .map(|(software, _)| *software)
+}
+
+/// HID device paths of the connected decks, with the serial of the deck each belongs to
+fn deck_paths() -> HashMap<String, String> {
+    // Only listed, never opened
+    let Ok(hidapi) = hidapi::HidApi::new() else {
+        return HashMap::new();
+    };
+    hidapi
+        .device_list()
+        .filter(|info| {
+            let (vid, pid) = (info.vendor_id(), info.product_id());
+            MirajazzDevice::is_supported(vid, pid) || ElgatoDevice::is_supported(vid, pid)
+        })
+        .map(|info| {
+            let usb_serial = info.serial_number().unwrap_or_default();
+            let serial = MirajazzDevice::identify(info.vendor_id(), info.product_id(), usb_serial)
+                .map_or_else(|| usb_serial.to_string(), |(serial, _)| serial);
+            (info.path().to_string_lossy().into_owned(), serial)
+        })
+        .collect()
+}
+
+/// Serials of the decks the process `pid` holds open, read from its open files. Only
+/// processes of the same user (or all of them, as root) can be looked into.
+#[cfg(target_os = "linux")]
+fn decks_held(pid: u32, paths: &HashMap<String, String>) -> Vec<String> {
+    let Ok(files) = std::fs::read_dir(format!("/proc/{}/fd", pid)) else {
+        return vec![];
+    };
+    let mut decks: Vec<String> = files
+        .flatten()
+        .filter_map(|file| std::fs::read_link(file.path()).ok())
+        .filter_map(|target| paths.get(target.to_string_lossy().as_ref()).cloned())
+        .collect();
+    decks.sort();
+    decks.dedup();
+    decks
+}
+
+#[cfg(not(target_os = "linux"))]
+fn decks_held(_pid: u32, _paths: &HashMap<String, String>) -> Vec<String> {
+    vec![]
+}
+
+/// Other processes running known deck software or holding a deck open
+pub fn find_conflicts() -> Vec<Conflict> {
+    let paths = deck_paths();
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing()
+            .with_cmd(UpdateKind::OnlyIfNotSet)
+            .without_tasks(),
+    );
+    let own = std::process::id();
+    let mut conflicts: Vec<Conflict> = system
+        .processes()
+        .values()
+        // Threads are listed as processes of their own on Linux
+        .filter(|process| process.thread_kind().is_none() && process.pid().as_u32() != own)
+        .filter_map(|process| {
+            let pid = process.pid().as_u32();
+            let process_name = process.name().to_string_lossy();
+            let args: Vec<_> = process
+                .cmd()
+                .iter()
+                .map(|arg| arg.to_string_lossy())
+                .collect();
+            let known = known_software(&process_name, args.iter().map(|arg| arg.as_ref()));
+            let decks = decks_held(pid, &paths);
+            if known.is_none() && decks.is_empty() {
+                return None;
+            }
+            Some(Conflict {
+                pid,
+                name: known.map_or_else(|| process_name.into_owned(), str::to_string),
+                decks,
+            })
+        })
+        .collect();
+    conflicts.sort_by_key(|conflict| conflict.pid);
+    conflicts
+}
+
+/// Why the deck `serial` may not open, for the error of a failed open
+pub fn holders_of(serial: &str) -> Vec<String> {
+    find_conflicts()
+        .into_iter()
+        .filter(|conflict| {
+            conflict.decks.is_empty() || conflict.decks.iter().any(|deck| deck == serial)
+        })
+        .map(|conflict| conflict.describe())
+        .collect()
+}
+
+/// Warns about the other software driving the decks when the daemon starts and, with
+/// `wait` seconds, waits up to that long for it to quit
+pub fn check_at_start(wait: Option<f32>) {
+    let conflicts = find_conflicts();
+    if conflicts.is_empty() {
+        return;
+    }
+    for conflict in &conflicts {
+        crate::warn_log!(
+            "{}; quit it, or it and KeyDeck fight over the keys",
+            conflict.describe()
+        );
+    }
+    let Some(wait) = wait.filter(|wait| *wait > 0.0) else {
+        return;
+    };
+    crate::info_log!("Waiting up to {}s for the decks to be released", wait);
+    let deadline = Instant::now() + Duration::from_secs_f32(wait);
+    while Instant::now() < deadline {
+        std::thread::sleep(WAIT_STEP);
+        if find_conflicts().is_empty() {
+            crate::info_log!("The decks were released");
+            return;
+        }
+    }
+    crate::warn_log!(
+        "The decks were not released in {}s; opening them anyway",
+        wait
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deck_software_is_recognized_by_process_or_script() {
+        assert_eq!(known_software("opendeck", []), Some("OpenDeck"));
+        assert_eq!(
+            known_software("python3", ["/usr/bin/python3", "/usr/bin/streamdeck"]),
+            Some("streamdeck-ui")
+        );
+        assert_eq!(
+            known_software("python3", ["python3", "-m", "streamdeck_ui"]),
+            Some("streamdeck-ui")
+        );
+        assert_eq!(
+            known_software(
+                "wine64-preloader",
+                ["C:\\Program Files\\Elgato\\StreamDeck\\StreamDeck.exe"]
+            ),
+            Some("Elgato Stream Deck")
+        );
+        // Only the program and the script it runs count, not files it is given
+        assert_eq!(
+            known_software("vim", ["vim", "notes.md", "streamdeck"]),
+            None
+        );
+        assert_eq!(known_software("keydeck", ["keydeck", "--server"]), None);
+    }
+}
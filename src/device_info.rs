@@ -2,4 +2,4 @@
 // Copyright (C) 2025 Panayotis Katsaloulis
 
 // Re-export device info types from keydeck-types
-pub use keydeck_types::{ButtonImage, ButtonLayout, DeviceInfo};
+pub use keydeck_types::{ButtonImage, ButtonLayout, DeviceInfo, LcdStrip};
@@ -1,14 +1,15 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
-use crate::device_info::{ButtonImage, ButtonLayout, DeviceInfo};
+use crate::device_info::{ButtonImage, ButtonLayout, DeviceInfo, LcdStrip};
 use crate::device_trait::{DeviceError, DeviceReader, KeydeckDevice};
 use crate::elgato_device::ElgatoDevice;
+use crate::error::Error;
 use crate::mirajazz_device::MirajazzDevice;
 use crate::{error_log, info_log, verbose_log};
 use elgato_streamdeck::{list_devices, new_hidapi};
 use image::DynamicImage;
-use std::path::Path;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 /// Creates a `HidApi` context configured for the current platform.
@@ -17,7 +18,7 @@ use std::sync::Arc;
 /// fails for Stream Deck / Mirabox controllers because the OS already holds an
 /// open handle on their keyboard/consumer HID interface ("exclusive access and
 /// device already open"). Opening non-exclusively fixes this. No-op elsewhere.
-fn new_hidapi_configured() -> hidapi::HidResult<hidapi::HidApi> {
+pub fn new_hidapi_configured() -> hidapi::HidResult<hidapi::HidApi> {
     let api = new_hidapi()?;
     #[cfg(target_os = "macos")]
     api.set_open_exclusive(false);
@@ -198,6 +199,27 @@ impl KeydeckDevice for Device {
         }
     }
 
+    fn lcd_strip_size(&self) -> Option<(u16, u16)> {
+        match self {
+            Device::Elgato(d) => d.lcd_strip_size(),
+            Device::Mirajazz(d) => d.lcd_strip_size(),
+        }
+    }
+
+    fn write_lcd(
+        &self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        image: &DynamicImage,
+    ) -> Result<(), DeviceError> {
+        match self {
+            Device::Elgato(d) => KeydeckDevice::write_lcd(d, x, y, width, height, image),
+            Device::Mirajazz(d) => d.write_lcd(x, y, width, height, image),
+        }
+    }
+
     fn background_image_size(&self) -> Option<(u16, u16)> {
         match self {
             Device::Elgato(d) => d.background_image_size(),
@@ -360,6 +382,29 @@ impl DeviceManager {
         serials
     }
 
+    /// Key image size of each connected device with a screen, by serial. The devices are
+    /// not opened, so this works while the daemon drives them.
+    pub fn connected_key_sizes() -> HashMap<String, (u32, u32)> {
+        let mut sizes = HashMap::new();
+        let Ok(hidapi) = new_hidapi_configured() else {
+            return sizes;
+        };
+        for device_info in hidapi.device_list() {
+            let size =
+                MirajazzDevice::key_image_size(device_info.vendor_id(), device_info.product_id());
+            if let (Some(size), Some(serial)) = (size, device_info.serial_number()) {
+                sizes.insert(serial.to_string(), size);
+            }
+        }
+        for (kind, serial) in list_devices(&hidapi) {
+            let (width, height) = kind.key_image_format().size;
+            if width > 0 && height > 0 {
+                sizes.entry(serial).or_insert((width as u32, height as u32));
+            }
+        }
+        sizes
+    }
+
     pub fn list_devices(&mut self) {
         for device in self.iter_active_devices() {
             println!(
@@ -372,7 +417,7 @@ impl DeviceManager {
         println!("Total devices: {}", self.count_active_devices());
     }
 
-    pub fn info_device(&mut self, identifier: String) -> Result<(), String> {
+    pub fn info_device(&mut self, identifier: String) -> Result<(), Error> {
         for device in &mut self.devices {
             if device.device_id() == identifier || device.serial().trim() == identifier {
                 let button_count = device.button_count();
@@ -398,11 +443,15 @@ impl DeviceManager {
                     button_image: ButtonImage {
                         width: img_width as usize,
                         height: img_height as usize,
-                        format: "JPEG".to_string(), // TODO: Query actual image format from device
+                        // TODO: Query actual image format from device
+                        format: if device.has_screen() { "JPEG" } else { "none" }.to_string(),
                     },
                     encoders: encoders as u8,
                     touchpoints: 0,  // TODO: Add touchpoint support to KeydeckDevice trait
-                    lcd_strip: None, // TODO: Report LCD strip dimensions from devices that support it
+                    lcd_strip: device.lcd_strip_size().map(|(width, height)| LcdStrip {
+                        width: width as usize,
+                        height: height as usize,
+                    }),
                     is_visual: device.has_screen(),
                 };
 
@@ -411,11 +460,22 @@ impl DeviceManager {
                         print!("{}", yaml);
                         return Ok(());
                     }
-                    Err(e) => return Err(format!("Failed to serialize device info: {}", e)),
+                    Err(e) => {
+                        return Err(Error::Device {
+                            serial: identifier,
+                            source: DeviceError::LibraryError(format!(
+                                "Failed to serialize device info: {}",
+                                e
+                            )),
+                        })
+                    }
                 }
             }
         }
-        Err(format!("Device with id '{}' not found", identifier))
+        Err(Error::Device {
+            serial: identifier,
+            source: DeviceError::NotFound,
+        })
     }
 
     fn count_active_devices(&self) -> usize {
@@ -433,23 +493,6 @@ impl DeviceManager {
     }
 }
 
-pub fn find_path(file: &str, dir: Option<String>) -> Option<String> {
-    if Path::new(file).exists() {
-        Some(file.to_string())
-    } else {
-        let other_path = format!(
-            "{}/{}",
-            dir.clone().unwrap_or_else(|| ".".to_string()),
-            file.replace("\\", "/")
-        );
-        if Path::new(&other_path).exists() {
-            Some(other_path)
-        } else {
-            None
-        }
-    }
-}
-
 pub fn find_device_by_serial(device_sn: &str) -> Option<Device> {
     let hidapi = match new_hidapi_configured().ok() {
         Some(api) => Arc::new(api),
@@ -0,0 +1,319 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Workarounds for devices whose firmware misbehaves (`quirks` in the configuration).
+//!
+//! A [`QuirkedDevice`] sits between the device and the rest of the daemon: it flushes
+//! after every key image, spaces key images out, reshapes the brightness, drops the
+//! first input events and sends keep-alive messages at its own pace, as configured for
+//! the serial number of the device. Devices without quirks are used as they are, and
+//! get a keep-alive message on every tick.
+
+use crate::device_trait::{DeviceError, DeviceReader, DeviceStateUpdate, KeydeckDevice};
+use image::DynamicImage;
+use keydeck_types::pages::{DeviceQuirks, KeepAlive};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A device with workarounds for its firmware bugs
+pub struct QuirkedDevice {
+    inner: Arc<dyn KeydeckDevice>,
+    quirks: DeviceQuirks,
+    /// When the last key image was sent, to space them out
+    last_image: Mutex<Option<Instant>>,
+    /// Input events still to be ignored, shared with the readers
+    ignored_events: Arc<AtomicU32>,
+}
+
+impl QuirkedDevice {
+    /// `device` with the given quirks, or as it is when there are none
+    pub fn wrap(
+        device: Box<dyn KeydeckDevice>,
+        quirks: Option<&DeviceQuirks>,
+    ) -> Box<dyn KeydeckDevice> {
+        match quirks.filter(|quirks| **quirks != DeviceQuirks::default()) {
+            Some(quirks) => {
+                let inner: Arc<dyn KeydeckDevice> = Arc::from(device);
+                if let Some(KeepAlive::Every(seconds)) = quirks.keep_alive {
+                    let device = Arc::downgrade(&inner);
+                    let interval = Duration::from_secs_f32(seconds);
+                    thread::spawn(move || keep_alive_on(device, every(interval)));
+                }
+                Box::new(QuirkedDevice {
+                    inner,
+                    quirks: quirks.clone(),
+                    last_image: Mutex::new(None),
+                    ignored_events: Arc::new(AtomicU32::new(
+                        quirks.ignore_first_events.unwrap_or(0),
+                    )),
+                })
+            }
+            None => device,
+        }
+    }
+
+    /// Sends a key image with `send`, waiting for `image_delay` since the previous one
+    /// first and flushing after it when configured
+    fn send_image(
+        &self,
+        send: impl FnOnce() -> Result<(), DeviceError>,
+    ) -> Result<(), DeviceError> {
+        if let Some(delay) = self.quirks.image_delay.filter(|delay| *delay > 0) {
+            let mut last_image = self.last_image.lock().unwrap();
+            if let Some(elapsed) = last_image.map(|last| last.elapsed()) {
+                std::thread::sleep(Duration::from_millis(delay).saturating_sub(elapsed));
+            }
+            let result = send();
+            *last_image = Some(Instant::now());
+            result?;
+        } else {
+            send()?;
+        }
+        if self.quirks.flush_after_image {
+            self.inner.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// A clock ticking every `interval`
+fn every(interval: Duration) -> impl Iterator<Item = ()> {
+    std::iter::repeat_with(move || thread::sleep(interval))
+}
+
+/// Sends a keep-alive message to `device` on every tick of `clock`, until it is dropped
+fn keep_alive_on(device: Weak<dyn KeydeckDevice>, clock: impl Iterator<Item = ()>) {
+    for () in clock {
+        match device.upgrade() {
+            Some(device) => device.keep_alive(),
+            None => return,
+        }
+    }
+}
+
+/// Reader dropping the first input events of a device
+struct QuirkedReader {
+    inner: Arc<dyn DeviceReader>,
+    ignored_events: Arc<AtomicU32>,
+}
+
+impl DeviceReader for QuirkedReader {
+    fn read(&self, timeout: Option<Duration>) -> Result<Vec<DeviceStateUpdate>, DeviceError> {
+        let mut updates = self.inner.read(timeout)?;
+        skip_events(&mut updates, &self.ignored_events);
+        Ok(updates)
+    }
+}
+
+/// Removes the first events of `updates` while `ignored` is not exhausted
+fn skip_events(updates: &mut Vec<DeviceStateUpdate>, ignored: &AtomicU32) {
+    let remaining = ignored.load(Ordering::Relaxed);
+    if remaining == 0 {
+        return;
+    }
+    let skipped = updates.len().min(remaining as usize);
+    updates.drain(..skipped);
+    ignored.fetch_sub(skipped as u32, Ordering::Relaxed);
+}
+
+impl KeydeckDevice for QuirkedDevice {
+    fn serial_number(&self) -> Result<String, DeviceError> {
+        self.inner.serial_number()
+    }
+
+    fn firmware_version(&self) -> Result<String, DeviceError> {
+        self.inner.firmware_version()
+    }
+
+    fn manufacturer(&self) -> String {
+        self.inner.manufacturer()
+    }
+
+    fn kind_name(&self) -> String {
+        self.inner.kind_name()
+    }
+
+    fn button_count(&self) -> u8 {
+        self.inner.button_count()
+    }
+
+    fn has_screen(&self) -> bool {
+        self.inner.has_screen()
+    }
+
+    fn button_image_size(&self) -> (u16, u16) {
+        self.inner.button_image_size()
+    }
+
+    fn button_layout(&self) -> (usize, usize) {
+        self.inner.button_layout()
+    }
+
+    fn encoder_count(&self) -> usize {
+        self.inner.encoder_count()
+    }
+
+    fn reset(&self) -> Result<(), DeviceError> {
+        self.inner.reset()
+    }
+
+    fn set_brightness(&self, brightness: u8) -> Result<(), DeviceError> {
+        self.inner
+            .set_brightness(self.quirks.scale_brightness(brightness))
+    }
+
+    fn set_button_image(&self, button_idx: u8, image: DynamicImage) -> Result<(), DeviceError> {
+        self.send_image(|| self.inner.set_button_image(button_idx, image))
+    }
+
+    fn clear_button_image(&self, button_idx: u8) -> Result<(), DeviceError> {
+        self.send_image(|| self.inner.clear_button_image(button_idx))
+    }
+
+    fn clear_all_button_images(&self) -> Result<(), DeviceError> {
+        self.inner.clear_all_button_images()
+    }
+
+    fn flush(&self) -> Result<(), DeviceError> {
+        self.inner.flush()
+    }
+
+    fn get_reader(&self) -> Arc<dyn DeviceReader> {
+        Arc::new(QuirkedReader {
+            inner: self.inner.get_reader(),
+            ignored_events: self.ignored_events.clone(),
+        })
+    }
+
+    fn supports_button_press_feedback(&self) -> bool {
+        self.inner.supports_button_press_feedback()
+    }
+
+    fn shutdown(&self) -> Result<(), DeviceError> {
+        self.inner.shutdown()
+    }
+
+    fn sleep(&self) -> Result<(), DeviceError> {
+        self.inner.sleep()
+    }
+
+    fn keep_alive(&self) {
+        // Sent at the pace of `keep_alive` instead, if at all
+        if self.quirks.keep_alive.is_none() {
+            self.inner.keep_alive()
+        }
+    }
+
+    fn lcd_strip_size(&self) -> Option<(u16, u16)> {
+        self.inner.lcd_strip_size()
+    }
+
+    fn write_lcd_fill(&self, x: u16, y: u16, image: &DynamicImage) -> Result<(), DeviceError> {
+        self.inner.write_lcd_fill(x, y, image)
+    }
+
+    fn write_lcd(
+        &self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        image: &DynamicImage,
+    ) -> Result<(), DeviceError> {
+        self.inner.write_lcd(x, y, width, height, image)
+    }
+
+    fn background_image_size(&self) -> Option<(u16, u16)> {
+        self.inner.background_image_size()
+    }
+
+    fn set_background_image(&self, image: DynamicImage) -> Result<(), DeviceError> {
+        self.inner.set_background_image(image)
+    }
+
+    fn clear_background_image(&self) -> Result<(), DeviceError> {
+        self.inner.clear_background_image()
+    }
+
+    fn set_boot_logo(&self, image: DynamicImage) -> Result<(), DeviceError> {
+        self.inner.set_boot_logo(image)
+    }
+
+    fn led_count(&self) -> u8 {
+        self.inner.led_count()
+    }
+
+    fn set_led_brightness(&self, brightness: u8) -> Result<(), DeviceError> {
+        self.inner.set_led_brightness(brightness)
+    }
+
+    fn set_led_color(&self, colors: &[(u8, u8, u8)]) -> Result<(), DeviceError> {
+        self.inner.set_led_color(colors)
+    }
+
+    fn reset_led_color(&self) -> Result<(), DeviceError> {
+        self.inner.reset_led_color()
+    }
+
+    fn wakeup(&self) -> Result<(), DeviceError> {
+        self.inner.wakeup()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_device::TestDevice;
+
+    #[test]
+    fn first_events_are_skipped() {
+        let ignored = AtomicU32::new(3);
+        let mut updates = vec![
+            DeviceStateUpdate::ButtonDown(0),
+            DeviceStateUpdate::ButtonUp(0),
+        ];
+        skip_events(&mut updates, &ignored);
+        assert!(updates.is_empty());
+        let mut updates = vec![
+            DeviceStateUpdate::ButtonDown(1),
+            DeviceStateUpdate::ButtonUp(1),
+        ];
+        skip_events(&mut updates, &ignored);
+        assert!(matches!(updates[..], [DeviceStateUpdate::ButtonUp(1)]));
+        assert_eq!(ignored.load(Ordering::Relaxed), 0);
+    }
+
+    /// A device with `quirks`, and the number of keep-alive messages it got
+    fn counted(quirks: &str) -> (Box<dyn KeydeckDevice>, Arc<AtomicU32>) {
+        let device = TestDevice::default();
+        let count = device.keep_alives.clone();
+        let quirks: DeviceQuirks = serde_yaml_ng::from_str(quirks).unwrap();
+        (QuirkedDevice::wrap(Box::new(device), Some(&quirks)), count)
+    }
+
+    #[test]
+    fn keep_alive_is_sent_at_the_pace_of_the_quirk() {
+        // Every tick by default, never when off, at its own interval otherwise
+        let (device, count) = counted("{}");
+        device.keep_alive();
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+        let (device, count) = counted("keep_alive: off");
+        device.keep_alive();
+        assert_eq!(count.load(Ordering::Relaxed), 0);
+        let (device, count) = counted("keep_alive: 60");
+        device.keep_alive();
+        assert_eq!(count.load(Ordering::Relaxed), 0);
+
+        // Once per tick of the clock, until the device is gone
+        let test_device = TestDevice::default();
+        let device: Arc<dyn KeydeckDevice> = Arc::new(test_device.clone());
+        keep_alive_on(Arc::downgrade(&device), std::iter::repeat_n((), 3));
+        assert_eq!(test_device.keep_alives.load(Ordering::Relaxed), 3);
+        let gone = Arc::downgrade(&device);
+        drop(device);
+        keep_alive_on(gone, std::iter::repeat(()));
+        assert_eq!(test_device.keep_alives.load(Ordering::Relaxed), 3);
+    }
+}
@@ -15,6 +15,7 @@ pub enum DeviceError {
     InvalidParameter(String),
     IoError(String),
     LibraryError(String),
+    NotFound,
 }
 
 impl fmt::Display for DeviceError {
@@ -25,6 +26,7 @@ impl fmt::Display for DeviceError {
             DeviceError::InvalidParameter(msg) => write!(f, "Invalid parameter: {}", msg),
             DeviceError::IoError(msg) => write!(f, "I/O error: {}", msg),
             DeviceError::LibraryError(msg) => write!(f, "Library error: {}", msg),
+            DeviceError::NotFound => write!(f, "not found"),
         }
     }
 }
@@ -54,7 +56,7 @@ pub trait DeviceReader: Send + Sync {
 }
 
 /// Device state update events
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum DeviceStateUpdate {
     ButtonDown(u8),
     ButtonUp(u8),
@@ -142,6 +144,10 @@ pub trait KeydeckDevice: Send + Sync {
     /// Get event reader for this device
     fn get_reader(&self) -> Arc<dyn DeviceReader>;
 
+    /// Sends the next image of a key ahead of the others and without waiting for the
+    /// frame rate limit, e.g. the feedback of a pressed key (no-op without a scheduler)
+    fn prioritize_button(&self, _button_idx: u8) {}
+
     /// Whether the device supports separate button down/up events.
     /// When false, button_down visual feedback is skipped (no point sending images).
     fn supports_button_press_feedback(&self) -> bool {
@@ -173,7 +179,12 @@ pub trait KeydeckDevice: Send + Sync {
 
     // === LCD/Display Features ===
 
-    // TODO: Implement LCD strip support for Stream Deck Neo/Plus devices
+    /// Get LCD strip resolution (width, height), or None if the device has no LCD strip
+    fn lcd_strip_size(&self) -> Option<(u16, u16)> {
+        None
+    }
+
+    // TODO: Implement LCD strip support for Stream Deck Neo devices
     /// Write to LCD strip (e.g., Stream Deck Neo/Plus)
     fn write_lcd_fill(&self, _x: u16, _y: u16, _image: &DynamicImage) -> Result<(), DeviceError> {
         warn_log!("write_lcd_fill() not supported on this device");
@@ -1,7 +1,10 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
-use crate::pages::{Action, Button, ButtonConfig, DrawConfig, KeyDeckConf, Macro, TextConfig};
+use crate::pages::{
+    Action, Button, ButtonConfig, ColorConfig, DrawConfig, Enabled, FocusTarget, KeyDeckConf,
+    Macro, TextConfig,
+};
 use indexmap::IndexMap;
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
@@ -48,6 +51,30 @@ fn has_dynamic_in_draw(draw_configs: &Option<Vec<DrawConfig>>) -> bool {
     }
 }
 
+/// Scans the button colors, animation gates and `enabled` condition for dynamic patterns
+fn has_dynamic_in_state(button: &Button) -> bool {
+    state_source_values(button).any(has_dynamic_pattern)
+}
+
+/// Source values of the color-mapped `background`/`text_color`, of the `blink`/`pulse`
+/// gates and of the `enabled` condition of a button
+fn state_source_values(button: &Button) -> impl Iterator<Item = &str> {
+    let mapped = [&button.background, &button.text_color]
+        .into_iter()
+        .filter_map(|color| match color {
+            Some(ColorConfig::Mapped { value, .. }) => Some(value.as_str()),
+            _ => None,
+        });
+    let gates = [&button.blink, &button.pulse]
+        .into_iter()
+        .filter_map(|animation| animation.as_ref()?.value.as_deref());
+    let enabled = match &button.enabled {
+        Some(Enabled::Condition(condition)) => Some(condition.as_str()),
+        _ => None,
+    };
+    mapped.chain(gates).chain(enabled)
+}
+
 /// Scans actions for dynamic patterns (recursively)
 fn has_dynamic_in_actions(
     actions: &[Action],
@@ -61,18 +88,64 @@ fn has_dynamic_in_actions(
                     return true;
                 }
             }
-            Action::Text { text } => {
+            Action::Http {
+                http,
+                headers,
+                body,
+                ..
+            } => {
+                let dynamic = has_dynamic_pattern(http)
+                    || body.as_deref().is_some_and(has_dynamic_pattern)
+                    || headers
+                        .iter()
+                        .flat_map(|headers| headers.values())
+                        .any(|value| has_dynamic_pattern(value));
+                if dynamic {
+                    return true;
+                }
+            }
+            Action::ChatRead { chat_read: text }
+            | Action::JoinMeeting { join_meeting: text }
+            | Action::Open { open: text }
+            | Action::Wol { wol: text, .. }
+            | Action::OpenRgb { openrgb: text }
+            | Action::KbdLayout { kbd_layout: text }
+            | Action::Check { check: text }
+                if has_dynamic_pattern(text) =>
+            {
+                return true;
+            }
+            Action::AudioDevice { audio_device } => {
+                let dynamic = [
+                    &audio_device.set_default_sink,
+                    &audio_device.set_default_source,
+                ]
+                .into_iter()
+                .flatten()
+                .any(|target| has_dynamic_pattern(target));
+                if dynamic {
+                    return true;
+                }
+            }
+            Action::Notify { notify } if has_dynamic_pattern(notify) => return true,
+            Action::Text { text, .. } => {
                 if has_dynamic_pattern(text) {
                     return true;
                 }
             }
-            Action::Key { key } => {
+            Action::Key { key, .. } => {
                 if has_dynamic_pattern(key) {
                     return true;
                 }
             }
-            Action::Focus { focus } => {
-                if has_dynamic_pattern(focus) {
+            Action::Focus { focus, .. } => {
+                let dynamic = match focus {
+                    FocusTarget::Name(name) => has_dynamic_pattern(name),
+                    FocusTarget::Detailed(spec) => [&spec.class, &spec.title, &spec.desktop]
+                        .iter()
+                        .any(|v| v.as_deref().is_some_and(has_dynamic_pattern)),
+                };
+                if dynamic {
                     return true;
                 }
             }
@@ -90,6 +163,36 @@ fn has_dynamic_in_actions(
                     return true;
                 }
             }
+            Action::If {
+                condition,
+                then,
+                else_actions,
+            } => {
+                if has_dynamic_pattern(condition)
+                    || has_dynamic_in_actions(then, macros, visited_macros)
+                {
+                    return true;
+                }
+                if let Some(else_acts) = else_actions {
+                    if has_dynamic_in_actions(else_acts, macros, visited_macros) {
+                        return true;
+                    }
+                }
+            }
+            Action::Confirm {
+                confirm_actions,
+                cancel_actions,
+                ..
+            } => {
+                if has_dynamic_in_actions(confirm_actions, macros, visited_macros) {
+                    return true;
+                }
+                if let Some(cancel_acts) = cancel_actions {
+                    if has_dynamic_in_actions(cancel_acts, macros, visited_macros) {
+                        return true;
+                    }
+                }
+            }
             Action::Try {
                 try_actions,
                 else_actions,
@@ -118,6 +221,11 @@ fn has_dynamic_in_actions(
                     return true;
                 }
             }
+            Action::Random { random_actions }
+                if has_dynamic_in_actions(random_actions, macros, visited_macros) =>
+            {
+                return true;
+            }
             // Other actions (Jump, AutoJump, Wait, WaitFor, Return, Fail, Refresh) don't have dynamic content
             _ => {}
         }
@@ -257,6 +365,11 @@ pub fn is_button_dynamic(button: &Button, macros: &Option<IndexMap<String, Macro
         return true;
     }
 
+    // Check color-mapped background and text color, animation gates and enabled state
+    if has_dynamic_in_state(button) {
+        return true;
+    }
+
     // Check actions
     if let Some(actions) = &button.actions {
         let mut visited_macros = HashSet::new();
@@ -269,6 +382,23 @@ pub fn is_button_dynamic(button: &Button, macros: &Option<IndexMap<String, Macro
     false
 }
 
+/// Checks whether the button's rendered content (text or draw values) references the
+/// given provider, e.g. `uses_provider(button, "focus")` for `${focus:title}`.
+pub fn uses_provider(button: &Button, provider: &str) -> bool {
+    let needle = format!("${{{}:", provider);
+    let in_text = match &button.text {
+        Some(TextConfig::Simple(s)) => s.contains(&needle),
+        Some(TextConfig::Detailed { value, .. }) => value.contains(&needle),
+        None => false,
+    };
+    in_text
+        || button
+            .draw
+            .as_ref()
+            .is_some_and(|draws| draws.iter().any(|d| d.value.contains(&needle)))
+        || state_source_values(button).any(|value| value.contains(&needle))
+}
+
 /// Computes and sets the is_dynamic_computed field for all buttons in the configuration.
 /// Called once during configuration load, after template inheritance is resolved.
 pub fn compute_all_dynamic_flags(conf: &mut KeyDeckConf) {
@@ -284,13 +414,23 @@ pub fn compute_all_dynamic_flags(conf: &mut KeyDeckConf) {
     // Second pass: Scan all page buttons (after template resolution)
     for (_, pages) in &mut conf.page_groups {
         for (_, page) in &mut pages.pages {
-            for (_, button_config) in &mut page.buttons {
+            for (_, button_config) in page.all_buttons_mut() {
                 if let ButtonConfig::Detailed(button) = button_config {
                     button.is_dynamic_computed = is_button_dynamic(button, &macros);
                 }
                 // ButtonConfig::Template references will use the computed value
                 // from the button definition (computed in first pass above)
             }
+            if let Some(generator) = &mut page.generate {
+                for button in generator.buttons.iter_mut() {
+                    button.is_dynamic_computed = is_button_dynamic(button, &macros);
+                }
+            }
+            if let Some(provider) = &mut page.provider {
+                for button in provider.buttons.iter_mut() {
+                    button.is_dynamic_computed = is_button_dynamic(button, &macros);
+                }
+            }
         }
     }
 }
@@ -331,6 +471,7 @@ mod tests {
         let text_detailed = Some(TextConfig::Detailed {
             value: "${time:%H:%M}".to_string(),
             font_size: Some(20.0),
+            marquee: None,
         });
         assert!(has_dynamic_in_text(&text_detailed));
 
@@ -1,9 +1,14 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
+use crate::calendar;
+use crate::chats;
 use crate::context::ContextVars;
+use crate::i18n::Translations;
+use crate::locale_format;
 use crate::pages::ServiceConfig;
 use crate::services::{ensure_service_started, get_service_value, ServicesState};
+use crate::stats::{format_dwell, DeviceStats};
 use crate::system_info::get_system_value;
 use chrono::Local;
 use indexmap::IndexMap;
@@ -11,18 +16,61 @@ use regex::Regex;
 use std::collections::HashMap;
 use std::env;
 use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock, Mutex};
 
 /// Error indicator displayed when dynamic parameter evaluation fails
 pub const ERROR_INDICATOR: &str = "⚠";
 
+/// Values known only to the device evaluating the parameters
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LocalParams<'a> {
+    /// Class of the currently focused window
+    pub focus_class: &'a str,
+    /// Title of the currently focused window
+    pub focus_title: &'a str,
+    /// Name of the page currently shown on the device
+    pub page: &'a str,
+    /// Serial number of the device
+    pub serial: &'a str,
+    /// Model name of the device
+    pub model: &'a str,
+    /// Usage statistics of the device, if it was used before
+    pub stats: Option<&'a DeviceStats>,
+    /// The shown page is pinned against focus changes
+    pub pinned: bool,
+    /// Translations of the configured locale
+    pub strings: Option<&'a Translations>,
+}
+
 /// Evaluates all dynamic parameters in a string and returns a map of parameter -> value.
-/// Supports five provider types:
+/// Supports twenty-three provider types:
 /// - ${time:FORMAT} - Current time using strftime format
 /// - ${env:VAR} - Environment variable
 /// - ${service:NAME} - Cached service value
 /// - ${system:METRIC} - Built-in system metrics (CPU, RAM, temperatures)
 /// - ${var:NAME} - External context variable (set via `keydeck --set`)
+/// - ${audio:volume|muted[:SINK]} - Audio sink volume / mute state
+/// - ${audio:default_sink|default_source} - Description of the default output / input
+/// - ${kbd:layout} - The active keyboard layout
+/// - ${focus:class|title} - The currently focused window
+/// - ${page:current} - The page shown on the evaluating device
+/// - ${device:serial|model} - The evaluating device
+/// - ${stats:presses[:[PAGE:]BUTTON]|dwell[:PAGE]} - Usage statistics of the evaluating device
+/// - ${pin:active} - Whether the evaluating device's page is pinned
+/// - ${telegram:unread[:CHAT]}, ${matrix:unread[:ROOM]} - Unread messages of a chat service
+/// - ${calendar:next_title|next_time|in_meeting} - The next meeting in the calendars
+/// - ${i18n:KEY} - Text of KEY translated to the configured locale
+/// - ${ocr:region:X,Y,W,H} - Text recognized in a region of the screen
+/// - ${checklist:done|label:LIST/ITEM} - Whether a checklist item is done, its label
+/// - ${proc:running:TAG} - Whether a command started by a tagged `exec` action runs
+/// - ${openrgb:profile} - The OpenRGB lighting profile loaded last
+/// - ${random:MIN-MAX} - A whole number picked at random, both ends included
+/// - ${choice:A|B|C} - One of the texts picked at random
+/// - ${rotate:NAME:A|B|C} - The next of the texts, in turn, every time it is evaluated
+///
+/// Values can be written the way the locale does with `|num[:DECIMALS]` and `|locale`
+/// filters after the provider, and in units with `|bytes`, `|rate` and `|duration`, see
+/// [`locale_format`].
 ///
 /// On error, returns ERROR_INDICATOR for that parameter.
 pub fn evaluate_dynamic_params(
@@ -31,6 +79,7 @@ pub fn evaluate_dynamic_params(
     services_state: &ServicesState,
     services_active: &Arc<AtomicBool>,
     context_vars: &ContextVars,
+    local: &LocalParams,
 ) -> HashMap<String, String> {
     let mut params = HashMap::new();
 
@@ -39,17 +88,45 @@ pub fn evaluate_dynamic_params(
 
     for cap in re.captures_iter(text) {
         let content = &cap[1]; // e.g., "time:%H:%M"
+        if params.contains_key(content) {
+            // Once per text, so a repeated `rotate` advances once
+            continue;
+        }
+        let (expression, filters) = locale_format::split_filters(content);
 
         // Parse provider type and argument
-        let value = if let Some((provider, arg)) = content.split_once(':') {
+        let value = if let Some((provider, arg)) = expression.split_once(':') {
             match provider {
-                "time" => evaluate_time_provider(arg),
+                "time" => evaluate_time_provider(&locale_format::time_format(arg, &filters)),
                 "env" => evaluate_env_provider(arg),
                 "service" => {
                     evaluate_service_provider(arg, services_config, services_state, services_active)
                 }
                 "system" => evaluate_system_provider(arg),
                 "var" => evaluate_var_provider(arg, context_vars),
+                "audio" => evaluate_audio_provider(arg),
+                "kbd" => evaluate_kbd_provider(arg),
+                "focus" => evaluate_focus_provider(arg, local),
+                "page" => evaluate_page_provider(arg, local),
+                "device" => evaluate_device_provider(arg, local),
+                "stats" => evaluate_stats_provider(arg, local),
+                "pin" => evaluate_pin_provider(arg, local),
+                "telegram" | "matrix" => evaluate_chat_provider(provider, arg),
+                "calendar" => calendar::value(arg).unwrap_or_else(|| ERROR_INDICATOR.to_string()),
+                "i18n" => evaluate_i18n_provider(arg, local),
+                "ocr" => crate::ocr::value(arg).unwrap_or_else(|| ERROR_INDICATOR.to_string()),
+                "checklist" => {
+                    crate::checklist::value(arg).unwrap_or_else(|| ERROR_INDICATOR.to_string())
+                }
+                "proc" => {
+                    crate::processes::value(arg).unwrap_or_else(|| ERROR_INDICATOR.to_string())
+                }
+                "openrgb" => {
+                    crate::openrgb::value(arg).unwrap_or_else(|| ERROR_INDICATOR.to_string())
+                }
+                "random" => evaluate_random_provider(arg),
+                "choice" => evaluate_choice_provider(arg),
+                "rotate" => evaluate_rotate_provider(arg),
                 _ => {
                     // Unknown provider
                     ERROR_INDICATOR.to_string()
@@ -62,7 +139,10 @@ pub fn evaluate_dynamic_params(
 
         // Store mapping from full pattern to value
         // Use content (not full_match) as key for substitution
-        params.insert(content.to_string(), value);
+        params.insert(
+            content.to_string(),
+            locale_format::apply_filters(value, &filters),
+        );
     }
 
     params
@@ -77,6 +157,46 @@ fn evaluate_time_provider(format: &str) -> String {
     }
 }
 
+/// Evaluates ${random:MIN-MAX}, e.g. `${random:1-6}` or `${random:-10-10}`
+fn evaluate_random_provider(range: &str) -> String {
+    // The first character may be the sign of MIN
+    let bounds = range
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| *c == '-')
+        .map(|(at, _)| (&range[..at], &range[at + 1..]));
+    let Some((min, max)) = bounds else {
+        return ERROR_INDICATOR.to_string();
+    };
+    match (min.trim().parse::<i64>(), max.trim().parse::<i64>()) {
+        (Ok(min), Ok(max)) if min <= max => fastrand::i64(min..=max).to_string(),
+        _ => ERROR_INDICATOR.to_string(),
+    }
+}
+
+/// Evaluates ${choice:A|B|C}
+fn evaluate_choice_provider(choices: &str) -> String {
+    let choices: Vec<&str> = choices.split('|').collect();
+    choices[fastrand::usize(..choices.len())].to_string()
+}
+
+/// How far each `${rotate:NAME:...}` went, by name
+static ROTATIONS: LazyLock<Mutex<HashMap<String, usize>>> = LazyLock::new(Default::default);
+
+/// Evaluates ${rotate:NAME:A|B|C}: A, then B, then C, then A again. Parameters of the same
+/// name, on any button, take turns from the same rotation.
+fn evaluate_rotate_provider(arg: &str) -> String {
+    let Some((name, choices)) = arg.split_once(':') else {
+        return ERROR_INDICATOR.to_string();
+    };
+    let choices: Vec<&str> = choices.split('|').collect();
+    let mut rotations = ROTATIONS.lock().unwrap();
+    let turn = rotations.entry(name.to_string()).or_default();
+    let value = choices[*turn % choices.len()];
+    *turn = (*turn + 1) % choices.len();
+    value.to_string()
+}
+
 /// Evaluates ${env:VAR} provider
 fn evaluate_env_provider(var_name: &str) -> String {
     env::var(var_name).unwrap_or_else(|_| ERROR_INDICATOR.to_string())
@@ -125,6 +245,105 @@ fn evaluate_system_provider(metric: &str) -> String {
     }
 }
 
+/// Evaluates ${audio:volume} / ${audio:muted} provider, optionally followed by `:SINK`,
+/// and ${audio:default_sink} / ${audio:default_source}
+fn evaluate_audio_provider(arg: &str) -> String {
+    use crate::audio::DeviceKind;
+    let (query, sink) = arg.split_once(':').unwrap_or((arg, crate::audio::DEFAULT_SINK));
+    let value = match query {
+        "volume" => crate::audio::get_volume(sink).map(|v| format!("{:.0}", v)),
+        "muted" => crate::audio::is_muted(sink).map(|m| if m { "yes" } else { "no" }.to_string()),
+        "default_sink" => crate::audio::default_device(DeviceKind::Sink).map(|d| d.description),
+        "default_source" => crate::audio::default_device(DeviceKind::Source).map(|d| d.description),
+        _ => None,
+    };
+    value.unwrap_or_else(|| ERROR_INDICATOR.to_string())
+}
+
+/// Evaluates ${kbd:layout}, the short name of the active keyboard layout
+fn evaluate_kbd_provider(arg: &str) -> String {
+    match arg {
+        "layout" => {
+            crate::platform::keyboard_layout().unwrap_or_else(|_| ERROR_INDICATOR.to_string())
+        }
+        _ => ERROR_INDICATOR.to_string(),
+    }
+}
+
+/// Evaluates ${focus:class} / ${focus:title}. Empty when nothing is focused.
+fn evaluate_focus_provider(arg: &str, local: &LocalParams) -> String {
+    match arg {
+        "class" => local.focus_class.to_string(),
+        "title" => local.focus_title.to_string(),
+        _ => ERROR_INDICATOR.to_string(),
+    }
+}
+
+/// Evaluates ${page:current}
+fn evaluate_page_provider(arg: &str, local: &LocalParams) -> String {
+    match arg {
+        "current" => local.page.to_string(),
+        _ => ERROR_INDICATOR.to_string(),
+    }
+}
+
+/// Evaluates ${device:serial} / ${device:model}
+fn evaluate_device_provider(arg: &str, local: &LocalParams) -> String {
+    match arg {
+        "serial" => local.serial.to_string(),
+        "model" => local.model.to_string(),
+        _ => ERROR_INDICATOR.to_string(),
+    }
+}
+
+/// Evaluates ${pin:active}: "yes" while the page is pinned, otherwise "no"
+fn evaluate_pin_provider(arg: &str, local: &LocalParams) -> String {
+    match arg {
+        "active" => if local.pinned { "yes" } else { "no" }.to_string(),
+        _ => ERROR_INDICATOR.to_string(),
+    }
+}
+
+/// Evaluates ${telegram:unread} and ${matrix:unread}, totals of all chats, and
+/// ${telegram:unread:CHAT} and ${matrix:unread:ROOM}
+fn evaluate_chat_provider(service: &str, arg: &str) -> String {
+    let chat = match arg.split_once(':') {
+        Some(("unread", chat)) => Some(chat),
+        None if arg == "unread" => None,
+        _ => return ERROR_INDICATOR.to_string(),
+    };
+    chats::unread(service, chat)
+        .map(|count| count.to_string())
+        .unwrap_or_else(|| ERROR_INDICATOR.to_string())
+}
+
+/// Evaluates ${i18n:KEY}. An untranslated key is shown as is, so a missing entry stays
+/// readable on the key.
+fn evaluate_i18n_provider(key: &str, local: &LocalParams) -> String {
+    local
+        .strings
+        .and_then(|strings| strings.get(key).cloned())
+        .unwrap_or_else(|| key.to_string())
+}
+
+/// Evaluates ${stats:presses} (all buttons), ${stats:presses:button3} (current page),
+/// ${stats:presses:PAGE:button3}, ${stats:dwell} (current page) and ${stats:dwell:PAGE}
+fn evaluate_stats_provider(arg: &str, local: &LocalParams) -> String {
+    let unused = DeviceStats::default();
+    let stats = local.stats.unwrap_or(&unused);
+    let (kind, rest) = arg.split_once(':').unwrap_or((arg, ""));
+    match kind {
+        "presses" if rest.is_empty() => stats.total_presses().to_string(),
+        "presses" => {
+            let (page, button) = rest.rsplit_once(':').unwrap_or((local.page, rest));
+            stats.presses(page, button).to_string()
+        }
+        "dwell" if rest.is_empty() => format_dwell(stats.dwell(local.page)),
+        "dwell" => format_dwell(stats.dwell(rest)),
+        _ => ERROR_INDICATOR.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -147,6 +366,25 @@ mod tests {
         assert_eq!(result, "⚠");
     }
 
+    #[test]
+    fn test_random_providers() {
+        for _ in 0..50 {
+            let value: i64 = evaluate_random_provider("-2-3").parse().unwrap();
+            assert!((-2..=3).contains(&value));
+            assert!(["a", "b c"].contains(&evaluate_choice_provider("a|b c").as_str()));
+        }
+        assert_eq!(evaluate_random_provider("5-5"), "5");
+        assert_eq!(evaluate_random_provider("9-1"), "⚠");
+        assert_eq!(evaluate_random_provider("dice"), "⚠");
+
+        let turns: Vec<String> = (0..4)
+            .map(|_| evaluate_rotate_provider("greeting:Hi|Hello|Hey"))
+            .collect();
+        assert_eq!(turns, ["Hi", "Hello", "Hey", "Hi"]);
+        assert_eq!(evaluate_rotate_provider("other:x|y"), "x");
+        assert_eq!(evaluate_rotate_provider("greeting"), "⚠");
+    }
+
     #[test]
     fn test_evaluate_dynamic_params() {
         env::set_var("USER_TEST", "testuser");
@@ -165,6 +403,7 @@ mod tests {
             &services_state,
             &services_active,
             &context_vars,
+            &LocalParams::default(),
         );
 
         assert!(params.contains_key("time:%H:%M"));
@@ -173,6 +412,56 @@ mod tests {
         assert_eq!(params.get("var:mode").unwrap(), "focus");
     }
 
+    #[test]
+    fn test_focus_provider() {
+        let local = LocalParams {
+            focus_class: "firefox",
+            focus_title: "Mozilla Firefox",
+            ..Default::default()
+        };
+        assert_eq!(evaluate_focus_provider("class", &local), "firefox");
+        assert_eq!(evaluate_focus_provider("title", &local), "Mozilla Firefox");
+        assert_eq!(evaluate_focus_provider("pid", &local), "⚠");
+    }
+
+    #[test]
+    fn test_page_and_device_providers() {
+        let local = LocalParams {
+            page: "Main",
+            serial: "AL12345",
+            model: "Stream Deck XL",
+            ..Default::default()
+        };
+        assert_eq!(evaluate_page_provider("current", &local), "Main");
+        assert_eq!(evaluate_device_provider("serial", &local), "AL12345");
+        assert_eq!(evaluate_device_provider("model", &local), "Stream Deck XL");
+        assert_eq!(evaluate_device_provider("firmware", &local), "⚠");
+    }
+
+    #[test]
+    fn test_stats_provider() {
+        let mut stats = crate::stats::UsageStats::default();
+        stats.record_press("AL1", "Main", "button3");
+        stats.record_press("AL1", "Main", "button3");
+        stats.record_press("AL1", "Media", "button1");
+        stats.add_dwell("AL1", "Media", std::time::Duration::from_secs(312));
+        let local = LocalParams {
+            page: "Main",
+            stats: stats.device("AL1"),
+            ..Default::default()
+        };
+        assert_eq!(evaluate_stats_provider("presses", &local), "3");
+        assert_eq!(evaluate_stats_provider("presses:button3", &local), "2");
+        assert_eq!(
+            evaluate_stats_provider("presses:Media:button1", &local),
+            "1"
+        );
+        assert_eq!(evaluate_stats_provider("presses:button9", &local), "0");
+        assert_eq!(evaluate_stats_provider("dwell", &local), "0s");
+        assert_eq!(evaluate_stats_provider("dwell:Media", &local), "5m 12s");
+        assert_eq!(evaluate_stats_provider("uptime", &local), "⚠");
+    }
+
     #[test]
     fn test_system_provider_invalid_metric() {
         let result = evaluate_system_provider("doesnotexist");
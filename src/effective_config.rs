@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! `keydeck --effective-config`: the configuration as the daemon runs it.
+//!
+//! The file is loaded exactly as the daemon loads it: templates are merged into the
+//! pages inheriting them, buttons into those extending them, the `defaults` block into
+//! every button, `window_name` turned into `when`, and generated pages read from their
+//! sources. Settings left out take their default values. Macros are left as they are,
+//! as the daemon expands them only when they run. The result is printed as YAML in the
+//! order of the file, with the buttons of each page in key order, so two versions of a
+//! configuration can be compared with `diff` and a configuration can be attached to a bug
+//! report as the daemon saw it.
+
+use crate::pages::{parse_grid_key, KeyDeckConf, KeyDeckConfLoader};
+use serde_yaml_ng::Value;
+use std::path::Path;
+
+/// The fully resolved configuration of the file at `path`, as YAML
+pub fn effective_yaml(path: &Path) -> Result<String, String> {
+    let mut conf = KeyDeckConfLoader::try_load_from(path)?;
+    // Merged into the pages and buttons already
+    conf.templates = None;
+    conf.defaults = None;
+    conf.gradients = None;
+    for pages in conf.page_groups.values_mut() {
+        for page in pages.pages.values_mut() {
+            page.inherits = None;
+        }
+    }
+    let mut value =
+        serde_yaml_ng::to_value(&conf).map_err(|e| format!("Failed to serialize config: {}", e))?;
+    sort_buttons(&conf, &mut value);
+    serde_yaml_ng::to_string(&value).map_err(|e| format!("Failed to serialize config: {}", e))
+}
+
+/// Puts the buttons of every page of `value`, the YAML of `conf`, after the other fields
+/// of the page and in key order, as they are kept in no particular order
+fn sort_buttons(conf: &KeyDeckConf, value: &mut Value) {
+    for (group, pages) in &conf.page_groups {
+        for (name, page) in &pages.pages {
+            let Some(Value::Mapping(fields)) = value
+                .get_mut(group.as_str())
+                .and_then(|pages| pages.get_mut(name.as_str()))
+            else {
+                continue;
+            };
+            let (mut buttons, others): (Vec<_>, Vec<_>) =
+                std::mem::take(fields).into_iter().partition(|(key, _)| {
+                    key.as_str()
+                        .is_some_and(|key| page.buttons.contains_key(key))
+                });
+            buttons.sort_by_cached_key(|(key, _)| button_order(key.as_str().unwrap_or_default()));
+            fields.extend(others.into_iter().chain(buttons));
+        }
+    }
+}
+
+/// `buttonN` keys by number, then `rRcC` keys by row and column, then the others
+fn button_order(key: &str) -> (u8, usize, usize) {
+    if let Some(index) = key
+        .strip_prefix("button")
+        .and_then(|index| index.parse().ok())
+    {
+        (0, index, 0)
+    } else if let Some((row, column)) = parse_grid_key(key) {
+        (1, row, column)
+    } else {
+        (2, 0, 0)
+    }
+}
+
+/// Prints the effective configuration of the file at `path`, or of the configuration
+/// file of the daemon, exiting with 1 when it does not load
+pub fn print_effective_config(path: Option<&str>) {
+    let path = path
+        .map(Into::into)
+        .unwrap_or_else(keydeck_types::get_config_path);
+    match effective_yaml(&path) {
+        Ok(yaml) => print!("{}", yaml),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_config_is_resolved_and_reloads_the_same() {
+        let dir = std::env::temp_dir().join(format!("keydeck-effective-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        std::fs::write(
+            &path,
+            "defaults:\n  text_color: red\ntemplates:\n  Common:\n    button1:\n      text: Back\n      actions:\n        - jump: Main\nmacros:\n  greet:\n    actions:\n      - text: hello\nMain1234:\n  main_page: Main\n  Main:\n    inherits: [Common]\n    window_name: firefox\n    button10:\n      text: Ten\n    button2:\n      text: Hi\n      actions:\n        - macro: greet\n",
+        )
+        .unwrap();
+
+        let yaml = effective_yaml(&path).unwrap();
+        assert!(!yaml.contains("templates:") && !yaml.contains("inherits"));
+        assert!(yaml.contains("tick_time: 2.0"), "{}", yaml);
+        assert!(yaml.contains("macro: greet"));
+        assert!(!yaml.contains("window_name"));
+        let at = |text: &str| yaml.find(text).unwrap();
+        assert!(at("when:") < at("button1:"));
+        assert!(at("button1:") < at("button2:") && at("button2:") < at("button10:"));
+        assert!(yaml.contains("text: Back\n      text_color: red"));
+
+        // Printing the effective configuration of itself changes nothing
+        std::fs::write(&path, &yaml).unwrap();
+        assert_eq!(effective_yaml(&path).unwrap(), yaml);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
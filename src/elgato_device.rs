@@ -1,15 +1,16 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
+use crate::device_conflicts;
 use crate::device_trait::{DeviceError, DeviceReader, DeviceStateUpdate, KeydeckDevice};
 #[allow(unused_imports)]
 use crate::{error_log, verbose_log};
+use elgato_streamdeck::images::ImageRect;
 use elgato_streamdeck::info::Kind;
-use elgato_streamdeck::{DeviceStateReader, StreamDeck};
+use elgato_streamdeck::{StreamDeck, StreamDeckInput};
 use hidapi::HidApi;
 use image::DynamicImage;
-use std::cell::RefCell;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
 use std::time::Duration;
 
 pub struct ElgatoDevice {
@@ -17,18 +18,13 @@ pub struct ElgatoDevice {
     pub(crate) kind: Kind,
     pub serial: String,
     device_id: String,
-    pub(crate) deck: RefCell<Option<Arc<StreamDeck>>>,
-    pub(crate) reader: RefCell<Option<Arc<DeviceStateReader>>>,
+    /// Connected on first use; the HID handle inside is not `Sync`, so it is guarded by
+    /// a mutex
+    pub(crate) deck: OnceLock<Arc<Mutex<StreamDeck>>>,
+    pub(crate) reader: OnceLock<Arc<ElgatoDeviceReader>>,
     enabled: bool,
 }
 
-// SAFETY: ElgatoDevice is safe to Send/Sync because:
-// - RefCell is only used for lazy initialization (get_deck, get_reader_arc)
-// - The actual StreamDeck/DeviceStateReader are Arc-wrapped and can be safely shared
-// - Access patterns ensure no concurrent mutation (initialized once per access)
-unsafe impl Send for ElgatoDevice {}
-unsafe impl Sync for ElgatoDevice {}
-
 impl ElgatoDevice {
     /// Check if a device with given VID/PID is supported by Elgato library
     /// Elgato Stream Deck devices use VID 0x0fd9
@@ -42,8 +38,8 @@ impl ElgatoDevice {
             kind,
             serial,
             device_id,
-            deck: RefCell::new(None),
-            reader: RefCell::new(None),
+            deck: OnceLock::new(),
+            reader: OnceLock::new(),
             enabled: true,
         }
     }
@@ -56,47 +52,80 @@ impl ElgatoDevice {
         self.enabled
     }
 
-    pub fn get_deck(&self) -> Arc<StreamDeck> {
-        self.deck
-            .borrow_mut()
-            .get_or_insert_with(|| {
-                Arc::new(
-                    StreamDeck::connect(&self.hid_api, self.kind, &self.serial).unwrap_or_else(
-                        |e| {
-                            error_log!(
-                                "Failed to connect to Stream Deck device '{}': {}",
-                                self.serial,
-                                e
-                            );
-                            error_log!("This may be due to:");
-                            error_log!("  - Device was unplugged");
-                            error_log!("  - Insufficient USB permissions");
-                            error_log!("  - Device busy/in use by another process");
-                            panic!("Cannot continue without device connection");
-                        },
-                    ),
-                )
-            })
-            .clone()
+    /// The connected deck, locked for the caller
+    pub fn get_deck(&self) -> MutexGuard<'_, StreamDeck> {
+        self.shared_deck()
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
     }
 
-    pub fn get_reader_arc(&self) -> Arc<DeviceStateReader> {
-        // Borrow mutably to check or initialize `reader`
-        if self.reader.borrow().is_none() {
-            let deck = self.get_deck();
-            // Borrow mutably and set `reader`
-            *self.reader.borrow_mut() = Some(deck.get_reader());
-        }
+    fn shared_deck(&self) -> &Arc<Mutex<StreamDeck>> {
+        self.deck.get_or_init(|| {
+            Arc::new(Mutex::new(
+                StreamDeck::connect(&self.hid_api, self.kind, &self.serial).unwrap_or_else(|e| {
+                    error_log!(
+                        "Failed to connect to Stream Deck device '{}': {}",
+                        self.serial,
+                        e
+                    );
+                    error_log!("This may be due to:");
+                    error_log!("  - Device was unplugged");
+                    error_log!("  - Insufficient USB permissions");
+                    error_log!("  - Device busy/in use by another process");
+                    for holder in device_conflicts::holders_of(&self.serial) {
+                        error_log!("    {}", holder);
+                    }
+                    panic!("Cannot continue without device connection");
+                }),
+            ))
+        })
+    }
+
+    /// Devices without a display (e.g. Stream Deck Pedal) reject every image and
+    /// brightness command, so those operations are silently skipped for them.
+    fn is_visual(&self) -> bool {
+        self.kind.is_visual()
+    }
 
-        // Borrow immutably to clone the reader - safe because we just initialized it above
+    fn get_reader_arc(&self) -> Arc<ElgatoDeviceReader> {
         self.reader
-            .borrow()
-            .as_ref()
-            .expect("Reader should be initialized")
+            .get_or_init(|| {
+                Arc::new(ElgatoDeviceReader {
+                    deck: self.input_deck(),
+                    key_count: self.kind.key_count(),
+                    states: Mutex::new(InputStates {
+                        buttons: vec![
+                            false;
+                            (self.kind.key_count() + self.kind.touchpoint_count())
+                                as usize
+                        ],
+                        encoders: vec![false; self.kind.encoder_count() as usize],
+                    }),
+                })
+            })
             .clone()
     }
 
+    /// The handle input is read from: on Windows the one images are written to, as a
+    /// second handle gets no input there and reads are polled
+    #[cfg(target_os = "windows")]
+    fn input_deck(&self) -> Result<Arc<Mutex<StreamDeck>>, String> {
+        Ok(self.shared_deck().clone())
+    }
+
+    /// The handle input is read from: one of its own, so a blocking read never holds up
+    /// the images
+    #[cfg(not(target_os = "windows"))]
+    fn input_deck(&self) -> Result<Arc<Mutex<StreamDeck>>, String> {
+        StreamDeck::connect(&self.hid_api, self.kind, &self.serial)
+            .map(|deck| Arc::new(Mutex::new(deck)))
+            .map_err(|e| format!("cannot open device '{}' for input: {}", self.serial, e))
+    }
+
     pub fn reset(&self) -> Result<(), String> {
+        if !self.is_visual() {
+            return Ok(());
+        }
         let deck = self.get_deck();
         verbose_log!("Resetting device '{}'", self.serial);
         deck.reset()
@@ -104,6 +133,9 @@ impl ElgatoDevice {
     }
 
     pub fn clear_button_image(&self, button_idx: u8) -> Result<(), String> {
+        if !self.is_visual() {
+            return Ok(());
+        }
         let deck = self.get_deck();
         verbose_log!(
             "Clearing button image on device '{}' from button {}",
@@ -119,6 +151,9 @@ impl ElgatoDevice {
     }
 
     pub fn set_button_image(&self, button_idx: u8, image: DynamicImage) -> Result<(), String> {
+        if !self.is_visual() {
+            return Ok(());
+        }
         let deck = self.get_deck();
         verbose_log!(
             "Setting button image on device '{}' to button {}",
@@ -134,6 +169,9 @@ impl ElgatoDevice {
     }
 
     pub fn flush(&self) -> Result<(), String> {
+        if !self.is_visual() {
+            return Ok(());
+        }
         let deck = self.get_deck();
         verbose_log!("Flushing device '{}'", self.serial);
         deck.flush()
@@ -141,6 +179,9 @@ impl ElgatoDevice {
     }
 
     pub fn set_brightness(&self, brightness: u8) -> Result<(), String> {
+        if !self.is_visual() {
+            return Ok(());
+        }
         let deck = self.get_deck();
         verbose_log!(
             "Setting brightness {} on device '{}'",
@@ -155,7 +196,32 @@ impl ElgatoDevice {
         })
     }
 
+    pub fn write_lcd(&self, x: u16, y: u16, image: DynamicImage) -> Result<(), String> {
+        let deck = self.get_deck();
+        verbose_log!(
+            "Writing LCD strip region at {},{} on device '{}'",
+            x,
+            y,
+            self.serial
+        );
+        let rect = ImageRect::from_image(image).map_err(|e| {
+            format!(
+                "Failed to encode LCD image for device '{}': {}",
+                self.serial, e
+            )
+        })?;
+        deck.write_lcd(x, y, &rect).map_err(|e| {
+            format!(
+                "Failed to write LCD strip on device '{}': {}",
+                self.serial, e
+            )
+        })
+    }
+
     pub fn clear_all_button_images(&self) -> Result<(), String> {
+        if !self.is_visual() {
+            return Ok(());
+        }
         let deck = self.get_deck();
         verbose_log!("Cleared all button images on device '{}'", self.serial);
         deck.clear_all_button_images().map_err(|e| {
@@ -167,68 +233,101 @@ impl ElgatoDevice {
     }
 }
 
-/// Wrapper for elgato StreamDeck DeviceStateReader to implement our DeviceReader trait
-struct ElgatoDeviceReader {
-    reader: Arc<DeviceStateReader>,
+/// Pressed state of the buttons (touch points after the keys) and encoders last read
+struct InputStates {
+    buttons: Vec<bool>,
+    encoders: Vec<bool>,
 }
 
-// SAFETY: ElgatoDeviceReader is safe to Send/Sync because Arc<DeviceStateReader> is internally thread-safe
-unsafe impl Send for ElgatoDeviceReader {}
-unsafe impl Sync for ElgatoDeviceReader {}
+/// Reads the input of an Elgato device, turning the states it reports into changes, as
+/// elgato-streamdeck's `DeviceStateReader` does over a handle that cannot be shared
+pub(crate) struct ElgatoDeviceReader {
+    deck: Result<Arc<Mutex<StreamDeck>>, String>,
+    key_count: u8,
+    states: Mutex<InputStates>,
+}
 
 impl DeviceReader for ElgatoDeviceReader {
     fn read(&self, timeout: Option<Duration>) -> Result<Vec<DeviceStateUpdate>, DeviceError> {
-        let updates = self.reader.read(timeout).map_err(|e| {
-            DeviceError::LibraryError(format!("Failed to read device state: {}", e))
-        })?;
+        let deck = self
+            .deck
+            .as_ref()
+            .map_err(|e| DeviceError::ConnectionFailed(e.clone()))?;
+        let input = deck
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .read_input(timeout)
+            .map_err(|e| {
+                DeviceError::LibraryError(format!("Failed to read device state: {}", e))
+            })?;
+        let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(input_updates(input, &mut states, self.key_count))
+    }
+}
 
-        // Convert elgato-streamdeck's DeviceStateUpdate to our DeviceStateUpdate
-        let converted = updates
-            .into_iter()
-            .map(|update| {
-                match update {
-                    elgato_streamdeck::DeviceStateUpdate::ButtonDown(key) => {
-                        DeviceStateUpdate::ButtonDown(key)
-                    }
-                    elgato_streamdeck::DeviceStateUpdate::ButtonUp(key) => {
-                        DeviceStateUpdate::ButtonUp(key)
-                    }
-                    elgato_streamdeck::DeviceStateUpdate::EncoderDown(encoder) => {
+/// The changes an input report brings to `states`, which it updates
+fn input_updates(
+    input: StreamDeckInput,
+    states: &mut InputStates,
+    key_count: u8,
+) -> Vec<DeviceStateUpdate> {
+    let mut updates = Vec::new();
+    match input {
+        StreamDeckInput::NoData => {}
+        StreamDeckInput::ButtonStateChange(buttons) => {
+            for (index, (&pressed, &was)) in buttons.iter().zip(&states.buttons).enumerate() {
+                if pressed == was {
+                    continue;
+                }
+                let index = index as u8;
+                updates.push(match (index.checked_sub(key_count), pressed) {
+                    (None, true) => DeviceStateUpdate::ButtonDown(index),
+                    (None, false) => DeviceStateUpdate::ButtonUp(index),
+                    (Some(point), true) => DeviceStateUpdate::TouchPointDown(point),
+                    (Some(point), false) => DeviceStateUpdate::TouchPointUp(point),
+                });
+            }
+            states.buttons = buttons;
+        }
+        StreamDeckInput::EncoderStateChange(encoders) => {
+            for (index, (&pressed, &was)) in encoders.iter().zip(&states.encoders).enumerate() {
+                if pressed != was {
+                    let encoder = index as u8;
+                    updates.push(if pressed {
                         DeviceStateUpdate::EncoderDown(encoder)
-                    }
-                    elgato_streamdeck::DeviceStateUpdate::EncoderUp(encoder) => {
+                    } else {
                         DeviceStateUpdate::EncoderUp(encoder)
-                    }
-                    elgato_streamdeck::DeviceStateUpdate::EncoderTwist(encoder, ticks) => {
-                        DeviceStateUpdate::EncoderTwist { encoder, ticks }
-                    }
-                    elgato_streamdeck::DeviceStateUpdate::TouchPointDown(point) => {
-                        DeviceStateUpdate::TouchPointDown(point)
-                    }
-                    elgato_streamdeck::DeviceStateUpdate::TouchPointUp(point) => {
-                        DeviceStateUpdate::TouchPointUp(point)
-                    }
-                    elgato_streamdeck::DeviceStateUpdate::TouchScreenPress(x, y) => {
-                        DeviceStateUpdate::TouchScreenPress { x, y }
-                    }
-                    elgato_streamdeck::DeviceStateUpdate::TouchScreenLongPress(x, y) => {
-                        DeviceStateUpdate::TouchScreenLongPress { x, y }
-                    }
-                    elgato_streamdeck::DeviceStateUpdate::TouchScreenSwipe(start, end) => {
-                        // Unpack the tuples
-                        DeviceStateUpdate::TouchScreenSwipe {
-                            x: start.0,
-                            y: start.1,
-                            target_x: end.0,
-                            target_y: end.1,
-                        }
-                    }
+                    });
                 }
+            }
+            states.encoders = encoders;
+        }
+        StreamDeckInput::EncoderTwist(twists) => {
+            for (encoder, &ticks) in twists.iter().enumerate() {
+                if ticks != 0 {
+                    updates.push(DeviceStateUpdate::EncoderTwist {
+                        encoder: encoder as u8,
+                        ticks,
+                    });
+                }
+            }
+        }
+        StreamDeckInput::TouchScreenPress(x, y) => {
+            updates.push(DeviceStateUpdate::TouchScreenPress { x, y })
+        }
+        StreamDeckInput::TouchScreenLongPress(x, y) => {
+            updates.push(DeviceStateUpdate::TouchScreenLongPress { x, y })
+        }
+        StreamDeckInput::TouchScreenSwipe(start, end) => {
+            updates.push(DeviceStateUpdate::TouchScreenSwipe {
+                x: start.0,
+                y: start.1,
+                target_x: end.0,
+                target_y: end.1,
             })
-            .collect();
-
-        Ok(converted)
+        }
     }
+    updates
 }
 
 /// Implement KeydeckDevice trait for ElgatoDevice
@@ -270,6 +369,37 @@ impl KeydeckDevice for ElgatoDevice {
         (rows as usize, cols as usize)
     }
 
+    fn encoder_count(&self) -> usize {
+        self.kind.encoder_count() as usize
+    }
+
+    fn lcd_strip_size(&self) -> Option<(u16, u16)> {
+        self.kind
+            .lcd_strip_size()
+            .map(|(w, h)| (w as u16, h as u16))
+    }
+
+    fn write_lcd(
+        &self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        image: &DynamicImage,
+    ) -> Result<(), DeviceError> {
+        if self.lcd_strip_size().is_none() {
+            return Err(DeviceError::UnsupportedOperation(
+                "Device has no LCD strip".to_string(),
+            ));
+        }
+        let image = image.resize_exact(
+            width as u32,
+            height as u32,
+            image::imageops::FilterType::Lanczos3,
+        );
+        ElgatoDevice::write_lcd(self, x, y, image).map_err(DeviceError::from)
+    }
+
     fn reset(&self) -> Result<(), DeviceError> {
         ElgatoDevice::reset(self).map_err(DeviceError::from)
     }
@@ -295,11 +425,46 @@ impl KeydeckDevice for ElgatoDevice {
     }
 
     fn get_reader(&self) -> Arc<dyn DeviceReader> {
-        Arc::new(ElgatoDeviceReader {
-            reader: self.get_reader_arc(),
-        })
+        self.get_reader_arc()
     }
 
     // Lifecycle methods use default no-op implementations from trait
     // shutdown(), sleep(), keep_alive() - all no-op for Elgato devices
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_reports_turn_into_changes() {
+        let mut states = InputStates {
+            buttons: vec![false; 3],
+            encoders: vec![false; 2],
+        };
+        let mut read = |input| input_updates(input, &mut states, 2);
+        assert_eq!(
+            read(StreamDeckInput::ButtonStateChange(vec![false, true, true])),
+            [
+                DeviceStateUpdate::ButtonDown(1),
+                DeviceStateUpdate::TouchPointDown(0)
+            ]
+        );
+        assert_eq!(
+            read(StreamDeckInput::ButtonStateChange(vec![false, false, true])),
+            [DeviceStateUpdate::ButtonUp(1)]
+        );
+        assert_eq!(
+            read(StreamDeckInput::EncoderStateChange(vec![false, true])),
+            [DeviceStateUpdate::EncoderDown(1)]
+        );
+        assert_eq!(
+            read(StreamDeckInput::EncoderTwist(vec![-2, 0])),
+            [DeviceStateUpdate::EncoderTwist {
+                encoder: 0,
+                ticks: -2
+            }]
+        );
+        assert!(read(StreamDeckInput::NoData).is_empty());
+    }
+}
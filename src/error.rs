@@ -0,0 +1,227 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Errors of the daemon that carry more than their message.
+//!
+//! Most helpers still describe a failure with a string, which converts into
+//! [`Error::Message`]. Where it matters to whoever reports the error, the variants keep
+//! the device, the key injection backend, or the position of the failed action in its
+//! sequence, so the action history, the IPC replies and the configurator can point at
+//! the cause instead of showing an opaque text.
+
+use crate::device_trait::DeviceError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// A device could not be found or driven
+    #[error("Device {serial}: {source}")]
+    Device {
+        serial: String,
+        #[source]
+        source: DeviceError,
+    },
+
+    /// Keys could not be injected into the session
+    #[error("{source} ({backend} keyboard)")]
+    Keyboard {
+        backend: &'static str,
+        #[source]
+        source: KeyboardError,
+    },
+
+    /// The KWin scripting interface failed
+    #[cfg(target_os = "linux")]
+    #[error("KWin: {0}")]
+    KWin(#[from] crate::kwin_script::Error),
+
+    /// A page that the device does not have
+    #[error("Page not found: {0}")]
+    PageNotFound(String),
+
+    /// A key outside the keys of the device
+    #[error("Invalid button number: {button} (valid range: 1-{count})")]
+    InvalidButton { button: u8, count: u8 },
+
+    #[error("Macro '{0}' not found")]
+    MacroNotFound(String),
+
+    /// A color that is neither named nor in a known format
+    #[error("Invalid color format: {0}")]
+    InvalidColor(String),
+
+    #[error("Image not found: {0}")]
+    ImageNotFound(String),
+
+    /// An image could not be read or decoded
+    #[error("{path}: {source}")]
+    Image {
+        path: String,
+        #[source]
+        source: image::ImageError,
+    },
+
+    /// An action of a sequence failed; `index` counts from 0
+    #[error("Action {} ({action}): {source}", .index + 1)]
+    Action {
+        index: usize,
+        action: String,
+        #[source]
+        source: Box<Error>,
+    },
+
+    /// Any other failure, described by its message
+    #[error("{0}")]
+    Message(String),
+}
+
+/// Why keys could not be injected
+#[derive(Debug, Error)]
+pub enum KeyboardError {
+    #[cfg(target_os = "linux")]
+    #[error(transparent)]
+    Connect(#[from] x11rb::errors::ConnectError),
+
+    #[cfg(target_os = "linux")]
+    #[error(transparent)]
+    Connection(#[from] x11rb::errors::ConnectionError),
+
+    #[cfg(target_os = "linux")]
+    #[error(transparent)]
+    Reply(#[from] x11rb::errors::ReplyError),
+
+    #[error("XTest extension is not available")]
+    NoXTest,
+
+    #[error("Unknown key '{0}'")]
+    UnknownKey(String),
+
+    #[error("Unsupported character: {0}")]
+    UnsupportedChar(char),
+
+    /// No keycode of the keyboard produces the keysym
+    #[error("Keysym not found: {0}")]
+    UnmappedKeysym(u32),
+
+    #[error("Empty key part in key combination")]
+    EmptyKey,
+
+    #[error("Hotkey '{0}' has more than one key")]
+    ExtraKey(String),
+
+    #[error("Hotkey '{0}' has no key besides modifiers")]
+    NoKey(String),
+
+    /// A failure of a backend that describes it with a string
+    #[error("{0}")]
+    Message(String),
+}
+
+impl From<String> for KeyboardError {
+    fn from(message: String) -> Self {
+        KeyboardError::Message(message)
+    }
+}
+
+impl From<&str> for KeyboardError {
+    fn from(message: &str) -> Self {
+        KeyboardError::Message(message.to_string())
+    }
+}
+
+impl Error {
+    pub fn keyboard(backend: &'static str, source: impl Into<KeyboardError>) -> Self {
+        Error::Keyboard {
+            backend,
+            source: source.into(),
+        }
+    }
+
+    /// Positions of the failed action in its sequence and in the nested sequences of
+    /// `try`, `and`, `or`, `not` and macros, outermost first
+    pub fn action_path(&self) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut error = self;
+        while let Error::Action { index, source, .. } = error {
+            path.push(*index);
+            error = source;
+        }
+        path
+    }
+
+    /// The error that caused the failure, without the actions it surfaced through
+    pub fn cause(&self) -> &Error {
+        match self {
+            Error::Action { source, .. } => source.cause(),
+            error => error,
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Message(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::Message(message.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn action_errors_point_at_the_failed_action() {
+        let error = Error::Action {
+            index: 1,
+            action: "try".to_string(),
+            source: Box::new(Error::Action {
+                index: 0,
+                action: "key: ctrl+t".to_string(),
+                source: Box::new(Error::keyboard(
+                    "x11",
+                    KeyboardError::UnknownKey("t1".to_string()),
+                )),
+            }),
+        };
+        assert_eq!(error.action_path(), [1, 0]);
+        assert!(matches!(
+            error.cause(),
+            Error::Keyboard {
+                backend: "x11",
+                source: KeyboardError::UnknownKey(_)
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Action 2 (try): Action 1 (key: ctrl+t): Unknown key 't1' (x11 keyboard)"
+        );
+        assert_eq!(
+            Error::from("Fail action executed").to_string(),
+            "Fail action executed"
+        );
+    }
+
+    #[test]
+    fn device_and_keyboard_errors_keep_their_source() {
+        let error = Error::Device {
+            serial: "CL1".to_string(),
+            source: DeviceError::NotFound,
+        };
+        assert_eq!(error.to_string(), "Device CL1: not found");
+        assert!(std::error::Error::source(&error).is_some_and(|source| source.is::<DeviceError>()));
+
+        let error = Error::keyboard("enigo", "Failed to initialize input backend");
+        assert!(
+            std::error::Error::source(&error).is_some_and(|source| source.is::<KeyboardError>())
+        );
+        assert_eq!(
+            error.to_string(),
+            "Failed to initialize input backend (enigo keyboard)"
+        );
+    }
+}
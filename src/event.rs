@@ -1,12 +1,32 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
-use crate::error_log;
+use crate::config_patch::ButtonPatch;
+use crate::pages::{NightModeSwitch, Switch, WaitForEvent, WaitForEvents, WaitMode, WindowWait};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+/// Input events sent and not yet taken by the event loop, so long redraws can give way
+static INPUT_PENDING: AtomicUsize = AtomicUsize::new(0);
 
 pub fn send(tx: &Sender<DeviceEvent>, event: DeviceEvent) {
+    if event.is_input() {
+        INPUT_PENDING.fetch_add(1, Ordering::Relaxed);
+    }
     tx.send(event)
-        .unwrap_or_else(|e| error_log!("Error while sending event: {}", e))
+        .unwrap_or_else(|e| crate::error_log!("Error while sending event: {}", e))
+}
+
+/// The event loop took an input event
+pub fn input_taken() {
+    let _ = INPUT_PENDING.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+}
+
+/// Whether presses, twists or touches wait for the event loop
+pub fn input_pending() -> bool {
+    INPUT_PENDING.load(Ordering::Relaxed) > 0
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -52,6 +72,10 @@ pub enum DeviceEvent {
     /// Window focus changed
     FocusChanges { class: String, title: String },
 
+    /// A focus backend (KWin, X11) became available; the current focus follows as
+    /// `FocusChanges`
+    FocusBackendReady { backend: String },
+
     /// Set or clear an external context variable (from the control socket).
     /// `value: None` clears the variable.
     SetContextVar { key: String, value: Option<String> },
@@ -79,6 +103,82 @@ pub enum DeviceEvent {
 
     /// Set brightness on a device
     SetBrightness { sn: String, brightness: u8 },
+
+    /// Re-render a single button (e.g. when a temporary visual state expires)
+    RefreshButton { sn: String, button_id: u8 },
+
+    /// Repeat the actions of a held button; `press` identifies the press that started it
+    RepeatButton {
+        sn: String,
+        button_id: u8,
+        press: u64,
+    },
+
+    /// The time of a button `timer` is up; `run` identifies the start that scheduled it
+    TimerUp { sn: String, run: u64 },
+
+    /// A delayed focus change is due; `focus` identifies the change that scheduled it
+    FocusSettled { sn: String, focus: u64 },
+
+    /// The echo page of a `text` action shows its next frame or, after the last one,
+    /// types the text; `echo` identifies the echo that scheduled it
+    EchoStep { sn: String, echo: u64 },
+
+    /// The question of a `confirm` action was not answered in time; `confirmation`
+    /// identifies the question that scheduled it
+    ConfirmTimeout { sn: String, confirmation: u64 },
+
+    /// Draw the keys a bulk refresh left for after the input that was waiting
+    ResumeRefresh { sn: String },
+
+    /// Switch the night mode filter on all devices
+    NightMode { mode: NightModeSwitch },
+
+    /// Switch game mode on all devices
+    GameMode { mode: Switch },
+
+    /// Switch the active theme on all devices
+    SetTheme { theme: String },
+
+    /// A global hotkey was pressed
+    Hotkey { combination: String },
+
+    /// A webhook of the web deck was called
+    Webhook { name: String },
+
+    /// Values of a dynamic provider changed outside of the tick, e.g. a chat message
+    /// arrived
+    ProviderUpdated { provider: String },
+
+    /// Show the test pattern on a device, or on all of them
+    TestPattern { sn: Option<String> },
+
+    /// The test pattern was shown long enough; the page comes back
+    TestPatternEnd { sn: String },
+
+    /// A device reconnected after the system resumed should be checked and redrawn
+    VerifyDevice { sn: String },
+
+    /// Show the diagnostic page on a device, or on all of them
+    Diagnostics { sn: Option<String> },
+
+    /// Measure rendering on a device, or on all of them, for `keydeck --bench`
+    Bench { sn: Option<String> },
+
+    /// Write the keys of a device as a PNG at `path`, for `keydeck --screenshot`
+    Screenshot { sn: String, path: PathBuf },
+
+    /// Report the state of the services, for `keydeck --services`
+    Services,
+
+    /// Change a button of the running configuration, for `keydeck --set-button`
+    PatchButton { target: String, patch: ButtonPatch },
+
+    /// Show a page on the devices of a page group, or on the device with that serial
+    SetPage { target: String, page: String },
+
+    /// The provider of a page sent new buttons, see [`crate::page_provider`]
+    PageProvided { group: String, page: String },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -119,7 +219,221 @@ impl WaitEventType {
     }
 }
 
+/// What satisfies one of the conditions of a wait
+#[derive(Debug, Clone, PartialEq)]
+enum WaitTrigger {
+    Event(WaitEventType),
+    /// Time passed since the wait started
+    After(Duration),
+    /// A window with these (lowercased) class and title substrings has the focus
+    Window {
+        class: String,
+        title: String,
+    },
+}
+
+/// One condition of a wait, and whether it was met
+#[derive(Debug, Clone)]
+struct WaitCondition {
+    trigger: WaitTrigger,
+    /// The condition fails if not met this long after the wait started
+    timeout: Duration,
+    met: bool,
+}
+
+impl WaitCondition {
+    fn failed(&self, elapsed: Duration) -> bool {
+        !self.met
+            && match self.trigger {
+                WaitTrigger::Event(_) | WaitTrigger::Window { .. } => elapsed > self.timeout,
+                // Met once its timer arrives, unless the wait is over first
+                WaitTrigger::After(delay) => delay > self.timeout,
+            }
+    }
+}
+
+/// The conditions a paused action sequence waits for: the events of a `wait_for`, or
+/// the timer of a `wait`
+#[derive(Debug, Clone)]
+pub struct WaitConditions {
+    conditions: Vec<WaitCondition>,
+    mode: WaitMode,
+}
+
+impl WaitConditions {
+    /// Waits for a single event, for at most `timeout`
+    pub fn event(event_type: WaitEventType, timeout: Duration) -> Self {
+        WaitConditions {
+            conditions: vec![WaitCondition {
+                trigger: WaitTrigger::Event(event_type),
+                timeout,
+                met: false,
+            }],
+            mode: WaitMode::Any,
+        }
+    }
+
+    /// Waits for the focus of the window of a `wait_for_window` action, for its timeout
+    /// or 10 seconds
+    pub fn window(window: &WindowWait) -> Result<Self, String> {
+        let class = window.class.as_deref().unwrap_or_default().to_lowercase();
+        let title = window.title.as_deref().unwrap_or_default().to_lowercase();
+        if class.is_empty() && title.is_empty() {
+            return Err("wait_for_window needs a class or a title".to_string());
+        }
+        Ok(WaitConditions {
+            conditions: vec![WaitCondition {
+                trigger: WaitTrigger::Window { class, title },
+                timeout: Duration::try_from_secs_f64(window.timeout.unwrap_or(10.0))
+                    .unwrap_or_default(),
+                met: false,
+            }],
+            mode: WaitMode::Any,
+        })
+    }
+
+    /// Waits for the events of a `wait_for` action, each for at most its own timeout and
+    /// `timeout` of the action. That defaults to 1 second, or to the longest timeout or
+    /// delay of the events when longer. Fails on an unknown event type.
+    pub fn from_events(
+        events: &WaitForEvents,
+        mode: WaitMode,
+        timeout: Option<f64>,
+    ) -> Result<Self, String> {
+        let seconds = |s: f64| Duration::try_from_secs_f64(s).unwrap_or_default();
+        let events = events.events();
+        if events.is_empty() {
+            return Err("wait_for needs at least one event".to_string());
+        }
+        let timeout = timeout.map(seconds).unwrap_or_else(|| {
+            events
+                .iter()
+                .filter_map(|event| match event {
+                    WaitForEvent::Event(_) => None,
+                    WaitForEvent::Detailed { timeout, .. } => timeout.map(seconds),
+                    WaitForEvent::After { after } => Some(seconds(*after)),
+                })
+                .fold(Duration::from_secs(1), Duration::max)
+        });
+        let conditions = events
+            .iter()
+            .map(|event| {
+                let (trigger, own_timeout) = match event {
+                    WaitForEvent::Event(event) => {
+                        (WaitTrigger::Event(WaitEventType::from_str(event)?), None)
+                    }
+                    WaitForEvent::Detailed { event, timeout } => (
+                        WaitTrigger::Event(WaitEventType::from_str(event)?),
+                        timeout.map(seconds),
+                    ),
+                    WaitForEvent::After { after } => (WaitTrigger::After(seconds(*after)), None),
+                };
+                Ok(WaitCondition {
+                    trigger,
+                    timeout: own_timeout.map_or(timeout, |own| own.min(timeout)),
+                    met: false,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(WaitConditions { conditions, mode })
+    }
+
+    /// Delays of the `after` conditions, for which timers must be scheduled
+    pub fn delays(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.conditions.iter().filter_map(|c| match c.trigger {
+            WaitTrigger::After(delay) => Some(delay),
+            _ => None,
+        })
+    }
+
+    /// Marks the conditions met by `event_type` arriving `elapsed` after the wait started.
+    /// Timers of `after` conditions arrive as `timer` events, once their delay is over.
+    pub fn observe(&mut self, event_type: &WaitEventType, elapsed: Duration) {
+        for condition in &mut self.conditions {
+            condition.met = condition.met
+                || match &condition.trigger {
+                    WaitTrigger::Event(wanted) => {
+                        wanted == event_type && elapsed <= condition.timeout
+                    }
+                    // The timer arrives a little after the delay, even when it equals
+                    // the timeout
+                    WaitTrigger::After(delay) => elapsed >= *delay && *delay <= condition.timeout,
+                    WaitTrigger::Window { .. } => false,
+                };
+        }
+    }
+
+    /// Marks the window conditions met by the window with `class` and `title` having the
+    /// focus `elapsed` after the wait started
+    pub fn observe_window(&mut self, class: &str, title: &str, elapsed: Duration) {
+        let (class, title) = (class.to_lowercase(), title.to_lowercase());
+        for condition in &mut self.conditions {
+            if let WaitTrigger::Window {
+                class: wanted_class,
+                title: wanted_title,
+            } = &condition.trigger
+            {
+                condition.met = condition.met
+                    || (elapsed <= condition.timeout
+                        && !(class.is_empty() && title.is_empty())
+                        && class.contains(wanted_class.as_str())
+                        && title.contains(wanted_title.as_str()));
+            }
+        }
+    }
+
+    /// Some(true) once the wait is over, Some(false) once it can no longer be, None while
+    /// it goes on
+    pub fn outcome(&self, elapsed: Duration) -> Option<bool> {
+        let mut met = self.conditions.iter().map(|c| c.met);
+        let mut failed = self.conditions.iter().map(|c| c.failed(elapsed));
+        match self.mode {
+            WaitMode::Any if met.any(|m| m) => Some(true),
+            WaitMode::Any if failed.all(|f| f) => Some(false),
+            WaitMode::All if met.all(|m| m) => Some(true),
+            WaitMode::All if failed.any(|f| f) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// The conditions, as in `focus or 3s`
+    pub fn describe(&self) -> String {
+        let separator = match self.mode {
+            WaitMode::Any => " or ",
+            WaitMode::All => " and ",
+        };
+        self.conditions
+            .iter()
+            .map(|c| match &c.trigger {
+                WaitTrigger::Event(event_type) => event_type.as_str().to_string(),
+                WaitTrigger::After(delay) => format!("{}s", delay.as_secs_f64()),
+                WaitTrigger::Window { class, title } => {
+                    format!("window class='{}' title='{}'", class, title)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+}
+
 impl DeviceEvent {
+    /// Whether this is a press, twist or touch of a device
+    pub fn is_input(&self) -> bool {
+        matches!(
+            self,
+            DeviceEvent::ButtonDown { .. }
+                | DeviceEvent::ButtonUp { .. }
+                | DeviceEvent::EncoderDown { .. }
+                | DeviceEvent::EncoderUp { .. }
+                | DeviceEvent::EncoderTwist { .. }
+                | DeviceEvent::TouchPointDown { .. }
+                | DeviceEvent::TouchPointUp { .. }
+                | DeviceEvent::TouchScreenPress { .. }
+                | DeviceEvent::TouchScreenLongPress { .. }
+                | DeviceEvent::TouchScreenSwipe { .. }
+        )
+    }
+
     /// Extract the WaitEventType from this event, if it corresponds to a waitable event
     pub fn wait_event_type(&self) -> Option<WaitEventType> {
         match self {
@@ -133,3 +447,55 @@ impl DeviceEvent {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conditions(yaml: &str, mode: WaitMode) -> WaitConditions {
+        let events: WaitForEvents = serde_yaml_ng::from_str(yaml).unwrap();
+        WaitConditions::from_events(&events, mode, None).unwrap()
+    }
+
+    #[test]
+    fn any_wait_ends_with_the_first_event_or_delay() {
+        let mut wait = conditions("[focus, { after: 3 }]", WaitMode::Any);
+        assert_eq!(wait.describe(), "focus or 3s");
+        assert_eq!(wait.delays().collect::<Vec<_>>(), [Duration::from_secs(3)]);
+        wait.observe(&WaitEventType::Tick, Duration::from_secs(1));
+        assert_eq!(wait.outcome(Duration::from_secs(1)), None);
+        // The default timeout is the delay, which still counts when its timer is late
+        let late = Duration::from_millis(3010);
+        assert_eq!(wait.clone().outcome(late), None);
+        wait.observe(&WaitEventType::Timer, late);
+        assert_eq!(wait.outcome(late), Some(true));
+        assert!(WaitConditions::from_events(
+            &serde_yaml_ng::from_str("[focus, clicks]").unwrap(),
+            WaitMode::Any,
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn window_wait_needs_the_matching_window_focused() {
+        let window: WindowWait = serde_yaml_ng::from_str("{ class: Firefox, timeout: 5 }").unwrap();
+        let mut wait = WaitConditions::window(&window).unwrap();
+        wait.observe(&WaitEventType::Focus, Duration::from_secs(1));
+        wait.observe_window("org.kde.konsole", "~", Duration::from_secs(1));
+        assert_eq!(wait.outcome(Duration::from_secs(1)), None);
+        wait.observe_window("firefox", "Mozilla Firefox", Duration::from_secs(2));
+        assert_eq!(wait.outcome(Duration::from_secs(2)), Some(true));
+        assert!(WaitConditions::window(&WindowWait::default()).is_err());
+    }
+
+    #[test]
+    fn all_wait_fails_when_an_event_misses_its_timeout() {
+        let mut wait = conditions("[focus, { event: page, timeout: 2 }]", WaitMode::All);
+        wait.observe(&WaitEventType::Focus, Duration::from_secs(1));
+        assert_eq!(wait.outcome(Duration::from_secs(1)), None);
+        assert_eq!(wait.clone().outcome(Duration::from_secs(3)), Some(false));
+        wait.observe(&WaitEventType::Page, Duration::from_millis(1500));
+        assert_eq!(wait.outcome(Duration::from_millis(1500)), Some(true));
+    }
+}
@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Fan-out of the events of the daemon to the subsystems that observe them.
+//!
+//! The event loop of the server takes every [`DeviceEvent`] in order and drives the
+//! devices with it. Subsystems that only watch, such as the web deck showing key presses,
+//! subscribe to the [`Topic`]s they care about and get their own copy of those events,
+//! without a case in the event loop. The devices publish what the watchers of the control
+//! socket are told about the same way. Publishing never waits for a subscriber: one that
+//! falls more than [`CAPACITY`] events behind misses the oldest of them, and events are
+//! only copied while someone subscribed.
+
+use crate::event::DeviceEvent;
+use crate::live_events::LiveEvent;
+use crate::verbose_log;
+use std::sync::{Arc, LazyLock};
+use tokio::sync::broadcast::{
+    self,
+    error::{RecvError, TryRecvError},
+};
+
+/// Events kept for subscribers that did not take them yet
+const CAPACITY: usize = 256;
+
+/// Something published on the bus
+#[derive(Debug, Clone, PartialEq)]
+pub enum BusEvent {
+    /// An event the event loop takes
+    Device(DeviceEvent),
+    /// Something the devices tell the watchers of the daemon
+    Live(LiveEvent),
+}
+
+/// The kinds of events a subscriber can ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topic {
+    /// Keys, encoders and touch screens of the devices
+    Input,
+    /// Devices coming and going, and the system sleeping
+    Devices,
+    /// The focused window and the context variables
+    Focus,
+    /// Ticks, timers and refreshes the daemon schedules itself
+    Schedule,
+    /// Requests from the control socket, hotkeys, webhooks and actions
+    Control,
+    /// Key presses, page changes and failed refreshes as the watchers see them
+    Live,
+}
+
+impl Topic {
+    pub fn of(event: &BusEvent) -> Topic {
+        let event = match event {
+            BusEvent::Device(event) => event,
+            BusEvent::Live(_) => return Topic::Live,
+        };
+        match event {
+            DeviceEvent::ButtonDown { .. }
+            | DeviceEvent::ButtonUp { .. }
+            | DeviceEvent::EncoderDown { .. }
+            | DeviceEvent::EncoderUp { .. }
+            | DeviceEvent::EncoderTwist { .. }
+            | DeviceEvent::TouchPointDown { .. }
+            | DeviceEvent::TouchPointUp { .. }
+            | DeviceEvent::TouchScreenPress { .. }
+            | DeviceEvent::TouchScreenLongPress { .. }
+            | DeviceEvent::TouchScreenSwipe { .. } => Topic::Input,
+            DeviceEvent::NewDevice { .. }
+            | DeviceEvent::RemovedDevice { .. }
+            | DeviceEvent::VerifyDevice { .. }
+            | DeviceEvent::Sleep { .. } => Topic::Devices,
+            DeviceEvent::FocusChanges { .. }
+            | DeviceEvent::FocusBackendReady { .. }
+            | DeviceEvent::SetContextVar { .. } => Topic::Focus,
+            DeviceEvent::Tick
+            | DeviceEvent::TimerComplete { .. }
+            | DeviceEvent::RefreshButton { .. }
+            | DeviceEvent::RepeatButton { .. }
+            | DeviceEvent::TimerUp { .. }
+            | DeviceEvent::FocusSettled { .. }
+            | DeviceEvent::EchoStep { .. }
+            | DeviceEvent::ConfirmTimeout { .. }
+            | DeviceEvent::ResumeRefresh { .. }
+            | DeviceEvent::TestPatternEnd { .. }
+            | DeviceEvent::ProviderUpdated { .. }
+            | DeviceEvent::PageProvided { .. } => Topic::Schedule,
+            DeviceEvent::Exit
+            | DeviceEvent::Reload
+            | DeviceEvent::SetBrightness { .. }
+            | DeviceEvent::NightMode { .. }
+            | DeviceEvent::GameMode { .. }
+            | DeviceEvent::SetTheme { .. }
+            | DeviceEvent::Hotkey { .. }
+            | DeviceEvent::Webhook { .. }
+            | DeviceEvent::TestPattern { .. }
+            | DeviceEvent::Diagnostics { .. }
+            | DeviceEvent::Bench { .. }
+            | DeviceEvent::Screenshot { .. }
+            | DeviceEvent::Services
+            | DeviceEvent::PatchButton { .. }
+            | DeviceEvent::SetPage { .. } => Topic::Control,
+        }
+    }
+}
+
+static BUS: LazyLock<broadcast::Sender<Arc<BusEvent>>> =
+    LazyLock::new(|| broadcast::channel(CAPACITY).0);
+
+/// Hands `event` to the subscribers, as the event loop takes it
+pub fn publish(event: &DeviceEvent) {
+    if BUS.receiver_count() > 0 {
+        let _ = BUS.send(Arc::new(BusEvent::Device(event.clone())));
+    }
+}
+
+/// Hands `event` to the watchers of the daemon
+pub fn publish_live(event: LiveEvent) {
+    if BUS.receiver_count() > 0 {
+        let _ = BUS.send(Arc::new(BusEvent::Live(event)));
+    }
+}
+
+/// The events of some topics published from now on
+pub struct Subscription {
+    receiver: broadcast::Receiver<Arc<BusEvent>>,
+    topics: Vec<Topic>,
+}
+
+pub fn subscribe(topics: &[Topic]) -> Subscription {
+    Subscription {
+        receiver: BUS.subscribe(),
+        topics: topics.to_vec(),
+    }
+}
+
+impl Subscription {
+    /// The next event of the topics already published, without waiting for one
+    pub fn try_recv(&mut self) -> Option<Arc<BusEvent>> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) if self.topics.contains(&Topic::of(&event)) => return Some(event),
+                Ok(_) => {}
+                Err(TryRecvError::Lagged(missed)) => {
+                    verbose_log!("Event subscriber fell behind, missed {} events", missed)
+                }
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// The next event of the topics, waiting for one. Must not be called from async code.
+    pub fn recv(&mut self) -> Arc<BusEvent> {
+        loop {
+            match self.receiver.blocking_recv() {
+                Ok(event) if self.topics.contains(&Topic::of(&event)) => return event,
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    verbose_log!("Event subscriber fell behind, missed {} events", missed)
+                }
+                Err(RecvError::Closed) => unreachable!("the event bus is never closed"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscribers_get_the_events_of_their_topics() {
+        let press = DeviceEvent::ButtonDown {
+            sn: "AL1".to_string(),
+            button_id: 3,
+        };
+        publish(&press);
+        let mut input = subscribe(&[Topic::Input]);
+        let mut devices = subscribe(&[Topic::Devices, Topic::Focus]);
+        publish(&DeviceEvent::Tick);
+        publish(&press);
+        publish(&DeviceEvent::RemovedDevice {
+            sn: "AL1".to_string(),
+        });
+        assert_eq!(
+            input.try_recv().as_deref(),
+            Some(&BusEvent::Device(press.clone()))
+        );
+        assert_eq!(input.try_recv(), None);
+        assert!(matches!(
+            devices.try_recv().as_deref(),
+            Some(BusEvent::Device(DeviceEvent::RemovedDevice { .. }))
+        ));
+
+        // The oldest events are dropped for a subscriber that does not keep up; the live
+        // events of other tests may take some of the places left
+        for _ in 0..CAPACITY + 1 {
+            publish(&press);
+        }
+        let mut kept = 0;
+        while input.try_recv().is_some() {
+            kept += 1;
+        }
+        assert!(kept > 0 && kept <= CAPACITY, "{} events kept", kept);
+    }
+}
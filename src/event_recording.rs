@@ -0,0 +1,501 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Recording and replay of the events the daemon receives, for `keydeck --record-events`
+//! and `keydeck --replay-events`.
+//!
+//! A recording is one JSON line per event coming from outside the daemon, with the time
+//! it arrived since the start:
+//!
+//! ```text
+//! {"at_ms":0,"event":"new_device","sn":"<serial>","device":{"kind":"Stream Deck MK.2",...}}
+//! {"at_ms":1520,"event":"focus_changes","class":"firefox","title":"Mozilla Firefox"}
+//! {"at_ms":2310,"event":"button_down","sn":"<serial>","button_id":3}
+//! ```
+//!
+//! Events the daemon schedules itself (ticks, timers, repeats, refreshes) are left out,
+//! as the replaying daemon schedules them again. Connected devices are recorded with
+//! their shape, so a replay drives stand-in devices that draw nothing and works without
+//! the hardware of the recording. While replaying, real devices, focus changes and
+//! sleep are not listened to, so only the recorded events reach the pages.
+
+use crate::device_trait::{DeviceError, DeviceReader, DeviceStateUpdate, KeydeckDevice};
+use crate::event::{send, DeviceEvent};
+use crate::{error_log, info_log, runtime};
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// What the daemon is asked to do with its events
+pub enum EventLog {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// The shape of a device, enough to stand in for it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceShape {
+    pub kind: String,
+    pub buttons: u8,
+    /// Rows and columns of keys
+    pub layout: (usize, usize),
+    pub key_size: (u16, u16),
+    #[serde(default)]
+    pub encoders: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lcd_strip: Option<(u16, u16)>,
+}
+
+impl DeviceShape {
+    fn of(device: &dyn KeydeckDevice) -> Self {
+        DeviceShape {
+            kind: device.kind_name(),
+            buttons: device.button_count(),
+            layout: device.button_layout(),
+            key_size: device.button_image_size(),
+            encoders: device.encoder_count(),
+            lcd_strip: device.lcd_strip_size(),
+        }
+    }
+}
+
+/// An event of a recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Recorded {
+    ButtonDown {
+        sn: String,
+        button_id: u8,
+    },
+    ButtonUp {
+        sn: String,
+        button_id: u8,
+    },
+    EncoderDown {
+        sn: String,
+        encoder_id: u8,
+    },
+    EncoderUp {
+        sn: String,
+        encoder_id: u8,
+    },
+    EncoderTwist {
+        sn: String,
+        encoder_id: u8,
+        value: i8,
+    },
+    TouchPointDown {
+        sn: String,
+        point_id: u8,
+    },
+    TouchPointUp {
+        sn: String,
+        point_id: u8,
+    },
+    TouchScreenPress {
+        sn: String,
+        x: u16,
+        y: u16,
+    },
+    TouchScreenLongPress {
+        sn: String,
+        x: u16,
+        y: u16,
+    },
+    TouchScreenSwipe {
+        sn: String,
+        start: (u16, u16),
+        end: (u16, u16),
+    },
+    FocusChanges {
+        class: String,
+        title: String,
+    },
+    SetContextVar {
+        key: String,
+        value: Option<String>,
+    },
+    NewDevice {
+        sn: String,
+        device: DeviceShape,
+    },
+    RemovedDevice {
+        sn: String,
+    },
+    Sleep {
+        sleep: bool,
+    },
+    Hotkey {
+        combination: String,
+    },
+    Webhook {
+        name: String,
+    },
+    SetPage {
+        target: String,
+        page: String,
+    },
+    Reload,
+}
+
+impl Recorded {
+    /// The recorded form of an event from outside the daemon. New devices are recorded
+    /// once opened, with [`record_device`].
+    fn from_event(event: &DeviceEvent) -> Option<Self> {
+        let event = event.clone();
+        Some(match event {
+            DeviceEvent::ButtonDown { sn, button_id } => Recorded::ButtonDown { sn, button_id },
+            DeviceEvent::ButtonUp { sn, button_id } => Recorded::ButtonUp { sn, button_id },
+            DeviceEvent::EncoderDown { sn, encoder_id } => Recorded::EncoderDown { sn, encoder_id },
+            DeviceEvent::EncoderUp { sn, encoder_id } => Recorded::EncoderUp { sn, encoder_id },
+            DeviceEvent::EncoderTwist {
+                sn,
+                encoder_id,
+                value,
+            } => Recorded::EncoderTwist {
+                sn,
+                encoder_id,
+                value,
+            },
+            DeviceEvent::TouchPointDown { sn, point_id } => {
+                Recorded::TouchPointDown { sn, point_id }
+            }
+            DeviceEvent::TouchPointUp { sn, point_id } => Recorded::TouchPointUp { sn, point_id },
+            DeviceEvent::TouchScreenPress { sn, x, y } => Recorded::TouchScreenPress { sn, x, y },
+            DeviceEvent::TouchScreenLongPress { sn, x, y } => {
+                Recorded::TouchScreenLongPress { sn, x, y }
+            }
+            DeviceEvent::TouchScreenSwipe { sn, start, end } => {
+                Recorded::TouchScreenSwipe { sn, start, end }
+            }
+            DeviceEvent::FocusChanges { class, title } => Recorded::FocusChanges { class, title },
+            DeviceEvent::SetContextVar { key, value } => Recorded::SetContextVar { key, value },
+            DeviceEvent::RemovedDevice { sn } => Recorded::RemovedDevice { sn },
+            DeviceEvent::Sleep { sleep } => Recorded::Sleep { sleep },
+            DeviceEvent::Hotkey { combination } => Recorded::Hotkey { combination },
+            DeviceEvent::Webhook { name } => Recorded::Webhook { name },
+            DeviceEvent::SetPage { target, page } => Recorded::SetPage { target, page },
+            DeviceEvent::Reload => Recorded::Reload,
+            _ => return None,
+        })
+    }
+
+    fn into_event(self) -> DeviceEvent {
+        match self {
+            Recorded::ButtonDown { sn, button_id } => DeviceEvent::ButtonDown { sn, button_id },
+            Recorded::ButtonUp { sn, button_id } => DeviceEvent::ButtonUp { sn, button_id },
+            Recorded::EncoderDown { sn, encoder_id } => DeviceEvent::EncoderDown { sn, encoder_id },
+            Recorded::EncoderUp { sn, encoder_id } => DeviceEvent::EncoderUp { sn, encoder_id },
+            Recorded::EncoderTwist {
+                sn,
+                encoder_id,
+                value,
+            } => DeviceEvent::EncoderTwist {
+                sn,
+                encoder_id,
+                value,
+            },
+            Recorded::TouchPointDown { sn, point_id } => {
+                DeviceEvent::TouchPointDown { sn, point_id }
+            }
+            Recorded::TouchPointUp { sn, point_id } => DeviceEvent::TouchPointUp { sn, point_id },
+            Recorded::TouchScreenPress { sn, x, y } => DeviceEvent::TouchScreenPress { sn, x, y },
+            Recorded::TouchScreenLongPress { sn, x, y } => {
+                DeviceEvent::TouchScreenLongPress { sn, x, y }
+            }
+            Recorded::TouchScreenSwipe { sn, start, end } => {
+                DeviceEvent::TouchScreenSwipe { sn, start, end }
+            }
+            Recorded::FocusChanges { class, title } => DeviceEvent::FocusChanges { class, title },
+            Recorded::SetContextVar { key, value } => DeviceEvent::SetContextVar { key, value },
+            Recorded::NewDevice { sn, .. } => DeviceEvent::NewDevice { sn },
+            Recorded::RemovedDevice { sn } => DeviceEvent::RemovedDevice { sn },
+            Recorded::Sleep { sleep } => DeviceEvent::Sleep { sleep },
+            Recorded::Hotkey { combination } => DeviceEvent::Hotkey { combination },
+            Recorded::Webhook { name } => DeviceEvent::Webhook { name },
+            Recorded::SetPage { target, page } => DeviceEvent::SetPage { target, page },
+            Recorded::Reload => DeviceEvent::Reload,
+        }
+    }
+}
+
+/// A line of a recording
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Line {
+    /// Milliseconds since the daemon started
+    at_ms: u64,
+    #[serde(flatten)]
+    event: Recorded,
+}
+
+struct Recorder {
+    out: LineWriter<File>,
+    start: Instant,
+}
+
+/// The recording in progress, if any
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+/// Set once a replay started
+static REPLAYING: AtomicBool = AtomicBool::new(false);
+
+/// Shapes of the devices of the replay, by serial
+static REPLAYED: LazyLock<Mutex<HashMap<String, DeviceShape>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Starts writing the events of the daemon to `path`, replacing its content
+pub fn start_recording(path: &Path) -> Result<(), String> {
+    let file =
+        File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    *RECORDER.lock().unwrap() = Some(Recorder {
+        out: LineWriter::new(file),
+        start: Instant::now(),
+    });
+    info_log!("Recording events to {}", path.display());
+    Ok(())
+}
+
+fn write(event: Recorded) {
+    let mut recorder = RECORDER.lock().unwrap();
+    let Some(active) = recorder.as_mut() else {
+        return;
+    };
+    let line = Line {
+        at_ms: active.start.elapsed().as_millis() as u64,
+        event,
+    };
+    let Ok(json) = serde_json::to_string(&line) else {
+        return;
+    };
+    // Written line by line, so a crash keeps the events that led to it
+    if let Err(e) = writeln!(active.out, "{}", json) {
+        error_log!("Failed to record event, recording stopped: {}", e);
+        *recorder = None;
+    }
+}
+
+/// Records an event received by the daemon, when recording
+pub fn record(event: &DeviceEvent) {
+    if RECORDER.lock().unwrap().is_none() {
+        return;
+    }
+    if let Some(event) = Recorded::from_event(event) {
+        write(event);
+    }
+}
+
+/// Records a device that was just opened, when recording
+pub fn record_device(sn: &str, device: &dyn KeydeckDevice) {
+    if RECORDER.lock().unwrap().is_none() {
+        return;
+    }
+    write(Recorded::NewDevice {
+        sn: sn.to_string(),
+        device: DeviceShape::of(device),
+    });
+}
+
+/// Reads a recording; `number` counts lines from 1 in errors
+fn parse(content: &str) -> Result<Vec<Line>, String> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            serde_json::from_str(line).map_err(|e| format!("Line {}: {}", number + 1, e))
+        })
+        .collect()
+}
+
+/// Sends the events of the recording at `path` to the daemon, at the times they were
+/// recorded
+pub fn start_replay(path: &Path, tx: &Sender<DeviceEvent>) -> Result<(), String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let lines =
+        parse(&content).map_err(|e| format!("Invalid recording {}: {}", path.display(), e))?;
+    let mut replayed = REPLAYED.lock().unwrap();
+    for line in &lines {
+        if let Recorded::NewDevice { sn, device } = &line.event {
+            replayed.insert(sn.clone(), device.clone());
+        }
+    }
+    info_log!(
+        "Replaying {} events of {} with {} devices",
+        lines.len(),
+        path.display(),
+        replayed.len()
+    );
+    REPLAYING.store(true, Ordering::Relaxed);
+    let tx = tx.clone();
+    let start = tokio::time::Instant::now();
+    runtime::spawn(move |_| async move {
+        for line in lines {
+            tokio::time::sleep_until(start + Duration::from_millis(line.at_ms)).await;
+            send(&tx, line.event.into_event());
+        }
+        info_log!("Replay finished");
+    });
+    Ok(())
+}
+
+/// Whether the daemon replays a recording instead of listening to its surroundings
+pub fn is_replaying() -> bool {
+    REPLAYING.load(Ordering::Relaxed)
+}
+
+/// A stand-in for the device with this serial, when it is part of the replay
+pub fn replayed_device(sn: &str) -> Option<ReplayedDevice> {
+    let shape = REPLAYED.lock().unwrap().get(sn)?.clone();
+    Some(ReplayedDevice {
+        serial: sn.to_string(),
+        shape,
+    })
+}
+
+/// A device of a replay: it has the shape of the recorded one and draws nothing
+pub struct ReplayedDevice {
+    serial: String,
+    shape: DeviceShape,
+}
+
+/// Input of a replayed device, which comes from the recording instead
+struct NoInput;
+
+impl DeviceReader for NoInput {
+    fn read(&self, timeout: Option<Duration>) -> Result<Vec<DeviceStateUpdate>, DeviceError> {
+        std::thread::sleep(timeout.unwrap_or(Duration::from_secs(1)));
+        Ok(Vec::new())
+    }
+}
+
+impl KeydeckDevice for ReplayedDevice {
+    fn serial_number(&self) -> Result<String, DeviceError> {
+        Ok(self.serial.clone())
+    }
+
+    fn firmware_version(&self) -> Result<String, DeviceError> {
+        Ok("replay".to_string())
+    }
+
+    fn manufacturer(&self) -> String {
+        "KeyDeck replay".to_string()
+    }
+
+    fn kind_name(&self) -> String {
+        self.shape.kind.clone()
+    }
+
+    fn button_count(&self) -> u8 {
+        self.shape.buttons
+    }
+
+    fn has_screen(&self) -> bool {
+        true
+    }
+
+    fn button_image_size(&self) -> (u16, u16) {
+        self.shape.key_size
+    }
+
+    fn button_layout(&self) -> (usize, usize) {
+        self.shape.layout
+    }
+
+    fn encoder_count(&self) -> usize {
+        self.shape.encoders
+    }
+
+    fn lcd_strip_size(&self) -> Option<(u16, u16)> {
+        self.shape.lcd_strip
+    }
+
+    fn reset(&self) -> Result<(), DeviceError> {
+        Ok(())
+    }
+
+    fn set_brightness(&self, _brightness: u8) -> Result<(), DeviceError> {
+        Ok(())
+    }
+
+    fn set_button_image(&self, _button_idx: u8, _image: DynamicImage) -> Result<(), DeviceError> {
+        Ok(())
+    }
+
+    fn clear_button_image(&self, _button_idx: u8) -> Result<(), DeviceError> {
+        Ok(())
+    }
+
+    fn clear_all_button_images(&self) -> Result<(), DeviceError> {
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), DeviceError> {
+        Ok(())
+    }
+
+    fn get_reader(&self) -> Arc<dyn DeviceReader> {
+        Arc::new(NoInput)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_events_read_back() {
+        let shape = DeviceShape {
+            kind: "Stream Deck MK.2".to_string(),
+            buttons: 15,
+            layout: (3, 5),
+            key_size: (72, 72),
+            encoders: 0,
+            lcd_strip: None,
+        };
+        let lines = [
+            Line {
+                at_ms: 0,
+                event: Recorded::NewDevice {
+                    sn: "AL1".to_string(),
+                    device: shape,
+                },
+            },
+            Line {
+                at_ms: 1520,
+                event: Recorded::from_event(&DeviceEvent::ButtonDown {
+                    sn: "AL1".to_string(),
+                    button_id: 3,
+                })
+                .unwrap(),
+            },
+        ];
+        let content: String = lines
+            .iter()
+            .map(|line| serde_json::to_string(line).unwrap() + "\n")
+            .collect();
+        assert!(
+            content.contains(r#"{"at_ms":1520,"event":"button_down","sn":"AL1","button_id":3}"#)
+        );
+        assert_eq!(parse(&content).unwrap(), lines);
+        assert_eq!(
+            lines[1].event.clone().into_event(),
+            DeviceEvent::ButtonDown {
+                sn: "AL1".to_string(),
+                button_id: 3
+            }
+        );
+
+        assert_eq!(Recorded::from_event(&DeviceEvent::Tick), None);
+        assert!(parse("{\"at_ms\":5,\"event\":\"reload\"}\n\n{\"at_ms\":7}")
+            .unwrap_err()
+            .starts_with("Line 3:"));
+    }
+}
@@ -0,0 +1,192 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! How `exec` actions start their command: through the platform shell, optionally as
+//! another user (`run_as`, through `sudo`) and in a transient systemd scope (`scope`),
+//! so long-running launches get their own cgroup instead of the daemon's.
+//!
+//! A command waited for can be given a `timeout`: it then runs in a process group of its
+//! own, and the whole group is killed when the time is up, so a command that blocks
+//! cannot hold up the actions after it forever. A command run as another user is
+//! killed as that user, through `sudo` as it was started, since the daemon may not
+//! signal processes of other users.
+
+use crate::pages::ExecScope;
+use crate::warn_log;
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Command that runs `exec` with the options of its action
+pub fn exec_command(
+    exec: &str,
+    run_as: Option<&str>,
+    scope: Option<&ExecScope>,
+) -> Result<Command, String> {
+    let (shell, flag) = crate::platform::exec_shell();
+    let mut args: Vec<String> = vec![shell.to_string(), flag.to_string(), exec.to_string()];
+
+    if let Some(user) = run_as {
+        if cfg!(windows) {
+            return Err("run_as is not supported on this platform".to_string());
+        }
+        if user.trim().is_empty() {
+            return Err("run_as needs a user name".to_string());
+        }
+        // -n fails at once instead of asking for a password nobody can type
+        let sudo = ["sudo", "-n", "-u", user.trim(), "--"];
+        args.splice(0..0, sudo.map(String::from));
+    }
+
+    if let Some(properties) = scope.and_then(ExecScope::properties) {
+        if !cfg!(target_os = "linux") {
+            return Err("scope is only supported on Linux".to_string());
+        }
+        let mut systemd_run: Vec<String> =
+            ["systemd-run", "--user", "--scope", "--quiet", "--collect"]
+                .map(String::from)
+                .to_vec();
+        for property in properties {
+            systemd_run.push("-p".to_string());
+            systemd_run.push(property);
+        }
+        systemd_run.push("--".to_string());
+        args.splice(0..0, systemd_run);
+    }
+
+    let mut command = Command::new(&args[0]);
+    command.args(&args[1..]);
+    Ok(command)
+}
+
+/// Runs `command`, started as `run_as`, to its end and returns its output. Once it runs
+/// longer than `timeout`, it is killed with every process it started. Errors read as the
+/// end of a sentence about the command, e.g. "timed out after 5.0s".
+pub fn output_within(
+    command: &mut Command,
+    timeout: Option<Duration>,
+    run_as: Option<&str>,
+) -> Result<Output, String> {
+    let Some(timeout) = timeout else {
+        return command
+            .output()
+            .map_err(|e| format!("failed to start: {}", e));
+    };
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to start: {}", e))?;
+    let pid = child.id();
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(child.wait_with_output());
+    });
+    match rx.recv_timeout(timeout) {
+        Ok(output) => output.map_err(|e| format!("failed: {}", e)),
+        Err(_) => {
+            kill_tree(pid, run_as);
+            Err(format!("timed out after {:.1}s", timeout.as_secs_f32()))
+        }
+    }
+}
+
+/// Kills the process `pid`, started as `run_as`, and the processes it started. On Unix
+/// these are the processes of the group it leads.
+pub fn kill_tree(pid: u32, run_as: Option<&str>) {
+    let killed = kill_command(pid, run_as)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output();
+    match killed {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => warn_log!(
+            "Could not kill process {}: {}",
+            pid,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(e) => warn_log!("Could not kill process {}: {}", pid, e),
+    }
+}
+
+/// The command killing the process `pid` and what it started, as the user it runs as
+fn kill_command(pid: u32, run_as: Option<&str>) -> Command {
+    let pid = pid.to_string();
+    if cfg!(windows) {
+        let mut taskkill = Command::new("taskkill");
+        taskkill.args(["/T", "/F", "/PID", &pid]);
+        return taskkill;
+    }
+    let mut kill = match run_as.map(str::trim) {
+        Some(user) => {
+            let mut sudo = Command::new("sudo");
+            sudo.args(["-n", "-u", user, "--", "kill"]);
+            sudo
+        }
+        None => Command::new("kill"),
+    };
+    kill.args(["-KILL", "--", &format!("-{}", pid)]);
+    kill
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_wraps_the_user_switch() {
+        let scope = ExecScope::Limits {
+            memory_max: Some("2G".to_string()),
+            cpu_quota: None,
+            tasks_max: Some(64),
+        };
+        let command = exec_command("make -j8", Some("builder"), Some(&scope)).unwrap();
+        let args: Vec<_> = command.get_args().map(|a| a.to_string_lossy()).collect();
+        assert_eq!(command.get_program(), "systemd-run");
+        assert_eq!(
+            args.join(" "),
+            "--user --scope --quiet --collect -p MemoryMax=2G -p TasksMax=64 -- \
+             sudo -n -u builder -- bash -c make -j8"
+        );
+        let plain = exec_command("true", None, Some(&ExecScope::Enabled(false))).unwrap();
+        assert_eq!(plain.get_program(), "bash");
+    }
+
+    #[test]
+    fn commands_are_killed_after_their_timeout() {
+        let timeout = Some(Duration::from_millis(300));
+        let mut quick = exec_command("echo done", None, None).unwrap();
+        let output = output_within(&mut quick, timeout, None).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "done\n");
+
+        // The shell and the sleep it started both go
+        let started = std::time::Instant::now();
+        let mut stuck = exec_command("sleep 30; echo late", None, None).unwrap();
+        let error = output_within(&mut stuck, timeout, None).unwrap_err();
+        assert!(error.starts_with("timed out"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn commands_of_other_users_are_killed_as_them() {
+        let args = |command: Command| {
+            let args: Vec<_> = command.get_args().map(|a| a.to_string_lossy()).collect();
+            format!(
+                "{} {}",
+                command.get_program().to_string_lossy(),
+                args.join(" ")
+            )
+        };
+        assert_eq!(args(kill_command(42, None)), "kill -KILL -- -42");
+        assert_eq!(
+            args(kill_command(42, Some("builder"))),
+            "sudo -n -u builder -- kill -KILL -- -42"
+        );
+    }
+}
@@ -0,0 +1,336 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Icons fetched from websites, so a button opening a site can show its logo.
+//!
+//! The page is read for its `<link rel="icon">` and `apple-touch-icon` links, falling
+//! back to `/favicon.ico`; the largest icon that decodes wins. It is fitted into a
+//! transparent square at key resolution and saved as PNG in the icon dir
+//! (`keydeck --fetch-icon <URL>`, and the configuration UI's icon picker).
+
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, RgbaImage};
+use regex::Regex;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::Duration;
+use ureq::ResponseExt;
+
+/// Side of the saved icons: at least the key size of every supported deck, which scales
+/// them down when drawing
+const ICON_SIZE: u32 = 144;
+
+/// Icons tried before settling for the largest found
+const MAX_CANDIDATES: usize = 6;
+
+const PAGE_LIMIT: u64 = 2 * 1024 * 1024;
+const ICON_LIMIT: u64 = 1024 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+static LINK_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<link\b[^>]*>").unwrap());
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\b(rel|href|sizes|type)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#).unwrap()
+});
+
+#[derive(Debug, Serialize)]
+pub struct FetchedIcon {
+    /// File name in the icon dir
+    pub icon: String,
+    /// Where it was downloaded from
+    pub source: String,
+    /// Size of the downloaded image
+    pub width: u32,
+    pub height: u32,
+}
+
+/// An icon linked from a page, with the size it declares
+#[derive(Debug, PartialEq)]
+struct Candidate {
+    url: String,
+    size: u32,
+}
+
+fn agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .user_agent(concat!("keydeck/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .into()
+}
+
+/// Downloads the icon of the site at `url` into `icon_dir`, named `name` or after the
+/// site; an existing icon by that name gets a numbered sibling
+pub fn fetch_icon(url: &str, name: Option<&str>, icon_dir: &Path) -> Result<FetchedIcon, String> {
+    let url = if url.contains("://") {
+        url.to_string()
+    } else {
+        format!("https://{}", url)
+    };
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(format!("Not a web address: {}", url));
+    }
+    let agent = agent();
+
+    // A page that cannot be read still has a chance with /favicon.ico
+    let (page_url, html) = match agent.get(&url).call() {
+        Ok(mut response) => {
+            let page_url = response.get_uri().to_string();
+            let html = response
+                .body_mut()
+                .with_config()
+                .limit(PAGE_LIMIT)
+                .read_to_string()
+                .unwrap_or_default();
+            (page_url, html)
+        }
+        Err(_) => (url.clone(), String::new()),
+    };
+
+    let mut best: Option<(String, DynamicImage)> = None;
+    for candidate in candidates(&page_url, &html)
+        .into_iter()
+        .take(MAX_CANDIDATES)
+    {
+        let Ok(image) = download_image(&agent, &candidate.url) else {
+            continue;
+        };
+        if best
+            .as_ref()
+            .is_none_or(|(_, found)| image.width() > found.width())
+        {
+            best = Some((candidate.url, image));
+        }
+        if best
+            .as_ref()
+            .is_some_and(|(_, found)| found.width() >= ICON_SIZE)
+        {
+            break;
+        }
+    }
+    let Some((source, image)) = best else {
+        return Err(format!("No usable icon found for {}", url));
+    };
+
+    let base = sanitize(name.unwrap_or(&site_name(&page_url)));
+    let (path, icon) = free_path(icon_dir, &base);
+    std::fs::create_dir_all(icon_dir)
+        .map_err(|e| format!("Failed to create {}: {}", icon_dir.display(), e))?;
+    fit(&image)
+        .save(&path)
+        .map_err(|e| format!("Failed to save {}: {}", path.display(), e))?;
+    Ok(FetchedIcon {
+        icon,
+        source,
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+fn download_image(agent: &ureq::Agent, url: &str) -> Result<DynamicImage, String> {
+    let bytes = agent
+        .get(url)
+        .call()
+        .map_err(|e| e.to_string())?
+        .body_mut()
+        .with_config()
+        .limit(ICON_LIMIT)
+        .read_to_vec()
+        .map_err(|e| e.to_string())?;
+    image::load_from_memory(&bytes).map_err(|e| e.to_string())
+}
+
+/// Icons linked from `html`, largest first, then `/favicon.ico`. SVG icons are left out,
+/// as keys only draw raster images.
+fn candidates(page_url: &str, html: &str) -> Vec<Candidate> {
+    let mut found = vec![];
+    for tag in LINK_TAG.find_iter(html) {
+        let mut rel = String::new();
+        let mut href = None;
+        let mut size = 0;
+        let mut svg = false;
+        for attribute in ATTRIBUTE.captures_iter(tag.as_str()) {
+            let value = (2..=4)
+                .find_map(|group| attribute.get(group))
+                .map_or("", |value| value.as_str());
+            match attribute[1].to_ascii_lowercase().as_str() {
+                "rel" => rel = value.to_ascii_lowercase(),
+                "href" => href = Some(value.replace("&amp;", "&")),
+                "sizes" => {
+                    size = value
+                        .split_whitespace()
+                        .filter_map(|size| {
+                            size.to_ascii_lowercase().split_once('x')?.0.parse().ok()
+                        })
+                        .max()
+                        .unwrap_or(0)
+                }
+                "type" => svg = value.eq_ignore_ascii_case("image/svg+xml"),
+                _ => {}
+            }
+        }
+        let Some(href) = href else {
+            continue;
+        };
+        let touch = rel.contains("apple-touch-icon");
+        if !touch && !rel.split_whitespace().any(|rel| rel == "icon") {
+            continue;
+        }
+        if svg
+            || href
+                .split(['?', '#'])
+                .next()
+                .unwrap_or("")
+                .ends_with(".svg")
+        {
+            continue;
+        }
+        if let Some(url) = resolve(page_url, &href) {
+            // Touch icons are 180 pixels unless told otherwise
+            let size = if size == 0 && touch { 180 } else { size };
+            found.push(Candidate { url, size });
+        }
+    }
+    found.sort_by_key(|candidate| std::cmp::Reverse(candidate.size));
+    if let Some(url) = resolve(page_url, "/favicon.ico") {
+        if !found.iter().any(|candidate| candidate.url == url) {
+            found.push(Candidate { url, size: 0 });
+        }
+    }
+    found
+}
+
+/// The address `href` points to from the page at `base`
+fn resolve(base: &str, href: &str) -> Option<String> {
+    let href = href.trim();
+    if href.starts_with("http://") || href.starts_with("https://") {
+        return Some(href.to_string());
+    }
+    if href.is_empty() || href.contains(':') && !href.starts_with('/') {
+        // data:, javascript: and the like
+        return None;
+    }
+    let (scheme, rest) = base.split_once("://")?;
+    if let Some(network_path) = href.strip_prefix("//") {
+        return Some(format!("{}://{}", scheme, network_path));
+    }
+    let host_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let origin = format!("{}://{}", scheme, &rest[..host_end]);
+    if href.starts_with('/') {
+        return Some(format!("{}{}", origin, href));
+    }
+    let path = rest[host_end..].split(['?', '#']).next().unwrap_or("");
+    let dir = path.rfind('/').map_or("/", |slash| &path[..=slash]);
+    Some(format!("{}{}{}", origin, dir, href))
+}
+
+/// The site name of a page address: its host, without `www.` and the top-level domain
+fn site_name(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest
+        .split(['/', '?', '#', ':'])
+        .next()
+        .unwrap_or("")
+        .trim_start_matches("www.");
+    match host.rsplit_once('.') {
+        Some((name, _)) if !name.is_empty() => name.to_string(),
+        _ => host.to_string(),
+    }
+}
+
+fn sanitize(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        "website".to_string()
+    } else {
+        name
+    }
+}
+
+/// File name in the icon dir of an icon fetched with the given `name`, when it is free
+pub fn icon_file_name(name: &str) -> String {
+    format!("{}.png", sanitize(name))
+}
+
+/// `<base>.png` in the icon dir, or `<base>_2.png` and on if it is taken
+fn free_path(icon_dir: &Path, base: &str) -> (PathBuf, String) {
+    let mut name = format!("{}.png", base);
+    let mut counter = 2;
+    while icon_dir.join(&name).exists() {
+        name = format!("{}_{}.png", base, counter);
+        counter += 1;
+    }
+    (icon_dir.join(&name), name)
+}
+
+/// The image centered in a transparent square of [`ICON_SIZE`]; small icons are enlarged
+/// without smoothing, to stay crisp
+fn fit(image: &DynamicImage) -> RgbaImage {
+    let filter = if image.width().max(image.height()) < ICON_SIZE {
+        FilterType::Nearest
+    } else {
+        FilterType::Lanczos3
+    };
+    let scaled = image.resize(ICON_SIZE, ICON_SIZE, filter).to_rgba8();
+    let mut square = RgbaImage::new(ICON_SIZE, ICON_SIZE);
+    let x = (ICON_SIZE - scaled.width()) / 2;
+    let y = (ICON_SIZE - scaled.height()) / 2;
+    imageops::overlay(&mut square, &scaled, x.into(), y.into());
+    square
+}
+
+/// `keydeck --fetch-icon <URL> [--name <NAME>]`
+pub fn print_fetch_icon(url: &str, name: Option<&str>, json: bool) {
+    match fetch_icon(url, name, &keydeck_types::get_icon_dir_path()) {
+        Ok(fetched) if json => println!("{}", serde_json::to_string(&fetched).unwrap_or_default()),
+        Ok(fetched) => println!(
+            "Saved {} ({}x{}) as {}",
+            fetched.source, fetched.width, fetched.height, fetched.icon
+        ),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icon_links_are_ranked_and_resolved() {
+        let html = r#"<head>
+            <link rel="stylesheet" href="/style.css">
+            <link rel="icon" href="img/fav-32.png" sizes="32x32">
+            <LINK REL="apple-touch-icon" HREF='//cdn.example.com/touch.png'>
+            <link rel="icon" type="image/svg+xml" href="/logo.svg">
+            <link rel="shortcut icon" href="/favicon.ico?v=2&amp;x=1">
+        </head>"#;
+        let found = candidates("https://www.example.com/docs/index.html", html);
+        let urls: Vec<&str> = found.iter().map(|c| c.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://cdn.example.com/touch.png",
+                "https://www.example.com/docs/img/fav-32.png",
+                "https://www.example.com/favicon.ico?v=2&x=1",
+                "https://www.example.com/favicon.ico",
+            ]
+        );
+        assert_eq!(site_name("https://www.example.com/docs/"), "example");
+        assert_eq!(
+            fit(&DynamicImage::new_rgba8(16, 8)).dimensions(),
+            (144, 144)
+        );
+    }
+}
@@ -2,6 +2,7 @@
 // Copyright (C) 2025 Panayotis Katsaloulis
 
 use crate::session::{detect_session_type, SessionType};
+use crate::window_match::WindowQuery;
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::{
     Atom, AtomEnum, ClientMessageData, ClientMessageEvent, ConnectionExt, EventMask, Window,
@@ -9,19 +10,14 @@ use x11rb::protocol::xproto::{
 };
 use x11rb::rust_connection::RustConnection;
 
-pub fn set_focus(class: &String, title: &String) -> Result<(), String> {
+pub fn set_focus(query: &WindowQuery) -> Result<(), String> {
     match detect_session_type() {
-        SessionType::X11 => set_focus_x11(class, title),
-        SessionType::Wayland => crate::focus_property_wayland::set_focus(class, title),
+        SessionType::X11 => set_focus_x11(query),
+        SessionType::Wayland => crate::focus_property_wayland::set_focus(query),
     }
 }
 
-fn set_focus_x11(class: &String, title: &String) -> Result<(), String> {
-    // Ensure at least one of class or title is specified
-    if class.is_empty() && title.is_empty() {
-        return Err("At least one of class or title must be specified".to_string());
-    }
-
+fn set_focus_x11(query: &WindowQuery) -> Result<(), String> {
     // Connect to the X server
     let (conn, screen_num) = RustConnection::connect(None)
         .map_err(|e| format!("Failed to connect to X server: {}", e))?;
@@ -31,6 +27,7 @@ fn set_focus_x11(class: &String, title: &String) -> Result<(), String> {
     // Intern necessary atoms
     let net_client_list_atom = intern_atom(&conn, b"_NET_CLIENT_LIST")?;
     let net_wm_name_atom = intern_atom(&conn, b"_NET_WM_NAME")?;
+    let net_wm_pid_atom = intern_atom(&conn, b"_NET_WM_PID")?;
     let utf8_string_atom = intern_atom(&conn, b"UTF8_STRING")?;
     let net_active_window_atom = intern_atom(&conn, b"_NET_ACTIVE_WINDOW")?;
     let wm_class_atom = AtomEnum::WM_CLASS.into();
@@ -58,99 +55,78 @@ fn set_focus_x11(class: &String, title: &String) -> Result<(), String> {
         .value32()
         .ok_or_else(|| "Failed to parse _NET_CLIENT_LIST property".to_string())?;
 
-    // Iterate over each window and attempt to find a match
+    // Collect every matching window, in stacking-list order
+    let mut candidates = Vec::new();
     for window in window_ids {
-        // Get WM_CLASS
         let wm_class = get_wm_class(&conn, window, wm_class_atom)?;
-        // Get window title
         let window_title = get_window_title(
             &conn,
             window,
             net_wm_name_atom,
             utf8_string_atom,
             wm_name_atom,
-        )?;
-
-        // Perform matching
-        // If both class and title are empty, skip (shouldn't happen due to earlier check)
-        // If title is empty, match by class only
-        // If class is empty, match by title only
-        // If both are provided AND they're the same string, use OR logic (either matches)
-        // If both are provided AND they're different, use AND logic (both must match)
-
-        let use_or_logic = !class.is_empty() && !title.is_empty() && class == title;
-
-        let class_match = if !class.is_empty() {
-            if let Some((res_name, res_class)) = &wm_class {
-                let class_target_lower = class.to_lowercase();
-                res_name.to_lowercase().contains(&class_target_lower)
-                    || res_class.to_lowercase().contains(&class_target_lower)
-            } else {
-                false
-            }
+        )?
+        .unwrap_or_default();
+        let pid = if query.pid.is_some() {
+            get_window_pid(&conn, window, net_wm_pid_atom)
         } else {
-            !use_or_logic // If class empty and using OR logic, don't automatically match
+            None
         };
 
-        let title_match = if !title.is_empty() {
-            if let Some(window_title) = &window_title {
-                let title_target_lower = title.to_lowercase();
-                window_title.to_lowercase().contains(&title_target_lower)
-            } else {
-                false
-            }
-        } else {
-            !use_or_logic // If title empty and using OR logic, don't automatically match
-        };
-
-        // Use OR logic if both parameters are the same string, AND logic otherwise
-        let matches = if use_or_logic {
-            class_match || title_match
-        } else {
-            class_match && title_match
+        let classes: Vec<&str> = match &wm_class {
+            Some((res_name, res_class)) => vec![res_name.as_str(), res_class.as_str()],
+            None => Vec::new(),
         };
+        if query.matches(&classes, &window_title, pid) {
+            candidates.push(window);
+        }
+    }
 
-        if matches {
-            // Construct the ClientMessage data
-            let data32 = [
-                2, // Source indication (2 = pager)
-                0, // Timestamp (0 means CurrentTime)
-                0, // Flags (set to 0)
-                0, 0,
-            ];
-
-            let data: ClientMessageData = data32.into();
-
-            let event = ClientMessageEvent {
-                response_type: CLIENT_MESSAGE_EVENT,
-                format: 32,
-                sequence: 0,
-                window, // The window we want to activate
-                type_: net_active_window_atom,
-                data,
-            };
-
-            // Send the event to the root window
-            conn.send_event(
-                false,
-                root,
-                EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
-                &event,
-            )
-            .map_err(|e| format!("Failed to send _NET_ACTIVE_WINDOW event: {}", e))?;
+    let window = query.select(candidates)?;
+
+    // Construct the ClientMessage data
+    let data32 = [
+        2, // Source indication (2 = pager)
+        0, // Timestamp (0 means CurrentTime)
+        0, // Flags (set to 0)
+        0, 0,
+    ];
+
+    let data: ClientMessageData = data32.into();
+
+    let event = ClientMessageEvent {
+        response_type: CLIENT_MESSAGE_EVENT,
+        format: 32,
+        sequence: 0,
+        window, // The window we want to activate
+        type_: net_active_window_atom,
+        data,
+    };
+
+    // Send the event to the root window
+    conn.send_event(
+        false,
+        root,
+        EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+        event,
+    )
+    .map_err(|e| format!("Failed to send _NET_ACTIVE_WINDOW event: {}", e))?;
 
-            // Flush the request to ensure it's sent
-            conn.flush()
-                .map_err(|e| format!("Failed to flush X connection: {}", e))?;
+    // Flush the request to ensure it's sent
+    conn.flush()
+        .map_err(|e| format!("Failed to flush X connection: {}", e))?;
 
-            return Ok(());
-        }
-    }
+    Ok(())
+}
 
-    Err(format!(
-        "No matching window found using class '{}' and title '{}'",
-        class, title
-    ))
+/// Retrieves the owning process id (`_NET_WM_PID`) of a window, if advertised.
+fn get_window_pid(conn: &RustConnection, window: Window, net_wm_pid_atom: Atom) -> Option<u32> {
+    conn.get_property(false, window, net_wm_pid_atom, AtomEnum::CARDINAL, 0, 1)
+        .ok()?
+        .reply()
+        .ok()?
+        .value32()?
+        .next()
 }
 
 /// Helper function to intern an atom and return its identifier.
@@ -2,18 +2,19 @@
 // Copyright (C) 2025 Panayotis Katsaloulis
 
 use crate::kwin_script::KWinScriptClient;
+use crate::window_match::WindowQuery;
 
-/// Sets focus to a window matching the given class and title on Wayland
+/// Sets focus to a window matching the given query on Wayland
 ///
 /// Uses KWin D-Bus scripting API for event-driven window activation.
-/// The matching is case-insensitive substring matching.
-pub fn set_focus(class: &String, title: &String) -> Result<(), String> {
+/// The matching itself runs inside the KWin script, mirroring `WindowQuery::matches`.
+pub fn set_focus(query: &WindowQuery) -> Result<(), String> {
     // Create KWin client for this activation
     let client =
         KWinScriptClient::new().map_err(|e| format!("Failed to create KWin client: {}", e))?;
 
     // Use the event-driven activate_window method
-    client.activate_window(class, title).map_err(|e| {
-        format!("Failed to activate window with class '{}' and title '{}': {}", class, title, e)
+    client.activate_window(query).map_err(|e| {
+        format!("Failed to activate window matching {}: {}", query.describe(), e)
     })
 }
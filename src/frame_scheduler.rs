@@ -0,0 +1,481 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Per-device frame scheduler (`max_fps` in the device configuration).
+//!
+//! Animations, marquees and meters can redraw keys far faster than cheap devices accept
+//! images over USB; too many writes drop frames or even disconnect the device. The
+//! [`FrameScheduler`] keeps only the latest image of each key and sends them together,
+//! at most `max_fps` times per second. A flush after a quiet period goes out at once, so
+//! presses still get immediate feedback. The image of a pressed key never waits, and is
+//! written before the other keys of its batch.
+//!
+//! A batch is written as a whole: keys whose image fails to reach the device are retried
+//! after the rest, and keys that still fail are cleared, so a USB hiccup in the middle of
+//! a page change never leaves old and new images mixed. Watchers get a `key_images_failed`
+//! event naming those keys.
+
+use crate::device_trait::{DeviceError, DeviceReader, KeydeckDevice};
+use crate::live_events::{self, LiveEvent};
+use crate::{error_log, verbose_log, warn_log};
+use image::DynamicImage;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Update rate of devices without `max_fps`
+pub const DEFAULT_MAX_FPS: u32 = 30;
+
+/// Further attempts for key images that failed to reach the device
+const IMAGE_RETRIES: usize = 2;
+
+/// Pause before retrying failed key images, to let the device recover
+const RETRY_PAUSE: Duration = Duration::from_millis(20);
+
+/// Latest content of a key, not yet sent to the device
+enum Frame {
+    Image(DynamicImage),
+    Clear,
+}
+
+#[derive(Default)]
+struct Queue {
+    frames: BTreeMap<u8, Frame>,
+    /// When the oldest queued frame arrived
+    since: Option<Instant>,
+    /// The renderer finished a batch that should go out as soon as allowed
+    flush_requested: bool,
+    /// Key whose next image is sent first and without waiting for the interval
+    priority: Option<u8>,
+    last_flush: Option<Instant>,
+    closed: bool,
+}
+
+struct Shared {
+    inner: Box<dyn KeydeckDevice>,
+    max_fps: Arc<AtomicU32>,
+    queue: Mutex<Queue>,
+    wakeup: Condvar,
+    /// Held while frames are written, so batches reach the device in order
+    writing: Mutex<()>,
+}
+
+impl Shared {
+    /// Shortest time between two batches; None when unlimited
+    fn interval(&self) -> Option<Duration> {
+        match self.max_fps.load(Ordering::Relaxed) {
+            0 => None,
+            fps => Some(Duration::from_secs_f64(1.0 / fps as f64)),
+        }
+    }
+
+    /// When the queued frames should be sent
+    fn due(&self, queue: &Queue) -> Option<Instant> {
+        let since = queue.since?;
+        let priority = queue
+            .priority
+            .is_some_and(|key| queue.frames.contains_key(&key));
+        let Some(interval) = self.interval().filter(|_| !priority) else {
+            return Some(since);
+        };
+        let allowed = queue.last_flush.map_or(since, |last| last + interval);
+        if queue.flush_requested {
+            Some(allowed)
+        } else {
+            // Frames without a flush are sent once they waited a whole interval
+            Some(allowed.max(since + interval))
+        }
+    }
+
+    fn queue(&self, button_idx: u8, frame: Frame) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.frames.insert(button_idx, frame);
+        queue.since.get_or_insert_with(Instant::now);
+        self.wakeup.notify_one();
+    }
+
+    /// Sends all queued frames and flushes the device
+    fn send_queued(&self) -> Result<(), DeviceError> {
+        let _writing = self.writing.lock().unwrap();
+        let (frames, priority) = {
+            let mut queue = self.queue.lock().unwrap();
+            queue.since = None;
+            queue.flush_requested = false;
+            queue.last_flush = Some(Instant::now());
+            (std::mem::take(&mut queue.frames), queue.priority.take())
+        };
+        let total = frames.len();
+        let mut pending: Vec<(u8, Frame)> = frames.into_iter().collect();
+        // Stable, so the other keys keep their order
+        pending.sort_by_key(|(button_idx, _)| Some(*button_idx) != priority);
+        let mut last_error = None;
+        for attempt in 0..=IMAGE_RETRIES {
+            if attempt > 0 {
+                thread::sleep(RETRY_PAUSE);
+            }
+            pending.retain(|(button_idx, frame)| match self.write(*button_idx, frame) {
+                Ok(()) => false,
+                Err(e) => {
+                    last_error = Some(e);
+                    true
+                }
+            });
+            if pending.is_empty() {
+                break;
+            }
+            verbose_log!(
+                "Sent {}/{} key images, retrying {}",
+                total - pending.len(),
+                total,
+                pending.len()
+            );
+        }
+        if let Some(error) = last_error.filter(|_| !pending.is_empty()) {
+            self.recover(&pending, total, error);
+        }
+        self.inner.flush()
+    }
+
+    fn write(&self, button_idx: u8, frame: &Frame) -> Result<(), DeviceError> {
+        match frame {
+            Frame::Image(image) => self.inner.set_button_image(button_idx, image.clone()),
+            Frame::Clear => self.inner.clear_button_image(button_idx),
+        }
+    }
+
+    /// Clears the keys whose images could not be written, so they don't keep showing
+    /// the previous page, and tells the watchers
+    fn recover(&self, failed: &[(u8, Frame)], total: usize, error: DeviceError) {
+        let buttons: Vec<u8> = failed
+            .iter()
+            .map(|(button_idx, _)| button_idx + 1)
+            .collect();
+        let cleared = failed
+            .iter()
+            .all(|(button_idx, _)| self.inner.clear_button_image(*button_idx).is_ok());
+        let sn = self.inner.serial_number().unwrap_or_default();
+        warn_log!(
+            "{} of {} key images failed on device {} ({}), keys {:?} {}",
+            failed.len(),
+            total,
+            sn,
+            error,
+            buttons,
+            if cleared {
+                "cleared"
+            } else {
+                "left inconsistent"
+            }
+        );
+        live_events::publish(LiveEvent::KeyImagesFailed {
+            sn,
+            buttons,
+            written: total - failed.len(),
+            cleared,
+            error: error.to_string(),
+        });
+    }
+
+    /// Drops queued frames, e.g. when all keys are cleared anyway
+    fn discard_queued(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.frames.clear();
+        queue.since = None;
+        queue.flush_requested = false;
+        queue.priority = None;
+    }
+}
+
+/// A device whose key images are coalesced and sent at a limited rate
+pub struct FrameScheduler {
+    shared: Arc<Shared>,
+}
+
+impl FrameScheduler {
+    /// Wraps `inner`; `max_fps` can be changed while running, 0 disables the limit
+    pub fn new(inner: Box<dyn KeydeckDevice>, max_fps: Arc<AtomicU32>) -> Self {
+        let shared = Arc::new(Shared {
+            inner,
+            max_fps,
+            queue: Mutex::new(Queue::default()),
+            wakeup: Condvar::new(),
+            writing: Mutex::new(()),
+        });
+        let worker = shared.clone();
+        thread::spawn(move || deliver_frames(&worker));
+        FrameScheduler { shared }
+    }
+}
+
+/// Sends frames that are due; ends when the scheduler is dropped
+fn deliver_frames(shared: &Shared) {
+    let mut queue = shared.queue.lock().unwrap();
+    loop {
+        if queue.closed {
+            return;
+        }
+        match shared.due(&queue) {
+            None => queue = shared.wakeup.wait(queue).unwrap(),
+            Some(due) => {
+                let now = Instant::now();
+                if due > now {
+                    queue = shared.wakeup.wait_timeout(queue, due - now).unwrap().0;
+                    continue;
+                }
+                drop(queue);
+                if let Err(e) = shared.send_queued() {
+                    error_log!("Error while sending key images: {}", e);
+                }
+                queue = shared.queue.lock().unwrap();
+            }
+        }
+    }
+}
+
+impl Drop for FrameScheduler {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().closed = true;
+        self.shared.wakeup.notify_one();
+    }
+}
+
+impl KeydeckDevice for FrameScheduler {
+    fn serial_number(&self) -> Result<String, DeviceError> {
+        self.shared.inner.serial_number()
+    }
+
+    fn firmware_version(&self) -> Result<String, DeviceError> {
+        self.shared.inner.firmware_version()
+    }
+
+    fn manufacturer(&self) -> String {
+        self.shared.inner.manufacturer()
+    }
+
+    fn kind_name(&self) -> String {
+        self.shared.inner.kind_name()
+    }
+
+    fn button_count(&self) -> u8 {
+        self.shared.inner.button_count()
+    }
+
+    fn has_screen(&self) -> bool {
+        self.shared.inner.has_screen()
+    }
+
+    fn button_image_size(&self) -> (u16, u16) {
+        self.shared.inner.button_image_size()
+    }
+
+    fn button_layout(&self) -> (usize, usize) {
+        self.shared.inner.button_layout()
+    }
+
+    fn encoder_count(&self) -> usize {
+        self.shared.inner.encoder_count()
+    }
+
+    fn reset(&self) -> Result<(), DeviceError> {
+        self.shared.discard_queued();
+        self.shared.inner.reset()
+    }
+
+    fn set_brightness(&self, brightness: u8) -> Result<(), DeviceError> {
+        self.shared.inner.set_brightness(brightness)
+    }
+
+    fn set_button_image(&self, button_idx: u8, image: DynamicImage) -> Result<(), DeviceError> {
+        self.shared.queue(button_idx, Frame::Image(image));
+        Ok(())
+    }
+
+    fn clear_button_image(&self, button_idx: u8) -> Result<(), DeviceError> {
+        self.shared.queue(button_idx, Frame::Clear);
+        Ok(())
+    }
+
+    fn clear_all_button_images(&self) -> Result<(), DeviceError> {
+        self.shared.discard_queued();
+        self.shared.inner.clear_all_button_images()
+    }
+
+    fn flush(&self) -> Result<(), DeviceError> {
+        let send_now = {
+            let mut queue = self.shared.queue.lock().unwrap();
+            if queue.frames.is_empty() {
+                false
+            } else {
+                queue.flush_requested = true;
+                self.shared
+                    .due(&queue)
+                    .is_some_and(|due| due <= Instant::now())
+            }
+        };
+        if send_now {
+            // Nothing was sent for a while: no reason to wait
+            self.shared.send_queued()
+        } else {
+            // Queued frames go out with the next batch
+            self.shared.wakeup.notify_one();
+            Ok(())
+        }
+    }
+
+    fn get_reader(&self) -> Arc<dyn DeviceReader> {
+        self.shared.inner.get_reader()
+    }
+
+    fn prioritize_button(&self, button_idx: u8) {
+        self.shared.queue.lock().unwrap().priority = Some(button_idx);
+    }
+
+    fn supports_button_press_feedback(&self) -> bool {
+        self.shared.inner.supports_button_press_feedback()
+    }
+
+    fn shutdown(&self) -> Result<(), DeviceError> {
+        self.shared.discard_queued();
+        self.shared.inner.shutdown()
+    }
+
+    fn sleep(&self) -> Result<(), DeviceError> {
+        self.shared.discard_queued();
+        self.shared.inner.sleep()
+    }
+
+    fn keep_alive(&self) {
+        self.shared.inner.keep_alive()
+    }
+
+    fn lcd_strip_size(&self) -> Option<(u16, u16)> {
+        self.shared.inner.lcd_strip_size()
+    }
+
+    fn write_lcd_fill(&self, x: u16, y: u16, image: &DynamicImage) -> Result<(), DeviceError> {
+        self.shared.inner.write_lcd_fill(x, y, image)
+    }
+
+    fn write_lcd(
+        &self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        image: &DynamicImage,
+    ) -> Result<(), DeviceError> {
+        self.shared.inner.write_lcd(x, y, width, height, image)
+    }
+
+    fn background_image_size(&self) -> Option<(u16, u16)> {
+        self.shared.inner.background_image_size()
+    }
+
+    fn set_background_image(&self, image: DynamicImage) -> Result<(), DeviceError> {
+        self.shared.inner.set_background_image(image)
+    }
+
+    fn clear_background_image(&self) -> Result<(), DeviceError> {
+        self.shared.inner.clear_background_image()
+    }
+
+    fn set_boot_logo(&self, image: DynamicImage) -> Result<(), DeviceError> {
+        self.shared.inner.set_boot_logo(image)
+    }
+
+    fn led_count(&self) -> u8 {
+        self.shared.inner.led_count()
+    }
+
+    fn set_led_brightness(&self, brightness: u8) -> Result<(), DeviceError> {
+        self.shared.inner.set_led_brightness(brightness)
+    }
+
+    fn set_led_color(&self, colors: &[(u8, u8, u8)]) -> Result<(), DeviceError> {
+        self.shared.inner.set_led_color(colors)
+    }
+
+    fn reset_led_color(&self) -> Result<(), DeviceError> {
+        self.shared.inner.reset_led_color()
+    }
+
+    fn wakeup(&self) -> Result<(), DeviceError> {
+        self.shared.inner.wakeup()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_device::{TestDevice, Written};
+
+    fn scheduler(failures: usize, max_fps: u32) -> (FrameScheduler, Written) {
+        let device = TestDevice::default();
+        device.failures.store(failures, Ordering::Relaxed);
+        let written = device.written.clone();
+        let scheduler = FrameScheduler::new(Box::new(device), Arc::new(AtomicU32::new(max_fps)));
+        (scheduler, written)
+    }
+
+    fn send_batch(failures: usize) -> Vec<(u8, bool)> {
+        let (scheduler, written) = scheduler(failures, 0);
+        for button_idx in 0..3 {
+            scheduler
+                .shared
+                .queue(button_idx, Frame::Image(DynamicImage::new_rgb8(8, 8)));
+        }
+        scheduler.shared.send_queued().unwrap();
+        let written = written.lock().unwrap().clone();
+        written
+    }
+
+    #[test]
+    fn failed_key_images_are_retried_then_cleared() {
+        assert_eq!(send_batch(1), [(0, true), (2, true), (1, true)]);
+        assert_eq!(
+            send_batch(IMAGE_RETRIES + 1),
+            [(0, true), (2, true), (1, false)]
+        );
+    }
+
+    #[test]
+    fn updates_of_a_key_are_coalesced_until_the_next_batch() {
+        let (scheduler, written) = scheduler(0, 1);
+        scheduler.shared.queue.lock().unwrap().last_flush = Some(Instant::now());
+        scheduler
+            .set_button_image(0, DynamicImage::new_rgb8(8, 8))
+            .unwrap();
+        scheduler.clear_button_image(0).unwrap();
+        scheduler.flush().unwrap();
+        // A batch just went out at 1 fps, so this one waits for the next second
+        {
+            let queue = scheduler.shared.queue.lock().unwrap();
+            let due = scheduler.shared.due(&queue).unwrap();
+            assert!(due >= queue.last_flush.unwrap() + Duration::from_secs(1));
+        }
+        assert!(written.lock().unwrap().is_empty());
+        scheduler.shared.send_queued().unwrap();
+        assert_eq!(*written.lock().unwrap(), [(0, false)]);
+    }
+
+    #[test]
+    fn a_pressed_key_goes_out_first_and_at_once() {
+        let (scheduler, written) = scheduler(0, 1);
+        scheduler.shared.queue.lock().unwrap().last_flush = Some(Instant::now());
+        scheduler.clear_button_image(0).unwrap();
+        scheduler.clear_button_image(1).unwrap();
+        scheduler.prioritize_button(2);
+        scheduler.clear_button_image(2).unwrap();
+        // Sent at once, although a batch just went out at 1 fps
+        scheduler.flush().unwrap();
+        // The writer thread may have sent them first
+        drop(scheduler.shared.writing.lock().unwrap());
+        assert_eq!(
+            *written.lock().unwrap(),
+            [(2, false), (0, false), (1, false)]
+        );
+        assert_eq!(scheduler.shared.queue.lock().unwrap().priority, None);
+    }
+}
@@ -0,0 +1,217 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Action history: every action sequence the daemon runs is appended as one JSON line
+//! to [`keydeck::get_history_path`], with the device, page, trigger, result and
+//! duration. The file is rotated to `history.jsonl.1` once it grows past
+//! [`MAX_HISTORY_BYTES`], so at most two generations are kept.
+
+use crate::error::Error;
+use crate::error_log;
+use crate::pages::Action;
+use chrono::{Local, SecondsFormat};
+use keydeck::get_history_path;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Size after which the history file is rotated
+const MAX_HISTORY_BYTES: u64 = 1024 * 1024;
+
+/// Longest description kept for a single action
+const MAX_ACTION_CHARS: usize = 60;
+
+/// One run of an action sequence
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Local time the sequence started, RFC 3339
+    pub time: String,
+    pub device: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<String>,
+    /// What ran the sequence: "button", "hotkey <combination>", "encoder <n>", "tick", ...
+    pub trigger: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub button: Option<u8>,
+    /// Short description of each action, e.g. "exec: firefox"
+    pub actions: Vec<String>,
+    /// Error of a failed sequence; None when it succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Index of the failed action in `actions`, followed by its index in each nested
+    /// sequence (`try`, `and`, macros...) the failure came from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_action: Vec<usize>,
+    /// Time spent until the sequence finished or paused on a `wait`/`wait_for`
+    pub duration_ms: u64,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        device: &str,
+        page: Option<String>,
+        trigger: &str,
+        button: Option<u8>,
+        actions: &[Action],
+        result: &Result<(), Error>,
+        duration: Duration,
+    ) -> Self {
+        HistoryEntry {
+            time: Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+            device: device.to_string(),
+            page,
+            trigger: trigger.to_string(),
+            button,
+            actions: actions.iter().map(describe_action).collect(),
+            error: result.as_ref().err().map(|e| e.cause().to_string()),
+            failed_action: result
+                .as_ref()
+                .err()
+                .map(Error::action_path)
+                .unwrap_or_default(),
+            duration_ms: duration.as_millis() as u64,
+        }
+    }
+}
+
+/// Describes an action by its name, plus its argument when that is a single value
+pub fn describe_action(action: &Action) -> String {
+    let description = match serde_json::to_value(action) {
+        Ok(serde_json::Value::Object(map)) => match map.iter().next() {
+            Some((name, serde_json::Value::String(arg))) => format!("{}: {}", name, arg),
+            Some((name, serde_json::Value::Number(arg))) => format!("{}: {}", name, arg),
+            Some((name, _)) => name.clone(),
+            None => String::new(),
+        },
+        Ok(serde_json::Value::String(name)) => name,
+        _ => "?".to_string(),
+    };
+    if description.chars().count() > MAX_ACTION_CHARS {
+        let short: String = description.chars().take(MAX_ACTION_CHARS - 1).collect();
+        format!("{}…", short)
+    } else {
+        description
+    }
+}
+
+fn rotated_path() -> PathBuf {
+    let mut path = get_history_path().into_os_string();
+    path.push(".1");
+    PathBuf::from(path)
+}
+
+/// Appends an entry to the history file, rotating it when it is too large
+pub fn record(entry: &HistoryEntry) {
+    let path = get_history_path();
+    if fs::metadata(&path).is_ok_and(|meta| meta.len() > MAX_HISTORY_BYTES) {
+        let _ = fs::rename(&path, rotated_path());
+    }
+    let line = match serde_json::to_string(entry) {
+        Ok(line) => line + "\n",
+        Err(e) => {
+            error_log!("Error while serializing action history: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+    {
+        error_log!(
+            "Error while writing action history {}: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+/// The most recent `limit` entries, oldest first
+pub fn read_recent(limit: usize) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = [rotated_path(), get_history_path()]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|content| {
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let skip = entries.len().saturating_sub(limit);
+    entries.drain(..skip);
+    entries
+}
+
+/// `keydeck --history`: prints the most recent entries as a table, or as JSON
+pub fn print_history(limit: usize, json: bool) {
+    let entries = read_recent(limit);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&entries).unwrap_or_default()
+        );
+        return;
+    }
+    if entries.is_empty() {
+        println!("No actions recorded in {}", get_history_path().display());
+        return;
+    }
+    for entry in entries {
+        let time = entry
+            .time
+            .get(..19)
+            .unwrap_or(&entry.time)
+            .replace('T', " ");
+        let source = match entry.button {
+            Some(button) => format!("{} {}", entry.trigger, button),
+            None => entry.trigger.clone(),
+        };
+        let status = match entry.error {
+            Some(_) => "FAILED",
+            None => "ok",
+        };
+        println!(
+            "{}  {}  {}  {}  {:>5}ms  {}  {}",
+            time,
+            entry.device,
+            entry.page.as_deref().unwrap_or("-"),
+            source,
+            entry.duration_ms,
+            status,
+            entry.actions.join(", ")
+        );
+        if let Some(error) = &entry.error {
+            match entry.failed_action.is_empty() {
+                true => println!("    {}", error),
+                false => {
+                    let path: Vec<String> = entry
+                        .failed_action
+                        .iter()
+                        .map(|index| (index + 1).to_string())
+                        .collect();
+                    println!("    action {}: {}", path.join(" > "), error)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_are_described_briefly() {
+        let actions: Vec<Action> =
+            serde_yaml_ng::from_str("[{exec: firefox}, {jump: Main}, {wait: 1.5}]").unwrap();
+        let described: Vec<String> = actions.iter().map(describe_action).collect();
+        assert_eq!(described, ["exec: firefox", "jump: Main", "wait: 1.5"]);
+
+        let long: Action = serde_yaml_ng::from_str(&format!("exec: {}", "x".repeat(100))).unwrap();
+        assert_eq!(describe_action(&long).chars().count(), MAX_ACTION_CHARS);
+    }
+}
@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! The `http` action: a request to a web address, e.g. a Home Assistant, IFTTT or CI
+//! webhook, sent by the daemon itself instead of an `exec` of curl. HTTPS is supported
+//! out of the box; the response body can be kept in a context variable for keys to show.
+
+use indexmap::IndexMap;
+use std::time::Duration;
+
+/// Seconds a request may take, unless the action sets its own `timeout`
+const DEFAULT_TIMEOUT: f32 = 10.0;
+
+/// Most of a response body that is read
+const RESPONSE_LIMIT: u64 = 64 * 1024;
+
+/// Method of a request: the one given, or POST when there is a body and GET otherwise
+fn method_of(method: Option<&str>, body: Option<&str>) -> String {
+    match method {
+        Some(method) => method.trim().to_ascii_uppercase(),
+        None if body.is_some() => "POST".to_string(),
+        None => "GET".to_string(),
+    }
+}
+
+fn with_headers<B>(
+    mut request: ureq::RequestBuilder<B>,
+    headers: Option<&IndexMap<String, String>>,
+) -> ureq::RequestBuilder<B> {
+    for (name, value) in headers.into_iter().flatten() {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    request
+}
+
+/// Sends a request and returns its response body. Fails when the request cannot be
+/// sent, takes longer than `timeout` seconds or its status is not a success.
+pub fn send_request(
+    url: &str,
+    method: Option<&str>,
+    headers: Option<&IndexMap<String, String>>,
+    body: Option<&str>,
+    timeout: Option<f32>,
+) -> Result<String, String> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(format!("Not a web address: {}", url));
+    }
+    let seconds = timeout.unwrap_or(DEFAULT_TIMEOUT).max(0.1);
+    let timeout = Duration::try_from_secs_f32(seconds)
+        .map_err(|_| format!("Invalid timeout: {} seconds", seconds))?;
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(timeout))
+        .user_agent(concat!("keydeck/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .into();
+
+    let method = method_of(method, body);
+    let payload = body.unwrap_or_default().as_bytes();
+    let response = match method.as_str() {
+        "GET" => with_headers(agent.get(url), headers).call(),
+        "HEAD" => with_headers(agent.head(url), headers).call(),
+        "DELETE" => with_headers(agent.delete(url), headers).call(),
+        "POST" => with_headers(agent.post(url), headers).send(payload),
+        "PUT" => with_headers(agent.put(url), headers).send(payload),
+        "PATCH" => with_headers(agent.patch(url), headers).send(payload),
+        _ => return Err(format!("Unsupported HTTP method '{}'", method)),
+    };
+    let mut response = response.map_err(|e| format!("{} {} failed: {}", method, url, e))?;
+    if method == "HEAD" {
+        return Ok(String::new());
+    }
+    response
+        .body_mut()
+        .with_config()
+        .limit(RESPONSE_LIMIT)
+        .read_to_string()
+        .map_err(|e| format!("Failed to read the response of {}: {}", url, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn method_follows_the_body() {
+        assert_eq!(method_of(None, None), "GET");
+        assert_eq!(method_of(None, Some("{}")), "POST");
+        assert_eq!(method_of(Some(" put"), Some("{}")), "PUT");
+    }
+
+    #[test]
+    fn timeouts_out_of_range_are_rejected() {
+        let sent = send_request("http://localhost/", None, None, None, Some(f32::INFINITY));
+        assert_eq!(sent.unwrap_err(), "Invalid timeout: inf seconds");
+    }
+}
@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Translated labels for `${i18n:key}`, from the `strings` of the configuration.
+//!
+//! The locale is the configured `locale`, or else the one of the session (`LC_ALL`,
+//! `LC_MESSAGES`, `LANG`). A key is looked up in that locale (`de_AT`), then in its
+//! language (`de`), then in English, so shared page packs need only translate what
+//! differs.
+
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::env;
+
+/// Locale used for keys missing in the selected one
+const FALLBACK_LOCALE: &str = "en";
+
+/// Translations of the selected locale, by key
+pub type Translations = HashMap<String, String>;
+
+/// The locale of the session, e.g. `de_AT` for `LANG=de_AT.UTF-8`
+fn session_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .split(['.', '@'])
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        })
+        .filter(|locale| !locale.is_empty() && locale != "C" && locale != "POSIX")
+}
+
+/// Compares locales ignoring case and `-`/`_`, so `pt-BR` matches `pt_br`
+fn same_locale(a: &str, b: &str) -> bool {
+    let normal = |locale: &str| locale.replace('-', "_").to_ascii_lowercase();
+    normal(a) == normal(b)
+}
+
+/// Locales to look a key up in, the preferred first
+fn lookup_order(locale: &str) -> Vec<String> {
+    let mut order = vec![locale.to_string()];
+    if let Some((language, _)) = locale.split_once(['_', '-']) {
+        order.push(language.to_string());
+    }
+    order.push(FALLBACK_LOCALE.to_string());
+    order
+}
+
+/// Resolves the translations of the configured `strings` for `locale`, or for the
+/// locale of the session when none is configured. Returns the locale used as well.
+pub fn translations(
+    strings: Option<&IndexMap<String, IndexMap<String, String>>>,
+    locale: Option<&str>,
+) -> (String, Translations) {
+    let locale = locale
+        .map(str::to_string)
+        .or_else(session_locale)
+        .unwrap_or_else(|| FALLBACK_LOCALE.to_string());
+    let mut translations = Translations::new();
+    // The least preferred locale goes first, so the preferred ones overwrite it
+    for candidate in lookup_order(&locale).iter().rev() {
+        let found = strings
+            .into_iter()
+            .flatten()
+            .filter(|(name, _)| same_locale(name, candidate));
+        for (_, entries) in found {
+            translations.extend(entries.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+    }
+    (locale, translations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_fall_back_to_language_then_english() {
+        let strings: IndexMap<String, IndexMap<String, String>> = serde_yaml_ng::from_str(
+            "en: { copy: Copy, paste: Paste, cut: Cut }\nde: { copy: Kopieren, paste: Einfügen }\nde-AT: { paste: Einfügen! }\n",
+        )
+        .unwrap();
+        let (locale, translations) = translations(Some(&strings), Some("de_AT"));
+        assert_eq!(locale, "de_AT");
+        assert_eq!(translations["copy"], "Kopieren");
+        assert_eq!(translations["paste"], "Einfügen!");
+        assert_eq!(translations["cut"], "Cut");
+    }
+}
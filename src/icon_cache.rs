@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Icons scaled to the key size of a device, kept in the icon cache shared with the
+//! configuration UI, so large icons are decoded and resized once instead of on every
+//! start. The cache is pruned when the daemon starts and emptied by `--clear-cache`.
+//!
+//! While the daemon runs, scaled icons are also kept in memory, in an atlas shared by
+//! the devices with the same key size: animated and often refreshed buttons are then
+//! composed without reading or decoding their icon again.
+
+use crate::image_formats;
+#[allow(unused_imports)]
+use crate::verbose_log;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageResult, RgbaImage};
+use keydeck_types::icon_cache::{cache_entry, is_fresh};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::SystemTime;
+
+/// Scaled icons the atlas holds before it starts over
+const ATLAS_CAPACITY: usize = 512;
+
+/// Scaled icons by path and key size, with the modification time of their source
+type Atlas = HashMap<(String, u32, u32), (SystemTime, Arc<RgbaImage>)>;
+
+static ATLAS: LazyLock<Mutex<Atlas>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The icon at `path` fitted to `width`x`height`, from the atlas while its source is
+/// unchanged
+pub fn atlas_icon(path: &str, width: u32, height: u32) -> ImageResult<Arc<RgbaImage>> {
+    let modified = std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok();
+    let key = (path.to_string(), width, height);
+    if let Some((time, icon)) = ATLAS.lock().unwrap().get(&key) {
+        if Some(*time) == modified {
+            return Ok(icon.clone());
+        }
+    }
+
+    let icon = Arc::new(fitted_icon(path, width, height)?.into_rgba8());
+    if let Some(modified) = modified {
+        let mut atlas = ATLAS.lock().unwrap();
+        if atlas.len() >= ATLAS_CAPACITY {
+            atlas.clear();
+        }
+        atlas.insert(key, (modified, icon.clone()));
+    }
+    Ok(icon)
+}
+
+/// The icon at `path`, scaled to fit `width`x`height` while keeping its aspect ratio
+pub fn fitted_icon(path: &str, width: u32, height: u32) -> ImageResult<DynamicImage> {
+    let (img_width, img_height) = image_formats::dimensions(path)?;
+    let scale = (width as f32 / img_width as f32).min(height as f32 / img_height as f32);
+    let new_width = (img_width as f32 * scale) as u32;
+    let new_height = (img_height as f32 * scale) as u32;
+
+    let source = Path::new(path);
+    let entry = cache_entry(source, &format!("@{}x{}", new_width, new_height));
+    if is_fresh(&entry, source) {
+        if let Ok(cached) = image::open(&entry) {
+            return Ok(cached);
+        }
+    }
+
+    let resized =
+        image_formats::open(path)?.resize_exact(new_width, new_height, FilterType::Lanczos3);
+    // Only shrinking is worth keeping; small icons are as quick to read as their copy
+    if img_width > new_width {
+        if let Some(dir) = entry.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Err(e) = resized.save(&entry) {
+            verbose_log!("Could not cache {}: {}", entry.display(), e);
+        }
+    }
+    Ok(resized)
+}
+
+/// Evicts old entries of the icon cache, without delaying the start
+pub fn prune_in_background() {
+    std::thread::spawn(|| {
+        let removed = keydeck_types::prune_icon_cache(keydeck_types::IconCacheLimits::default());
+        if removed.files > 0 {
+            verbose_log!(
+                "Removed {} icons ({} KB) from the icon cache",
+                removed.files,
+                removed.bytes / 1024
+            );
+        }
+    });
+}
+
+/// `keydeck --clear-cache`
+pub fn print_clear_cache(json: bool) {
+    match keydeck_types::clear_icon_cache() {
+        Ok(removed) if json => println!("{}", serde_json::to_string(&removed).unwrap_or_default()),
+        Ok(removed) => println!(
+            "Removed {} icons ({} KB) from {}",
+            removed.files,
+            removed.bytes / 1024,
+            keydeck_types::get_icon_cache_dir().display()
+        ),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+    use std::time::Duration;
+
+    #[test]
+    fn icons_are_scaled_once_per_key_size_until_they_change() {
+        let dir = std::env::temp_dir().join(format!("keydeck-atlas-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("icon.png");
+        let icon = path.to_str().unwrap();
+        RgbaImage::from_pixel(16, 16, Rgba([255, 0, 0, 255]))
+            .save(&path)
+            .unwrap();
+
+        let first = atlas_icon(icon, 72, 72).unwrap();
+        assert_eq!(first.dimensions(), (72, 72));
+        assert!(Arc::ptr_eq(&first, &atlas_icon(icon, 72, 72).unwrap()));
+        assert_eq!(atlas_icon(icon, 36, 36).unwrap().dimensions(), (36, 36));
+
+        // An edited icon is read again
+        RgbaImage::from_pixel(16, 16, Rgba([0, 0, 255, 255]))
+            .save(&path)
+            .unwrap();
+        let later = SystemTime::now() + Duration::from_secs(10);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(later))
+            .unwrap();
+        let edited = atlas_icon(icon, 72, 72).unwrap();
+        assert_eq!(edited.get_pixel(36, 36), &Rgba([0, 0, 255, 255]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
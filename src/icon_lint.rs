@@ -0,0 +1,304 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Icons that will render badly on the keys they are shown on, for `keydeck --validate`.
+//!
+//! Icons are fitted to the key keeping their aspect ratio, so an icon much smaller than
+//! the key is enlarged and looks blurry, and an icon of another shape leaves empty bands.
+//! A page group named after a device serial is checked against the key size of that
+//! device when it is connected; everything else against the largest key of the
+//! connected devices, or [`DEFAULT_KEY_SIZE`] when none is.
+//!
+//! With `--fix`, small icons get a copy enlarged without smoothing by a whole factor, as
+//! favicons are, next to them (`name@96px.png`), and the configuration is changed to use
+//! the copies.
+
+use crate::image_formats;
+use crate::pages::{ButtonConfig, KeyDeckConf};
+use image::imageops::FilterType;
+use keydeck_types::pages::{Icon, IconLayer};
+use keydeck_types::IconPaths;
+use serde_yaml_ng::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Key size assumed when no device is connected, the size of the largest common keys
+pub const DEFAULT_KEY_SIZE: (u32, u32) = (96, 96);
+
+/// An icon is small when it has to be enlarged more than this to fit its key
+const MAX_UPSCALE: f32 = 2.0;
+
+/// Icon and key shapes differing by more than this leave visible bands
+const MAX_ASPECT_DIFFERENCE: f32 = 1.25;
+
+/// What is wrong with an icon
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Issue {
+    /// Enlarged more than [`MAX_UPSCALE`] times
+    Small,
+    /// Shaped unlike the key
+    Aspect,
+}
+
+/// An icon that will render badly
+#[derive(Debug, PartialEq)]
+pub struct IconLint {
+    pub icon: String,
+    pub size: (u32, u32),
+    /// The largest key it is shown on
+    pub key: (u32, u32),
+    pub issues: Vec<Issue>,
+}
+
+impl IconLint {
+    pub fn message(&self) -> String {
+        let (width, height) = self.size;
+        let (key_width, key_height) = self.key;
+        let problems: Vec<&str> = self
+            .issues
+            .iter()
+            .map(|issue| match issue {
+                Issue::Small => "is enlarged and will look blurry (--fix makes a sharp copy)",
+                Issue::Aspect => "is not shaped like the key and leaves empty bands",
+            })
+            .collect();
+        format!(
+            "Icon '{}' ({}x{}) on {}x{} keys {}",
+            self.icon,
+            width,
+            height,
+            key_width,
+            key_height,
+            problems.join(" and ")
+        )
+    }
+}
+
+/// Issues of an icon of `size` fitted to a key of `key` size
+fn issues(size: (u32, u32), key: (u32, u32)) -> Vec<Issue> {
+    let (width, height) = (size.0.max(1) as f32, size.1.max(1) as f32);
+    let (key_width, key_height) = (key.0 as f32, key.1 as f32);
+    let mut issues = Vec::new();
+    if (key_width / width).min(key_height / height) > MAX_UPSCALE {
+        issues.push(Issue::Small);
+    }
+    let aspect = (width / height) / (key_width / key_height);
+    if aspect.max(1.0 / aspect) > MAX_ASPECT_DIFFERENCE {
+        issues.push(Issue::Aspect);
+    }
+    issues
+}
+
+/// The larger of two key sizes, by area
+fn larger(a: (u32, u32), b: (u32, u32)) -> (u32, u32) {
+    if b.0 * b.1 > a.0 * a.1 {
+        b
+    } else {
+        a
+    }
+}
+
+/// The static icons of the configuration, with the largest key each is shown on
+fn icon_keys(
+    conf: &KeyDeckConf,
+    sizes: &HashMap<String, (u32, u32)>,
+) -> BTreeMap<String, (u32, u32)> {
+    let fallback = sizes
+        .values()
+        .copied()
+        .reduce(larger)
+        .unwrap_or(DEFAULT_KEY_SIZE);
+    let mut icons: BTreeMap<String, (u32, u32)> = BTreeMap::new();
+    let mut add = |icon: &Option<Icon>, key: (u32, u32)| {
+        // Layers are fitted to their share of the key
+        let layers: Vec<(&str, f32)> = match icon {
+            Some(Icon::File(image)) => vec![(image, 1.0)],
+            Some(Icon::Layers(layers)) => layers
+                .iter()
+                .map(|layer| match layer {
+                    IconLayer::File(image) => (image.as_str(), 1.0),
+                    IconLayer::Detailed { image, scale, .. } => {
+                        (image.as_str(), scale.unwrap_or(1.0))
+                    }
+                })
+                .collect(),
+            None => vec![],
+        };
+        for (image, scale) in layers
+            .into_iter()
+            .filter(|(image, _)| !image.contains("${"))
+        {
+            let key = (
+                ((key.0 as f32 * scale) as u32).max(1),
+                ((key.1 as f32 * scale) as u32).max(1),
+            );
+            let entry = icons.entry(image.to_string()).or_insert(key);
+            *entry = larger(*entry, key);
+        }
+    };
+    for button in conf.buttons.iter().flat_map(|buttons| buttons.values()) {
+        add(&button.icon, fallback);
+    }
+    for (group, pages) in &conf.page_groups {
+        let key = sizes.get(group).copied().unwrap_or(fallback);
+        for page in pages.pages.values() {
+            for button in page.buttons.values() {
+                if let ButtonConfig::Detailed(button) = button {
+                    add(&button.icon, key);
+                }
+            }
+        }
+    }
+    icons
+}
+
+/// Icons of the configuration that will render badly. Icons that cannot be read are left
+/// to the check for missing icons.
+pub fn lint(
+    conf: &KeyDeckConf,
+    icons: &IconPaths,
+    sizes: &HashMap<String, (u32, u32)>,
+) -> Vec<IconLint> {
+    icon_keys(conf, sizes)
+        .into_iter()
+        .filter_map(|(icon, key)| {
+            let size = image_formats::dimensions(icons.resolve(&icon)?).ok()?;
+            let issues = issues(size, key);
+            (!issues.is_empty()).then_some(IconLint {
+                icon,
+                size,
+                key,
+                issues,
+            })
+        })
+        .collect()
+}
+
+/// Name of the enlarged copy of `icon` for keys of `key` size, next to it
+fn copy_name(icon: &str, key: (u32, u32)) -> String {
+    let path = Path::new(icon);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}@{}px.png", stem, key.0.max(key.1)))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Writes an enlarged copy of each small icon and switches the configuration at
+/// `config_path` to the copies. Returns the icons replaced, with their copies.
+pub fn fix(
+    lints: &[IconLint],
+    icons: &IconPaths,
+    config_path: &Path,
+) -> Result<Vec<(String, String)>, String> {
+    keydeck_types::require_yaml(config_path, "Fixing icons")?;
+    let mut renamed = Vec::new();
+    for lint in lints
+        .iter()
+        .filter(|lint| lint.issues.contains(&Issue::Small))
+    {
+        let source = icons.locate(&lint.icon);
+        let image = image_formats::open(&source)
+            .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+        let (width, height) = lint.size;
+        let factor = (lint.key.0 / width.max(1))
+            .min(lint.key.1 / height.max(1))
+            .max(1);
+        let enlarged = image.resize_exact(width * factor, height * factor, FilterType::Nearest);
+        let copy = copy_name(&lint.icon, lint.key);
+        let target = source.with_file_name(Path::new(&copy).file_name().unwrap_or_default());
+        enlarged
+            .save(&target)
+            .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+        renamed.push((lint.icon.clone(), copy));
+    }
+    if renamed.is_empty() {
+        return Ok(renamed);
+    }
+
+    let yaml = std::fs::read_to_string(config_path)
+        .map_err(|e| format!("Failed to read {}: {}", config_path.display(), e))?;
+    let mut conf: Value = serde_yaml_ng::from_str(&yaml)
+        .map_err(|e| format!("Failed to parse {}: {}", config_path.display(), e))?;
+    let renames: HashMap<&str, &str> = renamed
+        .iter()
+        .map(|(icon, copy)| (icon.as_str(), copy.as_str()))
+        .collect();
+    rename_icons(&mut conf, &renames);
+    let yaml = serde_yaml_ng::to_string(&conf)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+    keydeck_types::save_config_file(config_path, &yaml)?;
+    Ok(renamed)
+}
+
+/// Replaces the `icon` fields of a configuration, and the images of layered icons, found
+/// in `renames`
+fn rename_icons(value: &mut Value, renames: &HashMap<&str, &str>) {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                match (key.as_str(), value) {
+                    (Some("icon"), Value::Sequence(layers)) => {
+                        for layer in layers {
+                            match layer {
+                                Value::Mapping(layer) => {
+                                    if let Some(image) = layer.get_mut("image") {
+                                        rename_icon(image, renames);
+                                    }
+                                }
+                                layer => rename_icon(layer, renames),
+                            }
+                        }
+                    }
+                    (Some("icon"), value) => rename_icon(value, renames),
+                    (_, value) => rename_icons(value, renames),
+                }
+            }
+        }
+        Value::Sequence(values) => values
+            .iter_mut()
+            .for_each(|value| rename_icons(value, renames)),
+        _ => {}
+    }
+}
+
+/// Replaces an icon name found in `renames`
+fn rename_icon(value: &mut Value, renames: &HashMap<&str, &str>) {
+    if let Some(copy) = value.as_str().and_then(|icon| renames.get(icon)) {
+        *value = Value::String(copy.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_and_oddly_shaped_icons_are_found() {
+        assert_eq!(issues((16, 16), (96, 96)), [Issue::Small]);
+        assert_eq!(issues((48, 48), (96, 96)), []);
+        assert_eq!(issues((512, 128), (72, 72)), [Issue::Aspect]);
+        assert_eq!(issues((16, 8), (96, 96)), [Issue::Small, Issue::Aspect]);
+        assert_eq!(
+            copy_name("apps/term.svg.png", (96, 96)),
+            "apps/term.svg@96px.png"
+        );
+
+        let mut conf: Value = serde_yaml_ng::from_str(
+            "default:\n  Main:\n    button1:\n      icon: tiny.png\n    button2:\n      icon: big.png\n    button3:\n      icon: [big.png, {image: tiny.png, scale: 0.5}]\n",
+        )
+        .unwrap();
+        rename_icons(&mut conf, &HashMap::from([("tiny.png", "tiny@96px.png")]));
+        assert_eq!(
+            conf["default"]["Main"]["button1"]["icon"].as_str(),
+            Some("tiny@96px.png")
+        );
+        assert_eq!(
+            conf["default"]["Main"]["button2"]["icon"].as_str(),
+            Some("big.png")
+        );
+        assert_eq!(
+            conf["default"]["Main"]["button3"]["icon"][1]["image"].as_str(),
+            Some("tiny@96px.png")
+        );
+    }
+}
@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Icons redrawn in one color and style, so a deck of icons from many sources looks alike
+//! (`keydeck --recolor-icons`, and the configuration UI's icon manager).
+//!
+//! Every PNG of the default icon dir, subdirectories included, is redrawn by
+//! [`graphics::recolor`] into its `recolored` subdirectory under the same name. The
+//! originals are left alone: listing the subdirectory first in `icon_dirs` shows the
+//! recolored icons on every key, and removing it brings the originals back. Running it
+//! again replaces the earlier copies.
+
+use keydeck_render::graphics::{self, IconStyle};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Subdirectory of the icon dir the recolored icons are saved in
+pub const RECOLORED_DIR: &str = "recolored";
+
+/// Color of the icons when none is given
+const DEFAULT_TINT: (u8, u8, u8) = (255, 255, 255);
+
+#[derive(Debug, Default, Serialize)]
+pub struct RecolorReport {
+    /// Where the icons were saved
+    pub directory: String,
+    /// Names of the recolored icons, relative to the icon dir
+    pub recolored: Vec<String>,
+    /// Icons left as they are, with the reason
+    pub skipped: Vec<String>,
+}
+
+/// Redraws the PNG icons of `icon_dir` in `tint` and `style`, into its
+/// [`RECOLORED_DIR`] subdirectory
+pub fn recolor_icons(
+    icon_dir: &Path,
+    tint: (u8, u8, u8),
+    style: IconStyle,
+) -> Result<RecolorReport, String> {
+    let target = icon_dir.join(RECOLORED_DIR);
+    let mut report = RecolorReport {
+        directory: target.display().to_string(),
+        ..Default::default()
+    };
+    let mut icons = Vec::new();
+    find_icons(icon_dir, icon_dir, &target, &mut icons)?;
+    icons.sort();
+
+    for name in icons {
+        let is_png = name
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+        let shown = name.to_string_lossy().replace('\\', "/");
+        if !is_png {
+            report
+                .skipped
+                .push(format!("{}: only PNG icons keep their transparency", shown));
+            continue;
+        }
+        let mut image = match image::open(icon_dir.join(&name)) {
+            Ok(image) => image.to_rgba8(),
+            Err(e) => {
+                report.skipped.push(format!("{}: {}", shown, e));
+                continue;
+            }
+        };
+        graphics::recolor(&mut image, tint, style);
+        let path = target.join(&name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        image
+            .save(&path)
+            .map_err(|e| format!("Failed to save {}: {}", path.display(), e))?;
+        report.recolored.push(shown);
+    }
+    Ok(report)
+}
+
+/// Collects the files below `dir` as names relative to `root`, leaving out `skip`
+fn find_icons(
+    root: &Path,
+    dir: &Path,
+    skip: &Path,
+    icons: &mut Vec<PathBuf>,
+) -> Result<(), String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && dir == root => return Ok(()),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path == skip {
+            continue;
+        }
+        if path.is_dir() {
+            find_icons(root, &path, skip, icons)?;
+        } else if let Ok(name) = path.strip_prefix(root) {
+            icons.push(name.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+/// `keydeck --recolor-icons [--tint <COLOR>] [--style <STYLE>]`
+pub fn print_recolor_icons(tint: Option<&str>, style: Option<&str>, json: bool) {
+    let options = tint
+        .map(graphics::parse_color)
+        .transpose()
+        .and_then(|tint| {
+            let style = style.map(str::parse).transpose()?;
+            Ok((tint.unwrap_or(DEFAULT_TINT), style.unwrap_or_default()))
+        });
+    let result = options
+        .and_then(|(tint, style)| recolor_icons(&keydeck_types::get_icon_dir_path(), tint, style));
+    match result {
+        Ok(report) if json => println!("{}", serde_json::to_string(&report).unwrap_or_default()),
+        Ok(report) => {
+            println!(
+                "Recolored {} icons into {}",
+                report.recolored.len(),
+                report.directory
+            );
+            for icon in &report.skipped {
+                println!("  skipped {}", icon);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn icons_are_recolored_into_a_subdirectory() {
+        let dir = std::env::temp_dir().join(format!("keydeck-recolor-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("apps")).unwrap();
+        RgbaImage::from_pixel(4, 4, Rgba([200, 10, 10, 255]))
+            .save(dir.join("apps/term.png"))
+            .unwrap();
+        std::fs::write(dir.join("notes.txt"), "not an icon").unwrap();
+
+        let report = recolor_icons(&dir, (0, 255, 0), IconStyle::Filled).unwrap();
+        assert_eq!(report.recolored, ["apps/term.png"]);
+        assert_eq!(report.skipped.len(), 1);
+        let recolored = image::open(dir.join("recolored/apps/term.png")).unwrap();
+        assert_eq!(
+            *recolored.to_rgba8().get_pixel(1, 1),
+            Rgba([0, 255, 0, 255])
+        );
+
+        // The copies of an earlier run are not recolored again
+        let again = recolor_icons(&dir, (0, 255, 0), IconStyle::Filled).unwrap();
+        assert_eq!(again.recolored, ["apps/term.png"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,223 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Reading the images of icons, wallpapers and backgrounds.
+//!
+//! PNG, JPEG and ICO are read by the `image` crate. Other formats have a [`Loader`] of
+//! their own, recognized by the content of the file rather than its extension, and built
+//! in with a cargo feature since each needs a system library: `avif` for AVIF (libdav1d)
+//! and `heif` for HEIF/HEIC (libheif). An image of a format that is not built in fails
+//! with an error naming the feature, which `keydeck --validate` reports for icons.
+
+use image::{DynamicImage, ImageError, ImageResult};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Decodes the bytes of a whole file
+type Decode = fn(&[u8]) -> Result<DynamicImage, String>;
+
+/// A format read by a decoder of its own
+pub struct Loader {
+    pub name: &'static str,
+    /// The cargo feature building in the decoder
+    pub feature: &'static str,
+    /// Whether a file starting with these bytes is of this format
+    detect: fn(&[u8]) -> bool,
+    /// The decoder, when built in
+    decode: Option<Decode>,
+}
+
+static LOADERS: &[Loader] = &[
+    // Before HEIF, as AVIF files also claim to be HEIF
+    Loader {
+        name: "AVIF",
+        feature: "avif",
+        detect: |head| has_brand(head, &[b"avif", b"avis"]),
+        #[cfg(feature = "avif")]
+        decode: Some(|bytes| {
+            image::load_from_memory_with_format(bytes, image::ImageFormat::Avif)
+                .map_err(|e| e.to_string())
+        }),
+        #[cfg(not(feature = "avif"))]
+        decode: None,
+    },
+    Loader {
+        name: "HEIF",
+        feature: "heif",
+        detect: |head| {
+            has_brand(
+                head,
+                &[
+                    b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"mif1", b"msf1",
+                ],
+            )
+        },
+        #[cfg(feature = "heif")]
+        decode: Some(decode_heif),
+        #[cfg(not(feature = "heif"))]
+        decode: None,
+    },
+];
+
+/// Bytes read to recognize the format of a file
+const HEAD_SIZE: u64 = 64;
+
+/// Whether `head` starts an ISO media file (`ftyp` box) with one of `brands`, as its
+/// major brand or a compatible one
+fn has_brand(head: &[u8], brands: &[&[u8; 4]]) -> bool {
+    if head.get(4..8) != Some(b"ftyp") {
+        return false;
+    }
+    let size = u32::from_be_bytes([head[0], head[1], head[2], head[3]]) as usize;
+    let end = size.min(head.len());
+    // The major brand, the minor version, then the compatible brands
+    let major = head.get(8..12).into_iter();
+    let compatible = head.get(16..end).unwrap_or_default().chunks_exact(4);
+    major
+        .chain(compatible)
+        .any(|brand| brands.iter().any(|wanted| brand == *wanted))
+}
+
+fn head_of(path: &Path) -> io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    File::open(path)?.take(HEAD_SIZE).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+/// The loader of the image at `path`, for a format the `image` crate does not read
+pub fn loader_of(path: &Path) -> Option<&'static Loader> {
+    let head = head_of(path).ok()?;
+    LOADERS.iter().find(|loader| (loader.detect)(&head))
+}
+
+fn not_built(loader: &Loader) -> ImageError {
+    ImageError::IoError(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "{} images need keydeck built with the `{}` feature",
+            loader.name, loader.feature
+        ),
+    ))
+}
+
+/// Opens the image at `path`, of any format built in
+pub fn open(path: impl AsRef<Path>) -> ImageResult<DynamicImage> {
+    let path = path.as_ref();
+    let Some(loader) = loader_of(path) else {
+        return image::open(path);
+    };
+    let decode = loader.decode.ok_or_else(|| not_built(loader))?;
+    let bytes = std::fs::read(path)?;
+    decode(&bytes).map_err(|e| {
+        ImageError::IoError(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to decode {} image: {}", loader.name, e),
+        ))
+    })
+}
+
+/// The width and height of the image at `path`
+pub fn dimensions(path: impl AsRef<Path>) -> ImageResult<(u32, u32)> {
+    let path = path.as_ref();
+    match loader_of(path) {
+        // Their decoders have no cheaper way to tell
+        Some(_) => open(path).map(|image| (image.width(), image.height())),
+        None => image::image_dimensions(path),
+    }
+}
+
+/// Why the image at `path` cannot be read, judged from its first bytes: a format that is
+/// not built in, or no image format keydeck knows
+pub fn unsupported(path: &Path) -> Option<String> {
+    let head = head_of(path).ok()?;
+    if let Some(loader) = LOADERS.iter().find(|loader| (loader.detect)(&head)) {
+        return loader
+            .decode
+            .is_none()
+            .then(|| not_built(loader).to_string());
+    }
+    match image::guess_format(&head) {
+        Ok(format) if format.reading_enabled() => None,
+        Ok(format) => Some(format!(
+            "{:?} images are not supported; use PNG, JPEG or ICO",
+            format
+        )),
+        Err(_) => Some("not an image format keydeck reads; use PNG, JPEG or ICO".to_string()),
+    }
+}
+
+#[cfg(feature = "heif")]
+fn decode_heif(bytes: &[u8]) -> Result<DynamicImage, String> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let context = HeifContext::read_from_bytes(bytes).map_err(|e| e.to_string())?;
+    let handle = context.primary_image_handle().map_err(|e| e.to_string())?;
+    let decoded = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)
+        .map_err(|e| e.to_string())?;
+    let plane = decoded
+        .planes()
+        .interleaved
+        .ok_or("no interleaved RGBA plane")?;
+    // Rows may be padded past their pixels
+    let row = plane.width as usize * 4;
+    let pixels = plane
+        .data
+        .chunks(plane.stride)
+        .take(plane.height as usize)
+        .flat_map(|line| &line[..row])
+        .copied()
+        .collect();
+    image::RgbaImage::from_raw(plane.width, plane.height, pixels)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| "truncated image data".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_are_recognized_by_their_content() {
+        let dir = std::env::temp_dir().join(format!("keydeck-formats-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, bytes: &[u8]| {
+            let path = dir.join(name);
+            std::fs::write(&path, bytes).unwrap();
+            path
+        };
+        let ftyp = |major: &[u8; 4], compatible: &[&[u8; 4]]| {
+            let mut bytes = (16 + 4 * compatible.len() as u32).to_be_bytes().to_vec();
+            bytes.extend_from_slice(b"ftyp");
+            bytes.extend_from_slice(major);
+            bytes.extend_from_slice(&[0; 4]);
+            compatible
+                .iter()
+                .for_each(|brand| bytes.extend_from_slice(*brand));
+            bytes
+        };
+
+        let avif = write("photo.png", &ftyp(b"avif", &[b"mif1", b"miaf"]));
+        let heic = write("shot.heic", &ftyp(b"mif1", &[b"heic"]));
+        let video = write("clip.mp4", &ftyp(b"isom", &[b"mp41"]));
+        assert_eq!(loader_of(&avif).map(|loader| loader.name), Some("AVIF"));
+        assert_eq!(loader_of(&heic).map(|loader| loader.name), Some("HEIF"));
+        assert!(loader_of(&video).is_none());
+        if cfg!(not(feature = "heif")) {
+            let message = unsupported(&heic).unwrap();
+            assert!(message.contains("`heif` feature"), "{}", message);
+            assert!(open(&heic).unwrap_err().to_string().contains("`heif`"));
+        }
+        assert!(unsupported(&video).is_some());
+
+        let png = dir.join("icon.png");
+        image::RgbaImage::new(3, 2).save(&png).unwrap();
+        assert_eq!(unsupported(&png), None);
+        assert_eq!(dimensions(&png).unwrap(), (3, 2));
+        let text = write("notes.png", b"not a picture");
+        assert!(unsupported(&text).unwrap().starts_with("not an image"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
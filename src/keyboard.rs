@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
+use crate::error::{Error, KeyboardError};
 use crate::keyboard_wayland::WaylandKeyboardSession;
 use crate::platform::process_escape_sequences;
 use crate::session::{detect_session_type, SessionType};
@@ -8,7 +9,7 @@ use std::str::FromStr;
 use std::sync::LazyLock;
 use std::{thread, time::Duration};
 use strum::{Display, EnumString};
-use x11rb::protocol::xproto::{GetKeyboardMappingReply, Keycode, Keysym};
+use x11rb::protocol::xproto::{GetKeyboardMappingReply, Keycode, Keysym, ModMask};
 use x11rb::{
     connection::Connection,
     protocol::{xproto::ConnectionExt, xtest},
@@ -50,18 +51,39 @@ static KEYBOARD: LazyLock<KeyboardBackend> = LazyLock::new(|| {
     }
 });
 
-pub fn send_key_combination(combination: &str) -> Result<(), String> {
-    match &*KEYBOARD {
+impl KeyboardBackend {
+    /// Name of the backend, as it appears in keyboard errors
+    fn name(&self) -> &'static str {
+        match self {
+            KeyboardBackend::X11 => "x11",
+            KeyboardBackend::Wayland(_) => "wayland",
+        }
+    }
+}
+
+/// Name of the backend keys are injected with, as it appears in keyboard errors
+pub fn keyboard_backend() -> &'static str {
+    KEYBOARD.name()
+}
+
+pub fn send_key_combination(combination: &str) -> Result<(), Error> {
+    let backend = &*KEYBOARD;
+    match backend {
         KeyboardBackend::X11 => send_key_combination_x11(combination),
-        KeyboardBackend::Wayland(session) => session.send_key_combination(combination),
+        KeyboardBackend::Wayland(session) => session
+            .send_key_combination(combination)
+            .map_err(KeyboardError::from),
     }
+    .map_err(|e| Error::keyboard(backend.name(), e))
 }
 
-pub fn send_string(text: &str) -> Result<(), String> {
-    match &*KEYBOARD {
+pub fn send_string(text: &str) -> Result<(), Error> {
+    let backend = &*KEYBOARD;
+    match backend {
         KeyboardBackend::X11 => send_string_x11(text),
-        KeyboardBackend::Wayland(session) => session.send_string(text),
+        KeyboardBackend::Wayland(session) => session.send_string(text).map_err(KeyboardError::from),
     }
+    .map_err(|e| Error::keyboard(backend.name(), e))
 }
 
 // --- X11 backend ---
@@ -162,7 +184,7 @@ enum Keys {
     Eject = 0x1008ff2c,
 }
 
-fn keysym_for_char(ch: char) -> Result<(Keysym, bool), String> {
+fn keysym_for_char(ch: char) -> Result<(Keysym, bool), KeyboardError> {
     match ch {
         'a'..='z' => Ok((ch as u32 - 'a' as u32 + 0x61, false)),
         'A'..='Z' => Ok((ch as u32 - 'A' as u32 + 0x61, true)),
@@ -200,7 +222,7 @@ fn keysym_for_char(ch: char) -> Result<(Keysym, bool), String> {
         '/' => Ok((0x2F, false)),
         '\\' => Ok((0x5C, false)),
         '`' => Ok((0x60, false)),
-        _ => Err(format!("Unsupported character: {}", ch)),
+        _ => Err(KeyboardError::UnsupportedChar(ch)),
     }
 }
 
@@ -208,7 +230,7 @@ fn keysym_to_keycode(
     keysym: Keysym,
     keysym_mapping: &GetKeyboardMappingReply,
     min_keycode: Keycode,
-) -> Result<u8, String> {
+) -> Result<u8, KeyboardError> {
     for (i, keysym_list) in keysym_mapping
         .keysyms
         .chunks(keysym_mapping.keysyms_per_keycode as usize)
@@ -220,12 +242,57 @@ fn keysym_to_keycode(
             }
         }
     }
-    Err(format!("Keysym not found: {}", keysym))
+    Err(KeyboardError::UnmappedKeysym(keysym))
+}
+
+/// Resolves a hotkey like "ctrl+alt+k" into the modifier mask and keycode to grab.
+/// Exactly one part must be a non-modifier key.
+pub fn hotkey_to_grab(
+    combination: &str,
+    keysym_mapping: &GetKeyboardMappingReply,
+    min_keycode: Keycode,
+) -> Result<(ModMask, Keycode), KeyboardError> {
+    let mut modifiers = ModMask::from(0u16);
+    let mut keycode = None;
+    for part in combination.split('+').map(str::trim) {
+        let modifier = match part.to_lowercase().as_str() {
+            "ctrl" | "control" | "lctrl" | "rctrl" => Some(ModMask::CONTROL),
+            "shift" | "lshift" | "rshift" => Some(ModMask::SHIFT),
+            "alt" | "lalt" | "ralt" => Some(ModMask::M1),
+            "super" | "lsuper" | "rsuper" | "meta" | "win" => Some(ModMask::M4),
+            _ => None,
+        };
+        if let Some(modifier) = modifier {
+            modifiers |= modifier;
+            continue;
+        }
+        if keycode.is_some() {
+            return Err(KeyboardError::ExtraKey(combination.to_string()));
+        }
+        let keysym = if part.chars().count() == 1 {
+            let (keysym, needs_shift) = keysym_for_char(part.chars().next().unwrap())?;
+            if needs_shift {
+                modifiers |= ModMask::SHIFT;
+            }
+            keysym
+        } else {
+            Keys::from_str(&part.to_lowercase())
+                .map_err(|_| KeyboardError::UnknownKey(part.to_string()))? as u32
+        };
+        keycode = Some(keysym_to_keycode(keysym, keysym_mapping, min_keycode)?);
+    }
+    keycode
+        .map(|keycode| (modifiers, keycode))
+        .ok_or_else(|| KeyboardError::NoKey(combination.to_string()))
 }
 
-fn send_key_event(keycode: &u8, conn: &RustConnection, event_type: u8) -> Result<(), String> {
+fn send_key_event(
+    keycode: &u8,
+    conn: &RustConnection,
+    event_type: u8,
+) -> Result<(), KeyboardError> {
     let device_id = 0;
-    if let Err(e) = xtest::fake_input(
+    xtest::fake_input(
         conn,
         event_type,
         *keycode,
@@ -234,12 +301,8 @@ fn send_key_event(keycode: &u8, conn: &RustConnection, event_type: u8) -> Result
         0,
         0,
         device_id,
-    ) {
-        return Err(format!("Error sending key event: {}", e));
-    }
-    if let Err(e) = conn.flush() {
-        return Err(format!("Error flushing connection: {}", e));
-    }
+    )?;
+    conn.flush()?;
     Ok(())
 }
 
@@ -248,18 +311,19 @@ fn press_key_combination(
     conn: &RustConnection,
     keyboard_mapping: &GetKeyboardMappingReply,
     min_keycode: Keycode,
-) -> Result<(), String> {
+) -> Result<(), KeyboardError> {
     let parts: Vec<&str> = combo.split('+').collect();
     let mut keycodes: Vec<u8> = Vec::new();
     for part in parts {
         if part.is_empty() {
-            return Err("Empty key part in key combination".to_string());
+            return Err(KeyboardError::EmptyKey);
         }
         let keysym = if part.len() == 1 {
             let (keysym, _) = keysym_for_char(part.chars().next().unwrap())?;
             keysym
         } else {
-            Keys::from_str(&part.to_lowercase()).map_err(|e| e.to_string())? as u32
+            Keys::from_str(&part.to_lowercase())
+                .map_err(|_| KeyboardError::UnknownKey(part.to_string()))? as u32
         };
         keycodes.push(keysym_to_keycode(keysym, keyboard_mapping, min_keycode)?);
     }
@@ -275,25 +339,24 @@ fn press_key_combination(
     Ok(())
 }
 
-fn send_key_combination_x11(combination: &str) -> Result<(), String> {
-    let (conn, _) = RustConnection::connect(None).map_err(|e| e.to_string())?;
+fn send_key_combination_x11(combination: &str) -> Result<(), KeyboardError> {
+    let (conn, _) = RustConnection::connect(None)?;
 
     let xtest_available = conn
         .query_extension(b"XTEST")
         .map(|r| r.reply().map(|x| x.present).unwrap_or(false))
         .unwrap_or(false);
     if !xtest_available {
-        return Err("XTest extension is not available".to_string());
+        return Err(KeyboardError::NoXTest);
     }
 
     let setup = conn.setup();
     let min_keycode = setup.min_keycode;
     let max_keycode = setup.max_keycode;
 
-    let keyboard_mapping = conn
-        .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)
-        .map_err(|e| e.to_string())?;
-    let keysym_mapping = keyboard_mapping.reply().map_err(|e| e.to_string())?;
+    let keysym_mapping = conn
+        .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?
+        .reply()?;
 
     press_key_combination(combination, &conn, &keysym_mapping, min_keycode)
         .unwrap_or_else(|e| eprintln!("{}", e));
@@ -309,25 +372,24 @@ fn keysym_for_control_char(ch: char) -> Option<Keysym> {
     }
 }
 
-fn send_string_x11(text: &str) -> Result<(), String> {
-    let (conn, _) = RustConnection::connect(None).map_err(|e| e.to_string())?;
+fn send_string_x11(text: &str) -> Result<(), KeyboardError> {
+    let (conn, _) = RustConnection::connect(None)?;
 
     let xtest_available = conn
         .query_extension(b"XTEST")
         .map(|r| r.reply().map(|x| x.present).unwrap_or(false))
         .unwrap_or(false);
     if !xtest_available {
-        return Err("XTest extension is not available".to_string());
+        return Err(KeyboardError::NoXTest);
     }
 
     let setup = conn.setup();
     let min_keycode = setup.min_keycode;
     let max_keycode = setup.max_keycode;
 
-    let keyboard_mapping = conn
-        .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)
-        .map_err(|e| e.to_string())?;
-    let keysym_mapping = keyboard_mapping.reply().map_err(|e| e.to_string())?;
+    let keysym_mapping = conn
+        .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?
+        .reply()?;
 
     let shift_keysym = Keys::LShift as u32;
     let shift_keycode = keysym_to_keycode(shift_keysym, &keysym_mapping, min_keycode)?;
@@ -364,3 +426,40 @@ fn send_string_x11(text: &str) -> Result<(), String> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hotkeys_resolve_to_a_modifier_mask_and_one_key() {
+        // Keycodes 10, 11 and 12 type k, F5 and 1
+        let mapping = GetKeyboardMappingReply {
+            keysyms_per_keycode: 1,
+            sequence: 0,
+            keysyms: vec![0x6b, Keys::F5 as u32, 0x31],
+        };
+        let grab = |combination| hotkey_to_grab(combination, &mapping, 10);
+        assert_eq!(
+            grab("ctrl+alt+k").unwrap(),
+            (ModMask::CONTROL | ModMask::M1, 10)
+        );
+        assert_eq!(grab("Super + F5").unwrap(), (ModMask::M4, 11));
+        assert_eq!(grab("!").unwrap(), (ModMask::SHIFT, 12));
+        assert!(matches!(grab("ctrl+alt"), Err(KeyboardError::NoKey(_))));
+        assert!(matches!(grab("k+F5"), Err(KeyboardError::ExtraKey(_))));
+        assert!(matches!(grab("ctrl+kk"), Err(KeyboardError::UnknownKey(key)) if key == "kk"));
+        assert!(matches!(
+            grab("ctrl+x"),
+            Err(KeyboardError::UnmappedKeysym(0x78))
+        ));
+    }
+
+    #[test]
+    fn injection_errors_name_the_backend_that_send_key_reports() {
+        // `--send-key` and `--type` name the backend as their errors do
+        let name = KeyboardBackend::X11.name();
+        let error = Error::keyboard(name, KeyboardError::UnknownKey("kk".into()));
+        assert_eq!(error.to_string(), "Unknown key 'kk' (x11 keyboard)");
+    }
+}
@@ -0,0 +1,186 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Keyboard layout of the session for the `${kbd:layout}` provider and the `kbd_layout`
+//! action.
+//!
+//! On KDE Plasma (X11 and Wayland) the layouts are read and switched through the
+//! `org.kde.keyboard` D-Bus service, which also updates the layout applet. On other X11
+//! desktops the configured layouts come from `setxkbmap -query` and the active one is
+//! the XKB group, locked directly on the X server. Other Wayland compositors offer no
+//! common interface.
+
+use crate::session::{detect_session_type, SessionType};
+use std::process::Command;
+use tokio::runtime::Runtime;
+use x11rb::connection::Connection;
+use x11rb::protocol::xkb::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::ModMask;
+
+#[zbus::proxy(
+    interface = "org.kde.KeyboardLayouts",
+    default_service = "org.kde.keyboard",
+    default_path = "/Layouts"
+)]
+trait KdeKeyboardLayouts {
+    #[zbus(name = "getLayout")]
+    fn get_layout(&self) -> zbus::Result<u32>;
+    #[zbus(name = "setLayout")]
+    fn set_layout(&self, index: u32) -> zbus::Result<bool>;
+    #[zbus(name = "getLayoutsList")]
+    fn get_layouts_list(&self) -> zbus::Result<Vec<(String, String, String)>>;
+}
+
+/// The configured layouts, by short name (`us`, `de`), and the active one
+pub struct Layouts {
+    pub names: Vec<String>,
+    pub current: usize,
+}
+
+impl Layouts {
+    /// Short name of the active layout
+    pub fn current_name(&self) -> &str {
+        self.names.get(self.current).map_or("", String::as_str)
+    }
+}
+
+/// Reads the configured layouts and the active one
+pub fn layouts() -> Result<Layouts, String> {
+    match kde_layouts() {
+        Ok(layouts) => Ok(layouts),
+        Err(_) if detect_session_type() == SessionType::X11 => x11_layouts(),
+        Err(e) => Err(format!("No keyboard layout service on this desktop: {}", e)),
+    }
+}
+
+/// Switches to the layout `name`, or to the one after the active one for `next`
+pub fn switch(target: &str) -> Result<String, String> {
+    let layouts = layouts()?;
+    let index = if target.eq_ignore_ascii_case("next") {
+        (layouts.current + 1) % layouts.names.len().max(1)
+    } else {
+        layouts
+            .names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(target))
+            .ok_or_else(|| {
+                format!(
+                    "Keyboard layout '{}' is not configured, expected one of {}",
+                    target,
+                    layouts.names.join(", ")
+                )
+            })?
+    };
+    match kde_set_layout(index) {
+        Ok(()) => {}
+        Err(_) if detect_session_type() == SessionType::X11 => x11_set_group(index)?,
+        Err(e) => return Err(format!("Failed to switch keyboard layout: {}", e)),
+    }
+    Ok(layouts.names.get(index).cloned().unwrap_or_default())
+}
+
+/// Runs `call` on the KDE keyboard layouts service of the session bus
+fn with_kde<T>(
+    call: impl FnOnce(&KdeKeyboardLayoutsProxyBlocking) -> zbus::Result<T>,
+) -> Result<T, String> {
+    // zbus::blocking requires a tokio runtime context on the current thread
+    let rt = Runtime::new().map_err(|e| format!("Failed to create tokio runtime: {}", e))?;
+    let _guard = rt.enter();
+    let conn = zbus::blocking::Connection::session()
+        .map_err(|e| format!("Failed to connect to session D-Bus: {}", e))?;
+    let proxy = KdeKeyboardLayoutsProxyBlocking::new(&conn).map_err(|e| e.to_string())?;
+    call(&proxy).map_err(|e| e.to_string())
+}
+
+fn kde_layouts() -> Result<Layouts, String> {
+    with_kde(|proxy| {
+        let names = proxy
+            .get_layouts_list()?
+            .into_iter()
+            .map(|(short, _variant, _display)| short)
+            .collect();
+        let current = proxy.get_layout()? as usize;
+        Ok(Layouts { names, current })
+    })
+}
+
+fn kde_set_layout(index: usize) -> Result<(), String> {
+    match with_kde(|proxy| proxy.set_layout(index as u32))? {
+        true => Ok(()),
+        false => Err(format!("Layout {} was rejected", index)),
+    }
+}
+
+/// The layouts of a `setxkbmap -query` output, e.g. `layout:     us,de`
+fn parse_setxkbmap_layouts(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("layout:"))
+        .map(|layouts| {
+            layouts
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn x11_layouts() -> Result<Layouts, String> {
+    let output = Command::new("setxkbmap")
+        .arg("-query")
+        .output()
+        .map_err(|e| format!("Failed to run setxkbmap: {}", e))?;
+    let names = parse_setxkbmap_layouts(&String::from_utf8_lossy(&output.stdout));
+    if names.is_empty() {
+        return Err("setxkbmap reported no keyboard layouts".to_string());
+    }
+    let conn = x11_connect()?;
+    let state = conn
+        .xkb_get_state(xkb::ID::USE_CORE_KBD.into())
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map_err(|e| format!("Failed to read the XKB state: {}", e))?;
+    let current = u8::from(state.group) as usize;
+    Ok(Layouts { names, current })
+}
+
+/// Locks the XKB group `index`, which is the layout at that position
+fn x11_set_group(index: usize) -> Result<(), String> {
+    let conn = x11_connect()?;
+    conn.xkb_latch_lock_state(
+        xkb::ID::USE_CORE_KBD.into(),
+        ModMask::from(0u16),
+        ModMask::from(0u16),
+        true,
+        xkb::Group::from(index as u8),
+        ModMask::from(0u16),
+        false,
+        0,
+    )
+    .map_err(|e| e.to_string())?;
+    conn.flush().map_err(|e| e.to_string())
+}
+
+/// Connects to the X server with the XKB extension enabled
+fn x11_connect() -> Result<x11rb::rust_connection::RustConnection, String> {
+    let (conn, _) =
+        x11rb::connect(None).map_err(|e| format!("Failed to connect to X server: {}", e))?;
+    conn.xkb_use_extension(1, 0)
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map_err(|e| format!("XKB extension is not available: {}", e))?;
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setxkbmap_layouts_in_group_order() {
+        let output = "rules:      evdev\nmodel:      pc105\nlayout:     us,de,gr\nvariant:    ,nodeadkeys,\noptions:    grp:alt_shift_toggle\n";
+        assert_eq!(parse_setxkbmap_layouts(output), ["us", "de", "gr"]);
+        assert!(parse_setxkbmap_layouts("rules: evdev\n").is_empty());
+    }
+}
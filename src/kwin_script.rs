@@ -25,6 +25,7 @@
 //! script, and retry. We never call the global `Scripting.start()` / `reconfigure`,
 //! which would start unrelated foreign scripts.
 
+use crate::window_match::WindowQuery;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fs;
@@ -49,28 +50,25 @@ static CONFIRM_CHANNELS: LazyLock<Arc<RwLock<HashMap<String, MpscSender<()>>>>>
 pub struct WindowInfo {
     pub title: String,
     pub class: String,
+    /// Name of the current virtual desktop (empty when unknown)
+    #[serde(default)]
+    pub desktop: String,
+    /// Id of the current activity (empty when unknown); see [`KWinScriptClient::activity_name`]
+    #[serde(default)]
+    pub activity: String,
 }
 
 /// Errors that can occur during KWin scripting operations
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error("D-Bus error: {0}")]
     DBusError(String),
+    #[error("Script error: {0}")]
     ScriptError(String),
+    #[error("I/O error: {0}")]
     IOError(String),
 }
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Error::DBusError(e) => write!(f, "D-Bus error: {}", e),
-            Error::ScriptError(e) => write!(f, "Script error: {}", e),
-            Error::IOError(e) => write!(f, "I/O error: {}", e),
-        }
-    }
-}
-
-impl std::error::Error for Error {}
-
 // Fixed script name for the single-instance focus listener.
 const LISTENER_SCRIPT_NAME: &str = "keydeck-focus-listener";
 const LISTENER_METHOD_NAME: &str = "keydeck_windowActivated";
@@ -84,6 +82,20 @@ const WELL_KNOWN_NAME: &str = "onl.ycode.keydeck";
 // termination under adversarial concurrent id churn.
 const MAX_ACQUIRE_ATTEMPTS: usize = 64;
 
+/// The window a listener reports as activated. Current listeners send JSON; a listener
+/// left over by an older daemon still sends "title|class".
+fn parse_window_info(data: &str) -> Option<WindowInfo> {
+    serde_json::from_str::<WindowInfo>(data).ok().or_else(|| {
+        let parts: Vec<&str> = data.split('|').collect();
+        (parts.len() >= 2).then(|| WindowInfo {
+            title: parts[0].to_string(),
+            class: parts[1].to_string(),
+            desktop: String::new(),
+            activity: String::new(),
+        })
+    })
+}
+
 /// D-Bus callback handler for KWin script responses.
 /// Registered on the ObjectServer at /onl/ycode/keydeck to receive callDBus callbacks.
 struct CallbackHandler;
@@ -98,12 +110,7 @@ impl CallbackHandler {
     }
 
     async fn window_activated(&self, data: &str) {
-        let parts: Vec<&str> = data.split('|').collect();
-        if parts.len() >= 2 {
-            let info = WindowInfo {
-                title: parts[0].to_string(),
-                class: parts[1].to_string(),
-            };
+        if let Some(info) = parse_window_info(data) {
             let channels = LISTENER_CHANNELS.read().unwrap();
             if let Some(sender) = channels.get(LISTENER_METHOD_NAME) {
                 let _ = sender.send(info);
@@ -203,6 +210,76 @@ impl KWinScriptClient {
         let _ = fs::remove_file(&listener_file);
     }
 
+    /// Whether KWin is on the session bus and accepts scripts. Cheap enough to poll while
+    /// waiting for the compositor at session start.
+    pub fn is_scripting_available() -> bool {
+        let rt = Runtime::new().ok();
+        let _guard = rt.as_ref().map(|r| r.enter());
+        let Ok(conn) = zbus::blocking::Connection::session() else {
+            return false;
+        };
+        // Asking first avoids D-Bus activation attempts for a KWin that is not running
+        let has_owner = conn
+            .call_method(
+                Some("org.freedesktop.DBus"),
+                "/org/freedesktop/DBus",
+                Some("org.freedesktop.DBus"),
+                "NameHasOwner",
+                &("org.kde.KWin",),
+            )
+            .ok()
+            .and_then(|reply| reply.body().deserialize::<bool>().ok())
+            .unwrap_or(false);
+        has_owner
+            && conn
+                .call_method(
+                    Some("org.kde.KWin"),
+                    "/Scripting",
+                    Some("org.kde.kwin.Scripting"),
+                    "isScriptLoaded",
+                    &(LISTENER_SCRIPT_NAME,),
+                )
+                .is_ok()
+    }
+
+    /// Follows the owner of `org.kde.KWin` on the session bus: sends true when KWin takes
+    /// the name (it started or was replaced) and false when it leaves the bus. A KWin
+    /// restart drops every loaded script, so the focus listener registers its script
+    /// again on true. The watching thread ends at the first change after the receiver
+    /// was dropped.
+    pub fn watch_kwin(&self) -> Result<MpscReceiver<bool>, Error> {
+        let changes = zbus::blocking::fdo::DBusProxy::new(&self.conn)
+            .and_then(|proxy| proxy.receive_name_owner_changed_with_args(&[(0, "org.kde.KWin")]))
+            .map_err(|e| Error::DBusError(format!("Failed to watch org.kde.KWin: {}", e)))?;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for change in changes {
+                let Ok(args) = change.args() else {
+                    continue;
+                };
+                if sender.send(args.new_owner().is_some()).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(receiver)
+    }
+
+    /// Display name of a KDE activity, looked up by the id KWin reports
+    pub fn activity_name(&self, id: &str) -> Option<String> {
+        self.conn
+            .call_method(
+                Some("org.kde.ActivityManager"),
+                "/ActivityManager/Activities",
+                Some("org.kde.ActivityManager.Activities"),
+                "ActivityName",
+                &(id,),
+            )
+            .ok()
+            .and_then(|reply| reply.body().deserialize::<String>().ok())
+            .filter(|name| !name.is_empty())
+    }
+
     /// Call a KWin D-Bus method and deserialize the response
     fn kwin_call<B: serde::Serialize + zbus::zvariant::DynamicType>(
         &self,
@@ -369,7 +446,7 @@ impl KWinScriptClient {
         out
     }
 
-    /// Activate a window matching the given class and title.
+    /// Activate a window matching the given query.
     ///
     /// Ownership of the *result* is proven by `method_uuid`: it is baked into the JS and
     /// echoed back in `ActivateResult`, and the dispatch matches on it (see
@@ -378,7 +455,7 @@ impl KWinScriptClient {
     /// "activated" reply — the worst case is a benign early start of a foreign
     /// loaded-not-started script plus our own timeout -> Error. Hence no separate
     /// proof-of-life is needed here (unlike the persistent listener).
-    pub fn activate_window(&self, class: &str, title: &str) -> Result<(), Error> {
+    pub fn activate_window(&self, query: &WindowQuery) -> Result<(), Error> {
         let script_name = format!("keydeck-activate-{}", uuid::Uuid::new_v4());
         let method_uuid = uuid::Uuid::new_v4().to_string().replace("-", "");
 
@@ -386,46 +463,56 @@ impl KWinScriptClient {
             r#"
                 var targetClass = "{}";
                 var targetTitle = "{}";
+                var exact = {};
+                var either = {};
+                var targetPid = {};
+                var index = {};
+                var wrap = {};
                 var clients = workspace.windowList();
-                var found = false;
-                var useOrLogic = targetClass !== "" && targetTitle !== "" && targetClass === targetTitle;
+                var matches = [];
+
+                function textMatch(haystack, needle) {{
+                    haystack = (haystack || "").toLowerCase();
+                    return exact ? haystack === needle : haystack.indexOf(needle) >= 0;
+                }}
 
                 for (var i = 0; i < clients.length; i++) {{
                     var client = clients[i];
-                    var classMatch = targetClass === "" ? !useOrLogic :
-                                   client.resourceClass.toLowerCase().indexOf(targetClass.toLowerCase()) >= 0;
-                    var titleMatch = targetTitle === "" ? !useOrLogic :
-                                   client.caption.toLowerCase().indexOf(targetTitle.toLowerCase()) >= 0;
-
-                    var matches = useOrLogic ? (classMatch || titleMatch) : (classMatch && titleMatch);
-
-                    if (matches) {{
-                        workspace.activeWindow = client;
-                        found = true;
-                        break;
+                    if (targetPid >= 0 && client.pid !== targetPid) {{
+                        continue;
+                    }}
+                    var classMatch = targetClass !== "" && textMatch(client.resourceClass, targetClass);
+                    var titleMatch = targetTitle !== "" && textMatch(client.caption, targetTitle);
+                    var isMatch = either ? (classMatch || titleMatch) :
+                        ((targetClass === "" || classMatch) && (targetTitle === "" || titleMatch));
+                    if (isMatch) {{
+                        matches.push(client);
                     }}
                 }}
 
-                if (found) {{
-                    callDBus("{}",
-                            "/onl/ycode/keydeck",
-                            "onl.ycode.keydeck.Callback",
-                            "ActivateResult",
-                            "{}",
-                            "activated");
-                }} else {{
-                    callDBus("{}",
-                            "/onl/ycode/keydeck",
-                            "onl.ycode.keydeck.Callback",
-                            "ActivateResult",
-                            "{}",
-                            "not_found");
+                var result = "not_found";
+                if (matches.length > 0) {{
+                    var pick = wrap ? index % matches.length : index;
+                    if (pick < matches.length) {{
+                        workspace.activeWindow = matches[pick];
+                        result = "activated";
+                    }}
                 }}
+
+                callDBus("{}",
+                        "/onl/ycode/keydeck",
+                        "onl.ycode.keydeck.Callback",
+                        "ActivateResult",
+                        "{}",
+                        result);
             "#,
-            Self::js_escape(class),
-            Self::js_escape(title),
-            self.dbus_addr,
-            method_uuid,
+            Self::js_escape(&query.class),
+            Self::js_escape(&query.title),
+            query.exact,
+            query.either,
+            query.pid.map(|p| p as i64).unwrap_or(-1),
+            query.index,
+            query.wrap,
             self.dbus_addr,
             method_uuid
         );
@@ -517,15 +604,25 @@ impl KWinScriptClient {
                 var currentClient = null;
                 var captionConnection = null;
                 var windowActivatedConnection = null;
+                var desktopConnection = null;
+                var activityConnection = null;
 
                 function sendWindowInfo(client) {{
-                    if (client) {{
-                        callDBus("{dbus_addr}",
-                                "/onl/ycode/keydeck",
-                                "onl.ycode.keydeck.Callback",
-                                "WindowActivated",
-                                client.caption + "|" + client.resourceClass);
-                    }}
+                    var desktop = workspace.currentDesktop;
+                    callDBus("{dbus_addr}",
+                            "/onl/ycode/keydeck",
+                            "onl.ycode.keydeck.Callback",
+                            "WindowActivated",
+                            JSON.stringify({{
+                                title: client ? client.caption : "",
+                                class: client ? client.resourceClass : "",
+                                desktop: desktop && desktop.name ? desktop.name : "",
+                                activity: workspace.currentActivity || ""
+                            }}));
+                }}
+
+                function sendWorkspaceInfo() {{
+                    sendWindowInfo(workspace.activeWindow);
                 }}
 
                 function setupClient(client) {{
@@ -555,10 +652,20 @@ impl KWinScriptClient {
                         windowActivatedConnection.disconnect();
                         windowActivatedConnection = null;
                     }}
+                    if (desktopConnection) {{
+                        desktopConnection.disconnect();
+                        desktopConnection = null;
+                    }}
+                    if (activityConnection) {{
+                        activityConnection.disconnect();
+                        activityConnection = null;
+                    }}
                 }}
 
                 setupClient(workspace.activeWindow);
                 windowActivatedConnection = workspace.windowActivated.connect(setupClient);
+                desktopConnection = workspace.currentDesktopChanged.connect(sendWorkspaceInfo);
+                activityConnection = workspace.currentActivityChanged.connect(sendWorkspaceInfo);
 
                 callDBus("{dbus_addr}",
                         "/onl/ycode/keydeck",
@@ -748,3 +855,30 @@ impl Drop for KWinScriptClient {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn focus_changes_carry_the_desktop_and_activity() {
+        let info = parse_window_info(
+            r#"{"title":"Doc","class":"kate","desktop":"Work","activity":"abc"}"#,
+        )
+        .unwrap();
+        assert_eq!((info.title.as_str(), info.class.as_str()), ("Doc", "kate"));
+        assert_eq!(
+            (info.desktop.as_str(), info.activity.as_str()),
+            ("Work", "abc")
+        );
+
+        let legacy = parse_window_info("Doc|kate").unwrap();
+        assert_eq!(legacy.class, "kate");
+        assert_eq!(
+            (legacy.desktop.as_str(), legacy.activity.as_str()),
+            ("", "")
+        );
+
+        assert!(parse_window_info("garbage").is_none());
+    }
+}
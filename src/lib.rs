@@ -12,23 +12,37 @@ pub static VERBOSITY: AtomicU8 = AtomicU8::new(0);
 #[macro_use]
 pub mod utils;
 
+pub mod bookmarks;
+pub mod button_look;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod config_hints;
+pub mod config_patch;
+pub mod device_conflicts;
 pub mod device_info;
 pub mod device_trait;
 pub mod dynamic_detection;
 pub mod elgato_device;
+pub mod event;
+pub mod favicon;
+pub mod icon_cache;
+pub mod image_formats;
 pub mod mirajazz_device;
+pub mod page_generator;
 pub mod pages;
 pub mod system_info;
-pub mod text_renderer;
 
 // Re-export types from keydeck-types
 pub use keydeck_types::{
-    get_config_dir, get_config_path, get_icon_dir, get_icon_dir_path, get_log_path, Action, Button,
+    get_config_dir, get_config_path, get_history_path, get_icon_dir, get_icon_dir_path, get_log_path, get_state_path, get_stats_path, Action, Button,
     ButtonConfig,
-    ButtonImage, ButtonLayout, ColorMapEntry, DeviceInfo, Direction, DrawConfig,
+    ButtonImage, ButtonLayout, ColorMap, ColorMapEntry, DeviceInfo, Direction, DrawConfig,
     FocusChangeRestorePolicy, GraphicType, KeyDeckConf, LcdStrip, Macro, MacroCall, Page, Pages,
     RefreshTarget, ServiceConfig, TextConfig, DEFAULT_ICON_DIR_REL,
 };
 
 // Re-export backend-specific loader
 pub use pages::KeyDeckConfLoader;
+
+// Text rendering, now part of the keydeck-render crate
+pub use keydeck_render::text as text_renderer;
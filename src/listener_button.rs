@@ -12,15 +12,114 @@
 //!   multiple independent handles; zero idle CPU, the original design).
 //!
 //! Both share [`dispatch_update`] for turning driver updates into events.
+//!
+//! In game mode input is wanted with the lowest latency: the Windows listener polls
+//! faster on a higher priority thread. Blocking reads already return as soon as a
+//! report arrives, so Linux and macOS need no change.
+//!
+//! `input_polling` of a device tunes the reads: the poll interval, the interval polls
+//! slow down to while the device is not used, and the timeout of blocking reads.
 
 use crate::device_trait::DeviceStateUpdate;
 use crate::event::{send, DeviceEvent};
+use crate::pages::InputPolling;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::mpsc::Sender;
 use std::time::Duration;
 
 pub(crate) const MAX_CONSECUTIVE_ERRORS: u32 = 3;
 pub(crate) const ERROR_BACKOFF: Duration = Duration::from_secs(1);
 
+/// Poll interval without `input_polling.interval` (~120 Hz, negligible cost)
+const DEFAULT_POLL_INTERVAL: u32 = 8;
+
+/// Interval polls slow down to without `input_polling.idle_interval`
+const DEFAULT_IDLE_POLL_INTERVAL: u32 = 32;
+
+/// Blocking read timeout without `input_polling.read_timeout`
+const DEFAULT_READ_TIMEOUT: f32 = 10.0;
+
+/// Polls stay at full speed this long after the last input, then double their interval
+/// every further period until they reach the idle interval
+const IDLE_AFTER: Duration = Duration::from_secs(10);
+
+/// The `input_polling` of a device, which reloads change while its listener runs, and the
+/// poll interval in use
+#[derive(Debug, Default)]
+pub struct ReadTuning {
+    /// Milliseconds
+    interval: AtomicU32,
+    /// Milliseconds
+    idle_interval: AtomicU32,
+    /// Milliseconds
+    read_timeout: AtomicU32,
+    /// Milliseconds between the latest polls; 0 before the first one
+    current: AtomicU32,
+}
+
+impl ReadTuning {
+    pub fn new(polling: Option<&InputPolling>) -> Self {
+        let tuning = ReadTuning::default();
+        tuning.update(polling);
+        tuning
+    }
+
+    pub fn update(&self, polling: Option<&InputPolling>) {
+        let polling = polling.cloned().unwrap_or_default();
+        let interval = polling.interval.unwrap_or(DEFAULT_POLL_INTERVAL).max(1);
+        let idle_interval = polling
+            .idle_interval
+            .unwrap_or(DEFAULT_IDLE_POLL_INTERVAL)
+            .max(interval);
+        let read_timeout = polling
+            .read_timeout
+            .unwrap_or(DEFAULT_READ_TIMEOUT)
+            .max(0.1);
+        self.interval.store(interval, Ordering::Relaxed);
+        self.idle_interval.store(idle_interval, Ordering::Relaxed);
+        self.read_timeout
+            .store((read_timeout * 1000.0) as u32, Ordering::Relaxed);
+    }
+
+    pub fn read_timeout(&self) -> Duration {
+        Duration::from_millis(self.read_timeout.load(Ordering::Relaxed) as u64)
+    }
+
+    /// The poll interval after `idle` without input, which is also shown as the one in use
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))] // Only Windows polls
+    pub fn poll_interval(&self, idle: Duration) -> Duration {
+        let interval = self.interval.load(Ordering::Relaxed);
+        let idle_interval = self.idle_interval.load(Ordering::Relaxed);
+        let doublings = (idle.as_secs() / IDLE_AFTER.as_secs()).min(16) as u32;
+        let current = interval.saturating_mul(1 << doublings).min(idle_interval);
+        self.current.store(current, Ordering::Relaxed);
+        Duration::from_millis(current as u64)
+    }
+
+    /// How input is read, for the diagnostics
+    pub fn describe(&self) -> String {
+        if cfg!(target_os = "windows") {
+            let current = self.current.load(Ordering::Relaxed);
+            let interval = self.interval.load(Ordering::Relaxed);
+            match current {
+                0 => format!("poll {}ms", interval),
+                current if current > interval => format!("idle {}ms", current),
+                current => format!("poll {}ms", current),
+            }
+        } else {
+            format!("wait {}s", self.read_timeout().as_secs_f32())
+        }
+    }
+}
+
+/// Input is read with the lowest latency the platform allows, while game mode is on
+pub(crate) static LOW_LATENCY: AtomicBool = AtomicBool::new(false);
+
+/// Switches low-latency input reading on or off for all devices
+pub fn set_low_latency(enabled: bool) {
+    LOW_LATENCY.store(enabled, Ordering::Relaxed);
+}
+
 /// Converts a driver-level input update into a `DeviceEvent` and dispatches it.
 pub(crate) fn dispatch_update(update: DeviceStateUpdate, serial: &str, tx: &Sender<DeviceEvent>) {
     let sn = serial.to_string();
@@ -106,3 +205,33 @@ pub use windows::button_listener;
 mod unix;
 #[cfg(not(target_os = "windows"))]
 pub use unix::button_listener;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polls_slow_down_while_the_device_is_idle() {
+        let tuning = ReadTuning::new(Some(&InputPolling {
+            interval: Some(4),
+            idle_interval: Some(20),
+            ..Default::default()
+        }));
+        let after = |secs| tuning.poll_interval(Duration::from_secs(secs)).as_millis();
+        assert_eq!(after(0), 4);
+        assert_eq!(after(9), 4);
+        assert_eq!(after(10), 8);
+        assert_eq!(after(25), 16);
+        assert_eq!(after(3600), 20);
+        assert_eq!(tuning.read_timeout(), Duration::from_secs(10));
+
+        // An idle interval shorter than the interval keeps polling at full speed
+        tuning.update(Some(&InputPolling {
+            interval: Some(10),
+            idle_interval: Some(2),
+            read_timeout: Some(0.5),
+        }));
+        assert_eq!(after(3600), 10);
+        assert_eq!(tuning.read_timeout(), Duration::from_millis(500));
+    }
+}
@@ -6,9 +6,9 @@
 //! hidraw (Linux) and IOKit (macOS) allow multiple independent handles on the
 //! same device, each delivering input reports. The listener therefore opens its
 //! own handle and uses a blocking read with a long timeout — zero idle CPU.
-//! This is the original design, unchanged.
+//! This is the original design, unchanged; only the timeout can be configured.
 
-use super::{dispatch_update, ERROR_BACKOFF, MAX_CONSECUTIVE_ERRORS};
+use super::{dispatch_update, ReadTuning, ERROR_BACKOFF, MAX_CONSECUTIVE_ERRORS};
 use crate::device_manager::find_device_by_serial;
 use crate::device_trait::KeydeckDevice;
 use crate::event::DeviceEvent;
@@ -17,24 +17,27 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
 
-/// Blocking read timeout. The read blocks until input arrives (or this elapses),
-/// so the listener consumes no CPU while idle.
-const READ_TIMEOUT: Duration = Duration::from_secs(10);
-
-/// Spawns the input-reading loop. Opens its own handle on the device.
-pub fn button_listener(sn: &String, tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>) {
-    let serial = sn.clone();
+/// Spawns the input-reading loop. Opens its own handle on the device. The read blocks
+/// until input arrives, or the read timeout of `tuning` elapses, so the listener
+/// consumes no CPU while idle.
+pub fn button_listener(
+    sn: &str,
+    tx: &Sender<DeviceEvent>,
+    active: &Arc<AtomicBool>,
+    tuning: &Arc<ReadTuning>,
+) {
+    let serial = sn.to_string();
     let active = active.clone();
     let tx = tx.clone();
+    let tuning = tuning.clone();
     thread::spawn(move || {
         if let Some(device) = find_device_by_serial(&serial) {
             verbose_log!("Starting key listener for device {}", serial);
             let reader = device.get_reader();
             let mut consecutive_errors: u32 = 0;
             while active.load(Ordering::Relaxed) {
-                match reader.read(Some(READ_TIMEOUT)) {
+                match reader.read(Some(tuning.read_timeout())) {
                     Ok(updates) => {
                         consecutive_errors = 0;
                         for update in updates {
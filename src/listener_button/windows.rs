@@ -9,7 +9,7 @@
 //! polled so the mutex is never held during a blocking read (which would
 //! deadlock rendering) — the approach `elgato-streamdeck`'s async wrapper uses.
 
-use super::{dispatch_update, ERROR_BACKOFF, MAX_CONSECUTIVE_ERRORS};
+use super::{dispatch_update, ReadTuning, ERROR_BACKOFF, LOW_LATENCY, MAX_CONSECUTIVE_ERRORS};
 use crate::device_trait::DeviceReader;
 use crate::event::DeviceEvent;
 use crate::{error_log, verbose_log};
@@ -17,29 +17,57 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use windows::Win32::System::Threading::{
+    GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_HIGHEST, THREAD_PRIORITY_NORMAL,
+};
 
-/// Poll interval between non-blocking reads (~120 Hz, negligible cost).
-const POLL_INTERVAL: Duration = Duration::from_millis(8);
+/// Poll interval in game mode (~1000 Hz, the rate of a full-speed USB device).
+const LOW_LATENCY_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Raises the priority of the listener thread in game mode, so polls are not delayed
+/// by a busy game, and lowers it back afterwards.
+fn set_thread_priority(high: bool) {
+    let priority = if high {
+        THREAD_PRIORITY_HIGHEST
+    } else {
+        THREAD_PRIORITY_NORMAL
+    };
+    if let Err(e) = unsafe { SetThreadPriority(GetCurrentThread(), priority) } {
+        error_log!("Failed to change key listener priority: {}", e);
+    }
+}
 
 /// Spawns the input-reading loop. `reader` comes from the same device instance
-/// used for rendering, so the device is opened exactly once.
+/// used for rendering, so the device is opened exactly once. Polls slow down while
+/// the device is not used, as `tuning` allows.
 pub fn button_listener(
     reader: Arc<dyn DeviceReader>,
     sn: &String,
     tx: &Sender<DeviceEvent>,
     active: &Arc<AtomicBool>,
+    tuning: &Arc<ReadTuning>,
 ) {
     let serial = sn.clone();
     let active = active.clone();
     let tx = tx.clone();
+    let tuning = tuning.clone();
     thread::spawn(move || {
         verbose_log!("Starting key listener for device {}", serial);
         let mut consecutive_errors: u32 = 0;
+        let mut low_latency = false;
+        let mut last_input = Instant::now();
         while active.load(Ordering::Relaxed) {
+            if LOW_LATENCY.load(Ordering::Relaxed) != low_latency {
+                low_latency = !low_latency;
+                set_thread_priority(low_latency);
+            }
             match reader.read(None) {
                 Ok(updates) => {
                     consecutive_errors = 0;
+                    if !updates.is_empty() {
+                        last_input = Instant::now();
+                    }
                     for update in updates {
                         dispatch_update(update, &serial, &tx);
                     }
@@ -64,7 +92,11 @@ pub fn button_listener(
                     }
                 }
             }
-            thread::sleep(POLL_INTERVAL);
+            thread::sleep(if low_latency {
+                LOW_LATENCY_POLL_INTERVAL
+            } else {
+                tuning.poll_interval(last_input.elapsed())
+            });
         }
         verbose_log!("Exiting key listener for device {}", serial);
     });
@@ -9,40 +9,116 @@
 //! ```text
 //! setvar <key> <value>
 //! clearvar <key>
+//! hotkey <combination>
+//! focus <class> [title]
+//! history [count]
+//! stats
+//! reloadstatus
+//! reload
+//! version
+//! status
+//! watch [version]
+//! testpattern [serial]
+//! bench [serial]
+//! setbutton <json>
+//! setpage <group|serial> <page>
+//! screenshot-device <serial> <file>
+//! press <serial> <button>
 //! ```
 //!
-//! and is turned into a [`DeviceEvent::SetContextVar`], exactly as `listener_focus`
-//! injects `FocusChanges`. The `keydeck --set key=value` CLI is the thin client that
-//! writes these lines, so external watchers never need to know the protocol.
+//! `setvar`/`clearvar` are turned into a [`DeviceEvent::SetContextVar`], exactly as
+//! `listener_focus` injects `FocusChanges`. The `keydeck --set key=value` CLI is the thin
+//! client that writes these lines, so external watchers never need to know the protocol.
+//! `hotkey` fires a [`DeviceEvent::Hotkey`], for desktops where keydeck cannot grab the
+//! shortcut itself (`keydeck --hotkey <combination>`).
+//! `focus` fires a [`DeviceEvent::FocusChanges`] for a window that need not exist, to try
+//! the `window_name` rules of pages (`keydeck --simulate-focus <class> <title>`).
+//! `reloadstatus` is answered with one JSON line describing the last configuration load
+//! (see [`crate::server::ReloadStatus`]).
+//! `history` is answered with one JSON line holding an array of the last `count` (default
+//! 20) [`crate::history::HistoryEntry`] records, for the config UI.
+//! `stats` is answered with one JSON line holding the [`crate::stats::UsageStats`]: press
+//! counts and page dwell times per device.
+//! `watch` turns the connection into a stream of [`crate::live_events`], one JSON line
+//! per key press and page change, until the client disconnects (`keydeck --watch`).
+//! `testpattern` shows the numbered test pattern for a few seconds on the device with
+//! that serial, or on every device (`keydeck --test-pattern`), for the setup wizard.
+//! `diagnostics` covers the keys of that device, or of every device, with the diagnostic
+//! page until a key is pressed (`keydeck --diagnostics`).
+//! `bench` redraws the pages of that device, or of every device, and is answered with one
+//! JSON line holding their [`crate::bench::BenchReport`]s (`keydeck --bench`).
+//! `services` is answered with one JSON line holding a [`crate::services::ServiceReport`]
+//! per configured service: its last value, error, run time and next run
+//! (`keydeck --services`).
+//! `setbutton` changes a button of the running configuration, described by a JSON
+//! object with its `target` (`GROUP/PAGE/BUTTON`) and its new fields, and `setpage` shows a page on the devices of a page group, or on the
+//! device with that serial (`keydeck --set-button`, `keydeck --set-page`). Both are
+//! answered with `ok`, or `error: ` and the reason.
+//! `screenshot-device` writes the keys of the device with that serial, as last drawn, as
+//! a PNG at the absolute path `file` (`keydeck --screenshot`), and is answered the same.
+//! `press` presses and releases the button with that number on the device with that
+//! serial, as the web deck does (`keydeck --tui`).
+//! `version` is answered with one JSON line holding the [`ApiInfo`] of the daemon, and
+//! `status` with one holding its [`DaemonStatus`]: version, uptime, configuration
+//! generation and connected devices (`keydeck --daemon status`, the config UI).
+//!
+//! The lines are the [`Command`]s of the versioned API in `keydeck_types::api`, which
+//! clients should use instead of writing them by hand. Every command may also be sent as
+//! one JSON object (`{"v":1,"command":"set_page","target":"AL1","page":"Media"}`). A line
+//! that is not a valid command is answered with `error: ` and the reason.
 
+use crate::config_patch::ButtonPatch;
+use crate::dbus_state;
 use crate::event::{send, DeviceEvent};
+use crate::live_events;
+use crate::server::SharedReloadStatus;
+use crate::stats::SharedStats;
 use crate::{error_log, verbose_log};
+use keydeck_types::api::{self, ApiInfo, Command, DaemonStatus, DeviceState};
 use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock};
 use std::thread;
+use std::time::{Duration, Instant};
 
-/// Path of the control socket: `$XDG_RUNTIME_DIR/keydeck.sock`, or a per-user name
-/// in the system temp dir when `XDG_RUNTIME_DIR` is unset.
-pub fn control_socket_path() -> PathBuf {
-    if let Ok(dir) = std::env::var("XDG_RUNTIME_DIR") {
-        if !dir.is_empty() {
-            return PathBuf::from(dir).join("keydeck.sock");
-        }
-    }
-    let user = std::env::var("USER").unwrap_or_else(|_| "user".to_string());
-    std::env::temp_dir().join(format!("keydeck-{}.sock", user))
-}
+/// How long writing to a watcher may block before it is dropped, so a watcher that
+/// stops reading does not hold on to its connection
+const WATCH_WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// How long a `bench` waits for the daemon to redraw the pages
+const BENCH_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Longest wait for the event loop to report on the services
+const SERVICES_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest wait for the event loop to apply a `setbutton` or `setpage`
+const CHANGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest wait for the event loop to write a screenshot
+const SCREENSHOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub use keydeck_types::api::control_socket_path;
+
+/// When the daemon started listening, for the `status` uptime
+static STARTED: LazyLock<Instant> = LazyLock::new(Instant::now);
 
 /// Spawns the control-socket listener thread. Binds the socket (replacing any stale
 /// file) and injects a `SetContextVar` event for every valid command line received.
-pub fn spawn_context_listener(tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>) {
+pub fn spawn_context_listener(
+    tx: &Sender<DeviceEvent>,
+    active: &Arc<AtomicBool>,
+    reload_status: &SharedReloadStatus,
+    usage_stats: &SharedStats,
+) {
     let tx = tx.clone();
     let active = active.clone();
+    let reload_status = reload_status.clone();
+    let usage_stats = usage_stats.clone();
     let path = control_socket_path();
+    LazyLock::force(&STARTED);
 
     // Replace a stale socket left behind by a previous run.
     let _ = std::fs::remove_file(&path);
@@ -63,11 +139,39 @@ pub fn spawn_context_listener(tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>
             }
             match stream {
                 Ok(stream) => {
+                    let mut writer = match stream.try_clone() {
+                        Ok(writer) => writer,
+                        Err(e) => {
+                            error_log!("Control socket clone error: {}", e);
+                            continue;
+                        }
+                    };
                     let reader = BufReader::new(stream);
                     for line in reader.lines() {
-                        match line {
-                            Ok(line) => handle_line(&tx, &line),
-                            Err(_) => break,
+                        let Ok(line) = line else { break };
+                        if line.trim().is_empty() {
+                            continue;
+                        }
+                        let reply = match Command::parse(&line) {
+                            Ok(Command::Watch { version }) => match api::negotiate(version) {
+                                Ok(version) => {
+                                    start_watching(&writer, version);
+                                    break;
+                                }
+                                Err(e) => Some(change_reply(Err(e))),
+                            },
+                            Ok(command) => {
+                                handle_command(&tx, &reload_status, &usage_stats, command)
+                            }
+                            Err(e) => {
+                                error_log!("Control command {:?}: {}", line.trim(), e);
+                                Some(change_reply(Err(e)))
+                            }
+                        };
+                        if let Some(reply) = reply {
+                            if writer.write_all(reply.as_bytes()).is_err() {
+                                break;
+                            }
                         }
                     }
                 }
@@ -77,32 +181,273 @@ pub fn spawn_context_listener(tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>
     });
 }
 
-/// Parses one command line and injects the matching event.
-fn handle_line(tx: &Sender<DeviceEvent>, line: &str) {
-    let line = line.trim();
-    if line.is_empty() {
-        return;
+/// Hands a connection over to [`live_events`], which writes to it from now on
+fn start_watching(stream: &UnixStream, version: u32) {
+    match stream.try_clone() {
+        Ok(watcher) => {
+            let _ = watcher.set_write_timeout(Some(WATCH_WRITE_TIMEOUT));
+            live_events::watch(Box::new(watcher), version);
+        }
+        Err(e) => error_log!("Control socket clone error: {}", e),
     }
-    let mut parts = line.splitn(3, char::is_whitespace);
-    match parts.next().unwrap_or("") {
-        "setvar" => {
-            let key = parts.next().unwrap_or("").trim().to_string();
-            if key.is_empty() {
-                error_log!("Control command 'setvar' missing key: {:?}", line);
-                return;
-            }
-            let value = parts.next().map(|s| s.to_string());
-            send(tx, DeviceEvent::SetContextVar { key, value });
-        }
-        "clearvar" => {
-            let key = parts.next().unwrap_or("").trim().to_string();
-            if key.is_empty() {
-                error_log!("Control command 'clearvar' missing key: {:?}", line);
-                return;
-            }
-            send(tx, DeviceEvent::SetContextVar { key, value: None });
+}
+
+/// Injects the event of a command. Returns the reply to write back for query commands.
+fn handle_command(
+    tx: &Sender<DeviceEvent>,
+    reload_status: &SharedReloadStatus,
+    usage_stats: &SharedStats,
+    command: Command,
+) -> Option<String> {
+    match command {
+        Command::SetVar { key, value } => send(tx, DeviceEvent::SetContextVar { key, value }),
+        Command::Hotkey { combination } => send(tx, DeviceEvent::Hotkey { combination }),
+        Command::Focus { class, title } => {
+            verbose_log!("Simulated focus: class={:?} title={:?}", class, title);
+            send(tx, DeviceEvent::FocusChanges { class, title });
+        }
+        Command::Reload => send(tx, DeviceEvent::Reload),
+        Command::History { count } => {
+            let entries = crate::history::read_recent(count.unwrap_or(20));
+            return serde_json::to_string(&entries).ok().map(|json| json + "\n");
+        }
+        Command::Stats => {
+            let stats = usage_stats.read().unwrap();
+            return serde_json::to_string(&*stats).ok().map(|json| json + "\n");
+        }
+        Command::Version => {
+            let info = ApiInfo {
+                keydeck: env!("CARGO_PKG_VERSION").to_string(),
+                api: api::API_VERSION,
+                min_api: api::MIN_API_VERSION,
+            };
+            return serde_json::to_string(&info).ok().map(|json| json + "\n");
+        }
+        Command::Status => {
+            let reload = reload_status.read().unwrap().clone();
+            let devices = dbus_state::devices()
+                .into_iter()
+                .map(|(sn, device)| DeviceState {
+                    sn,
+                    model: device.model,
+                    page: device.page,
+                    brightness: device.brightness,
+                    pinned: device.pinned,
+                })
+                .collect();
+            let status = DaemonStatus {
+                keydeck: env!("CARGO_PKG_VERSION").to_string(),
+                api: api::API_VERSION,
+                pid: std::process::id(),
+                uptime: STARTED.elapsed().as_secs(),
+                config_generation: reload.config_generation,
+                last_reload_error: reload.last_reload_error,
+                devices,
+            };
+            return serde_json::to_string(&status).ok().map(|json| json + "\n");
+        }
+        Command::TestPattern { sn } => send(tx, DeviceEvent::TestPattern { sn }),
+        Command::Diagnostics { sn } => send(tx, DeviceEvent::Diagnostics { sn }),
+        Command::Bench { sn } => {
+            let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+            crate::bench::await_reports(reply_tx);
+            send(tx, DeviceEvent::Bench { sn });
+            let reports = reply_rx.recv_timeout(BENCH_TIMEOUT).unwrap_or_default();
+            return serde_json::to_string(&reports).ok().map(|json| json + "\n");
+        }
+        Command::Services => {
+            let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+            crate::services::await_reports(reply_tx);
+            send(tx, DeviceEvent::Services);
+            let reports = reply_rx.recv_timeout(SERVICES_TIMEOUT).unwrap_or_default();
+            return serde_json::to_string(&reports).ok().map(|json| json + "\n");
+        }
+        Command::SetButton { target, patch } => {
+            let result = match serde_json::from_value::<ButtonPatch>(patch.into()) {
+                Ok(patch) => await_change(DeviceEvent::PatchButton { target, patch }, tx),
+                Err(e) => Err(format!("Invalid setbutton command: {}", e)),
+            };
+            return Some(change_reply(result));
+        }
+        Command::SetPage { target, page } => {
+            return Some(change_reply(await_change(
+                DeviceEvent::SetPage { target, page },
+                tx,
+            )));
+        }
+        Command::Screenshot { sn, file } => {
+            let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+            crate::screenshot::await_result(reply_tx);
+            send(tx, DeviceEvent::Screenshot { sn, path: file });
+            let result = reply_rx
+                .recv_timeout(SCREENSHOT_TIMEOUT)
+                .unwrap_or_else(|_| Err("The daemon did not answer in time".to_string()));
+            return Some(change_reply(result));
+        }
+        Command::ReloadStatus => {
+            let status = reload_status.read().unwrap().clone();
+            return serde_json::to_string(&status).ok().map(|json| json + "\n");
+        }
+        Command::Press { sn, button } => {
+            send(
+                tx,
+                DeviceEvent::ButtonDown {
+                    sn: sn.clone(),
+                    button_id: button,
+                },
+            );
+            send(
+                tx,
+                DeviceEvent::ButtonUp {
+                    sn,
+                    button_id: button,
+                },
+            );
+        }
+        // Taken by the connection before it gets here
+        Command::Watch { .. } => {}
+    }
+    None
+}
+
+/// Injects a configuration change and waits for the event loop to apply it
+fn await_change(event: DeviceEvent, tx: &Sender<DeviceEvent>) -> Result<(), String> {
+    let (reply_tx, reply_rx) = std::sync::mpsc::channel();
+    crate::config_patch::await_result(reply_tx);
+    send(tx, event);
+    reply_rx
+        .recv_timeout(CHANGE_TIMEOUT)
+        .unwrap_or_else(|_| Err("The daemon did not apply the change in time".to_string()))
+}
+
+/// The reply line to a configuration change
+fn change_reply(result: Result<(), String>) -> String {
+    match result {
+        Ok(()) => "ok\n".to_string(),
+        Err(e) => format!("error: {}\n", e.replace('\n', " ")),
+    }
+}
+
+/// Asks the running daemon for its [`DaemonStatus`] as a JSON value. Returns None when
+/// the daemon is not running or does not answer in time.
+pub fn query_status() -> Option<serde_json::Value> {
+    let answer = api::request(&Command::Status, Duration::from_secs(2)).ok()?;
+    serde_json::from_str(&answer).ok()
+}
+
+/// CLI client for `keydeck --hotkey <combination>`. Like `--set`, silently does nothing
+/// when the daemon is not running.
+pub fn send_hotkey(combination: &str) {
+    if let Ok(mut stream) = UnixStream::connect(control_socket_path()) {
+        let command = Command::Hotkey {
+            combination: combination.trim().to_string(),
+        };
+        if let Err(e) = stream.write_all(command.to_line().as_bytes()) {
+            eprintln!("Error: failed to write to control socket: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// CLI client for `keydeck --simulate-focus <class> <title>`; fails like `--test-pattern`
+/// when the daemon is not running.
+pub fn send_focus(class: &str, title: &str) {
+    send_command(Command::Focus {
+        class: class.trim().to_string(),
+        title: title.to_string(),
+    });
+}
+
+/// CLI client for `keydeck --test-pattern [serial]`. Unlike `--hotkey`, fails when the
+/// daemon is not running, as nothing would be shown.
+pub fn send_test_pattern(serial: Option<&str>) {
+    send_command(Command::TestPattern {
+        sn: serial.map(str::to_string),
+    });
+}
+
+/// CLI client for `keydeck --diagnostics [serial]`; fails like `--test-pattern` when the
+/// daemon is not running.
+pub fn send_diagnostics(serial: Option<&str>) {
+    send_command(Command::Diagnostics {
+        sn: serial.map(str::to_string),
+    });
+}
+
+/// CLI client for `keydeck --set-button <GROUP/PAGE/BUTTON>`. Fails when the daemon is
+/// not running or rejects the change.
+pub fn send_button_patch(target: &str, patch: ButtonPatch) {
+    match serde_json::to_value(patch) {
+        Ok(serde_json::Value::Object(patch)) => send_change(Command::SetButton {
+            target: target.to_string(),
+            patch,
+        }),
+        Ok(_) => unreachable!("a button patch is a JSON object"),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// CLI client for `keydeck --set-page <GROUP|SERIAL> <PAGE>`; fails like `--set-button`
+pub fn send_set_page(target: &str, page: &str) {
+    send_change(Command::SetPage {
+        target: target.trim().to_string(),
+        page: page.trim().to_string(),
+    });
+}
+
+/// CLI client for `keydeck --screenshot <SERIAL> <FILE>`: has the daemon write the keys
+/// of the device as a PNG at `file`, taken from the current directory when relative
+pub fn send_screenshot(serial: &str, file: &str) {
+    let path = std::env::current_dir()
+        .map(|dir| dir.join(file.trim()))
+        .unwrap_or_else(|_| PathBuf::from(file.trim()));
+    send_change(Command::Screenshot {
+        sn: serial.trim().to_string(),
+        file: path,
+    });
+}
+
+/// Writes a configuration change and exits with an error when it is not applied
+fn send_change(command: Command) {
+    let Ok(mut stream) = UnixStream::connect(control_socket_path()) else {
+        eprintln!("Error: the keydeck daemon is not running");
+        std::process::exit(1);
+    };
+    if let Err(e) = stream.write_all(command.to_line().as_bytes()) {
+        eprintln!("Error: failed to write to control socket: {}", e);
+        std::process::exit(1);
+    }
+    let mut reply = String::new();
+    let _ = BufReader::new(stream).read_line(&mut reply);
+    match reply.trim() {
+        "ok" => {}
+        reply => {
+            let reason = reply.strip_prefix("error: ").unwrap_or(reply);
+            eprintln!(
+                "Error: {}",
+                if reason.is_empty() {
+                    "no answer from the daemon"
+                } else {
+                    reason
+                }
+            );
+            std::process::exit(1);
         }
-        other => error_log!("Unknown control command: {:?}", other),
+    }
+}
+
+/// Writes a command that is not answered, failing when the daemon is not running
+fn send_command(command: Command) {
+    let Ok(mut stream) = UnixStream::connect(control_socket_path()) else {
+        eprintln!("Error: the keydeck daemon is not running");
+        std::process::exit(1);
+    };
+    if let Err(e) = stream.write_all(command.to_line().as_bytes()) {
+        eprintln!("Error: failed to write to control socket: {}", e);
+        std::process::exit(1);
     }
 }
 
@@ -118,16 +463,15 @@ pub fn send_context_var(arg: &str) {
         eprintln!("Error: --set requires key=value");
         std::process::exit(1);
     }
-    let line = if value.is_empty() {
-        format!("clearvar {}\n", key)
-    } else {
-        format!("setvar {} {}\n", key, value)
+    let command = Command::SetVar {
+        key: key.to_string(),
+        value: (!value.is_empty()).then(|| value.to_string()),
     };
 
     let path = control_socket_path();
     match UnixStream::connect(&path) {
         Ok(mut stream) => {
-            if let Err(e) = stream.write_all(line.as_bytes()) {
+            if let Err(e) = stream.write_all(command.to_line().as_bytes()) {
                 eprintln!("Error: failed to write to control socket: {}", e);
                 std::process::exit(1);
             }
@@ -137,3 +481,87 @@ pub fn send_context_var(arg: &str) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ReloadStatus;
+    use std::sync::mpsc::channel;
+    use std::sync::RwLock;
+
+    #[test]
+    fn reload_status_tells_the_last_reload() {
+        let (tx, _events) = channel();
+        let reload_status = Arc::new(RwLock::new(ReloadStatus {
+            config_generation: 3,
+            last_reload_error: Some("line 2: unknown field".to_string()),
+        }));
+        let reply = handle_command(&tx, &reload_status, &Arc::default(), Command::ReloadStatus);
+        assert_eq!(
+            reply.as_deref(),
+            Some("{\"config_generation\":3,\"last_reload_error\":\"line 2: unknown field\"}\n")
+        );
+    }
+
+    #[test]
+    fn simulated_focus_reaches_the_devices_as_a_focus_change() {
+        let (tx, events) = channel();
+        let reload_status = Arc::new(RwLock::new(ReloadStatus::default()));
+        for line in ["focus firefox GitHub - Mozilla Firefox", "focus kate"] {
+            let command = Command::parse(line).unwrap();
+            assert_eq!(
+                handle_command(&tx, &reload_status, &Arc::default(), command),
+                None
+            );
+        }
+        let focused: Vec<_> = events
+            .try_iter()
+            .map(|event| match event {
+                DeviceEvent::FocusChanges { class, title } => (class, title),
+                other => panic!("unexpected event: {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            focused,
+            [
+                (
+                    "firefox".to_string(),
+                    "GitHub - Mozilla Firefox".to_string()
+                ),
+                ("kate".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn status_tells_the_version_and_the_devices() {
+        let (tx, _events) = channel();
+        let reload_status = Arc::new(RwLock::new(ReloadStatus {
+            config_generation: 2,
+            last_reload_error: None,
+        }));
+        dbus_state::update("STATUS1", |device| {
+            device.model = "Stream Deck MK.2".to_string();
+            device.page = "Media".to_string();
+            device.brightness = 40;
+        });
+        let reply = handle_command(&tx, &reload_status, &Arc::default(), Command::Status).unwrap();
+        dbus_state::remove("STATUS1");
+
+        let status: DaemonStatus = serde_json::from_str(&reply).unwrap();
+        assert_eq!(status.keydeck, env!("CARGO_PKG_VERSION"));
+        assert_eq!(status.api, api::API_VERSION);
+        assert_eq!(status.pid, std::process::id());
+        assert_eq!(status.config_generation, 2);
+        // Other tests publish devices too
+        let device = status.devices.iter().find(|d| d.sn == "STATUS1").unwrap();
+        assert_eq!(
+            (
+                device.model.as_str(),
+                device.page.as_str(),
+                device.brightness
+            ),
+            ("Stream Deck MK.2", "Media", 40)
+        );
+    }
+}
@@ -3,32 +3,31 @@
 
 use crate::device_manager::DeviceManager;
 use crate::event::{send, DeviceEvent};
-use crate::verbose_log;
+use crate::{runtime, verbose_log};
 use std::collections::HashSet;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
-use std::thread;
+use std::time::Duration;
 
-pub fn listener_device(
-    tx: &Sender<DeviceEvent>,
-    active: &Arc<AtomicBool>,
-    should_reset: &Arc<AtomicBool>,
-) {
-    let active = active.clone();
+pub fn listener_device(tx: &Sender<DeviceEvent>, should_reset: &Arc<AtomicBool>) {
     let should_reset = should_reset.clone();
     let tx = tx.clone();
-    thread::spawn(move || {
+    runtime::spawn(move |_| async move {
         let mut devices: HashSet<String> = HashSet::new();
         verbose_log!("Starting device listener");
-        while active.load(std::sync::atomic::Ordering::Relaxed) {
-            if should_reset.load(std::sync::atomic::Ordering::Relaxed) {
+        loop {
+            if should_reset.load(Ordering::Relaxed) {
                 devices.clear();
-                should_reset.store(false, std::sync::atomic::Ordering::Relaxed);
+                should_reset.store(false, Ordering::Relaxed);
             }
 
+            // Enumerating goes through the USB stack, so it must not hold a worker
+            let connected = tokio::task::spawn_blocking(DeviceManager::enumerate_connected_devices)
+                .await
+                .unwrap_or_default();
             let mut current = devices.clone();
-            for serial in DeviceManager::enumerate_connected_devices() {
+            for serial in connected {
                 if current.contains(&serial) {
                     current.remove(&serial);
                 } else {
@@ -42,8 +41,7 @@ pub fn listener_device(
                 devices.remove(&removed);
                 send(&tx, DeviceEvent::RemovedDevice { sn: removed });
             }
-            thread::sleep(std::time::Duration::from_secs_f64(2.0));
+            tokio::time::sleep(Duration::from_secs_f64(2.0)).await;
         }
-        verbose_log!("Exiting device listener");
     });
 }
@@ -1,51 +1,171 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
+//! Focus tracking on Linux. One backend runs at a time, the first ready one of the
+//! configured `focus_backend` order. When it dies, the next ready one takes over; while
+//! a fallback runs, the backends preferred to it are probed every [`YIELD_CHECK_INTERVAL`]
+//! and the fallback stops as soon as one of them is ready again, so e.g. KWin takes over
+//! from X11 once it has restarted.
+
 use crate::event::{send, DeviceEvent};
-use crate::{error_log, verbose_log};
+use crate::kwin_script::KWinScriptClient;
+use crate::pages::FocusBackend;
+use crate::session::{detect_session_type, SessionType};
+use crate::{error_log, info_log, verbose_log};
 use std::error::Error;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 use x11rb::atom_manager;
 use x11rb::connection::Connection;
 use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, EventMask, PropertyNotifyEvent, Window};
 use x11rb::rust_connection::RustConnection;
 
+/// Shortest and longest pause between two readiness probes while no backend is available
+const PROBE_INTERVAL_MIN: Duration = Duration::from_secs(1);
+const PROBE_INTERVAL_MAX: Duration = Duration::from_secs(10);
+
+/// Under Wayland, X11 (XWayland) only sees part of the windows, so it is a fallback for
+/// when KWin did not come up within this time after start
+const KWIN_STARTUP_GRACE: Duration = Duration::from_secs(60);
+
+/// Pause between two polls of the X server while no event is pending
+const X11_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often a running fallback backend checks whether a preferred one is ready again
+const YIELD_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Order used when the configuration sets no `focus_backend`
+const DEFAULT_ORDER: [FocusBackend; 2] = [FocusBackend::KWin, FocusBackend::X11];
+
+/// Configured `focus_backend` order, replaced on every configuration load
+static BACKEND_ORDER: Mutex<Option<Vec<FocusBackend>>> = Mutex::new(None);
+
+/// Sets the order backends are tried in; None or an empty list restores the default.
+/// A running backend that is no longer listed, or that is now preceded by a ready one,
+/// stops at its next check.
+pub fn set_backend_order(order: Option<Vec<FocusBackend>>) {
+    *BACKEND_ORDER.lock().unwrap() = order.filter(|order| !order.is_empty());
+}
+
+fn backend_order() -> Vec<FocusBackend> {
+    BACKEND_ORDER
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| DEFAULT_ORDER.to_vec())
+}
+
+/// Whether the backend can be started right now
+fn is_ready(backend: FocusBackend) -> bool {
+    match backend {
+        FocusBackend::KWin => KWinScriptClient::is_scripting_available(),
+        FocusBackend::X11 => RustConnection::connect(None).is_ok(),
+    }
+}
+
+/// Runs the backend until it disconnects or yields. Returns false if it could not start.
+fn run(backend: FocusBackend, tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>) -> bool {
+    match backend {
+        FocusBackend::KWin => crate::listener_focus_wayland::try_wayland_listener(tx, active),
+        FocusBackend::X11 => try_x11_listener(tx, active),
+    }
+}
+
+/// Whether `backend` may be tried now. Under Wayland, X11 only sees XWayland windows, so
+/// while KWin is preferred to it, X11 waits for KWin to come up first.
+fn may_start(
+    backend: FocusBackend,
+    order: &[FocusBackend],
+    wayland: bool,
+    started: Instant,
+) -> bool {
+    let position = |backend| order.iter().position(|b| *b == backend);
+    let kwin_first = matches!(
+        (position(FocusBackend::KWin), position(FocusBackend::X11)),
+        (Some(kwin), Some(x11)) if kwin < x11
+    );
+    backend != FocusBackend::X11
+        || !kwin_first
+        || !wayland
+        || started.elapsed() >= KWIN_STARTUP_GRACE
+}
+
+/// The first backend of the configured order that is ready, if any
+fn ready_backend(started: Instant) -> Option<FocusBackend> {
+    let order = backend_order();
+    let wayland = detect_session_type() == SessionType::Wayland;
+    order
+        .iter()
+        .copied()
+        .filter(|backend| may_start(*backend, &order, wayland, started))
+        .find(|backend| is_ready(*backend))
+}
+
+/// Whether the running `backend` should stop: it is no longer configured, or a backend
+/// preferred to it is ready
+pub fn should_yield(backend: FocusBackend) -> bool {
+    let order = backend_order();
+    match order.iter().position(|b| *b == backend) {
+        None => true,
+        Some(index) => order[..index].iter().any(|preferred| is_ready(*preferred)),
+    }
+}
+
 pub fn listener_focus(tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>) {
     let tx = tx.clone();
     let active = active.clone();
 
     thread::spawn(move || {
         verbose_log!("Starting focus listener with auto-detection loop");
+        let started = Instant::now();
+        let mut probe_interval = PROBE_INTERVAL_MIN;
+        let mut waiting = false;
 
-        while active.load(std::sync::atomic::Ordering::Relaxed) {
-            // Try Wayland first
-            verbose_log!("Trying Wayland focus listener...");
-            if crate::listener_focus_wayland::try_wayland_listener(&tx, &active) {
-                verbose_log!("Wayland listener exited, will retry...");
-                thread::sleep(std::time::Duration::from_secs(5));
+        while active.load(Ordering::Relaxed) {
+            // At session start the compositor may not be up yet: probe with backoff
+            // until a backend answers, instead of attaching to a half-ready one
+            let Some(backend) = ready_backend(started) else {
+                if !waiting {
+                    info_log!(
+                        "Waiting for a focus backend ({:?}) to become available",
+                        backend_order()
+                    );
+                    waiting = true;
+                }
+                thread::sleep(probe_interval);
+                probe_interval = (probe_interval * 2).min(PROBE_INTERVAL_MAX);
                 continue;
-            }
+            };
+            waiting = false;
+            probe_interval = PROBE_INTERVAL_MIN;
 
-            // Wayland failed, try X11
-            verbose_log!("Wayland unavailable, trying X11 focus listener...");
-            if try_x11_listener(&tx, &active) {
-                verbose_log!("X11 listener exited, will retry...");
-                thread::sleep(std::time::Duration::from_secs(5));
-                continue;
+            verbose_log!("Trying {:?} focus listener...", backend);
+            if run(backend, &tx, &active) {
+                verbose_log!("{:?} listener exited, will retry...", backend);
+            } else {
+                verbose_log!("{:?} listener failed to start, retrying...", backend);
             }
-
-            // Both failed - wait and retry
-            verbose_log!("Both Wayland and X11 unavailable, retrying in 5 seconds...");
-            thread::sleep(std::time::Duration::from_secs(5));
+            thread::sleep(Duration::from_secs(5));
         }
 
         verbose_log!("Focus listener thread exiting");
     });
 }
 
+/// Announces a started backend, so the daemon takes the focus that follows as current
+pub fn report_backend_ready(tx: &Sender<DeviceEvent>, backend: &str) {
+    info_log!("Focus tracking through {}", backend);
+    send(
+        tx,
+        DeviceEvent::FocusBackendReady {
+            backend: backend.to_string(),
+        },
+    );
+}
+
 /// Try to run the X11 focus listener. Returns true if it ran successfully for a while,
 /// false if it failed to start.
 fn try_x11_listener(tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>) -> bool {
@@ -58,18 +178,34 @@ fn try_x11_listener(tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>) -> bool
     };
 
     verbose_log!("X11 focus listener started");
+    report_backend_ready(tx, "X11");
+    // Unlike KWin, X11 only reports changes: start from the window focused right now
+    match listener.active_window_focus() {
+        Ok(Some((class, title))) => send(tx, DeviceEvent::FocusChanges { class, title }),
+        Ok(None) => {}
+        Err(e) => error_log!("Error while reading the focused X11 window: {}", e),
+    }
 
-    // Event loop
-    while active.load(std::sync::atomic::Ordering::Relaxed) {
-        match listener.get_next_focus_change() {
-            Ok((class, title)) => {
-                send(&tx, DeviceEvent::FocusChanges { class, title });
+    // Event loop, polling so that a preferred backend can take over
+    let mut last_yield_check = Instant::now();
+    while active.load(Ordering::Relaxed) {
+        match listener.poll_focus_change() {
+            Ok(Some((class, title))) => {
+                send(tx, DeviceEvent::FocusChanges { class, title });
             }
+            Ok(None) => thread::sleep(X11_POLL_INTERVAL),
             Err(e) => {
                 error_log!("X11 focus listener error: {}", e);
                 return true; // Ran but disconnected
             }
         }
+        if last_yield_check.elapsed() >= YIELD_CHECK_INTERVAL {
+            last_yield_check = Instant::now();
+            if should_yield(FocusBackend::X11) {
+                info_log!("X11 focus listener giving way to a preferred focus backend");
+                return true;
+            }
+        }
     }
 
     verbose_log!("X11 focus listener stopped (shutdown requested)");
@@ -118,39 +254,48 @@ impl X11FocusListener {
         })
     }
 
-    fn get_next_focus_change(&mut self) -> Result<(String, String), Box<dyn Error>> {
-        loop {
-            let event = self.conn.wait_for_event()?;
+    /// The next focus change among the events received so far, if any
+    fn poll_focus_change(&mut self) -> Result<Option<(String, String)>, Box<dyn Error>> {
+        while let Some(event) = self.conn.poll_for_event()? {
             if let x11rb::protocol::Event::PropertyNotify(PropertyNotifyEvent { atom, .. }) = event
             {
                 if atom == self.atoms._NET_ACTIVE_WINDOW {
-                    if let Ok(reply) = self
-                        .conn
-                        .get_property(
-                            false,
-                            self.root,
-                            self.atoms._NET_ACTIVE_WINDOW,
-                            AtomEnum::WINDOW,
-                            0,
-                            1,
-                        )?
-                        .reply()
-                    {
-                        if let Some(window_id) = reply.value32().and_then(|mut v| v.next()) {
-                            if self.last_active_window != Some(window_id) {
-                                self.last_active_window = Some(window_id);
-                                let wm_class = self.get_window_class(window_id)?;
-                                let wm_title = self.get_window_title(window_id)?;
-                                return Ok((
-                                    wm_class.unwrap_or_default(),
-                                    wm_title.unwrap_or_default(),
-                                ));
-                            }
-                        }
+                    if let Some(focus) = self.active_window_focus()? {
+                        return Ok(Some(focus));
                     }
                 }
             }
         }
+        Ok(None)
+    }
+
+    /// Class and title of the active window, if it differs from the last one reported
+    fn active_window_focus(&mut self) -> Result<Option<(String, String)>, Box<dyn Error>> {
+        if let Ok(reply) = self
+            .conn
+            .get_property(
+                false,
+                self.root,
+                self.atoms._NET_ACTIVE_WINDOW,
+                AtomEnum::WINDOW,
+                0,
+                1,
+            )?
+            .reply()
+        {
+            if let Some(window_id) = reply.value32().and_then(|mut v| v.next()) {
+                if self.last_active_window != Some(window_id) {
+                    self.last_active_window = Some(window_id);
+                    let wm_class = self.get_window_class(window_id)?;
+                    let wm_title = self.get_window_title(window_id)?;
+                    return Ok(Some((
+                        wm_class.unwrap_or_default(),
+                        wm_title.unwrap_or_default(),
+                    )));
+                }
+            }
+        }
+        Ok(None)
     }
 
     fn get_window_class(&self, window: Window) -> Result<Option<String>, Box<dyn Error>> {
@@ -212,3 +357,28 @@ impl X11FocusListener {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn x11_waits_for_kwin_only_when_kwin_is_preferred() {
+        let started = Instant::now();
+        let x11_only = [FocusBackend::X11];
+        let x11_first = [FocusBackend::X11, FocusBackend::KWin];
+        assert!(may_start(FocusBackend::X11, &x11_only, true, started));
+        assert!(may_start(FocusBackend::X11, &x11_first, true, started));
+        assert!(may_start(FocusBackend::KWin, &DEFAULT_ORDER, true, started));
+        if let Some(long_ago) = started.checked_sub(KWIN_STARTUP_GRACE) {
+            assert!(may_start(FocusBackend::X11, &DEFAULT_ORDER, true, long_ago));
+        }
+    }
+
+    #[test]
+    fn x11_waits_for_kwin_only_under_wayland() {
+        let started = Instant::now();
+        assert!(!may_start(FocusBackend::X11, &DEFAULT_ORDER, true, started));
+        assert!(may_start(FocusBackend::X11, &DEFAULT_ORDER, false, started));
+    }
+}
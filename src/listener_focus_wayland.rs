@@ -2,13 +2,76 @@
 // Copyright (C) 2025 Panayotis Katsaloulis
 
 use crate::event::DeviceEvent;
-use crate::kwin_script::KWinScriptClient;
-use crate::{error_log, verbose_log};
+use crate::kwin_script::{KWinScriptClient, WindowInfo};
+use crate::pages::FocusBackend;
+use crate::{error_log, info_log, verbose_log};
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{Receiver, Sender};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+/// How long to wait for KWin to come back after it left the session bus, before giving
+/// way to the next focus backend
+const KWIN_RETURN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where KWin stands on the session bus, as far as the focus listener is concerned
+#[derive(Debug, PartialEq)]
+enum KWinPresence {
+    Present,
+    /// KWin took its name again; its scripts are gone
+    Returned,
+    /// KWin left the bus and may still come back
+    Away,
+    /// KWin left the bus longer than [`KWIN_RETURN_TIMEOUT`] ago
+    GaveUp,
+}
+
+/// Follows KWin leaving and returning to the session bus. `change` is the latest change
+/// of its name owner, if any (true when KWin took the name), and `gone` since when it is
+/// away.
+fn kwin_presence(change: Option<bool>, gone: &mut Option<Instant>, now: Instant) -> KWinPresence {
+    match change {
+        Some(true) => {
+            *gone = None;
+            return KWinPresence::Returned;
+        }
+        Some(false) => {
+            info_log!("KWin left the session bus, waiting for it to return");
+            *gone = Some(now);
+        }
+        None => {}
+    }
+    match gone {
+        Some(since) if now.duration_since(*since) > KWIN_RETURN_TIMEOUT => KWinPresence::GaveUp,
+        Some(_) => KWinPresence::Away,
+        None => KWinPresence::Present,
+    }
+}
+
+/// Starts the listener script, retrying a few times before giving up: a transient
+/// proof-of-life miss (e.g. a busy compositor at boot) must not make us fail, which
+/// would send the orchestrator to the X11 fallback that stalls under Wayland. A genuine
+/// "no KWin" failure still errors out quickly each attempt.
+fn start_listener(client: &mut KWinScriptClient) -> Option<Receiver<WindowInfo>> {
+    let mut attempt = 0;
+    loop {
+        match client.start_focus_listener() {
+            Ok(r) => return Some(r),
+            Err(e) => {
+                attempt += 1;
+                if attempt >= 3 {
+                    error_log!("Failed to start KWin focus listener after {} attempts: {}", attempt, e);
+                    let _ = client.stop_focus_listener();
+                    return None;
+                }
+                verbose_log!("KWin focus listener start attempt {} failed ({}), retrying...", attempt, e);
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        }
+    }
+}
+
 /// Try to run the Wayland/KWin focus listener. Returns true if it ran successfully for a while,
 /// false if it failed to start.
 pub fn try_wayland_listener(tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>) -> bool {
@@ -21,38 +84,61 @@ pub fn try_wayland_listener(tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>)
         }
     };
 
-    // Start the event listener. Retry a few times before giving up: a transient
-    // proof-of-life miss (e.g. a busy compositor at boot) must not make us return
-    // false, which would send the orchestrator to the X11 fallback that stalls under
-    // Wayland. A genuine "no KWin" failure still errors out quickly each attempt.
-    let receiver = {
-        let mut attempt = 0;
-        loop {
-            match client.start_focus_listener() {
-                Ok(r) => break r,
-                Err(e) => {
-                    attempt += 1;
-                    if attempt >= 3 {
-                        error_log!("Failed to start KWin focus listener after {} attempts: {}", attempt, e);
-                        let _ = client.stop_focus_listener();
-                        return false;
-                    }
-                    verbose_log!("KWin focus listener start attempt {} failed ({}), retrying...", attempt, e);
-                    std::thread::sleep(Duration::from_secs(1));
-                }
-            }
+    let Some(mut receiver) = start_listener(&mut client) else {
+        return false;
+    };
+    // Without the watch, a KWin restart is still caught by the health check below
+    let kwin_owner = match client.watch_kwin() {
+        Ok(changes) => Some(changes),
+        Err(e) => {
+            verbose_log!("Not watching KWin restarts: {}", e);
+            None
         }
     };
+    // Since when KWin is gone from the session bus
+    let mut kwin_gone: Option<Instant> = None;
 
     verbose_log!("KWin focus listener started");
+    // The script reports the window focused right now first, so devices catch up
+    crate::listener_focus::report_backend_ready(tx, "KWin");
     let mut last_event = Instant::now();
     let mut last_health_check = Instant::now();
+    // Desktop and activity last published as context variables, and activity names by id
+    let mut workspace: Option<(String, String)> = None;
+    let mut activity_names: HashMap<String, String> = HashMap::new();
 
     // Event loop - blocks until events arrive (no polling!)
     while active.load(Ordering::Relaxed) {
         match receiver.recv_timeout(Duration::from_millis(100)) {
             Ok(window) => {
                 last_event = Instant::now();
+                let activity = match window.activity.as_str() {
+                    "" => String::new(),
+                    id => activity_names
+                        .entry(id.to_string())
+                        .or_insert_with(|| {
+                            client.activity_name(id).unwrap_or_else(|| id.to_string())
+                        })
+                        .clone(),
+                };
+                let current = (window.desktop.clone(), activity);
+                if workspace.as_ref() != Some(&current) {
+                    verbose_log!(
+                        "Workspace changed: desktop '{}', activity '{}'",
+                        current.0,
+                        current.1
+                    );
+                    for (key, value) in [("desktop", &current.0), ("activity", &current.1)] {
+                        let value = (!value.is_empty()).then(|| value.clone());
+                        if let Err(e) = tx.send(DeviceEvent::SetContextVar {
+                            key: key.to_string(),
+                            value,
+                        }) {
+                            error_log!("Failed to send workspace change event: {}", e);
+                        }
+                    }
+                    workspace = Some(current);
+                }
                 // Only send events for valid windows
                 if !window.class.is_empty() && window.class != "<no class>" {
                     verbose_log!("Focus changed: {} - {}", window.class, window.title);
@@ -68,6 +154,33 @@ pub fn try_wayland_listener(tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>)
             }
             Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
                 // Timeout is normal - allows checking 'active' flag.
+                // A restarted KWin has none of our scripts: register the listener again
+                // in place, which reports the focused window first, instead of falling
+                // back to another backend meanwhile.
+                let change = kwin_owner
+                    .as_ref()
+                    .and_then(|changes| changes.try_recv().ok());
+                match kwin_presence(change, &mut kwin_gone, Instant::now()) {
+                    KWinPresence::Returned => {
+                        info_log!("KWin is back, registering the focus listener again");
+                        match start_listener(&mut client) {
+                            Some(restarted) => receiver = restarted,
+                            None => break,
+                        }
+                        last_event = Instant::now();
+                        last_health_check = Instant::now();
+                        workspace = None;
+                        crate::listener_focus::report_backend_ready(tx, "KWin");
+                        continue;
+                    }
+                    // Nothing to check or call while KWin is away
+                    KWinPresence::Away => continue,
+                    KWinPresence::GaveUp => {
+                        error_log!("KWin did not return to the session bus - restarting listener");
+                        break;
+                    }
+                    KWinPresence::Present => {}
+                }
                 // Health check: a KWin restart silently drops all dynamically-loaded
                 // scripts, so our listener would go quiet forever. Poll cheaply every
                 // 10s and re-install promptly (via the orchestrator retry) if our
@@ -78,6 +191,10 @@ pub fn try_wayland_listener(tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>)
                         error_log!("KWin focus listener script vanished (KWin restart?) - restarting listener");
                         break;
                     }
+                    if crate::listener_focus::should_yield(FocusBackend::KWin) {
+                        info_log!("KWin focus listener giving way to a preferred focus backend");
+                        break;
+                    }
                 }
                 // Backstop watchdog for the pathological case where the script is still
                 // loaded but silently delivering nothing.
@@ -102,3 +219,34 @@ pub fn try_wayland_listener(tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>)
     verbose_log!("KWin focus listener stopped");
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_listener_waits_a_while_for_kwin_to_return() {
+        let start = Instant::now();
+        let mut gone = None;
+        assert_eq!(kwin_presence(None, &mut gone, start), KWinPresence::Present);
+
+        assert_eq!(
+            kwin_presence(Some(false), &mut gone, start),
+            KWinPresence::Away
+        );
+        let later = start + KWIN_RETURN_TIMEOUT / 2;
+        assert_eq!(kwin_presence(None, &mut gone, later), KWinPresence::Away);
+        assert_eq!(
+            kwin_presence(Some(true), &mut gone, later),
+            KWinPresence::Returned
+        );
+        assert_eq!(kwin_presence(None, &mut gone, later), KWinPresence::Present);
+
+        kwin_presence(Some(false), &mut gone, start);
+        let too_late = start + KWIN_RETURN_TIMEOUT * 2;
+        assert_eq!(
+            kwin_presence(None, &mut gone, too_late),
+            KWinPresence::GaveUp
+        );
+    }
+}
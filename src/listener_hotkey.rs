@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Global hotkeys on X11: grabs the configured key combinations on the root window and
+//! turns their presses into [`DeviceEvent::Hotkey`]. Wayland offers no such grab, there
+//! the desktop's own shortcut settings run `keydeck --hotkey <combination>` instead.
+
+use crate::event::{send, DeviceEvent};
+use crate::keyboard::hotkey_to_grab;
+use crate::server::HotkeyUpdates;
+use crate::session::{detect_session_type, SessionType};
+use crate::{error_log, info_log, verbose_log, warn_log};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt, GrabMode, Keycode, ModMask};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+/// How often the listener checks for key presses and updated hotkeys
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Caps Lock (LOCK) and Num Lock (usually M2) must not prevent a hotkey from matching
+const LOCK_MODIFIERS: u16 = 0b1_0010;
+const IGNORED_MODIFIERS: [u16; 4] = [0, 0b10, 0b1_0000, LOCK_MODIFIERS];
+
+pub fn listener_hotkey(
+    tx: &Sender<DeviceEvent>,
+    still_active: &Arc<AtomicBool>,
+    updates: &HotkeyUpdates,
+) {
+    if detect_session_type() != SessionType::X11 {
+        info_log!("Global hotkeys are not grabbed on Wayland; bind them to 'keydeck --hotkey <combination>' in the desktop settings");
+        return;
+    }
+    let tx = tx.clone();
+    let still_active = still_active.clone();
+    let updates = updates.clone();
+    thread::spawn(move || {
+        let (conn, screen_num) = match RustConnection::connect(None) {
+            Ok(connection) => connection,
+            Err(e) => {
+                error_log!("Failed to connect to X11 for global hotkeys: {}", e);
+                return;
+            }
+        };
+        let root = conn.setup().roots[screen_num].root;
+        let mut grabbed: Vec<(ModMask, Keycode, String)> = Vec::new();
+
+        while still_active.load(Ordering::Relaxed) {
+            let update = updates.lock().unwrap().take();
+            if let Some(combinations) = update {
+                ungrab_all(&conn, root, &mut grabbed);
+                grab_all(&conn, root, &combinations, &mut grabbed);
+            }
+
+            loop {
+                match conn.poll_for_event() {
+                    Ok(Some(Event::KeyPress(event))) => {
+                        let state = u16::from(event.state) & !LOCK_MODIFIERS;
+                        if let Some((_, _, combination)) =
+                            grabbed.iter().find(|(modifiers, keycode, _)| {
+                                *keycode == event.detail && u16::from(*modifiers) == state
+                            })
+                        {
+                            verbose_log!("Hotkey {} pressed", combination);
+                            send(
+                                &tx,
+                                DeviceEvent::Hotkey {
+                                    combination: combination.clone(),
+                                },
+                            );
+                        }
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => break,
+                    Err(e) => {
+                        error_log!("Lost the X11 connection of global hotkeys: {}", e);
+                        return;
+                    }
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        ungrab_all(&conn, root, &mut grabbed);
+    });
+}
+
+fn grab_all(
+    conn: &RustConnection,
+    root: u32,
+    combinations: &[String],
+    grabbed: &mut Vec<(ModMask, Keycode, String)>,
+) {
+    let setup = conn.setup();
+    let min_keycode = setup.min_keycode;
+    let mapping = match conn
+        .get_keyboard_mapping(min_keycode, setup.max_keycode - min_keycode + 1)
+        .map_err(|e| e.to_string())
+        .and_then(|cookie| cookie.reply().map_err(|e| e.to_string()))
+    {
+        Ok(mapping) => mapping,
+        Err(e) => {
+            error_log!(
+                "Failed to read the keyboard mapping for global hotkeys: {}",
+                e
+            );
+            return;
+        }
+    };
+
+    for combination in combinations {
+        let (modifiers, keycode) = match hotkey_to_grab(combination, &mapping, min_keycode) {
+            Ok(grab) => grab,
+            Err(e) => {
+                error_log!("Invalid hotkey '{}': {}", combination, e);
+                continue;
+            }
+        };
+        let mut taken = false;
+        for extra in IGNORED_MODIFIERS {
+            let result = conn
+                .grab_key(
+                    false,
+                    root,
+                    modifiers | ModMask::from(extra),
+                    keycode,
+                    GrabMode::ASYNC,
+                    GrabMode::ASYNC,
+                )
+                .map_err(|e| e.to_string())
+                .and_then(|cookie| cookie.check().map_err(|e| e.to_string()));
+            taken |= result.is_err();
+        }
+        if taken {
+            warn_log!(
+                "Hotkey '{}' is already taken by another application",
+                combination
+            );
+        }
+        grabbed.push((modifiers, keycode, combination.clone()));
+    }
+    verbose_log!("Grabbed {} global hotkeys", grabbed.len());
+}
+
+fn ungrab_all(conn: &RustConnection, root: u32, grabbed: &mut Vec<(ModMask, Keycode, String)>) {
+    for (modifiers, keycode, _) in grabbed.drain(..) {
+        for extra in IGNORED_MODIFIERS {
+            let _ = conn.ungrab_key(keycode, root, modifiers | ModMask::from(extra));
+        }
+    }
+    let _ = conn.flush();
+}
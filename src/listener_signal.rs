@@ -1,16 +1,23 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
-use crate::{error_log, event::DeviceEvent};
-use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
-use signal_hook::iterator::Signals;
+use crate::{error_log, event::DeviceEvent, runtime};
 use std::sync::mpsc::Sender;
-use std::thread;
+use tokio::signal::unix::{signal, Signal, SignalKind};
+
+/// Streams of SIGINT, SIGTERM and SIGHUP
+fn signals() -> std::io::Result<(Signal, Signal, Signal)> {
+    Ok((
+        signal(SignalKind::interrupt())?,
+        signal(SignalKind::terminate())?,
+        signal(SignalKind::hangup())?,
+    ))
+}
 
 pub fn listener_signal(tx: &Sender<DeviceEvent>) {
     let tx = tx.clone();
-    thread::spawn(move || {
-        let mut signals = match Signals::new(&[SIGINT, SIGTERM, SIGHUP]) {
+    runtime::spawn(move |_| async move {
+        let (mut interrupt, mut terminate, mut hangup) = match signals() {
             Ok(s) => s,
             Err(e) => {
                 error_log!("Failed to initialize signal handler: {}", e);
@@ -19,12 +26,12 @@ pub fn listener_signal(tx: &Sender<DeviceEvent>) {
             }
         };
 
-        for signal in signals.forever() {
+        loop {
             // Differentiate between signals
-            let event = match signal {
-                SIGHUP => DeviceEvent::Reload,
-                SIGINT | SIGTERM => DeviceEvent::Exit,
-                _ => continue, // Ignore unexpected signals
+            let event = tokio::select! {
+                _ = hangup.recv() => DeviceEvent::Reload,
+                _ = interrupt.recv() => DeviceEvent::Exit,
+                _ = terminate.recv() => DeviceEvent::Exit,
             };
             // Silently ignore send errors - if receiver is dropped, we're shutting down anyway
             let _ = tx.send(event);
@@ -2,12 +2,11 @@
 // Copyright (C) 2025 Panayotis Katsaloulis
 
 use crate::event::DeviceEvent;
-use crate::error_log;
+use crate::{error_log, runtime};
+use futures_util::StreamExt;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
-use std::thread;
-use tokio::runtime::Runtime;
 
 #[zbus::proxy(
     interface = "org.freedesktop.login1.Manager",
@@ -19,63 +18,40 @@ trait Login1Manager {
     fn prepare_for_sleep(&self, start: bool);
 }
 
-pub fn listener_sleep(
-    tx: &Sender<DeviceEvent>,
-    still_active: &Arc<AtomicBool>,
-    should_reset: &Arc<AtomicBool>,
-) {
+pub fn listener_sleep(tx: &Sender<DeviceEvent>, should_reset: &Arc<AtomicBool>) {
     let tx = tx.clone();
-    let still_active = still_active.clone();
     let should_reset = should_reset.clone();
-    thread::spawn(move || {
-        // zbus::blocking requires a tokio runtime context on the current thread
-        let rt = match Runtime::new() {
-            Ok(rt) => rt,
-            Err(e) => {
-                error_log!("Failed to create tokio runtime for sleep listener: {}", e);
-                return;
-            }
-        };
-        let _guard = rt.enter();
-        let conn = match zbus::blocking::Connection::system() {
-            Ok(conn) => conn,
-            Err(e) => {
-                error_log!("Failed to connect to system D-Bus: {}", e);
-                return;
-            }
-        };
-
-        let proxy = match Login1ManagerProxyBlocking::builder(&conn).build() {
-            Ok(proxy) => proxy,
-            Err(e) => {
-                error_log!("Failed to create login1 proxy: {}", e);
-                return;
-            }
-        };
+    runtime::spawn(move |_| async move {
+        if let Err(e) = follow_sleep(&tx, &should_reset).await {
+            error_log!("{}", e);
+        }
+    });
+}
 
-        let signals = match proxy.receive_prepare_for_sleep() {
-            Ok(signals) => signals,
-            Err(e) => {
-                error_log!("Failed to subscribe to sleep signal: {}", e);
-                return;
-            }
-        };
+async fn follow_sleep(tx: &Sender<DeviceEvent>, should_reset: &AtomicBool) -> Result<(), String> {
+    let conn = zbus::Connection::system()
+        .await
+        .map_err(|e| format!("Failed to connect to system D-Bus: {}", e))?;
+    let proxy = Login1ManagerProxy::new(&conn)
+        .await
+        .map_err(|e| format!("Failed to create login1 proxy: {}", e))?;
+    let mut signals = proxy
+        .receive_prepare_for_sleep()
+        .await
+        .map_err(|e| format!("Failed to subscribe to sleep signal: {}", e))?;
 
-        for signal in signals {
-            if !still_active.load(Ordering::Relaxed) {
-                break;
-            }
-            match signal.args() {
-                Ok(args) => {
-                    let _ = tx.send(DeviceEvent::Sleep { sleep: args.start });
-                    if args.start {
-                        should_reset.store(true, Ordering::Relaxed);
-                    }
-                }
-                Err(e) => {
-                    error_log!("Failed to read sleep signal args: {}", e);
+    while let Some(signal) = signals.next().await {
+        match signal.args() {
+            Ok(args) => {
+                let _ = tx.send(DeviceEvent::Sleep { sleep: args.start });
+                if args.start {
+                    should_reset.store(true, Ordering::Relaxed);
                 }
             }
+            Err(e) => {
+                error_log!("Failed to read sleep signal args: {}", e);
+            }
         }
-    });
+    }
+    Ok(())
 }
@@ -2,24 +2,84 @@
 // Copyright (C) 2025 Panayotis Katsaloulis
 
 use crate::event::{send, DeviceEvent};
-use std::sync::atomic::AtomicBool;
+use crate::runtime;
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
-use std::thread;
 use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
 
-pub fn listener_tick(
-    tx: &Sender<DeviceEvent>,
-    still_active: &Arc<AtomicBool>,
-    tick_time: Arc<Mutex<f64>>,
-) {
+/// Seconds between two ticks: the shortest `tick` of the pages shown, or `tick_time`.
+/// A change takes effect at once, counted from the last tick.
+pub struct TickInterval {
+    seconds: Mutex<f64>,
+    changed: Notify,
+}
+
+impl TickInterval {
+    pub fn new(seconds: f64) -> Arc<Self> {
+        Arc::new(TickInterval {
+            seconds: Mutex::new(seconds),
+            changed: Notify::new(),
+        })
+    }
+
+    pub fn set(&self, seconds: f64) {
+        let mut current = self.seconds.lock().unwrap();
+        if *current != seconds {
+            *current = seconds;
+            self.changed.notify_one();
+        }
+    }
+
+    fn get(&self) -> Duration {
+        Duration::from_secs_f64(*self.seconds.lock().unwrap())
+    }
+}
+
+pub fn listener_tick(tx: &Sender<DeviceEvent>, interval: Arc<TickInterval>) {
     let tx = tx.clone();
-    let still_active = still_active.clone();
-    thread::spawn(move || {
-        while still_active.load(std::sync::atomic::Ordering::Relaxed) {
-            let current_tick_time = { *tick_time.lock().unwrap() };
-            thread::sleep(Duration::from_secs_f64(current_tick_time));
-            send(&tx, DeviceEvent::Tick);
+    runtime::spawn(move |_| tick(tx, interval));
+}
+
+async fn tick(tx: Sender<DeviceEvent>, interval: Arc<TickInterval>) {
+    let mut last_tick = Instant::now();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep_until(last_tick + interval.get()) => {
+                last_tick = Instant::now();
+                send(&tx, DeviceEvent::Tick);
+            }
+            // Sleep again, for the new interval
+            _ = interval.changed.notified() => {}
         }
-    });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn a_shorter_interval_applies_at_once() {
+        let (tx, rx) = mpsc::channel();
+        let interval = TickInterval::new(60.0);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        std::thread::spawn({
+            let interval = interval.clone();
+            move || runtime.block_on(tick(tx, interval))
+        });
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+        // Due already, counted from the start
+        interval.set(0.1);
+        assert!(matches!(
+            rx.recv_timeout(Duration::from_secs(5)),
+            Ok(DeviceEvent::Tick)
+        ));
+        assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
 }
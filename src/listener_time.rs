@@ -2,13 +2,13 @@
 // Copyright (C) 2025 Panayotis Katsaloulis
 
 use crate::event::{send, DeviceEvent};
+use crate::runtime;
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
-use std::sync::atomic::AtomicBool;
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
-use std::thread;
 use std::time::{Duration, Instant};
+use tokio::sync::Notify;
 
 /// A scheduled event that will fire at a specific time
 #[derive(Eq, PartialEq)]
@@ -30,77 +30,110 @@ impl PartialOrd for ScheduledEvent {
     }
 }
 
-/// Manages all time-based events in a single thread with a priority queue
+/// Manages all time-based events in a single task with a priority queue
 pub struct TimeManager {
     events: Arc<Mutex<BinaryHeap<ScheduledEvent>>>,
+    /// Wakes the task when an event is scheduled, as it may be due before the others
+    scheduled: Arc<Notify>,
 }
 
 impl TimeManager {
-    /// Creates a new TimeManager and spawns the background thread
-    pub fn new(tx: Sender<DeviceEvent>, still_active: Arc<AtomicBool>) -> Self {
+    /// Creates a new TimeManager and spawns the background task
+    pub fn new(tx: Sender<DeviceEvent>) -> Self {
         let events: Arc<Mutex<BinaryHeap<ScheduledEvent>>> =
             Arc::new(Mutex::new(BinaryHeap::new()));
-        let events_clone = events.clone();
-
-        thread::spawn(move || {
-            while still_active.load(std::sync::atomic::Ordering::Relaxed) {
-                let now = Instant::now();
-
-                // Fire all events that are due
-                loop {
-                    let next_event = {
-                        let mut heap = events_clone.lock().unwrap();
-                        if let Some(evt) = heap.peek() {
-                            if evt.fire_at <= now {
-                                heap.pop()
-                            } else {
-                                break; // Next event is in the future
-                            }
-                        } else {
-                            break; // No events
-                        }
-                    };
-
-                    if let Some(evt) = next_event {
-                        send(&tx, evt.event);
-                    } else {
-                        break;
-                    }
-                }
+        let scheduled = Arc::new(Notify::new());
+        let (events_clone, scheduled_clone) = (events.clone(), scheduled.clone());
 
-                // Sleep until next event (or max 100ms)
-                let sleep_duration = {
-                    let heap = events_clone.lock().unwrap();
-                    if let Some(next) = heap.peek() {
-                        let until_next = next.fire_at.saturating_duration_since(now);
-                        until_next.min(Duration::from_millis(100))
-                    } else {
-                        Duration::from_millis(100)
+        runtime::spawn(move |_| async move {
+            loop {
+                // Fire all events that are due, then wait for the next one
+                let next = {
+                    let mut heap = events_clone.lock().unwrap();
+                    let now = Instant::now();
+                    while heap.peek().is_some_and(|evt| evt.fire_at <= now) {
+                        if let Some(evt) = heap.pop() {
+                            send(&tx, evt.event);
+                        }
                     }
+                    heap.peek().map(|evt| evt.fire_at)
                 };
 
-                thread::sleep(sleep_duration);
+                match next {
+                    Some(fire_at) => tokio::select! {
+                        _ = tokio::time::sleep_until(fire_at.into()) => {}
+                        _ = scheduled_clone.notified() => {}
+                    },
+                    None => scheduled_clone.notified().await,
+                }
             }
         });
 
-        TimeManager { events }
+        TimeManager { events, scheduled }
     }
 
-    /// Schedule a timer to fire after the specified duration
-    pub fn schedule_timer(&self, sn: String, duration: Duration) {
-        let mut heap = self.events.lock().unwrap();
-        heap.push(ScheduledEvent {
+    fn schedule(&self, duration: Duration, event: DeviceEvent) {
+        self.events.lock().unwrap().push(ScheduledEvent {
             fire_at: Instant::now() + duration,
-            event: DeviceEvent::TimerComplete { sn },
+            event,
         });
+        self.scheduled.notify_one();
+    }
+
+    /// Schedule a timer to fire after the specified duration
+    pub fn schedule_timer(&self, sn: String, duration: Duration) {
+        self.schedule(duration, DeviceEvent::TimerComplete { sn });
     }
 
     /// Schedule a brightness change to fire after the specified duration
     pub fn schedule_brightness(&self, sn: String, brightness: u8, duration: Duration) {
-        let mut heap = self.events.lock().unwrap();
-        heap.push(ScheduledEvent {
-            fire_at: Instant::now() + duration,
-            event: DeviceEvent::SetBrightness { sn, brightness },
-        });
+        self.schedule(duration, DeviceEvent::SetBrightness { sn, brightness });
+    }
+
+    /// Schedule a single button to be re-rendered after the specified duration
+    pub fn schedule_refresh(&self, sn: String, button_id: u8, duration: Duration) {
+        self.schedule(duration, DeviceEvent::RefreshButton { sn, button_id });
+    }
+
+    /// Schedule the end of a button timer after the specified duration
+    pub fn schedule_timer_up(&self, sn: String, run: u64, duration: Duration) {
+        self.schedule(duration, DeviceEvent::TimerUp { sn, run });
+    }
+
+    /// Schedule a delayed focus change to be applied after the specified duration
+    pub fn schedule_focus_settled(&self, sn: String, focus: u64, duration: Duration) {
+        self.schedule(duration, DeviceEvent::FocusSettled { sn, focus });
+    }
+
+    /// Schedule the next step of a text echo page after the specified duration
+    pub fn schedule_echo_step(&self, sn: String, echo: u64, duration: Duration) {
+        self.schedule(duration, DeviceEvent::EchoStep { sn, echo });
+    }
+
+    /// Schedule the end of an unanswered confirmation page after the specified duration
+    pub fn schedule_confirm_timeout(&self, sn: String, confirmation: u64, duration: Duration) {
+        self.schedule(duration, DeviceEvent::ConfirmTimeout { sn, confirmation });
+    }
+
+    /// Schedule the end of a test pattern after the specified duration
+    pub fn schedule_test_pattern_end(&self, sn: String, duration: Duration) {
+        self.schedule(duration, DeviceEvent::TestPatternEnd { sn });
+    }
+
+    /// Schedule the check of a device after a system resume after the specified duration
+    pub fn schedule_verify_device(&self, sn: String, duration: Duration) {
+        self.schedule(duration, DeviceEvent::VerifyDevice { sn });
+    }
+
+    /// Schedule the next repetition of a held button after the specified duration
+    pub fn schedule_repeat(&self, sn: String, button_id: u8, press: u64, duration: Duration) {
+        self.schedule(
+            duration,
+            DeviceEvent::RepeatButton {
+                sn,
+                button_id,
+                press,
+            },
+        );
     }
 }
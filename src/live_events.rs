@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Live events for watchers of the running daemon.
+//!
+//! A client sending `watch` on the control socket keeps its connection and receives one
+//! JSON line per key press, key release, page change and failed key refresh, so the
+//! configuration UI can highlight the pressed key and the page every device shows
+//! (`keydeck --watch`):
+//!
+//! ```text
+//! {"v":1,"event":"button_down","sn":"<serial>","page":"Main","button":3}
+//! {"v":1,"event":"button_up","sn":"<serial>","page":"Main","button":3}
+//! {"v":1,"event":"page","sn":"<serial>","page":"Media"}
+//! {"v":1,"event":"key_images_failed","sn":"<serial>","buttons":[7],"written":31,"cleared":true,"error":"..."}
+//! ```
+//!
+//! The lines are [`keydeck_types::api::Event`]s of the API version the client asked for
+//! with `watch <version>`, version 1 when it named none.
+
+use crate::event_bus::{self, BusEvent, Subscription, Topic};
+use std::io::Write;
+
+/// Something a watcher is told about, as the versioned payload clients read
+pub use keydeck_types::api::Event as LiveEvent;
+
+/// Sends every following event to `watcher`, as payloads of `version`, until writing to
+/// it fails. A watcher that falls behind misses the oldest events, as any subscriber of
+/// the [`event_bus`].
+pub fn watch(watcher: Box<dyn Write + Send>, version: u32) {
+    let events = event_bus::subscribe(&[Topic::Live]);
+    std::thread::spawn(move || forward(events, watcher, version));
+}
+
+/// Sends an event to every watcher
+pub fn publish(event: LiveEvent) {
+    event_bus::publish_live(event);
+}
+
+/// Writes the events of `events` to `watcher` until that fails
+fn forward(mut events: Subscription, mut watcher: Box<dyn Write + Send>, version: u32) {
+    loop {
+        let BusEvent::Live(event) = &*events.recv() else {
+            continue;
+        };
+        let Some(line) = event.to_line(version) else {
+            continue;
+        };
+        if watcher
+            .write_all(line.as_bytes())
+            .and_then(|_| watcher.flush())
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// `keydeck --watch`: prints the events of the running daemon until it exits
+#[cfg(unix)]
+pub fn print_live_events(json: bool) {
+    use keydeck_types::api::Command;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = match UnixStream::connect(crate::listener_context::control_socket_path()) {
+        Ok(stream) => stream,
+        Err(_) => {
+            eprintln!("Error: the keydeck daemon is not running");
+            std::process::exit(1);
+        }
+    };
+    let watch = Command::Watch { version: None };
+    if let Err(e) = stream.write_all(watch.to_line().as_bytes()) {
+        eprintln!("Error: failed to write to control socket: {}", e);
+        std::process::exit(1);
+    }
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        if json {
+            println!("{}", line);
+            continue;
+        }
+        if let Some((sn, what)) = LiveEvent::from_line(&line).and_then(describe) {
+            println!("{}  {}", sn, what);
+        }
+    }
+}
+
+/// The serial of the device of an event, and what happened; none for events of a newer
+/// daemon
+pub fn describe(event: LiveEvent) -> Option<(String, String)> {
+    Some(match event {
+        LiveEvent::ButtonDown { sn, page, button } => (
+            sn,
+            format!("button{} down  {}", button, page.unwrap_or_default()),
+        ),
+        LiveEvent::ButtonUp { sn, page, button } => (
+            sn,
+            format!("button{} up    {}", button, page.unwrap_or_default()),
+        ),
+        LiveEvent::Page { sn, page } => (sn, format!("page {}", page)),
+        LiveEvent::KeyImagesFailed {
+            sn,
+            buttons,
+            cleared,
+            error,
+            ..
+        } => (
+            sn,
+            format!(
+                "key images failed {:?}{}: {}",
+                buttons,
+                if cleared { ", cleared" } else { "" },
+                error
+            ),
+        ),
+        LiveEvent::Unknown => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::DeviceEvent;
+    use std::sync::{Arc, Mutex};
+
+    /// A watcher keeping what it is sent, hanging up after the line containing `last`
+    struct Kept {
+        sent: Arc<Mutex<Vec<u8>>>,
+        last: &'static str,
+    }
+
+    impl Write for Kept {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.sent.lock().unwrap().extend_from_slice(buf);
+            match String::from_utf8_lossy(buf).contains(self.last) {
+                true => Err(std::io::ErrorKind::BrokenPipe.into()),
+                false => Ok(buf.len()),
+            }
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A watcher that went away, counting the writes tried on it
+    struct Gone(Arc<Mutex<usize>>);
+
+    impl Write for Gone {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            *self.0.lock().unwrap() += 1;
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn watchers_get_one_line_per_event_until_they_go_away() {
+        let kept = event_bus::subscribe(&[Topic::Live]);
+        let gone = event_bus::subscribe(&[Topic::Live]);
+        publish(LiveEvent::ButtonDown {
+            sn: "LIVE".to_string(),
+            page: Some("Main".to_string()),
+            button: 3,
+        });
+        event_bus::publish(&DeviceEvent::ButtonDown {
+            sn: "LIVE".to_string(),
+            button_id: 3,
+        });
+        publish(LiveEvent::Page {
+            sn: "LIVE".to_string(),
+            page: "Media".to_string(),
+        });
+
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let watcher = Kept {
+            sent: sent.clone(),
+            last: r#""sn":"LIVE","page":"Media""#,
+        };
+        forward(kept, Box::new(watcher), 1);
+        let tries = Arc::new(Mutex::new(0));
+        forward(gone, Box::new(Gone(tries.clone())), 1);
+
+        // Other tests press keys too; only the events of this one are checked
+        let sent = String::from_utf8(sent.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = sent.lines().filter(|l| l.contains("\"LIVE\"")).collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"v":1,"event":"button_down","sn":"LIVE","page":"Main","button":3}"#,
+                r#"{"v":1,"event":"page","sn":"LIVE","page":"Media"}"#,
+            ]
+        );
+        assert_eq!(*tries.lock().unwrap(), 1);
+    }
+}
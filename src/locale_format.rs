@@ -0,0 +1,388 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Filters writing dynamic values the way the locale does, e.g. `${service:price|num:2}`
+//! or `${time:%x %X|locale}`, and in units, e.g. `${service:disk_used|bytes}`.
+//!
+//! Filters follow the provider after `|` and apply in order:
+//! - `num[:DECIMALS]` rounds the first number of the value (to the decimals it has when
+//!   none are given) and writes it with the thousands and decimal separators of the
+//!   locale, keeping the text around it (`1234.5 MB` becomes `1.234,50 MB` in German).
+//! - `locale` writes the decimal separator of the locale in the numbers of the value,
+//!   without grouping, so years and counts stay as they are. On `${time:...}` it also
+//!   gives `%x` (date) and `%X` (time) the patterns of the locale, and turns `%H` into
+//!   a 12-hour clock with AM/PM where the locale uses one.
+//! - `bytes[:DECIMALS]` writes the first number of the value, a count of bytes, in the
+//!   largest binary unit it reaches (`1503238553` becomes `1.4 GiB`), with one decimal
+//!   unless given.
+//! - `rate[:DECIMALS]` does the same for bytes per second, in decimal units as network
+//!   speeds are written (`12345678` becomes `12.3 MB/s`).
+//! - `duration` writes the first number of the value, in seconds, as its two largest
+//!   units (`8040` becomes `2h 14m`).
+//!
+//! `${choice:...}` and `${rotate:...}` take no filters: every `|` of theirs separates two
+//! of their texts, even a last one named like a filter.
+//!
+//! The locale is the configured `locale`, or else the one of the session, as for
+//! `${i18n:...}`. Month and day names stay English.
+
+use regex::Regex;
+use std::sync::{LazyLock, RwLock};
+
+/// Locale the filters follow, set from the configuration
+static LOCALE: RwLock<String> = RwLock::new(String::new());
+
+/// Units of `bytes`, each 1024 times the previous
+const BYTE_UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+/// Units of `rate`, each 1000 times the previous
+const RATE_UNITS: [&str; 5] = ["B/s", "kB/s", "MB/s", "GB/s", "TB/s"];
+
+/// Units of `duration`, by their length in seconds
+const DURATION_UNITS: [(u64, &str); 4] = [(86400, "d"), (3600, "h"), (60, "m"), (1, "s")];
+
+/// A number, with an optional sign and fraction
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"-?\d+(?:\.\d+)?").unwrap());
+
+/// How a locale writes numbers, dates and times
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conventions {
+    pub decimal: char,
+    /// Separator of thousands
+    pub group: &'static str,
+    pub twelve_hour: bool,
+    /// strftime pattern of a short date
+    pub date: &'static str,
+}
+
+/// Selects the locale of the filters, e.g. `de_AT`
+pub fn set_locale(locale: &str) {
+    *LOCALE.write().unwrap() = locale.to_string();
+}
+
+/// Conventions of a locale such as `de_AT`, `pt-BR` or `fr`
+pub fn conventions(locale: &str) -> Conventions {
+    let locale = locale.replace('-', "_").to_ascii_lowercase();
+    let (language, region) = locale.split_once('_').unwrap_or((&locale, ""));
+    let (decimal, group) = match (language, region) {
+        ("de" | "fr" | "it", "ch") => ('.', "'"),
+        ("de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr" | "sl", _) => {
+            (',', ".")
+        }
+        (
+            "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "nn" | "no" | "uk" | "hu"
+            | "bg" | "lt" | "lv" | "et",
+            _,
+        ) => (',', "\u{a0}"),
+        _ => ('.', ","),
+    };
+    let twelve_hour = match language {
+        "en" => !matches!(region, "gb" | "ie"),
+        "hi" => true,
+        _ => false,
+    };
+    let date = match (language, region) {
+        ("en", "" | "us") => "%m/%d/%Y",
+        ("en" | "fr" | "es" | "it" | "pt" | "el", _) => "%d/%m/%Y",
+        ("nl", _) => "%d-%m-%Y",
+        ("sv" | "lt", _) => "%Y-%m-%d",
+        ("hu" | "ko", _) => "%Y. %m. %d.",
+        ("ja" | "zh", _) => "%Y/%m/%d",
+        (
+            "de" | "ru" | "pl" | "cs" | "sk" | "fi" | "nb" | "nn" | "no" | "da" | "tr" | "uk"
+            | "ro" | "bg" | "hr" | "sl" | "lv" | "et",
+            _,
+        ) => "%d.%m.%Y",
+        _ => "%Y-%m-%d",
+    };
+    Conventions {
+        decimal,
+        group,
+        twelve_hour,
+        date,
+    }
+}
+
+/// Conventions of the selected locale
+fn current() -> Conventions {
+    conventions(&LOCALE.read().unwrap())
+}
+
+/// Whether `segment` is a filter, and not part of the provider argument
+fn is_filter(segment: &str) -> bool {
+    match segment.split_once(':') {
+        Some(("num" | "bytes" | "rate", decimals)) => decimals.parse::<usize>().is_ok(),
+        None => matches!(segment, "num" | "locale" | "bytes" | "rate" | "duration"),
+        _ => false,
+    }
+}
+
+/// Providers whose argument is a list of texts separated by `|`
+const LIST_PROVIDERS: [&str; 2] = ["choice", "rotate"];
+
+/// Splits `service:price|num:2` into the provider expression and its filters. A `|`
+/// not followed by a filter belongs to the expression, as do all of those of a list.
+pub fn split_filters(content: &str) -> (&str, Vec<&str>) {
+    let provider = content
+        .split_once(':')
+        .map_or(content, |(provider, _)| provider);
+    if LIST_PROVIDERS.contains(&provider) {
+        return (content, vec![]);
+    }
+    let mut expression = content;
+    let mut filters = Vec::new();
+    while let Some((rest, last)) = expression.rsplit_once('|') {
+        if !is_filter(last) {
+            break;
+        }
+        filters.insert(0, last);
+        expression = rest;
+    }
+    (expression, filters)
+}
+
+/// The strftime `format` of `${time:...}` adapted to the locale when `filters` include
+/// `locale`
+pub fn time_format(format: &str, filters: &[&str]) -> String {
+    if filters.contains(&"locale") {
+        localize_time_format(format, &current())
+    } else {
+        format.to_string()
+    }
+}
+
+fn localize_time_format(format: &str, conventions: &Conventions) -> String {
+    let format = format
+        .replace("%x", conventions.date)
+        .replace("%X", "%H:%M:%S");
+    if !conventions.twelve_hour {
+        return format;
+    }
+    let Some(hour) = format.find("%H") else {
+        return format;
+    };
+    // AM/PM goes after the minutes and seconds that follow the hour
+    let mut end = hour + 2;
+    while format[end..].starts_with(":%M") || format[end..].starts_with(":%S") {
+        end += 3;
+    }
+    format!(
+        "{}%-I{} %p{}",
+        &format[..hour],
+        &format[hour + 2..end],
+        &format[end..]
+    )
+}
+
+/// Applies the filters to an evaluated value
+pub fn apply_filters(value: String, filters: &[&str]) -> String {
+    if filters.is_empty() {
+        return value;
+    }
+    let conventions = current();
+    filters.iter().fold(value, |value, filter| {
+        apply_filter(&value, filter, &conventions)
+    })
+}
+
+fn apply_filter(value: &str, filter: &str, conventions: &Conventions) -> String {
+    match filter.split_once(':').unwrap_or((filter, "")) {
+        ("num", decimals) => {
+            let decimals = decimals.parse().ok();
+            NUMBER
+                .replacen(value, 1, |number: &regex::Captures| {
+                    format_number(&number[0], decimals, conventions)
+                })
+                .into_owned()
+        }
+        ("locale", _) => NUMBER
+            .replace_all(value, |number: &regex::Captures| {
+                let found = number.get(0).unwrap();
+                // Dates, versions and addresses (16.10.2026, 1.2.3) are no decimals
+                let joined = |c: Option<char>| c.is_some_and(|c| c == '.' || c.is_ascii_digit());
+                if joined(value[..found.start()].chars().last())
+                    || joined(value[found.end()..].chars().next())
+                {
+                    found.as_str().to_string()
+                } else {
+                    found
+                        .as_str()
+                        .replace('.', &conventions.decimal.to_string())
+                }
+            })
+            .into_owned(),
+        (unit @ ("bytes" | "rate"), decimals) => {
+            let (units, base) = match unit {
+                "bytes" => (&BYTE_UNITS[..], 1024.0),
+                _ => (&RATE_UNITS[..], 1000.0),
+            };
+            let decimals = decimals.parse().unwrap_or(1);
+            NUMBER
+                .replacen(value, 1, |number: &regex::Captures| {
+                    format_size(&number[0], decimals, units, base, conventions)
+                })
+                .into_owned()
+        }
+        ("duration", _) => NUMBER
+            .replacen(value, 1, |number: &regex::Captures| {
+                format_duration(&number[0])
+            })
+            .into_owned(),
+        _ => value.to_string(),
+    }
+}
+
+/// `number` in the largest of `units` (each `base` times the previous) it reaches, with
+/// `decimals` past the first unit
+fn format_size(
+    number: &str,
+    decimals: usize,
+    units: &[&str],
+    base: f64,
+    conventions: &Conventions,
+) -> String {
+    let Ok(mut value) = number.parse::<f64>() else {
+        return number.to_string();
+    };
+    let mut unit = 0;
+    // Compared as rounded, so 1023.99 KiB is written 1.0 MiB
+    let rounded = |value: f64, unit: usize| {
+        let decimals = if unit == 0 { 0 } else { decimals };
+        format!("{:.*}", decimals, value.abs())
+            .parse::<f64>()
+            .unwrap_or(value)
+    };
+    while unit + 1 < units.len() && rounded(value, unit) >= base {
+        value /= base;
+        unit += 1;
+    }
+    let decimals = if unit == 0 { 0 } else { decimals };
+    format!(
+        "{} {}",
+        format_number(&value.to_string(), Some(decimals), conventions),
+        units[unit]
+    )
+}
+
+/// `number` of seconds as its two largest units, e.g. `2h 14m` or `3d 4h`
+fn format_duration(number: &str) -> String {
+    let Ok(value) = number.parse::<f64>() else {
+        return number.to_string();
+    };
+    let seconds = value.abs().round() as u64;
+    let first = DURATION_UNITS
+        .iter()
+        .position(|(length, _)| seconds >= *length)
+        .unwrap_or(DURATION_UNITS.len() - 1);
+    let (length, unit) = DURATION_UNITS[first];
+    let mut formatted = format!(
+        "{}{}{}",
+        if value < 0.0 { "-" } else { "" },
+        seconds / length,
+        unit
+    );
+    if let Some((next, next_unit)) = DURATION_UNITS.get(first + 1) {
+        let rest = seconds % length / next;
+        if rest > 0 {
+            formatted.push_str(&format!(" {}{}", rest, next_unit));
+        }
+    }
+    formatted
+}
+
+/// `number` rounded to `decimals` (or as many as it has) with the separators of the locale
+fn format_number(number: &str, decimals: Option<usize>, conventions: &Conventions) -> String {
+    let Ok(value) = number.parse::<f64>() else {
+        return number.to_string();
+    };
+    let decimals = decimals.unwrap_or_else(|| {
+        number
+            .split_once('.')
+            .map_or(0, |(_, fraction)| fraction.len())
+    });
+    let fixed = format!("{:.*}", decimals, value.abs());
+    let (integer, fraction) = fixed.split_once('.').unwrap_or((&fixed, ""));
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push_str(conventions.group);
+        }
+        grouped.push(digit);
+    }
+    let negative = value < 0.0 && fixed.chars().any(|c| c.is_ascii_digit() && c != '0');
+    let mut formatted = if negative {
+        format!("-{}", grouped)
+    } else {
+        grouped
+    };
+    if !fraction.is_empty() {
+        formatted.push(conventions.decimal);
+        formatted.push_str(fraction);
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_follow_the_locale() {
+        let german = conventions("de_AT");
+        let american = conventions("en-US");
+        assert_eq!(apply_filter("1234.5 MB", "num:2", &german), "1.234,50 MB");
+        assert_eq!(apply_filter("-1234567", "num", &american), "-1,234,567");
+        assert_eq!(apply_filter("-0.001", "num:1", &american), "0.0");
+        assert_eq!(apply_filter("n/a", "num:2", &german), "n/a");
+        assert_eq!(apply_filter("2025: 3.5", "locale", &german), "2025: 3,5");
+        assert_eq!(apply_filter("16.10.2026", "locale", &german), "16.10.2026");
+
+        assert_eq!(localize_time_format("%a %H:%M", &american), "%a %-I:%M %p");
+        assert_eq!(
+            localize_time_format("%x %X", &american),
+            "%m/%d/%Y %-I:%M:%S %p"
+        );
+        assert_eq!(localize_time_format("%x %X", &german), "%d.%m.%Y %H:%M:%S");
+
+        assert_eq!(
+            split_filters("service:price|num:2"),
+            ("service:price", vec!["num:2"])
+        );
+        assert_eq!(
+            split_filters("time:%H|%M|locale"),
+            ("time:%H|%M", vec!["locale"])
+        );
+        assert_eq!(split_filters("var:a|b"), ("var:a|b", vec![]));
+        // The texts of a list may be named like filters
+        assert_eq!(split_filters("choice:a|b|num"), ("choice:a|b|num", vec![]));
+        assert_eq!(
+            split_filters("rotate:size:small|large|bytes"),
+            ("rotate:size:small|large|bytes", vec![])
+        );
+    }
+
+    #[test]
+    fn numbers_are_written_in_units() {
+        let american = conventions("en_US");
+        let german = conventions("de_DE");
+        assert_eq!(apply_filter("1503238553", "bytes", &american), "1.4 GiB");
+        assert_eq!(apply_filter("512", "bytes", &american), "512 B");
+        assert_eq!(apply_filter("1048575", "bytes:2", &american), "1.00 MiB");
+        assert_eq!(apply_filter("used 1536", "bytes", &german), "used 1,5 KiB");
+        assert_eq!(apply_filter("12345678", "rate", &american), "12.3 MB/s");
+        assert_eq!(apply_filter("999.4", "rate", &american), "999 B/s");
+        assert_eq!(apply_filter("8040", "duration", &american), "2h 14m");
+        assert_eq!(apply_filter("7200", "duration", &american), "2h");
+        assert_eq!(apply_filter("273600.4", "duration", &american), "3d 4h");
+        assert_eq!(apply_filter("42", "duration", &american), "42s");
+        assert_eq!(apply_filter("n/a", "duration", &american), "n/a");
+        assert_eq!(
+            split_filters("service:rx|rate:0"),
+            ("service:rx", vec!["rate:0"])
+        );
+        assert_eq!(
+            split_filters("service:up|duration"),
+            ("service:up", vec!["duration"])
+        );
+    }
+}
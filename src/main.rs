@@ -1,36 +1,92 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
+mod audio;
+mod bench;
+mod bookmarks;
+mod bundle;
+mod button_look;
+mod calendar;
+mod chats;
+mod checklist;
+mod config_diff;
+mod config_hints;
+mod config_patch;
+mod config_test;
+mod crash_report;
+mod dbus_state;
+mod device_check;
+mod device_conflicts;
 mod device_info;
 mod device_manager;
+mod device_quirks;
 mod device_registry_init;
 mod context;
 mod device_trait;
 mod dynamic_detection;
 mod dynamic_params;
+mod effective_config;
 mod elgato_device;
+mod error;
 mod event;
+mod event_bus;
+mod event_recording;
+mod exec;
+mod favicon;
+mod frame_scheduler;
 mod integrations;
 mod konsole;
-mod graphics_renderer;
+mod history;
+mod http_action;
+mod i18n;
+mod icon_cache;
+mod icon_lint;
+mod icon_recolor;
+mod image_formats;
 mod listener_button;
 #[cfg(unix)]
 mod listener_context;
 mod listener_device;
 mod listener_tick;
+mod live_events;
+mod locale_format;
 mod listener_time;
 mod lock;
 mod mirajazz_device;
+mod mirror;
+mod ocr;
+mod openrgb;
+mod oriented_device;
+mod page_generator;
+mod page_provider;
 mod paged_device;
 mod platform;
 mod press_effect;
+mod processes;
+mod render_budget;
+mod runtime;
+mod runtime_state;
 mod pages;
+mod safe_mode;
+mod screenshot;
 mod server;
 mod services;
+mod stats;
+mod status_page;
+mod streamdeck_ui;
 mod system_info;
-mod text_renderer;
+#[cfg(test)]
+mod test_device;
+#[cfg(unix)]
+mod tui;
 mod utils;
 mod validate;
+mod wallpaper;
+mod web_auth;
+mod web_deck;
+mod window_match;
+mod wol;
+mod zeroconf;
 
 // Linux-only native backends (X11 / Wayland / KWin / logind / signals).
 // On Windows and macOS these are provided by `platform::{windows,macos}`.
@@ -41,6 +97,8 @@ mod focus_property_wayland;
 #[cfg(target_os = "linux")]
 mod keyboard;
 #[cfg(target_os = "linux")]
+mod keyboard_layout;
+#[cfg(target_os = "linux")]
 mod keyboard_wayland;
 #[cfg(target_os = "linux")]
 mod kwin_script;
@@ -49,14 +107,19 @@ mod listener_focus;
 #[cfg(target_os = "linux")]
 mod listener_focus_wayland;
 #[cfg(target_os = "linux")]
+mod listener_hotkey;
+#[cfg(target_os = "linux")]
 mod listener_signal;
 #[cfg(target_os = "linux")]
 mod listener_sleep;
 #[cfg(target_os = "linux")]
+mod power;
+#[cfg(target_os = "linux")]
 mod session;
 
 use crate::device_registry_init::initialize_device_registry;
 use crate::device_trait::KeydeckDevice;
+use crate::event_recording::EventLog;
 use crate::mirajazz_device::init_registry;
 use crate::server::start_server;
 use std::env;
@@ -72,18 +135,86 @@ fn print_help() {
     println!("Options:");
     println!("      --logo <PATH>           Set persistent boot logo on device");
     println!("      --list                  List all devices");
+    println!("      --check-devices         Print the devices found and whether they can be");
+    println!("                                opened as JSON; exits with 0 when all are ready,");
+    println!("                                2 when none is connected, 3 when one cannot be");
+    println!("                                opened (usually missing permissions)");
     println!("      --info <DEVICE>         Show detailed device information as YAML");
     println!("      --validate <FILE>       Validate configuration file and test services");
     println!("      --json                  Output validation results as JSON (use with --validate)");
+    println!("      --effective-config [FILE]");
+    println!("                              Print the configuration as the daemon runs it, with");
+    println!("                                templates, defaults and button definitions merged");
+    println!("      --test-config [FILE]    Check the tests of the configuration, and of test");
+    println!("                                files given with --tests <FILE>, with stubbed");
+    println!("                                dynamic values (with --json as JSON)");
+    println!("      --fix                   Replace icons too small for the keys by sharp enlarged");
+    println!("                                copies (use with --validate)");
     println!("      --set <KEY=VALUE>       Set a context variable on the running daemon");
     println!("                                (empty value clears it; used by external watchers)");
+    println!("      --hotkey <COMBINATION>  Trigger a configured hotkey on the running daemon");
+    println!("      --simulate-focus <CLASS> <TITLE>");
+    println!("                              Make the running daemon act as if that window got");
+    println!("                                the focus, to try the window_name rules of pages");
+    println!("      --send-key <COMBINATION>");
+    println!("                              Press a key combination in this session, to check");
+    println!("                                that key injection works");
+    println!("      --type <TEXT>           Type a text in this session, to check that key");
+    println!("                                injection works");
+    println!("      --test-pattern [SERIAL] Show numbered keys for a few seconds on a device (or");
+    println!("                                all devices) of the running daemon");
+    println!("      --diagnostics [SERIAL]  Show serial, firmware, page, tick time, focused");
+    println!("                                window, config generation and input polling on");
+    println!("                                the keys of a device (or all devices) until a key");
+    println!("                                is pressed");
+    println!("      --services [--watch]    Show the last value, error, run time and next run of");
+    println!("                                every service of the running daemon (--watch");
+    println!("                                refreshes every second; --json for JSON)");
+    println!("      --set-button <GROUP/PAGE/BUTTON>");
+    println!("                              Change a button of the running daemon with --text,");
+    println!("                                --icon, --background or --text-color (empty");
+    println!("                                removes; --save also writes the config file)");
+    println!("      --set-page <GROUP|SERIAL> <PAGE>");
+    println!("                              Show a page on the devices of a page group");
+    println!("      --screenshot <SERIAL> <FILE>");
+    println!("                              Save the keys a device of the running daemon shows");
+    println!("                                as a PNG");
+    println!("      --history [COUNT]       Show the last actions run by the daemon (default 20;");
+    println!("                                with --json as a JSON array)");
+    println!("      --watch                 Follow key presses and page changes of the running");
+    println!("                                daemon (with --json as JSON lines)");
+    println!("      --tui                   Show the devices, pages, events and services of the");
+    println!("                                running daemon in the terminal, switching pages");
+    println!("                                and pressing buttons from the keyboard");
+    println!("      --discover              List KeyDeck daemons advertising their web deck on");
+    println!("                                the network (with --json as a JSON array)");
+    println!("      --export <FILE>         Save the configuration and its icons as a .keydeck");
+    println!("                                archive");
+    println!("      --import <FILE>         Restore a .keydeck archive, replacing the");
+    println!("                                configuration");
+    println!("      --on-conflict <POLICY>  What --import does with icons that exist with other");
+    println!("                                content: rename (default), skip or overwrite");
+    println!("      --import-streamdeck-ui <FILE>");
+    println!("                              Add the pages, key images and actions of a");
+    println!("                                streamdeck-ui configuration to the configuration");
+    println!("                                (with --json for JSON)");
+    println!("      --fetch-icon <URL>      Save the icon of a website to the icon directory");
+    println!("                                (--name <NAME> names the file; --json for JSON)");
+    println!("      --clear-cache           Empty the icon cache (with --json as JSON)");
+    println!("      --recolor-icons         Redraw the PNG icons of the icon directory in one");
+    println!("                                color into its recolored/ folder (--tint <COLOR>,");
+    println!("                                default white; --style filled, outline or shaded;");
+    println!("                                --json for JSON)");
+    println!("      --bench [SERIAL]        Time key rendering, page switches and USB flushes on");
+    println!("                                a device (or all devices) of the running daemon");
+    println!("                                (with --json as JSON)");
     println!("      --daemon <ACTION>       Manage the daemon lifecycle. ACTION is one of:");
     println!("                                install    register autostart at login");
     println!("                                uninstall  remove autostart entry");
     println!("                                start      start the daemon now");
     println!("                                stop       stop the running daemon");
     println!("                                restart    restart the daemon");
-    println!("                                status     print JSON {{running,pid,enabled}}");
+    println!("                                status     print JSON {{running,pid,enabled,...}}");
     println!("                                reload     reload config of running daemon");
     println!("      --integration <NAME> <ACTION>");
     println!("                              Manage a terminal integration (NAME: kitty).");
@@ -91,9 +222,30 @@ fn print_help() {
     println!("  -v, --verbose               Print detailed messages (key presses, page changes)");
     println!("  -vv, --verbose --verbose    Print all verbose/debug messages");
     println!("      --server                Start the server (default when no arguments)");
+    println!("      --record-events <FILE>  Start the server, writing the events it receives to");
+    println!("                                FILE, to attach to a bug report");
+    println!("      --replay-events <FILE>  Start the server with the devices and events of a");
+    println!("                                recording instead of the real ones");
     println!("      --help                  Display this help and exit");
 }
 
+/// Exits after `--send-key` or `--type`, naming the backend the keys went through
+fn report_key_injection(result: Result<(), crate::error::Error>) -> ! {
+    match result {
+        Ok(()) => {
+            info_log!(
+                "Keys sent with the {} keyboard",
+                crate::platform::keyboard_backend()
+            );
+            std::process::exit(0);
+        }
+        Err(e) => {
+            error_log!("Key injection failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn main() {
     let args = env::args().skip(1).collect::<Vec<String>>();
 
@@ -104,6 +256,7 @@ fn main() {
         _ => level,
     });
     VERBOSITY.store(verbosity, std::sync::atomic::Ordering::Relaxed);
+    crash_report::install();
 
     // Initialize device registry: extract embedded JSON files and get search paths
     let device_paths = match initialize_device_registry() {
@@ -124,13 +277,14 @@ fn main() {
 
     let mut arg_iter = args.iter();
     let mut should_start_server = false;
+    let mut event_log = None;
 
     while let Some(arg) = arg_iter.next() {
         match arg.as_str() {
             "--help" => print_help(),
             "--logo" => {
                 if let Some(path) = arg_iter.next() {
-                    match image::open(path) {
+                    match crate::image_formats::open(path) {
                         Ok(img) => {
                             let mut manager = crate::device_manager::DeviceManager::new();
                             for device in manager.iter_active_devices() {
@@ -145,6 +299,7 @@ fn main() {
                     error_log!("Error: --logo requires a path argument");
                 }
             }
+            "--check-devices" => crate::device_check::print_and_exit(),
             "--list" => {
                 let mut manager = crate::device_manager::DeviceManager::new();
                 manager.list_devices();
@@ -162,13 +317,39 @@ fn main() {
             "--validate" => {
                 if let Some(config_path) = arg_iter.next() {
                     let json_output = args.iter().any(|a| a == "--json");
-                    let success = crate::validate::validate_config(config_path, json_output);
+                    let fix = args.iter().any(|a| a == "--fix");
+                    let success = crate::validate::validate_config(config_path, json_output, fix);
                     std::process::exit(if success { 0 } else { 1 });
                 } else {
                     error_log!("Error: --validate requires a configuration file path argument");
                     std::process::exit(1);
                 }
             }
+            "--effective-config" => {
+                let file = arg_iter.clone().next().filter(|a| !a.starts_with('-'));
+                if file.is_some() {
+                    arg_iter.next();
+                }
+                crate::effective_config::print_effective_config(file.map(String::as_str));
+                std::process::exit(0);
+            }
+            "--test-config" => {
+                let config = arg_iter.clone().next().filter(|a| !a.starts_with('-'));
+                if config.is_some() {
+                    arg_iter.next();
+                }
+                let files: Vec<&str> = args
+                    .windows(2)
+                    .filter(|pair| pair[0] == "--tests")
+                    .map(|pair| pair[1].as_str())
+                    .collect();
+                let json_output = args.iter().any(|a| a == "--json");
+                crate::config_test::print_test_config(
+                    config.map(String::as_str),
+                    &files,
+                    json_output,
+                );
+            }
             "--daemon" => {
                 use crate::platform::lifecycle::Action;
                 let action = arg_iter.next().and_then(|a| Action::parse(a));
@@ -201,6 +382,168 @@ fn main() {
                     std::process::exit(1);
                 }
             }
+            "--hotkey" => {
+                if let Some(combination) = arg_iter.next() {
+                    #[cfg(unix)]
+                    crate::listener_context::send_hotkey(combination);
+                    #[cfg(not(unix))]
+                    {
+                        let _ = combination;
+                        error_log!("Error: --hotkey is not supported on this platform");
+                        std::process::exit(1);
+                    }
+                } else {
+                    error_log!("Error: --hotkey requires a key combination");
+                    std::process::exit(1);
+                }
+            }
+            "--send-key" => {
+                let Some(combination) = arg_iter.next() else {
+                    error_log!("Error: --send-key requires a key combination");
+                    std::process::exit(1);
+                };
+                report_key_injection(crate::platform::send_key_combination(combination));
+            }
+            "--type" => {
+                let Some(text) = arg_iter.next() else {
+                    error_log!("Error: --type requires a text");
+                    std::process::exit(1);
+                };
+                report_key_injection(crate::platform::send_string(text));
+            }
+            "--test-pattern" => {
+                let serial = arg_iter.clone().next().filter(|a| !a.starts_with('-'));
+                if serial.is_some() {
+                    arg_iter.next();
+                }
+                #[cfg(unix)]
+                crate::listener_context::send_test_pattern(serial.map(String::as_str));
+                #[cfg(not(unix))]
+                {
+                    let _ = serial;
+                    error_log!("Error: --test-pattern is not supported on this platform");
+                    std::process::exit(1);
+                }
+            }
+            "--diagnostics" => {
+                let serial = arg_iter.clone().next().filter(|a| !a.starts_with('-'));
+                if serial.is_some() {
+                    arg_iter.next();
+                }
+                #[cfg(unix)]
+                crate::listener_context::send_diagnostics(serial.map(String::as_str));
+                #[cfg(not(unix))]
+                {
+                    let _ = serial;
+                    error_log!("Error: --diagnostics is not supported on this platform");
+                    std::process::exit(1);
+                }
+            }
+            "--bench" => {
+                let serial = arg_iter.clone().next().filter(|a| !a.starts_with('-'));
+                if serial.is_some() {
+                    arg_iter.next();
+                }
+                let json_output = args.iter().any(|a| a == "--json");
+                #[cfg(unix)]
+                crate::bench::print_bench(serial.map(String::as_str), json_output);
+                #[cfg(not(unix))]
+                {
+                    let _ = (serial, json_output);
+                    error_log!("Error: --bench is not supported on this platform");
+                    std::process::exit(1);
+                }
+            }
+            "--services" => {
+                let watch = args.iter().any(|a| a == "--watch");
+                let json_output = args.iter().any(|a| a == "--json");
+                #[cfg(unix)]
+                crate::services::print_services(watch, json_output);
+                #[cfg(not(unix))]
+                {
+                    let _ = (watch, json_output);
+                    error_log!("Error: --services is not supported on this platform");
+                    std::process::exit(1);
+                }
+            }
+            "--set-button" => {
+                let Some(target) = arg_iter.next() else {
+                    error_log!("Error: --set-button requires a button as GROUP/PAGE/BUTTON");
+                    std::process::exit(1);
+                };
+                let option = |name: &str| args.iter().skip_while(|a| *a != name).nth(1).cloned();
+                let patch = crate::config_patch::ButtonPatch {
+                    text: option("--text"),
+                    icon: option("--icon"),
+                    background: option("--background"),
+                    text_color: option("--text-color"),
+                    save: args.iter().any(|a| a == "--save"),
+                };
+                #[cfg(unix)]
+                crate::listener_context::send_button_patch(target, patch);
+                #[cfg(not(unix))]
+                {
+                    let _ = (target, patch);
+                    error_log!("Error: --set-button is not supported on this platform");
+                    std::process::exit(1);
+                }
+            }
+            "--simulate-focus" => match (arg_iter.next(), arg_iter.next()) {
+                (Some(class), Some(title)) => {
+                    #[cfg(unix)]
+                    crate::listener_context::send_focus(class, title);
+                    #[cfg(not(unix))]
+                    {
+                        let _ = (class, title);
+                        error_log!("Error: --simulate-focus is not supported on this platform");
+                        std::process::exit(1);
+                    }
+                }
+                _ => {
+                    error_log!("Error: --simulate-focus requires <CLASS> <TITLE>");
+                    std::process::exit(1);
+                }
+            },
+            "--set-page" => match (arg_iter.next(), arg_iter.next()) {
+                (Some(target), Some(page)) => {
+                    #[cfg(unix)]
+                    crate::listener_context::send_set_page(target, page);
+                    #[cfg(not(unix))]
+                    {
+                        let _ = (target, page);
+                        error_log!("Error: --set-page is not supported on this platform");
+                        std::process::exit(1);
+                    }
+                }
+                _ => {
+                    error_log!("Error: --set-page requires <GROUP|SERIAL> <PAGE>");
+                    std::process::exit(1);
+                }
+            },
+            "--screenshot" => match (arg_iter.next(), arg_iter.next()) {
+                (Some(serial), Some(file)) => {
+                    #[cfg(unix)]
+                    crate::listener_context::send_screenshot(serial, file);
+                    #[cfg(not(unix))]
+                    {
+                        let _ = (serial, file);
+                        error_log!("Error: --screenshot is not supported on this platform");
+                        std::process::exit(1);
+                    }
+                }
+                _ => {
+                    error_log!("Error: --screenshot requires <SERIAL> <FILE>");
+                    std::process::exit(1);
+                }
+            },
+            "--history" => {
+                let count = arg_iter.clone().next().and_then(|a| a.parse::<usize>().ok());
+                if count.is_some() {
+                    arg_iter.next();
+                }
+                let json_output = args.iter().any(|a| a == "--json");
+                crate::history::print_history(count.unwrap_or(20), json_output);
+            }
             "--integration" => {
                 let name = arg_iter.next();
                 let action = arg_iter.next();
@@ -218,14 +561,126 @@ fn main() {
                     }
                 }
             }
-            "--json" | "--verbose" | "-v" | "-vv" => {} // Processed elsewhere
+            "--tui" => {
+                #[cfg(unix)]
+                crate::tui::run();
+                #[cfg(not(unix))]
+                {
+                    error_log!("Error: --tui is not supported on this platform");
+                    std::process::exit(1);
+                }
+            }
+            "--watch" => {
+                let json_output = args.iter().any(|a| a == "--json");
+                #[cfg(unix)]
+                crate::live_events::print_live_events(json_output);
+                #[cfg(not(unix))]
+                {
+                    let _ = json_output;
+                    error_log!("Error: --watch is not supported on this platform");
+                    std::process::exit(1);
+                }
+            }
+            "--discover" => {
+                let json_output = args.iter().any(|a| a == "--json");
+                crate::zeroconf::print_discovered(json_output);
+            }
+            "--export" => {
+                if let Some(file) = arg_iter.next() {
+                    let json_output = args.iter().any(|a| a == "--json");
+                    crate::bundle::print_export(file, json_output);
+                } else {
+                    error_log!("Error: --export requires a file name");
+                    std::process::exit(1);
+                }
+            }
+            "--import" => {
+                let Some(file) = arg_iter.next() else {
+                    error_log!("Error: --import requires a file name");
+                    std::process::exit(1);
+                };
+                let policy = args
+                    .iter()
+                    .skip_while(|a| *a != "--on-conflict")
+                    .nth(1)
+                    .map_or("rename", String::as_str);
+                match policy.parse() {
+                    Ok(conflict) => {
+                        let json_output = args.iter().any(|a| a == "--json");
+                        crate::bundle::print_import(file, conflict, json_output);
+                    }
+                    Err(e) => {
+                        error_log!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--import-streamdeck-ui" => {
+                let Some(file) = arg_iter.next() else {
+                    error_log!("Error: --import-streamdeck-ui requires a file name");
+                    std::process::exit(1);
+                };
+                let json_output = args.iter().any(|a| a == "--json");
+                crate::streamdeck_ui::print_import(file, json_output);
+            }
+            "--fetch-icon" => {
+                let Some(url) = arg_iter.next() else {
+                    error_log!("Error: --fetch-icon requires a web address");
+                    std::process::exit(1);
+                };
+                let name = args.iter().skip_while(|a| *a != "--name").nth(1);
+                let json_output = args.iter().any(|a| a == "--json");
+                crate::favicon::print_fetch_icon(url, name.map(String::as_str), json_output);
+            }
+            "--clear-cache" => {
+                let json_output = args.iter().any(|a| a == "--json");
+                crate::icon_cache::print_clear_cache(json_output);
+            }
+            "--recolor-icons" => {
+                let option = |name: &str| args.iter().skip_while(|a| *a != name).nth(1);
+                let json_output = args.iter().any(|a| a == "--json");
+                crate::icon_recolor::print_recolor_icons(
+                    option("--tint").map(String::as_str),
+                    option("--style").map(String::as_str),
+                    json_output,
+                );
+            }
+            "--on-conflict" => {
+                arg_iter.next(); // Processed by --import
+            }
+            "--name" => {
+                arg_iter.next(); // Processed by --fetch-icon
+            }
+            "--tests" => {
+                arg_iter.next(); // Processed by --test-config
+            }
+            "--tint" | "--style" => {
+                arg_iter.next(); // Processed by --recolor-icons
+            }
+            "--text" | "--icon" | "--background" | "--text-color" => {
+                arg_iter.next(); // Processed by --set-button
+            }
+            "--json" | "--save" | "--verbose" | "-v" | "-vv" => {} // Processed elsewhere
             "--server" => should_start_server = true,
+            "--record-events" | "--replay-events" => {
+                let Some(file) = arg_iter.next() else {
+                    error_log!("Error: {} requires a file name", arg);
+                    std::process::exit(1);
+                };
+                let file = std::path::PathBuf::from(file);
+                event_log = Some(if arg == "--record-events" {
+                    EventLog::Record(file)
+                } else {
+                    EventLog::Replay(file)
+                });
+                should_start_server = true;
+            }
             _ => {
                 error_log!("Error: Unknown command '{}'", arg);
             }
         }
     }
     if args.is_empty() || should_start_server {
-        start_server();
+        start_server(event_log);
     }
 }
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
+use crate::device_conflicts;
 use crate::device_trait::{DeviceError, DeviceReader, DeviceStateUpdate, KeydeckDevice};
 #[allow(unused_imports)]
 use crate::{error_log, verbose_log};
@@ -11,12 +12,15 @@ use mirajazz_json::{
     registry::{DeviceDefinition, DeviceRegistry},
     state::DeviceStateReader,
 };
-use std::cell::RefCell;
-use std::sync::{Arc, OnceLock};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 use std::time::Duration;
 
-/// Global registry instance (loaded once at startup)
-static REGISTRY: OnceLock<Option<DeviceRegistry>> = OnceLock::new();
+/// Global registry instance, loaded at startup and replaced by [`reload_registry`]
+static REGISTRY: RwLock<Option<Arc<DeviceRegistry>>> = RwLock::new(None);
+
+/// Search paths the registry was loaded from
+static REGISTRY_PATHS: Mutex<Vec<String>> = Mutex::new(Vec::new());
 
 /// Initialize the device registry with the given search paths.
 /// Paths are searched in order, with later paths overriding earlier ones.
@@ -30,20 +34,52 @@ pub fn init_registry(paths: &[String]) -> Result<(), String> {
         verbose_log!("  [{}] {}", i, path);
     }
 
-    REGISTRY.get_or_init(|| DeviceRegistry::load_from_paths(paths).ok());
-
-    if REGISTRY.get().and_then(|r| r.as_ref()).is_none() {
-        return Err("Failed to load device registry from any provided path".to_string());
-    }
+    *REGISTRY_PATHS.lock().unwrap() = paths.to_vec();
+    let registry = DeviceRegistry::load_from_paths(paths)
+        .map_err(|_| "Failed to load device registry from any provided path".to_string())?;
+    *REGISTRY.write().unwrap() = Some(Arc::new(registry));
 
     verbose_log!("Mirajazz device registry loaded successfully");
     Ok(())
 }
 
+/// Loads the device definitions again from the paths given to [`init_registry`], so
+/// edited definitions and overrides apply without a restart. Returns whether any
+/// definition changed; devices already open keep the definition they were opened with.
+/// On error the current registry is kept.
+pub fn reload_registry() -> Result<bool, String> {
+    let paths = REGISTRY_PATHS.lock().unwrap().clone();
+    if paths.is_empty() {
+        return Ok(false);
+    }
+    let registry = DeviceRegistry::load_from_paths(&paths)
+        .map_err(|e| format!("Failed to reload device registry: {}", e))?;
+    let changed =
+        get_registry().is_none_or(|current| definitions(&current) != definitions(&registry));
+    *REGISTRY.write().unwrap() = Some(Arc::new(registry));
+    Ok(changed)
+}
+
+/// The definitions of a registry, comparable and independent of their load order
+fn definitions(registry: &DeviceRegistry) -> BTreeMap<String, serde_json::Value> {
+    registry
+        .all_devices()
+        .map(|def| {
+            let id = format!("{}:{}", def.hardware.vendor_id, def.hardware.product_id);
+            (id, serde_json::to_value(def).unwrap_or_default())
+        })
+        .collect()
+}
+
 /// Get the device registry (must be initialized first via init_registry)
 /// Returns None if registry fails to load (e.g., JSON files not found)
-pub fn get_registry() -> Option<&'static DeviceRegistry> {
-    REGISTRY.get().and_then(|r| r.as_ref())
+pub fn get_registry() -> Option<Arc<DeviceRegistry>> {
+    REGISTRY.read().unwrap().clone()
+}
+
+/// Serial of a device whose firmware gives every unit the same USB serial (`force_serial`)
+fn forced_serial(usb_serial: &str, vid: u16, pid: u16) -> String {
+    format!("{}-{:04X}{:04X}", usb_serial, vid, pid)
 }
 
 /// Wrapper for Mirajazz devices to implement KeydeckDevice trait
@@ -53,19 +89,13 @@ pub struct MirajazzDevice {
     pid: u16,
     pub serial: String,
     device_id: String,
-    device_def: &'static DeviceDefinition,
-    device: RefCell<Option<Arc<Device>>>,
-    reader: RefCell<Option<Arc<DeviceStateReader>>>,
+    device_def: Arc<DeviceDefinition>,
+    /// Connected on first use; the HID handle inside is guarded by its own mutex
+    device: OnceLock<Arc<Device>>,
+    reader: OnceLock<Arc<DeviceStateReader>>,
     enabled: bool,
 }
 
-// SAFETY: MirajazzDevice is safe to Send/Sync because:
-// - RefCell is only used for lazy initialization (get_device, get_reader_arc)
-// - The actual Device/DeviceStateReader are Arc-wrapped and can be safely shared
-// - Access patterns ensure no concurrent mutation (initialized once per access)
-unsafe impl Send for MirajazzDevice {}
-unsafe impl Sync for MirajazzDevice {}
-
 impl MirajazzDevice {
     /// Create a new MirajazzDevice wrapper
     pub fn new(
@@ -86,9 +116,11 @@ impl MirajazzDevice {
             ))
         })?;
 
+        let device_def = Arc::new(device_def.clone());
+
         // Generate unique serial if force_serial is enabled
         let serial = if device_def.quirks.force_serial {
-            let generated = format!("{}-{:04X}{:04X}", usb_serial, vid, pid);
+            let generated = forced_serial(&usb_serial, vid, pid);
             verbose_log!(
                 "Generated serial for device (USB serial was '{}'): {}",
                 usb_serial,
@@ -106,8 +138,8 @@ impl MirajazzDevice {
             serial,
             device_id,
             device_def,
-            device: RefCell::new(None),
-            reader: RefCell::new(None),
+            device: OnceLock::new(),
+            reader: OnceLock::new(),
             enabled: true,
         })
     }
@@ -119,6 +151,28 @@ impl MirajazzDevice {
             .unwrap_or(false)
     }
 
+    /// Serial the device with given VID/PID and USB serial is known by, and its model,
+    /// from the registry
+    pub fn identify(vid: u16, pid: u16, usb_serial: &str) -> Option<(String, String)> {
+        let registry = get_registry()?;
+        let device_def = registry.find_by_vid_pid(vid, pid)?;
+        let serial = if device_def.quirks.force_serial {
+            forced_serial(usb_serial, vid, pid)
+        } else {
+            usb_serial.to_string()
+        };
+        Some((serial, device_def.info.human_name.clone()))
+    }
+
+    /// Size of the key images of a device with given VID/PID, from the registry
+    pub fn key_image_size(vid: u16, pid: u16) -> Option<(u32, u32)> {
+        let size = get_registry()?
+            .find_by_vid_pid(vid, pid)?
+            .image_format
+            .default_size;
+        Some((size[0] as u32, size[1] as u32))
+    }
+
     pub fn device_id(&self) -> &str {
         &self.device_id
     }
@@ -128,8 +182,8 @@ impl MirajazzDevice {
     }
 
     fn get_device(&self) -> Result<Arc<Device>, DeviceError> {
-        if let Some(device) = self.device.borrow().clone() {
-            return Ok(device);
+        if let Some(device) = self.device.get() {
+            return Ok(device.clone());
         }
 
         let mut device = Device::connect_with_report_id(
@@ -153,6 +207,9 @@ impl MirajazzDevice {
             error_log!("  - Device was unplugged");
             error_log!("  - Insufficient USB permissions (check udev rules / uaccess)");
             error_log!("  - Device busy/in use by another process");
+            for holder in device_conflicts::holders_of(&self.serial) {
+                error_log!("    {}", holder);
+            }
             DeviceError::ConnectionFailed(format!(
                 "could not open Mirajazz device '{}': {}",
                 self.serial, e
@@ -171,23 +228,17 @@ impl MirajazzDevice {
             });
         }
 
-        let device = Arc::new(device);
-        *self.device.borrow_mut() = Some(device.clone());
-        Ok(device)
+        // A connection made meanwhile by another thread wins; this one is closed
+        Ok(self.device.get_or_init(|| Arc::new(device)).clone())
     }
 
     fn get_reader_arc(&self) -> Arc<DeviceStateReader> {
-        if self.reader.borrow().is_none() {
-            let device = self
-                .get_device()
-                .expect("device connection required to create input reader");
-            *self.reader.borrow_mut() = Some(device.get_reader());
-        }
-
         self.reader
-            .borrow()
-            .as_ref()
-            .expect("Reader should be initialized")
+            .get_or_init(|| {
+                self.get_device()
+                    .expect("device connection required to create input reader")
+                    .get_reader()
+            })
             .clone()
     }
 
@@ -356,7 +407,7 @@ impl KeydeckDevice for MirajazzDevice {
     fn get_reader(&self) -> Arc<dyn DeviceReader> {
         Arc::new(MirajazzDeviceReader {
             reader: self.get_reader_arc(),
-            device_def: self.device_def,
+            device_def: self.device_def.clone(),
         })
     }
 
@@ -448,13 +499,9 @@ impl KeydeckDevice for MirajazzDevice {
 /// Wrapper for mirajazz DeviceStateReader to implement our DeviceReader trait
 struct MirajazzDeviceReader {
     reader: Arc<DeviceStateReader>,
-    device_def: &'static DeviceDefinition,
+    device_def: Arc<DeviceDefinition>,
 }
 
-// SAFETY: MirajazzDeviceReader is safe to Send/Sync because Arc<DeviceStateReader> is internally thread-safe
-unsafe impl Send for MirajazzDeviceReader {}
-unsafe impl Sync for MirajazzDeviceReader {}
-
 impl DeviceReader for MirajazzDeviceReader {
     fn read(&self, timeout: Option<Duration>) -> Result<Vec<DeviceStateUpdate>, DeviceError> {
         use mirajazz_json::types::DeviceInput;
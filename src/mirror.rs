@@ -0,0 +1,454 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Copies of the key images sent to the devices, for showing the deck elsewhere.
+//!
+//! When a mirror is needed (`web_deck` or `snapshots` in the configuration), every
+//! connected device is wrapped in a [`MirroredDevice`]. It forwards all calls to the
+//! real device and records the last image of each key in the shared [`Mirror`], which
+//! passes every change on to its [`FrameSink`]s: the web deck streams them to browsers,
+//! and the [`SnapshotSink`] keeps a PNG of each device's keys up to date.
+
+use crate::device_trait::{DeviceError, DeviceReader, KeydeckDevice};
+use crate::{error_log, info_log};
+use image::{DynamicImage, Rgba, RgbaImage};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Space between keys in snapshot images, in pixels
+const SNAPSHOT_GAP: u32 = 8;
+
+/// Minimum time between two writes of the same snapshot, so animations don't keep
+/// the disk busy
+const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Last known contents of a mirrored device
+pub struct MirroredKeys {
+    pub rows: usize,
+    pub cols: usize,
+    /// Size of a key image in pixels
+    pub key_size: (u32, u32),
+    /// Last image of each key; None when the key is cleared
+    pub keys: Vec<Option<DynamicImage>>,
+    /// Distinguishes a device from an earlier instance with the same serial
+    id: u64,
+}
+
+/// All mirrored devices by serial number
+pub type MirroredDevices = BTreeMap<String, MirroredKeys>;
+
+/// Receives every change of the mirrored devices. Called with the mirror locked, so
+/// slow work (encoding, I/O) belongs on a thread of the sink.
+pub trait FrameSink: Send {
+    /// A device was connected or disconnected; also called once when the sink is added
+    fn devices_changed(&mut self, devices: &MirroredDevices);
+
+    /// The image of a key of a device changed
+    fn key_changed(&mut self, devices: &MirroredDevices, sn: &str, index: u8);
+}
+
+#[derive(Default)]
+struct MirrorInner {
+    devices: MirroredDevices,
+    sinks: Vec<Box<dyn FrameSink>>,
+}
+
+/// Key images of all mirrored devices, shared with the sinks showing them
+#[derive(Clone, Default)]
+pub struct Mirror {
+    inner: Arc<Mutex<MirrorInner>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Mirror {
+    pub fn add_sink(&self, mut sink: Box<dyn FrameSink>) {
+        let mut inner = self.inner.lock().unwrap();
+        sink.devices_changed(&inner.devices);
+        inner.sinks.push(sink);
+    }
+
+    /// Runs `f` on the current devices, with no change happening meanwhile
+    pub fn with_devices<R>(&self, f: impl FnOnce(&MirroredDevices) -> R) -> R {
+        f(&self.inner.lock().unwrap().devices)
+    }
+
+    pub(crate) fn add_device(
+        &self,
+        sn: &str,
+        rows: usize,
+        cols: usize,
+        key_size: (u32, u32),
+        keys: usize,
+    ) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut inner = self.inner.lock().unwrap();
+        let MirrorInner { devices, sinks } = &mut *inner;
+        devices.insert(
+            sn.to_string(),
+            MirroredKeys {
+                rows,
+                cols,
+                key_size,
+                keys: vec![None; keys],
+                id,
+            },
+        );
+        sinks
+            .iter_mut()
+            .for_each(|sink| sink.devices_changed(devices));
+        id
+    }
+
+    fn remove_device(&self, sn: &str, id: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        let MirrorInner { devices, sinks } = &mut *inner;
+        if devices.get(sn).is_some_and(|state| state.id == id) {
+            devices.remove(sn);
+            sinks
+                .iter_mut()
+                .for_each(|sink| sink.devices_changed(devices));
+        }
+    }
+
+    fn set_key(&self, sn: &str, index: u8, image: Option<DynamicImage>) {
+        let mut inner = self.inner.lock().unwrap();
+        let MirrorInner { devices, sinks } = &mut *inner;
+        let Some(slot) = devices
+            .get_mut(sn)
+            .and_then(|state| state.keys.get_mut(index as usize))
+        else {
+            return;
+        };
+        *slot = image;
+        sinks
+            .iter_mut()
+            .for_each(|sink| sink.key_changed(devices, sn, index));
+    }
+}
+
+/// A device whose key images are also recorded in a [`Mirror`]
+pub struct MirroredDevice {
+    inner: Box<dyn KeydeckDevice>,
+    serial: String,
+    id: u64,
+    mirror: Mirror,
+}
+
+impl MirroredDevice {
+    pub fn new(inner: Box<dyn KeydeckDevice>, mirror: &Mirror) -> Self {
+        let serial = inner.serial_number().unwrap_or_default();
+        let keys = inner.button_count() as usize;
+        let (rows, cols) = match inner.button_layout() {
+            (rows, cols) if rows > 0 && cols > 0 => (rows, cols),
+            _ => {
+                let cols = (keys as f64).sqrt().ceil().max(1.0) as usize;
+                (keys.div_ceil(cols), cols)
+            }
+        };
+        let (width, height) = inner.button_image_size();
+        let id = mirror.add_device(&serial, rows, cols, (width as u32, height as u32), keys);
+        MirroredDevice {
+            inner,
+            serial,
+            id,
+            mirror: mirror.clone(),
+        }
+    }
+}
+
+impl Drop for MirroredDevice {
+    fn drop(&mut self) {
+        self.mirror.remove_device(&self.serial, self.id);
+    }
+}
+
+impl KeydeckDevice for MirroredDevice {
+    fn serial_number(&self) -> Result<String, DeviceError> {
+        self.inner.serial_number()
+    }
+
+    fn firmware_version(&self) -> Result<String, DeviceError> {
+        self.inner.firmware_version()
+    }
+
+    fn manufacturer(&self) -> String {
+        self.inner.manufacturer()
+    }
+
+    fn kind_name(&self) -> String {
+        self.inner.kind_name()
+    }
+
+    fn button_count(&self) -> u8 {
+        self.inner.button_count()
+    }
+
+    fn has_screen(&self) -> bool {
+        self.inner.has_screen()
+    }
+
+    fn button_image_size(&self) -> (u16, u16) {
+        self.inner.button_image_size()
+    }
+
+    fn button_layout(&self) -> (usize, usize) {
+        self.inner.button_layout()
+    }
+
+    fn encoder_count(&self) -> usize {
+        self.inner.encoder_count()
+    }
+
+    fn reset(&self) -> Result<(), DeviceError> {
+        self.inner.reset()
+    }
+
+    fn set_brightness(&self, brightness: u8) -> Result<(), DeviceError> {
+        self.inner.set_brightness(brightness)
+    }
+
+    fn set_button_image(&self, button_idx: u8, image: DynamicImage) -> Result<(), DeviceError> {
+        self.mirror
+            .set_key(&self.serial, button_idx, Some(image.clone()));
+        self.inner.set_button_image(button_idx, image)
+    }
+
+    fn clear_button_image(&self, button_idx: u8) -> Result<(), DeviceError> {
+        self.mirror.set_key(&self.serial, button_idx, None);
+        self.inner.clear_button_image(button_idx)
+    }
+
+    fn clear_all_button_images(&self) -> Result<(), DeviceError> {
+        for button_idx in 0..self.inner.button_count() {
+            self.mirror.set_key(&self.serial, button_idx, None);
+        }
+        self.inner.clear_all_button_images()
+    }
+
+    fn flush(&self) -> Result<(), DeviceError> {
+        self.inner.flush()
+    }
+
+    fn get_reader(&self) -> Arc<dyn DeviceReader> {
+        self.inner.get_reader()
+    }
+
+    fn supports_button_press_feedback(&self) -> bool {
+        self.inner.supports_button_press_feedback()
+    }
+
+    fn shutdown(&self) -> Result<(), DeviceError> {
+        self.inner.shutdown()
+    }
+
+    fn sleep(&self) -> Result<(), DeviceError> {
+        self.inner.sleep()
+    }
+
+    fn keep_alive(&self) {
+        self.inner.keep_alive()
+    }
+
+    fn lcd_strip_size(&self) -> Option<(u16, u16)> {
+        self.inner.lcd_strip_size()
+    }
+
+    fn write_lcd_fill(&self, x: u16, y: u16, image: &DynamicImage) -> Result<(), DeviceError> {
+        self.inner.write_lcd_fill(x, y, image)
+    }
+
+    fn write_lcd(
+        &self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        image: &DynamicImage,
+    ) -> Result<(), DeviceError> {
+        self.inner.write_lcd(x, y, width, height, image)
+    }
+
+    fn background_image_size(&self) -> Option<(u16, u16)> {
+        self.inner.background_image_size()
+    }
+
+    fn set_background_image(&self, image: DynamicImage) -> Result<(), DeviceError> {
+        self.inner.set_background_image(image)
+    }
+
+    fn clear_background_image(&self) -> Result<(), DeviceError> {
+        self.inner.clear_background_image()
+    }
+
+    fn set_boot_logo(&self, image: DynamicImage) -> Result<(), DeviceError> {
+        self.inner.set_boot_logo(image)
+    }
+
+    fn led_count(&self) -> u8 {
+        self.inner.led_count()
+    }
+
+    fn set_led_brightness(&self, brightness: u8) -> Result<(), DeviceError> {
+        self.inner.set_led_brightness(brightness)
+    }
+
+    fn set_led_color(&self, colors: &[(u8, u8, u8)]) -> Result<(), DeviceError> {
+        self.inner.set_led_color(colors)
+    }
+
+    fn reset_led_color(&self) -> Result<(), DeviceError> {
+        self.inner.reset_led_color()
+    }
+
+    fn wakeup(&self) -> Result<(), DeviceError> {
+        self.inner.wakeup()
+    }
+}
+
+/// Keeps `<dir>/<serial>.png` showing the keys of each connected device as a grid,
+/// for tools that can display an image file (e.g. an OBS image source). The files of
+/// disconnected devices are removed.
+pub struct SnapshotSink {
+    known: BTreeSet<String>,
+    tx: Sender<(String, Option<RgbaImage>)>,
+}
+
+impl SnapshotSink {
+    pub fn start(dir: PathBuf) -> Result<Self, String> {
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Cannot create snapshot directory {}: {}", dir.display(), e))?;
+        info_log!("Writing device snapshots to {}", dir.display());
+        let (tx, rx) = channel::<(String, Option<RgbaImage>)>();
+        thread::spawn(move || {
+            // Ends when the mirror, and so the sender, is dropped
+            while let Ok(first) = rx.recv() {
+                let mut latest = BTreeMap::new();
+                latest.insert(first.0, first.1);
+                latest.extend(rx.try_iter());
+                for (sn, grid) in latest {
+                    write_snapshot(&dir, &sn, grid);
+                }
+                thread::sleep(SNAPSHOT_INTERVAL);
+            }
+        });
+        Ok(SnapshotSink {
+            known: BTreeSet::new(),
+            tx,
+        })
+    }
+}
+
+impl FrameSink for SnapshotSink {
+    fn devices_changed(&mut self, devices: &MirroredDevices) {
+        for sn in std::mem::take(&mut self.known) {
+            if !devices.contains_key(&sn) {
+                let _ = self.tx.send((sn, None));
+            }
+        }
+        for (sn, state) in devices {
+            let _ = self.tx.send((sn.clone(), Some(compose_grid(state))));
+            self.known.insert(sn.clone());
+        }
+    }
+
+    fn key_changed(&mut self, devices: &MirroredDevices, sn: &str, _index: u8) {
+        if let Some(state) = devices.get(sn) {
+            let _ = self.tx.send((sn.to_string(), Some(compose_grid(state))));
+        }
+    }
+}
+
+/// Draws all keys of a device in their physical layout: black where a key is cleared,
+/// transparent between the keys
+fn compose_grid(state: &MirroredKeys) -> RgbaImage {
+    stitch_keys(state.rows, state.cols, state.key_size, &state.keys)
+}
+
+/// Lays out key images of `key_size` in `rows` and `cols` as on the device, with
+/// [`SNAPSHOT_GAP`] between them; missing keys are black
+pub fn stitch_keys(
+    rows: usize,
+    cols: usize,
+    key_size: (u32, u32),
+    keys: &[Option<DynamicImage>],
+) -> RgbaImage {
+    let (key_w, key_h) = key_size;
+    let cols = cols.max(1) as u32;
+    let rows = rows.max(1) as u32;
+    let mut grid = RgbaImage::new(
+        cols * key_w + (cols - 1) * SNAPSHOT_GAP,
+        rows * key_h + (rows - 1) * SNAPSHOT_GAP,
+    );
+    let black = RgbaImage::from_pixel(key_w, key_h, Rgba([0, 0, 0, 255]));
+    for (index, image) in keys.iter().enumerate() {
+        let (col, row) = (index as u32 % cols, index as u32 / cols);
+        let (x, y) = (col * (key_w + SNAPSHOT_GAP), row * (key_h + SNAPSHOT_GAP));
+        match image {
+            Some(image) => {
+                image::imageops::overlay(&mut grid, &image.to_rgba8(), x as i64, y as i64)
+            }
+            None => image::imageops::overlay(&mut grid, &black, x as i64, y as i64),
+        }
+    }
+    grid
+}
+
+/// Replaces the snapshot of a device through a temporary file, so readers never see
+/// a partial image. None removes it.
+fn write_snapshot(dir: &Path, sn: &str, grid: Option<RgbaImage>) {
+    let path = dir.join(format!("{}.png", sn));
+    let Some(grid) = grid else {
+        let _ = std::fs::remove_file(&path);
+        return;
+    };
+    let temp = dir.join(format!(".{}.png.tmp", sn));
+    if let Err(e) = grid
+        .save_with_format(&temp, image::ImageFormat::Png)
+        .map_err(|e| e.to_string())
+        .and_then(|_| std::fs::rename(&temp, &path).map_err(|e| e.to_string()))
+    {
+        error_log!("Error while writing snapshot {}: {}", path.display(), e);
+    }
+}
+
+/// Default snapshot directory: `keydeck-mirror` in the per-user runtime dir
+/// (`$XDG_RUNTIME_DIR`, kept in memory on most systems), or in the temp dir.
+pub fn default_snapshot_dir() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|p| p.is_dir())
+        .unwrap_or_else(std::env::temp_dir)
+        .join("keydeck-mirror")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_follows_device_layout() {
+        let key = DynamicImage::ImageRgba8(RgbaImage::from_pixel(10, 10, Rgba([255, 0, 0, 255])));
+        let state = MirroredKeys {
+            rows: 2,
+            cols: 3,
+            key_size: (10, 10),
+            keys: vec![None, None, None, None, Some(key), None],
+            id: 0,
+        };
+        let grid = compose_grid(&state);
+        assert_eq!(
+            grid.dimensions(),
+            (30 + 2 * SNAPSHOT_GAP, 20 + SNAPSHOT_GAP)
+        );
+        // Second key of the second row
+        let (x, y) = (10 + SNAPSHOT_GAP, 10 + SNAPSHOT_GAP);
+        assert_eq!(*grid.get_pixel(x + 5, y + 5), Rgba([255, 0, 0, 255]));
+        assert_eq!(*grid.get_pixel(5, 5), Rgba([0, 0, 0, 255]));
+        assert_eq!(grid.get_pixel(10, 5)[3], 0);
+    }
+}
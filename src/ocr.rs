@@ -0,0 +1,211 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Text of a screen region for the `${ocr:region:X,Y,W,H}` provider, for applications
+//! that expose their values nowhere else.
+//!
+//! Each region in use is read by its own thread every [`OCR_INTERVAL`]: the screen is
+//! captured through the screenshot portal, cropped to the region and passed to
+//! `tesseract`. The provider shows the last reading, so rendering never waits for it.
+//! A region no button asked for in [`IDLE_TIMEOUT`] is no longer read.
+
+use image::GenericImageView;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time between two readings of a region
+const OCR_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A region not asked for this long is no longer read
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Screen region in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Region {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Last reading of a region and when a button last asked for it
+struct Reading {
+    text: Result<String, String>,
+    requested: Instant,
+}
+
+/// Readings by region, as written in the parameter (`10,20,200,40`)
+static READINGS: Mutex<Option<HashMap<String, Reading>>> = Mutex::new(None);
+
+/// Parses `X,Y,W,H`
+fn parse_region(spec: &str) -> Result<Region, String> {
+    let numbers: Vec<u32> = spec
+        .split(',')
+        .map(|n| n.trim().parse::<u32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid OCR region '{}', expected X,Y,W,H", spec))?;
+    match numbers[..] {
+        [x, y, width, height] if width > 0 && height > 0 => Ok(Region {
+            x,
+            y,
+            width,
+            height,
+        }),
+        _ => Err(format!("Invalid OCR region '{}', expected X,Y,W,H", spec)),
+    }
+}
+
+/// Value of `region:X,Y,W,H`: the text last read in the region, empty until the first
+/// reading. None when the region is invalid or it could not be read.
+pub fn value(arg: &str) -> Option<String> {
+    let spec = arg.strip_prefix("region:")?.trim();
+    let region = parse_region(spec).ok()?;
+    let mut readings = READINGS.lock().unwrap();
+    let readings = readings.get_or_insert_with(HashMap::new);
+    match readings.get_mut(spec) {
+        Some(reading) => {
+            reading.requested = Instant::now();
+            reading.text.clone().ok()
+        }
+        None => {
+            readings.insert(
+                spec.to_string(),
+                Reading {
+                    text: Ok(String::new()),
+                    requested: Instant::now(),
+                },
+            );
+            let spec = spec.to_string();
+            thread::spawn(move || follow(&spec, region));
+            Some(String::new())
+        }
+    }
+}
+
+/// Reads the region until no button asks for it anymore
+fn follow(spec: &str, region: Region) {
+    loop {
+        let text = read_region(region);
+        if let Err(e) = &text {
+            crate::verbose_log!("OCR of region {} failed: {}", spec, e);
+        }
+        {
+            let mut readings = READINGS.lock().unwrap();
+            let Some(readings) = readings.as_mut() else {
+                return;
+            };
+            let idle = readings
+                .get(spec)
+                .is_none_or(|reading| reading.requested.elapsed() > IDLE_TIMEOUT);
+            if idle {
+                readings.remove(spec);
+                return;
+            }
+            if let Some(reading) = readings.get_mut(spec) {
+                reading.text = text;
+            }
+        }
+        thread::sleep(OCR_INTERVAL);
+    }
+}
+
+/// Captures the screen and recognizes the text of the region, on a single line
+fn read_region(region: Region) -> Result<String, String> {
+    let screenshot = capture_screen()?;
+    let image = image::open(&screenshot);
+    // The portal leaves the capture in the pictures directory of some desktops
+    let _ = std::fs::remove_file(&screenshot);
+    let image = image.map_err(|e| format!("Failed to read screenshot: {}", e))?;
+    let (screen_width, screen_height) = image.dimensions();
+    if region.x >= screen_width || region.y >= screen_height {
+        return Err(format!(
+            "Region is outside the {}x{} screen",
+            screen_width, screen_height
+        ));
+    }
+    let crop = image.crop_imm(
+        region.x,
+        region.y,
+        region.width.min(screen_width - region.x),
+        region.height.min(screen_height - region.y),
+    );
+    let file = std::env::temp_dir().join(format!(
+        "keydeck-ocr-{}-{}-{}-{}-{}.png",
+        std::process::id(),
+        region.x,
+        region.y,
+        region.width,
+        region.height
+    ));
+    crop.save(&file)
+        .map_err(|e| format!("Failed to save OCR region: {}", e))?;
+    let text = tesseract(&file);
+    let _ = std::fs::remove_file(&file);
+    Ok(text?.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Runs tesseract on an image, treating it as a single block of text
+fn tesseract(file: &Path) -> Result<String, String> {
+    let output = Command::new("tesseract")
+        .arg(file)
+        .args(["stdout", "--psm", "6"])
+        .output()
+        .map_err(|e| format!("Failed to run tesseract (is it installed?): {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Captures the whole screen without asking, through the screenshot portal. Returns the
+/// file the portal saved it to.
+#[cfg(target_os = "linux")]
+fn capture_screen() -> Result<PathBuf, String> {
+    use ashpd::desktop::screenshot::Screenshot;
+    let runtime = tokio::runtime::Runtime::new()
+        .map_err(|e| format!("Failed to create tokio runtime: {}", e))?;
+    let screenshot = runtime
+        .block_on(async {
+            Screenshot::request()
+                .interactive(false)
+                .modal(false)
+                .send()
+                .await?
+                .response()
+        })
+        .map_err(|e| format!("Screenshot portal failed: {}", e))?;
+    screenshot
+        .uri()
+        .to_file_path()
+        .map_err(|_| format!("Screenshot is not a local file: {}", screenshot.uri()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn capture_screen() -> Result<PathBuf, String> {
+    Err("Screen OCR is only supported on Linux".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_need_four_numbers_and_a_size() {
+        assert_eq!(
+            parse_region("10, 20,200,40"),
+            Ok(Region {
+                x: 10,
+                y: 20,
+                width: 200,
+                height: 40
+            })
+        );
+        assert!(parse_region("10,20,200").is_err());
+        assert!(parse_region("10,20,0,40").is_err());
+        assert!(parse_region("a,b,c,d").is_err());
+    }
+}
@@ -0,0 +1,268 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Lighting profiles of OpenRGB, so the deck sets the scene of the rest of the desk
+//! (`openrgb` in the configuration, the `openrgb` action and page field).
+//!
+//! KeyDeck talks to the SDK server of OpenRGB with its binary protocol: every packet is
+//! a header of `ORGB` and three little endian `u32`, the device index, the packet id and
+//! the size of the data, followed by the data. Profiles need protocol version 2 (OpenRGB
+//! 0.6). Profiles are loaded rarely and the server may come and go, so every profile is
+//! loaded over a connection of its own. OpenRGB does not tell which profile is active,
+//! so `${openrgb:profile}` is the last one KeyDeck loaded.
+
+use crate::event::{send, DeviceEvent};
+use crate::pages::OpenRgb;
+use crate::warn_log;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::Sender;
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_HOST: &str = "localhost";
+const DEFAULT_PORT: u16 = 6742;
+
+/// The protocol version spoken, the first one with profiles
+const PROTOCOL_VERSION: u32 = 2;
+
+/// Time to connect, and to wait for each answer of the server
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Profile lists larger than this are not from an OpenRGB server
+const MAX_PACKET: u32 = 1024 * 1024;
+
+const REQUEST_PROTOCOL_VERSION: u32 = 40;
+const SET_CLIENT_NAME: u32 = 50;
+const REQUEST_PROFILE_LIST: u32 = 150;
+const REQUEST_LOAD_PROFILE: u32 = 152;
+
+/// The configured server, the local one when None
+static SERVER: RwLock<Option<OpenRgb>> = RwLock::new(None);
+
+/// The profile loaded last
+static PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Uses the server of the `openrgb` settings from now on
+pub fn configure(conf: Option<&OpenRgb>) {
+    *SERVER.write().unwrap() = conf.cloned();
+}
+
+/// A packet for the server
+fn packet(id: u32, data: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(16 + data.len());
+    packet.extend_from_slice(b"ORGB");
+    packet.extend_from_slice(&0u32.to_le_bytes());
+    packet.extend_from_slice(&id.to_le_bytes());
+    packet.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    packet.extend_from_slice(data);
+    packet
+}
+
+/// A text as the server reads it, ending with a null byte
+fn c_string(text: &str) -> Vec<u8> {
+    let mut bytes = text.as_bytes().to_vec();
+    bytes.push(0);
+    bytes
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+/// Reads packets until one with the id `id`, and returns its data. The server also sends
+/// packets of its own, e.g. when its devices change.
+fn read_packet(stream: &mut TcpStream, id: u32) -> Result<Vec<u8>, std::io::Error> {
+    loop {
+        let mut header = [0u8; 16];
+        stream.read_exact(&mut header)?;
+        let size = u32_at(&header, 12).unwrap_or_default();
+        if &header[..4] != b"ORGB" || size > MAX_PACKET {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "not an OpenRGB server",
+            ));
+        }
+        let mut data = vec![0; size as usize];
+        stream.read_exact(&mut data)?;
+        if u32_at(&header, 8) == Some(id) {
+            return Ok(data);
+        }
+    }
+}
+
+/// Names of the profiles in the data of a profile list: its size, their number as `u16`,
+/// then every name as its length as `u16` and the name ending with a null byte
+fn parse_profiles(data: &[u8]) -> Option<Vec<String>> {
+    let count = u16_at(data, 4)?;
+    let mut at = 6;
+    let mut profiles = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let length = u16_at(data, at)? as usize;
+        let name = data.get(at + 2..at + 2 + length)?;
+        let name = name.strip_suffix(&[0]).unwrap_or(name);
+        profiles.push(String::from_utf8_lossy(name).into_owned());
+        at += 2 + length;
+    }
+    Some(profiles)
+}
+
+/// Connects to the server and tells who is calling
+fn connect() -> Result<TcpStream, String> {
+    let server = SERVER.read().unwrap().clone().unwrap_or_default();
+    let host = server.host.as_deref().unwrap_or(DEFAULT_HOST);
+    let address = format!("{}:{}", host, server.port.unwrap_or(DEFAULT_PORT));
+    let unreachable = |e: &dyn std::fmt::Display| {
+        format!("Failed to reach the OpenRGB server at {}: {}", address, e)
+    };
+    let addresses = address
+        .to_socket_addrs()
+        .map_err(|e| unreachable(&e))?
+        .collect::<Vec<_>>();
+    let mut stream = addresses
+        .iter()
+        .map(|socket| TcpStream::connect_timeout(socket, TIMEOUT))
+        .reduce(|found, next| found.or(next))
+        .unwrap_or_else(|| Err(std::io::Error::from(ErrorKind::NotFound)))
+        .map_err(|e| unreachable(&e))?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .map_err(|e| unreachable(&e))?;
+
+    stream
+        .write_all(&packet(
+            REQUEST_PROTOCOL_VERSION,
+            &PROTOCOL_VERSION.to_le_bytes(),
+        ))
+        .map_err(|e| unreachable(&e))?;
+    // Servers before protocol version 1 do not answer at all
+    let version = match read_packet(&mut stream, REQUEST_PROTOCOL_VERSION) {
+        Ok(data) => u32_at(&data, 0).unwrap_or_default(),
+        Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => 0,
+        Err(e) => return Err(unreachable(&e)),
+    };
+    if version < PROTOCOL_VERSION {
+        return Err(format!(
+            "The OpenRGB server at {} is too old for profiles (protocol {}, {} needed)",
+            address, version, PROTOCOL_VERSION
+        ));
+    }
+    stream
+        .write_all(&packet(SET_CLIENT_NAME, &c_string("KeyDeck")))
+        .map_err(|e| unreachable(&e))?;
+    Ok(stream)
+}
+
+/// Loads the profile `name`, written in any case, and returns its name as OpenRGB has it
+pub fn load_profile(name: &str) -> Result<String, String> {
+    let mut stream = connect()?;
+    let failed = |e: std::io::Error| format!("Failed to load OpenRGB profile '{}': {}", name, e);
+    stream
+        .write_all(&packet(REQUEST_PROFILE_LIST, &[]))
+        .map_err(failed)?;
+    let data = read_packet(&mut stream, REQUEST_PROFILE_LIST).map_err(failed)?;
+    let profiles = parse_profiles(&data)
+        .ok_or_else(|| format!("OpenRGB sent a broken list of profiles for '{}'", name))?;
+    let Some(profile) = profiles
+        .iter()
+        .find(|profile| *profile == name)
+        .or_else(|| profiles.iter().find(|p| p.eq_ignore_ascii_case(name)))
+    else {
+        return Err(format!(
+            "OpenRGB has no profile '{}' (it has: {})",
+            name,
+            profiles.join(", ")
+        ));
+    };
+    stream
+        .write_all(&packet(REQUEST_LOAD_PROFILE, &c_string(profile)))
+        .map_err(failed)?;
+    *PROFILE.write().unwrap() = Some(profile.clone());
+    Ok(profile.clone())
+}
+
+/// Loads the profile `name` without holding up the caller, as for a page shown, and
+/// redraws the keys showing `${openrgb:profile}`
+pub fn load_profile_in_background(name: String, event_tx: &Sender<DeviceEvent>) {
+    let event_tx = event_tx.clone();
+    thread::spawn(move || match load_profile(&name) {
+        Ok(_) => updated(&event_tx),
+        Err(e) => warn_log!("{}", e),
+    });
+}
+
+/// Redraws the keys showing `${openrgb:profile}`
+pub fn updated(event_tx: &Sender<DeviceEvent>) {
+    send(
+        event_tx,
+        DeviceEvent::ProviderUpdated {
+            provider: "openrgb".to_string(),
+        },
+    );
+}
+
+/// Value of `${openrgb:profile}`: the profile loaded last, empty before any is
+pub fn value(arg: &str) -> Option<String> {
+    match arg {
+        "profile" => Some(PROFILE.read().unwrap().clone().unwrap_or_default()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn profiles_are_loaded_by_name() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        configure(Some(&OpenRgb {
+            host: Some("127.0.0.1".to_string()),
+            port: Some(listener.local_addr().unwrap().port()),
+        }));
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            let version = read_packet(&mut stream, REQUEST_PROTOCOL_VERSION).unwrap();
+            stream
+                .write_all(&packet(REQUEST_PROTOCOL_VERSION, &version))
+                .unwrap();
+            received.push(
+                String::from_utf8(read_packet(&mut stream, SET_CLIENT_NAME).unwrap()).unwrap(),
+            );
+            read_packet(&mut stream, REQUEST_PROFILE_LIST).unwrap();
+            // A device list update comes in between
+            stream.write_all(&packet(100, &[])).unwrap();
+            let mut list = vec![0; 4];
+            list.extend_from_slice(&2u16.to_le_bytes());
+            for name in ["Gaming", "Movie Night"] {
+                list.extend_from_slice(&(name.len() as u16 + 1).to_le_bytes());
+                list.extend_from_slice(&c_string(name));
+            }
+            stream
+                .write_all(&packet(REQUEST_PROFILE_LIST, &list))
+                .unwrap();
+            received.push(
+                String::from_utf8(read_packet(&mut stream, REQUEST_LOAD_PROFILE).unwrap()).unwrap(),
+            );
+            received
+        });
+
+        assert_eq!(load_profile("movie night").unwrap(), "Movie Night");
+        assert_eq!(server.join().unwrap(), ["KeyDeck\0", "Movie Night\0"]);
+        assert_eq!(value("profile").as_deref(), Some("Movie Night"));
+        assert_eq!(value("brightness"), None);
+        assert_eq!(
+            parse_profiles(&[0, 0, 0, 0, 1, 0, 9, 0, b'x']),
+            None,
+            "a name longer than the data"
+        );
+        configure(None);
+    }
+}
@@ -0,0 +1,288 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Devices mounted upside down or sideways (`orientation` in the device configuration).
+//!
+//! Pages are laid out as the user sees the device. An [`OrientedDevice`] moves every key
+//! image to the physical key at that position and turns the image with the device, and
+//! the [`KeyMap`] translates the physical keys reported on presses back. Touch screens,
+//! LCD strips and encoders are left as they are.
+
+use crate::device_trait::{DeviceError, DeviceReader, KeydeckDevice};
+use crate::pages::Orientation;
+use crate::warn_log;
+use image::DynamicImage;
+use std::sync::Arc;
+
+/// Translation between the keys as the user sees them and the physical keys
+#[derive(Debug, Clone, Copy)]
+pub struct KeyMap {
+    orientation: Orientation,
+    /// Physical rows of the device
+    rows: usize,
+    /// Physical columns of the device
+    columns: usize,
+}
+
+impl KeyMap {
+    /// Key map of a device with the given physical (rows, columns). Devices without a
+    /// known layout cannot be turned.
+    pub fn new(orientation: Orientation, (rows, columns): (usize, usize)) -> Self {
+        let orientation = if orientation != Orientation::Normal && (rows == 0 || columns == 0) {
+            warn_log!("Device layout is unknown, ignoring its orientation");
+            Orientation::Normal
+        } else {
+            orientation
+        };
+        KeyMap {
+            orientation,
+            rows,
+            columns,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.orientation == Orientation::Normal
+    }
+
+    /// (rows, columns) as the user sees them
+    pub fn layout(&self) -> (usize, usize) {
+        match self.orientation {
+            Orientation::Rotate90 | Orientation::Rotate270 => (self.columns, self.rows),
+            _ => (self.rows, self.columns),
+        }
+    }
+
+    /// Physical key (from 0) shown at a position the user sees
+    pub fn to_physical(self, key: u8) -> u8 {
+        let (rows, columns) = (self.rows, self.columns);
+        let (row, column) = match self.cell(key, self.layout().1) {
+            Some(cell) => cell,
+            None => return key,
+        };
+        let (row, column) = match self.orientation {
+            Orientation::Normal => (row, column),
+            Orientation::Rotate90 => (rows - 1 - column, row),
+            Orientation::Rotate180 => (rows - 1 - row, columns - 1 - column),
+            Orientation::Rotate270 => (column, columns - 1 - row),
+        };
+        (row * columns + column) as u8
+    }
+
+    /// Position the user sees (from 0) of a physical key
+    pub fn to_logical(self, key: u8) -> u8 {
+        let (rows, columns) = (self.rows, self.columns);
+        let (row, column) = match self.cell(key, columns) {
+            Some(cell) => cell,
+            None => return key,
+        };
+        let (row, column) = match self.orientation {
+            Orientation::Normal => (row, column),
+            Orientation::Rotate90 => (column, rows - 1 - row),
+            Orientation::Rotate180 => (rows - 1 - row, columns - 1 - column),
+            Orientation::Rotate270 => (columns - 1 - column, row),
+        };
+        (row * self.layout().1 + column) as u8
+    }
+
+    /// Row and column of a key in a grid with `columns` columns; None for keys outside
+    /// the grid, which are left where they are
+    fn cell(&self, key: u8, columns: usize) -> Option<(usize, usize)> {
+        let key = key as usize;
+        (columns > 0 && key < self.rows * self.columns).then(|| (key / columns, key % columns))
+    }
+
+    /// Turns an image drawn upright so it is upright on the mounted device
+    fn rotate(&self, image: DynamicImage) -> DynamicImage {
+        match self.orientation {
+            Orientation::Normal => image,
+            Orientation::Rotate90 => image.rotate270(),
+            Orientation::Rotate180 => image.rotate180(),
+            Orientation::Rotate270 => image.rotate90(),
+        }
+    }
+}
+
+/// A device whose keys are addressed and drawn as the user sees it when mounted turned
+pub struct OrientedDevice {
+    inner: Box<dyn KeydeckDevice>,
+    map: KeyMap,
+}
+
+impl OrientedDevice {
+    pub fn new(inner: Box<dyn KeydeckDevice>, map: KeyMap) -> Self {
+        OrientedDevice { inner, map }
+    }
+}
+
+impl KeydeckDevice for OrientedDevice {
+    fn serial_number(&self) -> Result<String, DeviceError> {
+        self.inner.serial_number()
+    }
+
+    fn firmware_version(&self) -> Result<String, DeviceError> {
+        self.inner.firmware_version()
+    }
+
+    fn manufacturer(&self) -> String {
+        self.inner.manufacturer()
+    }
+
+    fn kind_name(&self) -> String {
+        self.inner.kind_name()
+    }
+
+    fn button_count(&self) -> u8 {
+        self.inner.button_count()
+    }
+
+    fn has_screen(&self) -> bool {
+        self.inner.has_screen()
+    }
+
+    fn button_image_size(&self) -> (u16, u16) {
+        let (width, height) = self.inner.button_image_size();
+        match self.map.orientation {
+            Orientation::Rotate90 | Orientation::Rotate270 => (height, width),
+            _ => (width, height),
+        }
+    }
+
+    fn button_layout(&self) -> (usize, usize) {
+        self.map.layout()
+    }
+
+    fn encoder_count(&self) -> usize {
+        self.inner.encoder_count()
+    }
+
+    fn reset(&self) -> Result<(), DeviceError> {
+        self.inner.reset()
+    }
+
+    fn set_brightness(&self, brightness: u8) -> Result<(), DeviceError> {
+        self.inner.set_brightness(brightness)
+    }
+
+    fn set_button_image(&self, button_idx: u8, image: DynamicImage) -> Result<(), DeviceError> {
+        self.inner
+            .set_button_image(self.map.to_physical(button_idx), self.map.rotate(image))
+    }
+
+    fn clear_button_image(&self, button_idx: u8) -> Result<(), DeviceError> {
+        self.inner
+            .clear_button_image(self.map.to_physical(button_idx))
+    }
+
+    fn clear_all_button_images(&self) -> Result<(), DeviceError> {
+        self.inner.clear_all_button_images()
+    }
+
+    fn flush(&self) -> Result<(), DeviceError> {
+        self.inner.flush()
+    }
+
+    fn get_reader(&self) -> Arc<dyn DeviceReader> {
+        self.inner.get_reader()
+    }
+
+    fn supports_button_press_feedback(&self) -> bool {
+        self.inner.supports_button_press_feedback()
+    }
+
+    fn shutdown(&self) -> Result<(), DeviceError> {
+        self.inner.shutdown()
+    }
+
+    fn sleep(&self) -> Result<(), DeviceError> {
+        self.inner.sleep()
+    }
+
+    fn keep_alive(&self) {
+        self.inner.keep_alive()
+    }
+
+    fn lcd_strip_size(&self) -> Option<(u16, u16)> {
+        self.inner.lcd_strip_size()
+    }
+
+    fn write_lcd_fill(&self, x: u16, y: u16, image: &DynamicImage) -> Result<(), DeviceError> {
+        self.inner.write_lcd_fill(x, y, image)
+    }
+
+    fn write_lcd(
+        &self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        image: &DynamicImage,
+    ) -> Result<(), DeviceError> {
+        self.inner.write_lcd(x, y, width, height, image)
+    }
+
+    fn background_image_size(&self) -> Option<(u16, u16)> {
+        self.inner.background_image_size()
+    }
+
+    fn set_background_image(&self, image: DynamicImage) -> Result<(), DeviceError> {
+        self.inner.set_background_image(image)
+    }
+
+    fn clear_background_image(&self) -> Result<(), DeviceError> {
+        self.inner.clear_background_image()
+    }
+
+    fn set_boot_logo(&self, image: DynamicImage) -> Result<(), DeviceError> {
+        self.inner.set_boot_logo(image)
+    }
+
+    fn led_count(&self) -> u8 {
+        self.inner.led_count()
+    }
+
+    fn set_led_brightness(&self, brightness: u8) -> Result<(), DeviceError> {
+        self.inner.set_led_brightness(brightness)
+    }
+
+    fn set_led_color(&self, colors: &[(u8, u8, u8)]) -> Result<(), DeviceError> {
+        self.inner.set_led_color(colors)
+    }
+
+    fn reset_led_color(&self) -> Result<(), DeviceError> {
+        self.inner.reset_led_color()
+    }
+
+    fn wakeup(&self) -> Result<(), DeviceError> {
+        self.inner.wakeup()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_follow_the_turned_device() {
+        // 3 rows of 5 keys, turned a quarter clockwise: the top-left key the user sees
+        // is the bottom-left physical key
+        let map = KeyMap::new(Orientation::Rotate90, (3, 5));
+        assert_eq!(map.layout(), (5, 3));
+        assert_eq!(map.to_physical(0), 10);
+        assert_eq!(map.to_physical(2), 0);
+        let upside_down = KeyMap::new(Orientation::Rotate180, (3, 5));
+        assert_eq!(upside_down.to_physical(0), 14);
+
+        for orientation in [
+            Orientation::Rotate90,
+            Orientation::Rotate180,
+            Orientation::Rotate270,
+        ] {
+            let map = KeyMap::new(orientation, (3, 5));
+            for key in 0..15 {
+                assert_eq!(map.to_logical(map.to_physical(key)), key);
+            }
+            assert_eq!(map.to_physical(20), 20);
+        }
+    }
+}
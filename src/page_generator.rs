@@ -0,0 +1,473 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Pages with a `generate` section get a button for every entry of a directory, playlist
+//! or bookmark folder, created each time the configuration is loaded. Each device lays
+//! them out on the keys the page leaves free; when they don't fit, the page continues on
+//! pages named "Page 2", "Page 3", ..., and the last two free keys of each page move to
+//! the previous and next one.
+//!
+//! Bookmarks show the favicon of their site from the icon dir. Missing favicons are
+//! downloaded in the background, and the configuration is reloaded once they arrive.
+
+use crate::bookmarks;
+use crate::event::{send, DeviceEvent};
+use crate::favicon;
+use crate::pages::{Button, ButtonConfig, Generator, GeneratorSource, KeyDeckConf, Page, Pages};
+use indexmap::IndexMap;
+use serde_yaml_ng::{Mapping, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::thread;
+
+/// Most entries read from one source, so a huge directory can't create endless pages
+const MAX_ENTRIES: usize = 1000;
+
+/// Text of the key moving to the previous page of generated buttons
+const PREVIOUS_TEXT: &str = "◀";
+
+/// Text of the key moving to the next page of generated buttons
+const NEXT_TEXT: &str = "▶";
+
+/// Favicons missing from the icon dir, as the address of the bookmark and the name of the
+/// icon, waiting for [`fetch_missing_icons`]
+static MISSING_ICONS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Favicons that could not be downloaded, not tried again until keydeck restarts
+static FAILED_ICONS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// One entry of a source, as seen by the placeholders of the generated button
+struct Entry {
+    name: String,
+    file: String,
+    path: String,
+    /// Favicon of a bookmark, when it is in the icon dir
+    icon: String,
+}
+
+impl Entry {
+    fn from_path(path: &Path) -> Self {
+        let file = path
+            .file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = match path.is_dir() {
+            true => file.clone(),
+            false => path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_else(|| file.clone()),
+        };
+        Entry {
+            name,
+            file,
+            path: path.to_string_lossy().into_owned(),
+            icon: String::new(),
+        }
+    }
+
+    fn params(&self) -> HashMap<String, String> {
+        HashMap::from([
+            ("name".to_string(), self.name.clone()),
+            ("file".to_string(), self.file.clone()),
+            ("path".to_string(), self.path.clone()),
+            ("icon".to_string(), self.icon.clone()),
+        ])
+    }
+}
+
+/// Reads the source of every generated page and creates its buttons. A source that
+/// cannot be read is logged and leaves its page with the configured buttons only.
+pub fn read_sources(conf: &mut KeyDeckConf) {
+    for pages in conf.page_groups.values_mut() {
+        for (name, page) in pages.pages.iter_mut() {
+            let Some(generator) = &mut page.generate else {
+                continue;
+            };
+            let entries = read_entries(generator, name).unwrap_or_else(|e| {
+                crate::warn_log!("Page '{}': {}", name, e);
+                Vec::new()
+            });
+            generator.buttons = entries
+                .iter()
+                .filter_map(|entry| {
+                    generator
+                        .button_for(&entry.params())
+                        .map_err(|e| {
+                            crate::warn_log!("Page '{}', entry '{}': {}", name, entry.name, e)
+                        })
+                        .ok()
+                })
+                .collect();
+        }
+    }
+}
+
+/// Replaces the pages with a `generate` section or a `provider` by the pages showing their
+/// buttons, for a device with `button_count` keys
+pub fn generate_pages(pages: &mut Pages, button_count: u8) {
+    let configured = std::mem::take(&mut pages.pages);
+    for (name, mut page) in configured {
+        let (generator, provider) = (page.generate.take(), page.provider.take());
+        if generator.is_none() && provider.is_none() {
+            pages.pages.insert(name, page);
+            continue;
+        }
+        let mut buttons = generator.map(|g| g.buttons).unwrap_or_default();
+        buttons.extend(provider.map(|p| p.buttons).unwrap_or_default());
+        for (generated_name, generated) in paginate(&name, page, buttons, button_count) {
+            if pages.pages.contains_key(&generated_name) {
+                crate::warn_log!(
+                    "Generated page '{}' replaces a configured page",
+                    generated_name
+                );
+            }
+            pages.pages.insert(generated_name, generated);
+        }
+    }
+}
+
+/// Spreads the buttons over the free keys of copies of `page`
+fn paginate(name: &str, page: Page, buttons: Vec<Button>, button_count: u8) -> Vec<(String, Page)> {
+    let free: Vec<String> = (1..=button_count)
+        .map(|id| format!("button{}", id))
+        .filter(|key| !page.buttons.contains_key(key))
+        .collect();
+    if buttons.len() <= free.len() {
+        let mut page = page;
+        page.buttons
+            .extend(free.into_iter().zip(buttons.into_iter().map(detailed)));
+        return vec![(name.to_string(), page)];
+    }
+    if free.len() < 3 {
+        crate::warn_log!(
+            "Page '{}': {} entries don't fit on its {} free keys",
+            name,
+            buttons.len(),
+            free.len()
+        );
+        let mut page = page;
+        page.buttons
+            .extend(free.into_iter().zip(buttons.into_iter().map(detailed)));
+        return vec![(name.to_string(), page)];
+    }
+
+    let (slots, navigation) = free.split_at(free.len() - 2);
+    let chunks: Vec<&[Button]> = buttons.chunks(slots.len()).collect();
+    let page_name = |index: usize| match index {
+        0 => name.to_string(),
+        _ => format!("{} {}", name, index + 1),
+    };
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut generated = page.clone();
+            if index > 0 {
+                // Only the first page is selected by the focused window
+                generated.when = None;
+            }
+            generated.buttons.extend(
+                slots
+                    .iter()
+                    .cloned()
+                    .zip(chunk.iter().cloned().map(detailed)),
+            );
+            let previous = page_name((index + chunks.len() - 1) % chunks.len());
+            let next = page_name((index + 1) % chunks.len());
+            generated
+                .buttons
+                .insert(navigation[0].clone(), jump_button(PREVIOUS_TEXT, &previous));
+            generated
+                .buttons
+                .insert(navigation[1].clone(), jump_button(NEXT_TEXT, &next));
+            (page_name(index), generated)
+        })
+        .collect()
+}
+
+fn detailed(button: Button) -> ButtonConfig {
+    ButtonConfig::Detailed(button)
+}
+
+/// A key with `text` that jumps to `page`
+fn jump_button(text: &str, page: &str) -> ButtonConfig {
+    let mut jump = Mapping::new();
+    jump.insert("jump".into(), page.into());
+    let mut button = Mapping::new();
+    button.insert("text".into(), text.into());
+    button.insert(
+        "actions".into(),
+        Value::Sequence(vec![Value::Mapping(jump)]),
+    );
+    detailed(serde_yaml_ng::from_value(Value::Mapping(button)).expect("navigation button is valid"))
+}
+
+/// The entries of the source of page `page`, in order
+fn read_entries(generator: &Generator, page: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = match generator.from {
+        GeneratorSource::Dir => read_dir_entries(&expand_home(&generator.path))?,
+        GeneratorSource::Playlist => read_playlist_entries(&expand_home(&generator.path))?,
+        GeneratorSource::Bookmarks => {
+            read_bookmark_entries(generator.browser.as_deref(), &generator.path)?
+        }
+        GeneratorSource::Checklist => read_checklist_entries(generator, page)?,
+    };
+    if let Some(filter) = &generator.filter {
+        entries.retain(|entry| wildcard_match(filter, &entry.file));
+    }
+    if entries.len() > MAX_ENTRIES {
+        crate::warn_log!(
+            "'{}' has {} entries, using the first {}",
+            generator.path,
+            entries.len(),
+            MAX_ENTRIES
+        );
+        entries.truncate(MAX_ENTRIES);
+    }
+    Ok(entries)
+}
+
+/// The items of a checklist, each with `PAGE/ITEM` as path, which is how the `check`
+/// action and the `${checklist:...}` provider address it
+fn read_checklist_entries(generator: &Generator, page: &str) -> Result<Vec<Entry>, String> {
+    let items = match &generator.items {
+        Some(items) => items.clone(),
+        None => {
+            let file = expand_home(&generator.path);
+            fs::read_to_string(&file)
+                .map_err(|e| format!("Cannot read checklist {}: {}", file.display(), e))?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string)
+                .collect()
+        }
+    };
+    Ok(items
+        .into_iter()
+        .map(|item| Entry {
+            name: item.clone(),
+            file: item.clone(),
+            path: format!("{}/{}", page, item),
+            icon: String::new(),
+        })
+        .collect())
+}
+
+/// The visible files and directories of a directory, sorted by name
+fn read_dir_entries(dir: &Path) -> Result<Vec<Entry>, String> {
+    let listing =
+        fs::read_dir(dir).map_err(|e| format!("Cannot read directory {}: {}", dir.display(), e))?;
+    let mut entries: Vec<Entry> = listing
+        .flatten()
+        .map(|item| item.path())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|f| !f.to_string_lossy().starts_with('.'))
+        })
+        .map(|path| Entry::from_path(&path))
+        .collect();
+    entries.sort_by_key(|entry| entry.name.to_lowercase());
+    Ok(entries)
+}
+
+/// The tracks of an `.m3u`/`.m3u8` or `.pls` playlist, with the titles it gives them.
+/// Relative locations are resolved against the directory of the playlist.
+fn read_playlist_entries(playlist: &Path) -> Result<Vec<Entry>, String> {
+    let content = fs::read_to_string(playlist)
+        .map_err(|e| format!("Cannot read playlist {}: {}", playlist.display(), e))?;
+    let base = playlist.parent().unwrap_or(Path::new("."));
+    let is_pls = playlist
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pls"));
+    let tracks = match is_pls {
+        true => parse_pls(&content),
+        false => parse_m3u(&content),
+    };
+    Ok(tracks
+        .into_iter()
+        .map(|(location, title)| {
+            let mut entry = if location.contains("://") {
+                let file = location.rsplit('/').next().unwrap_or_default().to_string();
+                Entry {
+                    name: file.clone(),
+                    file,
+                    path: location,
+                    icon: String::new(),
+                }
+            } else {
+                Entry::from_path(&base.join(location))
+            };
+            if let Some(title) = title.filter(|t| !t.is_empty()) {
+                entry.name = title;
+            }
+            entry
+        })
+        .collect())
+}
+
+/// The bookmarks of a folder, with the site as file name. Favicons not yet in the icon
+/// dir are queued for [`fetch_missing_icons`].
+fn read_bookmark_entries(browser: Option<&str>, folder: &str) -> Result<Vec<Entry>, String> {
+    let icon_dir = keydeck_types::get_icon_dir_path();
+    let failed = FAILED_ICONS.lock().unwrap();
+    let mut missing = MISSING_ICONS.lock().unwrap();
+    Ok(bookmarks::read(browser, folder)?
+        .into_iter()
+        .map(|bookmark| {
+            let rest = bookmark.url.split_once("://").map_or("", |(_, rest)| rest);
+            let site = rest.split(['/', '?', '#']).next().unwrap_or_default();
+            let site = site.rsplit('@').next().unwrap_or_default().to_string();
+            let icon_name = format!("bookmark_{}", site);
+            let icon_file = favicon::icon_file_name(&icon_name);
+            let icon = if icon_dir.join(&icon_file).exists() {
+                icon_file
+            } else {
+                if !site.is_empty() && !failed.contains(&icon_name) {
+                    missing.push((bookmark.url.clone(), icon_name));
+                }
+                String::new()
+            };
+            Entry {
+                name: match bookmark.title.is_empty() {
+                    true => site.clone(),
+                    false => bookmark.title,
+                },
+                file: site,
+                path: bookmark.url,
+                icon,
+            }
+        })
+        .collect())
+}
+
+/// Downloads the favicons found missing while reading the sources, in the background.
+/// Once any of them arrived the configuration is reloaded, so the keys show them.
+pub fn fetch_missing_icons(tx: &Sender<DeviceEvent>) {
+    let mut missing = std::mem::take(&mut *MISSING_ICONS.lock().unwrap());
+    missing.sort_by(|a, b| a.1.cmp(&b.1));
+    missing.dedup_by(|a, b| a.1 == b.1);
+    if missing.is_empty() {
+        return;
+    }
+    let tx = tx.clone();
+    thread::spawn(move || {
+        let icon_dir = keydeck_types::get_icon_dir_path();
+        let mut fetched = 0;
+        for (url, name) in missing {
+            // Another reload may have fetched it meanwhile
+            if icon_dir.join(favicon::icon_file_name(&name)).exists() {
+                continue;
+            }
+            match favicon::fetch_icon(&url, Some(&name), &icon_dir) {
+                Ok(_) => fetched += 1,
+                Err(e) => {
+                    crate::verbose_log!("No favicon for bookmark {}: {}", url, e);
+                    FAILED_ICONS.lock().unwrap().insert(name);
+                }
+            }
+        }
+        if fetched > 0 {
+            crate::info_log!("Fetched {} bookmark icons, reloading", fetched);
+            send(&tx, DeviceEvent::Reload);
+        }
+    });
+}
+
+/// Locations of an M3U playlist, with the title of their `#EXTINF` line
+fn parse_m3u(content: &str) -> Vec<(String, Option<String>)> {
+    let mut tracks = Vec::new();
+    let mut title = None;
+    for line in content
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}').trim())
+    {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            title = info.split_once(',').map(|(_, t)| t.trim().to_string());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            tracks.push((line.to_string(), title.take()));
+        }
+    }
+    tracks
+}
+
+/// Locations of a PLS playlist (`FileN=`), with their `TitleN=`
+fn parse_pls(content: &str) -> Vec<(String, Option<String>)> {
+    let mut files = IndexMap::new();
+    let mut titles = HashMap::new();
+    for line in content.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        let key = key.trim().to_ascii_lowercase();
+        if let Some(n) = key.strip_prefix("file") {
+            files.insert(n.to_string(), value);
+        } else if let Some(n) = key.strip_prefix("title") {
+            titles.insert(n.to_string(), value);
+        }
+    }
+    files
+        .into_iter()
+        .map(|(n, file)| (file, titles.remove(&n)))
+        .collect()
+}
+
+/// Replaces a leading `~` by the home directory
+fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE"));
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            PathBuf::from(format!("{}{}", home, rest))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Case-insensitive match of a name against a pattern with `*` and `?` wildcards
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and of the name where it started matching
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn m3u_titles_and_wildcards() {
+        let tracks =
+            parse_m3u("#EXTM3U\n#EXTINF:123,Artist - Song\nsong.mp3\nhttp://radio/stream\n");
+        assert_eq!(
+            tracks[0],
+            ("song.mp3".to_string(), Some("Artist - Song".to_string()))
+        );
+        assert_eq!(tracks[1], ("http://radio/stream".to_string(), None));
+        assert!(wildcard_match("*.M3U", "Chill.m3u"));
+        assert!(wildcard_match("a?c*", "abcdef"));
+        assert!(!wildcard_match("*.m3u", "notes.txt"));
+    }
+}
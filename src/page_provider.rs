@@ -0,0 +1,276 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Pages with a `provider` get their buttons from a command or a web address, as a JSON
+//! array of buttons, so user programs can fill whole pages (a pod list, open tickets).
+//!
+//! Each provider is read by its own thread: once, every `interval` seconds, or, with
+//! `stream`, on every line the command prints while it keeps running. New buttons are
+//! kept here and announced with a [`DeviceEvent::PageProvided`]; the event loop then
+//! lays them out on the devices of the page group like generated buttons. The threads
+//! stop when the configuration is reloaded.
+
+use crate::event::{send, DeviceEvent};
+use crate::pages::{Button, ButtonDefaults, KeyDeckConf, Macro, PageProvider};
+use crate::{error_log, verbose_log};
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Longest wait for a command or web address without a `timeout`
+const DEFAULT_TIMEOUT: f64 = 10.0;
+
+/// Shortest `interval`, so a provider cannot keep the daemon busy
+const MIN_INTERVAL: f64 = 1.0;
+
+/// Most buttons taken from one provider
+const MAX_BUTTONS: usize = 1000;
+
+/// Buttons provided, by page group and page
+type Provided = HashMap<(String, String), Vec<Button>>;
+
+/// Buttons provided and not yet taken by the event loop
+static PROVIDED: Mutex<Option<Provided>> = Mutex::new(None);
+
+/// What the buttons of a provider need from the rest of the configuration
+struct Context {
+    group: String,
+    page: String,
+    defaults: Option<ButtonDefaults>,
+    macros: Option<IndexMap<String, Macro>>,
+}
+
+/// Starts reading the providers of the configuration, until `active` turns false
+pub fn start(conf: &KeyDeckConf, tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>) {
+    for (group, pages) in &conf.page_groups {
+        for (page, config) in &pages.pages {
+            let Some(provider) = &config.provider else {
+                continue;
+            };
+            if let Err(e) = check(provider) {
+                error_log!("Page '{}': {}", page, e);
+                continue;
+            }
+            let context = Context {
+                group: group.clone(),
+                page: page.clone(),
+                defaults: conf.defaults.clone(),
+                macros: conf.macros.clone(),
+            };
+            let (provider, tx, active) = (provider.clone(), tx.clone(), active.clone());
+            thread::spawn(move || match provider.stream {
+                true => follow_stream(&provider, &context, &tx, &active),
+                false => poll(&provider, &context, &tx, &active),
+            });
+        }
+    }
+}
+
+/// The buttons last provided for a page, once
+pub fn take(group: &str, page: &str) -> Option<Vec<Button>> {
+    PROVIDED
+        .lock()
+        .unwrap()
+        .as_mut()?
+        .remove(&(group.to_string(), page.to_string()))
+}
+
+fn check(provider: &PageProvider) -> Result<(), String> {
+    match (&provider.exec, &provider.url) {
+        (Some(_), None) => Ok(()),
+        (None, Some(_)) if provider.stream => {
+            Err("provider: stream needs a command (exec)".to_string())
+        }
+        (None, Some(_)) => Ok(()),
+        _ => Err("provider needs either exec or url".to_string()),
+    }
+}
+
+/// Reads the provider now and then every `interval`
+fn poll(provider: &PageProvider, context: &Context, tx: &Sender<DeviceEvent>, active: &AtomicBool) {
+    let timeout = provider.timeout.unwrap_or(DEFAULT_TIMEOUT);
+    let interval = provider
+        .interval
+        .map(|interval| Duration::from_secs_f64(interval.max(MIN_INTERVAL)));
+    let mut last_output = None;
+    loop {
+        let output = match (&provider.exec, &provider.url) {
+            (Some(exec), _) => crate::services::execute_with_timeout(exec, Some(timeout)),
+            (None, Some(url)) => {
+                crate::http_action::send_request(url, None, None, None, Some(timeout as f32))
+            }
+            (None, None) => return,
+        };
+        match output {
+            Ok(output) if last_output.as_ref() != Some(&output) => {
+                deliver(&output, context, tx, active);
+                last_output = Some(output);
+            }
+            Ok(_) => {}
+            Err(e) => error_log!("Provider of page '{}' failed: {}", context.page, e),
+        }
+        let Some(interval) = interval else {
+            return;
+        };
+        let next = Instant::now() + interval;
+        while Instant::now() < next {
+            if !active.load(Ordering::Relaxed) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+    }
+}
+
+/// Runs the command and takes every line it prints as the new buttons. The command is
+/// stopped with the configuration.
+fn follow_stream(
+    provider: &PageProvider,
+    context: &Context,
+    tx: &Sender<DeviceEvent>,
+    active: &Arc<AtomicBool>,
+) {
+    let Some(exec) = &provider.exec else {
+        return;
+    };
+    let (shell, flag) = crate::platform::exec_shell();
+    let mut child = match Command::new(shell)
+        .arg(flag)
+        .arg(exec)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            error_log!("Provider of page '{}' failed to start: {}", context.page, e);
+            return;
+        }
+    };
+    let Some(stdout) = child.stdout.take() else {
+        return;
+    };
+    let child = Arc::new(Mutex::new(child));
+    // Lines may be far apart, so another thread stops the command once reloaded
+    {
+        let (child, active) = (child.clone(), active.clone());
+        thread::spawn(move || {
+            while active.load(Ordering::Relaxed) {
+                if child
+                    .lock()
+                    .unwrap()
+                    .try_wait()
+                    .is_ok_and(|status| status.is_some())
+                {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(500));
+            }
+            let _ = child.lock().unwrap().kill();
+        });
+    }
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if !line.trim().is_empty() {
+            deliver(&line, context, tx, active);
+        }
+    }
+    let _ = child.lock().unwrap().wait();
+    if active.load(Ordering::Relaxed) {
+        error_log!("Provider of page '{}' exited", context.page);
+    }
+}
+
+/// Parses the output of a provider and hands its buttons to the event loop
+fn deliver(output: &str, context: &Context, tx: &Sender<DeviceEvent>, active: &AtomicBool) {
+    let mut buttons = match parse_buttons(output) {
+        Ok(buttons) => buttons,
+        Err(e) => {
+            error_log!("Provider of page '{}': {}", context.page, e);
+            return;
+        }
+    };
+    if !active.load(Ordering::Relaxed) {
+        return;
+    }
+    for button in buttons.iter_mut() {
+        if let Some(defaults) = &context.defaults {
+            defaults.apply(button);
+        }
+        button.is_dynamic_computed =
+            crate::dynamic_detection::is_button_dynamic(button, &context.macros);
+    }
+    verbose_log!(
+        "Provider of page '{}' sent {} buttons",
+        context.page,
+        buttons.len()
+    );
+    PROVIDED
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert((context.group.clone(), context.page.clone()), buttons);
+    send(
+        tx,
+        DeviceEvent::PageProvided {
+            group: context.group.clone(),
+            page: context.page.clone(),
+        },
+    );
+}
+
+/// The buttons of a JSON array, written like buttons in the configuration
+fn parse_buttons(output: &str) -> Result<Vec<Button>, String> {
+    let mut buttons: Vec<Button> =
+        serde_json::from_str(output.trim()).map_err(|e| format!("Invalid buttons: {}", e))?;
+    if buttons.len() > MAX_BUTTONS {
+        crate::warn_log!(
+            "Provider sent {} buttons, using the first {}",
+            buttons.len(),
+            MAX_BUTTONS
+        );
+        buttons.truncate(MAX_BUTTONS);
+    }
+    Ok(buttons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pages::{Action, TextConfig};
+
+    #[test]
+    fn providers_send_buttons_as_json() {
+        let buttons = parse_buttons(
+            r#"[{"text": "web-1", "icon": "pod.png", "actions": [{"exec": "kubectl logs web-1"}]},
+                {"text": "db-0", "background": "0x203040"}]"#,
+        )
+        .unwrap();
+        assert_eq!(buttons.len(), 2);
+        assert!(matches!(&buttons[0].text, Some(TextConfig::Simple(text)) if text == "web-1"));
+        assert!(matches!(
+            buttons[0].actions.as_deref(),
+            Some([Action::Exec { .. }])
+        ));
+        assert!(parse_buttons(r#"[{"txt": "typo"}]"#).is_err());
+        assert!(parse_buttons("not json").is_err());
+
+        let stream = PageProvider {
+            exec: None,
+            url: Some("http://localhost/pods".to_string()),
+            interval: None,
+            stream: true,
+            timeout: None,
+            buttons: vec![],
+        };
+        assert!(check(&stream).is_err());
+    }
+}
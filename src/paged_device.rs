@@ -1,33 +1,234 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
+use crate::audio::{self, DeviceKind};
+use crate::bench::{self, BenchReport, Probe};
+use crate::button_look::{self, IconImage};
+use crate::calendar;
+use crate::chats;
+use crate::checklist;
+use crate::config_diff::{self, PageChange};
 use crate::context::ContextVars;
-use crate::device_manager::find_path;
-use crate::device_trait::KeydeckDevice;
-use crate::dynamic_params::evaluate_dynamic_params;
-use crate::event::{send, DeviceEvent, WaitEventType};
-use crate::graphics_renderer;
-use crate::platform::{process_escape_sequences, send_key_combination, send_string, set_focus};
-use crate::listener_button::button_listener;
+use crate::dbus_state;
+use crate::device_trait::{DeviceError, KeydeckDevice};
+use crate::dynamic_detection::uses_provider;
+use crate::dynamic_params::{evaluate_dynamic_params, LocalParams};
+use crate::error::Error;
+use crate::event::{send, DeviceEvent, WaitConditions, WaitEventType};
+use crate::exec::{self, exec_command};
+use crate::frame_scheduler::{FrameScheduler, DEFAULT_MAX_FPS};
+use crate::history::{self, HistoryEntry};
+use crate::http_action;
+use crate::i18n::Translations;
+use crate::image_formats;
+use crate::live_events::{self, LiveEvent};
+use crate::mirror;
+use crate::openrgb;
+use crate::oriented_device::{KeyMap, OrientedDevice};
+use crate::page_generator;
+use crate::platform::{
+    open_url, power, process_escape_sequences, send_key_combination, send_notification,
+    send_string, set_focus, speak, switch_keyboard_layout,
+};
+use crate::listener_button::{button_listener, ReadTuning};
 use crate::listener_time::TimeManager;
+use crate::pages::condition;
 use crate::pages::{
-    Action, Button, ButtonConfig, Direction, DrawConfig, Encoder, FocusChangeRestorePolicy,
-    GraphicType, MacroCall, Page, Pages, RefreshTarget, ServiceConfig, TextConfig,
+    Accessibility, Action, Animation, Button, ButtonBuiltin, ButtonConfig, Chord, ColorConfig, Direction,
+    DrawConfig, Effects, Enabled, Encoder, EncoderBuiltin, EncoderWidget, FocusChangeRestorePolicy, FocusTarget,
+    GraphicType, MacroCall, OnError, Page, Pages, PressSound, RefreshTarget, ServiceConfig,
+    ShutdownMode, ShutdownState, Switch, TextConfig, Theme, Timer, TimerStyle, Wallpaper,
+    WindowWait,
 };
+use crate::runtime_state::DeviceState;
 use crate::services::ServicesState;
-use crate::text_renderer;
+use crate::stats::SharedStats;
+use crate::status_page::{self, Diagnostics};
+use crate::window_match::WindowQuery;
+use crate::wallpaper::{self, KeyGrid};
+use crate::wol;
 use crate::press_effect::compose_button;
+use crate::processes;
+use crate::render_budget::{DeferredKeys, RenderSlice};
 use crate::{detail_log, error_log, verbose_log, warn_log};
+use chrono::{Datelike, Local, Timelike};
 use image::imageops::overlay;
-use image::{open, DynamicImage, Rgba, RgbaImage};
+use image::{DynamicImage, Rgba, RgbaImage};
 use indexmap::IndexMap;
-use std::cell::RefCell;
+use keydeck_render::graphics::{self, BarDirection, NightFilter};
+use keydeck_render::text::{needs_marquee, render_text_on_canvas};
+use keydeck_render::{Area, ButtonSpec, Graphic, Label};
+use keydeck_types::pages::Icon;
+use keydeck_types::IconPaths;
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::mpsc::Sender;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
 use std::time::{Duration, Instant};
 
+/// How much earlier than its interval a tick may come and still run the tick actions
+const TICK_SLACK: Duration = Duration::from_millis(250);
+
+/// Background shown while a button flashes after a failed action
+const ERROR_FLASH_COLOR: &str = "0xC00000";
+
+/// Time between re-renders of a pulsing or scrolling button (about 15 frames per second)
+const ANIMATION_FRAME: Duration = Duration::from_millis(66);
+
+/// Opacity of disabled buttons, on top of their own `effects`
+const DISABLED_OPACITY: f32 = 0.4;
+
+/// Wall clock in seconds, so that all animated keys stay in step
+fn epoch_seconds() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+/// How long the error flash stays visible
+const ERROR_FLASH_DURATION: Duration = Duration::from_millis(600);
+
+/// Color of the badge on the `pin_indicator` button while the page is pinned
+const PIN_BADGE_COLOR: (u8, u8, u8) = (230, 40, 40);
+
+/// Shortest time between two repetitions of a held button
+const MIN_REPEAT_INTERVAL: Duration = Duration::from_millis(20);
+
+/// `text` actions without `echo` are shown on the keys when longer than this
+const ECHO_MIN_CHARS: usize = 40;
+
+/// How long each row of a scrolling echo is shown
+const ECHO_SCROLL_STEP: Duration = Duration::from_millis(800);
+
+/// How long the complete text (or its end) is shown before it is typed
+const ECHO_HOLD: Duration = Duration::from_millis(1500);
+
+/// Pages remembered for the `back` button, the most recent last
+const PAGE_HISTORY: usize = 20;
+
+/// How long a `confirm` action waits for an answer without `timeout`
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the `target` window of keys or text is given to take the focus
+const TARGET_FOCUS_SETTLE: Duration = Duration::from_millis(150);
+
+/// How long after keys were sent to a `target` window the pages do not follow the focus,
+/// while it goes back to the window that had it
+const TARGET_FOCUS_GRACE: Duration = Duration::from_millis(500);
+
+/// How long the test pattern is shown, unless a key press ends it first
+const TEST_PATTERN_DURATION: Duration = Duration::from_secs(5);
+
+/// Smallest label size in high-contrast mode, unless `min_font_size` is set
+const HIGH_CONTRAST_MIN_FONT_SIZE: f32 = 20.0;
+
+/// Scroll speed of high-contrast labels too long for their key, in pixels per second
+const HIGH_CONTRAST_MARQUEE_SPEED: f32 = 30.0;
+
+/// Volume of press sounds, unless `press_sound` sets one
+const PRESS_SOUND_VOLUME: f32 = 0.5;
+
+/// How long a `timer` button is held to reset it
+const TIMER_RESET_HOLD: f32 = 1.0;
+
+/// Color of a `timer` without its own
+const TIMER_COLOR: (u8, u8, u8) = (255, 128, 0);
+
+/// Blinks per second of a `timer` whose time is up
+const TIMER_BLINK_RATE: f64 = 2.0;
+
+/// Brightness change per step of an encoder bound to `brightness`
+const BRIGHTNESS_STEP: i32 = 5;
+
+/// A label as drawn in high-contrast mode: never below `min_size`, and scrolling when
+/// it does not fit its key at that size
+fn high_contrast_text(text: TextConfig, min_size: f32) -> TextConfig {
+    let (value, font_size, marquee) = match text {
+        TextConfig::Simple(value) => (value, None, None),
+        TextConfig::Detailed {
+            value,
+            font_size,
+            marquee,
+        } => (value, font_size, marquee),
+    };
+    TextConfig::Detailed {
+        value,
+        font_size: font_size.map(|size| size.max(min_size)),
+        marquee: marquee.or(Some(HIGH_CONTRAST_MARQUEE_SPEED)),
+    }
+}
+
+/// A running `cooldown` of the button at an index of a page
+#[derive(Clone, Copy)]
+struct CooldownState {
+    page: usize,
+    until: Instant,
+    countdown: bool,
+}
+
+/// A `timer` that is not at its full duration
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TimerState {
+    /// Counting down; `run` identifies this start, so an alarm of an earlier one is ignored
+    Running {
+        until: Instant,
+        run: u64,
+    },
+    Paused {
+        left: Duration,
+    },
+    /// The time is up, until the button is pressed
+    Ringing,
+}
+
+impl TimerState {
+    /// Time left of a timer of `duration`, or all of it when not started
+    fn left(state: Option<TimerState>, duration: Duration) -> Duration {
+        match state {
+            None => duration,
+            Some(TimerState::Running { until, .. }) => {
+                until.saturating_duration_since(Instant::now())
+            }
+            Some(TimerState::Paused { left }) => left,
+            Some(TimerState::Ringing) => Duration::ZERO,
+        }
+    }
+}
+
+/// Whole minutes and seconds of `seconds`, with hours once there are any
+fn format_timer(seconds: u64) -> String {
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+/// A `text` action shown across the keys before it is typed
+struct TextEcho {
+    id: u64,
+    text: String,
+    frames: Vec<Vec<String>>,
+    /// Index of the frame to show next
+    next: usize,
+    /// Window the text is typed into, see [`PagedDevice::send_to_window`]
+    target: Option<FocusTarget>,
+    /// Actions after the `text` action, run once it is typed
+    remaining: Vec<Action>,
+}
+
+/// The question of a `confirm` action, covering the keys until it is answered
+struct Confirmation {
+    id: u64,
+    /// Keys answering the question, as button ids
+    confirm_key: u8,
+    cancel_key: u8,
+    /// The `then` actions, followed by the actions after the `confirm` action
+    confirm: Vec<Action>,
+    /// The `else` actions
+    cancel: Vec<Action>,
+}
+
 /// Represents a queue of actions waiting to be executed after an event occurs.
 /// Created when a WaitFor action is executed, and resumed when the corresponding event arrives.
 struct PendingActionQueue {
@@ -35,51 +236,172 @@ struct PendingActionQueue {
     actions: Vec<Action>,
     /// Timestamp when this queue was last modified, used for timeout detection
     last_modified: Instant,
-    /// Maximum time to wait for the event before dropping the queue
-    timeout: Duration,
-    /// The event type we're waiting for
-    event_type: WaitEventType,
+    /// The events we're waiting for
+    conditions: WaitConditions,
 }
 
 pub struct PagedDevice {
     device: Box<dyn KeydeckDevice>,
+    /// Update rate limit of the device's frame scheduler, follows `max_fps`
+    max_fps: Arc<AtomicU32>,
+    /// How the key listener reads the device, follows `input_polling`
+    read_tuning: Arc<ReadTuning>,
+    /// Translates the physical keys of presses to the keys as the user sees them
+    key_map: KeyMap,
     serial: String,
     pages: Arc<Pages>,
     colors: Arc<Option<IndexMap<String, String>>>,
+    themes: Arc<Option<IndexMap<String, Theme>>>,
     button_templates: Arc<Option<IndexMap<String, Button>>>,
     macros: Arc<Option<IndexMap<String, crate::pages::Macro>>>,
     services_config: Arc<Option<IndexMap<String, ServiceConfig>>>,
+    on_error: Arc<Option<OnError>>,
     services_state: ServicesState,
     services_active: Arc<AtomicBool>,
     context_vars: ContextVars,
-    image_dir: Option<String>,
-    current_page_ref: RefCell<usize>,
-    button_images: RefCell<Vec<String>>,
-    button_backgrounds: RefCell<Vec<String>>,
-    button_canvases: RefCell<Vec<Option<RgbaImage>>>,
-    button_pressed: RefCell<Vec<bool>>,
+    usage_stats: SharedStats,
+    /// When the current page was shown, or its dwell time last added to the statistics
+    page_since: Mutex<Instant>,
+    /// Keys are tinted by how often they were pressed
+    heatmap: AtomicBool,
+    /// The shown page is pinned with the `pin` action and ignores focus changes
+    pinned: AtomicBool,
+    /// When the shown page was switched to, for the `rotation` and `auto_return` of the
+    /// pages
+    rotated_at: Mutex<Instant>,
+    /// When a key, encoder or touch screen was last used, to hold the `rotation` and
+    /// `auto_return`
+    last_interaction: Mutex<Option<Instant>>,
+    /// Pages shown before the current one, for the `back` button
+    page_history: RwLock<Vec<String>>,
+    /// The screen is off after a `sleep` button, until the next key press
+    asleep: AtomicBool,
+    /// When the tick actions of the shown page last ran
+    last_tick: Mutex<Instant>,
+    /// Game mode is on: focus changes are ignored and keys are not animated
+    game_mode: AtomicBool,
+    /// Brightness of the screen: the configured one, until an encoder changes it
+    brightness: AtomicU8,
+    /// Where the icons of the keys are looked up
+    icon_paths: RwLock<Arc<IconPaths>>,
+    current_page_ref: RwLock<usize>,
+    button_images: RwLock<Vec<String>>,
+    button_backgrounds: RwLock<Vec<String>>,
+    button_canvases: RwLock<Vec<Option<RgbaImage>>>,
+    button_pressed: RwLock<Vec<bool>>,
+    button_flash_until: RwLock<Vec<Option<Instant>>>,
+    button_cooldowns: RwLock<Vec<Option<CooldownState>>>,
+    /// Page and press id of each held button that repeats its actions
+    button_repeats: RwLock<Vec<Option<(usize, u64)>>>,
+    /// Press id of each held `timer` button, and whether holding it reset the timer
+    timer_holds: RwLock<Vec<Option<(u64, bool)>>>,
+    /// When each held button was pressed, to recognize the `chords` of the page
+    held_since: RwLock<Vec<Option<Instant>>>,
+    /// Buttons of a chord that ran, whose release runs nothing
+    chord_keys: RwLock<Vec<bool>>,
+    /// Page, layer and `modifier` button of the layer shown while the button is held
+    active_layer: RwLock<Option<(usize, String, u8)>>,
+    /// Timers by page name and button, while not at their full duration
+    timers: RwLock<HashMap<(String, u8), TimerState>>,
+    press_counter: AtomicU64,
+    /// When the next animation frame of each animated button is already scheduled
+    animation_due: RwLock<Vec<Option<Instant>>>,
     active_events: Arc<AtomicBool>,
-    last_active_page: RefCell<Option<String>>,
-    last_auto_target_page: RefCell<Option<String>>,
-    current_class: RefCell<String>,
-    current_title: RefCell<String>,
-    pending_actions: RefCell<Option<PendingActionQueue>>,
+    last_active_page: RwLock<Option<String>>,
+    last_auto_target_page: RwLock<Option<String>>,
+    current_class: RwLock<String>,
+    current_title: RwLock<String>,
+    /// Id of the latest focus change; a delayed one is only applied if still the latest
+    focus_counter: AtomicU64,
+    /// Until when the pages do not follow the focus, taken to send keys to another window
+    targeted_until: RwLock<Option<Instant>>,
+    pending_actions: RwLock<Option<PendingActionQueue>>,
+    /// Widget values written via SetContextVar that the event loop has not applied yet
+    pending_widget_values: RwLock<HashMap<String, f32>>,
+    /// Encoders held down, and whether they were twisted with their press twist actions
+    encoders_held: RwLock<HashMap<u8, bool>>,
+    /// Press counters of cycling `focus` targets, keyed by target description
+    focus_cycles: RwLock<HashMap<String, usize>>,
+    /// The built-in error page covers the keys until the next press or reload
+    error_page_shown: AtomicBool,
+    /// The echo of a `text` action covers the keys until it is typed or cancelled
+    text_echo: RwLock<Option<TextEcho>>,
+    /// Id of the latest text echo
+    echo_counter: AtomicU64,
+    /// The question of a `confirm` action covers the keys until it is answered
+    confirmation: RwLock<Option<Confirmation>>,
+    /// Id of the latest confirmation
+    confirm_counter: AtomicU64,
+    /// Keys a bulk refresh left to draw after the waiting input
+    deferred_keys: Mutex<DeferredKeys>,
+    /// The test pattern covers the keys until a key press or its time is up
+    test_pattern_shown: AtomicBool,
+    /// What the diagnostic page shows while it covers the keys, until a key press
+    diagnostics: RwLock<Option<Diagnostics>>,
+    /// Night mode filter currently applied to rendered keys, if any
+    night_filter: RwLock<Option<NightFilter>>,
+    /// Globally selected theme; a page's own `theme` takes precedence
+    active_theme: RwLock<Option<String>>,
+    /// Spoken announcements and high-contrast keys
+    accessibility: RwLock<Option<Accessibility>>,
+    /// Sound played on key presses
+    press_sound: RwLock<Option<PressSound>>,
+    /// Translations shown by `${i18n:key}`
+    strings: RwLock<Translations>,
     time_manager: Arc<TimeManager>,
     background_image: Option<String>,
     event_tx: Sender<DeviceEvent>,
 }
 
+// Devices may be rendered, and their actions run, away from the server thread
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<PagedDevice>();
+};
+
+/// Translates the `r2c3` style button keys of a configuration to button indices of the
+/// device; the configuration is shared as is when it uses none
+fn resolve_grid_keys(pages: Arc<Pages>, device: &dyn KeydeckDevice) -> Arc<Pages> {
+    if !pages.has_grid_keys() {
+        return pages;
+    }
+    let (rows, columns) = device.button_layout();
+    let mut resolved = (*pages).clone();
+    for problem in resolved.resolve_grid_keys(rows, columns) {
+        warn_log!("{}", problem);
+    }
+    Arc::new(resolved)
+}
+
+/// Creates the buttons of the pages with a `generate` section or a `provider` for the keys
+/// of the device; the configuration is shared as is when it has none
+fn generate_pages(pages: Arc<Pages>, device: &dyn KeydeckDevice) -> Arc<Pages> {
+    if pages
+        .pages
+        .values()
+        .all(|page| page.generate.is_none() && page.provider.is_none())
+    {
+        return pages;
+    }
+    let mut generated = (*pages).clone();
+    page_generator::generate_pages(&mut generated, device.button_count());
+    Arc::new(generated)
+}
+
 impl PagedDevice {
     pub fn new(
         pages: Arc<Pages>,
-        image_dir: Option<String>,
+        icon_paths: Arc<IconPaths>,
         colors: Arc<Option<IndexMap<String, String>>>,
+        themes: Arc<Option<IndexMap<String, Theme>>>,
         button_templates: Arc<Option<IndexMap<String, Button>>>,
         macros: Arc<Option<IndexMap<String, crate::pages::Macro>>>,
         services_config: Arc<Option<IndexMap<String, ServiceConfig>>>,
+        on_error: Arc<Option<OnError>>,
         services_state: ServicesState,
         services_active: Arc<AtomicBool>,
         context_vars: ContextVars,
+        usage_stats: SharedStats,
         device: Box<dyn KeydeckDevice>,
         tx: &Sender<DeviceEvent>,
         time_manager: Arc<TimeManager>,
@@ -87,6 +409,19 @@ impl PagedDevice {
         brightness: u8,
         background_image: Option<String>,
     ) -> Self {
+        let max_fps = Arc::new(AtomicU32::new(pages.max_fps.unwrap_or(DEFAULT_MAX_FPS)));
+        let key_map = KeyMap::new(
+            pages.orientation.unwrap_or_default(),
+            device.button_layout(),
+        );
+        let device: Box<dyn KeydeckDevice> = if key_map.is_identity() {
+            device
+        } else {
+            Box::new(OrientedDevice::new(device, key_map))
+        };
+        let device: Box<dyn KeydeckDevice> = Box::new(FrameScheduler::new(device, max_fps.clone()));
+        let pages = resolve_grid_keys(pages, device.as_ref());
+        let pages = generate_pages(pages, device.as_ref());
         let serial = device.serial_number().unwrap_or_else(|e| {
             error_log!("Failed to get device serial number: {}", e);
             "Unknown".to_string()
@@ -96,20 +431,30 @@ impl PagedDevice {
         // Input reading strategy is platform-specific (see `listener_button`).
         // Windows shares this device's single handle (passes the reader); Linux
         // and macOS open their own handle by serial.
+        let read_tuning = Arc::new(ReadTuning::new(pages.input_polling.as_ref()));
         #[cfg(target_os = "windows")]
-        button_listener(device.get_reader(), &serial, tx, &active_events);
+        button_listener(
+            device.get_reader(),
+            &serial,
+            tx,
+            &active_events,
+            &read_tuning,
+        );
         #[cfg(not(target_os = "windows"))]
-        button_listener(&serial, tx, &active_events);
+        button_listener(&serial, tx, &active_events, &read_tuning);
         device
             .reset()
             .unwrap_or_else(|e| error_log!("Error while resetting device: {}", e));
         device
             .clear_all_button_images()
             .unwrap_or_else(|e| error_log!("Error while clearing button images: {}", e));
+        if device.has_screen() {
+            status_page::show_splash(device.as_ref());
+        }
 
         // Set runtime background image (BGPIC) if configured
-        if let Some(ref bg_path) = background_image {
-            match image::open(bg_path) {
+        if let Some(bg_path) = background_image.as_ref().filter(|_| device.has_screen()) {
+            match image_formats::open(bg_path) {
                 Ok(img) => {
                     device.set_background_image(img).unwrap_or_else(|e| {
                         error_log!("Failed to set background image '{}': {}", bg_path, e);
@@ -156,28 +501,72 @@ impl PagedDevice {
 
         let paged_device = PagedDevice {
             device,
+            key_map,
+            max_fps,
+            read_tuning,
             serial,
             pages,
             colors,
+            themes,
             button_templates,
             macros,
             services_config,
+            on_error,
             services_state,
             services_active,
             context_vars,
-            image_dir,
+            usage_stats,
+            page_since: Mutex::new(Instant::now()),
+            heatmap: AtomicBool::new(false),
+            pinned: AtomicBool::new(false),
+            rotated_at: Mutex::new(Instant::now()),
+            last_interaction: Mutex::new(None),
+            page_history: RwLock::new(Vec::new()),
+            asleep: AtomicBool::new(false),
+            last_tick: Mutex::new(Instant::now()),
+            game_mode: AtomicBool::new(false),
+            brightness: AtomicU8::new(brightness),
+            icon_paths: RwLock::new(icon_paths),
             // Initialize to sentinel value so first set_page() will trigger refresh
-            current_page_ref: RefCell::new(usize::MAX),
-            button_images: RefCell::new(vec![String::new(); button_count]),
-            button_backgrounds: RefCell::new(vec![String::new(); button_count]),
-            button_canvases: RefCell::new(vec![None; button_count]),
-            button_pressed: RefCell::new(vec![false; button_count]),
+            current_page_ref: RwLock::new(usize::MAX),
+            button_images: RwLock::new(vec![String::new(); button_count]),
+            button_backgrounds: RwLock::new(vec![String::new(); button_count]),
+            button_canvases: RwLock::new(vec![None; button_count]),
+            button_pressed: RwLock::new(vec![false; button_count]),
+            button_flash_until: RwLock::new(vec![None; button_count]),
+            button_cooldowns: RwLock::new(vec![None; button_count]),
+            button_repeats: RwLock::new(vec![None; button_count]),
+            timer_holds: RwLock::new(vec![None; button_count]),
+            held_since: RwLock::new(vec![None; button_count]),
+            chord_keys: RwLock::new(vec![false; button_count]),
+            active_layer: RwLock::new(None),
+            timers: RwLock::new(HashMap::new()),
+            press_counter: AtomicU64::new(0),
+            animation_due: RwLock::new(vec![None; button_count]),
             active_events,
-            last_active_page: RefCell::new(None),
-            last_auto_target_page: RefCell::new(None),
-            current_class: RefCell::new(String::new()),
-            current_title: RefCell::new(String::new()),
-            pending_actions: RefCell::new(None),
+            last_active_page: RwLock::new(None),
+            last_auto_target_page: RwLock::new(None),
+            current_class: RwLock::new(String::new()),
+            current_title: RwLock::new(String::new()),
+            focus_counter: AtomicU64::new(0),
+            targeted_until: RwLock::new(None),
+            pending_actions: RwLock::new(None),
+            pending_widget_values: RwLock::new(HashMap::new()),
+            encoders_held: RwLock::new(HashMap::new()),
+            focus_cycles: RwLock::new(HashMap::new()),
+            error_page_shown: AtomicBool::new(false),
+            text_echo: RwLock::new(None),
+            echo_counter: AtomicU64::new(0),
+            confirmation: RwLock::new(None),
+            confirm_counter: AtomicU64::new(0),
+            deferred_keys: Mutex::new(DeferredKeys::default()),
+            test_pattern_shown: AtomicBool::new(false),
+            diagnostics: RwLock::new(None),
+            night_filter: RwLock::new(None),
+            accessibility: RwLock::new(None),
+            press_sound: RwLock::new(None),
+            strings: RwLock::new(Translations::new()),
+            active_theme: RwLock::new(None),
             time_manager,
             background_image,
             event_tx: tx.clone(),
@@ -230,18 +619,31 @@ impl PagedDevice {
             }
         }
 
+        let model = paged_device.device.kind_name();
+        dbus_state::update(&paged_device.serial, |status| {
+            status.model = model;
+            status.brightness = brightness;
+        });
+
         // The hardware may not accept brightness commands immediately after reset/reconnect
         // Try at 100ms first, then again at 1000ms to ensure it gets set
-        paged_device.time_manager.schedule_brightness(
-            paged_device.serial.clone(),
-            brightness,
-            Duration::from_millis(100),
-        );
-        paged_device.time_manager.schedule_brightness(
-            paged_device.serial.clone(),
-            brightness,
-            Duration::from_millis(1000),
-        );
+        if paged_device.device.has_screen() {
+            paged_device.time_manager.schedule_brightness(
+                paged_device.serial.clone(),
+                brightness,
+                Duration::from_millis(100),
+            );
+            paged_device.time_manager.schedule_brightness(
+                paged_device.serial.clone(),
+                brightness,
+                Duration::from_millis(1000),
+            );
+        } else {
+            verbose_log!(
+                "Device '{}' has no display, buttons are mapped to actions only",
+                paged_device.serial
+            );
+        }
 
         paged_device
     }
@@ -258,7 +660,10 @@ impl PagedDevice {
 
     /// Returns the name of the currently displayed page, or None if no page is set
     pub fn get_current_page_name(&self) -> Option<String> {
-        let current_page_idx = { self.current_page_ref.borrow().clone() };
+        let current_page_idx = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
         if current_page_idx == usize::MAX {
             None
         } else {
@@ -269,23 +674,199 @@ impl PagedDevice {
         }
     }
 
-    pub fn handle_tick(&self) {
-        // Skip tick if no valid page is set
-        if !self.has_valid_page() {
+    /// Seconds between ticks while the shown page is shown: its own `tick`, else
+    /// `tick_time`
+    pub fn tick_interval(&self, tick_time: f64) -> f64 {
+        let current_page = {
+            *self
+                .current_page_ref
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+        };
+        self.find_page(current_page)
+            .and_then(|page| page.tick)
+            .unwrap_or(tick_time)
+    }
+
+    /// Whether the tick of the shown page is due. Ticks come as often as the page of any
+    /// device needs them, so those of a page with a longer interval are skipped.
+    fn tick_due(&self, tick_time: f64) -> bool {
+        let interval = Duration::from_secs_f64(self.tick_interval(tick_time));
+        let mut last_tick = self
+            .last_tick
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // A tick handled a little late counts as on time
+        if last_tick.elapsed() + TICK_SLACK < interval {
+            return false;
+        }
+        *last_tick = Instant::now();
+        true
+    }
+
+    pub fn handle_tick(&self, tick_time: f64) {
+        self.add_dwell_time();
+        // Skip tick if no valid page is set or a built-in page covers the keys
+        if !self.has_valid_page() || self.keys_covered() {
             return;
         }
 
-        let current_page = { self.current_page_ref.borrow().clone() };
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        if self
+            .find_page(current_page)
+            .is_some_and(|page| !self.page_visible(page))
+        {
+            self.leave_hidden_page();
+            return;
+        }
+        self.rotate_pages();
+        self.auto_return();
+        if !self.tick_due(tick_time) {
+            return;
+        }
+        let current_page = {
+            *self
+                .current_page_ref
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+        };
         if let Some(page) = self.find_page(current_page) {
             if let Some(actions) = &page.on_tick {
-                if let Err(e) = self.execute_actions(actions.clone()) {
-                    error_log!("Error executing tick actions: {}", e);
+                let page = self.get_current_page_name();
+                let started = Instant::now();
+                let result = self.execute_actions(actions.clone());
+                if let Err(e) = &result {
+                    // Only failed ticks are recorded, successful ones would flood the history
+                    history::record(&HistoryEntry::new(
+                        &self.serial,
+                        page,
+                        "tick",
+                        None,
+                        actions,
+                        &result,
+                        started.elapsed(),
+                    ));
+                    self.report_action_error(
+                        &format!("Error executing tick actions: {}", e).into(),
+                        None,
+                        None,
+                    );
                 }
             }
         }
+
+        // Widgets may reflect values changed outside keydeck (e.g. volume keys)
+        self.render_encoder_widgets();
+    }
+
+    /// Shows the next page of the `rotation` once the shown one had its time, unless the
+    /// page is pinned, is not part of the rotation, or the deck was used too recently
+    fn rotate_pages(&self) {
+        let Some(rotation) = &self.pages.rotation else {
+            return;
+        };
+        if self.pinned.load(Ordering::Relaxed) {
+            return;
+        }
+        let interval = Duration::from_secs_f32(rotation.interval.unwrap_or(30.0).max(1.0));
+        if self
+            .rotated_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .elapsed()
+            < interval
+        {
+            return;
+        }
+        let pause = Duration::from_secs_f32(rotation.pause.unwrap_or(0.0).max(0.0));
+        if self
+            .last_interaction
+            .lock()
+            .unwrap()
+            .is_some_and(|used| used.elapsed() < pause)
+        {
+            return;
+        }
+        let Some(current) = self.get_current_page_name() else {
+            return;
+        };
+        let next = rotation.next_after(&current, |name| {
+            self.pages
+                .pages
+                .get(name)
+                .is_some_and(|page| self.page_visible(page))
+        });
+        if let Some(next) = next.filter(|next| *next != current) {
+            detail_log!("[{}] Rotating to page '{}'", self.serial, next);
+            if let Err(e) = self.set_page(&next.to_string(), false) {
+                error_log!("{}", e);
+            }
+        }
+    }
+
+    /// Shows the main page once the shown page, having an `auto_return`, was left unused
+    /// for that long. A pinned page stays.
+    fn auto_return(&self) {
+        let current = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(seconds) = self.find_page(current).and_then(|page| page.auto_return) else {
+            return;
+        };
+        if self.pinned.load(Ordering::Relaxed) {
+            return;
+        }
+        let shown = self
+            .rotated_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .elapsed();
+        let idle = self
+            .last_interaction
+            .lock()
+            .unwrap()
+            .map_or(shown, |used| used.elapsed().min(shown));
+        if idle < Duration::from_secs_f32(seconds.max(0.0)) {
+            return;
+        }
+        let main_page = self.main_page_index().filter(|at| *at != current);
+        let Some((main_name, _)) = main_page.and_then(|at| self.pages.pages.get_index(at)) else {
+            return;
+        };
+        detail_log!(
+            "[{}] Page unused for {:.0}s, returning to '{}'",
+            self.serial,
+            idle.as_secs_f32(),
+            main_name
+        );
+        if let Err(e) = self.set_page(main_name, false) {
+            error_log!("{}", e);
+        }
+    }
+
+    /// The `main_page` of the device, or its first page without one
+    fn main_page_index(&self) -> Option<usize> {
+        match &self.pages.main_page {
+            Some(name) => self.pages.pages.get_index_of(name),
+            None => Some(0),
+        }
+    }
+
+    /// Holds the `rotation` of the pages for its `pause`, and their `auto_return`
+    fn note_interaction(&self) {
+        *self
+            .last_interaction
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Instant::now());
     }
 
     pub fn disable(&self) {
+        dbus_state::remove(&self.serial);
+        self.add_dwell_time();
         self.active_events
             .store(false, std::sync::atomic::Ordering::Relaxed);
     }
@@ -297,44 +878,131 @@ impl PagedDevice {
             .unwrap_or_else(|e| error_log!("Error while shutting down device: {}", e));
     }
 
+    /// Leaves the keys in the `shutdown` state of the page group as the daemon exits;
+    /// without one the device is shut down
+    pub fn exit(&self) {
+        let Some(shutdown) = &self.pages.shutdown else {
+            self.terminate();
+            return;
+        };
+        self.disable();
+        let result = match shutdown {
+            ShutdownState::Mode(ShutdownMode::Keep) => Ok(()),
+            ShutdownState::Mode(ShutdownMode::Clear) => self
+                .device
+                .clear_all_button_images()
+                .and_then(|_| self.device.flush())
+                .map_err(|e| self.device_error(e)),
+            ShutdownState::Image(image) => self.show_shutdown_image(image),
+        };
+        result.unwrap_or_else(|e| error_log!("Error while leaving device {}: {}", self.serial, e));
+    }
+
+    /// Spreads the `shutdown` image across the keys, as a wallpaper
+    fn show_shutdown_image(&self, image: &Wallpaper) -> Result<(), Error> {
+        let path = self
+            .icon_paths
+            .read()
+            .unwrap()
+            .resolve(image.image())
+            .ok_or_else(|| Error::ImageNotFound(image.image().to_string()))?;
+        let path = path.to_string_lossy();
+        let (rows, cols) = self.device.button_layout();
+        let (width, height) = self.device.button_image_size();
+        let grid = KeyGrid::new(
+            rows as u32,
+            cols as u32,
+            width as u32,
+            height as u32,
+            image.gap(),
+        );
+        for key in 1..=self.device.button_count() {
+            let slice = wallpaper::slice(&path, &grid, key).map_err(|source| Error::Image {
+                path: path.to_string(),
+                source,
+            })?;
+            self.device
+                .set_button_image(key - 1, DynamicImage::ImageRgba8(slice))
+                .map_err(|e| self.device_error(e))?;
+        }
+        self.device.flush().map_err(|e| self.device_error(e))
+    }
+
+    /// `source` as the error of this device
+    fn device_error(&self, source: DeviceError) -> Error {
+        Error::Device {
+            serial: self.serial.clone(),
+            source,
+        }
+    }
+
     /// Reload configuration without reinitializing the device
     pub fn reload(
         &mut self,
         pages: Arc<Pages>,
         colors: Arc<Option<IndexMap<String, String>>>,
+        themes: Arc<Option<IndexMap<String, Theme>>>,
         button_templates: Arc<Option<IndexMap<String, Button>>>,
         macros: Arc<Option<IndexMap<String, crate::pages::Macro>>>,
         services_config: Arc<Option<IndexMap<String, ServiceConfig>>>,
+        on_error: Arc<Option<OnError>>,
         services_state: ServicesState,
         services_active: Arc<AtomicBool>,
-        brightness: u8,
+        brightness: Option<u8>,
         background_image: Option<String>,
     ) {
         verbose_log!("Reloading configuration for device {}", self.serial);
 
         // Get current page name before updating pages reference
         let current_page_name = self.get_current_page_name();
+        // Kept to find the keys the reload changes
+        let old_pages = self.pages.clone();
+        let old_templates = self.button_templates.clone();
+        let mut redraw_all = false;
+
+        self.max_fps.store(
+            pages.max_fps.unwrap_or(DEFAULT_MAX_FPS),
+            std::sync::atomic::Ordering::Relaxed,
+        );
+        self.read_tuning.update(pages.input_polling.as_ref());
 
         // Update all Arc references
-        self.pages = pages;
+        let pages = resolve_grid_keys(pages, self.device.as_ref());
+        self.pages = generate_pages(pages, self.device.as_ref());
+        // Settings of the page group, such as the press effect, change every key
+        if !config_diff::same_group_settings(&old_pages, &self.pages) {
+            redraw_all = true;
+        }
+        // Theme fonts and colors are not part of the per-key cache, so redraw on changes
+        if self.themes != themes || self.colors != colors {
+            self.invalidate_render_caches();
+            redraw_all = true;
+        }
         self.colors = colors;
+        self.themes = themes;
         self.button_templates = button_templates;
         self.macros = macros;
         self.services_config = services_config;
+        self.on_error = on_error;
         self.services_state = services_state;
         self.services_active = services_active;
 
-        // Update brightness
-        self.device.set_brightness(brightness).unwrap_or_else(|e| {
-            error_log!("Error setting brightness: {}", e);
-        });
+        // Update brightness, when the configured one changed
+        if let Some(brightness) = brightness.filter(|_| self.device.has_screen()) {
+            self.brightness.store(brightness, Ordering::Relaxed);
+            dbus_state::update(&self.serial, |status| status.brightness = brightness);
+            self.device.set_brightness(brightness).unwrap_or_else(|e| {
+                error_log!("Error setting brightness: {}", e);
+            });
+        }
 
         // Handle background image changes
-        let background_changed = self.background_image != background_image;
+        let background_changed =
+            self.device.has_screen() && self.background_image != background_image;
         if background_changed {
             if let Some(ref bg_path) = background_image {
                 // Background added or changed: send new BGPIC
-                match image::open(bg_path) {
+                match image_formats::open(bg_path) {
                     Ok(img) => {
                         self.device.set_background_image(img).unwrap_or_else(|e| {
                             error_log!("Failed to set background image '{}': {}", bg_path, e);
@@ -354,22 +1022,29 @@ impl PagedDevice {
             self.background_image = background_image;
 
             // Invalidate button caches: background change affects what's visible behind buttons
-            let button_count = self.device.button_count() as usize;
-            *self.button_images.borrow_mut() = vec![String::new(); button_count];
-            *self.button_backgrounds.borrow_mut() = vec![String::new(); button_count];
-            *self.button_canvases.borrow_mut() = vec![None; button_count];
+            self.invalidate_render_caches();
+            redraw_all = true;
         }
 
-        // Check if current page still exists in new configuration
-        let page_exists = if let Some(ref page_name) = current_page_name {
-            self.pages.pages.contains_key(page_name)
-        } else {
-            false
-        };
+        // A successful reload replaces the error page of an earlier failed one
+        if self.error_page_shown.swap(false, Ordering::Relaxed) {
+            self.invalidate_render_caches();
+            redraw_all = true;
+        }
 
-        if !page_exists {
+        // The current page stays when it still exists, wherever it moved to
+        let page = current_page_name
+            .as_ref()
+            .and_then(|name| self.pages.pages.get_index_of(name));
+        if let Some(page) = page {
+            *self
+                .current_page_ref
+                .write()
+                .unwrap_or_else(PoisonError::into_inner) = page;
+        } else {
             // Current page doesn't exist anymore, go to default page
             verbose_log!("Current page no longer exists, switching to default page");
+            redraw_all = true;
 
             // Try main page first, then first page (same logic as in new())
             let default_page_name = match &self.pages.main_page {
@@ -379,165 +1054,1836 @@ impl PagedDevice {
 
             if let Some(page_name) = default_page_name {
                 if let Some(page_index) = self.pages.pages.get_index_of(&page_name) {
-                    *self.current_page_ref.borrow_mut() = page_index;
+                    self.add_dwell_time();
+                    *self
+                        .current_page_ref
+                        .write()
+                        .unwrap_or_else(PoisonError::into_inner) = page_index;
                 }
             }
         }
 
-        // Re-render the current page with new configuration
-        self.refresh_page();
-    }
-
-    /// Check if there are pending actions waiting for a specific event.
-    /// If event type matches, resume action execution. Returns true if event was consumed.
-    pub fn check_pending_event(&self, event_type: &WaitEventType) -> bool {
-        // Take the pending actions if any exist
-        let pending = { self.pending_actions.borrow_mut().take() }; // Borrow ends here
-
-        if let Some(pending) = pending {
-            // Check timeout
-            if pending.last_modified.elapsed() > pending.timeout {
-                verbose_log!(
-                    "Pending action queue timed out for event '{}'",
-                    pending.event_type.as_str()
-                );
-                return false;
-            }
-
-            // Check if event type matches
-            if &pending.event_type != event_type {
-                // Different event type, put queue back
-                *self.pending_actions.borrow_mut() = Some(pending);
-                return false;
+        // Redraw what the new configuration changed on the current page
+        let change = if redraw_all {
+            PageChange::Page
+        } else {
+            let name = current_page_name.unwrap_or_default();
+            config_diff::page_change(
+                old_pages.pages.get(&name),
+                self.pages.pages.get(&name),
+                &old_templates,
+                &self.button_templates,
+                self.device.button_count(),
+            )
+        };
+        match change {
+            PageChange::Unchanged => verbose_log!("[{}] Current page unchanged", self.serial),
+            PageChange::Keys(keys) => {
+                verbose_log!("[{}] Redrawing changed keys {:?}", self.serial, keys);
+                self.redraw_keys(&keys);
             }
+            PageChange::Page => self.refresh_page(),
+        }
+    }
 
-            // Event type matches, resume actions
-            verbose_log!(
-                "WaitFor condition met for event '{}', resuming actions",
-                event_type.as_str()
-            );
-            if let Err(e) = self.execute_actions(pending.actions) {
-                error_log!("{}", e);
-            }
-            return true;
+    /// Replaces the pages of the device, e.g. after a change to a button or new buttons
+    /// from a provider, keeping everything else. The page shown stays when it still
+    /// exists.
+    pub fn replace_pages(&mut self, pages: Arc<Pages>) {
+        let current_page_name = self.get_current_page_name();
+        let pages = resolve_grid_keys(pages, self.device.as_ref());
+        self.pages = generate_pages(pages, self.device.as_ref());
+        let page = current_page_name
+            .and_then(|name| self.pages.pages.get_index_of(&name))
+            .or_else(|| {
+                self.pages
+                    .main_page
+                    .as_ref()
+                    .and_then(|name| self.pages.pages.get_index_of(name))
+            })
+            .unwrap_or(0);
+        if page
+            != *self
+                .current_page_ref
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+        {
+            self.add_dwell_time();
+            *self
+                .current_page_ref
+                .write()
+                .unwrap_or_else(PoisonError::into_inner) = page;
         }
-        false
+        self.refresh_page();
     }
 
-    /// Cancels any pending action queue.
-    /// This is called when user interacts with the device (button press, encoder twist, etc.)
-    /// to clear any actions that were waiting for events. Provides a central location
-    /// for future conditional logic if needed.
-    fn cancel_pending_actions(&self) {
-        if let Some(pending) = self.pending_actions.borrow_mut().take() {
-            verbose_log!(
-                "Canceling pending actions that were waiting for event '{}'",
-                pending.event_type.as_str()
-            );
+    /// Covers the keys with the built-in error page describing `error`. The page stays
+    /// until a key is pressed (which restores the current page) or the next reload.
+    pub fn show_error_page(&self, error: &str) {
+        if !self.device.has_screen() {
+            return;
         }
+        self.cancel_pending_actions();
+        self.text_echo
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        self.confirmation
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        self.test_pattern_shown.store(false, Ordering::Relaxed);
+        self.diagnostics
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        self.error_page_shown.store(true, Ordering::Relaxed);
+        status_page::show_error(self.device.as_ref(), error);
     }
 
-    /// Returns true if the button has actions configured on the current page
-    fn button_has_actions(&self, button_id: u8) -> bool {
-        let current_page = *self.current_page_ref.borrow();
-        self.find_button(current_page, button_id)
-            .and_then(|b| b.actions.as_ref())
-            .is_some_and(|a| !a.is_empty())
+    /// The error page, a text echo, a confirmation, the test pattern or the diagnostic
+    /// page is drawn instead of the current page
+    fn keys_covered(&self) -> bool {
+        self.error_page_shown.load(Ordering::Relaxed)
+            || self
+                .text_echo
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .is_some()
+            || self
+                .confirmation
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .is_some()
+            || self.test_pattern_shown.load(Ordering::Relaxed)
+            || self
+                .diagnostics
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .is_some()
     }
 
-    pub fn button_down(&self, button_id: u8) {
-        if !self.device.supports_button_press_feedback() {
-            return;
-        }
-        if !self.button_has_actions(button_id) {
-            return;
-        }
-        self.button_pressed.borrow_mut()[button_id as usize - 1] = true;
-        self.invalidate_and_refresh_button(button_id)
-            .unwrap_or_else(|e| error_log!("Error refreshing pressed button: {}", e));
+    /// Removes the error page and redraws the current page
+    fn dismiss_error_page(&self) {
+        self.error_page_shown.store(false, Ordering::Relaxed);
+        self.invalidate_render_caches();
+        self.refresh_page();
+        // Catch up with focus changes that were held back while the error page was shown
+        let class = {
+            self.current_class
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        };
+        let title = {
+            self.current_title
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        };
+        self.apply_focus_change(&class, &title, false);
     }
 
-    pub fn button_up(&self, button_id: u8) {
-        if !self.button_has_actions(button_id) {
-            return;
-        }
-        if self.device.supports_button_press_feedback() {
-            self.button_pressed.borrow_mut()[button_id as usize - 1] = false;
-            self.invalidate_and_refresh_button(button_id)
-                .unwrap_or_else(|e| error_log!("Error refreshing released button: {}", e));
-        }
+    /// Whether a `text` action shows its text on the keys before typing it
+    fn should_echo(&self, text: &str, echo: Option<bool>) -> bool {
+        self.device.has_screen()
+            && !self.error_page_shown.load(Ordering::Relaxed)
+            && echo.unwrap_or_else(|| text.chars().count() > ECHO_MIN_CHARS)
+    }
 
-        self.cancel_pending_actions();
-        let current_page = { self.current_page_ref.borrow().clone() };
-        if let Some(button) = self.find_button(current_page, button_id) {
-            if let Some(actions) = &button.actions {
-                if let Err(e) = self.execute_actions(actions.clone()) {
-                    error_log!("{}", e);
-                }
+    /// Covers the keys with the echo of `text`; it is typed, followed by `remaining`,
+    /// once the echo was shown, unless a key press cancels it first
+    fn start_echo(&self, text: String, target: Option<FocusTarget>, remaining: Vec<Action>) {
+        let (_, columns) = self.device.button_layout();
+        let frames = status_page::echo_frames(&text, self.device.button_count() as usize, columns);
+        let id = self.echo_counter.fetch_add(1, Ordering::Relaxed) + 1;
+        *self
+            .text_echo
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(TextEcho {
+            id,
+            text,
+            frames,
+            next: 0,
+            target,
+            remaining,
+        });
+        self.echo_step(id);
+    }
+
+    /// Shows the next frame of the text echo or, after the last one, types its text
+    pub fn echo_step(&self, echo: u64) {
+        let finished = {
+            let mut state = self
+                .text_echo
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            let Some(current) = state.as_mut().filter(|current| current.id == echo) else {
+                return;
+            };
+            if let Some(frame) = current.frames.get(current.next) {
+                current.next += 1;
+                let delay = if current.next < current.frames.len() {
+                    ECHO_SCROLL_STEP
+                } else {
+                    ECHO_HOLD
+                };
+                status_page::show_echo(self.device.as_ref(), frame);
+                self.time_manager
+                    .schedule_echo_step(self.serial.clone(), echo, delay);
+                return;
             }
+            state.take().unwrap()
+        };
+        self.invalidate_render_caches();
+        self.refresh_page();
+        let typed = match &finished.target {
+            Some(target) => self.send_to_window(target, || send_string(&finished.text)),
+            None => send_string(&finished.text),
+        };
+        let result =
+            typed.and_then(|_| self.execute_recorded(finished.remaining, "text echo", None));
+        if let Err(e) = result {
+            self.report_action_error(&e, None, None);
         }
     }
 
-    /// Recursively substitutes ${param} placeholders in a YAML Value with provided parameters.
-    fn substitute_in_value(value: &mut serde_yaml_ng::Value, params: &HashMap<String, String>) {
-        match value {
-            serde_yaml_ng::Value::String(s) => {
-                // Replace all ${param} patterns in the string
-                for (key, val) in params {
-                    let pattern = format!("${{{}}}", key);
-                    *s = s.replace(&pattern, val);
-                }
-            }
-            serde_yaml_ng::Value::Sequence(seq) => {
-                for item in seq {
-                    Self::substitute_in_value(item, params);
-                }
-            }
-            serde_yaml_ng::Value::Mapping(map) => {
-                for (_, v) in map {
-                    Self::substitute_in_value(v, params);
-                }
+    /// Gives the window of `target` the focus while `send` types into it, then gives the
+    /// focus back to the window that had it. The pages do not follow the focus meanwhile.
+    fn send_to_window(
+        &self,
+        target: &FocusTarget,
+        send: impl FnOnce() -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let query = WindowQuery::from_target(target, 0)?;
+        let class = {
+            self.current_class
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        };
+        let title = {
+            self.current_title
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        };
+        let previous = (!class.is_empty()).then(|| WindowQuery {
+            class: class.to_lowercase(),
+            title: title.to_lowercase(),
+            exact: true,
+            ..Default::default()
+        });
+        *self
+            .targeted_until
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) =
+            Some(Instant::now() + TARGET_FOCUS_SETTLE + TARGET_FOCUS_GRACE);
+        let sent = set_focus(&query).map_err(Error::from).and_then(|()| {
+            std::thread::sleep(TARGET_FOCUS_SETTLE);
+            send()
+        });
+        if let Some(previous) = previous {
+            match set_focus(&previous) {
+                Ok(()) => verbose_log!("Sent to {}, focus back", query.describe()),
+                Err(e) => warn_log!(
+                    "Focus not given back after sending to {}: {}",
+                    query.describe(),
+                    e
+                ),
             }
-            _ => {}
         }
+        *self
+            .targeted_until
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Instant::now() + TARGET_FOCUS_GRACE);
+        sent
     }
 
-    /// Expands a single macro call into a sequence of actions.
-    /// This performs parameter substitution and parses the macro's actions.
-    fn expand_single_macro(&self, macro_call: MacroCall) -> Result<Vec<Action>, String> {
-        // Extract macro name and provided parameters
-        let macro_name = macro_call.name;
-        let provided_params = macro_call.params;
-
-        // Find the macro definition
-        let macros = self
-            .macros
-            .as_ref()
-            .as_ref()
-            .ok_or_else(|| format!("No macros defined"))?;
-        let macro_def = macros
-            .get(&macro_name)
-            .ok_or_else(|| format!("Macro '{}' not found", macro_name))?;
-
-        // Merge provided params with default params (provided params override defaults)
-        let mut final_params = macro_def.params.clone().unwrap_or_default();
-        for (key, value) in provided_params {
-            final_params.insert(key, value);
+    /// Removes the text echo without typing its text; false if none was shown
+    fn cancel_echo(&self) -> bool {
+        if self
+            .text_echo
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+            .is_none()
+        {
+            return false;
         }
+        verbose_log!("[{}] Text echo cancelled", self.serial);
+        self.invalidate_render_caches();
+        self.refresh_page();
+        true
+    }
 
-        // Clone the macro's actions Value for substitution
-        let mut actions_value = macro_def.actions.clone();
-
-        // Substitute parameters in the YAML value
-        Self::substitute_in_value(&mut actions_value, &final_params);
-
-        // Parse the substituted YAML into Vec<Action>
-        let actions: Vec<Action> = serde_yaml_ng::from_value(actions_value).map_err(|e| {
-            format!(
-                "Failed to parse macro '{}' actions after parameter substitution: {}",
-                macro_name, e
-            )
-        })?;
+    /// Covers the keys with `question`, asking whether to run `confirm` or `cancel`.
+    /// Unanswered after `timeout`, it is cancelled.
+    fn start_confirmation(
+        &self,
+        question: &str,
+        confirm: Vec<Action>,
+        cancel: Vec<Action>,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        let (_, columns) = self.device.button_layout();
+        let keys = status_page::confirm_keys(self.device.button_count() as usize, columns);
+        let Some((cancel_key, confirm_key)) = keys.filter(|_| self.device.has_screen()) else {
+            return Err("confirm action needs a device with a screen and two keys".into());
+        };
+        let id = self.confirm_counter.fetch_add(1, Ordering::Relaxed) + 1;
+        *self
+            .confirmation
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Confirmation {
+            id,
+            confirm_key: confirm_key as u8 + 1,
+            cancel_key: cancel_key as u8 + 1,
+            confirm,
+            cancel,
+        });
+        status_page::show_confirm(self.device.as_ref(), question);
+        self.time_manager
+            .schedule_confirm_timeout(self.serial.clone(), id, timeout);
+        Ok(())
+    }
+
+    /// Answers the shown confirmation with the release of `button_id`: its confirm key
+    /// runs the confirmed actions, its cancel key the cancelled ones and other keys are
+    /// ignored. False if no confirmation was shown.
+    fn answer_confirmation(&self, button_id: u8) -> bool {
+        let answered = {
+            let mut state = self
+                .confirmation
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            let Some(current) = state.as_ref() else {
+                return false;
+            };
+            if button_id != current.confirm_key && button_id != current.cancel_key {
+                return true;
+            }
+            state.take().unwrap()
+        };
+        let confirmed = button_id == answered.confirm_key;
+        verbose_log!(
+            "[{}] Confirmation {}",
+            self.serial,
+            if confirmed { "confirmed" } else { "cancelled" }
+        );
+        self.finish_confirmation(if confirmed {
+            answered.confirm
+        } else {
+            answered.cancel
+        });
+        true
+    }
+
+    /// Cancels the confirmation `confirmation` if it is still unanswered
+    pub fn confirm_timeout(&self, confirmation: u64) {
+        let expired = {
+            let mut state = self
+                .confirmation
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            if state.as_ref().map(|current| current.id) != Some(confirmation) {
+                return;
+            }
+            state.take().unwrap()
+        };
+        verbose_log!("[{}] Confirmation timed out", self.serial);
+        self.finish_confirmation(expired.cancel);
+    }
+
+    /// Brings the current page back and runs the actions chosen by the answer
+    fn finish_confirmation(&self, actions: Vec<Action>) {
+        self.invalidate_render_caches();
+        self.refresh_page();
+        if actions.is_empty() {
+            return;
+        }
+        if let Err(e) = self.execute_recorded(actions, "confirmation", None) {
+            self.report_action_error(&e, None, None);
+        }
+    }
+
+    /// Checks a device after the system resumed: it must answer a firmware query, then
+    /// gets its brightness and the current page again, as devices sometimes come back
+    /// with blank or corrupted keys. An error means the device should be reconnected.
+    pub fn verify_after_resume(&self) -> Result<(), DeviceError> {
+        let firmware = self.device.firmware_version()?;
+        verbose_log!(
+            "[{}] Answers after resume, firmware {}",
+            self.serial,
+            firmware
+        );
+        if !self.device.has_screen() {
+            return Ok(());
+        }
+        self.device
+            .set_brightness(self.brightness.load(Ordering::Relaxed))?;
+        if !self.keys_covered() {
+            self.invalidate_render_caches();
+            self.refresh_page();
+        }
+        Ok(())
+    }
+
+    /// Covers the keys with the test pattern for a few seconds, unless the error page or
+    /// a text echo is shown
+    pub fn show_test_pattern(&self) {
+        if !self.device.has_screen()
+            || self.keys_covered() && !self.test_pattern_shown.load(Ordering::Relaxed)
+        {
+            return;
+        }
+        self.test_pattern_shown.store(true, Ordering::Relaxed);
+        status_page::show_test_pattern(self.device.as_ref());
+        self.time_manager
+            .schedule_test_pattern_end(self.serial.clone(), TEST_PATTERN_DURATION);
+    }
+
+    /// Removes the test pattern and redraws the current page; false if none was shown
+    pub fn end_test_pattern(&self) -> bool {
+        if !self.test_pattern_shown.swap(false, Ordering::Relaxed) {
+            return false;
+        }
+        self.invalidate_render_caches();
+        self.refresh_page();
+        true
+    }
+
+    /// Covers the keys with the diagnostic page until a key is pressed, unless another
+    /// built-in page is shown. The firmware is read once; the other values follow
+    /// [`Self::update_diagnostics`].
+    pub fn show_diagnostics(&self, tick_time: f64, config_generation: u64) {
+        if !self.device.has_screen()
+            || self.keys_covered()
+                && self
+                    .diagnostics
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .is_none()
+        {
+            return;
+        }
+        let firmware = self.device.firmware_version().unwrap_or_else(|e| {
+            warn_log!("[{}] Failed to read firmware version: {}", self.serial, e);
+            String::new()
+        });
+        *self
+            .diagnostics
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Diagnostics {
+            serial: self.serial.clone(),
+            model: self.device.kind_name(),
+            firmware,
+            ..Default::default()
+        });
+        self.update_diagnostics(tick_time, config_generation);
+    }
+
+    /// Redraws the diagnostic page with the current page, focused window, tick time and
+    /// configuration generation, if it is shown
+    pub fn update_diagnostics(&self, tick_time: f64, config_generation: u64) {
+        let mut diagnostics = self
+            .diagnostics
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(diagnostics) = diagnostics.as_mut() else {
+            return;
+        };
+        diagnostics.page = self.get_current_page_name().unwrap_or_default();
+        diagnostics.focus_class = self
+            .current_class
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        diagnostics.tick_time = tick_time;
+        diagnostics.config_generation = config_generation;
+        diagnostics.input = self.read_tuning.describe();
+        status_page::show_diagnostics(self.device.as_ref(), diagnostics);
+    }
+
+    /// Removes the diagnostic page and redraws the current page; false if none was shown
+    fn end_diagnostics(&self) -> bool {
+        if self
+            .diagnostics
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+            .is_none()
+        {
+            return false;
+        }
+        self.invalidate_render_caches();
+        self.refresh_page();
+        true
+    }
+
+    /// The keys as last drawn, stitched in the layout of the device; cleared keys are black
+    pub fn screenshot(&self) -> RgbaImage {
+        let canvases = self
+            .button_canvases
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let key_size = canvases
+            .iter()
+            .flatten()
+            .next()
+            .map(|canvas| canvas.dimensions())
+            .unwrap_or_else(|| {
+                let (width, height) = self.device.button_image_size();
+                (width as u32, height as u32)
+            });
+        let keys: Vec<Option<DynamicImage>> = canvases
+            .iter()
+            .map(|canvas| canvas.clone().map(DynamicImage::ImageRgba8))
+            .collect();
+        let (rows, cols) = match self.device.button_layout() {
+            (rows, cols) if rows > 0 && cols > 0 => (rows, cols),
+            _ => (1, keys.len()),
+        };
+        mirror::stitch_keys(rows, cols, key_size, &keys)
+    }
+
+    /// Redraws every page [`bench::ROUNDS`] times, sending each one at once, and reports
+    /// how long drawing, flushing and whole page switches took. The current page is shown
+    /// again afterwards.
+    pub fn bench(&self) -> BenchReport {
+        let model = self.device.kind_name();
+        let page_count = self.pages.pages.len();
+        if !self.device.has_screen() || self.keys_covered() {
+            return BenchReport::new(self.serial.clone(), model, page_count, vec![], &[], &[]);
+        }
+        let max_fps = self.max_fps.swap(0, std::sync::atomic::Ordering::Relaxed);
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut renders = vec![];
+        let mut switches = vec![];
+        let mut flushes = vec![];
+        bench::set_recording(true);
+        for _ in 0..bench::ROUNDS {
+            for (index, name) in self.pages.pages.keys().enumerate() {
+                *self
+                    .current_page_ref
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner) = index;
+                self.invalidate_render_caches();
+                let started = Instant::now();
+                self.refresh_page();
+                switches.push(started.elapsed());
+                for (probe, time) in bench::take_samples() {
+                    match probe {
+                        Probe::Render(button) => renders.push((name.clone(), button, time)),
+                        Probe::Flush => flushes.push(time),
+                    }
+                }
+            }
+        }
+        bench::set_recording(false);
+        *self
+            .current_page_ref
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = current_page;
+        self.invalidate_render_caches();
+        self.refresh_page();
+        self.max_fps
+            .store(max_fps, std::sync::atomic::Ordering::Relaxed);
+        let sn = self.serial.clone();
+        BenchReport::new(sn, model, page_count, renders, &switches, &flushes)
+    }
+
+    /// Enables, changes or removes the night mode filter, redrawing the keys if it changed
+    pub fn set_night_filter(&self, filter: Option<NightFilter>) {
+        if *self
+            .night_filter
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            == filter
+        {
+            return;
+        }
+        verbose_log!(
+            "[{}] Night mode {}",
+            self.serial,
+            if filter.is_some() { "on" } else { "off" }
+        );
+        *self
+            .night_filter
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = filter;
+        if !self.keys_covered() {
+            self.invalidate_render_caches();
+            self.refresh_page();
+        }
+    }
+
+    /// Selects the global theme and redraws the keys if it changed. Pages that set their
+    /// own `theme` are not affected.
+    pub fn set_theme(&self, theme: Option<String>) {
+        if *self
+            .active_theme
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            == theme
+        {
+            return;
+        }
+        verbose_log!("[{}] Theme set to {}", self.serial, theme.as_deref().unwrap_or("none"));
+        *self
+            .active_theme
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = theme;
+        if !self.keys_covered() {
+            self.invalidate_render_caches();
+            self.refresh_page();
+        }
+    }
+
+    /// Name of the theme in effect: the current page's own theme, else the global one
+    fn current_theme_name(&self) -> Option<String> {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.find_page(current_page)
+            .and_then(|page| page.theme.clone())
+            .or_else(|| {
+                self.active_theme
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone()
+            })
+    }
+
+    fn current_theme(&self) -> Option<&Theme> {
+        let name = self.current_theme_name()?;
+        self.themes.as_ref().as_ref()?.get(&name)
+    }
+
+    /// Resolves a color, letting the current theme's named colors shadow the global ones.
+    /// Theme color values may themselves name a global color.
+    fn resolve_color(&self, color: &str) -> Result<(u8, u8, u8), Error> {
+        button_look::resolve_color(color, self.current_theme(), &self.colors)
+            .map_err(|_| Error::InvalidColor(color.to_string()))
+    }
+
+    /// Applies the `accessibility` options, redrawing the keys for high-contrast mode
+    pub fn set_accessibility(&self, accessibility: Option<Accessibility>) {
+        {
+            let mut current = self
+                .accessibility
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            if *current == accessibility {
+                return;
+            }
+            *current = accessibility;
+        }
+        if !self.keys_covered() {
+            self.invalidate_render_caches();
+            self.refresh_page();
+        }
+    }
+
+    /// Looks up the icons of the keys in new places, redrawing the page when they moved
+    pub fn set_icon_paths(&self, icon_paths: Arc<IconPaths>) {
+        {
+            let mut current = self
+                .icon_paths
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            if **current == *icon_paths {
+                return;
+            }
+            *current = icon_paths;
+        }
+        if !self.keys_covered() {
+            self.invalidate_render_caches();
+            self.refresh_page();
+        }
+    }
+
+    /// Replaces the translations of `${i18n:key}`, redrawing the keys if they changed
+    pub fn set_strings(&self, strings: Translations) {
+        {
+            let mut current = self.strings.write().unwrap_or_else(PoisonError::into_inner);
+            if *current == strings {
+                return;
+            }
+            *current = strings;
+        }
+        if !self.keys_covered() {
+            self.invalidate_render_caches();
+            self.refresh_page();
+        }
+    }
+
+    pub fn set_press_sound(&self, press_sound: Option<PressSound>) {
+        *self
+            .press_sound
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = press_sound;
+    }
+
+    /// Plays the sound of a pressed button: its own `sound`, or else the `press_sound`
+    fn play_press_sound(&self, button_id: u8) {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let own = self
+            .find_button(current_page, button_id)
+            .and_then(|button| button.sound.clone());
+        let press_sound = self
+            .press_sound
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let sample = match (own, &press_sound) {
+            (Some(sound), _) => sound,
+            (None, Some(press_sound)) => press_sound
+                .sample
+                .clone()
+                .unwrap_or_else(|| audio::CLICK_SAMPLE.to_string()),
+            (None, None) => return,
+        };
+        if sample.trim().eq_ignore_ascii_case("none") {
+            return;
+        }
+        let volume = press_sound
+            .and_then(|press_sound| press_sound.volume)
+            .unwrap_or(PRESS_SOUND_VOLUME);
+        audio::play_sample(&sample, volume).unwrap_or_else(|e| warn_log!("{}", e));
+    }
+
+    /// Smallest label size while high-contrast mode is on
+    fn high_contrast_font_size(&self) -> Option<f32> {
+        let accessibility = self
+            .accessibility
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let accessibility = accessibility.as_ref().filter(|a| a.high_contrast)?;
+        Some(
+            accessibility
+                .min_font_size
+                .unwrap_or(HIGH_CONTRAST_MIN_FONT_SIZE),
+        )
+    }
+
+    /// Speaks a page name or key label when announcements are on
+    fn announce(&self, text: &str) {
+        let announce = self
+            .accessibility
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|a| a.announce);
+        if announce && !text.trim().is_empty() {
+            speak(text).unwrap_or_else(|e| warn_log!("{}", e));
+        }
+    }
+
+    /// Label of a button of the current page as shown, on one line
+    fn button_label(&self, button_id: u8) -> Option<String> {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let text = match self.find_button(current_page, button_id)?.text.as_ref()? {
+            TextConfig::Simple(value) | TextConfig::Detailed { value, .. } => value,
+        };
+        let text: String = process_escape_sequences(&self.substitute_dynamic_params(text))
+            .into_iter()
+            .collect();
+        Some(text.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    /// Forgets what is drawn on every key, so the next refresh redraws all of them
+    fn invalidate_render_caches(&self) {
+        let button_count = self.device.button_count() as usize;
+        *self
+            .button_images
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = vec![String::new(); button_count];
+        *self
+            .button_backgrounds
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = vec![String::new(); button_count];
+        *self
+            .button_canvases
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = vec![None; button_count];
+    }
+
+    /// Check if there are pending actions waiting for a specific event.
+    /// If event type matches, resume action execution. Returns true if event was consumed.
+    /// `focused` is the window a focus event reports; other events leave the focus as
+    /// the device last saw it.
+    pub fn check_pending_event(
+        &self,
+        event_type: &WaitEventType,
+        focused: Option<(&str, &str)>,
+    ) -> bool {
+        // Take the pending actions if any exist
+        let pending = {
+            self.pending_actions
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take()
+        }; // Lock released here
+
+        if let Some(mut pending) = pending {
+            let elapsed = pending.last_modified.elapsed();
+            pending.conditions.observe(event_type, elapsed);
+            match focused {
+                Some((class, title)) => pending.conditions.observe_window(class, title, elapsed),
+                None => {
+                    let class = {
+                        self.current_class
+                            .read()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .clone()
+                    };
+                    let title = {
+                        self.current_title
+                            .read()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .clone()
+                    };
+                    pending.conditions.observe_window(&class, &title, elapsed);
+                }
+            }
+            match pending.conditions.outcome(elapsed) {
+                Some(true) => {}
+                Some(false) => {
+                    verbose_log!(
+                        "Pending action queue timed out for event '{}'",
+                        pending.conditions.describe()
+                    );
+                    return false;
+                }
+                None => {
+                    // Still waiting, put queue back
+                    *self
+                        .pending_actions
+                        .write()
+                        .unwrap_or_else(PoisonError::into_inner) = Some(pending);
+                    return false;
+                }
+            }
+
+            // Conditions met, resume actions
+            verbose_log!(
+                "WaitFor condition met for event '{}', resuming actions",
+                event_type.as_str()
+            );
+            let trigger = format!("{} resumed", event_type.as_str());
+            if let Err(e) = self.execute_recorded(pending.actions, &trigger, None) {
+                self.report_action_error(&e, None, None);
+            }
+            return true;
+        }
+        false
+    }
+
+    /// Cancels any pending action queue.
+    /// This is called when user interacts with the device (button press, encoder twist, etc.)
+    /// to clear any actions that were waiting for events. Provides a central location
+    /// for future conditional logic if needed.
+    fn cancel_pending_actions(&self) {
+        if let Some(pending) = self
+            .pending_actions
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            verbose_log!(
+                "Canceling pending actions that were waiting for event '{}'",
+                pending.conditions.describe()
+            );
+        }
+    }
+
+    /// Returns true if the button is enabled and has actions configured on the current page
+    fn button_has_actions(&self, button_id: u8) -> bool {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.find_button(current_page, button_id)
+            .filter(|b| self.button_enabled(b))
+            .is_some_and(|b| {
+                b.timer.is_some()
+                    || b.builtin.is_some()
+                    || b.actions.as_ref().is_some_and(|a| !a.is_empty())
+            })
+    }
+
+    /// Evaluates the `enabled` state of a button; buttons without one are enabled
+    fn button_enabled(&self, button: &Button) -> bool {
+        match &button.enabled {
+            None => true,
+            Some(Enabled::Fixed(enabled)) => *enabled,
+            Some(Enabled::Condition(condition)) => self.condition_holds(condition),
+        }
+    }
+
+    /// Whether a condition holds with the dynamic parameters of this device. A condition
+    /// that is not valid does not hold.
+    fn condition_holds(&self, text: &str) -> bool {
+        condition::holds(text, &|value| self.substitute_dynamic_params(value)).unwrap_or_else(|e| {
+            warn_log!("[{}] Invalid condition '{}': {}", self.serial, text, e);
+            false
+        })
+    }
+
+    /// The effects to render a button with: its own, dimmed and grayed out when disabled
+    fn button_effects(&self, button: &Button) -> Option<Effects> {
+        if self.button_enabled(button) {
+            return button.effects.clone();
+        }
+        let mut effects = button.effects.clone().unwrap_or_default();
+        effects.grayscale = Some(true);
+        effects.opacity = Some(effects.opacity.unwrap_or(1.0) * DISABLED_OPACITY);
+        Some(effects)
+    }
+
+    /// Key the user sees at a physical button (both from 1)
+    fn logical_button(&self, button_id: u8) -> u8 {
+        match button_id.checked_sub(1) {
+            Some(key) => self.key_map.to_logical(key) + 1,
+            None => button_id,
+        }
+    }
+
+    pub fn button_down(&self, button_id: u8) {
+        if button_id < 1 || button_id > self.device.button_count() {
+            return;
+        }
+        self.note_interaction();
+        let button_id = self.logical_button(button_id);
+        live_events::publish(LiveEvent::ButtonDown {
+            sn: self.serial.clone(),
+            page: self.get_current_page_name(),
+            button: button_id,
+        });
+        if self.asleep.load(Ordering::Relaxed) {
+            self.wake();
+            return;
+        }
+        if self.keys_covered() {
+            return;
+        }
+        if self.hold_modifier(button_id) || self.run_chord(button_id) {
+            return;
+        }
+        if !self.button_has_actions(button_id) || self.cooldown_remaining(button_id).is_some() {
+            return;
+        }
+        self.play_press_sound(button_id);
+        if self.device.has_screen() && self.device.supports_button_press_feedback() {
+            self.button_pressed
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)[button_id as usize - 1] = true;
+            // The feedback goes out before the images of keys refreshing meanwhile
+            self.device.prioritize_button(button_id - 1);
+            self.invalidate_and_refresh_button(button_id)
+                .unwrap_or_else(|e| error_log!("Error refreshing pressed button: {}", e));
+        }
+        self.start_repeat(button_id);
+        self.start_timer_hold(button_id);
+    }
+
+    pub fn button_up(&self, button_id: u8) {
+        if button_id < 1 || button_id > self.device.button_count() {
+            return;
+        }
+        let button_id = self.logical_button(button_id);
+        live_events::publish(LiveEvent::ButtonUp {
+            sn: self.serial.clone(),
+            page: self.get_current_page_name(),
+            button: button_id,
+        });
+        let index = button_id as usize - 1;
+        if let Some(held_since) = self
+            .held_since
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(index)
+        {
+            *held_since = None;
+        }
+        let chord_key = self
+            .chord_keys
+            .write()
+            .unwrap()
+            .get_mut(index)
+            .is_some_and(std::mem::take);
+        if self.asleep.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        if self.release_modifier(button_id) {
+            return;
+        }
+        if self.error_page_shown.load(Ordering::Relaxed) {
+            self.dismiss_error_page();
+            return;
+        }
+        if self.answer_confirmation(button_id)
+            || self.cancel_echo()
+            || self.end_test_pattern()
+            || self.end_diagnostics()
+        {
+            return;
+        }
+        // Releasing stops a repeating button, which already ran its actions when pressed
+        let repeating = self
+            .button_repeats
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)[button_id as usize - 1]
+            .take()
+            .is_some();
+        let timer_hold = self
+            .timer_holds
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)[button_id as usize - 1]
+            .take();
+        if chord_key {
+            // The chord ran instead of the actions of its keys
+            if std::mem::take(
+                &mut self
+                    .button_pressed
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)[index],
+            ) {
+                self.invalidate_and_refresh_button(button_id)
+                    .unwrap_or_else(|e| error_log!("Error refreshing released button: {}", e));
+            }
+            return;
+        }
+        if !self.button_has_actions(button_id) {
+            return;
+        }
+        if !repeating && self.cooldown_remaining(button_id).is_some() {
+            verbose_log!("Ignoring press of button {} during its cooldown", button_id);
+            return;
+        }
+        self.start_cooldown(button_id);
+        if self.device.has_screen() && self.device.supports_button_press_feedback() {
+            self.button_pressed
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)[button_id as usize - 1] = false;
+            self.invalidate_and_refresh_button(button_id)
+                .unwrap_or_else(|e| error_log!("Error refreshing released button: {}", e));
+        }
+        self.count_press(button_id);
+
+        if repeating {
+            return;
+        }
+        match timer_hold {
+            // Held long enough to reset the timer instead
+            Some((_, true)) => return,
+            Some((_, false)) => self.press_timer(button_id),
+            None => {}
+        }
+
+        if let Some(label) = self.button_label(button_id) {
+            self.announce(&label);
+        }
+        self.cancel_pending_actions();
+        self.run_button_actions(button_id);
+    }
+
+    /// Shows the layer of a `modifier` button over the other keys of the page while the
+    /// button is held. Returns whether the button is a modifier.
+    fn hold_modifier(&self, button_id: u8) -> bool {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(layer) = self
+            .find_button(current_page, button_id)
+            .and_then(|button| button.modifier.clone())
+        else {
+            return false;
+        };
+        verbose_log!("[{}] Layer '{}' shown", self.serial, layer);
+        let keys = self.layer_keys(current_page, &layer);
+        *self
+            .active_layer
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some((current_page, layer, button_id));
+        self.redraw_keys(&keys);
+        true
+    }
+
+    /// Restores the keys of the page when the `modifier` button of the shown layer is
+    /// released. Returns whether it was.
+    fn release_modifier(&self, button_id: u8) -> bool {
+        let released = {
+            let mut active_layer = self
+                .active_layer
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            match active_layer.as_ref() {
+                Some((_, _, modifier)) if *modifier == button_id => active_layer.take(),
+                _ => None,
+            }
+        };
+        let Some((page, layer, _)) = released else {
+            return false;
+        };
+        verbose_log!("[{}] Layer '{}' hidden", self.serial, layer);
+        if page
+            == *self
+                .current_page_ref
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+        {
+            self.redraw_keys(&self.layer_keys(page, &layer));
+        }
+        true
+    }
+
+    /// The keys of the device a layer of a page has buttons for
+    fn layer_keys(&self, page_id: usize, layer: &str) -> Vec<u8> {
+        let button_count = self.device.button_count();
+        self.find_page(page_id)
+            .and_then(|page| page.layers.as_ref()?.get(layer))
+            .into_iter()
+            .flat_map(|buttons| buttons.keys())
+            .filter_map(|key| key.strip_prefix("button")?.parse().ok())
+            .filter(|key| (1..=button_count).contains(key))
+            .collect()
+    }
+
+    /// Notes the press of a button and runs the chord of the shown page it completes: one
+    /// whose keys are all held, pressed within its window. The keys of the chord then run
+    /// nothing when released, and stop repeating. Returns whether a chord ran.
+    fn run_chord(&self, button_id: u8) -> bool {
+        let now = Instant::now();
+        match self
+            .held_since
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(button_id as usize - 1)
+        {
+            Some(held_since) => *held_since = Some(now),
+            None => return false,
+        }
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(chords) = self
+            .find_page(current_page)
+            .and_then(|page| page.chords.as_ref())
+        else {
+            return false;
+        };
+        let chord = {
+            let held_since = self
+                .held_since
+                .read()
+                .unwrap_or_else(PoisonError::into_inner);
+            let pressed_in_window = |chord: &Chord, button: u8| {
+                held_since
+                    .get(button as usize - 1)
+                    .copied()
+                    .flatten()
+                    .is_some_and(|since| (now - since).as_secs_f32() <= chord.window)
+            };
+            chords.iter().find(|chord| {
+                chord.buttons().any(|button| button == button_id)
+                    && chord
+                        .buttons()
+                        .all(|button| pressed_in_window(chord, button))
+            })
+        };
+        let Some(chord) = chord else {
+            return false;
+        };
+
+        verbose_log!("[{}] Chord {} pressed", self.serial, chord.keys.join(" + "));
+        for button in chord.buttons() {
+            let index = button as usize - 1;
+            self.chord_keys
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)[index] = true;
+            self.held_since
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)[index] = None;
+            self.button_repeats
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)[index] = None;
+            self.timer_holds
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)[index] = None;
+        }
+        self.cancel_pending_actions();
+        if let Err(e) = self.execute_recorded(chord.actions.clone(), "chord", None) {
+            self.report_action_error(&e, None, None);
+        }
+        true
+    }
+
+    /// Executes the actions of a button on the current page, surfacing failures
+    fn run_button_actions(&self, button_id: u8) -> bool {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(button) = self.find_button(current_page, button_id) {
+            if let Some(builtin) = button.builtin {
+                self.run_button_builtin(builtin);
+            }
+            if let Some(actions) = &button.actions {
+                let result = self.execute_recorded(actions.clone(), "button", Some(button_id));
+                if let Err(e) = result {
+                    self.report_action_error(&e, Some(button_id), button.on_error.as_ref());
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Does what a `builtin` button does on its own
+    fn run_button_builtin(&self, builtin: ButtonBuiltin) {
+        verbose_log!("[{}] Built-in {:?}", self.serial, builtin);
+        match builtin {
+            ButtonBuiltin::NextPage => self.scroll_pages(1),
+            ButtonBuiltin::PrevPage => self.scroll_pages(-1),
+            ButtonBuiltin::MainPage => {
+                let main_page = self.main_page_index();
+                if let Some((name, _)) = main_page.and_then(|at| self.pages.pages.get_index(at)) {
+                    if let Err(e) = self.set_page(name, false) {
+                        error_log!("{}", e);
+                    }
+                }
+            }
+            ButtonBuiltin::Back => self.go_back(),
+            ButtonBuiltin::TogglePin => self.set_pin(Switch::Toggle),
+            ButtonBuiltin::BrightnessUp => self.step_brightness(1),
+            ButtonBuiltin::BrightnessDown => self.step_brightness(-1),
+            ButtonBuiltin::Sleep => {
+                if self.device.has_screen() {
+                    self.asleep.store(true, Ordering::Relaxed);
+                    self.device
+                        .set_brightness(0)
+                        .unwrap_or_else(|e| error_log!("Error setting brightness: {}", e));
+                }
+            }
+        }
+    }
+
+    /// Goes back to the page shown before the current one; nothing without one
+    fn go_back(&self) {
+        let Some(previous) = self
+            .page_history
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop()
+        else {
+            return;
+        };
+        if let Err(e) = self.set_page(&previous, false) {
+            error_log!("{}", e);
+            return;
+        }
+        // Going back is not a page to come back to
+        self.page_history
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+    }
+
+    /// Turns the screen on again after a `sleep` button. The key press that does it is
+    /// used up, its release included, see [`Self::button_up`].
+    fn wake(&self) {
+        let brightness = self.brightness.load(Ordering::Relaxed);
+        self.device
+            .set_brightness(brightness)
+            .unwrap_or_else(|e| error_log!("Error setting brightness: {}", e));
+    }
+
+    /// Runs actions not bound to a button, such as those of a global hotkey
+    pub fn run_actions(&self, actions: Vec<Action>, trigger: &str) {
+        self.cancel_pending_actions();
+        if let Err(e) = self.execute_recorded(actions, trigger, None) {
+            self.report_action_error(&e, None, None);
+        }
+    }
+
+    /// Runs the `on_start` actions of the device configuration, then redraws the page so
+    /// its keys show what the actions synced instead of waiting for the next tick
+    pub fn run_start_actions(&self) {
+        if let Some(actions) = &self.pages.on_start {
+            verbose_log!("Running start actions of device {}", self.serial);
+            self.run_actions(actions.clone(), "start");
+            self.refresh_page();
+            self.render_encoder_widgets();
+        }
+    }
+
+    /// Runs the `on_connect` or `on_disconnect` actions of the device configuration
+    pub fn run_hotplug_actions(&self, connected: bool) {
+        let (actions, trigger) = if connected {
+            (&self.pages.on_connect, "connect")
+        } else {
+            (&self.pages.on_disconnect, "disconnect")
+        };
+        if let Some(actions) = actions {
+            verbose_log!("Running {} actions of device {}", trigger, self.serial);
+            self.run_actions(actions.clone(), trigger);
+        }
+    }
+
+    /// Executes an action sequence and records the run in the action history
+    fn execute_recorded(
+        &self,
+        actions: Vec<Action>,
+        trigger: &str,
+        button_id: Option<u8>,
+    ) -> Result<(), Error> {
+        let page = self.get_current_page_name();
+        let started = Instant::now();
+        let result = self.execute_actions(actions.clone());
+        history::record(&HistoryEntry::new(
+            &self.serial,
+            page,
+            trigger,
+            button_id,
+            &actions,
+            &result,
+            started.elapsed(),
+        ));
+        result
+    }
+
+    /// Adds a press of a button on the current page to the usage statistics
+    fn count_press(&self, button_id: u8) {
+        let Some(page) = self.get_current_page_name() else {
+            return;
+        };
+        let button = format!("button{}", button_id);
+        self.usage_stats
+            .write()
+            .unwrap()
+            .record_press(&self.serial, &page, &button);
+        if self.heatmap.load(Ordering::Relaxed) {
+            self.refresh_page();
+        } else {
+            self.refresh_buttons_using("stats");
+        }
+    }
+
+    /// Adds the time the current page was shown since the last call to the statistics
+    fn add_dwell_time(&self) {
+        let shown = std::mem::replace(
+            &mut *self
+                .page_since
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            Instant::now(),
+        )
+        .elapsed();
+        if let Some(page) = self.get_current_page_name() {
+            self.usage_stats
+                .write()
+                .unwrap()
+                .add_dwell(&self.serial, &page, shown);
+        }
+    }
+
+    /// Turns the usage heatmap on or off, redrawing the keys when it changes
+    fn set_heatmap(&self, switch: Switch) {
+        let enabled = match switch {
+            Switch::On => true,
+            Switch::Off => false,
+            Switch::Toggle => !self.heatmap.load(Ordering::Relaxed),
+        };
+        if self.heatmap.swap(enabled, Ordering::Relaxed) != enabled {
+            verbose_log!(
+                "[{}] Heatmap {}",
+                self.serial,
+                if enabled { "on" } else { "off" }
+            );
+            self.refresh_page();
+        }
+    }
+
+    /// Pins or unpins the shown page. Once unpinned, the page follows the focus again.
+    fn set_pin(&self, switch: Switch) {
+        let pinned = match switch {
+            Switch::On => true,
+            Switch::Off => false,
+            Switch::Toggle => !self.pinned.load(Ordering::Relaxed),
+        };
+        if self.pinned.swap(pinned, Ordering::Relaxed) == pinned {
+            return;
+        }
+        dbus_state::update(&self.serial, |status| status.pinned = pinned);
+        verbose_log!(
+            "[{}] Page {}",
+            self.serial,
+            if pinned { "pinned" } else { "unpinned" }
+        );
+        if let Some(button_id) = self.pages.pin_indicator {
+            if let Err(e) = self.invalidate_and_refresh_button(button_id) {
+                error_log!("{}", e);
+            }
+        }
+        self.refresh_buttons_using("pin");
+        if !pinned {
+            let class = {
+                self.current_class
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone()
+            };
+            let title = {
+                self.current_title
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone()
+            };
+            self.apply_focus_change(&class, &title, false);
+        }
+    }
+
+    /// Turns game mode on or off. While on, `page` (when the device has it) is shown,
+    /// focus changes are ignored and animations and marquees stand still. Once off, the
+    /// page follows the focus again unless it is pinned.
+    pub fn set_game_mode(&self, enabled: bool, page: Option<&String>) {
+        if self.game_mode.swap(enabled, Ordering::Relaxed) == enabled {
+            return;
+        }
+        verbose_log!(
+            "[{}] Game mode {}",
+            self.serial,
+            if enabled { "on" } else { "off" }
+        );
+        let page = page.filter(|page| self.pages.pages.contains_key(*page));
+        if enabled {
+            if let Some(page) = page {
+                if let Err(e) = self.set_page(page, true) {
+                    error_log!("{}", e);
+                }
+            }
+        } else if !self.pinned.load(Ordering::Relaxed) {
+            let class = {
+                self.current_class
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone()
+            };
+            let title = {
+                self.current_title
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone()
+            };
+            // The game page is left even if the focus target did not change meanwhile
+            let leave_page = page.is_some() && self.get_current_page_name().as_ref() == page;
+            self.apply_focus_change(&class, &title, leave_page);
+        }
+        // Start or stop the animations of the keys shown
+        self.refresh_page();
+    }
+
+    /// Switches to `page` as a `jump` does, for `keydeck --set-page`
+    pub fn show_page(&self, page: &String) -> Result<(), Error> {
+        self.set_page(page, false)
+    }
+
+    /// The state of the device to keep across restarts
+    pub fn runtime_state(&self) -> DeviceState {
+        DeviceState {
+            page: self.get_current_page_name(),
+            pinned: self.pinned.load(Ordering::Relaxed),
+            heatmap: self.heatmap.load(Ordering::Relaxed),
+            focus_cycles: self
+                .focus_cycles
+                .read()
+                .unwrap()
+                .iter()
+                .map(|(target, count)| (target.clone(), *count))
+                .collect(),
+        }
+    }
+
+    /// Restores the state of an earlier run. The page itself is restored by passing it
+    /// as the initial page.
+    pub fn restore_runtime_state(&self, state: &DeviceState) {
+        *self
+            .focus_cycles
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = state
+            .focus_cycles
+            .iter()
+            .map(|(target, count)| (target.clone(), *count))
+            .collect();
+        if state.heatmap {
+            self.set_heatmap(Switch::On);
+        }
+        if state.pinned {
+            self.set_pin(Switch::On);
+        }
+    }
+
+    /// Usage of a button relative to the most used one of its page, when the heatmap
+    /// is shown and the button has actions to count
+    fn button_heat(&self, button_id: u8) -> Option<f32> {
+        if !self.heatmap.load(Ordering::Relaxed) || !self.button_has_actions(button_id) {
+            return None;
+        }
+        let page = self.get_current_page_name()?;
+        let stats = self
+            .usage_stats
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let stats = stats.device(&self.serial);
+        let max = stats.map_or(0, |s| s.max_presses(&page));
+        let presses = stats.map_or(0, |s| s.presses(&page, &format!("button{}", button_id)));
+        Some(presses as f32 / max.max(1) as f32)
+    }
+
+    /// Runs the actions of a just pressed button with `repeat`, and schedules their
+    /// first repetition
+    fn start_repeat(&self, button_id: u8) {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(repeat) = self
+            .find_button(current_page, button_id)
+            .and_then(|b| b.repeat.clone())
+        else {
+            return;
+        };
+        let press = self.press_counter.fetch_add(1, Ordering::Relaxed) + 1;
+        self.button_repeats
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)[button_id as usize - 1] =
+            Some((current_page, press));
+
+        self.cancel_pending_actions();
+        if self.run_button_actions(button_id) {
+            self.schedule_repeat(button_id, press, repeat.delay);
+        }
+    }
+
+    /// Repeats the actions of a button that is still held since the given press. Stops
+    /// once it is released, its page is left, or its actions fail. A `timer` button held
+    /// this long is reset instead.
+    pub fn repeat_button(&self, button_id: u8, press: u64) {
+        if button_id < 1 || button_id > self.device.button_count() {
+            return;
+        }
+        if self.reset_held_timer(button_id, press) {
+            return;
+        }
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        if self
+            .button_repeats
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)[button_id as usize - 1]
+            != Some((current_page, press))
+        {
+            return;
+        }
+        let Some(repeat) = self
+            .find_button(current_page, button_id)
+            .and_then(|b| b.repeat.clone())
+        else {
+            return;
+        };
+        if self.run_button_actions(button_id) {
+            self.schedule_repeat(button_id, press, repeat.interval);
+        }
+    }
+
+    /// Starts waiting for a `timer` button to be held long enough to reset it
+    fn start_timer_hold(&self, button_id: u8) {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        if self
+            .find_button(current_page, button_id)
+            .is_none_or(|b| b.timer.is_none())
+        {
+            return;
+        }
+        let press = self.press_counter.fetch_add(1, Ordering::Relaxed) + 1;
+        self.timer_holds
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)[button_id as usize - 1] = Some((press, false));
+        self.schedule_repeat(button_id, press, TIMER_RESET_HOLD);
+    }
+
+    /// Resets the timer of a button still held since the given press
+    fn reset_held_timer(&self, button_id: u8, press: u64) -> bool {
+        {
+            let mut holds = self
+                .timer_holds
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            match &mut holds[button_id as usize - 1] {
+                Some((held, reset)) if *held == press => *reset = true,
+                _ => return false,
+            }
+        }
+        if let Some(page) = self.get_current_page_name() {
+            verbose_log!("[{}] Resetting timer of button {}", self.serial, button_id);
+            self.timers
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(&(page, button_id));
+            self.refresh_button(button_id);
+        }
+        true
+    }
+
+    /// A short press of a `timer` button: starts, pauses or resumes the countdown, or
+    /// silences an alarm
+    fn press_timer(&self, button_id: u8) {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (Some(timer), Some(page)) = (
+            self.find_button(current_page, button_id)
+                .and_then(|b| b.timer.as_ref()),
+            self.get_current_page_name(),
+        ) else {
+            return;
+        };
+        let key = (page, button_id);
+        let mut timers = self.timers.write().unwrap_or_else(PoisonError::into_inner);
+        let duration = Duration::from_secs_f32(timer.duration.max(0.0));
+        let state = timers.get(&key).copied();
+        match state {
+            Some(TimerState::Running { .. }) => {
+                let left = TimerState::left(state, duration);
+                timers.insert(key, TimerState::Paused { left });
+            }
+            Some(TimerState::Ringing) => {
+                timers.remove(&key);
+            }
+            None | Some(TimerState::Paused { .. }) => {
+                let left = TimerState::left(state, duration);
+                let run = self.press_counter.fetch_add(1, Ordering::Relaxed) + 1;
+                timers.insert(
+                    key,
+                    TimerState::Running {
+                        until: Instant::now() + left,
+                        run,
+                    },
+                );
+                self.time_manager
+                    .schedule_timer_up(self.serial.clone(), run, left);
+            }
+        }
+        drop(timers);
+        self.refresh_button(button_id);
+    }
+
+    /// The time of a timer is up: plays its sound, runs its alarm actions and blinks the
+    /// button until it is pressed. Ignored if the timer was paused or reset since.
+    pub fn timer_up(&self, run: u64) {
+        let key = {
+            let mut timers = self.timers.write().unwrap_or_else(PoisonError::into_inner);
+            let Some((key, state)) = timers.iter_mut().find(
+                |(_, state)| matches!(state, TimerState::Running { run: r, .. } if *r == run),
+            ) else {
+                return;
+            };
+            *state = TimerState::Ringing;
+            key.clone()
+        };
+        let (page, button_id) = key;
+        let Some(timer) = self
+            .pages
+            .pages
+            .get_index_of(&page)
+            .and_then(|index| self.find_button(index, button_id))
+            .and_then(|b| b.timer.clone())
+        else {
+            return;
+        };
+        verbose_log!(
+            "[{}] Timer of {}/button{} is up",
+            self.serial,
+            page,
+            button_id
+        );
+        if let Some(sound) = &timer.sound {
+            audio::play_sample(sound, 1.0).unwrap_or_else(|e| warn_log!("{}", e));
+        }
+        if let Some(actions) = timer.alarm {
+            if let Err(e) = self.execute_recorded(actions, "timer", Some(button_id)) {
+                self.report_action_error(&e, None, None);
+            }
+        }
+        if self.get_current_page_name().as_ref() == Some(&page) {
+            self.refresh_button(button_id);
+        }
+    }
+
+    /// The `timer` of a button on the current page and its state
+    fn timer_view(&self, button_id: u8) -> Option<(Timer, Option<TimerState>)> {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let timer = self.find_button(current_page, button_id)?.timer.clone()?;
+        let page = self.get_current_page_name()?;
+        let state = self
+            .timers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&(page, button_id))
+            .copied();
+        Some((timer, state))
+    }
+
+    fn schedule_repeat(&self, button_id: u8, press: u64, seconds: f32) {
+        let delay = if seconds.is_finite() {
+            Duration::from_secs_f32(seconds.max(0.0)).max(MIN_REPEAT_INTERVAL)
+        } else {
+            MIN_REPEAT_INTERVAL
+        };
+        self.time_manager
+            .schedule_repeat(self.serial.clone(), button_id, press, delay);
+    }
+
+    /// Starts the `cooldown` of a just pressed button, if it has one
+    fn start_cooldown(&self, button_id: u8) {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(cooldown) = self
+            .find_button(current_page, button_id)
+            .and_then(|b| b.cooldown.clone())
+        else {
+            return;
+        };
+        let seconds = cooldown.seconds();
+        if !seconds.is_finite() || seconds <= 0.0 {
+            return;
+        }
+        self.button_cooldowns
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)[button_id as usize - 1] =
+            Some(CooldownState {
+                page: current_page,
+                until: Instant::now() + Duration::from_secs_f32(seconds),
+                countdown: cooldown.countdown(),
+            });
+        // Without press feedback nothing else redraws the button to show the countdown
+        if cooldown.countdown() && !self.device.supports_button_press_feedback() {
+            self.refresh_button(button_id);
+        }
+    }
+
+    /// Time left in the cooldown of a button on the current page, and whether it is
+    /// counted down on the key
+    fn cooldown_remaining(&self, button_id: u8) -> Option<(Duration, bool)> {
+        let state = self
+            .button_cooldowns
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)[button_id as usize - 1]?;
+        if state.page
+            != *self
+                .current_page_ref
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+        {
+            return None;
+        }
+        let remaining = state.until.checked_duration_since(Instant::now())?;
+        Some((remaining, state.countdown))
+    }
+
+    /// Surfaces a failed action sequence according to the button's `on_error`, falling
+    /// back to the global policy. Without any policy the error is only logged.
+    fn report_action_error(&self, error: &Error, button_id: Option<u8>, policy: Option<&OnError>) {
+        let error = &error.to_string();
+        let policy = match policy.or(self.on_error.as_ref().as_ref()) {
+            Some(policy) => policy,
+            None => {
+                error_log!("{}", error);
+                return;
+            }
+        };
+        if policy.log {
+            error_log!("{}", error);
+        }
+        if policy.notify {
+            send_notification("KeyDeck action failed", error)
+                .unwrap_or_else(|e| error_log!("{}", e));
+        }
+        if policy.flash {
+            if let Some(button_id) = button_id {
+                self.flash_button(button_id);
+            }
+        }
+        if let Some(actions) = &policy.actions {
+            // Written directly (not via SetContextVar) so the handler actions see it immediately
+            if let Ok(mut vars) = self.context_vars.write() {
+                vars.insert("last_error".to_string(), error.to_string());
+            }
+            if let Err(e) = self.execute_actions(actions.clone()) {
+                error_log!("Error executing on_error actions: {}", e);
+            }
+        }
+    }
+
+    /// Temporarily paints the button with the error color
+    fn flash_button(&self, button_id: u8) {
+        if !self.device.has_screen() || button_id < 1 || button_id > self.device.button_count() {
+            return;
+        }
+        self.button_flash_until
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)[button_id as usize - 1] =
+            Some(Instant::now() + ERROR_FLASH_DURATION);
+        self.refresh_button(button_id);
+        self.time_manager
+            .schedule_refresh(self.serial.clone(), button_id, ERROR_FLASH_DURATION);
+    }
+
+    /// Re-renders a single button, e.g. when a temporary visual state expired
+    pub fn refresh_button(&self, button_id: u8) {
+        self.invalidate_and_refresh_button(button_id)
+            .unwrap_or_else(|e| error_log!("Error refreshing button: {}", e));
+    }
+
+    /// Expands a single macro call into a sequence of actions.
+    /// This performs parameter substitution and parses the macro's actions.
+    fn expand_single_macro(&self, macro_call: MacroCall) -> Result<Vec<Action>, Error> {
+        let macro_name = macro_call.name;
+
+        // Find the macro definition
+        let macro_def = self
+            .macros
+            .as_ref()
+            .as_ref()
+            .and_then(|macros| macros.get(&macro_name))
+            .ok_or_else(|| Error::MacroNotFound(macro_name.clone()))?;
+
+        let actions = macro_def.expand(&macro_name, &macro_call.params)?;
 
         verbose_log!(
             "Expanded macro '{}' with {} actions",
@@ -547,23 +2893,89 @@ impl PagedDevice {
         Ok(actions)
     }
 
+    /// Holds the `remaining` actions until the window of `wait` has the focus. Returns
+    /// false, taking nothing, when it already has it.
+    fn pause_for_window(
+        &self,
+        wait: &WindowWait,
+        remaining: &mut std::vec::IntoIter<Action>,
+    ) -> Result<bool, String> {
+        let mut conditions = WaitConditions::window(wait)?;
+        let class = {
+            self.current_class
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        };
+        let title = {
+            self.current_title
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        };
+        conditions.observe_window(&class, &title, Duration::ZERO);
+        if conditions.outcome(Duration::ZERO) == Some(true) {
+            verbose_log!("WaitFor {} already focused", conditions.describe());
+            return Ok(false);
+        }
+        verbose_log!("WaitFor paused, waiting for {}", conditions.describe());
+        *self
+            .pending_actions
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(PendingActionQueue {
+            actions: remaining.collect(),
+            last_modified: Instant::now(),
+            conditions,
+        });
+        Ok(true)
+    }
+
     /// Execute a sequence of actions. Returns when actions are complete, or pauses
     /// when a waitFor action needs to wait for an event to occur.
-    /// Returns Ok(()) if all actions succeed, Err(message) on failure.
-    fn execute_actions(&self, actions: Vec<Action>) -> Result<(), String> {
+    /// Returns Ok(()) if all actions succeed, Err(Error::Action) naming the failed
+    /// action on failure.
+    fn execute_actions(&self, actions: Vec<Action>) -> Result<(), Error> {
+        self.execute_actions_from(0, actions)
+    }
+
+    /// Like [`Self::execute_actions`], for actions taken from a list at position `first`
+    fn execute_actions_from(&self, first: usize, actions: Vec<Action>) -> Result<(), Error> {
+        let position = Cell::new(0);
+        self.execute_sequence(actions.clone(), &position)
+            .map_err(|source| {
+                let index = position.get();
+                Error::Action {
+                    index: first + index,
+                    action: history::describe_action(&actions[index]),
+                    source: Box::new(source),
+                }
+            })
+    }
+
+    /// Executes the actions, keeping in `position` the index of the one running
+    fn execute_sequence(&self, actions: Vec<Action>, position: &Cell<usize>) -> Result<(), Error> {
         let mut actions_iter = actions.into_iter();
+        let mut index = 0;
 
         while let Some(action) = actions_iter.next() {
+            position.set(index);
+            index += 1;
             match action {
-                Action::Exec { exec, wait } => {
-                    let (shell, flag) = crate::platform::exec_shell();
+                Action::Exec {
+                    exec,
+                    wait,
+                    run_as,
+                    scope,
+                    timeout,
+                    tag,
+                    kill_on_exit,
+                } => {
+                    let mut command = exec_command(&exec, run_as.as_deref(), scope.as_ref())?;
                     if wait.unwrap_or(false) {
                         // Synchronous: wait for command to complete and check exit status
-                        let output = std::process::Command::new(shell)
-                            .arg(flag)
-                            .arg(&exec)
-                            .output()
-                            .map_err(|e| format!("Failed to execute command '{}': {}", exec, e))?;
+                        let timeout = timeout.map(|t| Duration::from_secs_f32(t.max(0.0)));
+                        let output = exec::output_within(&mut command, timeout, run_as.as_deref())
+                            .map_err(|e| format!("Command '{}' {}", exec, e))?;
 
                         if !output.status.success() {
                             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -576,17 +2988,144 @@ impl PagedDevice {
                                 exec,
                                 exit_code,
                                 stderr.trim()
-                            ));
+                            )
+                            .into());
                         }
                     } else {
-                        // Asynchronous: fire and forget (original behavior)
-                        std::process::Command::new(shell)
-                            .arg(flag)
-                            .arg(&exec)
-                            .spawn()
+                        // Asynchronous: fire and forget, reaped once it ends
+                        let page = kill_on_exit
+                            .unwrap_or(false)
+                            .then(|| self.get_current_page_name())
+                            .flatten();
+                        let supervision = processes::Supervision {
+                            run_as,
+                            tag,
+                            kill_on_exit: page.map(|page| (self.serial.clone(), page)),
+                        };
+                        processes::spawn(command, supervision, &self.event_tx)
                             .map_err(|e| format!("Failed to execute command '{}': {}", exec, e))?;
                     }
                 }
+                Action::Http {
+                    http,
+                    method,
+                    headers,
+                    body,
+                    capture,
+                    timeout,
+                } => {
+                    let url = self.substitute_dynamic_params(&http);
+                    let headers = headers.map(|headers| {
+                        headers
+                            .into_iter()
+                            .map(|(name, value)| (name, self.substitute_dynamic_params(&value)))
+                            .collect()
+                    });
+                    let body = body.map(|body| self.substitute_dynamic_params(&body));
+                    let response = http_action::send_request(
+                        &url,
+                        method.as_deref(),
+                        headers.as_ref(),
+                        body.as_deref(),
+                        timeout,
+                    )?;
+                    verbose_log!("[{}] HTTP request to {} succeeded", self.serial, url);
+                    if let Some(key) = capture {
+                        let value = response.trim().to_string();
+                        send(
+                            &self.event_tx,
+                            DeviceEvent::SetContextVar {
+                                key,
+                                value: if value.is_empty() { None } else { Some(value) },
+                            },
+                        );
+                    }
+                }
+                Action::ChatRead { chat_read } => {
+                    let target = self.substitute_dynamic_params(&chat_read);
+                    chats::mark_read(&target)?;
+                    let service = target.split(':').next().unwrap_or_default().trim();
+                    send(
+                        &self.event_tx,
+                        DeviceEvent::ProviderUpdated {
+                            provider: service.to_string(),
+                        },
+                    );
+                }
+                Action::JoinMeeting { join_meeting } => {
+                    let target = self.substitute_dynamic_params(&join_meeting);
+                    let link = calendar::meeting_link(&target)?;
+                    verbose_log!("[{}] Joining meeting at {}", self.serial, link);
+                    open_url(&link)?;
+                }
+                Action::Open { open } => {
+                    let target = self.substitute_dynamic_params(&open);
+                    verbose_log!("[{}] Opening {}", self.serial, target);
+                    open_url(&target)?;
+                }
+                Action::Wol { wol, broadcast } => {
+                    let mac = self.substitute_dynamic_params(&wol);
+                    verbose_log!("[{}] Waking {}", self.serial, mac);
+                    wol::wake(&mac, broadcast.as_deref())?;
+                }
+                Action::OpenRgb { openrgb: profile } => {
+                    let profile = openrgb::load_profile(&self.substitute_dynamic_params(&profile))?;
+                    verbose_log!("[{}] OpenRGB profile {}", self.serial, profile);
+                    openrgb::updated(&self.event_tx);
+                }
+                Action::Power { power: action } => {
+                    verbose_log!("[{}] Power {:?}", self.serial, action);
+                    power(action)?;
+                }
+                Action::KbdLayout { kbd_layout } => {
+                    let target = self.substitute_dynamic_params(&kbd_layout);
+                    let layout = switch_keyboard_layout(&target)?;
+                    verbose_log!("[{}] Keyboard layout {}", self.serial, layout);
+                    send(
+                        &self.event_tx,
+                        DeviceEvent::ProviderUpdated {
+                            provider: "kbd".to_string(),
+                        },
+                    );
+                }
+                Action::AudioDevice { audio_device } => {
+                    let switches = [
+                        (DeviceKind::Sink, audio_device.set_default_sink),
+                        (DeviceKind::Source, audio_device.set_default_source),
+                    ];
+                    for (kind, target) in switches {
+                        let Some(target) = target else { continue };
+                        let target = self.substitute_dynamic_params(&target);
+                        let device = audio::set_default_device(kind, &target)?;
+                        verbose_log!(
+                            "[{}] Default audio {:?} {}",
+                            self.serial,
+                            kind,
+                            device.description
+                        );
+                    }
+                    send(
+                        &self.event_tx,
+                        DeviceEvent::ProviderUpdated {
+                            provider: "audio".to_string(),
+                        },
+                    );
+                }
+                Action::Check { check } => {
+                    let target = self.substitute_dynamic_params(&check);
+                    let done = checklist::toggle(&target)?;
+                    verbose_log!("[{}] Checklist item {} done: {}", self.serial, target, done);
+                    send(
+                        &self.event_tx,
+                        DeviceEvent::ProviderUpdated {
+                            provider: "checklist".to_string(),
+                        },
+                    );
+                }
+                Action::Notify { notify } => {
+                    let message = self.substitute_dynamic_params(&notify);
+                    send_notification("KeyDeck", &message)?;
+                }
                 Action::Set { set } => {
                     // Set a context variable in-daemon, same key=value grammar as
                     // `--set` (empty value clears). Routed through the event loop so
@@ -596,7 +3135,7 @@ impl PagedDevice {
                         None => (set.trim().to_string(), String::new()),
                     };
                     if key.is_empty() {
-                        return Err(format!("set action requires key=value, got '{}'", set));
+                        return Err(format!("set action requires key=value, got '{}'", set).into());
                     }
                     send(
                         &self.event_tx,
@@ -606,46 +3145,129 @@ impl PagedDevice {
                         },
                     );
                 }
+                Action::NightMode { night_mode } => {
+                    // Night mode spans all devices, so the event loop owns its state
+                    send(&self.event_tx, DeviceEvent::NightMode { mode: night_mode });
+                }
+                Action::Heatmap { heatmap } => {
+                    self.set_heatmap(heatmap);
+                }
+                Action::Pin { pin } => {
+                    self.set_pin(pin);
+                }
+                Action::GameMode { game_mode } => {
+                    // Game mode spans all devices, so the event loop owns its state
+                    send(&self.event_tx, DeviceEvent::GameMode { mode: game_mode });
+                }
+                Action::Theme { theme } => {
+                    if !self.themes.as_ref().as_ref().is_some_and(|t| t.contains_key(&theme)) {
+                        return Err(format!("Theme '{}' is not defined", theme).into());
+                    }
+                    send(&self.event_tx, DeviceEvent::SetTheme { theme });
+                }
                 Action::Jump { jump } => {
+                    if self
+                        .pages
+                        .pages
+                        .get(&jump)
+                        .is_some_and(|page| !self.page_visible(page))
+                    {
+                        return Err(format!("Page '{}' is hidden by its visible_when", jump).into());
+                    }
                     self.set_page(&jump, false)?;
                 }
                 Action::AutoJump { auto_jump: _ } => {
-                    let class = { self.current_class.borrow().clone() };
-                    let title = { self.current_title.borrow().clone() };
+                    let class = {
+                        self.current_class
+                            .read()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .clone()
+                    };
+                    let title = {
+                        self.current_title
+                            .read()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .clone()
+                    };
                     self.focus_changed(&class, &title, true)
                 }
-                Action::Focus { focus } => {
-                    // Focus action: match against both window class and title
-                    set_focus(&focus, &focus)?;
-                    verbose_log!(
-                        "Requested focus for '{}' (checking both class and title)",
-                        focus
-                    );
+                Action::Focus {
+                    focus,
+                    fallback_exec,
+                    wait_for_window,
+                } => {
+                    // Cycling targets advance to the next match on every press
+                    let press_count = match &focus {
+                        FocusTarget::Detailed(spec) if spec.cycle => {
+                            let mut cycles = self
+                                .focus_cycles
+                                .write()
+                                .unwrap_or_else(PoisonError::into_inner);
+                            let count = cycles.entry(focus.describe()).or_insert(0);
+                            *count += 1;
+                            *count - 1
+                        }
+                        _ => 0,
+                    };
+                    let query = WindowQuery::from_target(&focus, press_count)?;
+                    match (set_focus(&query), fallback_exec) {
+                        (Ok(()), _) => verbose_log!("Requested focus for {}", query.describe()),
+                        (Err(e), None) => return Err(e.into()),
+                        (Err(e), Some(fallback)) => {
+                            verbose_log!(
+                                "Focus of {} failed ({}), launching '{}'",
+                                query.describe(),
+                                e,
+                                fallback
+                            );
+                            exec_command(&fallback, None, None)?.spawn().map_err(|e| {
+                                format!("Failed to execute command '{}': {}", fallback, e)
+                            })?;
+                            if let Some(window) = wait_for_window {
+                                if self.pause_for_window(&window, &mut actions_iter)? {
+                                    return Ok(()); // Resumes when the launched window is focused
+                                }
+                            }
+                        }
+                    }
                 }
                 Action::WaitFor {
                     wait_for_event,
+                    mode,
                     timeout,
                 } => {
-                    let event_type = WaitEventType::from_str(&wait_for_event)?;
-                    let timeout_secs = timeout.unwrap_or(1.0);
+                    let conditions = WaitConditions::from_events(
+                        &wait_for_event,
+                        mode.unwrap_or_default(),
+                        timeout,
+                    )?;
 
                     // Pause and wait for the event to occur
                     let remaining: Vec<Action> = actions_iter.collect();
+                    let description = conditions.describe();
+                    let delays: Vec<Duration> = conditions.delays().collect();
 
-                    *self.pending_actions.borrow_mut() = Some(PendingActionQueue {
+                    *self
+                        .pending_actions
+                        .write()
+                        .unwrap_or_else(PoisonError::into_inner) = Some(PendingActionQueue {
                         actions: remaining,
                         last_modified: Instant::now(),
-                        timeout: Duration::from_secs_f64(timeout_secs),
-                        event_type: event_type.clone(),
+                        conditions,
                     });
+                    // Delays are checked when their timer event arrives, after the start
+                    for delay in delays {
+                        self.time_manager.schedule_timer(self.serial.clone(), delay);
+                    }
 
-                    verbose_log!(
-                        "WaitFor paused, waiting for event '{}' (timeout: {}s)",
-                        event_type.as_str(),
-                        timeout_secs
-                    );
+                    verbose_log!("WaitFor paused, waiting for event '{}'", description);
                     return Ok(()); // Pause execution, will resume when event arrives
                 }
+                Action::WaitForWindow { wait_for_window } => {
+                    if self.pause_for_window(&wait_for_window, &mut actions_iter)? {
+                        return Ok(()); // Pause execution, will resume when the window is focused
+                    }
+                }
                 Action::Wait { wait } => {
                     // Schedule an async timer event instead of blocking
                     self.time_manager
@@ -654,21 +3276,70 @@ impl PagedDevice {
                     // Pause and wait for the TimerComplete event
                     let remaining: Vec<Action> = actions_iter.collect();
 
-                    *self.pending_actions.borrow_mut() = Some(PendingActionQueue {
+                    *self
+                        .pending_actions
+                        .write()
+                        .unwrap_or_else(PoisonError::into_inner) = Some(PendingActionQueue {
                         actions: remaining,
                         last_modified: Instant::now(),
-                        timeout: Duration::from_secs_f64((wait as f64) * 2.0), // Generous timeout
-                        event_type: WaitEventType::Timer,
+                        // Generous timeout
+                        conditions: WaitConditions::event(
+                            WaitEventType::Timer,
+                            Duration::from_secs_f64((wait as f64) * 2.0),
+                        ),
                     });
 
                     verbose_log!("Wait scheduled for {}s (non-blocking)", wait);
                     return Ok(()); // Non-blocking return, will resume when TimerComplete arrives
                 }
-                Action::Key { key } => {
-                    send_key_combination(&key)?;
+                Action::Key { key, target } => match target {
+                    Some(target) => self.send_to_window(&target, || send_key_combination(&key))?,
+                    None => send_key_combination(&key)?,
+                },
+                Action::Text { text, echo, target } => {
+                    if self.should_echo(&text, echo) {
+                        self.start_echo(text, target, actions_iter.collect());
+                        return Ok(()); // Typed when the echo ends, see echo_step()
+                    }
+                    match target {
+                        Some(target) => self.send_to_window(&target, || send_string(&text))?,
+                        None => send_string(&text)?,
+                    }
+                }
+                Action::If {
+                    condition,
+                    then,
+                    else_actions,
+                } => {
+                    let holds = condition::holds(&condition, &|value| {
+                        self.substitute_dynamic_params(value)
+                    })?;
+                    verbose_log!("If '{}': {}", condition, holds);
+                    if holds {
+                        self.execute_actions(then)?;
+                    } else if let Some(else_acts) = else_actions {
+                        self.execute_actions(else_acts)?;
+                    }
                 }
-                Action::Text { text } => {
-                    send_string(&text)?;
+                Action::Confirm {
+                    message,
+                    confirm_actions,
+                    cancel_actions,
+                    timeout,
+                } => {
+                    let question = self.substitute_dynamic_params(&message);
+                    let timeout = timeout.map_or(CONFIRM_TIMEOUT, |secs| {
+                        Duration::from_secs_f32(secs.max(0.0))
+                    });
+                    let mut confirm = confirm_actions;
+                    confirm.extend(actions_iter);
+                    self.start_confirmation(
+                        &question,
+                        confirm,
+                        cancel_actions.unwrap_or_default(),
+                        timeout,
+                    )?;
+                    return Ok(()); // Run once answered, see answer_confirmation()
                 }
                 Action::Try {
                     try_actions,
@@ -712,13 +3383,13 @@ impl PagedDevice {
                 }
                 Action::Fail { .. } => {
                     verbose_log!("Fail action: stopping execution with error");
-                    return Err("Fail action executed".to_string());
+                    return Err("Fail action executed".into());
                 }
                 Action::And { and_actions } => {
                     // Execute all actions sequentially, short-circuit on first error
                     verbose_log!("AND: executing {} conditions", and_actions.len());
-                    for action in and_actions {
-                        self.execute_actions(vec![action])?; // Propagate first error
+                    for (idx, action) in and_actions.into_iter().enumerate() {
+                        self.execute_actions_from(idx, vec![action])?; // Propagate first error
                     }
                     verbose_log!("AND: all conditions succeeded");
                     // All succeeded, continue
@@ -728,7 +3399,7 @@ impl PagedDevice {
                     verbose_log!("OR: trying {} conditions", or_actions.len());
                     let mut last_error = None;
                     for (idx, action) in or_actions.into_iter().enumerate() {
-                        match self.execute_actions(vec![action]) {
+                        match self.execute_actions_from(idx, vec![action]) {
                             Ok(_) => {
                                 verbose_log!("OR: condition {} succeeded", idx + 1);
                                 return Ok(()); // First success, stop and succeed
@@ -741,7 +3412,7 @@ impl PagedDevice {
                     }
                     // All failed, return last error
                     return Err(
-                        last_error.unwrap_or_else(|| "All OR conditions failed".to_string())
+                        last_error.unwrap_or_else(|| "All OR conditions failed".into())
                     );
                 }
                 Action::Not { not_action } => {
@@ -751,7 +3422,7 @@ impl PagedDevice {
                         Ok(_) => {
                             verbose_log!("NOT: action succeeded, inverting to failure");
                             return Err(
-                                "NOT condition: action succeeded (inverted to failure)".to_string()
+                                "NOT condition: action succeeded (inverted to failure)".into()
                             );
                         }
                         Err(e) => {
@@ -760,24 +3431,39 @@ impl PagedDevice {
                         }
                     }
                 }
+                Action::Random { mut random_actions } => {
+                    if !random_actions.is_empty() {
+                        let pick = fastrand::usize(..random_actions.len());
+                        verbose_log!(
+                            "RANDOM: picked action {} of {}",
+                            pick + 1,
+                            random_actions.len()
+                        );
+                        self.execute_actions(vec![random_actions.swap_remove(pick)])?;
+                    }
+                }
                 Action::Refresh { refresh } => {
                     match refresh {
                         RefreshTarget::Dynamic(_) => {
                             // Refresh all dynamic buttons
                             verbose_log!("Refresh: updating all dynamic buttons");
-                            let current_page = { self.current_page_ref.borrow().clone() };
+                            let current_page = *self
+                                .current_page_ref
+                                .read()
+                                .unwrap_or_else(PoisonError::into_inner);
                             let button_count = self.device.button_count();
 
-                            for button_id in 1..=button_count {
-                                if let Some(button) = self.find_button(current_page, button_id) {
+                            let keys = (1..=button_count)
+                                .filter(|button_id| {
                                     // Hybrid: explicit dynamic flag takes precedence, otherwise use computed
-                                    let is_dynamic =
-                                        button.dynamic.unwrap_or(button.is_dynamic_computed);
-                                    if is_dynamic {
-                                        self.invalidate_and_refresh_button(button_id)?;
-                                    }
-                                }
-                            }
+                                    let is_dynamic = |button: &Button| {
+                                        button.dynamic.unwrap_or(button.is_dynamic_computed)
+                                    };
+                                    self.find_button(current_page, *button_id)
+                                        .is_some_and(is_dynamic)
+                                })
+                                .collect();
+                            self.refresh_in_slices(keys);
                         }
                         RefreshTarget::Single(button_id) => {
                             // Refresh single button
@@ -798,40 +3484,337 @@ impl PagedDevice {
         Ok(())
     }
 
-    pub fn encoder_down(&self, _encoder_id: u8) {
+    pub fn encoder_down(&self, encoder_id: u8) {
+        self.note_interaction();
         self.cancel_pending_actions();
+        self.encoders_held
+            .write()
+            .unwrap()
+            .insert(encoder_id, false);
     }
 
     pub fn encoder_up(&self, encoder_id: u8) {
         self.cancel_pending_actions();
-        let current_page = { self.current_page_ref.borrow().clone() };
+        // Twisting a held encoder was a gesture of its own, not a press
+        if self
+            .encoders_held
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&encoder_id)
+            == Some(true)
+        {
+            return;
+        }
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
         if let Some(encoder) = self.find_encoder(current_page, encoder_id) {
             if let Some(actions) = &encoder.press {
-                if let Err(e) = self.execute_actions(actions.clone()) {
-                    error_log!("{}", e);
+                let trigger = format!("encoder {} press", encoder_id);
+                if let Err(e) = self.execute_recorded(actions.clone(), &trigger, None) {
+                    self.report_action_error(&e, None, None);
                 }
             }
         }
     }
 
     pub fn encoder_twist(&self, encoder_id: u8, value: i8) {
+        self.note_interaction();
         self.cancel_pending_actions();
-        let current_page = { self.current_page_ref.borrow().clone() };
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
         if let Some(encoder) = self.find_encoder(current_page, encoder_id) {
+            // A held encoder with press twist actions runs them instead of the plain twist
+            let pressed = encoder.has_press_twist() && {
+                let mut held = self
+                    .encoders_held
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner);
+                match held.get_mut(&encoder_id) {
+                    Some(twisted) => {
+                        *twisted = true;
+                        true
+                    }
+                    None => false,
+                }
+            };
+            if pressed {
+                let actions = if value > 0 {
+                    &encoder.press_twist_right
+                } else {
+                    &encoder.press_twist_left
+                };
+                if let Some(actions) = actions {
+                    let trigger = format!("encoder {} press twist", encoder_id);
+                    if let Err(e) = self.execute_recorded(actions.clone(), &trigger, None) {
+                        self.report_action_error(&e, None, None);
+                    }
+                }
+                return;
+            }
+            if let Some(widget) = &encoder.widget {
+                self.adjust_encoder_widget(encoder_id, widget, value);
+            }
+            match encoder.builtin {
+                Some(EncoderBuiltin::Brightness) => self.step_brightness(value),
+                Some(EncoderBuiltin::PageScroll) => self.scroll_pages(value),
+                None => {}
+            }
             let actions = if value > 0 {
                 &encoder.twist_right
             } else {
                 &encoder.twist_left
             };
             if let Some(actions) = actions {
-                if let Err(e) = self.execute_actions(actions.clone()) {
-                    error_log!("{}", e);
+                let trigger = format!("encoder {} twist", encoder_id);
+                if let Err(e) = self.execute_recorded(actions.clone(), &trigger, None) {
+                    self.report_action_error(&e, None, None);
                 }
             }
         }
     }
 
+    /// Makes the screen brighter by `ticks` steps of an encoder, darker when negative
+    fn step_brightness(&self, ticks: i8) {
+        if !self.device.has_screen() {
+            return;
+        }
+        let current = self.brightness.load(Ordering::Relaxed) as i32;
+        let brightness = (current + BRIGHTNESS_STEP * ticks as i32).clamp(0, 100) as u8;
+        verbose_log!("[{}] Brightness set to {}", self.serial, brightness);
+        self.brightness.store(brightness, Ordering::Relaxed);
+        dbus_state::update(&self.serial, |status| status.brightness = brightness);
+        self.device
+            .set_brightness(brightness)
+            .unwrap_or_else(|e| error_log!("Error setting brightness: {}", e));
+    }
+
+    /// Moves `ticks` pages forward, backward when negative, skipping hidden pages and
+    /// wrapping around
+    fn scroll_pages(&self, ticks: i8) {
+        let current = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let visible: Vec<usize> = (0..self.pages.pages.len())
+            .filter(|&index| index == current || self.page_visible(&self.pages.pages[index]))
+            .collect();
+        let Some(position) = visible.iter().position(|&index| index == current) else {
+            return;
+        };
+        let target = (position as i64 + ticks as i64).rem_euclid(visible.len() as i64);
+        let Some((name, _)) = self.pages.pages.get_index(visible[target as usize]) else {
+            return;
+        };
+        if let Err(e) = self.set_page(name, false) {
+            error_log!("{}", e);
+        }
+    }
+
+    /// Replaces every `${provider:arg}` in `text` with its current value
+    fn substitute_dynamic_params(&self, text: &str) -> String {
+        if !text.contains("${") {
+            return text.to_string();
+        }
+        let class = self
+            .current_class
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let title = self
+            .current_title
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let page = self.get_current_page_name().unwrap_or_default();
+        let model = self.device.kind_name();
+        let stats = self
+            .usage_stats
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let strings = self.strings.read().unwrap_or_else(PoisonError::into_inner);
+        let local = LocalParams {
+            focus_class: &class,
+            focus_title: &title,
+            page: &page,
+            serial: &self.serial,
+            model: &model,
+            stats: stats.device(&self.serial),
+            pinned: self.pinned.load(Ordering::Relaxed),
+            strings: Some(&strings),
+        };
+        let params = evaluate_dynamic_params(
+            text,
+            &self.services_config,
+            &self.services_state,
+            &self.services_active,
+            &self.context_vars,
+            &local,
+        );
+        let mut result = text.to_string();
+        for (pattern, value) in params {
+            result = result.replace(&format!("${{{}}}", pattern), &value);
+        }
+        result
+    }
+
+    /// Returns the current value bound to an encoder widget
+    fn read_widget_value(&self, widget: &EncoderWidget) -> Option<f32> {
+        if let Some(sink) = &widget.audio {
+            return crate::audio::get_volume(sink);
+        }
+        let var = widget.var.as_ref()?;
+        let stored = self
+            .context_vars
+            .read()
+            .ok()
+            .and_then(|vars| vars.get(var).and_then(|v| v.trim().parse::<f32>().ok()));
+        let mut pending = self
+            .pending_widget_values
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        match pending.get(var).copied() {
+            // Our own write has reached the store; stop shadowing it
+            Some(value) if stored == Some(value) => {
+                pending.remove(var);
+                Some(value)
+            }
+            Some(value) => Some(value),
+            None => Some(stored.unwrap_or(widget.min)),
+        }
+    }
+
+    /// Applies `ticks` encoder steps to the widget value and redraws its LCD segment
+    fn adjust_encoder_widget(&self, encoder_id: u8, widget: &EncoderWidget, ticks: i8) {
+        let current = match self.read_widget_value(widget) {
+            Some(value) => value,
+            None => {
+                warn_log!("Encoder {} widget has no readable value", encoder_id);
+                return;
+            }
+        };
+        // Unlike clamp, never panics on bounds out of order or NaN in the configuration
+        let value = (current + widget.step * ticks as f32)
+            .max(widget.min)
+            .min(widget.max);
+        let value = (value * 100.0).round() / 100.0;
+        if let Some(sink) = &widget.audio {
+            if let Err(e) = crate::audio::set_volume(sink, value) {
+                error_log!("{}", e);
+                return;
+            }
+        } else if let Some(var) = &widget.var {
+            self.pending_widget_values
+                .write()
+                .unwrap()
+                .insert(var.clone(), value);
+            send(
+                &self.event_tx,
+                DeviceEvent::SetContextVar {
+                    key: var.clone(),
+                    value: Some(value.to_string()),
+                },
+            );
+        }
+        self.render_encoder_widget(encoder_id, widget, value);
+    }
+
+    /// Redraws all encoder widgets defined on the current page
+    fn render_encoder_widgets(&self) {
+        if self.device.lcd_strip_size().is_none() {
+            return;
+        }
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let encoders = match self.find_page(current_page).and_then(|p| p.encoders.as_ref()) {
+            Some(encoders) => encoders,
+            None => return,
+        };
+        for (key, encoder) in encoders {
+            let widget = match &encoder.widget {
+                Some(widget) => widget,
+                None => continue,
+            };
+            let encoder_id = match key.strip_prefix("encoder").and_then(|i| i.parse::<u8>().ok())
+            {
+                Some(id) => id,
+                None => continue,
+            };
+            if let Some(value) = self.read_widget_value(widget) {
+                self.render_encoder_widget(encoder_id, widget, value);
+            }
+        }
+    }
+
+    /// Renders label, value and bar into the LCD strip segment above the encoder
+    fn render_encoder_widget(&self, encoder_id: u8, widget: &EncoderWidget, value: f32) {
+        let (lcd_w, lcd_h) = match self.device.lcd_strip_size() {
+            Some(size) => (size.0 as u32, size.1 as u32),
+            None => return,
+        };
+        let encoder_count = self.device.encoder_count().max(1) as u32;
+        if encoder_id == 0 || encoder_id as u32 > encoder_count {
+            return;
+        }
+        let segment_w = lcd_w / encoder_count;
+        let segment_x = segment_w * (encoder_id as u32 - 1);
+
+        let label = self.substitute_dynamic_params(widget.label.as_deref().unwrap_or(""));
+        let text = if label.is_empty() {
+            value.to_string()
+        } else {
+            format!("{}\n{}", label, value)
+        };
+
+        let text_h = lcd_h * 3 / 5;
+        let mut text_canvas = RgbaImage::from_pixel(segment_w, text_h, Rgba([0, 0, 0, 255]));
+        render_text_on_canvas(&mut text_canvas, &text, None, None, None);
+        let mut canvas = RgbaImage::from_pixel(segment_w, lcd_h, Rgba([0, 0, 0, 255]));
+        overlay(&mut canvas, &text_canvas, 0, 0);
+
+        let color = widget
+            .color
+            .as_ref()
+            .and_then(|c| self.resolve_color(c).ok())
+            .unwrap_or((255, 255, 255));
+        let padding = segment_w / 10;
+        let bar_h = (lcd_h - text_h) / 2;
+        graphics::render_bar(
+            &mut canvas,
+            padding as i64,
+            (text_h + (lcd_h - text_h - bar_h) / 2) as i64,
+            value,
+            (widget.min, widget.max),
+            segment_w - 2 * padding,
+            bar_h,
+            color,
+            None,
+            BarDirection::LeftToRight,
+        );
+        if let Some(filter) = *self
+            .night_filter
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+        {
+            filter.apply(&mut canvas);
+        }
+
+        self.device
+            .write_lcd(
+                segment_x as u16,
+                0,
+                segment_w as u16,
+                lcd_h as u16,
+                &DynamicImage::ImageRgba8(canvas),
+            )
+            .unwrap_or_else(|e| error_log!("Error while drawing encoder widget: {}", e));
+    }
+
     pub fn touch_point_down(&self, _point_id: u8) {
+        self.note_interaction();
         self.cancel_pending_actions();
     }
 
@@ -840,22 +3823,307 @@ impl PagedDevice {
     }
 
     pub fn touch_screen_press(&self, _x: u16, _y: u16) {
+        self.note_interaction();
         self.cancel_pending_actions();
     }
 
     pub fn touch_screen_long_press(&self, _x: u16, _y: u16) {
+        self.note_interaction();
         self.cancel_pending_actions();
     }
 
     pub fn touch_screen_swipe(&self, _from: (u16, u16), _to: (u16, u16)) {
+        self.note_interaction();
         self.cancel_pending_actions();
     }
 
     pub fn focus_changed(&self, class: &str, title: &str, force_change: bool) {
+        let old_class = std::mem::replace(
+            &mut *self
+                .current_class
+                .write()
+                .unwrap_or_else(PoisonError::into_inner),
+            class.to_string(),
+        );
+        let old_title = std::mem::replace(
+            &mut *self
+                .current_title
+                .write()
+                .unwrap_or_else(PoisonError::into_inner),
+            title.to_string(),
+        );
+        // A newer change supersedes one still waiting to settle
+        let focus = self.focus_counter.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.error_page_shown.load(Ordering::Relaxed) {
+            return;
+        }
+        let old_page = {
+            *self
+                .current_page_ref
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+        };
+        let focus_differs = old_class != class || old_title != title;
+
+        // The first focus a device sees is followed at once
+        let delay = if force_change || old_class.is_empty() {
+            Duration::ZERO
+        } else {
+            self.focus_delay(class, title, focus_differs)
+        };
+        if delay.is_zero() {
+            self.apply_focus_change(class, title, force_change);
+        } else {
+            self.time_manager
+                .schedule_focus_settled(self.serial.clone(), focus, delay);
+        }
+
+        // Buttons showing ${focus:...} need a redraw when the page itself didn't change
+        if focus_differs
+            && *self
+                .current_page_ref
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                == old_page
+        {
+            self.refresh_buttons_using("focus");
+        }
+    }
+
+    /// Applies a delayed focus change, unless the focus changed again meanwhile
+    pub fn focus_settled(&self, focus: u64) {
+        if focus != self.focus_counter.load(Ordering::Relaxed)
+            || self.error_page_shown.load(Ordering::Relaxed)
         {
-            self.current_class.replace(class.to_string());
-            self.current_title.replace(title.to_string());
+            return;
+        }
+        let class = {
+            self.current_class
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        };
+        let title = {
+            self.current_title
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        };
+        self.apply_focus_change(&class, &title, false);
+    }
+
+    /// How long a focus change waits before pages follow it: `focus_debounce` when
+    /// another window got the focus, or the `switch_delay` of the page it leads to
+    fn focus_delay(&self, class: &str, title: &str, window_changed: bool) -> Duration {
+        let debounce = if window_changed {
+            self.pages.focus_debounce.unwrap_or(0.0)
+        } else {
+            0.0
+        };
+        let switch_delay = self
+            .focus_target(class, title)
+            .and_then(|name| self.pages.pages.get(&name))
+            .and_then(|page| page.switch_delay)
+            .unwrap_or(0.0);
+        let seconds = debounce.max(switch_delay);
+        let delay = if seconds.is_finite() && seconds > 0.0 {
+            Duration::from_secs_f32(seconds)
+        } else {
+            Duration::ZERO
+        };
+        // The focus taken to send keys to another window is given back shortly
+        let targeted = self
+            .targeted_until
+            .read()
+            .unwrap()
+            .map_or(Duration::ZERO, |until| {
+                until.saturating_duration_since(Instant::now())
+            });
+        delay.max(targeted)
+    }
+
+    /// Re-renders the buttons of the current page whose content references `provider`
+    pub fn refresh_buttons_using(&self, provider: &str) {
+        let current_page = {
+            *self
+                .current_page_ref
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+        };
+        let keys: Vec<u8> = (1..=self.device.button_count())
+            .filter(|button_id| {
+                self.find_button(current_page, *button_id)
+                    .is_some_and(|button| uses_provider(button, provider))
+            })
+            .collect();
+        self.refresh_in_slices(keys);
+    }
+
+    /// Redraws `keys` of the current page a slice at a time, so waiting input is not held
+    /// up by many keys refreshing at once. Keys already waiting to be drawn are joined.
+    fn refresh_in_slices(&self, keys: Vec<u8>) {
+        let current_page = {
+            *self
+                .current_page_ref
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+        };
+        let start = self
+            .deferred_keys
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .defer(current_page, keys);
+        if start {
+            self.resume_refresh();
+        }
+    }
+
+    /// Draws the keys a bulk refresh left for later, until the slice is used up
+    pub fn resume_refresh(&self) {
+        let current_page = {
+            *self
+                .current_page_ref
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+        };
+        let slice = RenderSlice::start();
+        loop {
+            let next = self
+                .deferred_keys
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .next(current_page);
+            let Some(button_id) = next else {
+                return;
+            };
+            if let Err(e) = self.invalidate_and_refresh_button(button_id) {
+                error_log!("{}", e);
+            }
+            if slice.used_up()
+                && !self
+                    .deferred_keys
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .is_empty()
+            {
+                send(
+                    &self.event_tx,
+                    DeviceEvent::ResumeRefresh {
+                        sn: self.serial.clone(),
+                    },
+                );
+                return;
+            }
+        }
+    }
+
+    /// The page the auto-matching logic selects for a focused window. A page matches when
+    /// its `when` condition holds (DNF: any group; all keys in a group; any value of a
+    /// key). Reserved keys `window`/`class`/`title` test the focused window
+    /// (case-insensitive substring); any other key tests an external context variable.
+    /// Pages hidden by their `visible_when` are never selected.
+    fn focus_target(&self, class: &str, title: &str) -> Option<String> {
+        let class_lower = class.to_lowercase();
+        let title_lower = title.to_lowercase();
+        let visible: Vec<_> = self
+            .pages
+            .pages
+            .iter()
+            .filter(|(_, page)| self.page_visible(page))
+            .collect();
+        let vars = self
+            .context_vars
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        visible
+            .into_iter()
+            .find(|(_, page)| {
+                page.when.as_ref().is_some_and(|when| {
+                    when.matches(|key, value| {
+                        let value = value.to_lowercase();
+                        match key {
+                            "window" => {
+                                class_lower.contains(&value) || title_lower.contains(&value)
+                            }
+                            "class" => class_lower.contains(&value),
+                            "title" => title_lower.contains(&value),
+                            _ => vars.get(key).is_some_and(|cur| cur.to_lowercase() == value),
+                        }
+                    })
+                })
+            })
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Whether the `visible_when` conditions of a page hold now
+    fn page_visible(&self, page: &Page) -> bool {
+        let Some(visibility) = &page.visible_when else {
+            return true;
+        };
+        let now = Local::now();
+        let weekday = now.weekday().num_days_from_monday();
+        if !visibility.is_scheduled(weekday, now.hour() * 60 + now.minute()) {
+            return false;
+        }
+        let vars_match = visibility.vars.as_ref().is_none_or(|wanted| {
+            let vars = self
+                .context_vars
+                .read()
+                .unwrap_or_else(PoisonError::into_inner);
+            wanted.iter().all(|(key, value)| {
+                value.any(|v| vars.get(key).is_some_and(|cur| cur.eq_ignore_ascii_case(v)))
+            })
+        });
+        vars_match
+            && visibility
+                .value
+                .as_ref()
+                .is_none_or(|value| self.condition_holds(value))
+    }
+
+    /// Moves away from the shown page once its `visible_when` stops holding: to the page
+    /// the focused window selects, else to the main page when that is visible
+    fn leave_hidden_page(&self) {
+        let class = {
+            self.current_class
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        };
+        let title = {
+            self.current_title
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        };
+        let focus_target = self.focus_target(&class, &title);
+        let main_page = match &self.pages.main_page {
+            Some(name) => self.pages.pages.get_key_value(name),
+            None => self.pages.pages.get_index(0),
+        };
+        let target = focus_target.clone().or_else(|| {
+            main_page
+                .filter(|(_, page)| self.page_visible(page))
+                .map(|(name, _)| name.clone())
+        });
+        let Some(target) = target else {
+            return;
+        };
+        detail_log!(
+            "[{}] Page hidden by visible_when, switching to '{}'",
+            self.serial,
+            target
+        );
+        *self
+            .last_auto_target_page
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = focus_target;
+        if let Err(e) = self.set_page(&target, true) {
+            error_log!("{}", e);
         }
+    }
+
+    fn apply_focus_change(&self, class: &str, title: &str, force_change: bool) {
 
         // If device has no pages configured, nothing to do
         if self.pages.pages.is_empty() {
@@ -866,7 +4134,18 @@ impl PagedDevice {
             return;
         }
         if !force_change {
-            let old_page = { self.current_page_ref.borrow().clone() };
+            if self.pinned.load(Ordering::Relaxed) {
+                detail_log!("[{}] Focus change ignored: page is pinned", self.serial);
+                return;
+            }
+            if self.game_mode.load(Ordering::Relaxed) {
+                detail_log!("[{}] Focus change ignored: game mode is on", self.serial);
+                return;
+            }
+            let old_page = *self
+                .current_page_ref
+                .read()
+                .unwrap_or_else(PoisonError::into_inner);
             if let Some((name, page)) = self.pages.pages.get_index(old_page) {
                 if page.lock.unwrap_or(false) {
                     detail_log!(
@@ -879,36 +4158,16 @@ impl PagedDevice {
             }
         }
 
-        // Determine what page the auto-matching logic would select. A page matches when
-        // its `when` condition holds (DNF: any group; all keys in a group; any value of a
-        // key). Reserved keys `window`/`class`/`title` test the focused window
-        // (case-insensitive substring); any other key tests an external context variable.
-        let class_lower = class.to_lowercase();
-        let title_lower = title.to_lowercase();
-        let vars = self.context_vars.read().unwrap();
-        let mut target_page: Option<String> = None;
-        for (name, page) in &self.pages.pages {
-            if let Some(when) = &page.when {
-                let matched = when.matches(|key, value| {
-                    let value = value.to_lowercase();
-                    match key {
-                        "window" => class_lower.contains(&value) || title_lower.contains(&value),
-                        "class" => class_lower.contains(&value),
-                        "title" => title_lower.contains(&value),
-                        _ => vars.get(key).is_some_and(|cur| cur.to_lowercase() == value),
-                    }
-                });
-                if matched {
-                    target_page = Some(name.clone());
-                    break;
-                }
-            }
-        }
-        drop(vars);
+        let target_page = self.focus_target(class, title);
 
         // Compare with the last auto-selected target page (skip if force_change=true, e.g., from auto_jump)
         if !force_change {
-            let last_target = { self.last_auto_target_page.borrow().clone() };
+            let last_target = {
+                self.last_auto_target_page
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone()
+            };
             if target_page == last_target {
                 // Target hasn't changed, swallow the event
                 verbose_log!(
@@ -925,7 +4184,10 @@ impl PagedDevice {
             target_page,
             force_change
         );
-        self.last_auto_target_page.replace(target_page.clone());
+        *self
+            .last_auto_target_page
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = target_page.clone();
 
         // If we found a matching page, switch to it
         if let Some(page_name) = target_page {
@@ -939,12 +4201,20 @@ impl PagedDevice {
         match self.pages.restore_mode {
             FocusChangeRestorePolicy::Last => {
                 // Restore to last active page if available
-                let last_active_page = { self.last_active_page.borrow().clone() };
+                let last_active_page = {
+                    self.last_active_page
+                        .read()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .clone()
+                };
                 if let Some(last_active_page) = last_active_page {
                     if let Err(e) = self.set_page(&last_active_page, false) {
                         error_log!("{}", e);
                     }
-                    self.last_active_page.take();
+                    self.last_active_page
+                        .write()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .take();
                 }
             }
             FocusChangeRestorePolicy::Main => {
@@ -960,57 +4230,273 @@ impl PagedDevice {
                 } else {
                     error_log!("Cannot restore to main page: no pages available");
                 }
-                self.last_active_page.take();
+                self.last_active_page
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .take();
+            }
+            FocusChangeRestorePolicy::Keep => {
+                // Keep current page, do nothing
+            }
+        }
+    }
+
+    /// Render a graphic based on DrawConfig
+    /// Evaluates dynamic parameters, parses colors, and calls appropriate renderer
+    /// Get color for a value using color_map if available, otherwise use base_color
+    fn get_color_for_value(
+        &self,
+        draw_config: &DrawConfig,
+        value: f32,
+        range: (f32, f32),
+        base_color: (u8, u8, u8),
+    ) -> (u8, u8, u8) {
+        if let Some(ref color_map) = draw_config.color_map {
+            self.map_color(color_map, value, range).unwrap_or(base_color)
+        } else {
+            base_color
+        }
+    }
+
+    /// Picks the color_map color for where `value` falls within `range`
+    fn map_color(
+        &self,
+        color_map: &crate::pages::ColorMap,
+        value: f32,
+        range: (f32, f32),
+    ) -> Option<(u8, u8, u8)> {
+        let percent = if range.1 > range.0 {
+            ((value - range.0) / (range.1 - range.0) * 100.0).clamp(0.0, 100.0)
+        } else {
+            0.0
+        };
+        self.parse_color_map(color_map, color_map.ease(percent))
+    }
+
+    /// Turns a button color into a color string. Mapped colors are evaluated against the
+    /// current value of their dynamic source; `None` if it is not a number yet.
+    fn evaluate_color(&self, color: ColorConfig) -> Option<String> {
+        match color {
+            ColorConfig::Fixed(color) => Some(color),
+            ColorConfig::Mapped {
+                value,
+                range,
+                color_map,
+            } => {
+                let value: f32 = self.substitute_dynamic_params(&value).trim().parse().ok()?;
+                let (r, g, b) = self.map_color(&color_map, value, (range[0], range[1]))?;
+                Some(format!("0x{:02X}{:02X}{:02X}", r, g, b))
+            }
+        }
+    }
+
+    /// Applies a running blink or pulse animation to the background for the current
+    /// moment. Also returns the delay until the animation needs its next frame.
+    fn animate_background(
+        &self,
+        background: Option<String>,
+        blink: Option<&Animation>,
+        pulse: Option<&Animation>,
+    ) -> (Option<String>, Option<Duration>) {
+        let (animation, pulsing) = match (blink, pulse) {
+            (Some(blink), _) => (blink, false),
+            (None, Some(pulse)) => (pulse, true),
+            (None, None) => return (background, None),
+        };
+        if !animation.is_active(&|value| self.substitute_dynamic_params(value)) {
+            return (background, None);
+        }
+
+        let resolve = |color: Option<&String>| color.and_then(|c| self.resolve_color(c).ok());
+        let first = resolve(animation.colors.first()).unwrap_or((0xC0, 0, 0));
+        let second = resolve(animation.colors.get(1))
+            .or_else(|| resolve(background.as_ref()))
+            .unwrap_or((0, 0, 0));
+
+        let rate = animation.rate.clamp(0.1, 10.0) as f64;
+        let phase = (epoch_seconds() * rate).fract() as f32;
+
+        let (color, next_frame) = if pulsing {
+            let strength = (1.0 - (phase * std::f32::consts::TAU).cos()) / 2.0;
+            (graphics::interpolate_color(second, first, strength), ANIMATION_FRAME)
+        } else {
+            let duty = animation.duty.clamp(0.0, 1.0);
+            let (color, remaining) = if phase < duty {
+                (first, duty - phase)
+            } else {
+                (second, 1.0 - phase)
+            };
+            let until_flip = Duration::from_secs_f64(remaining as f64 / rate);
+            (color, until_flip.max(Duration::from_millis(20)))
+        };
+
+        (
+            Some(format!("0x{:02X}{:02X}{:02X}", color.0, color.1, color.2)),
+            Some(next_frame),
+        )
+    }
+
+    /// Schedules the next animation frame of a button. Keeps a single pending frame per
+    /// button, however often the button is redrawn in between.
+    fn schedule_animation_frame(&self, button_index: u8, delay: Duration) {
+        let now = Instant::now();
+        let mut animation_due = self
+            .animation_due
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let due = &mut animation_due[button_index as usize - 1];
+        if due.is_none_or(|at| at <= now) {
+            *due = Some(now + delay);
+            self.time_manager
+                .schedule_refresh(self.serial.clone(), button_index, delay);
+        }
+    }
+
+    /// Resolves the colors of a button's `effects` block, applied to its rendered canvas
+    fn resolve_effects(&self, effects: &Effects) -> keydeck_render::Effects {
+        let tint = effects.tint.as_ref().and_then(|c| {
+            self.resolve_color(c)
+                .map_err(|e| error_log!("Error parsing tint color: {}", e))
+                .ok()
+        });
+        let border = effects.border.as_ref().and_then(|c| {
+            self.resolve_color(c)
+                .map_err(|e| error_log!("Error parsing border color: {}", e))
+                .ok()
+        });
+        keydeck_render::Effects {
+            grayscale: effects.grayscale == Some(true),
+            tint,
+            opacity: effects.opacity,
+            corner_radius: effects.corner_radius.unwrap_or(0),
+            border: border.map(|color| (color, effects.border_width.unwrap_or(2))),
+        }
+    }
+
+    /// Resolves the value, colors and place of a graphic on a `width`x`height` button.
+    /// None when its value is not a number, or its data is empty.
+    fn resolve_graphic(
+        &self,
+        draw_config: &DrawConfig,
+        width: u32,
+        height: u32,
+    ) -> Option<Graphic> {
+        verbose_log!(
+            "Rendering graphic: type={:?}, value={}",
+            draw_config.graphic_type,
+            draw_config.value
+        );
+
+        // Evaluate dynamic parameters in value
+        let value_str = self.substitute_dynamic_params(&draw_config.value);
+
+        // Calculate position with padding or use explicit position
+        let padding = draw_config.padding.unwrap_or(5);
+        let (x, y) = match draw_config.position {
+            Some(pos) => (pos[0] as i64, pos[1] as i64),
+            None => (padding as i64, padding as i64),
+        };
+        let area = Area {
+            x,
+            y,
+            width: draw_config
+                .width
+                .unwrap_or(width.saturating_sub(2 * padding)),
+            height: draw_config
+                .height
+                .unwrap_or(height.saturating_sub(2 * padding)),
+        };
+
+        // Parse color
+        let base_color = if let Some(ref color_str) = draw_config.color {
+            self.resolve_color(color_str).unwrap_or_else(|e| {
+                error_log!("Error parsing draw color: {}", e);
+                (255, 255, 255)
+            })
+        } else {
+            (255, 255, 255)
+        };
+
+        let range = (draw_config.range[0], draw_config.range[1]);
+        // Bars fill bottom to top unless told otherwise; multiple bars stand side by side
+        let direction = match draw_config.direction.as_ref() {
+            Some(Direction::LeftToRight) => BarDirection::LeftToRight,
+            Some(Direction::RightToLeft) => BarDirection::RightToLeft,
+            Some(Direction::TopToBottom) => BarDirection::TopToBottom,
+            Some(Direction::BottomToTop) | None => BarDirection::BottomToTop,
+        };
+
+        match &draw_config.graphic_type {
+            GraphicType::Bar => {
+                let value = value_str.trim().parse::<f32>().ok()?;
+                Some(Graphic::Bar {
+                    area,
+                    value,
+                    range,
+                    color: self.get_color_for_value(draw_config, value, range, base_color),
+                    segments: draw_config.segments,
+                    direction,
+                })
+            }
+            GraphicType::Gauge => {
+                let value = value_str.trim().parse::<f32>().ok()?;
+                Some(Graphic::Gauge {
+                    area,
+                    value,
+                    range,
+                    color: self.get_color_for_value(draw_config, value, range, base_color),
+                })
+            }
+            GraphicType::QrCode => {
+                let data = value_str.trim();
+                // Dark on light, whatever the default graphic color
+                let color = match draw_config.color {
+                    Some(_) => base_color,
+                    None => (0, 0, 0),
+                };
+                (!data.is_empty()).then(|| Graphic::QrCode {
+                    area,
+                    data: data.to_string(),
+                    color,
+                })
             }
-            FocusChangeRestorePolicy::Keep => {
-                // Keep current page, do nothing
+            GraphicType::MultiBar => {
+                let values: Vec<f32> = value_str
+                    .split_whitespace()
+                    .filter_map(|s| s.parse::<f32>().ok())
+                    .collect();
+                if values.is_empty() {
+                    return None;
+                }
+                // Calculate color for each bar based on its value
+                let colors = values
+                    .iter()
+                    .map(|&value| self.get_color_for_value(draw_config, value, range, base_color))
+                    .collect();
+                Some(Graphic::MultiBar {
+                    area,
+                    values,
+                    range,
+                    colors,
+                    spacing: draw_config.bar_spacing.unwrap_or(2),
+                    segments: draw_config.segments,
+                    direction,
+                })
             }
         }
     }
 
-    /// Render a graphic based on DrawConfig
-    /// Evaluates dynamic parameters, parses colors, and calls appropriate renderer
-    /// Get color for a value using color_map if available, otherwise use base_color
-    fn get_color_for_value(
-        &self,
-        draw_config: &DrawConfig,
-        value: f32,
-        range: (f32, f32),
-        base_color: (u8, u8, u8),
-    ) -> (u8, u8, u8) {
-        if let Some(ref color_map) = draw_config.color_map {
-            let percent = if range.1 > range.0 {
-                ((value - range.0) / (range.1 - range.0) * 100.0).clamp(0.0, 100.0)
-            } else {
-                0.0
-            };
-            self.parse_color_map(color_map, percent)
-                .unwrap_or(base_color)
-        } else {
-            base_color
-        }
-    }
-
-    /// Parse color_map into format expected by graphics_renderer
+    /// Parse color_map into format expected by graphics
     fn parse_color_map(
         &self,
-        color_map: &[crate::pages::ColorMapEntry],
+        color_map: &crate::pages::ColorMap,
         value_percent: f32,
     ) -> Option<(u8, u8, u8)> {
         let mut parsed_map: Vec<(f32, (u8, u8, u8))> = Vec::new();
 
-        for entry in color_map {
-            match entry {
-                crate::pages::ColorMapEntry::Array(arr) => {
-                    // arr[0] is threshold (number), arr[1] is color (string)
-                    if let Some(threshold) = arr[0].as_f64() {
-                        if let Some(color_str) = arr[1].as_str() {
-                            if let Ok(rgb) = graphics_renderer::parse_hex_color(color_str) {
-                                parsed_map.push((threshold as f32, rgb));
-                            }
-                        }
-                    }
-                }
+        for (threshold, color_str) in color_map.stops() {
+            if let Ok(rgb) = self.resolve_color(color_str) {
+                parsed_map.push((threshold, rgb));
             }
         }
 
@@ -1021,7 +4507,7 @@ impl PagedDevice {
         // Sort by threshold
         parsed_map.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
 
-        Some(graphics_renderer::calculate_color_from_map(
+        Some(graphics::calculate_color_from_map(
             value_percent,
             &parsed_map,
         ))
@@ -1029,18 +4515,26 @@ impl PagedDevice {
 
     /// Invalidates cache and refreshes a single button with dynamic parameter evaluation.
     /// Returns error if button number is invalid or button doesn't exist in config.
-    fn invalidate_and_refresh_button(&self, button_id: u8) -> Result<(), String> {
+    fn invalidate_and_refresh_button(&self, button_id: u8) -> Result<(), Error> {
         let button_count = self.device.button_count();
 
         // Validate button range
         if button_id < 1 || button_id > button_count {
-            return Err(format!(
-                "Invalid button number: {} (valid range: 1-{})",
-                button_id, button_count
-            ));
+            return Err(Error::InvalidButton {
+                button: button_id,
+                count: button_count,
+            });
+        }
+
+        // Devices without a display have nothing to render; built-in pages must stay intact
+        if !self.device.has_screen() || self.keys_covered() {
+            return Ok(());
         }
 
-        let current_page = { self.current_page_ref.borrow().clone() };
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
 
         // If button has no config, nothing to refresh
         let button = match self.find_button(current_page, button_id) {
@@ -1050,60 +4544,125 @@ impl PagedDevice {
 
         // Invalidate cache for this button
         {
-            let mut button_images = self.button_images.borrow_mut();
-            let mut button_backgrounds = self.button_backgrounds.borrow_mut();
+            let mut button_images = self
+                .button_images
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            let mut button_backgrounds = self
+                .button_backgrounds
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
             button_images[button_id as usize - 1] = String::new();
             button_backgrounds[button_id as usize - 1] = String::new();
         }
 
         // Re-render button (update_button will evaluate dynamic params internally)
         let mut invalid_indices = Vec::new();
-        if let Some(icon) = &button.icon {
-            self.update_button(
-                icon,
-                self.image_dir.clone(),
-                button.background.clone(),
-                button.draw.clone(),
-                button.text.clone(),
-                button.outline.clone(),
-                button.text_color.clone(),
-                button_id,
-                &mut invalid_indices,
-            );
-        } else {
-            self.update_button(
-                "",
-                None,
-                button.background.clone(),
-                button.draw.clone(),
-                button.text.clone(),
-                button.outline.clone(),
-                button.text_color.clone(),
-                button_id,
-                &mut invalid_indices,
-            );
-        }
+        self.update_button(
+            button.icon.as_ref(),
+            button.background.clone(),
+            button.draw.clone(),
+            button.text.clone(),
+            button.outline.clone(),
+            button.text_color.clone(),
+            button.blink.as_ref(),
+            button.pulse.as_ref(),
+            self.button_effects(button).as_ref(),
+            button_id,
+            &mut invalid_indices,
+        );
 
         // Flush to device
-        self.device
-            .flush()
-            .map_err(|e| format!("Failed to flush device: {}", e))?;
+        self.device.flush().map_err(|e| self.device_error(e))
+    }
 
-        Ok(())
+    /// The images of an icon that are found, bottom layer first
+    fn icon_layers(&self, icon: Option<&Icon>) -> Vec<IconImage> {
+        let icon_paths = self
+            .icon_paths
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        button_look::icon_layers(icon, &icon_paths, |image| {
+            self.substitute_dynamic_params(image)
+        })
+    }
+
+    /// The image of the wallpaper of the current page, when it is found, with its gap
+    fn current_wallpaper(&self) -> Option<(String, f32)> {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (_, page) = self.pages.pages.get_index(current_page)?;
+        let wallpaper = page.wallpaper.as_ref()?;
+        let image = wallpaper.image();
+        match self
+            .icon_paths
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .resolve(image)
+        {
+            Some(path) => Some((path.to_string_lossy().into_owned(), wallpaper.gap())),
+            None => {
+                warn_log!("Wallpaper not found: {}", image);
+                None
+            }
+        }
     }
 
     fn update_button(
         &self,
-        image: &str,
-        image_path: Option<String>,
-        background: Option<String>,
+        icon: Option<&Icon>,
+        background: Option<ColorConfig>,
         draw: Option<Vec<DrawConfig>>,
         text: Option<TextConfig>,
         outline: Option<String>,
-        text_color: Option<String>,
+        text_color: Option<ColorConfig>,
+        blink: Option<&Animation>,
+        pulse: Option<&Animation>,
+        effects: Option<&Effects>,
         button_index: u8,
         invalid_indices: &mut Vec<u8>,
     ) {
+        let started = Instant::now();
+        // A pending error flash overrides the configured background
+        let flashing = self
+            .button_flash_until
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)[button_index as usize - 1]
+            .is_some_and(|until| Instant::now() < until);
+        let theme = self.current_theme();
+        let wallpaper = self.current_wallpaper();
+        // Nothing moves in game mode, so keys are only written when their content changes
+        let still = self.game_mode.load(Ordering::Relaxed);
+        let (blink, pulse) = if still { (None, None) } else { (blink, pulse) };
+        let (mut background, mut next_frame) = if flashing {
+            (Some(ERROR_FLASH_COLOR.to_string()), None)
+        } else {
+            // The wallpaper of the page takes the place of the background of the theme
+            let background = background.and_then(|c| self.evaluate_color(c)).or_else(|| {
+                theme
+                    .filter(|_| wallpaper.is_none())
+                    .and_then(|t| t.background.clone())
+            });
+            self.animate_background(background, blink, pulse)
+        };
+        let text_color = text_color
+            .and_then(|c| self.evaluate_color(c))
+            .or_else(|| theme.and_then(|t| t.text_color.clone()));
+        let outline = outline.or_else(|| theme.and_then(|t| t.outline.clone()));
+        let font = theme.and_then(|t| t.font.as_deref());
+
+        // High-contrast labels are outlined and keep their minimum size, scrolling when
+        // they do not fit
+        let min_font_size = self.high_contrast_font_size();
+        let outline = outline.or_else(|| min_font_size.map(|_| "black".to_string()));
+        let text = match (text, min_font_size) {
+            (Some(text), Some(min_size)) => Some(high_contrast_text(text, min_size)),
+            (text, _) => text,
+        };
+
         // Get the button size from the device, reduced by press effect canvas requirements
         let (device_w, device_h) = {
             let (w, h) = self.device.button_image_size();
@@ -1116,58 +4675,89 @@ impl PagedDevice {
         };
         let (width, height) = (device_w - reduce_w, device_h - reduce_h);
 
-        // Determine if we're rendering text or using an icon
-        let has_text = text.is_some();
-        let mut text_str = if let Some(ref text_cfg) = text {
-            match text_cfg {
-                TextConfig::Simple(s) => s.clone(),
-                TextConfig::Detailed { value, .. } => value.clone(),
-            }
-        } else {
-            String::new()
-        };
-
-        // Evaluate dynamic parameters in text (${time:}, ${env:}, ${service:})
-        if !text_str.is_empty() && text_str.contains("${") {
-            let params = evaluate_dynamic_params(
-                &text_str,
-                &self.services_config,
-                &self.services_state,
-                &self.services_active,
-                &self.context_vars,
-            );
-            // Substitute parameters
-            for (pattern, value) in params {
-                let full_pattern = format!("${{{}}}", pattern);
-                text_str = text_str.replace(&full_pattern, &value);
-            }
-        }
-
-        // Process escape sequences (\n, \t, \r, \\, \e) for display
-        if !text_str.is_empty() {
-            text_str = process_escape_sequences(&text_str).into_iter().collect();
-        }
+        // A timer shows the time left, redrawn as it passes
+        let timer = self.timer_view(button_index);
 
-        // Find the icon path if provided
-        let image_exists = if image.len() > 0 {
-            find_path(image, image_path.clone())
-        } else {
-            Some(image.to_string())
-        };
-        let image_path = if let Some(image) = image_exists {
-            image
-        } else {
-            if image.len() > 0 {
-                warn_log!("Image not found: {}", image);
+        // Determine if we're rendering text or using an icon
+        let has_text = text.is_some() || timer.is_some();
+        // Dynamic parameters in text (${time:}, ${env:}, ${service:}) are evaluated
+        let mut text_str = text
+            .as_ref()
+            .map(|text| button_look::label_text(text, |s| self.substitute_dynamic_params(s)))
+            .unwrap_or_default();
+
+        // The time left goes under the label; a timer that is up blinks in its color
+        let timer_ring = match &timer {
+            Some((config, state)) => {
+                let duration = Duration::from_secs_f32(config.duration.max(0.0));
+                let left = TimerState::left(*state, duration);
+                let seconds = left.as_secs_f64().ceil();
+                if matches!(state, Some(TimerState::Running { .. })) && seconds > 0.0 {
+                    let until_change = left.as_secs_f64() - (seconds - 1.0);
+                    let delay = Duration::from_secs_f64(until_change) + Duration::from_millis(10);
+                    next_frame = Some(next_frame.map_or(delay, |d| d.min(delay)));
+                }
+                let time = format_timer(seconds as u64);
+                text_str = match text_str.is_empty() {
+                    true => time,
+                    false => format!("{}\n{}", text_str, time),
+                };
+                let color = config
+                    .color
+                    .as_ref()
+                    .and_then(|c| self.resolve_color(c).ok())
+                    .unwrap_or(TIMER_COLOR);
+                if *state == Some(TimerState::Ringing) && config.blink && !flashing {
+                    // Nothing moves in game mode, so the key stays lit instead
+                    let phase = match still {
+                        true => 0.0,
+                        false => (epoch_seconds() * TIMER_BLINK_RATE).fract(),
+                    };
+                    if phase < 0.5 {
+                        let (r, g, b) = color;
+                        background = Some(format!("0x{:02X}{:02X}{:02X}", r, g, b));
+                    }
+                    if !still {
+                        let phase_end = if phase < 0.5 { 0.5 } else { 1.0 };
+                        let delay = Duration::from_secs_f64((phase_end - phase) / TIMER_BLINK_RATE);
+                        next_frame = Some(next_frame.map_or(delay, |d| d.min(delay)));
+                    }
+                }
+                let fraction = match duration.is_zero() {
+                    true => 0.0,
+                    false => left.as_secs_f32() / duration.as_secs_f32(),
+                };
+                let color = match state {
+                    Some(TimerState::Paused { .. }) => {
+                        graphics::interpolate_color(color, (0, 0, 0), 0.5)
+                    }
+                    _ => color,
+                };
+                (config.style == TimerStyle::Ring).then_some((fraction, color))
             }
-            "".to_string()
+            None => None,
         };
+        let timer_str = timer_ring
+            .map(|(fraction, color)| format!("{:.3}{:?}", fraction, color))
+            .unwrap_or_default();
+
+        // Find the icon images if provided, bottom layer first
+        let layers = self.icon_layers(icon);
+        let image_path = layers
+            .iter()
+            .map(|layer| format!("{}@{:?}*{}", layer.path, layer.position, layer.scale))
+            .collect::<Vec<_>>()
+            .join("|");
 
         let bg_color_str = if let Some(bg_color) = background.as_ref() {
             bg_color.as_str()
         } else {
             ""
         };
+        let wallpaper_str = wallpaper
+            .as_ref()
+            .map(|(path, gap)| format!("{}@{}", path, gap))
+            .unwrap_or_default();
 
         // Extract font_size from TextConfig if available
         let font_size_str = if let Some(TextConfig::Detailed { font_size, .. }) = &text {
@@ -1179,16 +4769,81 @@ impl PagedDevice {
         let text_color_str = text_color.as_deref().unwrap_or("");
         let outline_str = outline.as_deref().unwrap_or("");
 
+        // Named colors and the font depend on the theme, so it is part of the key as well
+        let theme_str = self.current_theme_name().unwrap_or_default();
+
+        // Text that does not fit scrolls by, at a position derived from the wall clock
+        let marquee_scroll = match &text {
+            Some(TextConfig::Detailed {
+                marquee: Some(speed),
+                font_size,
+                ..
+            }) if *speed > 0.0
+                && !still
+                && needs_marquee(width, height, &text_str, *font_size, font, min_font_size) =>
+            {
+                next_frame = Some(next_frame.map_or(ANIMATION_FRAME, |d| d.min(ANIMATION_FRAME)));
+                Some(epoch_seconds() * *speed as f64)
+            }
+            _ => None,
+        };
+        let marquee_str = marquee_scroll
+            .map(|scroll| (scroll as i64).to_string())
+            .unwrap_or_default();
+        let effects_str = effects.map(|e| format!("{:?}", e)).unwrap_or_default();
+
+        // A cooldown with countdown shows its remaining whole seconds until it is over
+        let countdown = match self.cooldown_remaining(button_index) {
+            Some((remaining, true)) => {
+                let seconds = remaining.as_secs_f64().ceil();
+                let until_change = remaining.as_secs_f64() - (seconds - 1.0);
+                let delay = Duration::from_secs_f64(until_change) + Duration::from_millis(10);
+                next_frame = Some(next_frame.map_or(delay, |d| d.min(delay)));
+                Some(seconds as u64)
+            }
+            _ => None,
+        };
+        let countdown_str = countdown.map(|c| c.to_string()).unwrap_or_default();
+
+        let heat = self.button_heat(button_index);
+        let heat_str = heat.map(|h| format!("{:.2}", h)).unwrap_or_default();
+
+        let pin_badge =
+            self.pinned.load(Ordering::Relaxed) && self.pages.pin_indicator == Some(button_index);
+
+        if let Some(delay) = next_frame {
+            self.schedule_animation_frame(button_index, delay);
+        }
+
         // Create cache key including all visual properties that affect rendering
         let cache_key = format!(
-            "{}:{}:{}:{}:{}:{}",
-            image_path, bg_color_str, text_str, text_color_str, outline_str, font_size_str
+            "{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}",
+            image_path,
+            bg_color_str,
+            wallpaper_str,
+            text_str,
+            text_color_str,
+            outline_str,
+            font_size_str,
+            theme_str,
+            marquee_str,
+            effects_str,
+            countdown_str,
+            timer_str,
+            heat_str,
+            pin_badge
         );
 
         {
             // Check if the button state is the same as the current one
-            let mut button_images = self.button_images.borrow_mut();
-            let mut button_backgrounds = self.button_backgrounds.borrow_mut();
+            let mut button_images = self
+                .button_images
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            let mut button_backgrounds = self
+                .button_backgrounds
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
             if button_images[button_index as usize - 1] == cache_key
                 && button_backgrounds[button_index as usize - 1] == bg_color_str
             {
@@ -1201,277 +4856,120 @@ impl PagedDevice {
         }
 
         // If button has no content at all, clear it so background shows through
-        let has_content = background.is_some() || !image_path.is_empty() || !text_str.is_empty() || draw.is_some();
+        let has_content = background.is_some() || wallpaper.is_some() || !image_path.is_empty() || !text_str.is_empty() || draw.is_some() || pin_badge;
         if !has_content {
-            self.button_canvases.borrow_mut()[button_index as usize - 1] = None;
+            self.button_canvases
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)[button_index as usize - 1] = None;
             self.device
                 .clear_button_image(button_index - 1)
                 .unwrap_or_else(|e| error_log!("Error while clearing button image: {}", e));
             return;
         }
 
-        // Simple linear pipeline: Create ONE canvas, then modify it step by step
+        // Everything drawn is resolved first: colors, values, the wallpaper and the icons
+        let mut spec = ButtonSpec {
+            background: background
+                .as_ref()
+                .map(|bg| self.resolve_color(bg).unwrap_or((0, 0, 0))),
+            ring: timer_ring,
+            countdown,
+            heat,
+            badge: pin_badge.then_some(PIN_BADGE_COLOR),
+            ..Default::default()
+        };
+        // Without a background of its own the key shows its part of the wallpaper
+        if let (None, Some((path, gap))) = (&background, &wallpaper) {
+            let (rows, cols) = self.device.button_layout();
+            let grid = KeyGrid::new(rows as u32, cols as u32, width, height, *gap);
+            match wallpaper::slice(path, &grid, button_index) {
+                Ok(slice) => spec.wallpaper = Some(slice),
+                Err(e) => error_log!("Error while loading wallpaper {}: {}", path, e),
+            }
+        }
 
-        // Step 1: Create base canvas with background color
-        let bg_color = if let Some(ref bg) = background {
-            let (r, g, b) = string_to_color(bg, &self.colors).unwrap_or((0, 0, 0));
-            Rgba([r, g, b, 255])
-        } else {
-            Rgba([0, 0, 0, 0]) // Transparent when no background; flattened to black for JPEG/BMP
-        };
-        let mut canvas = RgbaImage::from_pixel(width, height, bg_color);
-
-        // Step 2: Overlay icon image if provided (scaled with Lanczos filter)
-        if !image_path.is_empty() {
-            match open(&image_path) {
-                Ok(icon_img) => {
-                    let img_width = icon_img.width();
-                    let img_height = icon_img.height();
-
-                    // Calculate scaling factor to fit while maintaining aspect ratio
-                    let scale_x = width as f32 / img_width as f32;
-                    let scale_y = height as f32 / img_height as f32;
-                    let scale = scale_x.min(scale_y);
-
-                    let new_width = (img_width as f32 * scale) as u32;
-                    let new_height = (img_height as f32 * scale) as u32;
-
-                    // Center the image
-                    let x_offset = (width - new_width) / 2;
-                    let y_offset = (height - new_height) / 2;
-
-                    // Resize and overlay with Lanczos filter
-                    let resized = icon_img.resize_exact(
-                        new_width,
-                        new_height,
-                        image::imageops::FilterType::Lanczos3,
-                    );
-                    overlay(&mut canvas, &resized, x_offset as i64, y_offset as i64);
-                }
+        // Icon images, bottom layer first, scaled with Lanczos filter once per size through
+        // the icon atlas
+        for layer in &layers {
+            match layer.layer(width, height) {
+                Ok(icon) => spec.icons.push(icon),
                 Err(_) => {
-                    error_log!("Error while opening image: {}", image_path);
+                    error_log!("Error while opening image: {}", layer.path);
                     invalid_indices.push(button_index);
                     return;
                 }
             }
         }
 
-        // Step 3: Render graphics array directly on the canvas
         // Graphics are drawn in order (first item drawn first, last item on top)
-        if let Some(ref draw_configs) = draw {
-            for draw_config in draw_configs {
-                verbose_log!(
-                    "Rendering graphic: type={:?}, value={}",
-                    draw_config.graphic_type,
-                    draw_config.value
-                );
-
-                // Evaluate dynamic parameters in value
-                let mut value_str = draw_config.value.clone();
-                if value_str.contains("${") {
-                    let params = evaluate_dynamic_params(
-                        &value_str,
-                        &self.services_config,
-                        &self.services_state,
-                        &self.services_active,
-                        &self.context_vars,
-                    );
-                    for (pattern, value) in params {
-                        let full_pattern = format!("${{{}}}", pattern);
-                        value_str = value_str.replace(&full_pattern, &value);
-                    }
-                }
-
-                // Calculate position with padding or use explicit position
-                let (x, y) = if let Some(pos) = draw_config.position {
-                    (pos[0] as i64, pos[1] as i64)
-                } else {
-                    let padding = draw_config.padding.unwrap_or(5) as i64;
-                    (padding, padding)
-                };
-
-                // Calculate dimensions
-                let padding = draw_config.padding.unwrap_or(5);
-                let draw_width = draw_config
-                    .width
-                    .unwrap_or(width.saturating_sub(2 * padding));
-                let draw_height = draw_config
-                    .height
-                    .unwrap_or(height.saturating_sub(2 * padding));
-
-                // Parse color
-                let base_color = if let Some(ref color_str) = draw_config.color {
-                    graphics_renderer::parse_hex_color(color_str).unwrap_or_else(|e| {
-                        error_log!("Error parsing draw color: {}", e);
-                        (255, 255, 255)
-                    })
-                } else {
-                    (255, 255, 255)
-                };
-
-                let range = (draw_config.range[0], draw_config.range[1]);
-
-                // Render based on graphic type
-                match &draw_config.graphic_type {
-                    GraphicType::Bar => {
-                        if let Ok(value) = value_str.trim().parse::<f32>() {
-                            let color =
-                                self.get_color_for_value(draw_config, value, range, base_color);
-
-                            // Determine direction from optional direction field
-                            let direction = match draw_config.direction.as_ref() {
-                                Some(Direction::LeftToRight) => {
-                                    graphics_renderer::BarDirection::LeftToRight
-                                }
-                                Some(Direction::RightToLeft) => {
-                                    graphics_renderer::BarDirection::RightToLeft
-                                }
-                                Some(Direction::TopToBottom) => {
-                                    graphics_renderer::BarDirection::TopToBottom
-                                }
-                                Some(Direction::BottomToTop) => {
-                                    graphics_renderer::BarDirection::BottomToTop
-                                }
-                                None => graphics_renderer::BarDirection::BottomToTop, // Default: bottom to top
-                            };
-
-                            graphics_renderer::render_bar(
-                                &mut canvas,
-                                x,
-                                y,
-                                value,
-                                range,
-                                draw_width,
-                                draw_height,
-                                color,
-                                draw_config.segments,
-                                direction,
-                            );
-                        }
-                    }
-                    GraphicType::Gauge => {
-                        if let Ok(value) = value_str.trim().parse::<f32>() {
-                            let color =
-                                self.get_color_for_value(draw_config, value, range, base_color);
-                            graphics_renderer::render_gauge(
-                                &mut canvas,
-                                x,
-                                y,
-                                value,
-                                range,
-                                draw_width,
-                                draw_height,
-                                color,
-                            );
-                        }
-                    }
-                    GraphicType::MultiBar => {
-                        let values: Vec<f32> = value_str
-                            .split_whitespace()
-                            .filter_map(|s| s.parse::<f32>().ok())
-                            .collect();
-                        if !values.is_empty() {
-                            let bar_spacing = draw_config.bar_spacing.unwrap_or(2);
-
-                            // Calculate color for each bar based on its value
-                            let colors: Vec<(u8, u8, u8)> = values
-                                .iter()
-                                .map(|&value| {
-                                    self.get_color_for_value(draw_config, value, range, base_color)
-                                })
-                                .collect();
-
-                            // Determine direction
-                            let direction = match draw_config.direction.as_ref() {
-                                Some(Direction::LeftToRight) => {
-                                    graphics_renderer::BarDirection::LeftToRight
-                                }
-                                Some(Direction::RightToLeft) => {
-                                    graphics_renderer::BarDirection::RightToLeft
-                                }
-                                Some(Direction::TopToBottom) => {
-                                    graphics_renderer::BarDirection::TopToBottom
-                                }
-                                Some(Direction::BottomToTop) => {
-                                    graphics_renderer::BarDirection::BottomToTop
-                                }
-                                None => graphics_renderer::BarDirection::BottomToTop, // Default: vertical bars side-by-side
-                            };
-
-                            graphics_renderer::render_multi_bar(
-                                &mut canvas,
-                                x,
-                                y,
-                                &values,
-                                range,
-                                draw_width,
-                                draw_height,
-                                &colors,
-                                bar_spacing,
-                                draw_config.segments,
-                                direction,
-                            );
-                        }
-                    }
-                }
-            }
-        }
+        spec.graphics = draw
+            .iter()
+            .flatten()
+            .filter_map(|draw_config| self.resolve_graphic(draw_config, width, height))
+            .collect();
 
-        // Step 4: Render text on the canvas
         if has_text {
             verbose_log!("Rendering text '{}' on canvas", text_str);
-            let font_size = if let Some(TextConfig::Detailed { font_size, .. }) = text {
-                font_size
-            } else {
-                None
-            };
-
-            // Parse outline color if provided
-            let outline_rgb = if let Some(ref outline_str) = outline {
-                match string_to_color(outline_str, &self.colors) {
-                    Ok((r, g, b)) => Some([r, g, b]),
-                    Err(_) => None,
-                }
-            } else {
-                None
-            };
-
-            // Parse text color if provided (defaults to white in renderer)
-            let text_color_rgba = if let Some(ref color_str) = text_color {
-                match string_to_color(color_str, &self.colors) {
-                    Ok((r, g, b)) => Some(image::Rgba([r, g, b, 255u8])),
-                    Err(_) => None,
-                }
-            } else {
-                None
+            let font_size = match text {
+                Some(TextConfig::Detailed { font_size, .. }) => font_size,
+                _ => None,
             };
-
-            // Render text directly onto the canvas
-            text_renderer::render_text_on_canvas(
-                &mut canvas,
-                &text_str,
+            spec.label = Some(Label {
+                text: text_str,
                 font_size,
-                text_color_rgba,
-                outline_rgb,
-            );
+                // Defaults to white in the renderer
+                color: text_color.and_then(|c| self.resolve_color(&c).ok()),
+                outline: outline.and_then(|c| self.resolve_color(&c).ok()),
+                font: font.map(str::to_string),
+                marquee: marquee_scroll,
+            });
+        }
+        if let Some(effects) = effects {
+            spec.effects = self.resolve_effects(effects);
         }
 
-        // Cache the unmodified canvas for future re-renders
-        self.button_canvases.borrow_mut()[button_index as usize - 1] = Some(canvas.clone());
+        // The previous canvas of the button is drawn over again, sparing an allocation on
+        // every frame of an animation
+        let previous = self
+            .button_canvases
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)[button_index as usize - 1]
+            .take();
+        let mut canvas = match previous {
+            Some(previous) if previous.dimensions() == (width, height) => previous,
+            _ => RgbaImage::new(width, height),
+        };
+        for problem in keydeck_render::render_into(&spec, &mut canvas) {
+            warn_log!("{}", problem);
+        }
 
         // Compose final image with press effect (always, for translate/emboss border)
-        let final_canvas = if self.device.supports_button_press_feedback() {
-            let pressed = self.button_pressed.borrow()[button_index as usize - 1];
+        let mut final_canvas = if self.device.supports_button_press_feedback() {
+            let pressed = self
+                .button_pressed
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)[button_index as usize - 1];
             let border_rgba = self
                 .pages
                 .press_effect
                 .border_color()
-                .and_then(|c| string_to_color(c, &self.colors).ok())
+                .and_then(|c| self.resolve_color(c).ok())
                 .map(|(r, g, b)| Rgba([r, g, b, 255]));
             compose_button(&canvas, device_w, device_h, &self.pages.press_effect, pressed, border_rgba)
         } else {
-            canvas
+            canvas.clone()
         };
+        // Keep the unmodified canvas for the next render of the button
+        self.button_canvases
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)[button_index as usize - 1] = Some(canvas);
+        if let Some(filter) = *self
+            .night_filter
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+        {
+            filter.apply(&mut final_canvas);
+        }
 
         let image_data = DynamicImage::ImageRgba8(final_canvas);
 
@@ -1479,6 +4977,7 @@ impl PagedDevice {
         self.device
             .set_button_image(button_index - 1, image_data)
             .unwrap_or_else(|e| error_log!("Error while setting button image: {}", e));
+        bench::record(Probe::Render(button_index), started);
     }
 
     /// Clear a button and its cache entry
@@ -1491,13 +4990,24 @@ impl PagedDevice {
             });
 
         // Clear the cache for this button so it can be redrawn properly next time
-        let mut button_images = self.button_images.borrow_mut();
-        let mut button_backgrounds = self.button_backgrounds.borrow_mut();
+        let mut button_images = self
+            .button_images
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut button_backgrounds = self
+            .button_backgrounds
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
         button_images[button_index as usize - 1] = String::new();
         button_backgrounds[button_index as usize - 1] = String::new();
     }
 
     fn refresh_page(&self) {
+        // Non-display devices (e.g. Stream Deck Pedal) only map buttons to actions
+        if !self.device.has_screen() || self.keys_covered() {
+            return;
+        }
+
         // If no valid page is set, clear all buttons and return
         if !self.has_valid_page() {
             let button_count = self.device.button_count();
@@ -1510,62 +5020,116 @@ impl PagedDevice {
             return;
         }
 
-        let button_count = self.device.button_count();
-        let current_page = { self.current_page_ref.borrow().clone() };
+        self.draw_keys(1..=self.device.button_count());
+        self.render_encoder_widgets();
+    }
+
+    /// Draws `keys` of the current page again, e.g. the ones a reload changed
+    fn redraw_keys(&self, keys: &[u8]) {
+        if !self.device.has_screen() || self.keys_covered() || !self.has_valid_page() {
+            return;
+        }
+        {
+            let mut button_images = self
+                .button_images
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            let mut button_backgrounds = self
+                .button_backgrounds
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            for &key in keys {
+                button_images[key as usize - 1] = String::new();
+                button_backgrounds[key as usize - 1] = String::new();
+            }
+        }
+        self.draw_keys(keys.iter().copied());
+    }
+
+    /// Draws `keys` of the current page, clearing the ones without a button unless the page
+    /// has a wallpaper, and sends them to the device
+    fn draw_keys(&self, keys: impl Iterator<Item = u8>) {
+        let current_page = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        // Keys without a button still show their part of the wallpaper
+        let wallpaper = self
+            .pages
+            .pages
+            .get_index(current_page)
+            .is_some_and(|(_, page)| page.wallpaper.is_some());
         let mut invalid_indices = Vec::new();
-        for button_index in 1..=button_count {
+        for button_index in keys {
             if let Some(button) = self.find_button(current_page, button_index).as_ref() {
-                if let Some(icon) = &button.icon {
-                    self.update_button(
-                        icon,
-                        self.image_dir.clone(),
-                        button.background.clone(),
-                        button.draw.clone(),
-                        button.text.clone(),
-                        button.outline.clone(),
-                        button.text_color.clone(),
-                        button_index,
-                        &mut invalid_indices,
-                    );
-                } else {
-                    self.update_button(
-                        "",
-                        None,
-                        button.background.clone(),
-                        button.draw.clone(),
-                        button.text.clone(),
-                        button.outline.clone(),
-                        button.text_color.clone(),
-                        button_index,
-                        &mut invalid_indices,
-                    );
-                }
+                self.update_button(
+                    button.icon.as_ref(),
+                    button.background.clone(),
+                    button.draw.clone(),
+                    button.text.clone(),
+                    button.outline.clone(),
+                    button.text_color.clone(),
+                    button.blink.as_ref(),
+                    button.pulse.as_ref(),
+                    self.button_effects(button).as_ref(),
+                    button_index,
+                    &mut invalid_indices,
+                );
+            } else if wallpaper {
+                self.update_button(
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    button_index,
+                    &mut invalid_indices,
+                );
             } else {
                 self.clear_button(button_index);
             }
         }
+        let started = Instant::now();
         self.device
             .flush()
             .unwrap_or_else(|e| error_log!("Error while flushing device: {}", e));
+        bench::record(Probe::Flush, started);
         // Process all invalid button indices
         for &button_index in &invalid_indices {
             self.clear_button(button_index);
         }
     }
 
-    fn set_page(&self, page_name: &String, is_auto: bool) -> Result<(), String> {
+    fn set_page(&self, page_name: &String, is_auto: bool) -> Result<(), Error> {
         let page = self.pages.pages.get_index_of(page_name);
         if let Some(page) = page {
-            let old_page = { self.current_page_ref.borrow_mut().clone() };
+            let old_page = {
+                *self
+                    .current_page_ref
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+            };
             if page != old_page {
                 detail_log!("[{}] Page changed to '{}'", self.serial, page_name);
 
                 if is_auto {
-                    if self.last_active_page.borrow().is_none() {
+                    if self
+                        .last_active_page
+                        .read()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .is_none()
+                    {
                         // only if the page that the old_page refers to is not locked, update the active page
                         if let Some((name, target_page)) = self.pages.pages.get_index(old_page) {
                             if !target_page.lock.unwrap_or(false) {
-                                self.last_active_page.replace(Some(name.clone()));
+                                *self
+                                    .last_active_page
+                                    .write()
+                                    .unwrap_or_else(PoisonError::into_inner) = Some(name.clone());
                             }
                         }
                     }
@@ -1576,16 +5140,55 @@ impl PagedDevice {
                         .get_index(page)
                         .map_or(true, |(_, target_page)| !target_page.lock.unwrap_or(false))
                     {
-                        self.last_active_page.take();
+                        self.last_active_page
+                            .write()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .take();
                     }
                 }
-                self.current_page_ref.replace(page);
-                self.button_pressed.borrow_mut().iter_mut().for_each(|p| *p = false);
+                self.add_dwell_time();
+                if let Some((old_name, _)) = self.pages.pages.get_index(old_page) {
+                    processes::page_left(&self.serial, old_name);
+                    let mut history = self
+                        .page_history
+                        .write()
+                        .unwrap_or_else(PoisonError::into_inner);
+                    history.push(old_name.clone());
+                    if history.len() > PAGE_HISTORY {
+                        history.remove(0);
+                    }
+                }
+                *self
+                    .current_page_ref
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner) = page;
+                *self
+                    .rotated_at
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = Instant::now();
+                live_events::publish(LiveEvent::Page {
+                    sn: self.serial.clone(),
+                    page: page_name.clone(),
+                });
+                dbus_state::update(&self.serial, |status| status.page = page_name.clone());
+                self.announce(page_name);
+                if let Some(profile) = self.find_page(page).and_then(|p| p.openrgb.clone()) {
+                    openrgb::load_profile_in_background(profile, &self.event_tx);
+                }
+                self.button_pressed
+                    .write()
+                    .unwrap()
+                    .iter_mut()
+                    .for_each(|p| *p = false);
+                self.active_layer
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .take();
                 self.refresh_page();
             }
             Ok(())
         } else {
-            Err(format!("Page not found: {}", page_name))
+            Err(Error::PageNotFound(page_name.clone()))
         }
     }
 
@@ -1594,14 +5197,18 @@ impl PagedDevice {
     }
 
     fn has_valid_page(&self) -> bool {
-        let current = *self.current_page_ref.borrow();
+        let current = *self
+            .current_page_ref
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
         current != usize::MAX && self.pages.pages.get_index(current).is_some()
     }
 
     fn find_button(&self, page_id: usize, button_id: u8) -> Option<&Button> {
         let key = format!("button{}", button_id); // Generate the key based on button_id
         let page = self.find_page(page_id)?;
-        if let Some(bc) = page.buttons.get(&key) {
+        let layered = self.layer_button(page_id, page, &key, button_id);
+        if let Some(bc) = layered.or_else(|| page.buttons.get(&key)) {
             match bc {
                 ButtonConfig::Template(template) => {
                     match self.button_templates.as_ref().as_ref()?.get(template) {
@@ -1619,6 +5226,26 @@ impl PagedDevice {
         }
     }
 
+    /// The button on `key` of the layer shown over the page `page_id`, except on the
+    /// `modifier` button holding the layer
+    fn layer_button<'a>(
+        &self,
+        page_id: usize,
+        page: &'a Page,
+        key: &str,
+        button_id: u8,
+    ) -> Option<&'a ButtonConfig> {
+        let active_layer = self
+            .active_layer
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let (layer_page, layer, modifier) = active_layer.as_ref()?;
+        if *layer_page != page_id || *modifier == button_id {
+            return None;
+        }
+        page.layers.as_ref()?.get(layer)?.get(key)
+    }
+
     fn find_encoder(&self, page_id: usize, encoder_id: u8) -> Option<&Encoder> {
         let key = format!("encoder{}", encoder_id);
         let page = self.find_page(page_id)?;
@@ -1626,37 +5253,417 @@ impl PagedDevice {
     }
 }
 
-fn string_to_color(
-    color: &str,
-    named_colors: &Option<IndexMap<String, String>>,
-) -> Result<(u8, u8, u8), String> {
-    if (color.len() == 8 || color.len() == 10) && color.starts_with("0x") {
-        let offset = if color.len() == 10 { 2 } else { 0 };
-        let a = if color.len() == 10 {
-            u8::from_str_radix(&color[2..4], 16)
-                .map_err(|_| format!("Invalid color format: {}", color))?
-        } else {
-            255
-        };
-        let r = u8::from_str_radix(&color[offset + 2..offset + 4], 16)
-            .map_err(|_| format!("Invalid color format: {}", color))?;
-        let g = u8::from_str_radix(&color[offset + 4..offset + 6], 16)
-            .map_err(|_| format!("Invalid color format: {}", color))?;
-        let b = u8::from_str_radix(&color[offset + 6..offset + 8], 16)
-            .map_err(|_| format!("Invalid color format: {}", color))?;
-
-        // Assuming the background color is 0,0,0
-        let alpha = a as f32 / 255.0;
-        let final_r = (r as f32 * alpha).round() as u8;
-        let final_g = (g as f32 * alpha).round() as u8;
-        let final_b = (b as f32 * alpha).round() as u8;
-        Ok((final_r, final_g, final_b))
-    } else {
-        if let Some(idx_named_colors) = named_colors {
-            if let Some(idx_color) = idx_named_colors.get(color) {
-                return string_to_color(idx_color, named_colors);
-            }
-        }
-        Err(format!("Unable to find named color '{}'", color))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::new_context_vars;
+    use crate::pages::KeyDeckConf;
+    use crate::services::new_services_state;
+    use crate::test_device::TestDevice;
+    use std::path::Path;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::sync::Once;
+
+    /// A test deck with a screen showing the `default` pages of `yaml`, and the events it
+    /// sends
+    fn deck(yaml: &str) -> (PagedDevice, Receiver<DeviceEvent>) {
+        deck_of(
+            TestDevice {
+                screen: true,
+                ..Default::default()
+            },
+            yaml,
+        )
+    }
+
+    /// `device` showing the `default` pages of `yaml`, and the events it sends
+    fn deck_of(device: TestDevice, yaml: &str) -> (PagedDevice, Receiver<DeviceEvent>) {
+        // The action history goes to a directory of the tests instead of the user's
+        static ISOLATED: Once = Once::new();
+        ISOLATED.call_once(|| {
+            let dir = std::env::temp_dir().join(format!("keydeck-deck-{}", std::process::id()));
+            std::env::set_var("XDG_CONFIG_HOME", &dir);
+            std::env::set_var("XDG_STATE_HOME", &dir);
+        });
+        let conf: KeyDeckConf = serde_yaml_ng::from_str(yaml).unwrap();
+        let (tx, rx) = channel();
+        let device = PagedDevice::new(
+            Arc::new(conf.page_groups["default"].clone()),
+            Arc::new(IconPaths::new(Path::new("/nonexistent/config.yaml"), None)),
+            Arc::new(conf.colors),
+            Arc::new(conf.themes),
+            Arc::new(conf.buttons),
+            Arc::new(conf.macros),
+            Arc::new(conf.services),
+            Arc::new(conf.on_error),
+            new_services_state(),
+            Arc::new(AtomicBool::new(false)),
+            new_context_vars(),
+            Arc::default(),
+            Box::new(device),
+            &tx,
+            Arc::new(TimeManager::new(tx.clone())),
+            None,
+            100,
+            None,
+        );
+        (device, rx)
+    }
+
+    fn press(deck: &PagedDevice, button_id: u8) {
+        deck.button_down(button_id);
+        deck.button_up(button_id);
+    }
+
+    /// How many `set` actions ran since the last call
+    fn sets(events: &Receiver<DeviceEvent>) -> usize {
+        events
+            .try_iter()
+            .filter(|event| matches!(event, DeviceEvent::SetContextVar { .. }))
+            .count()
+    }
+
+    #[test]
+    fn failed_actions_follow_the_on_error_policy() {
+        let (deck, _events) = deck(
+            "on_error:\n  log: false\n  flash: true\n  actions:\n    - wait: 0\ndefault:\n  Main:\n    button1:\n      actions:\n        - jump: Missing\n    button2:\n      actions:\n        - jump: Missing\n      on_error:\n        log: false\n",
+        );
+        press(&deck, 1);
+        let last_error = deck.context_vars.read().unwrap()["last_error"].clone();
+        assert!(last_error.contains("Missing"), "{}", last_error);
+        assert!(deck.button_flash_until.read().unwrap()[0].is_some());
+
+        // The policy of the button replaces the global one
+        deck.context_vars.write().unwrap().clear();
+        press(&deck, 2);
+        assert!(deck.button_flash_until.read().unwrap()[1].is_none());
+        assert!(deck
+            .context_vars
+            .read()
+            .unwrap()
+            .get("last_error")
+            .is_none());
+    }
+
+    #[test]
+    fn held_buttons_repeat_until_released() {
+        let (deck, events) = deck(
+            "default:\n  Main:\n    button1:\n      repeat:\n        delay: 0.05\n        interval: 0.05\n      actions:\n        - set: volume=up\n",
+        );
+        // The actions run on the press, and their first repetition is scheduled
+        deck.button_down(1);
+        let mut ran = 0;
+        let press = loop {
+            match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+                DeviceEvent::SetContextVar { .. } => ran += 1,
+                DeviceEvent::RepeatButton {
+                    button_id: 1,
+                    press,
+                    ..
+                } => break press,
+                _ => {}
+            }
+        };
+        assert_eq!(ran, 1);
+        deck.repeat_button(1, press);
+        assert_eq!(sets(&events), 1);
+
+        // Releasing neither runs the actions again nor lets a late repetition through
+        deck.button_up(1);
+        deck.repeat_button(1, press);
+        assert_eq!(sets(&events), 0);
+    }
+
+    #[test]
+    fn keys_without_a_screen_run_actions_and_draw_nothing() {
+        let pedal = TestDevice::default();
+        let (deck, events) = deck_of(
+            pedal.clone(),
+            "default:\n  Main:\n    button1:\n      text: Next\n      actions:\n        - set: slide=next\n",
+        );
+        press(&deck, 1);
+        assert_eq!(sets(&events), 1);
+        deck.refresh_page();
+        assert_eq!(pedal.drawn(), 0);
+        assert!(!deck.button_pressed.read().unwrap()[0]);
+    }
+
+    #[test]
+    fn disabled_buttons_are_grayed_out_and_ignore_presses() {
+        let (deck, events) = deck(
+            "default:\n  Main:\n    button1:\n      enabled: \"${var:armed}\"\n      actions:\n        - set: fired=1\n",
+        );
+        let main = deck.pages.pages.get_index_of("Main").unwrap();
+        press(&deck, 1);
+        assert_eq!(sets(&events), 0);
+        let button = deck.find_button(main, 1).unwrap();
+        let effects = deck.button_effects(button).unwrap();
+        assert_eq!(effects.grayscale, Some(true));
+
+        deck.context_vars
+            .write()
+            .unwrap()
+            .insert("armed".to_string(), "true".to_string());
+        press(&deck, 1);
+        assert_eq!(sets(&events), 1);
+        assert_eq!(deck.button_effects(button), None);
+    }
+
+    #[test]
+    fn presses_during_a_cooldown_are_ignored() {
+        let (deck, events) = deck(
+            "default:\n  Main:\n    button1:\n      cooldown:\n        seconds: 60\n        countdown: true\n      actions:\n        - set: doorbell=rung\n",
+        );
+        press(&deck, 1);
+        press(&deck, 1);
+        assert_eq!(sets(&events), 1);
+        let (remaining, countdown) = deck.cooldown_remaining(1).unwrap();
+        assert!(remaining > Duration::from_secs(59) && countdown);
+
+        // Once it is over the button works again
+        deck.button_cooldowns.write().unwrap()[0]
+            .as_mut()
+            .unwrap()
+            .until = Instant::now();
+        press(&deck, 1);
+        assert_eq!(sets(&events), 1);
+    }
+
+    #[test]
+    fn device_connections_run_their_actions() {
+        let (deck, events) = deck(
+            "default:\n  on_connect:\n    - set: dock=on\n  on_disconnect:\n    - set: dock=off\n  Main: {}\n",
+        );
+        deck.run_hotplug_actions(true);
+        deck.run_hotplug_actions(false);
+        let docked: Vec<_> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                DeviceEvent::SetContextVar { key, value } if key == "dock" => value,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(docked, ["on", "off"]);
+    }
+
+    #[test]
+    fn start_actions_run_and_redraw_the_page() {
+        let start = |yaml: &str| {
+            let test_deck = TestDevice {
+                screen: true,
+                ..Default::default()
+            };
+            let (deck, events) = deck_of(test_deck.clone(), yaml);
+            // What the actions sync reaches the keys only when they are drawn again
+            deck.context_vars
+                .write()
+                .unwrap()
+                .insert("synced".to_string(), "yes".to_string());
+            let shown = test_deck.drawn();
+            deck.run_start_actions();
+            (sets(&events), test_deck.drawn() - shown)
+        };
+        let (ran, redrawn) = start(
+            "default:\n  max_fps: 0\n  on_start:\n    - set: synced=yes\n  Main:\n    button1:\n      text: ${var:synced}\n",
+        );
+        assert_eq!(ran, 1);
+        assert!(redrawn > 0);
+
+        // Without start actions the page is left as it is
+        assert_eq!(
+            start("default:\n  max_fps: 0\n  Main:\n    button1:\n      text: ${var:synced}\n"),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn high_contrast_labels_keep_a_readable_size() {
+        let (deck, _events) = deck(
+            "default:\n  Main:\n    button1:\n      text: \"Next\\nTrack\"\n    button2:\n      text: ${var:song}\n",
+        );
+        assert_eq!(deck.high_contrast_font_size(), None);
+        deck.set_accessibility(Some(Accessibility {
+            high_contrast: true,
+            ..Default::default()
+        }));
+        assert_eq!(
+            deck.high_contrast_font_size(),
+            Some(HIGH_CONTRAST_MIN_FONT_SIZE)
+        );
+        deck.set_accessibility(Some(Accessibility {
+            high_contrast: true,
+            min_font_size: Some(24.0),
+            ..Default::default()
+        }));
+        assert_eq!(deck.high_contrast_font_size(), Some(24.0));
+
+        // Labels grow to the minimum size and scroll, unless they already do
+        assert_eq!(
+            high_contrast_text(TextConfig::Simple("Play".to_string()), 24.0),
+            TextConfig::Detailed {
+                value: "Play".to_string(),
+                font_size: None,
+                marquee: Some(HIGH_CONTRAST_MARQUEE_SPEED),
+            }
+        );
+        let detailed = |font_size, marquee| TextConfig::Detailed {
+            value: "Play".to_string(),
+            font_size: Some(font_size),
+            marquee,
+        };
+        assert_eq!(
+            high_contrast_text(detailed(12.0, Some(5.0)), 24.0),
+            detailed(24.0, Some(5.0))
+        );
+        assert_eq!(
+            high_contrast_text(detailed(32.0, None), 24.0),
+            detailed(32.0, Some(HIGH_CONTRAST_MARQUEE_SPEED))
+        );
+
+        // Announced labels are read as shown, on one line
+        deck.context_vars
+            .write()
+            .unwrap()
+            .insert("song".to_string(), "Blue  in Green".to_string());
+        assert_eq!(deck.button_label(1).as_deref(), Some("Next Track"));
+        assert_eq!(deck.button_label(2).as_deref(), Some("Blue in Green"));
+        assert_eq!(deck.button_label(3), None);
+    }
+
+    #[test]
+    fn windows_not_found_are_launched_by_the_fallback_command() {
+        let (deck, events) = deck(
+            "default:\n  Main:\n    button1:\n      actions:\n        - focus: keydeck-test-missing\n          fallback_exec: \"true\"\n          wait_for_window: { class: scratchpad }\n        - set: launched=yes\n    button2:\n      actions:\n        - focus: keydeck-test-missing\n          fallback_exec: \"true\"\n        - set: launched=yes\n    button3:\n      actions:\n        - focus: keydeck-test-missing\n        - set: launched=yes\n      on_error:\n        log: false\n",
+        );
+        // The following actions wait for the launched window
+        press(&deck, 1);
+        assert_eq!(sets(&events), 0);
+        assert!(!deck.check_pending_event(&WaitEventType::Focus, Some(("kate", "notes"))));
+        assert!(deck.check_pending_event(&WaitEventType::Focus, Some(("scratchpad", "notes"))));
+        assert_eq!(sets(&events), 1);
+
+        // Without a window to wait for they run right away, and without a fallback the
+        // focus fails
+        press(&deck, 2);
+        assert_eq!(sets(&events), 1);
+        press(&deck, 3);
+        assert_eq!(sets(&events), 0);
+    }
+
+    #[test]
+    fn devices_are_redrawn_after_a_resume_unless_they_are_gone() {
+        let test_deck = TestDevice {
+            screen: true,
+            ..Default::default()
+        };
+        let (deck, _events) = deck_of(
+            test_deck.clone(),
+            "default:\n  max_fps: 0\n  Main:\n    button1:\n      text: Play\n",
+        );
+        let shown = test_deck.drawn();
+        deck.verify_after_resume().unwrap();
+        assert!(test_deck.drawn() > shown);
+
+        // A device that does not answer is to be reconnected, without drawing on it
+        test_deck.lost.store(true, Ordering::Relaxed);
+        let shown = test_deck.drawn();
+        assert!(matches!(
+            deck.verify_after_resume(),
+            Err(DeviceError::NotFound)
+        ));
+        assert_eq!(test_deck.drawn(), shown);
+    }
+
+    #[test]
+    fn unused_pages_return_to_the_main_page() {
+        let (deck, _events) = deck(
+            "default:\n  main_page: Home\n  Volume:\n    auto_return: 0.05\n    button1:\n      actions:\n        - wait: 0\n  Home: {}\n",
+        );
+        let page = || deck.get_current_page_name().unwrap();
+        let unused = || std::thread::sleep(Duration::from_millis(80));
+        deck.show_page(&"Volume".to_string()).unwrap();
+        deck.auto_return();
+        assert_eq!(page(), "Volume");
+
+        // A press restarts the count
+        unused();
+        press(&deck, 1);
+        deck.auto_return();
+        assert_eq!(page(), "Volume");
+        unused();
+        deck.auto_return();
+        assert_eq!(page(), "Home");
+
+        // A pinned page stays
+        deck.show_page(&"Volume".to_string()).unwrap();
+        deck.pinned.store(true, Ordering::Relaxed);
+        unused();
+        deck.auto_return();
+        assert_eq!(page(), "Volume");
+    }
+
+    #[test]
+    fn pages_follow_a_focus_only_once_it_settles() {
+        let (deck, events) = deck(
+            "default:\n  focus_debounce: 0.05\n  Main: {}\n  Code:\n    when:\n      class: kate\n  Chat:\n    when:\n      class: chat\n",
+        );
+        let settled = || loop {
+            if let DeviceEvent::FocusSettled { focus, .. } =
+                events.recv_timeout(Duration::from_secs(5)).unwrap()
+            {
+                break focus;
+            }
+        };
+        let page = || deck.get_current_page_name().unwrap();
+
+        // The first focus is followed at once
+        deck.focus_changed("kate", "notes.txt", false);
+        assert_eq!(page(), "Code");
+
+        // A window passed while alt-tabbing is ignored
+        deck.focus_changed("chat", "Team", false);
+        deck.focus_changed("kate", "notes.txt", false);
+        assert_eq!(page(), "Code");
+        let passed = settled();
+        deck.focus_settled(passed);
+        assert_eq!(page(), "Code");
+        deck.focus_settled(settled());
+
+        // A window that keeps the focus gets its page once the debounce is over
+        deck.focus_changed("chat", "Team", false);
+        assert_eq!(page(), "Code");
+        deck.focus_settled(settled());
+        assert_eq!(page(), "Chat");
+    }
+
+    #[test]
+    fn presses_outside_the_keys_are_ignored() {
+        let (deck, _events) =
+            deck("default:\n  Main:\n    button1:\n      actions:\n        - wait: 0\n");
+        for button_id in [0, 7, 255] {
+            press(&deck, button_id);
+        }
+        assert!(deck.held_since.read().unwrap().iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn two_threads_drive_one_deck() {
+        let (deck, _events) = deck(
+            "default:\n  Main:\n    button1:\n      text: Go\n      actions:\n        - jump: Other\n  Other:\n    button1:\n      text: Back\n      actions:\n        - jump: Main\n",
+        );
+        let deck = Arc::new(deck);
+        let presser = {
+            let deck = deck.clone();
+            std::thread::spawn(move || (0..50).for_each(|_| press(&deck, 1)))
+        };
+        let main = "Main".to_string();
+        for tick in 0..50 {
+            deck.show_page(&main).unwrap();
+            deck.handle_tick(tick as f64);
+        }
+        presser.join().unwrap();
+        let page = deck.get_current_page_name().unwrap();
+        assert!(page == "Main" || page == "Other", "{}", page);
+        assert!(!deck.button_pressed.read().unwrap()[0]);
     }
 }
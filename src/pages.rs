@@ -7,7 +7,7 @@
 use indexmap::IndexMap;
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 // Re-export types from keydeck-types
 pub use keydeck_types::*;
@@ -21,7 +21,7 @@ pub struct KeyDeckConfLoader;
 
 impl KeyDeckConfLoader {
     /// Recursively resolves a template and all its parent templates, with cycle detection.
-    /// Returns merged buttons, on_tick actions, and lock value in parent-first order (grandparent -> parent -> child).
+    /// Returns merged buttons, on_tick actions, lock value, encoders and theme in parent-first order (grandparent -> parent -> child).
     pub fn resolve_template_recursive(
         template_name: &str,
         templates: &IndexMap<String, Page>,
@@ -32,6 +32,7 @@ impl KeyDeckConfLoader {
             Option<Vec<Action>>,
             Option<bool>,
             Option<IndexMap<String, crate::pages::Encoder>>,
+            Option<String>,
         ),
         String,
     > {
@@ -57,11 +58,12 @@ impl KeyDeckConfLoader {
         let mut merged_on_tick: Option<Vec<Action>> = None;
         let mut merged_lock: Option<bool> = None;
         let mut merged_encoders: Option<IndexMap<String, crate::pages::Encoder>> = None;
+        let mut merged_theme: Option<String> = None;
 
         // First, recursively resolve parent templates
         if let Some(parent_templates) = &template.inherits {
             for parent_name in parent_templates {
-                let (parent_buttons, parent_on_tick, parent_lock, parent_encoders) =
+                let (parent_buttons, parent_on_tick, parent_lock, parent_encoders, parent_theme) =
                     Self::resolve_template_recursive(parent_name, templates, visited)?;
                 // Merge parent buttons (later parents override earlier ones)
                 merged_buttons.extend(parent_buttons);
@@ -79,6 +81,10 @@ impl KeyDeckConfLoader {
                         .get_or_insert_with(IndexMap::new)
                         .extend(parent_enc);
                 }
+                // theme is overridden by later parents (not merged)
+                if parent_theme.is_some() {
+                    merged_theme = parent_theme;
+                }
             }
         }
 
@@ -102,10 +108,15 @@ impl KeyDeckConfLoader {
                 .extend(template_enc.clone());
         }
 
+        // theme is overridden by child (not merged)
+        if template.theme.is_some() {
+            merged_theme = template.theme.clone();
+        }
+
         // Remove from visited (backtrack for DFS)
         visited.pop();
 
-        Ok((merged_buttons, merged_on_tick, merged_lock, merged_encoders))
+        Ok((merged_buttons, merged_on_tick, merged_lock, merged_encoders, merged_theme))
     }
 
     /// Load and fully resolve the configuration, exiting the process on any error.
@@ -147,8 +158,15 @@ impl KeyDeckConfLoader {
                 )
             })?;
         }
+        Self::try_load_from(&path)
+    }
 
-        let data = fs::read_to_string(&path).map_err(|e| {
+    /// Loads and fully resolves the configuration file at `path`, like [`try_load`] but
+    /// without creating it when missing.
+    ///
+    /// [`try_load`]: Self::try_load
+    pub fn try_load_from(path: &Path) -> Result<KeyDeckConf, String> {
+        let data = fs::read_to_string(path).map_err(|e| {
             format!(
                 "Error: Failed to read config file at {}\nReason: {}\n\nPlease create a config file at ~/.config/keydeck/config.yaml\nSee the documentation for configuration format.",
                 path.display(),
@@ -160,18 +178,39 @@ impl KeyDeckConfLoader {
         let mut conf: KeyDeckConf = if data.trim().is_empty() {
             KeyDeckConf::default()
         } else {
-            let deserializer = serde_yaml_ng::Deserializer::from_str(&data);
-            serde_path_to_error::deserialize(deserializer).map_err(|e| {
-                let err_path = e.path().to_string();
+            keydeck_types::parse_config(path, &data).map_err(|e| {
+                let hint = crate::config_hints::unknown_name_hint(&e.message)
+                    .map(|name| format!("\nDid you mean '{}'?", name))
+                    .unwrap_or_default();
                 format!(
-                    "Error parsing config file: {}\n\nPath: {}\n{}",
+                    "Error parsing config file: {}\n\nPath: {}\n{}{}",
                     path.display(),
-                    err_path,
-                    e.into_inner()
+                    e.path,
+                    e.message,
+                    hint
                 )
             })?
         };
 
+        let unknown: Vec<String> = conf
+            .unknown_fields()
+            .iter()
+            .map(|path| path.join("."))
+            .collect();
+        if conf.strict == Some(true) && !unknown.is_empty() {
+            return Err(format!(
+                "Error: Unknown fields in config file {}:\n  {}\n\nPages only have their own fields and buttons named 'button#' or 'r#c#'.",
+                path.display(),
+                unknown.join("\n  ")
+            ));
+        }
+        for field in &unknown {
+            crate::warn_log!(
+                "Unknown field '{}' in config file, ignored; 'strict: true' rejects it",
+                field
+            );
+        }
+
         // Validate tick_time is within range (1-60 seconds)
         if conf.tick_time < 1.0 || conf.tick_time > 60.0 {
             return Err(format!(
@@ -180,6 +219,51 @@ impl KeyDeckConfLoader {
                 path.display()
             ));
         }
+        for (group_name, page_group) in &conf.page_groups {
+            for (page_name, page) in &page_group.pages {
+                if let Some(tick) = page.tick.filter(|tick| !(1.0..=60.0).contains(tick)) {
+                    return Err(format!(
+                        "Error: tick of page '{}' in page group '{}' must be between 1 and 60 seconds\nCurrent value: {}\n\nPlease update your config file at {}",
+                        page_name,
+                        group_name,
+                        tick,
+                        path.display()
+                    ));
+                }
+            }
+        }
+
+        if let Some(night_mode) = &conf.night_mode {
+            night_mode.validate().map_err(|e| {
+                format!("Error: {}\n\nPlease update your config file at {}", e, path.display())
+            })?;
+        }
+
+        for pages in conf.page_groups.values() {
+            for (page_name, page) in &pages.pages {
+                if let Some(visibility) = &page.visible_when {
+                    visibility.validate().map_err(|e| {
+                        format!(
+                            "Error: Page '{}': {}\n\nPlease update your config file at {}",
+                            page_name,
+                            e,
+                            path.display()
+                        )
+                    })?;
+                }
+                let chords = page.chords.iter().flatten().map(|chord| chord.validate());
+                for checked in chords.chain([page.validate_layers()]) {
+                    checked.map_err(|e| {
+                        format!(
+                            "Error: Page '{}': {}\n\nPlease update your config file at {}",
+                            page_name,
+                            e,
+                            path.display()
+                        )
+                    })?;
+                }
+            }
+        }
 
         // Upgrade legacy `window_name` into the unified `when` structure.
         conf.migrate_legacy_window_name();
@@ -213,7 +297,13 @@ impl KeyDeckConfLoader {
                             templates_map,
                             &mut visited,
                         ) {
-                            Ok((resolved_buttons, resolved_on_tick, resolved_lock, resolved_encoders)) => {
+                            Ok((
+                                resolved_buttons,
+                                resolved_on_tick,
+                                resolved_lock,
+                                resolved_encoders,
+                                resolved_theme,
+                            )) => {
                                 // Merge resolved buttons into page (page buttons take priority)
                                 for (button_name, button_config) in resolved_buttons {
                                     page.buttons.entry(button_name).or_insert(button_config);
@@ -233,6 +323,10 @@ impl KeyDeckConfLoader {
                                         page_encoders.entry(enc_name).or_insert(enc_config);
                                     }
                                 }
+                                // Merge theme (page's theme takes priority over template's)
+                                if page.theme.is_none() && resolved_theme.is_some() {
+                                    page.theme = resolved_theme;
+                                }
                             }
                             Err(e) => {
                                 return Err(format!(
@@ -246,6 +340,54 @@ impl KeyDeckConfLoader {
             }
         }
 
+        // Validate that every referenced theme is defined
+        let theme_refs = conf.theme.iter().map(|t| ("theme".to_string(), t)).chain(
+            conf.page_groups.iter().flat_map(|(group, pages)| {
+                pages.pages.iter().filter_map(move |(page_name, page)| {
+                    page.theme
+                        .as_ref()
+                        .map(|t| (format!("{}.{}.theme", group, page_name), t))
+                })
+            }),
+        );
+        for (location, theme) in theme_refs {
+            if !conf.themes.as_ref().is_some_and(|themes| themes.contains_key(theme)) {
+                return Err(format!(
+                    "Error: Unknown theme '{}' referenced by {}
+Themes must be defined in the 'themes' section.
+
+Please update your config file at {}",
+                    theme,
+                    location,
+                    path.display()
+                ));
+            }
+        }
+
+        // Resolve buttons based on other button definitions
+        conf.resolve_button_extends().map_err(|e| {
+            format!(
+                "Error resolving button definitions: {}\n\nPlease update your config file at {}",
+                e,
+                path.display()
+            )
+        })?;
+
+        // Create the buttons of generated pages from their current sources
+        crate::page_generator::read_sources(&mut conf);
+
+        // Fill the global button style into buttons that don't override it
+        conf.apply_button_defaults();
+
+        // Put the color maps of the `gradients` section in place of their names
+        conf.resolve_gradients().map_err(|e| {
+            format!(
+                "Error: {}\nGradients must be defined in the 'gradients' section.\n\nPlease update your config file at {}",
+                e,
+                path.display()
+            )
+        })?;
+
         // Compute dynamic flags for all buttons after template resolution
         crate::dynamic_detection::compute_all_dynamic_flags(&mut conf);
 
@@ -5,8 +5,8 @@
 //!
 //! Exposes a uniform API for all OS-specific operations the daemon needs:
 //! keyboard injection, window focus (query + request), sleep/resume
-//! notification, config-reload signalling, process-exit cleanup and shell
-//! selection for the `Exec` action.
+//! notification, config-reload signalling, process-exit cleanup, desktop
+//! notifications and shell selection for the `Exec` action.
 //!
 //! * **Linux** reuses the mature native X11 / Wayland (RemoteDesktop portal +
 //!   KWin) implementations that already live in the crate root.
@@ -18,50 +18,14 @@
 // Shared, platform-independent helpers
 // ---------------------------------------------------------------------------
 
-/// Processes escape sequences in a string and returns the actual characters.
-///
-/// Supported escape sequences: `\n` (Enter), `\t` (Tab), `\r` (carriage
-/// return), `\\` (backslash), `\e` (Escape). Used both for keyboard input and
-/// for text rendering, so it lives in the platform-independent layer.
-pub fn process_escape_sequences(text: &str) -> Vec<char> {
-    let mut result = Vec::new();
-    let mut chars = text.chars().peekable();
-
-    while let Some(ch) = chars.next() {
-        if ch == '\\' {
-            if let Some(&next_ch) = chars.peek() {
-                match next_ch {
-                    'n' => {
-                        chars.next();
-                        result.push('\n');
-                    }
-                    't' => {
-                        chars.next();
-                        result.push('\t');
-                    }
-                    'r' => {
-                        chars.next();
-                        result.push('\r');
-                    }
-                    '\\' => {
-                        chars.next();
-                        result.push('\\');
-                    }
-                    'e' => {
-                        chars.next();
-                        result.push('\x1b');
-                    }
-                    _ => result.push(ch),
-                }
-            } else {
-                result.push(ch);
-            }
-        } else {
-            result.push(ch);
-        }
-    }
+pub use crate::utils::process_escape_sequences;
 
-    result
+/// Starts `command` without waiting for it to end. A thread of its own waits for it, so
+/// it does not linger as a zombie once it exits.
+pub fn spawn_reaped(command: &mut std::process::Command) -> std::io::Result<()> {
+    let mut child = command.spawn()?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
 }
 
 // Daemon lifecycle management (install/uninstall/start/stop/restart/status/reload).
@@ -73,14 +37,18 @@ pub mod lifecycle;
 pub fn spawn_context_listener(
     tx: &std::sync::mpsc::Sender<crate::event::DeviceEvent>,
     active: &std::sync::Arc<std::sync::atomic::AtomicBool>,
+    reload_status: &crate::server::SharedReloadStatus,
+    usage_stats: &crate::stats::SharedStats,
 ) {
-    crate::listener_context::spawn_context_listener(tx, active);
+    crate::listener_context::spawn_context_listener(tx, active, reload_status, usage_stats);
 }
 
 #[cfg(not(unix))]
 pub fn spawn_context_listener(
     _tx: &std::sync::mpsc::Sender<crate::event::DeviceEvent>,
     _active: &std::sync::Arc<std::sync::atomic::AtomicBool>,
+    _reload_status: &crate::server::SharedReloadStatus,
+    _usage_stats: &crate::stats::SharedStats,
 ) {
 }
 
@@ -96,18 +64,37 @@ mod linux_glue {
     use std::sync::Arc;
 
     pub use crate::focus_property::set_focus;
-    pub use crate::keyboard::{send_key_combination, send_string};
+    pub use crate::keyboard::{keyboard_backend, send_key_combination, send_string};
 
     pub fn spawn_focus_listener(tx: &Sender<DeviceEvent>, active: &Arc<AtomicBool>) {
         crate::listener_focus::listener_focus(tx, active);
     }
 
+    /// Order the focus backends are tried in, from the `focus_backend` setting.
+    pub fn set_focus_backends(order: Option<Vec<crate::pages::FocusBackend>>) {
+        crate::listener_focus::set_backend_order(order);
+    }
+
+    /// The state of the devices as properties on the session bus.
+    pub fn spawn_state_service() {
+        crate::dbus_state::serve();
+    }
+
     pub fn spawn_sleep_listener(
         tx: &Sender<DeviceEvent>,
-        active: &Arc<AtomicBool>,
+        _active: &Arc<AtomicBool>,
         should_reset: &Arc<AtomicBool>,
     ) {
-        crate::listener_sleep::listener_sleep(tx, active, should_reset);
+        crate::listener_sleep::listener_sleep(tx, should_reset);
+    }
+
+    /// Global hotkeys grabbed on X11
+    pub fn spawn_hotkey_listener(
+        tx: &Sender<DeviceEvent>,
+        active: &Arc<AtomicBool>,
+        updates: &crate::server::HotkeyUpdates,
+    ) {
+        crate::listener_hotkey::listener_hotkey(tx, active, updates);
     }
 
     /// Reload (SIGHUP) + exit (SIGINT/SIGTERM) signalling.
@@ -124,6 +111,49 @@ mod linux_glue {
     pub fn exec_shell() -> (&'static str, &'static str) {
         ("bash", "-c")
     }
+
+    /// Desktop notification through the freedesktop `notify-send` helper.
+    pub fn send_notification(title: &str, body: &str) -> Result<(), String> {
+        super::spawn_reaped(std::process::Command::new("notify-send").args([
+            "--app-name=KeyDeck",
+            title,
+            body,
+        ]))
+        .map_err(|e| format!("Failed to run notify-send: {}", e))
+    }
+
+    /// Speaks text aloud through speech-dispatcher, the speech service screen readers
+    /// such as Orca use.
+    pub fn speak(text: &str) -> Result<(), String> {
+        super::spawn_reaped(std::process::Command::new("spd-say").args([
+            "--application-name",
+            "KeyDeck",
+            "--",
+            text,
+        ]))
+        .map_err(|e| format!("Failed to run spd-say: {}", e))
+    }
+
+    /// Opens an address in the default browser or handler, through `xdg-open`.
+    pub fn open_url(url: &str) -> Result<(), String> {
+        super::spawn_reaped(std::process::Command::new("xdg-open").arg(url))
+            .map_err(|e| format!("Failed to run xdg-open: {}", e))
+    }
+
+    /// Suspends, hibernates, reboots or powers off the machine through logind.
+    pub fn power(action: crate::pages::PowerAction) -> Result<(), String> {
+        crate::power::power(action)
+    }
+
+    /// Short name of the active keyboard layout, e.g. `us`.
+    pub fn keyboard_layout() -> Result<String, String> {
+        crate::keyboard_layout::layouts().map(|layouts| layouts.current_name().to_string())
+    }
+
+    /// Switches to the keyboard layout `target`, or the next one; returns the new layout.
+    pub fn switch_keyboard_layout(target: &str) -> Result<String, String> {
+        crate::keyboard_layout::switch(target)
+    }
 }
 
 #[cfg(target_os = "linux")]
@@ -144,7 +174,7 @@ pub mod keymap;
 mod reload;
 
 #[cfg(not(target_os = "linux"))]
-pub use keymap::{send_key_combination, send_string};
+pub use keymap::{keyboard_backend, send_key_combination, send_string};
 
 #[cfg(not(target_os = "linux"))]
 pub fn spawn_control_listener(
@@ -154,17 +184,37 @@ pub fn spawn_control_listener(
     reload::spawn_control_listener(tx.clone(), active.clone());
 }
 
+/// Hotkeys are not grabbed on Windows and macOS yet; they can still be triggered
+/// through the control socket (macOS) with `keydeck --hotkey`.
+#[cfg(not(target_os = "linux"))]
+pub fn spawn_hotkey_listener(
+    _tx: &std::sync::mpsc::Sender<crate::event::DeviceEvent>,
+    _active: &std::sync::Arc<std::sync::atomic::AtomicBool>,
+    _updates: &crate::server::HotkeyUpdates,
+) {
+}
+
+/// D-Bus is only served on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn spawn_state_service() {}
+
+/// Windows and macOS have a single focus backend; `focus_backend` is ignored there.
+#[cfg(not(target_os = "linux"))]
+pub fn set_focus_backends(_order: Option<Vec<crate::pages::FocusBackend>>) {}
+
 #[cfg(target_os = "windows")]
 mod windows;
 #[cfg(target_os = "windows")]
 pub use windows::{
-    exec_shell, on_exit_cleanup, set_focus, spawn_focus_listener, spawn_sleep_listener,
+    exec_shell, keyboard_layout, on_exit_cleanup, open_url, power, send_notification, set_focus,
+    spawn_focus_listener, spawn_sleep_listener, speak, switch_keyboard_layout,
 };
 
 #[cfg(target_os = "macos")]
 mod macos;
 #[cfg(target_os = "macos")]
 pub use macos::{
-    exec_shell, on_exit_cleanup, run_main_thread, set_focus, spawn_focus_listener,
-    spawn_sleep_listener,
+    exec_shell, keyboard_layout, on_exit_cleanup, open_url, power, run_main_thread,
+    send_notification, set_focus, spawn_focus_listener, spawn_sleep_listener, speak,
+    switch_keyboard_layout,
 };
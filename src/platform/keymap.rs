@@ -10,6 +10,7 @@
 
 use enigo::{Direction, Enigo, Key, Keyboard, Settings};
 
+use crate::error::Error;
 use crate::platform::process_escape_sequences;
 
 /// Runs `f` with a fresh `Enigo` instance.
@@ -158,9 +159,18 @@ fn parse_key(token: &str) -> Result<Key, String> {
     Ok(key)
 }
 
+/// Name of the backend keys are injected with, as it appears in keyboard errors
+pub fn keyboard_backend() -> &'static str {
+    "enigo"
+}
+
 /// Sends a key combination such as `"LCtrl+LShift+z"`.
 /// All keys are pressed in order, then released in reverse order.
-pub fn send_key_combination(combination: &str) -> Result<(), String> {
+pub fn send_key_combination(combination: &str) -> Result<(), Error> {
+    press_combination(combination).map_err(|e| Error::keyboard("enigo", e))
+}
+
+fn press_combination(combination: &str) -> Result<(), String> {
     let keys: Vec<Key> = combination
         .split('+')
         .map(str::trim)
@@ -195,7 +205,11 @@ pub fn send_key_combination(combination: &str) -> Result<(), String> {
 /// correctly (unlike per-character `Key::Unicode`, which sends the bare
 /// physical key without its shift modifier). Control characters are emitted as
 /// dedicated key presses.
-pub fn send_string(text: &str) -> Result<(), String> {
+pub fn send_string(text: &str) -> Result<(), Error> {
+    type_string(text).map_err(|e| Error::keyboard("enigo", e))
+}
+
+fn type_string(text: &str) -> Result<(), String> {
     let chars = process_escape_sequences(text);
     with_enigo(|enigo| {
         let mut buffer = String::new();
@@ -88,16 +88,28 @@ pub fn run(action: Action) -> io::Result<i32> {
 /// Prints daemon status as JSON (`running`, `pid`, `enabled`) and returns exit
 /// code 0 if the daemon is currently running, 1 otherwise. The `running`/`pid`
 /// half is fully cross-platform (lock file + `sysinfo`); only `enabled` is
-/// delegated to the per-OS backend.
+/// delegated to the per-OS backend. On Unix a running daemon is also asked for
+/// its [`keydeck_types::api::DaemonStatus`] (`keydeck` version, `uptime`,
+/// `config_generation`, `last_reload_error`, `devices`).
 fn status() -> io::Result<i32> {
     let pid = crate::lock::running_pid();
     let running = pid.is_some();
     let enabled = imp::is_enabled();
-    let pid_json = match pid {
-        Some(p) => p.to_string(),
-        None => "null".to_string(),
-    };
-    println!("{{\"running\":{},\"pid\":{},\"enabled\":{}}}", running, pid_json, enabled);
+    let mut json = serde_json::json!({
+        "running": running,
+        "pid": pid,
+        "enabled": enabled,
+    });
+    #[cfg(unix)]
+    if running {
+        if let (Some(map), Some(serde_json::Value::Object(daemon))) = (
+            json.as_object_mut(),
+            crate::listener_context::query_status(),
+        ) {
+            map.extend(daemon);
+        }
+    }
+    println!("{}", json);
     Ok(if running { 0 } else { 1 })
 }
 
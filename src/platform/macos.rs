@@ -15,6 +15,7 @@
 
 use crate::event::DeviceEvent;
 use crate::verbose_log;
+use crate::window_match::WindowQuery;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
@@ -31,6 +32,60 @@ pub fn exec_shell() -> (&'static str, &'static str) {
     ("/bin/sh", "-c")
 }
 
+/// Desktop notification through AppleScript's `display notification`.
+pub fn send_notification(title: &str, body: &str) -> Result<(), String> {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let script = format!(
+        "display notification \"{}\" with title \"{}\"",
+        escape(body),
+        escape(title)
+    );
+    super::spawn_reaped(std::process::Command::new("osascript").args(["-e", &script]))
+        .map_err(|e| format!("Failed to run osascript: {}", e))
+}
+
+/// Speaks text aloud, through `say`.
+pub fn speak(text: &str) -> Result<(), String> {
+    super::spawn_reaped(std::process::Command::new("say").args(["--", text]))
+        .map_err(|e| format!("Failed to run say: {}", e))
+}
+
+/// Opens an address with its default handler, through `open`.
+pub fn open_url(url: &str) -> Result<(), String> {
+    super::spawn_reaped(std::process::Command::new("open").arg(url))
+        .map_err(|e| format!("Failed to run open: {}", e))
+}
+
+/// Sleeps, restarts or shuts down the Mac. Restart and shut down go through System
+/// Events, so applications get the chance to save their documents.
+pub fn power(action: crate::pages::PowerAction) -> Result<(), String> {
+    use crate::pages::PowerAction;
+    let (program, args): (&str, &[&str]) = match action {
+        PowerAction::Suspend => ("pmset", &["sleepnow"]),
+        PowerAction::Hibernate => return Err("Hibernate is not supported on macOS".to_string()),
+        PowerAction::Reboot => (
+            "osascript",
+            &["-e", "tell application \"System Events\" to restart"],
+        ),
+        PowerAction::Poweroff => (
+            "osascript",
+            &["-e", "tell application \"System Events\" to shut down"],
+        ),
+    };
+    super::spawn_reaped(std::process::Command::new(program).args(args))
+        .map_err(|e| format!("Failed to run {}: {}", program, e))
+}
+
+/// Keyboard layouts are not read on macOS yet.
+pub fn keyboard_layout() -> Result<String, String> {
+    Err("Keyboard layouts are not supported on macOS".to_string())
+}
+
+/// Keyboard layouts are not switched on macOS yet.
+pub fn switch_keyboard_layout(_target: &str) -> Result<String, String> {
+    Err("Keyboard layouts are not supported on macOS".to_string())
+}
+
 /// No macOS-specific teardown is required on exit.
 pub fn on_exit_cleanup() {}
 
@@ -168,21 +223,11 @@ pub fn run_main_thread(active: &Arc<AtomicBool>) {
 // set_focus: activate a matching application
 // ---------------------------------------------------------------------------
 
-/// Activates an application matching `class` (app name / bundle id) and/or
-/// `title`. macOS activation is application-scoped; matching semantics mirror
-/// the Linux backend (substring, case-insensitive; OR when the same string is
-/// supplied for both, which the `Focus` action does).
-pub fn set_focus(class: &String, title: &String) -> Result<(), String> {
-    if class.is_empty() && title.is_empty() {
-        return Err("At least one of class or title must be specified".to_string());
-    }
-
-    let needles: Vec<String> = [class, title]
-        .iter()
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_lowercase())
-        .collect();
-
+/// Activates an application matching `query`, where the class is the app name
+/// or bundle id. macOS activation is application-scoped, so the window title
+/// is matched against the application name as well.
+pub fn set_focus(query: &WindowQuery) -> Result<(), String> {
+    let mut candidates = Vec::new();
     unsafe {
         let workspace = NSWorkspace::sharedWorkspace();
         let apps = workspace.runningApplications();
@@ -191,28 +236,22 @@ pub fn set_focus(class: &String, title: &String) -> Result<(), String> {
             let name = app
                 .localizedName()
                 .map(|s| s.to_string())
-                .unwrap_or_default()
-                .to_lowercase();
+                .unwrap_or_default();
             let bundle = app
                 .bundleIdentifier()
                 .map(|s| s.to_string())
-                .unwrap_or_default()
-                .to_lowercase();
-
-            let hit = needles
-                .iter()
-                .any(|n| name.contains(n) || bundle.contains(n));
-            if hit {
-                app.activateWithOptions(
-                    NSApplicationActivationOptions::NSApplicationActivateAllWindows,
-                );
-                return Ok(());
+                .unwrap_or_default();
+            let pid = u32::try_from(app.processIdentifier()).ok();
+
+            if query.matches(&[name.as_str(), bundle.as_str()], &name, pid) {
+                candidates.push(app);
             }
         }
     }
 
-    Err(format!(
-        "No matching application found using class '{}' and title '{}'",
-        class, title
-    ))
+    let app = query.select(candidates)?;
+    unsafe {
+        app.activateWithOptions(NSApplicationActivationOptions::NSApplicationActivateAllWindows);
+    }
+    Ok(())
 }
@@ -7,6 +7,7 @@
 //! `platform::reload`).
 
 use crate::event::{send, DeviceEvent};
+use crate::window_match::WindowQuery;
 use crate::{error_log, verbose_log};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
@@ -41,6 +42,78 @@ pub fn exec_shell() -> (&'static str, &'static str) {
     ("cmd", "/C")
 }
 
+/// Desktop notification as a tray balloon, shown by a short-lived PowerShell process.
+pub fn send_notification(title: &str, body: &str) -> Result<(), String> {
+    let escape = |s: &str| s.replace('\'', "''");
+    let script = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; \
+         $n = New-Object System.Windows.Forms.NotifyIcon; \
+         $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+         $n.ShowBalloonTip(5000, '{}', '{}', 'None'); Start-Sleep -Seconds 6; $n.Dispose()",
+        escape(title),
+        escape(body)
+    );
+    super::spawn_reaped(std::process::Command::new("powershell").args([
+        "-NoProfile",
+        "-WindowStyle",
+        "Hidden",
+        "-Command",
+        &script,
+    ]))
+    .map_err(|e| format!("Failed to show notification: {}", e))
+}
+
+/// Speaks text aloud with the speech synthesizer of Windows.
+pub fn speak(text: &str) -> Result<(), String> {
+    let script = format!(
+        "Add-Type -AssemblyName System.Speech; \
+         (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak('{}')",
+        text.replace('\'', "''")
+    );
+    super::spawn_reaped(std::process::Command::new("powershell").args([
+        "-NoProfile",
+        "-WindowStyle",
+        "Hidden",
+        "-Command",
+        &script,
+    ]))
+    .map_err(|e| format!("Failed to speak: {}", e))
+}
+
+/// Opens an address with its default handler. `rundll32` is used instead of `start`
+/// because cmd would split the address at `&`.
+pub fn open_url(url: &str) -> Result<(), String> {
+    super::spawn_reaped(
+        std::process::Command::new("rundll32").args(["url.dll,FileProtocolHandler", url]),
+    )
+    .map_err(|e| format!("Failed to open {}: {}", url, e))
+}
+
+/// Suspends, hibernates, reboots or powers off the machine.
+pub fn power(action: crate::pages::PowerAction) -> Result<(), String> {
+    use crate::pages::PowerAction;
+    let (program, args): (&str, &[&str]) = match action {
+        // With hibernation enabled this hibernates instead; Windows offers no plain
+        // command for sleep
+        PowerAction::Suspend => ("rundll32", &["powrprof.dll,SetSuspendState", "0,1,0"]),
+        PowerAction::Hibernate => ("shutdown", &["/h"]),
+        PowerAction::Reboot => ("shutdown", &["/r", "/t", "0"]),
+        PowerAction::Poweroff => ("shutdown", &["/s", "/t", "0"]),
+    };
+    super::spawn_reaped(std::process::Command::new(program).args(args))
+        .map_err(|e| format!("Failed to run {}: {}", program, e))
+}
+
+/// Keyboard layouts are not read on Windows yet.
+pub fn keyboard_layout() -> Result<String, String> {
+    Err("Keyboard layouts are not supported on Windows".to_string())
+}
+
+/// Keyboard layouts are not switched on Windows yet.
+pub fn switch_keyboard_layout(_target: &str) -> Result<String, String> {
+    Err("Keyboard layouts are not supported on Windows".to_string())
+}
+
 /// No Windows-specific teardown is required on exit.
 pub fn on_exit_cleanup() {}
 
@@ -147,16 +220,9 @@ unsafe extern "system" fn enum_windows_cb(hwnd: HWND, lparam: LPARAM) -> BOOL {
     TRUE
 }
 
-/// Activates a window matching `class` (process name) and/or `title`.
-///
-/// Matching mirrors the Linux backend: substring, case-insensitive; when the
-/// same string is passed for both (the `Focus` action does this) either a
-/// class or a title match suffices, otherwise both must match.
-pub fn set_focus(class: &String, title: &String) -> Result<(), String> {
-    if class.is_empty() && title.is_empty() {
-        return Err("At least one of class or title must be specified".to_string());
-    }
-
+/// Activates a window matching `query`, where the class is the owning
+/// process name. Matching follows the shared `WindowQuery` semantics.
+pub fn set_focus(query: &WindowQuery) -> Result<(), String> {
     let mut windows: Vec<HWND> = Vec::new();
     unsafe {
         EnumWindows(
@@ -166,42 +232,29 @@ pub fn set_focus(class: &String, title: &String) -> Result<(), String> {
         .map_err(|e| format!("EnumWindows failed: {e}"))?;
     }
 
-    let use_or = !class.is_empty() && !title.is_empty() && class == title;
-    let class_lower = class.to_lowercase();
-    let title_lower = title.to_lowercase();
-
-    for hwnd in windows {
-        let wtitle = window_title(hwnd);
-        let wclass = window_process_name(hwnd).unwrap_or_default();
+    let candidates: Vec<HWND> = windows
+        .into_iter()
+        .filter(|&hwnd| {
+            let wtitle = window_title(hwnd);
+            let wclass = window_process_name(hwnd).unwrap_or_default();
+            query.matches(&[wclass.as_str()], &wtitle, window_pid(hwnd))
+        })
+        .collect();
 
-        let class_match = if !class.is_empty() {
-            wclass.to_lowercase().contains(&class_lower)
-        } else {
-            !use_or
-        };
-        let title_match = if !title.is_empty() {
-            wtitle.to_lowercase().contains(&title_lower)
-        } else {
-            !use_or
-        };
-        let matches = if use_or {
-            class_match || title_match
-        } else {
-            class_match && title_match
-        };
-
-        if matches {
-            unsafe {
-                let _ = SetForegroundWindow(hwnd);
-            }
-            return Ok(());
-        }
+    let hwnd = query.select(candidates)?;
+    unsafe {
+        let _ = SetForegroundWindow(hwnd);
     }
+    Ok(())
+}
 
-    Err(format!(
-        "No matching window found using class '{}' and title '{}'",
-        class, title
-    ))
+/// Returns the id of the process owning `hwnd`.
+fn window_pid(hwnd: HWND) -> Option<u32> {
+    let mut pid: u32 = 0;
+    unsafe {
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+    }
+    (pid != 0).then_some(pid)
 }
 
 // ---------------------------------------------------------------------------
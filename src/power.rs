@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+use crate::pages::PowerAction;
+use tokio::runtime::Runtime;
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Login1Manager {
+    fn suspend(&self, interactive: bool) -> zbus::Result<()>;
+    fn hibernate(&self, interactive: bool) -> zbus::Result<()>;
+    fn reboot(&self, interactive: bool) -> zbus::Result<()>;
+    fn power_off(&self, interactive: bool) -> zbus::Result<()>;
+}
+
+/// Asks logind to change the power state of the machine. The calls are interactive, so
+/// polkit may ask the user for authorization, as the desktop's own power menu does.
+pub fn power(action: PowerAction) -> Result<(), String> {
+    // zbus::blocking requires a tokio runtime context on the current thread
+    let rt = Runtime::new().map_err(|e| format!("Failed to create tokio runtime: {}", e))?;
+    let _guard = rt.enter();
+    let conn = zbus::blocking::Connection::system()
+        .map_err(|e| format!("Failed to connect to system D-Bus: {}", e))?;
+    let proxy = Login1ManagerProxyBlocking::new(&conn)
+        .map_err(|e| format!("Failed to create login1 proxy: {}", e))?;
+    match action {
+        PowerAction::Suspend => proxy.suspend(true),
+        PowerAction::Hibernate => proxy.hibernate(true),
+        PowerAction::Reboot => proxy.reboot(true),
+        PowerAction::Poweroff => proxy.power_off(true),
+    }
+    .map_err(|e| format!("{:?} failed: {}", action, e))
+}
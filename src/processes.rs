@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Commands started by `exec` actions that do not wait for them.
+//!
+//! Every such command is waited for on a thread of its own, so it does not linger as a
+//! zombie once it ends. A command started with a `tag` shows in `${proc:running:TAG}`
+//! while it runs, and buttons showing it are redrawn when it starts and ends. With
+//! `kill_on_exit`, the command and the processes it started are killed when the device
+//! that launched it leaves the page, and when the daemon exits.
+
+use crate::event::{send, DeviceEvent};
+use crate::exec;
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::thread;
+
+/// A command that still runs
+struct Tracked {
+    id: u64,
+    pid: u32,
+    /// User it runs as, for `run_as`
+    run_as: Option<String>,
+    tag: Option<String>,
+    /// Device serial and page to kill it on leaving, for `kill_on_exit`
+    owner: Option<(String, String)>,
+}
+
+static RUNNING: Mutex<Vec<Tracked>> = Mutex::new(Vec::new());
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// How a command is watched
+pub struct Supervision {
+    /// User the command runs as, to kill it as that user
+    pub run_as: Option<String>,
+    pub tag: Option<String>,
+    /// Device serial and page shown when it was started, to kill it when the page is left
+    pub kill_on_exit: Option<(String, String)>,
+}
+
+/// Starts `command` and watches it until it ends
+pub fn spawn(
+    mut command: Command,
+    supervision: Supervision,
+    event_tx: &Sender<DeviceEvent>,
+) -> Result<(), String> {
+    if supervision.kill_on_exit.is_some() {
+        // Its own process group, to kill what it started along with it
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    }
+    let mut child = command.spawn().map_err(|e| e.to_string())?;
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let tagged = supervision.tag.is_some();
+    RUNNING.lock().unwrap().push(Tracked {
+        id,
+        pid: child.id(),
+        run_as: supervision.run_as,
+        tag: supervision.tag,
+        owner: supervision.kill_on_exit,
+    });
+    if tagged {
+        notify(event_tx);
+    }
+
+    let event_tx = event_tx.clone();
+    thread::spawn(move || {
+        let _ = child.wait();
+        RUNNING.lock().unwrap().retain(|tracked| tracked.id != id);
+        if tagged {
+            notify(&event_tx);
+        }
+    });
+    Ok(())
+}
+
+fn notify(event_tx: &Sender<DeviceEvent>) {
+    send(
+        event_tx,
+        DeviceEvent::ProviderUpdated {
+            provider: "proc".to_string(),
+        },
+    );
+}
+
+/// Whether a command started with `tag` runs
+pub fn is_running(tag: &str) -> bool {
+    RUNNING
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|tracked| tracked.tag.as_deref() == Some(tag))
+}
+
+/// Kills the `kill_on_exit` commands started on `page` of the device `sn`
+pub fn page_left(sn: &str, page: &str) {
+    kill_where(|(owner_sn, owner_page)| owner_sn == sn && owner_page == page);
+}
+
+/// Kills every `kill_on_exit` command, when the daemon exits
+pub fn kill_on_exit() {
+    kill_where(|_| true);
+}
+
+fn kill_where(matches: impl Fn(&(String, String)) -> bool) {
+    for tracked in RUNNING.lock().unwrap().iter() {
+        if tracked.owner.as_ref().is_some_and(&matches) {
+            crate::verbose_log!("Killing process {} started by an exec action", tracked.pid);
+            exec::kill_tree(tracked.pid, tracked.run_as.as_deref());
+        }
+    }
+}
+
+/// Evaluates `${proc:running:TAG}`: `yes` while a command started with the tag runs
+pub fn value(arg: &str) -> Option<String> {
+    match arg.split_once(':')? {
+        ("running", tag) => Some(if is_running(tag) { "yes" } else { "no" }.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn tagged_commands_are_running_until_they_end_or_are_killed() {
+        let (tx, rx) = mpsc::channel();
+        let sleep = |seconds: &str| {
+            let mut command = Command::new("sleep");
+            command.arg(seconds);
+            command
+        };
+        let owner = Some(("AL1".to_string(), "Main".to_string()));
+        let supervision = |tag: &str, kill_on_exit| Supervision {
+            run_as: None,
+            tag: Some(tag.to_string()),
+            kill_on_exit,
+        };
+        spawn(sleep("0.5"), supervision("short", None), &tx).unwrap();
+        spawn(sleep("30"), supervision("helper", owner), &tx).unwrap();
+        assert_eq!(value("running:short").as_deref(), Some("yes"));
+        assert_eq!(value("running:helper").as_deref(), Some("yes"));
+        assert_eq!(value("running:other").as_deref(), Some("no"));
+        assert_eq!(value("status:helper"), None);
+
+        // Started, started and ended
+        for _ in 0..3 {
+            rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+        assert!(!is_running("short"));
+
+        page_left("AL1", "Media");
+        assert!(is_running("helper"));
+        page_left("AL1", "Main");
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(!is_running("helper"));
+    }
+}
@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Time-sliced redraws, so presses are not held up by keys refreshing in bulk.
+//!
+//! Events are handled one after the other: a tick refreshing twenty `draw` buttons used to
+//! render all of them before the next press was even looked at. Bulk refreshes (the
+//! `refresh` action and values of providers changing) now draw their keys in slices.
+//! After every key the slice ends when input waits for the event loop or the slice took
+//! [`RENDER_SLICE`], and the remaining keys are drawn by a `ResumeRefresh` event queued
+//! behind that input. The image of a pressed key goes to the device ahead of the others.
+
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
+/// Longest time a bulk refresh draws keys before letting other events go first
+pub const RENDER_SLICE: Duration = Duration::from_millis(15);
+
+/// The drawing time of a bulk refresh, from the first key it draws
+pub struct RenderSlice {
+    started: Instant,
+}
+
+impl RenderSlice {
+    pub fn start() -> Self {
+        RenderSlice {
+            started: Instant::now(),
+        }
+    }
+
+    /// Whether the refresh should stop here and let the waiting events go first
+    pub fn used_up(&self) -> bool {
+        crate::event::input_pending() || self.started.elapsed() >= RENDER_SLICE
+    }
+}
+
+/// Keys of a page that a bulk refresh still has to draw
+#[derive(Debug, Default)]
+pub struct DeferredKeys {
+    page: usize,
+    keys: BTreeSet<u8>,
+}
+
+impl DeferredKeys {
+    /// Adds `keys` of `page` to the ones to draw, and returns whether nothing was waiting,
+    /// that is whether the caller should start drawing. Keys of another page are dropped.
+    pub fn defer(&mut self, page: usize, keys: impl IntoIterator<Item = u8>) -> bool {
+        let idle = self.keys.is_empty();
+        if self.page != page {
+            self.keys.clear();
+            self.page = page;
+        }
+        self.keys.extend(keys);
+        idle && !self.keys.is_empty()
+    }
+
+    /// The next key of `page` to draw; none once all are drawn or another page is shown
+    pub fn next(&mut self, page: usize) -> Option<u8> {
+        if self.page != page {
+            self.keys.clear();
+        }
+        self.keys.pop_first()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deferred_keys_are_drawn_once_for_the_shown_page() {
+        let mut deferred = DeferredKeys::default();
+        assert!(deferred.defer(0, [3, 1]));
+        assert_eq!(deferred.next(0), Some(1));
+        // A refresh while keys wait joins them, as a resume is already queued
+        assert!(!deferred.defer(0, [3, 5]));
+        assert_eq!(deferred.next(0), Some(3));
+        assert_eq!(deferred.next(0), Some(5));
+        assert_eq!(deferred.next(0), None);
+        assert!(deferred.is_empty());
+
+        // The keys of a page that is no longer shown are not drawn
+        assert!(deferred.defer(0, [2]));
+        assert!(!deferred.defer(1, [4]));
+        assert_eq!(deferred.next(1), Some(4));
+        assert!(deferred.defer(1, [2]));
+        assert_eq!(deferred.next(0), None);
+        assert!(!deferred.defer(1, []));
+    }
+}
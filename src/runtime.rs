@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! The tokio runtime shared by the listeners and timers of the daemon.
+//!
+//! Listeners that mostly wait (the tick, device polling, scheduled events, signals and
+//! sleep notifications) run as tasks on a couple of worker threads instead of a
+//! dedicated thread each. Every task stops when [`shutdown`] cancels the token it got
+//! from [`spawn`], so the daemon no longer depends on each loop noticing a flag before
+//! it exits. Listeners blocked in a foreign event loop (X11 focus and hotkeys, the
+//! control socket) keep their own threads.
+
+use std::future::Future;
+use std::sync::LazyLock;
+use tokio::runtime::{Builder, Runtime};
+use tokio_util::sync::CancellationToken;
+
+/// Worker threads of the runtime; the tasks spend their time waiting
+const WORKER_THREADS: usize = 2;
+
+static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
+    Builder::new_multi_thread()
+        .worker_threads(WORKER_THREADS)
+        .thread_name("keydeck-runtime")
+        .enable_all()
+        .build()
+        .expect("Failed to create tokio runtime")
+});
+
+/// Cancelled once the daemon exits
+static SHUTDOWN: LazyLock<CancellationToken> = LazyLock::new(CancellationToken::new);
+
+/// Runs the task made by `task` on the shared runtime. The task gets a token that is
+/// cancelled on [`shutdown`]; the task is dropped there if it is still waiting.
+pub fn spawn<F, T>(task: T)
+where
+    T: FnOnce(CancellationToken) -> F,
+    F: Future<Output = ()> + Send + 'static,
+{
+    spawn_under(&SHUTDOWN, task);
+}
+
+/// Runs the task made by `task` on the shared runtime, until `parent` is cancelled
+fn spawn_under<F, T>(parent: &CancellationToken, task: T)
+where
+    T: FnOnce(CancellationToken) -> F,
+    F: Future<Output = ()> + Send + 'static,
+{
+    let token = parent.child_token();
+    let future = task(token.clone());
+    RUNTIME.spawn(async move {
+        tokio::select! {
+            _ = token.cancelled() => {}
+            _ = future => {}
+        }
+    });
+}
+
+/// Stops all tasks of the runtime
+pub fn shutdown() {
+    SHUTDOWN.cancel();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    struct Dropped(mpsc::Sender<()>);
+
+    impl Drop for Dropped {
+        fn drop(&mut self) {
+            let _ = self.0.send(());
+        }
+    }
+
+    #[test]
+    fn tasks_stop_on_shutdown() {
+        // A token of its own: cancelling the daemon one would stop the tasks of other tests
+        let shutdown = CancellationToken::new();
+        let (tx, rx) = mpsc::channel();
+        let dropped = Dropped(tx);
+        spawn_under(&shutdown, move |_| async move {
+            let _dropped = dropped;
+            std::future::pending::<()>().await;
+        });
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        shutdown.cancel();
+        assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+}
@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Runtime state kept across restarts: the page each device shows, whether it is
+//! pinned, whether the heatmap is on and where its cycling `focus` targets are, plus the
+//! context variables. It is written to [`keydeck::get_state_path`] every little while
+//! and when the daemon exits, and restored when it starts, so a reboot does not reset
+//! them. Pages kept across a reload are handled by the server itself.
+
+use crate::context::ContextVars;
+use crate::error_log;
+use crate::paged_device::PagedDevice;
+use indexmap::IndexMap;
+use keydeck::get_state_path;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// Shortest time between two writes of the state file while the daemon runs
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Runtime state of one device
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceState {
+    /// Name of the page shown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<String>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub heatmap: bool,
+    /// Press counters of cycling `focus` targets, keyed by target description
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub focus_cycles: BTreeMap<String, usize>,
+}
+
+/// Runtime state of all devices seen so far, by serial number
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuntimeState {
+    #[serde(default)]
+    pub devices: BTreeMap<String, DeviceState>,
+    /// Context variables, as set with `keydeck --set`
+    #[serde(default)]
+    pub vars: IndexMap<String, String>,
+    /// Content of the state file as last written or read
+    #[serde(skip)]
+    written: String,
+    #[serde(skip)]
+    last_saved: Option<Instant>,
+}
+
+impl RuntimeState {
+    /// State read from the state file; empty when there is none yet
+    pub fn load() -> Self {
+        let path = get_state_path();
+        let Ok(content) = fs::read_to_string(&path) else {
+            return RuntimeState::default();
+        };
+        match serde_json::from_str::<RuntimeState>(&content) {
+            Ok(state) => RuntimeState {
+                written: content,
+                ..state
+            },
+            Err(e) => {
+                error_log!(
+                    "Ignoring unreadable runtime state {}: {}",
+                    path.display(),
+                    e
+                );
+                RuntimeState::default()
+            }
+        }
+    }
+
+    /// Takes the state of the connected devices and the current context variables;
+    /// devices that are not connected keep their last known state
+    pub fn update(&mut self, devices: &HashMap<String, PagedDevice>, context_vars: &ContextVars) {
+        for (sn, device) in devices {
+            self.devices.insert(sn.clone(), device.runtime_state());
+        }
+        self.vars = context_vars.read().unwrap().clone();
+    }
+
+    pub fn device(&self, sn: &str) -> Option<&DeviceState> {
+        self.devices.get(sn)
+    }
+
+    /// Saves the state unless it was saved less than [`SAVE_INTERVAL`] ago
+    pub fn save_periodically(&mut self) {
+        if self
+            .last_saved
+            .is_none_or(|saved| saved.elapsed() >= SAVE_INTERVAL)
+        {
+            self.save();
+        }
+    }
+
+    /// Writes the state if it changed since it was last written
+    pub fn save(&mut self) {
+        self.last_saved = Some(Instant::now());
+        let json = match serde_json::to_string_pretty(self) {
+            Ok(json) => json,
+            Err(e) => {
+                error_log!("Error while serializing runtime state: {}", e);
+                return;
+            }
+        };
+        if json == self.written {
+            return;
+        }
+        let path = get_state_path();
+        match keydeck_types::BackupManager::STATE.save(&path, &json) {
+            Ok(_) => self.written = json,
+            Err(e) => error_log!("Error while saving runtime state {}: {}", path.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_read_as_defaults() {
+        let state: RuntimeState =
+            serde_json::from_str(r#"{"devices": {"SN1": {"page": "Main"}}}"#).unwrap();
+        let device = state.device("SN1").unwrap();
+        assert_eq!(device.page.as_deref(), Some("Main"));
+        assert!(!device.pinned && !device.heatmap);
+        assert!(state.vars.is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Safe mode after repeated crashes.
+//!
+//! Every server start is recorded in the runtime dir, and the record is cleared once the
+//! daemon has run for a while or exits cleanly. When the daemon keeps dying right after
+//! it starts, e.g. because an action, a service or the configuration itself brings it
+//! down, the next start ignores the configuration: every device shows a built-in page to
+//! reload the configuration, read the logs or quit.
+
+use crate::dynamic_detection::compute_all_dynamic_flags;
+use crate::error_log;
+use crate::pages::KeyDeckConf;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Starts in a row that did not last, after which the daemon starts in safe mode
+const CRASH_LIMIT: usize = 3;
+
+/// Starts longer ago than this are not part of a crash loop
+const CRASH_WINDOW: Duration = Duration::from_secs(300);
+
+/// A daemon that has run this long is not crashing
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+/// Recent starts, one time (seconds since epoch) per line: `<runtime-dir>/.keydeck-starts`
+static STARTS_FILE: LazyLock<PathBuf> = LazyLock::new(|| runtime_dir().join(".keydeck-starts"));
+
+/// The per-user runtime dir (`$XDG_RUNTIME_DIR`), or the temp dir
+fn runtime_dir() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|p| p.is_dir())
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+fn epoch_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Start times of a starts file that are recent enough to count
+fn recent_starts(content: &str, now: u64) -> Vec<u64> {
+    content
+        .lines()
+        .filter_map(|line| line.trim().parse::<u64>().ok())
+        .filter(|start| now.saturating_sub(*start) < CRASH_WINDOW.as_secs())
+        .collect()
+}
+
+/// Records this start; true when the earlier ones crashed too often and the daemon
+/// should start in safe mode
+pub fn record_start() -> bool {
+    let now = epoch_seconds();
+    let mut starts = recent_starts(&fs::read_to_string(&*STARTS_FILE).unwrap_or_default(), now);
+    let crash_loop = starts.len() >= CRASH_LIMIT;
+    starts.push(now);
+    let content: Vec<String> = starts.iter().map(u64::to_string).collect();
+    if let Err(e) = fs::write(&*STARTS_FILE, content.join("\n")) {
+        error_log!(
+            "Error while recording start in {}: {}",
+            STARTS_FILE.display(),
+            e
+        );
+    }
+    crash_loop
+}
+
+/// Forgets the recorded starts, e.g. when the daemon exits cleanly
+pub fn clear_starts() {
+    let _ = fs::remove_file(&*STARTS_FILE);
+}
+
+/// Forgets the recorded starts once this daemon has run long enough to be stable
+pub fn clear_starts_when_stable() {
+    thread::spawn(|| {
+        thread::sleep(STABLE_AFTER);
+        clear_starts();
+    });
+}
+
+/// Shell command that shows the daemon log
+#[cfg(target_os = "linux")]
+fn logs_command() -> String {
+    // The daemon logs to the journal; a snapshot of it is opened in the default viewer
+    let file = quoted(
+        &runtime_dir()
+            .join("keydeck-safe-mode.log")
+            .to_string_lossy(),
+    );
+    format!(
+        "journalctl --user -u keydeck -n 500 --no-pager > {} 2>&1; xdg-open {}",
+        file, file
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn logs_command() -> String {
+    format!(
+        "open {}",
+        quoted(&keydeck::get_log_path().to_string_lossy())
+    )
+}
+
+#[cfg(target_os = "windows")]
+fn logs_command() -> String {
+    format!(
+        "start \"\" {}",
+        quoted(&keydeck::get_log_path().to_string_lossy())
+    )
+}
+
+fn quoted(path: &str) -> String {
+    format!("\"{}\"", path)
+}
+
+/// The configuration used in safe mode: a single page on every device
+pub fn safe_mode_conf() -> KeyDeckConf {
+    let exe = crate::platform::lifecycle::current_exe().unwrap_or_else(|_| "keydeck".to_string());
+    let daemon = |action: &str| format!("{} --daemon {}", quoted(&exe), action);
+    // JSON strings are valid YAML scalars, whatever the paths contain
+    let yaml = format!(
+        r#"
+default:
+  main_page: Safe mode
+  Safe mode:
+    lock: true
+    button1:
+      text: "Safe\nmode"
+      background: "0xC00000"
+    button2:
+      text: "Reload\nconfig"
+      actions:
+        - exec: {}
+    button3:
+      text: "Logs"
+      actions:
+        - exec: {}
+    button4:
+      text: "Quit"
+      actions:
+        - exec: {}
+"#,
+        serde_json::json!(daemon("reload")),
+        serde_json::json!(logs_command()),
+        serde_json::json!(daemon("stop")),
+    );
+    let mut conf: KeyDeckConf =
+        serde_yaml_ng::from_str(&yaml).expect("The safe mode configuration is valid");
+    conf.apply_button_defaults();
+    compute_all_dynamic_flags(&mut conf);
+    conf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_recent_starts_count() {
+        let now = 10_000;
+        let starts = recent_starts("9990\n9000\ngarbage\n9750\n", now);
+        assert_eq!(starts, [9990, 9750]);
+    }
+
+    #[test]
+    fn safe_mode_page_is_valid() {
+        let conf = safe_mode_conf();
+        let page = &conf.page_groups["default"].pages["Safe mode"];
+        assert!(page.buttons.contains_key("button4"));
+    }
+}
@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Pictures of what a device shows, for `keydeck --screenshot`.
+//!
+//! The client sends `screenshot-device <serial> <file>` on the control socket; the daemon
+//! stitches the keys it last drew on that device in their layout, as the snapshots of the
+//! mirror are, and writes them as a PNG. Nothing is read back from the device, so this
+//! works on headless machines and needs no mirror to be configured.
+
+use image::RgbaImage;
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+
+/// Where the result of the screenshot in progress is sent
+static PENDING: Mutex<Option<Sender<Result<(), String>>>> = Mutex::new(None);
+
+/// Sends the result of the next screenshot to `reply`
+#[cfg(unix)]
+pub fn await_result(reply: Sender<Result<(), String>>) {
+    PENDING.lock().unwrap().replace(reply);
+}
+
+/// Hands the result to the client waiting for it
+pub fn deliver(result: Result<(), String>) {
+    if let Some(reply) = PENDING.lock().unwrap().take() {
+        let _ = reply.send(result);
+    }
+}
+
+/// Writes `image` as a PNG at `path`
+pub fn save(image: &RgbaImage, path: &Path) -> Result<(), String> {
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn screenshots_are_written_as_png() {
+        let path = std::env::temp_dir().join(format!("keydeck-shot-{}.png", std::process::id()));
+        let image = RgbaImage::from_pixel(4, 2, Rgba([255, 0, 0, 255]));
+        save(&image, &path).unwrap();
+        assert_eq!(image::open(&path).unwrap().to_rgba8(), image);
+        std::fs::remove_file(&path).unwrap();
+        assert!(save(&image, &path.join("missing/shot.png")).is_err());
+    }
+}
@@ -1,49 +1,292 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
+use crate::calendar;
+use crate::chats;
+use crate::config_patch;
 use crate::context::{new_context_vars, ContextVars};
+use crate::crash_report;
+use crate::device_conflicts;
 use crate::device_manager::find_device_by_serial;
-use crate::event::DeviceEvent;
+use crate::device_quirks::QuirkedDevice;
+use crate::device_trait::KeydeckDevice;
+use crate::event::{self, DeviceEvent};
+use crate::event_bus;
+use crate::event_recording::{self, EventLog};
+use crate::i18n::{self, Translations};
 use crate::konsole::KonsoleResolver;
+use crate::listener_button::set_low_latency;
 use crate::listener_device::listener_device;
-use crate::listener_tick::listener_tick;
+use crate::listener_tick::{listener_tick, TickInterval};
 use crate::platform;
 use crate::listener_time::TimeManager;
+use crate::locale_format;
 use crate::lock::{cleanup_lock, ensure_lock};
+use crate::mirajazz_device;
+use crate::mirror::{default_snapshot_dir, Mirror, MirroredDevice, SnapshotSink};
+use crate::openrgb;
+use crate::page_generator;
+use crate::page_provider;
 use crate::paged_device::PagedDevice;
-use crate::pages::KeyDeckConfLoader;
+use crate::processes;
+use crate::pages::{KeyDeckConf, KeyDeckConfLoader};
+use crate::runtime;
+use crate::runtime_state::{DeviceState, RuntimeState};
+use crate::safe_mode;
 use crate::services::new_services_state;
-use crate::{detail_log, error_log, info_log, verbose_log};
+use crate::stats::{load_shared_stats, SharedStats};
+use crate::web_deck::start_web_deck;
+use crate::zeroconf;
+use crate::{detail_log, error_log, info_log, verbose_log, warn_log};
 use indexmap::IndexMap;
-use keydeck::get_icon_dir;
-use keydeck_types::pages::{Button, Macro, Pages, ServiceConfig};
-use std::collections::HashMap;
+use chrono::{Local, Timelike};
+use keydeck_render::graphics::NightFilter;
+use keydeck_render::text::set_emoji_substitution;
+use keydeck_types::pages::{
+    Button, DeviceQuirks, GameMode, Hotkey, Macro, NightMode, NightModeSwitch, OnError, Pages,
+    ServiceConfig, Switch, Theme, Webhook,
+};
+use keydeck_types::{get_config_path, IconPaths};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// Time the devices get to reach their `shutdown` state, without `shutdown_timeout`
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time after a system resume before reconnected devices are checked and redrawn, so
+/// their firmware is done starting up
+const RESUME_CHECK_DELAY: Duration = Duration::from_secs(3);
+
+/// Devices connected this long after a system resume are checked as well
+const RESUME_WINDOW: Duration = Duration::from_secs(60);
+
+/// Outcome of the latest configuration load, answered on the control socket so the
+/// config UI can tell whether its last save was picked up.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReloadStatus {
+    /// Number of configurations applied so far (1 = the startup configuration)
+    pub config_generation: u64,
+    /// Error of the most recent reload attempt, cleared by the next successful one
+    pub last_reload_error: Option<String>,
+}
+
+/// Reload status shared between the event loop and the control socket
+pub type SharedReloadStatus = Arc<RwLock<ReloadStatus>>;
+
+/// New set of hotkey combinations for the hotkey listener to grab, replacing the current one
+pub type HotkeyUpdates = Arc<std::sync::Mutex<Option<Vec<String>>>>;
+
+/// Combinations of all configured global hotkeys
+fn hotkey_combinations(hotkeys: &Option<IndexMap<String, Hotkey>>) -> Vec<String> {
+    hotkeys
+        .as_ref()
+        .map(|hotkeys| hotkeys.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Runs the actions of a global hotkey on its device, or on the first connected one
+fn dispatch_hotkey(
+    combination: &str,
+    hotkeys: &Option<IndexMap<String, Hotkey>>,
+    devices: &HashMap<String, PagedDevice>,
+) {
+    let wanted = Hotkey::normalize(combination);
+    let Some(hotkey) = hotkeys.as_ref().and_then(|hotkeys| {
+        hotkeys
+            .iter()
+            .find(|(key, _)| Hotkey::normalize(key) == wanted)
+            .map(|(_, hotkey)| hotkey)
+    }) else {
+        warn_log!("No hotkey configured for '{}'", combination);
+        return;
+    };
+    run_bound_actions(hotkey, &format!("hotkey {}", combination), devices);
+}
+
+/// Runs the actions of the webhook called `name`
+fn dispatch_webhook(
+    name: &str,
+    webhooks: &Option<IndexMap<String, Webhook>>,
+    devices: &HashMap<String, PagedDevice>,
+) {
+    let Some(webhook) = webhooks.as_ref().and_then(|webhooks| webhooks.get(name)) else {
+        warn_log!("No webhook configured for '{}'", name);
+        return;
+    };
+    run_bound_actions(webhook, &format!("webhook {}", name), devices);
+}
+
+/// Runs the actions of a hotkey or webhook on its device, or on the first connected one
+fn run_bound_actions(binding: &Hotkey, trigger: &str, devices: &HashMap<String, PagedDevice>) {
+    let device = match binding.device() {
+        Some(sn) => devices.get(sn),
+        None => devices.iter().min_by(|a, b| a.0.cmp(b.0)).map(|(_, d)| d),
+    };
+    match device {
+        Some(device) => device.run_actions(binding.actions().to_vec(), trigger),
+        None => warn_log!("{} triggered but its device is not connected", trigger),
+    }
+}
 
 /// Helper function to dispatch wait events to all devices that might be waiting for them.
 /// Checks if the event can be waited for, and if so, notifies all devices.
 fn dispatch_wait_event(message: &DeviceEvent, devices: &HashMap<String, PagedDevice>) {
     if let Some(event_type) = message.wait_event_type() {
+        let focused = match message {
+            DeviceEvent::FocusChanges { class, title } => Some((class.as_str(), title.as_str())),
+            _ => None,
+        };
         for device in devices.values() {
-            device.check_pending_event(&event_type);
+            device.check_pending_event(&event_type, focused);
         }
     }
 }
 
+/// Night mode filter that should currently be applied: an override set by the
+/// `night_mode` action wins, otherwise the configured schedule decides.
+fn night_filter(conf: &Option<NightMode>, night_override: Option<bool>) -> Option<NightFilter> {
+    let default_conf = NightMode::default();
+    let night = conf.as_ref().unwrap_or(&default_conf);
+    let active = night_override.unwrap_or_else(|| {
+        let now = Local::now();
+        night.is_scheduled(now.hour() * 60 + now.minute())
+    });
+    active.then_some(NightFilter {
+        warmth: night.warmth,
+        gamma: night.gamma,
+    })
+}
+
+/// Brings every device to its `shutdown` state on a thread of its own, waiting at most
+/// `timeout`, so a device that stopped responding does not hold up the exit
+fn exit_devices(devices: HashMap<String, PagedDevice>, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    let count = devices.len();
+    for (sn, device) in devices {
+        let done_tx = done_tx.clone();
+        std::thread::spawn(move || {
+            device.exit();
+            let _ = done_tx.send(sn);
+        });
+    }
+    for _ in 0..count {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if done_rx.recv_timeout(left).is_err() {
+            warn_log!(
+                "Devices did not shut down within {:.1}s, exiting without them",
+                timeout.as_secs_f32()
+            );
+            return;
+        }
+    }
+}
+
+/// Closes all devices so the device listener opens them again, e.g. with new device
+/// definitions or quirks. Their pages are kept in `runtime_state`, when given.
+fn reconnect_devices(
+    devices: &mut HashMap<String, PagedDevice>,
+    runtime_state: Option<&mut RuntimeState>,
+    context_vars: &ContextVars,
+    should_reset_devices: &AtomicBool,
+) {
+    if let Some(runtime_state) = runtime_state {
+        runtime_state.update(devices, context_vars);
+        runtime_state.save();
+    }
+    for device in devices.values() {
+        device.terminate();
+    }
+    devices.clear();
+    should_reset_devices.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Turns game mode on or off on all devices and the key listeners
+fn apply_game_mode(active: bool, conf: &Option<GameMode>, devices: &HashMap<String, PagedDevice>) {
+    info_log!("Game mode {}", if active { "on" } else { "off" });
+    set_low_latency(active);
+    let page = conf.as_ref().and_then(|game| game.page.as_ref());
+    for device in devices.values() {
+        device.set_game_mode(active, page);
+    }
+}
+
+/// Gives the devices using the page group `group` its changed pages
+fn replace_group_pages(
+    devices: &mut HashMap<String, PagedDevice>,
+    conf_pages: &IndexMap<String, Pages>,
+    group: &str,
+) {
+    let Some(pages) = conf_pages.get(group) else {
+        return;
+    };
+    let pages = Arc::new(pages.clone());
+    for (sn, device) in devices.iter_mut() {
+        if config_patch::group_of(conf_pages, sn) == group {
+            device.replace_pages(pages.clone());
+        }
+    }
+}
+
+/// Translations of the configured `strings` for `${i18n:key}`; selects the locale of the
+/// `num` and `locale` filters as well
+fn translations(conf: &KeyDeckConf) -> Translations {
+    let (locale, strings) = i18n::translations(conf.strings.as_ref(), conf.locale.as_deref());
+    locale_format::set_locale(&locale);
+    if conf.strings.is_some() {
+        verbose_log!("Using {} translated strings for {}", strings.len(), locale);
+    }
+    strings
+}
+
+/// Creates the mirror of the device keys with its sinks, when any of them is configured
+fn start_mirror(
+    conf: &KeyDeckConf,
+    tx: &std::sync::mpsc::Sender<DeviceEvent>,
+    still_active: &Arc<AtomicBool>,
+) -> Option<Mirror> {
+    if conf.web_deck.is_none() && conf.snapshots.is_none() {
+        return None;
+    }
+    let mirror = Mirror::default();
+    if let Some(web) = &conf.web_deck {
+        start_web_deck(web, &mirror, tx, still_active);
+        zeroconf::advertise(web);
+    }
+    if let Some(snapshots) = &conf.snapshots {
+        let dir = snapshots
+            .dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(default_snapshot_dir);
+        match SnapshotSink::start(dir) {
+            Ok(sink) => mirror.add_sink(Box::new(sink)),
+            Err(e) => error_log!("{}", e),
+        }
+    }
+    Some(mirror)
+}
+
 /// Helper function to initialize a device with given configuration.
 /// Always creates the device, even if no config exists (device will be inactive until config is provided).
 fn initialize_device(
     sn: &str,
     conf_pages: &Arc<IndexMap<String, Pages>>,
     conf_colors: &Arc<Option<IndexMap<String, String>>>,
+    conf_themes: &Arc<Option<IndexMap<String, Theme>>>,
     conf_buttons: &Arc<Option<IndexMap<String, Button>>>,
     conf_macros: &Arc<Option<IndexMap<String, Macro>>>,
     conf_services: &Arc<Option<IndexMap<String, ServiceConfig>>>,
+    conf_on_error: &Arc<Option<OnError>>,
     services_state: &crate::services::ServicesState,
     services_active: &Arc<AtomicBool>,
     context_vars: &ContextVars,
-    icon_dir: Option<&String>,
+    usage_stats: &SharedStats,
+    icon_paths: &Arc<IconPaths>,
     tx: &std::sync::mpsc::Sender<DeviceEvent>,
     time_manager: &Arc<TimeManager>,
     current_class: &str,
@@ -52,9 +295,17 @@ fn initialize_device(
     conf_background_image: Option<String>,
     devices: &mut HashMap<String, PagedDevice>,
     initial_page: Option<String>,
+    restored: Option<&DeviceState>,
+    mirror: Option<&Mirror>,
+    quirks: Option<&DeviceQuirks>,
 ) {
-    if let Some(device) = find_device_by_serial(sn) {
+    let device: Option<Box<dyn KeydeckDevice>> = match event_recording::replayed_device(sn) {
+        Some(device) => Some(Box::new(device)),
+        None => find_device_by_serial(sn).map(|device| Box::new(device) as Box<dyn KeydeckDevice>),
+    };
+    if let Some(device) = device {
         info_log!("Adding device {}", sn);
+        event_recording::record_device(sn, device.as_ref());
         verbose_log!("Looking for configuration for device serial: '{}'", sn);
         verbose_log!(
             "Available page groups: {:?}",
@@ -74,50 +325,117 @@ fn initialize_device(
                 main_page: None,
                 restore_mode: keydeck_types::pages::FocusChangeRestorePolicy::Main,
                 press_effect: Default::default(),
+                max_fps: None,
+                orientation: None,
+                focus_debounce: None,
+                input_polling: None,
+                pin_indicator: None,
+                rotation: None,
+                on_start: None,
+                on_connect: None,
+                on_disconnect: None,
+                shutdown: None,
                 pages: IndexMap::new(),
             })
         };
 
+        let device = QuirkedDevice::wrap(device, quirks);
+        let device: Box<dyn KeydeckDevice> = match mirror {
+            Some(mirror) => Box::new(MirroredDevice::new(device, mirror)),
+            None => device,
+        };
         let new_device = PagedDevice::new(
             pages_arc,
-            icon_dir.cloned(),
+            icon_paths.clone(),
             conf_colors.clone(),
+            conf_themes.clone(),
             conf_buttons.clone(),
             conf_macros.clone(),
             conf_services.clone(),
+            conf_on_error.clone(),
             services_state.clone(),
             services_active.clone(),
             context_vars.clone(),
-            Box::new(device),
+            usage_stats.clone(),
+            device,
             tx,
             time_manager.clone(),
             initial_page,
             conf_brightness,
             conf_background_image.clone(),
         );
+        // Restored before the focus is applied, so a pinned page stays
+        if let Some(state) = restored {
+            new_device.restore_runtime_state(state);
+        }
         new_device.focus_changed(current_class, current_title, false);
         devices.insert(sn.to_string(), new_device);
     }
 }
 
-pub fn start_server() {
+pub fn start_server(event_log: Option<EventLog>) {
     ensure_lock();
     info_log!("Starting KeyDeck Server");
 
     // Keep any installed terminal integration's watcher in sync with this binary.
     crate::integrations::refresh_installed();
+    crate::icon_cache::prune_in_background();
+
+    // A crash loop starts with the built-in safe mode page instead of the configuration,
+    // until the configuration is reloaded
+    let mut in_safe_mode = safe_mode::record_start();
+    let conf = if in_safe_mode {
+        error_log!("KeyDeck stopped repeatedly right after starting; starting in safe mode");
+        if let Err(e) = platform::send_notification(
+            "KeyDeck safe mode",
+            "KeyDeck crashed repeatedly. Fix the configuration, then reload it from the device.",
+        ) {
+            error_log!("{}", e);
+        }
+        safe_mode::safe_mode_conf()
+    } else {
+        safe_mode::clear_starts_when_stable();
+        KeyDeckConfLoader::load()
+    };
+
+    crash_report::remember_config(&conf);
 
     // Configuration - now reloadable via SIGHUP using Arc
-    let conf = Arc::new(KeyDeckConfLoader::load());
+    let conf = Arc::new(conf);
     let mut conf_pages = Arc::new(conf.page_groups.clone());
     let mut conf_colors = Arc::new(conf.colors.clone());
+    let mut conf_themes = Arc::new(conf.themes.clone());
     let mut conf_buttons = Arc::new(conf.buttons.clone());
     let mut conf_macros = Arc::new(conf.macros.clone());
     let mut conf_services = Arc::new(conf.services.clone());
-    let icon_dir = Some(get_icon_dir());
+    let mut conf_on_error = Arc::new(conf.on_error.clone());
+    let mut icon_paths = Arc::new(IconPaths::new(
+        &get_config_path(),
+        conf.icon_dirs.as_deref(),
+    ));
     let mut conf_brightness = conf.brightness;
     let mut conf_background_image = conf.background_image.clone();
+    let mut conf_night_mode = conf.night_mode.clone();
+    let mut conf_accessibility = conf.accessibility.clone();
+    let mut conf_press_sound = conf.press_sound.clone();
+    let mut conf_quirks = conf.quirks.clone();
+    let mut conf_shutdown_timeout = conf.shutdown_timeout;
+    set_emoji_substitution(conf.emoji != Some(false));
+    openrgb::configure(conf.openrgb.as_ref());
+    let mut conf_strings = translations(&conf);
+    // Set by the `night_mode` action; None follows the schedule
+    let mut night_override: Option<bool> = None;
+    let mut conf_game_mode = conf.game_mode.clone();
+    // Set by the `game_mode` action, and by focusing or leaving a window of its `classes`
+    let mut game_mode = false;
+    let mut game_window_focused = false;
+    // Globally active theme; starts from `theme` and is switched by the `theme` action
+    let mut active_theme = conf.theme.clone();
     let conf_tick_time = Arc::new(std::sync::Mutex::new(conf.tick_time));
+    let mut conf_hotkeys = conf.hotkeys.clone();
+    let mut conf_webhooks = conf.webhooks.clone();
+    let hotkeys_grabbed = hotkey_combinations(&conf_hotkeys);
+    let hotkey_updates: HotkeyUpdates = Arc::new(std::sync::Mutex::new(Some(hotkeys_grabbed)));
 
     // Initialize with empty focus - listener will send current window immediately
     let (mut current_class, mut current_title) = (String::new(), String::new());
@@ -127,16 +445,35 @@ pub fn start_server() {
     let should_reset_devices = Arc::new(AtomicBool::new(false));
 
     // Create TimeManager for handling async wait timers
-    let time_manager = Arc::new(TimeManager::new(tx.clone(), still_active.clone()));
+    let time_manager = Arc::new(TimeManager::new(tx.clone()));
 
     // Create shared services state for dynamic buttons - can be replaced on reload
     let mut services_state = new_services_state();
     let mut services_active = Arc::new(AtomicBool::new(true));
+    // Page providers are restarted on every reload, services only when they changed
+    let mut providers_active = Arc::new(AtomicBool::new(true));
 
     // External context variables (set via `keydeck --set`). Independent of the config
     // file, so it is created once and survives reloads.
     let context_vars: ContextVars = new_context_vars();
 
+    // Pages, pins and context variables of the previous run
+    let mut runtime_state = RuntimeState::load();
+    if !in_safe_mode {
+        context_vars
+            .write()
+            .unwrap()
+            .extend(runtime_state.vars.clone());
+    }
+
+    // Press counts and page dwell times, carried over from earlier runs
+    let usage_stats: SharedStats = load_shared_stats();
+
+    let reload_status: SharedReloadStatus = Arc::new(RwLock::new(ReloadStatus {
+        config_generation: 1,
+        last_reload_error: None,
+    }));
+
     // Konsole terminal-context resolver. Triggered on konsole focus/caption events;
     // publishes `terminal_app` like the kitty watcher. Idle (and
     // thread-lazy) unless `konsole_context` is on. A no-op stub on non-Linux.
@@ -156,12 +493,40 @@ pub fn start_server() {
         pull_triggers.extend(konsole.as_pull_trigger());
     }
 
-    platform::spawn_sleep_listener(&tx, &still_active.clone(), &should_reset_devices);
-    listener_device(&tx, &still_active.clone(), &should_reset_devices);
-    platform::spawn_focus_listener(&tx, &still_active.clone());
+    let logging = match &event_log {
+        Some(EventLog::Record(path)) => event_recording::start_recording(path),
+        Some(EventLog::Replay(path)) => event_recording::start_replay(path, &tx),
+        None => Ok(()),
+    };
+    if let Err(e) = logging {
+        error_log!("{}", e);
+        std::process::exit(1);
+    }
+    // A replay brings its own devices, focus changes and sleep
+    if !event_recording::is_replaying() {
+        device_conflicts::check_at_start(conf.wait_for_release);
+        platform::spawn_sleep_listener(&tx, &still_active.clone(), &should_reset_devices);
+        listener_device(&tx, &should_reset_devices);
+        platform::set_focus_backends(conf.focus_backend.clone());
+        platform::spawn_focus_listener(&tx, &still_active.clone());
+    }
     platform::spawn_control_listener(&tx, &still_active.clone());
-    platform::spawn_context_listener(&tx, &still_active.clone());
-    listener_tick(&tx, &still_active.clone(), conf_tick_time.clone());
+    platform::spawn_state_service();
+    platform::spawn_context_listener(&tx, &still_active.clone(), &reload_status, &usage_stats);
+    platform::spawn_hotkey_listener(&tx, &still_active.clone(), &hotkey_updates);
+    // Changes to `web_deck`, `snapshots`, `chats` and `calendars` apply after a restart, like
+    // the listeners above
+    let key_mirror = start_mirror(&conf, &tx, &still_active);
+    if let Some(conf_chats) = &conf.chats {
+        chats::start(conf_chats, &tx, &still_active);
+    }
+    if let Some(calendars) = conf.calendars.as_ref().filter(|c| !c.is_empty()) {
+        calendar::start(calendars, &tx, &still_active);
+    }
+    page_generator::fetch_missing_icons(&tx);
+    page_provider::start(&conf, &tx, &providers_active);
+    let tick_interval = TickInterval::new(conf.tick_time);
+    listener_tick(&tx, tick_interval.clone());
 
     // The event loop is wrapped in a closure so that, on macOS, it can run on a
     // worker thread while the main thread runs the Cocoa run loop — required to
@@ -174,7 +539,16 @@ pub fn start_server() {
         let mut devices: HashMap<String, PagedDevice> = HashMap::new();
         // Track saved page states across reload events
         let mut saved_pages: HashMap<String, String> = HashMap::new();
+        // Devices whose `on_start` actions already ran in this daemon run
+        let mut started: HashSet<String> = HashSet::new();
+        // When the system last resumed from sleep
+        let mut resumed_at: Option<Instant> = None;
         for message in rx {
+            if message.is_input() {
+                event::input_taken();
+            }
+            event_recording::record(&message);
+            event_bus::publish(&message);
             match message {
             DeviceEvent::ButtonDown { sn, button_id } => {
                 detail_log!("[{}] Button {} pressed", sn, button_id);
@@ -257,10 +631,32 @@ pub fn start_server() {
                 }
                 // Dispatch wait event first
                 dispatch_wait_event(message, &devices);
+                // Game mode follows the game window: it turns on before the devices see the
+                // focus change, so they ignore it, and off after, so they follow it again
+                let game_window = conf_game_mode
+                    .as_ref()
+                    .is_some_and(|game| game.matches_class(&current_class));
+                let game_switch = (game_window != game_window_focused).then_some(game_window);
+                game_window_focused = game_window;
+                if game_switch == Some(true) && !game_mode {
+                    game_mode = true;
+                    apply_game_mode(game_mode, &conf_game_mode, &devices);
+                }
                 // Then handle normal focus change
                 for device in devices.values() {
                     device.focus_changed(&current_class, &current_title, false);
                 }
+                if game_switch == Some(false) && game_mode {
+                    game_mode = false;
+                    apply_game_mode(game_mode, &conf_game_mode, &devices);
+                }
+            }
+            DeviceEvent::FocusBackendReady { backend } => {
+                // Focus seen before, if any, came from a backend that has since gone away;
+                // the backend reports the current focus next, which devices then follow
+                verbose_log!("Focus backend {} ready, resynchronizing focus", backend);
+                current_class.clear();
+                current_title.clear();
             }
             DeviceEvent::SetContextVar { key, value } => {
                 // The daemon is the single point of dedup: sources (konsole resolver,
@@ -296,9 +692,19 @@ pub fn start_server() {
                 // Dispatch wait event first
                 dispatch_wait_event(message, &devices);
                 // Then handle tick
+                let filter = night_filter(&conf_night_mode, night_override);
+                let tick_time = *conf_tick_time.lock().unwrap();
+                let generation = reload_status.read().unwrap().config_generation;
                 for device in devices.values() {
                     device.get_hardware().keep_alive();
-                    device.handle_tick();
+                    device.set_night_filter(filter);
+                    device.handle_tick(tick_time);
+                    device.update_diagnostics(tick_time, generation);
+                }
+                usage_stats.write().unwrap().save_periodically();
+                if !in_safe_mode {
+                    runtime_state.update(&devices, &context_vars);
+                    runtime_state.save_periodically();
                 }
             }
             ref message @ DeviceEvent::NewDevice { ref sn } => {
@@ -306,8 +712,12 @@ pub fn start_server() {
                 dispatch_wait_event(message, &devices);
                 // Then handle new device
                 if !devices.contains_key(sn) {
-                    // Check if we have a saved page for this device (from reload)
-                    let initial_page = saved_pages.remove(sn).clone();
+                    // Check if we have a saved page for this device (from reload), or
+                    // else the page it showed in the previous run
+                    let restored = runtime_state.device(sn).filter(|_| !in_safe_mode).cloned();
+                    let initial_page = saved_pages
+                        .remove(sn)
+                        .or_else(|| restored.as_ref().and_then(|state| state.page.clone()));
                     if initial_page.is_some() {
                         verbose_log!(
                             "Restoring device {} to page '{}'",
@@ -319,13 +729,16 @@ pub fn start_server() {
                         sn,
                         &conf_pages,
                         &conf_colors,
+                        &conf_themes,
                         &conf_buttons,
                         &conf_macros,
                         &conf_services,
+                        &conf_on_error,
                         &services_state,
                         &services_active,
                         &context_vars,
-                        icon_dir.as_ref(),
+                        &usage_stats,
+                        &icon_paths,
                         &tx,
                         &time_manager,
                         &current_class,
@@ -334,21 +747,62 @@ pub fn start_server() {
                         conf_background_image.clone(),
                         &mut devices,
                         initial_page,
+                        restored.as_ref(),
+                        key_mirror.as_ref(),
+                        conf_quirks.as_ref().and_then(|quirks| quirks.get(sn)),
                     );
+                    if let Some(device) = devices.get(sn) {
+                        device.set_night_filter(night_filter(&conf_night_mode, night_override));
+                        device.set_accessibility(conf_accessibility.clone());
+                        device.set_press_sound(conf_press_sound.clone());
+                        device.set_strings(conf_strings.clone());
+                        device.set_theme(active_theme.clone());
+                        device.set_game_mode(
+                            game_mode,
+                            conf_game_mode.as_ref().and_then(|game| game.page.as_ref()),
+                        );
+                        if started.insert(sn.clone()) {
+                            device.run_start_actions();
+                        }
+                        device.run_hotplug_actions(true);
+                        if resumed_at.is_some_and(|at| at.elapsed() < RESUME_WINDOW) {
+                            time_manager.schedule_verify_device(sn.clone(), RESUME_CHECK_DELAY);
+                        }
+                    }
                 }
             }
             ref message @ DeviceEvent::RemovedDevice { ref sn } => {
                 // Dispatch wait event first
                 dispatch_wait_event(message, &devices);
-                // Then handle device removal
+                // Then handle device removal, keeping the state it leaves in
+                if !in_safe_mode {
+                    runtime_state.update(&devices, &context_vars);
+                }
                 if let Some(device) = devices.remove(sn) {
                     info_log!("Removing device {}", sn);
+                    device.run_hotplug_actions(false);
                     device.disable();
                 }
             }
             DeviceEvent::Reload => {
                 info_log!("Reloading Configuration (SIGHUP received)");
 
+                // Edited device definitions apply to devices opened after the reload, so
+                // the devices are closed and the device listener opens them again
+                match mirajazz_device::reload_registry() {
+                    Ok(true) => {
+                        info_log!("Device definitions changed, reconnecting devices");
+                        reconnect_devices(
+                            &mut devices,
+                            (!in_safe_mode).then_some(&mut runtime_state),
+                            &context_vars,
+                            &should_reset_devices,
+                        );
+                    }
+                    Ok(false) => {}
+                    Err(e) => error_log!("{}; keeping the current device definitions", e),
+                }
+
                 // Load the new configuration BEFORE disrupting anything. An invalid
                 // config must not kill a running daemon, so on error we log it and
                 // keep the current in-memory configuration untouched.
@@ -358,20 +812,80 @@ pub fn start_server() {
                     Err(e) => {
                         error_log!("Failed to reload configuration; keeping the running configuration:");
                         error_log!("{}", e);
+                        if conf_on_error.as_ref().as_ref().is_some_and(|policy| policy.notify) {
+                            let summary = e.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+                            if let Err(err) = platform::send_notification("KeyDeck configuration error", summary) {
+                                error_log!("{}", err);
+                            }
+                        }
+                        for device in devices.values() {
+                            device.show_error_page(&e);
+                        }
+                        reload_status.write().unwrap().last_reload_error = Some(e);
                         continue;
                     }
                 };
+                {
+                    let mut status = reload_status.write().unwrap();
+                    status.config_generation += 1;
+                    status.last_reload_error = None;
+                }
+                crash_report::remember_config(&new_conf);
+                page_generator::fetch_missing_icons(&tx);
+                if in_safe_mode {
+                    info_log!("Leaving safe mode");
+                    in_safe_mode = false;
+                    safe_mode::clear_starts();
+                }
 
-                // Stop old services (but keep devices running)
-                services_active.store(false, std::sync::atomic::Ordering::Relaxed);
+                // Services keep running, and their values shown, when none of them changed
+                let services_changed = new_conf.services != *conf_services;
+                if services_changed {
+                    // Stop old services (but keep devices running)
+                    services_active.store(false, std::sync::atomic::Ordering::Relaxed);
+                }
+                providers_active.store(false, std::sync::atomic::Ordering::Relaxed);
                 conf_pages = Arc::new(new_conf.page_groups.clone());
                 conf_colors = Arc::new(new_conf.colors.clone());
+                conf_themes = Arc::new(new_conf.themes.clone());
+                // Keep a theme picked at runtime as long as it still exists
+                if !active_theme.as_ref().is_some_and(|theme| {
+                    new_conf.themes.as_ref().is_some_and(|themes| themes.contains_key(theme))
+                }) {
+                    active_theme = new_conf.theme.clone();
+                }
                 conf_buttons = Arc::new(new_conf.buttons.clone());
                 conf_macros = Arc::new(new_conf.macros.clone());
                 conf_services = Arc::new(new_conf.services.clone());
-                // icon_dir remains hard-coded - no need to update
+                conf_on_error = Arc::new(new_conf.on_error.clone());
+                icon_paths = Arc::new(IconPaths::new(
+                    &get_config_path(),
+                    new_conf.icon_dirs.as_deref(),
+                ));
+                let brightness_changed = new_conf.brightness != conf_brightness;
                 conf_brightness = new_conf.brightness;
                 conf_background_image = new_conf.background_image.clone();
+                conf_night_mode = new_conf.night_mode.clone();
+                conf_game_mode = new_conf.game_mode.clone();
+                conf_accessibility = new_conf.accessibility.clone();
+                conf_press_sound = new_conf.press_sound.clone();
+                conf_shutdown_timeout = new_conf.shutdown_timeout;
+                if new_conf.quirks != conf_quirks {
+                    info_log!("Device quirks changed, reconnecting devices");
+                    conf_quirks = new_conf.quirks.clone();
+                    reconnect_devices(
+                        &mut devices,
+                        (!in_safe_mode).then_some(&mut runtime_state),
+                        &context_vars,
+                        &should_reset_devices,
+                    );
+                }
+                set_emoji_substitution(new_conf.emoji != Some(false));
+                openrgb::configure(new_conf.openrgb.as_ref());
+                conf_strings = translations(&new_conf);
+                conf_hotkeys = new_conf.hotkeys.clone();
+                conf_webhooks = new_conf.webhooks.clone();
+                *hotkey_updates.lock().unwrap() = Some(hotkey_combinations(&conf_hotkeys));
                 konsole.set_apps(
                     new_conf
                         .konsole_apps
@@ -388,10 +902,15 @@ pub fn start_server() {
 
                 // Update tick_time in the mutex (will be used in next tick cycle)
                 *conf_tick_time.lock().unwrap() = new_conf.tick_time;
+                platform::set_focus_backends(new_conf.focus_backend.clone());
 
                 // Create new services state and active flag
-                services_state = new_services_state();
-                services_active = Arc::new(AtomicBool::new(true));
+                if services_changed {
+                    services_state = new_services_state();
+                    services_active = Arc::new(AtomicBool::new(true));
+                }
+                providers_active = Arc::new(AtomicBool::new(true));
+                page_provider::start(&new_conf, &tx, &providers_active);
 
                 // Update all connected devices with new configuration
                 info_log!(
@@ -412,6 +931,16 @@ pub fn start_server() {
                             main_page: None,
                             restore_mode: keydeck_types::pages::FocusChangeRestorePolicy::Main,
                             press_effect: Default::default(),
+                            max_fps: None,
+                            orientation: None,
+                            focus_debounce: None,
+                            input_polling: None,
+                            pin_indicator: None,
+                            rotation: None,
+                            on_start: None,
+                            on_connect: None,
+                            on_disconnect: None,
+                            shutdown: None,
                             pages: IndexMap::new(),
                         })
                     };
@@ -419,28 +948,50 @@ pub fn start_server() {
                     device.reload(
                         pages_arc,
                         conf_colors.clone(),
+                        conf_themes.clone(),
                         conf_buttons.clone(),
                         conf_macros.clone(),
                         conf_services.clone(),
+                        conf_on_error.clone(),
                         services_state.clone(),
                         services_active.clone(),
-                        conf_brightness,
+                        brightness_changed.then_some(conf_brightness),
                         conf_background_image.clone(),
                     );
                 }
 
+                let filter = night_filter(&conf_night_mode, night_override);
+                for device in devices.values() {
+                    device.set_night_filter(filter);
+                    device.set_theme(active_theme.clone());
+                    device.set_accessibility(conf_accessibility.clone());
+                    device.set_press_sound(conf_press_sound.clone());
+                    device.set_strings(conf_strings.clone());
+                    device.set_icon_paths(icon_paths.clone());
+                }
+
                 info_log!("Configuration reloaded successfully");
             }
             DeviceEvent::Exit => {
                 info_log!("Exiting Application");
-                for device in devices.values() {
-                    device.terminate();
+                if !in_safe_mode {
+                    runtime_state.update(&devices, &context_vars);
+                    runtime_state.save();
                 }
+                let timeout = conf_shutdown_timeout
+                    .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                    .map_or(SHUTDOWN_TIMEOUT, Duration::from_secs_f32);
+                exit_devices(std::mem::take(&mut devices), timeout);
+                usage_stats.write().unwrap().save();
                 still_active.store(false, std::sync::atomic::Ordering::Relaxed);
+                processes::kill_on_exit();
+                runtime::shutdown();
 
                 // Platform-specific cleanup before exiting (e.g. KWin scripts on Wayland).
                 platform::on_exit_cleanup();
 
+                zeroconf::stop_advertising();
+                safe_mode::clear_starts();
                 cleanup_lock();
                 break; // Exit the event loop gracefully
             }
@@ -450,12 +1001,20 @@ pub fn start_server() {
                 // Handle sleep event
                 if sleep {
                     verbose_log!("Sleeping");
+                    if !in_safe_mode {
+                        runtime_state.update(&devices, &context_vars);
+                        runtime_state.save();
+                    }
                     for device in devices.values() {
                         device.terminate();
                     }
                     devices.clear();
                 } else {
                     verbose_log!("Waking up");
+                    resumed_at = Some(Instant::now());
+                    for sn in devices.keys() {
+                        time_manager.schedule_verify_device(sn.clone(), RESUME_CHECK_DELAY);
+                    }
                 }
             }
             ref message @ DeviceEvent::TimerComplete { ref sn } => {
@@ -474,7 +1033,218 @@ pub fn start_server() {
                         });
                 }
             }
+            DeviceEvent::RefreshButton { sn, button_id } => {
+                if let Some(device) = devices.get(&sn) {
+                    device.refresh_button(button_id);
+                }
+            }
+            DeviceEvent::RepeatButton { sn, button_id, press } => {
+                if let Some(device) = devices.get(&sn) {
+                    device.repeat_button(button_id, press);
+                }
+            }
+            DeviceEvent::TimerUp { sn, run } => {
+                if let Some(device) = devices.get(&sn) {
+                    device.timer_up(run);
+                }
+            }
+            DeviceEvent::FocusSettled { sn, focus } => {
+                if let Some(device) = devices.get(&sn) {
+                    device.focus_settled(focus);
+                }
+            }
+            DeviceEvent::EchoStep { sn, echo } => {
+                if let Some(device) = devices.get(&sn) {
+                    device.echo_step(echo);
+                }
+            }
+            DeviceEvent::ConfirmTimeout { sn, confirmation } => {
+                if let Some(device) = devices.get(&sn) {
+                    device.confirm_timeout(confirmation);
+                }
+            }
+            DeviceEvent::ResumeRefresh { sn } => {
+                if let Some(device) = devices.get(&sn) {
+                    device.resume_refresh();
+                }
+            }
+            DeviceEvent::NightMode { mode } => {
+                let active = night_filter(&conf_night_mode, night_override).is_some();
+                night_override = match mode {
+                    NightModeSwitch::On => Some(true),
+                    NightModeSwitch::Off => Some(false),
+                    NightModeSwitch::Toggle => Some(!active),
+                    NightModeSwitch::Auto => None,
+                };
+                let filter = night_filter(&conf_night_mode, night_override);
+                for device in devices.values() {
+                    device.set_night_filter(filter);
+                }
+            }
+            DeviceEvent::GameMode { mode } => {
+                let active = match mode {
+                    Switch::On => true,
+                    Switch::Off => false,
+                    Switch::Toggle => !game_mode,
+                };
+                if active != game_mode {
+                    game_mode = active;
+                    apply_game_mode(game_mode, &conf_game_mode, &devices);
+                }
+            }
+            DeviceEvent::SetTheme { theme } => {
+                info_log!("Switching theme to '{}'", theme);
+                active_theme = Some(theme);
+                for device in devices.values() {
+                    device.set_theme(active_theme.clone());
+                }
+            }
+            DeviceEvent::Hotkey { combination } => {
+                verbose_log!("Hotkey '{}' pressed", combination);
+                dispatch_hotkey(&combination, &conf_hotkeys, &devices);
+            }
+            DeviceEvent::Webhook { name } => {
+                verbose_log!("Webhook '{}' called", name);
+                dispatch_webhook(&name, &conf_webhooks, &devices);
+            }
+            DeviceEvent::ProviderUpdated { provider } => {
+                for device in devices.values() {
+                    device.refresh_buttons_using(&provider);
+                }
+            }
+            DeviceEvent::TestPattern { sn } => {
+                for (serial, device) in &devices {
+                    if sn.as_ref().is_none_or(|sn| sn == serial) {
+                        device.show_test_pattern();
+                    }
+                }
+            }
+            DeviceEvent::TestPatternEnd { sn } => {
+                if let Some(device) = devices.get(&sn) {
+                    device.end_test_pattern();
+                }
+            }
+            DeviceEvent::VerifyDevice { sn } => {
+                let Some(device) = devices.get(&sn) else {
+                    continue;
+                };
+                match device.verify_after_resume() {
+                    Ok(()) => verbose_log!("Device {} verified after resume", sn),
+                    Err(e) => {
+                        warn_log!("Device {} did not come back from sleep ({}), reconnecting", sn, e);
+                        if let Some(page) = device.get_current_page_name() {
+                            saved_pages.insert(sn.clone(), page);
+                        }
+                        device.terminate();
+                        devices.remove(&sn);
+                        should_reset_devices.store(true, std::sync::atomic::Ordering::Relaxed);
+                    }
+                }
+            }
+            DeviceEvent::Diagnostics { sn } => {
+                let tick_time = *conf_tick_time.lock().unwrap();
+                let generation = reload_status.read().unwrap().config_generation;
+                for (serial, device) in &devices {
+                    if sn.as_ref().is_none_or(|sn| sn == serial) {
+                        device.show_diagnostics(tick_time, generation);
+                    }
+                }
+            }
+            DeviceEvent::Bench { sn } => {
+                let reports = devices
+                    .iter()
+                    .filter(|(serial, _)| sn.as_ref().is_none_or(|sn| sn == *serial))
+                    .map(|(_, device)| device.bench())
+                    .filter(|report| report.render.samples > 0)
+                    .collect();
+                crate::bench::deliver(reports);
+            }
+            DeviceEvent::Screenshot { sn, path } => {
+                let result = match devices.get(&sn) {
+                    Some(device) => crate::screenshot::save(&device.screenshot(), &path),
+                    None => Err(format!("No device with serial {}", sn)),
+                };
+                crate::screenshot::deliver(result);
+            }
+            DeviceEvent::Services => {
+                crate::services::deliver_reports(crate::services::reports(
+                    &conf_services,
+                    &services_state,
+                ));
+            }
+            DeviceEvent::PatchButton { target, patch } => {
+                let mut page_groups = (*conf_pages).clone();
+                let result = config_patch::apply(
+                    &mut page_groups,
+                    &conf_buttons,
+                    &conf_macros,
+                    &target,
+                    &patch,
+                )
+                .and_then(|patched| {
+                    if patch.save {
+                        config_patch::save(&target, &patch, patched.created)?;
+                    }
+                    Ok(patched.group)
+                });
+                match &result {
+                    Ok(group) => {
+                        let saved = if patch.save { " and saved" } else { "" };
+                        info_log!("Button {} changed{}", target, saved);
+                        conf_pages = Arc::new(page_groups);
+                        replace_group_pages(&mut devices, &conf_pages, group);
+                    }
+                    Err(e) => error_log!("Failed to change button {}: {}", target, e),
+                }
+                config_patch::deliver_result(result.map(|_| ()));
+            }
+            DeviceEvent::SetPage { target, page } => {
+                let mut result = Err(format!("No device uses page group or serial '{}'", target));
+                for (sn, device) in &devices {
+                    if *sn == target || config_patch::group_of(&conf_pages, sn) == target {
+                        result = device.show_page(&page).map_err(|e| e.to_string());
+                        if result.is_err() {
+                            break;
+                        }
+                    }
+                }
+                config_patch::deliver_result(result);
+            }
+            DeviceEvent::PageProvided { group, page } => {
+                let Some(buttons) = page_provider::take(&group, &page) else {
+                    continue;
+                };
+                let mut page_groups = (*conf_pages).clone();
+                let provider = page_groups
+                    .get_mut(&group)
+                    .and_then(|pages| pages.pages.get_mut(&page))
+                    .and_then(|page| page.provider.as_mut());
+                // The provider of a configuration replaced since
+                let Some(provider) = provider else {
+                    continue;
+                };
+                provider.buttons = buttons;
+                conf_pages = Arc::new(page_groups);
+                replace_group_pages(&mut devices, &conf_pages, &group);
+            }
         }
+            crash_report::remember_devices(
+                devices
+                    .iter()
+                    .map(|(sn, device)| (sn.as_str(), device.get_hardware())),
+            );
+
+            // Ticks come as often as the shown page that needs them most frequently
+            let tick_time = *conf_tick_time.lock().unwrap();
+            let shortest = devices
+                .values()
+                .map(|device| device.tick_interval(tick_time))
+                .fold(f64::INFINITY, f64::min);
+            tick_interval.set(if shortest.is_finite() {
+                shortest
+            } else {
+                tick_time
+            });
         }
     };
 
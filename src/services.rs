@@ -5,15 +5,58 @@ use crate::dynamic_params::ERROR_INDICATOR;
 use crate::pages::ServiceConfig;
 use crate::{error_log, verbose_log};
 use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::process::{Command, Stdio};
 use std::sync::atomic::AtomicBool;
-use std::sync::{Arc, RwLock};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-/// Shared state for all services. Maps service name to current cached value.
-pub type ServicesState = Arc<RwLock<HashMap<String, String>>>;
+/// Value shown before the first run of a service ends
+const LOADING_INDICATOR: &str = "...";
+
+/// Cached result of a service and how its last run went
+#[derive(Debug, Clone)]
+pub struct ServiceStatus {
+    /// Output of the last run, [`ERROR_INDICATOR`] if it failed
+    pub value: String,
+    /// Why the last run failed
+    pub error: Option<String>,
+    /// How long the last run took
+    pub last_duration: Option<Duration>,
+    /// When the last run ended; the next one starts an interval later
+    pub finished: Option<Instant>,
+}
+
+/// Shared state for all services. Maps service name to its current status.
+pub type ServicesState = Arc<RwLock<HashMap<String, ServiceStatus>>>;
+
+/// A configured service as `keydeck --services` shows it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceReport {
+    pub name: String,
+    pub exec: String,
+    pub interval: f64,
+    /// Services start when a button first shows `${service:name}`
+    pub started: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_duration_ms: Option<f64>,
+    /// Seconds since the last run ended
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run_secs_ago: Option<f64>,
+    /// Seconds until the next run starts; None while the first run is still going
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_run_in_secs: Option<f64>,
+}
+
+/// Where the reports asked for on the control socket are sent
+static PENDING: Mutex<Option<Sender<Vec<ServiceReport>>>> = Mutex::new(None);
 
 /// Creates a new empty services state
 pub fn new_services_state() -> ServicesState {
@@ -43,24 +86,31 @@ pub fn spawn_service(
 
         while still_active.load(std::sync::atomic::Ordering::Relaxed) {
             // Execute command with timeout
+            let started = Instant::now();
             let result = execute_with_timeout(&command, timeout);
+            let (value, error) = match result {
+                Ok(output) => {
+                    // Trim left and right whitespace, preserve internal spaces
+                    let trimmed = output.trim().to_string();
+                    verbose_log!("Service '{}' updated: {}", name, trimmed);
+                    (trimmed, None)
+                }
+                Err(e) => {
+                    error_log!("Service '{}' failed: {}", name, e);
+                    (ERROR_INDICATOR.to_string(), Some(e))
+                }
+            };
 
             // Update shared state
-            {
-                let mut state_lock = state.write().unwrap();
-                match result {
-                    Ok(output) => {
-                        // Trim left and right whitespace, preserve internal spaces
-                        let trimmed = output.trim().to_string();
-                        verbose_log!("Service '{}' updated: {}", name, trimmed);
-                        state_lock.insert(name.clone(), trimmed);
-                    }
-                    Err(e) => {
-                        error_log!("Service '{}' failed: {}", name, e);
-                        state_lock.insert(name.clone(), ERROR_INDICATOR.to_string());
-                    }
-                }
-            }
+            state.write().unwrap().insert(
+                name.clone(),
+                ServiceStatus {
+                    value,
+                    error,
+                    last_duration: Some(started.elapsed()),
+                    finished: Some(Instant::now()),
+                },
+            );
 
             // Sleep until next interval
             thread::sleep(Duration::from_secs_f64(interval));
@@ -71,7 +121,7 @@ pub fn spawn_service(
 
 /// Executes a bash command with an optional timeout.
 /// Returns stdout on success, or error message on failure/timeout.
-fn execute_with_timeout(command: &str, timeout_secs: Option<f64>) -> Result<String, String> {
+pub fn execute_with_timeout(command: &str, timeout_secs: Option<f64>) -> Result<String, String> {
     let child = Command::new("bash")
         .arg("-c")
         .arg(command)
@@ -173,7 +223,15 @@ pub fn ensure_service_started(
         // Initialize with loading indicator
         {
             let mut state_lock = state.write().unwrap();
-            state_lock.insert(name.to_string(), "...".to_string());
+            state_lock.insert(
+                name.to_string(),
+                ServiceStatus {
+                    value: LOADING_INDICATOR.to_string(),
+                    error: None,
+                    last_duration: None,
+                    finished: None,
+                },
+            );
         }
 
         // Spawn service thread
@@ -196,6 +254,159 @@ pub fn get_service_value(name: &str, state: &ServicesState) -> String {
     let state_lock = state.read().unwrap();
     state_lock
         .get(name)
-        .cloned()
+        .map(|status| status.value.clone())
         .unwrap_or_else(|| ERROR_INDICATOR.to_string())
 }
+
+/// Reports on every configured service, started or not, in configuration order
+pub fn reports(
+    services_config: &Option<IndexMap<String, ServiceConfig>>,
+    state: &ServicesState,
+) -> Vec<ServiceReport> {
+    let state = state.read().unwrap();
+    let Some(services_config) = services_config else {
+        return Vec::new();
+    };
+    services_config
+        .iter()
+        .map(|(name, config)| {
+            let status = state.get(name);
+            let finished = status.and_then(|status| status.finished);
+            let interval = Duration::from_secs_f64(config.interval.max(0.0));
+            ServiceReport {
+                name: name.clone(),
+                exec: config.exec.clone(),
+                interval: config.interval,
+                started: status.is_some(),
+                value: status.map(|status| status.value.clone()),
+                error: status.and_then(|status| status.error.clone()),
+                last_duration_ms: status
+                    .and_then(|status| status.last_duration)
+                    .map(|duration| duration.as_secs_f64() * 1000.0),
+                last_run_secs_ago: finished.map(|finished| finished.elapsed().as_secs_f64()),
+                next_run_in_secs: finished
+                    .map(|finished| interval.saturating_sub(finished.elapsed()).as_secs_f64()),
+            }
+        })
+        .collect()
+}
+
+/// Registers the control socket client waiting for [`deliver_reports`]
+pub fn await_reports(reply: Sender<Vec<ServiceReport>>) {
+    PENDING.lock().unwrap().replace(reply);
+}
+
+/// Hands the reports to the client waiting for them
+pub fn deliver_reports(reports: Vec<ServiceReport>) {
+    if let Some(reply) = PENDING.lock().unwrap().take() {
+        let _ = reply.send(reports);
+    }
+}
+
+/// Describes one report on a line: name, value or error, and timing
+pub fn describe(report: &ServiceReport) -> String {
+    if !report.started {
+        return format!("{}: not started (no button shows it)", report.name);
+    }
+    let Some(duration) = report.last_duration_ms else {
+        return format!("{}: first run in progress", report.name);
+    };
+    let outcome = match &report.error {
+        Some(error) => format!("error: {}", error),
+        None => format!("{:?}", report.value.as_deref().unwrap_or_default()),
+    };
+    let next = match report.next_run_in_secs {
+        Some(next) if next > 0.0 => format!("next in {:.1}s", next),
+        _ => "running".to_string(),
+    };
+    format!(
+        "{}: {}  (took {:.0} ms, {:.1}s ago, {})",
+        report.name,
+        outcome,
+        duration,
+        report.last_run_secs_ago.unwrap_or_default(),
+        next
+    )
+}
+
+/// `keydeck --services [--watch]`: prints the services of the running daemon, every
+/// second with `watch`
+#[cfg(unix)]
+pub fn print_services(watch: bool, json: bool) {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    loop {
+        let reply = UnixStream::connect(crate::listener_context::control_socket_path())
+            .ok()
+            .and_then(|mut stream| {
+                stream.write_all(b"services\n").ok()?;
+                let mut reply = String::new();
+                BufReader::new(stream).read_line(&mut reply).ok()?;
+                Some(reply)
+            });
+        let Some(reply) = reply else {
+            eprintln!("Error: the keydeck daemon is not running");
+            std::process::exit(1);
+        };
+        if json {
+            println!("{}", reply.trim());
+        } else {
+            let reports: Vec<ServiceReport> =
+                serde_json::from_str(reply.trim()).unwrap_or_default();
+            if watch {
+                // Redraw in place, like watch(1)
+                print!("\x1b[2J\x1b[H");
+            }
+            if reports.is_empty() {
+                println!("No services configured");
+            }
+            for report in &reports {
+                println!("{}", describe(report));
+            }
+        }
+        if !watch {
+            return;
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_cover_every_configured_service() {
+        let config = |exec: &str| ServiceConfig {
+            exec: exec.to_string(),
+            interval: 60.0,
+            timeout: None,
+        };
+        let services = Some(IndexMap::from([
+            ("load".to_string(), config("uptime")),
+            ("idle".to_string(), config("true")),
+        ]));
+        let state = new_services_state();
+        state.write().unwrap().insert(
+            "load".to_string(),
+            ServiceStatus {
+                value: ERROR_INDICATOR.to_string(),
+                error: Some("Command failed with exit code: 1".to_string()),
+                last_duration: Some(Duration::from_millis(40)),
+                finished: Some(Instant::now()),
+            },
+        );
+        let reports = reports(&services, &state);
+        assert_eq!(reports.len(), 2);
+        assert!(reports[0].started);
+        assert_eq!(reports[0].last_duration_ms, Some(40.0));
+        assert!(reports[0].next_run_in_secs.unwrap() > 59.0);
+        assert!(describe(&reports[0]).starts_with("load: error: Command failed"));
+        assert!(!reports[1].started);
+        assert_eq!(
+            describe(&reports[1]),
+            "idle: not started (no button shows it)"
+        );
+    }
+}
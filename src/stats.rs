@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Usage statistics: how often each button was pressed and how long each page was
+//! shown, per device. They are kept in [`keydeck::get_stats_path`] across restarts,
+//! shown on keys through `${stats:...}`, returned by the `stats` control socket
+//! command, and drive the `heatmap` render mode.
+
+use crate::error_log;
+use keydeck::get_stats_path;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// Shortest time between two writes of the stats file while the daemon runs
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Usage of one device
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceStats {
+    /// Press count of each button (`button3`), per page
+    #[serde(default)]
+    pub presses: BTreeMap<String, BTreeMap<String, u64>>,
+    /// Seconds each page was shown
+    #[serde(default)]
+    pub dwell: BTreeMap<String, f64>,
+}
+
+impl DeviceStats {
+    /// Times a button of a page was pressed
+    pub fn presses(&self, page: &str, button: &str) -> u64 {
+        self.presses
+            .get(page)
+            .and_then(|buttons| buttons.get(button))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Presses of all buttons on all pages
+    pub fn total_presses(&self) -> u64 {
+        self.presses
+            .values()
+            .flat_map(|buttons| buttons.values())
+            .sum()
+    }
+
+    /// Press count of the most used button of a page, the reference of the heatmap
+    pub fn max_presses(&self, page: &str) -> u64 {
+        self.presses
+            .get(page)
+            .and_then(|buttons| buttons.values().max())
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Seconds a page was shown
+    pub fn dwell(&self, page: &str) -> f64 {
+        self.dwell.get(page).copied().unwrap_or(0.0)
+    }
+}
+
+/// Usage of all devices seen so far, by serial number
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageStats {
+    #[serde(default)]
+    pub devices: BTreeMap<String, DeviceStats>,
+    /// Changed since the last save
+    #[serde(skip)]
+    dirty: bool,
+    #[serde(skip)]
+    last_saved: Option<Instant>,
+}
+
+impl UsageStats {
+    /// Statistics read from the stats file; empty when there are none yet
+    pub fn load() -> Self {
+        let path = get_stats_path();
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                error_log!("Ignoring unreadable statistics {}: {}", path.display(), e);
+                UsageStats::default()
+            }),
+            Err(_) => UsageStats::default(),
+        }
+    }
+
+    /// Saves the statistics unless they were saved less than [`SAVE_INTERVAL`] ago
+    pub fn save_periodically(&mut self) {
+        if self
+            .last_saved
+            .is_none_or(|saved| saved.elapsed() >= SAVE_INTERVAL)
+        {
+            self.save();
+        }
+    }
+
+    /// Writes the statistics if they changed since they were last saved
+    pub fn save(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        self.last_saved = Some(Instant::now());
+        let path = get_stats_path();
+        let result = serde_json::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|json| keydeck_types::BackupManager::STATE.save(&path, json));
+        if let Err(e) = result {
+            error_log!("Error while saving statistics {}: {}", path.display(), e);
+        }
+    }
+
+    pub fn record_press(&mut self, sn: &str, page: &str, button: &str) {
+        let device = self.devices.entry(sn.to_string()).or_default();
+        let buttons = device.presses.entry(page.to_string()).or_default();
+        *buttons.entry(button.to_string()).or_default() += 1;
+        self.dirty = true;
+    }
+
+    pub fn add_dwell(&mut self, sn: &str, page: &str, shown: Duration) {
+        let device = self.devices.entry(sn.to_string()).or_default();
+        *device.dwell.entry(page.to_string()).or_default() += shown.as_secs_f64();
+        self.dirty = true;
+    }
+
+    pub fn device(&self, sn: &str) -> Option<&DeviceStats> {
+        self.devices.get(sn)
+    }
+}
+
+/// Thread-shared usage statistics, alive for the whole daemon run
+pub type SharedStats = Arc<RwLock<UsageStats>>;
+
+/// Loads the statistics of earlier runs into a new shared store
+pub fn load_shared_stats() -> SharedStats {
+    Arc::new(RwLock::new(UsageStats::load()))
+}
+
+/// Short human-readable form of a dwell time, e.g. "42s", "5m 12s", "3h 5m"
+pub fn format_dwell(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m {}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
@@ -0,0 +1,444 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Built-in pages that are not part of the configuration: the boot splash drawn while a
+//! device initializes, the error page drawn when a configuration reload fails, the
+//! echo of a `text` action about to be typed, the question of a `confirm` action, the
+//! test pattern of the setup wizard, and the diagnostic page describing the device and
+//! the daemon.
+
+use crate::device_trait::KeydeckDevice;
+use crate::error_log;
+use image::{DynamicImage, Rgba, RgbaImage};
+use keydeck_render::text::render_text_on_canvas;
+
+const SPLASH_BG: Rgba<u8> = Rgba([20, 20, 28, 255]);
+const HEADER_BG: Rgba<u8> = Rgba([192, 0, 0, 255]);
+const PATH_BG: Rgba<u8> = Rgba([120, 80, 0, 255]);
+const MESSAGE_BG: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const HINT_BG: Rgba<u8> = Rgba([50, 50, 50, 255]);
+const ECHO_BG: Rgba<u8> = Rgba([0, 40, 90, 255]);
+const DIAGNOSTICS_BG: Rgba<u8> = Rgba([0, 70, 40, 255]);
+const QUESTION_BG: Rgba<u8> = Rgba([60, 45, 0, 255]);
+const CONFIRM_BG: Rgba<u8> = Rgba([0, 120, 40, 255]);
+const CANCEL_BG: Rgba<u8> = Rgba([150, 0, 0, 255]);
+const TEXT_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// Maximum characters per line and lines per key when spreading the error message
+const LINE_CHARS: usize = 9;
+const TILE_LINES: usize = 3;
+
+/// Draws the boot splash: a "KeyDeck" label on the center key, all other keys blank.
+pub fn show_splash(device: &dyn KeydeckDevice) {
+    let count = device.button_count() as usize;
+    if count == 0 {
+        return;
+    }
+    let center = match device.button_layout() {
+        (rows, cols) if rows > 0 && cols > 0 => (rows / 2) * cols + cols / 2,
+        _ => count / 2,
+    };
+    let tiles: Vec<(String, Rgba<u8>)> = (0..count)
+        .map(|i| {
+            let label = if i == center { "Key\nDeck" } else { "" };
+            (label.to_string(), SPLASH_BG)
+        })
+        .collect();
+    paint(device, &tiles);
+}
+
+/// Draws the configuration error page, spreading `error` over the keys. The page stays
+/// until the caller redraws the device.
+pub fn show_error(device: &dyn KeydeckDevice, error: &str) {
+    let count = device.button_count() as usize;
+    if count == 0 {
+        return;
+    }
+    let tiles: Vec<(String, Rgba<u8>)> = error_tiles(error, count)
+        .into_iter()
+        .map(|(label, kind)| {
+            let bg = match kind {
+                TileKind::Header => HEADER_BG,
+                TileKind::Path => PATH_BG,
+                TileKind::Message => MESSAGE_BG,
+                TileKind::Hint => HINT_BG,
+            };
+            (label, bg)
+        })
+        .collect();
+    paint(device, &tiles);
+}
+
+/// Splits a text about to be typed into the frames of its echo page: one word per key,
+/// leaving the last key for the cancel hint. Text that does not fit scrolls a row at a
+/// time, so each frame is one row further into it. Words too long for a key are broken
+/// up, and keys like Enter and Tab get symbols.
+pub fn echo_frames(text: &str, count: usize, columns: usize) -> Vec<Vec<String>> {
+    let keys = count.saturating_sub(1).max(1);
+    let mut words = Vec::new();
+    for word in echo_words(text) {
+        let chars: Vec<char> = word.chars().collect();
+        words.extend(
+            chars
+                .chunks(LINE_CHARS)
+                .map(|chunk| chunk.iter().collect::<String>()),
+        );
+    }
+    if words.is_empty() {
+        return vec![Vec::new()];
+    }
+
+    let step = columns.clamp(1, keys);
+    let mut frames = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = (start + keys).min(words.len());
+        frames.push(words[start..end].to_vec());
+        if end == words.len() {
+            return frames;
+        }
+        start += step;
+    }
+}
+
+/// Words of a text as they are shown, with the escape sequences the `text` action
+/// understands as separate symbols
+fn echo_words(text: &str) -> Vec<String> {
+    let mut words = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        let symbol = match c {
+            '\\' => match chars.next() {
+                Some('n' | 'r') => "⏎",
+                Some('t') => "⇥",
+                Some('e') => "Esc",
+                escaped => {
+                    words.last_mut().unwrap().push(escaped.unwrap_or('\\'));
+                    continue;
+                }
+            },
+            '\n' | '\r' => "⏎",
+            '\t' => "⇥",
+            c if c.is_whitespace() => "",
+            c => {
+                words.last_mut().unwrap().push(c);
+                continue;
+            }
+        };
+        words.push(symbol.to_string());
+        words.push(String::new());
+    }
+    words.retain(|word| !word.is_empty());
+    words
+}
+
+/// Draws one frame of the echo page, see [`echo_frames`]
+pub fn show_echo(device: &dyn KeydeckDevice, frame: &[String]) {
+    let count = device.button_count() as usize;
+    if count == 0 {
+        return;
+    }
+    let mut tiles: Vec<(String, Rgba<u8>)> = frame
+        .iter()
+        .map(|word| (word.clone(), ECHO_BG))
+        .take(count)
+        .collect();
+    if count > 1 {
+        tiles.resize(count - 1, (String::new(), ECHO_BG));
+        tiles.push(("Press to\ncancel".to_string(), HINT_BG));
+    }
+    paint(device, &tiles);
+}
+
+/// Keys answering the confirmation page, from 0: the first key of the bottom row cancels
+/// and the last one confirms, as far apart as they can be. None with fewer than two keys.
+pub fn confirm_keys(count: usize, columns: usize) -> Option<(usize, usize)> {
+    if count < 2 {
+        return None;
+    }
+    let last = count - 1;
+    let row_start = last / columns.max(1) * columns.max(1);
+    Some((row_start.min(last - 1), last))
+}
+
+/// Draws the confirmation page: the question spread over the keys, with the keys of
+/// [`confirm_keys`] to answer it
+pub fn show_confirm(device: &dyn KeydeckDevice, question: &str) {
+    let (_, columns) = device.button_layout();
+    paint(
+        device,
+        &confirm_tiles(question, device.button_count() as usize, columns),
+    );
+}
+
+/// Labels of the confirmation page: the question word-wrapped from the first key on, the
+/// answers where [`confirm_keys`] puts them
+fn confirm_tiles(question: &str, count: usize, columns: usize) -> Vec<(String, Rgba<u8>)> {
+    let Some((cancel, confirm)) = confirm_keys(count, columns) else {
+        return vec![];
+    };
+    let mut lines = wrap_lines(question.split_whitespace()).into_iter();
+    (0..count)
+        .map(|key| match key {
+            key if key == cancel => ("✖\nNo".to_string(), CANCEL_BG),
+            key if key == confirm => ("✔\nYes".to_string(), CONFIRM_BG),
+            _ => (lines.next().unwrap_or_default(), QUESTION_BG),
+        })
+        .collect()
+}
+
+/// Draws the test pattern: every key shows its number on a color of its own, so the
+/// user sees that keydeck can draw on the device and which key is which.
+pub fn show_test_pattern(device: &dyn KeydeckDevice) {
+    let count = device.button_count() as usize;
+    let tiles: Vec<(String, Rgba<u8>)> = (0..count)
+        .map(|i| ((i + 1).to_string(), pattern_color(i, count)))
+        .collect();
+    paint(device, &tiles);
+}
+
+/// A dark color of the hue wheel, key `index` of `count` keys apart
+fn pattern_color(index: usize, count: usize) -> Rgba<u8> {
+    let hue = index as f32 * 6.0 / count.max(1) as f32;
+    let rising = (hue % 1.0 * 160.0) as u8;
+    let falling = 160 - rising;
+    let [r, g, b] = match hue as u32 {
+        0 => [160, rising, 0],
+        1 => [falling, 160, 0],
+        2 => [0, 160, rising],
+        3 => [0, falling, 160],
+        4 => [rising, 0, 160],
+        _ => [160, 0, falling],
+    };
+    Rgba([r, g, b, 255])
+}
+
+/// What the diagnostic page shows, for headless installs where the logs are out of reach
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    pub serial: String,
+    pub model: String,
+    pub firmware: String,
+    pub page: String,
+    pub tick_time: f64,
+    pub focus_class: String,
+    pub config_generation: u64,
+    /// How input is read, e.g. the poll interval in use
+    pub input: String,
+}
+
+/// Draws the diagnostic page: one value per key, under its name, and a close hint on the
+/// last key
+pub fn show_diagnostics(device: &dyn KeydeckDevice, diagnostics: &Diagnostics) {
+    let count = device.button_count() as usize;
+    if count == 0 {
+        return;
+    }
+    paint(device, &diagnostic_tiles(diagnostics, count));
+}
+
+/// Labels of the diagnostic page, cut to `count` keys
+fn diagnostic_tiles(diagnostics: &Diagnostics, count: usize) -> Vec<(String, Rgba<u8>)> {
+    let values = [
+        ("Serial", diagnostics.serial.clone()),
+        ("Model", diagnostics.model.clone()),
+        ("Firmware", diagnostics.firmware.clone()),
+        ("Page", diagnostics.page.clone()),
+        ("Tick", format!("{}s", diagnostics.tick_time)),
+        ("Focus", diagnostics.focus_class.clone()),
+        ("Config", format!("gen {}", diagnostics.config_generation)),
+        ("Input", diagnostics.input.clone()),
+    ];
+    let mut tiles: Vec<(String, Rgba<u8>)> = values
+        .iter()
+        .map(|(name, value)| (diagnostic_label(name, value), DIAGNOSTICS_BG))
+        .collect();
+    if count < 2 {
+        tiles.truncate(count);
+        return tiles;
+    }
+    tiles.truncate(count - 1);
+    tiles.resize(count - 1, (String::new(), DIAGNOSTICS_BG));
+    tiles.push(("Press to\nclose".to_string(), HINT_BG));
+    tiles
+}
+
+/// The name of a value above the value itself, broken into lines that fit a key; a value
+/// too long for the key ends with an ellipsis
+fn diagnostic_label(name: &str, value: &str) -> String {
+    let value = if value.is_empty() { "-" } else { value };
+    let chars: Vec<char> = value.chars().collect();
+    let mut lines: Vec<String> = chars
+        .chunks(LINE_CHARS)
+        .take(TILE_LINES - 1)
+        .map(|chunk| chunk.iter().collect())
+        .collect();
+    if chars.len() > LINE_CHARS * (TILE_LINES - 1) {
+        if let Some(last) = lines.last_mut() {
+            last.pop();
+            last.push('…');
+        }
+    }
+    lines.insert(0, name.to_string());
+    lines.join("\n")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TileKind {
+    Header,
+    Path,
+    Message,
+    Hint,
+}
+
+/// Splits a configuration error into per-key labels: a header key, the location of the
+/// error (one path segment per key), the message itself word-wrapped over the following
+/// keys, and a dismiss hint on the last key.
+fn error_tiles(error: &str, count: usize) -> Vec<(String, TileKind)> {
+    let mut tiles = vec![("⚠\nConfig\nerror".to_string(), TileKind::Header)];
+    if count < 2 {
+        return tiles;
+    }
+
+    let mut words = Vec::new();
+    for line in error.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if line.starts_with("Error parsing config file") {
+            continue;
+        }
+        if let Some(path) = line.strip_prefix("Path:") {
+            tiles.extend(
+                path.trim()
+                    .split('.')
+                    .filter(|segment| !segment.is_empty())
+                    .map(|segment| (segment.to_string(), TileKind::Path)),
+            );
+            continue;
+        }
+        words.extend(line.trim_start_matches("Error:").split_whitespace());
+    }
+
+    tiles.extend(
+        wrap_lines(words)
+            .into_iter()
+            .map(|label| (label, TileKind::Message)),
+    );
+
+    tiles.truncate(count - 1);
+    tiles.resize(count - 1, (String::new(), TileKind::Message));
+    tiles.push(("Press to\ndismiss".to_string(), TileKind::Hint));
+    tiles
+}
+
+/// Word-wraps a text into the labels of consecutive keys, [`TILE_LINES`] lines of up to
+/// [`LINE_CHARS`] characters each
+fn wrap_lines<'a>(words: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in words {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= LINE_CHARS => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+        .chunks(TILE_LINES)
+        .map(|chunk| chunk.join("\n"))
+        .collect()
+}
+
+/// Renders one label per key and flushes the device
+fn paint(device: &dyn KeydeckDevice, tiles: &[(String, Rgba<u8>)]) {
+    let (w, h) = device.button_image_size();
+    for (index, (label, bg)) in tiles.iter().enumerate() {
+        let mut canvas = RgbaImage::from_pixel(w as u32, h as u32, *bg);
+        if !label.is_empty() {
+            render_text_on_canvas(&mut canvas, label, None, Some(TEXT_COLOR), None);
+        }
+        device
+            .set_button_image(index as u8, DynamicImage::ImageRgba8(canvas))
+            .unwrap_or_else(|e| error_log!("Error while drawing status page: {}", e));
+    }
+    device
+        .flush()
+        .unwrap_or_else(|e| error_log!("Error while flushing device: {}", e));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_is_spread_over_keys() {
+        let error = "Error parsing config file: /home/u/.config/keydeck/config.yaml\n\nPath: default.Main.button3\nunknown field `txt`";
+        let tiles = error_tiles(error, 8);
+        assert_eq!(tiles.len(), 8);
+        assert_eq!(tiles[0].1, TileKind::Header);
+        assert_eq!(tiles[1], ("default".to_string(), TileKind::Path));
+        assert_eq!(tiles[3], ("button3".to_string(), TileKind::Path));
+        assert_eq!(
+            tiles[4],
+            ("unknown\nfield\n`txt`".to_string(), TileKind::Message)
+        );
+        assert_eq!(tiles[7].1, TileKind::Hint);
+    }
+
+    #[test]
+    fn long_echo_scrolls_by_rows() {
+        // 2 rows of 3 keys: 5 words per frame next to the cancel hint
+        let frames = echo_frames("ls -la /tmp && echo done\\n", 6, 3);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], ["ls", "-la", "/tmp", "&&", "echo"]);
+        assert_eq!(frames[1], ["&&", "echo", "done", "⏎"]);
+        assert_eq!(echo_frames("supercalifragilistic", 6, 3)[0].len(), 3);
+    }
+
+    #[test]
+    fn diagnostics_fit_the_keys() {
+        let diagnostics = Diagnostics {
+            serial: "A00NA3182ZOCFG".to_string(),
+            page: "Main".to_string(),
+            tick_time: 2.0,
+            focus_class: "org.kde.konsole.desktop".to_string(),
+            config_generation: 3,
+            input: "poll 8ms".to_string(),
+            ..Default::default()
+        };
+        let tiles = diagnostic_tiles(&diagnostics, 6);
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles[0].0, "Serial\nA00NA3182\nZOCFG");
+        assert_eq!(tiles[1].0, "Model\n-");
+        assert_eq!(tiles[4].0, "Tick\n2s");
+        assert_eq!(tiles[5], ("Press to\nclose".to_string(), HINT_BG));
+        let tiles = diagnostic_tiles(&diagnostics, 15);
+        assert_eq!(tiles[5].0, "Focus\norg.kde.k\nonsole.d…");
+        assert_eq!(tiles[6].0, "Config\ngen 3");
+        assert_eq!(tiles[7].0, "Input\npoll 8ms");
+        assert_eq!(tiles[8].0, "");
+    }
+
+    #[test]
+    fn confirmation_answers_sit_on_the_bottom_row() {
+        // 3 rows of 5 keys
+        assert_eq!(confirm_keys(15, 5), Some((10, 14)));
+        // A single row, and a single column
+        assert_eq!(confirm_keys(4, 4), Some((0, 3)));
+        assert_eq!(confirm_keys(3, 1), Some((1, 2)));
+        assert_eq!(confirm_keys(1, 1), None);
+
+        let tiles = confirm_tiles("Shut down the machine now?", 6, 3);
+        assert_eq!(tiles[0].0, "Shut down\nthe\nmachine");
+        assert_eq!(tiles[1].0, "now?");
+        assert_eq!(tiles[3], ("✖\nNo".to_string(), CANCEL_BG));
+        assert_eq!(tiles[5], ("✔\nYes".to_string(), CONFIRM_BG));
+    }
+
+    #[test]
+    fn error_fits_small_devices() {
+        let tiles = error_tiles("Path: a.b.c.d.e\nbroken", 3);
+        assert_eq!(tiles.len(), 3);
+        assert_eq!(tiles[1].1, TileKind::Path);
+        assert_eq!(tiles[2].1, TileKind::Hint);
+    }
+}
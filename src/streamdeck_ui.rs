@@ -0,0 +1,413 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Migration from streamdeck-ui, whose configuration is a single JSON file
+//! (`~/.config/streamdeck-ui.json`, or `~/.streamdeck_ui.json` for older versions).
+//!
+//! Every deck of the file becomes a page group (`default` when there is only one), and
+//! each of its pages a page `Page 1`, `Page 2`... Keys keep their text, colors and image,
+//! copied to `streamdeck-ui/` in the icon dir; their command, hotkeys, typed text and
+//! page switch become `exec`, `key`, `text` and `jump` actions, run in that order as
+//! streamdeck-ui runs them. The imported page groups are added to the configuration,
+//! replacing groups of the same name, after keeping a timestamped copy of it.
+
+use crate::error_log;
+use crate::pages::{ButtonBuiltin, KeyDeckConf};
+use keydeck_types::builder::{ActionBuilder, ButtonBuilder, PageBuilder};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Folder of the icon dir the key images are copied to
+const ICON_SUBDIR: &str = "streamdeck-ui";
+
+#[derive(Debug, Default, Serialize)]
+pub struct MigrationReport {
+    /// Page groups written, by the serial of their deck
+    pub groups: Vec<String>,
+    pub pages: usize,
+    pub buttons: usize,
+    /// Key images copied to the icon dir
+    pub icons: usize,
+    /// Key images named by the streamdeck-ui configuration that were not found
+    pub missing: Vec<String>,
+    /// Settings without a KeyDeck counterpart, which were left out
+    pub ignored: Vec<String>,
+    /// Copy of the configuration that was extended
+    pub backup: Option<String>,
+}
+
+/// Adds the decks of the streamdeck-ui configuration `source` to the configuration at
+/// `config_path`, copying their key images to `icon_dir`
+pub fn import(
+    source: &Path,
+    config_path: &Path,
+    icon_dir: &Path,
+) -> Result<MigrationReport, String> {
+    let data = fs::read_to_string(source)
+        .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+    let json: Value = serde_json::from_str(&data)
+        .map_err(|e| format!("Failed to parse {}: {}", source.display(), e))?;
+    // Since version 2 the decks are under `state`, before they were the whole file
+    let decks = json
+        .get("state")
+        .unwrap_or(&json)
+        .as_object()
+        .filter(|decks| !decks.is_empty())
+        .ok_or_else(|| format!("{} holds no streamdeck-ui decks", source.display()))?;
+
+    let mut conf = if config_path.exists() {
+        keydeck_types::require_yaml(config_path, "Importing from streamdeck-ui")?;
+        let yaml = fs::read_to_string(config_path)
+            .map_err(|e| format!("Failed to read {}: {}", config_path.display(), e))?;
+        KeyDeckConf::from_yaml(&yaml)?
+    } else {
+        KeyDeckConf::default()
+    };
+
+    let mut report = MigrationReport::default();
+    let mut icons = IconCopier {
+        source_dir: source.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        icon_dir: icon_dir.join(ICON_SUBDIR),
+        copied: HashMap::new(),
+    };
+    for (serial, deck) in decks {
+        let group = if decks.len() == 1 {
+            "default"
+        } else {
+            serial.as_str()
+        };
+        conf.page_groups.shift_remove(group);
+        for (page, name, buttons) in pages_of(deck) {
+            let mut builder = PageBuilder::new();
+            for (index, button) in buttons {
+                let place = format!("{} {} button{}", serial, name, index);
+                if let Some(button) = convert_button(&place, button, &mut icons, &mut report) {
+                    builder = builder.button(index, button);
+                    report.buttons += 1;
+                }
+            }
+            conf.add_page(group, &name, builder);
+            if deck.get("page").and_then(Value::as_u64) == Some(page) {
+                if let Some(pages) = conf.page_groups.get_mut(group) {
+                    pages.main_page = Some(name);
+                }
+            }
+            report.pages += 1;
+        }
+        if let Some(brightness) = deck.get("brightness").and_then(Value::as_u64) {
+            if decks.len() == 1 {
+                conf.brightness = brightness.min(100) as u8;
+            } else {
+                report.ignored.push(format!("{} brightness", serial));
+            }
+        }
+        for setting in ["display_timeout", "rotation"] {
+            if deck
+                .get(setting)
+                .and_then(Value::as_u64)
+                .is_some_and(|value| value > 0)
+            {
+                report.ignored.push(format!("{} {}", serial, setting));
+            }
+        }
+        report.groups.push(group.to_string());
+    }
+    report.icons = icons.copied.len();
+
+    report.backup = conf
+        .save(config_path)?
+        .map(|backup| backup.to_string_lossy().to_string());
+    Ok(report)
+}
+
+/// A page of a deck: its index, name and buttons (from 1)
+type DeckPage<'a> = (u64, String, Vec<(u8, &'a Value)>);
+
+/// Pages of a deck in their order
+fn pages_of(deck: &Value) -> Vec<DeckPage<'_>> {
+    let Some(pages) = deck.get("buttons").and_then(Value::as_object) else {
+        return vec![];
+    };
+    let mut pages: Vec<(u64, Vec<(u8, &Value)>)> = pages
+        .iter()
+        .filter_map(|(page, buttons)| {
+            let mut buttons: Vec<(u8, &Value)> = buttons
+                .as_object()?
+                .iter()
+                .filter_map(|(index, button)| {
+                    let index: u8 = index.parse().ok()?;
+                    Some((index.checked_add(1)?, button))
+                })
+                .collect();
+            buttons.sort_by_key(|(index, _)| *index);
+            Some((page.parse().ok()?, buttons))
+        })
+        .collect();
+    pages.sort_by_key(|(page, _)| *page);
+    pages
+        .into_iter()
+        .map(|(page, buttons)| (page, page_name(page + 1), buttons))
+        .collect()
+}
+
+/// Name of the page streamdeck-ui numbers `number`, from 1 as in its `switch_page`
+fn page_name(number: u64) -> String {
+    format!("Page {}", number)
+}
+
+/// The button of a streamdeck-ui key, unless it is empty
+fn convert_button(
+    place: &str,
+    button: &Value,
+    icons: &mut IconCopier,
+    report: &mut MigrationReport,
+) -> Option<ButtonBuilder> {
+    // Keys with several states, since version 3, are imported in the state they were in
+    let button = match button.get("states").and_then(Value::as_object) {
+        Some(states) => {
+            if states.len() > 1 {
+                report.ignored.push(format!("{} states", place));
+            }
+            let state = button
+                .get("state")
+                .map_or("0".to_string(), Value::to_string);
+            states.get(&state).or_else(|| states.values().next())?
+        }
+        None => button,
+    };
+    let field = |name: &str| {
+        button
+            .get(name)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+
+    let mut builder = ButtonBuilder::new();
+    let mut empty = true;
+    if let Some(text) = field("text") {
+        builder = builder.text(text);
+        empty = false;
+    }
+    if let Some(icon) = field("icon") {
+        match icons.copy(icon) {
+            Some(name) => {
+                builder = builder.icon(name);
+                empty = false;
+            }
+            None => report.missing.push(icon.to_string()),
+        }
+    }
+    if let Some(color) = field("background_color").filter(|color| *color != "#000000") {
+        builder = builder.background(color);
+    }
+    if let Some(color) = field("font_color").filter(|color| *color != "#ffffff") {
+        builder = builder.text_color(color);
+    }
+
+    let mut actions = ActionBuilder::new();
+    if let Some(command) = field("command") {
+        actions = actions.exec(command);
+    }
+    if let Some(keys) = field("keys") {
+        for combination in keys.split(',').filter(|keys| !keys.trim().is_empty()) {
+            actions = actions.key(key_combination(combination));
+        }
+    }
+    if let Some(text) = button.get("write").and_then(Value::as_str) {
+        if !text.is_empty() {
+            actions = actions.text(text);
+        }
+    }
+    if let Some(page) = button.get("switch_page").and_then(Value::as_u64) {
+        if page > 0 {
+            actions = actions.jump(page_name(page));
+        }
+    }
+    let actions = actions.build();
+    let brightness = button.get("brightness_change").and_then(Value::as_i64);
+    match brightness {
+        Some(change) if change != 0 && actions.is_empty() => {
+            builder = builder.builtin(if change > 0 {
+                ButtonBuiltin::BrightnessUp
+            } else {
+                ButtonBuiltin::BrightnessDown
+            });
+            empty = false;
+        }
+        Some(change) if change != 0 => report.ignored.push(format!("{} brightness_change", place)),
+        _ => {}
+    }
+    if !actions.is_empty() {
+        builder = builder.actions(actions);
+        empty = false;
+    }
+    (!empty).then_some(builder)
+}
+
+/// A streamdeck-ui key combination, `ctrl+alt+t`, as a KeyDeck `key`, `Ctrl+Alt+t`
+fn key_combination(keys: &str) -> String {
+    keys.split('+')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| match key.to_lowercase().as_str() {
+            "ctrl" | "control" => "Ctrl".to_string(),
+            "alt" => "Alt".to_string(),
+            "shift" => "Shift".to_string(),
+            "super" | "meta" | "cmd" | "win" | "windows" => "Super".to_string(),
+            // A comma separates combinations in streamdeck-ui, so the key is spelled out
+            "comma" => ",".to_string(),
+            _ => key.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// Copies key images to the icon dir, each file once
+struct IconCopier {
+    /// Directory relative image paths are looked up in
+    source_dir: PathBuf,
+    icon_dir: PathBuf,
+    /// Icon name of every image copied, by its path
+    copied: HashMap<PathBuf, String>,
+}
+
+impl IconCopier {
+    /// The icon name of the image at `path`, after copying it, or `None` when it cannot
+    /// be read
+    fn copy(&mut self, path: &str) -> Option<String> {
+        let source = self.source_dir.join(path);
+        if let Some(name) = self.copied.get(&source) {
+            return Some(name.clone());
+        }
+        let data = fs::read(&source).ok()?;
+        let file_name = source.file_name()?.to_string_lossy().into_owned();
+        // An image of another deck may have the same name, and a file of an earlier
+        // migration the same content
+        let target = crate::bundle::free_name(&file_name, |candidate| {
+            fs::read(self.icon_dir.join(candidate)).is_ok_and(|existing| existing != data)
+                || self
+                    .copied
+                    .values()
+                    .any(|name| name.ends_with(&format!("/{}", candidate)))
+        });
+        let path = self.icon_dir.join(&target);
+        if let Err(e) = fs::create_dir_all(&self.icon_dir).and_then(|_| fs::write(&path, &data)) {
+            error_log!("Failed to write {}: {}", path.display(), e);
+            return None;
+        }
+        let name = format!("{}/{}", ICON_SUBDIR, target);
+        self.copied.insert(source, name.clone());
+        Some(name)
+    }
+}
+
+/// `keydeck --import-streamdeck-ui <FILE>`
+pub fn print_import(source: &str, json: bool) {
+    let result = import(
+        Path::new(source),
+        &keydeck::get_config_path(),
+        &keydeck::get_icon_dir_path(),
+    );
+    match result {
+        Ok(report) if json => println!("{}", serde_json::to_string(&report).unwrap_or_default()),
+        Ok(report) => {
+            println!(
+                "Imported {} pages with {} buttons and {} icons from {} into the page groups {}",
+                report.pages,
+                report.buttons,
+                report.icons,
+                source,
+                report.groups.join(", ")
+            );
+            for icon in &report.missing {
+                println!("  missing icon: {}", icon);
+            }
+            for setting in &report.ignored {
+                println!("  not imported: {}", setting);
+            }
+            if let Some(backup) = &report.backup {
+                println!("The previous configuration was saved as {}", backup);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_keys_and_images_are_migrated() {
+        let dir =
+            std::env::temp_dir().join(format!("keydeck-streamdeck-ui-{}", std::process::id()));
+        let (home, icons) = (dir.join("home"), dir.join("icons"));
+        fs::create_dir_all(&home).unwrap();
+        fs::create_dir_all(icons.join(ICON_SUBDIR)).unwrap();
+        fs::write(home.join("term.png"), "terminal").unwrap();
+        fs::write(icons.join(ICON_SUBDIR).join("term.png"), "other").unwrap();
+        let config = home.join("config.yaml");
+        fs::write(&config, "AL1:\n  Main:\n    button1:\n      text: Kept\n").unwrap();
+        let source = home.join("streamdeck-ui.json");
+        fs::write(
+            &source,
+            r##"{"streamdeck_ui_version": 2, "state": {"CL123": {
+                "brightness": 60, "page": 1, "display_timeout": 1800,
+                "buttons": {
+                    "0": {
+                        "0": {"text": "Term", "icon": "term.png", "command": "konsole", "switch_page": 2},
+                        "1": {"keys": "ctrl+alt+t,super+comma", "write": "hi", "background_color": "#ff0000"},
+                        "2": {"text": "", "icon": ""},
+                        "3": {"brightness_change": -10},
+                        "4": {"icon": "gone.png"}
+                    },
+                    "1": {"0": {"state": 0, "states": {"0": {"text": "Back", "switch_page": 1}, "1": {"text": "Other"}}}}
+                }
+            }}}"##,
+        )
+        .unwrap();
+
+        let report = import(&source, &config, &icons).unwrap();
+        assert_eq!(report.groups, ["default"]);
+        assert_eq!((report.pages, report.buttons, report.icons), (2, 4, 1));
+        assert_eq!(report.missing, ["gone.png"]);
+        assert_eq!(
+            report.ignored,
+            ["CL123 Page 2 button1 states", "CL123 display_timeout"]
+        );
+        assert!(report.backup.is_some());
+        assert_eq!(
+            fs::read_to_string(icons.join(ICON_SUBDIR).join("term-2.png")).unwrap(),
+            "terminal"
+        );
+
+        let conf = KeyDeckConf::from_yaml(&fs::read_to_string(&config).unwrap()).unwrap();
+        assert!(conf.page_groups.contains_key("AL1"));
+        assert_eq!(conf.brightness, 60);
+        let pages = &conf.page_groups["default"];
+        assert_eq!(pages.main_page.as_deref(), Some("Page 2"));
+        let page = serde_json::to_value(&pages.pages["Page 1"]).unwrap();
+        assert_eq!(page["button1"]["icon"], "streamdeck-ui/term-2.png");
+        assert_eq!(
+            page["button1"]["actions"],
+            serde_json::json!([{"exec": "konsole"}, {"jump": "Page 2"}])
+        );
+        assert_eq!(
+            page["button2"]["actions"],
+            serde_json::json!([{"key": "Ctrl+Alt+t"}, {"key": "Super+,"}, {"text": "hi"}])
+        );
+        assert!(page.get("button3").is_none());
+        assert_eq!(page["button4"]["builtin"], "brightness_down");
+        assert!(page.get("button5").is_none());
+        let page = serde_json::to_value(&pages.pages["Page 2"]).unwrap();
+        assert_eq!(page["button1"]["text"], "Back");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
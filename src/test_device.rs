@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! A deck for the tests: two rows of three keys, without input, recording what it is
+//! sent. Clones share what they record, so a test keeps one and hands the other over.
+
+use crate::device_trait::{DeviceError, DeviceReader, DeviceStateUpdate, KeydeckDevice};
+use image::DynamicImage;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Keys written, and whether with an image
+pub type Written = Arc<Mutex<Vec<(u8, bool)>>>;
+
+#[derive(Clone, Default)]
+pub struct TestDevice {
+    pub screen: bool,
+    pub written: Written,
+    /// Writes of key 1 still to fail, as a key that stalls
+    pub failures: Arc<AtomicUsize>,
+    /// A lost device no longer answers
+    pub lost: Arc<AtomicBool>,
+    pub keep_alives: Arc<AtomicU32>,
+}
+
+impl TestDevice {
+    /// Key images drawn so far
+    pub fn drawn(&self) -> usize {
+        let written = self.written.lock().unwrap();
+        written.iter().filter(|(_, image)| *image).count()
+    }
+
+    fn record(&self, button_idx: u8, image: bool) -> Result<(), DeviceError> {
+        if button_idx == 1 && self.failures.load(Ordering::Relaxed) > 0 {
+            self.failures.fetch_sub(1, Ordering::Relaxed);
+            return Err(DeviceError::IoError("stalled".to_string()));
+        }
+        self.written.lock().unwrap().push((button_idx, image));
+        Ok(())
+    }
+}
+
+struct NoInput;
+
+impl DeviceReader for NoInput {
+    fn read(&self, _: Option<Duration>) -> Result<Vec<DeviceStateUpdate>, DeviceError> {
+        Ok(Vec::new())
+    }
+}
+
+impl KeydeckDevice for TestDevice {
+    fn serial_number(&self) -> Result<String, DeviceError> {
+        Ok("TEST".to_string())
+    }
+    fn firmware_version(&self) -> Result<String, DeviceError> {
+        if self.lost.load(Ordering::Relaxed) {
+            return Err(DeviceError::NotFound);
+        }
+        Ok(String::new())
+    }
+    fn manufacturer(&self) -> String {
+        String::new()
+    }
+    fn kind_name(&self) -> String {
+        String::new()
+    }
+    fn button_count(&self) -> u8 {
+        6
+    }
+    fn has_screen(&self) -> bool {
+        self.screen
+    }
+    fn button_image_size(&self) -> (u16, u16) {
+        (72, 72)
+    }
+    fn button_layout(&self) -> (usize, usize) {
+        (2, 3)
+    }
+    fn reset(&self) -> Result<(), DeviceError> {
+        Ok(())
+    }
+    fn set_brightness(&self, _: u8) -> Result<(), DeviceError> {
+        Ok(())
+    }
+    fn set_button_image(&self, button_idx: u8, _: DynamicImage) -> Result<(), DeviceError> {
+        self.record(button_idx, true)
+    }
+    fn clear_button_image(&self, button_idx: u8) -> Result<(), DeviceError> {
+        self.record(button_idx, false)
+    }
+    fn clear_all_button_images(&self) -> Result<(), DeviceError> {
+        Ok(())
+    }
+    fn flush(&self) -> Result<(), DeviceError> {
+        Ok(())
+    }
+    fn get_reader(&self) -> Arc<dyn DeviceReader> {
+        Arc::new(NoInput)
+    }
+    fn keep_alive(&self) {
+        self.keep_alives.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,503 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Terminal monitor and controller of the running daemon (`keydeck --tui`), for SSH
+//! sessions and machines without a desktop.
+//!
+//! It only talks to the daemon over the control socket: `status` every second for the
+//! devices and the pages they show, `services` for the values of the services, and a
+//! `watch` connection for the key presses and page changes. The pages and buttons
+//! offered come from the configuration file, read again whenever the daemon reloads it.
+//! Enter on a page shows it on the selected device (`setpage`), and Enter on a button of
+//! the page it shows presses it (`press`).
+
+use crate::live_events::{self, LiveEvent};
+use crate::pages::{ButtonConfig, KeyDeckConf, KeyDeckConfLoader, TextConfig};
+use crate::services::{self, ServiceReport};
+use keydeck_types::api::{self, Command, DaemonStatus, DeviceState};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// How often the devices are asked for, and the services every other time
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest wait for an answer of the daemon
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Events kept on screen
+const MAX_EVENTS: usize = 200;
+
+/// What the threads talking to the daemon hand over to the screen
+enum Update {
+    /// The state of the daemon, none while it is not running
+    Status(Option<DaemonStatus>),
+    Services(Vec<ServiceReport>),
+    Event(String),
+}
+
+/// The list the arrow keys and Enter act on
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pane {
+    Devices,
+    Pages,
+    Buttons,
+}
+
+#[derive(Default)]
+struct App {
+    status: Option<DaemonStatus>,
+    /// The configuration, with the generation of the daemon it was read for
+    conf: Option<(u64, KeyDeckConf)>,
+    services: Vec<ServiceReport>,
+    events: VecDeque<String>,
+    pane: Option<Pane>,
+    devices: ListState,
+    pages: ListState,
+    buttons: ListState,
+    /// Outcome of the last command sent
+    message: String,
+}
+
+impl App {
+    fn pane(&self) -> Pane {
+        self.pane.unwrap_or(Pane::Devices)
+    }
+
+    fn update(&mut self, update: Update) {
+        match update {
+            Update::Status(status) => {
+                let generation = status.as_ref().map(|status| status.config_generation);
+                if generation.is_some() && generation != self.conf.as_ref().map(|conf| conf.0) {
+                    self.conf = generation.zip(KeyDeckConfLoader::try_load().ok());
+                }
+                self.status = status;
+                if self.devices.selected().is_none() && !self.device_list().is_empty() {
+                    self.devices.select(Some(0));
+                }
+            }
+            Update::Services(services) => self.services = services,
+            Update::Event(event) => {
+                self.events.push_front(event);
+                self.events.truncate(MAX_EVENTS);
+            }
+        }
+    }
+
+    fn device_list(&self) -> &[DeviceState] {
+        self.status
+            .as_ref()
+            .map_or(&[], |status| status.devices.as_slice())
+    }
+
+    fn device(&self) -> Option<&DeviceState> {
+        self.device_list().get(self.devices.selected().unwrap_or(0))
+    }
+
+    /// Pages of the page group of the selected device
+    fn page_list(&self) -> Vec<String> {
+        let (Some(device), Some((_, conf))) = (self.device(), &self.conf) else {
+            return vec![];
+        };
+        let group = crate::config_patch::group_of(&conf.page_groups, &device.sn);
+        conf.page_groups
+            .get(group)
+            .map(|pages| pages.pages.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Buttons of the page the selected device shows, by number, with their text
+    fn button_list(&self) -> Vec<(u8, String)> {
+        let (Some(device), Some((_, conf))) = (self.device(), &self.conf) else {
+            return vec![];
+        };
+        let group = crate::config_patch::group_of(&conf.page_groups, &device.sn);
+        let Some(page) = conf
+            .page_groups
+            .get(group)
+            .and_then(|pages| pages.pages.get(&device.page))
+        else {
+            return vec![];
+        };
+        let mut buttons: Vec<(u8, String)> = page
+            .buttons
+            .iter()
+            .filter_map(|(key, button)| {
+                let number = key.strip_prefix("button")?.parse().ok()?;
+                let label = match button {
+                    ButtonConfig::Template(name) => name.clone(),
+                    ButtonConfig::Detailed(button) => match &button.text {
+                        Some(TextConfig::Simple(text)) => text.clone(),
+                        Some(TextConfig::Detailed { value, .. }) => value.clone(),
+                        None => String::new(),
+                    },
+                };
+                Some((number, label.replace('\n', " ")))
+            })
+            .collect();
+        buttons.sort();
+        buttons
+    }
+
+    /// Acts on a key, and returns the command it sends to the daemon
+    fn on_key(&mut self, key: KeyCode) -> Option<Command> {
+        let count = match self.pane() {
+            Pane::Devices => self.device_list().len(),
+            Pane::Pages => self.page_list().len(),
+            Pane::Buttons => self.button_list().len(),
+        };
+        let list = match self.pane() {
+            Pane::Devices => &mut self.devices,
+            Pane::Pages => &mut self.pages,
+            Pane::Buttons => &mut self.buttons,
+        };
+        match key {
+            KeyCode::Tab | KeyCode::BackTab => {
+                let forward = key == KeyCode::Tab;
+                self.pane = Some(match (self.pane(), forward) {
+                    (Pane::Devices, true) | (Pane::Buttons, false) => Pane::Pages,
+                    (Pane::Pages, true) | (Pane::Devices, false) => Pane::Buttons,
+                    (Pane::Buttons, true) | (Pane::Pages, false) => Pane::Devices,
+                });
+            }
+            KeyCode::Up if count > 0 => {
+                list.select(Some(list.selected().unwrap_or(0).saturating_sub(1)));
+            }
+            KeyCode::Down if count > 0 => {
+                list.select(Some(list.selected().map_or(0, |at| at + 1).min(count - 1)));
+            }
+            KeyCode::Enter => {
+                let sn = self.device()?.sn.clone();
+                return match self.pane() {
+                    Pane::Devices => None,
+                    Pane::Pages => Some(Command::SetPage {
+                        target: sn,
+                        page: self.page_list().get(self.pages.selected()?)?.clone(),
+                    }),
+                    Pane::Buttons => Some(Command::Press {
+                        sn,
+                        button: self.button_list().get(self.buttons.selected()?)?.0,
+                    }),
+                };
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Asks the daemon for its state every second, and for its services every other time,
+/// until the screen is closed
+fn spawn_poller(tx: Sender<Update>) {
+    thread::spawn(move || {
+        for round in 0u64.. {
+            let status = api::request(&Command::Status, ANSWER_TIMEOUT)
+                .ok()
+                .and_then(|answer| serde_json::from_str(&answer).ok());
+            let running = status.is_some();
+            if tx.send(Update::Status(status)).is_err() {
+                return;
+            }
+            if running && round % 2 == 0 {
+                let services = api::request(&Command::Services, ANSWER_TIMEOUT)
+                    .ok()
+                    .and_then(|answer| serde_json::from_str(&answer).ok())
+                    .unwrap_or_default();
+                if tx.send(Update::Services(services)).is_err() {
+                    return;
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+/// Follows the events of the daemon, connecting again when it restarts
+fn spawn_watcher(tx: Sender<Update>) {
+    thread::spawn(move || loop {
+        let stream = UnixStream::connect(api::control_socket_path()).and_then(|mut stream| {
+            let watch = Command::Watch { version: None };
+            stream.write_all(watch.to_line().as_bytes())?;
+            Ok(stream)
+        });
+        if let Ok(stream) = stream {
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                let Some((sn, what)) = LiveEvent::from_line(&line).and_then(live_events::describe)
+                else {
+                    continue;
+                };
+                let time = chrono::Local::now().format("%H:%M:%S");
+                if tx
+                    .send(Update::Event(format!("{}  {}  {}", time, sn, what)))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    });
+}
+
+/// Sends a command, and describes how it went
+fn send(command: &Command) -> String {
+    match api::send(command, ANSWER_TIMEOUT) {
+        Ok(Some(answer)) => answer,
+        Ok(None) => "sent".to_string(),
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let [top, bottom, help] = Layout::vertical([
+        Constraint::Percentage(55),
+        Constraint::Fill(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [devices_area, pages_area, buttons_area] = Layout::horizontal([
+        Constraint::Percentage(40),
+        Constraint::Percentage(25),
+        Constraint::Fill(1),
+    ])
+    .areas(top);
+    let [services_area, events_area] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Fill(1)]).areas(bottom);
+
+    let pane = app.pane();
+    let block = |title: String, this: Pane| {
+        let block = Block::bordered().title(title);
+        if this == pane {
+            block.border_style(Style::new().add_modifier(Modifier::BOLD))
+        } else {
+            block
+        }
+    };
+    let list = |items: Vec<ListItem<'static>>, block: Block<'static>| {
+        List::new(items)
+            .block(block)
+            .highlight_symbol("> ")
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+    };
+
+    let title = match &app.status {
+        Some(status) => format!(" Devices (keydeck {}) ", status.keydeck),
+        None => " Devices (daemon not running) ".to_string(),
+    };
+    let devices: Vec<ListItem> = app
+        .device_list()
+        .iter()
+        .map(|device| {
+            ListItem::new(format!(
+                "{}  {}  {}  {}%{}",
+                device.sn,
+                device.model,
+                device.page,
+                device.brightness,
+                if device.pinned { "  pinned" } else { "" }
+            ))
+        })
+        .collect();
+    frame.render_stateful_widget(
+        list(devices, block(title, Pane::Devices)),
+        devices_area,
+        &mut app.devices,
+    );
+
+    let shown = app.device().map(|device| device.page.clone());
+    let pages: Vec<ListItem> = app
+        .page_list()
+        .into_iter()
+        .map(|page| {
+            let mark = if Some(&page) == shown.as_ref() {
+                "*"
+            } else {
+                " "
+            };
+            ListItem::new(format!("{} {}", mark, page))
+        })
+        .collect();
+    frame.render_stateful_widget(
+        list(pages, block(" Pages ".to_string(), Pane::Pages)),
+        pages_area,
+        &mut app.pages,
+    );
+
+    let buttons: Vec<ListItem> = app
+        .button_list()
+        .into_iter()
+        .map(|(number, label)| ListItem::new(format!("{:>3}  {}", number, label)))
+        .collect();
+    let title = format!(" Buttons of {} ", shown.unwrap_or_default());
+    frame.render_stateful_widget(
+        list(buttons, block(title, Pane::Buttons)),
+        buttons_area,
+        &mut app.buttons,
+    );
+
+    let services: Vec<Line> = if app.services.is_empty() {
+        vec![Line::from("No services configured")]
+    } else {
+        app.services
+            .iter()
+            .map(|report| Line::from(services::describe(report)))
+            .collect()
+    };
+    frame.render_widget(
+        Paragraph::new(services).block(Block::bordered().title(" Services ")),
+        services_area,
+    );
+    let events: Vec<Line> = app
+        .events
+        .iter()
+        .map(|event| Line::from(event.as_str()))
+        .collect();
+    frame.render_widget(
+        Paragraph::new(events).block(Block::bordered().title(" Events ")),
+        events_area,
+    );
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            " Tab: next list  Up/Down: select  Enter: show page / press button  q: quit   {}",
+            app.message
+        )),
+        help,
+    );
+}
+
+/// `keydeck --tui`: shows the running daemon until q, Esc or Ctrl-C
+pub fn run() {
+    if api::request(&Command::Version, ANSWER_TIMEOUT).is_err() {
+        eprintln!("Error: the keydeck daemon is not running");
+        std::process::exit(1);
+    }
+    let (tx, rx) = channel();
+    spawn_poller(tx.clone());
+    spawn_watcher(tx);
+
+    let mut terminal = ratatui::init();
+    let result = run_screen(&mut terminal, &rx);
+    ratatui::restore();
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run_screen(
+    terminal: &mut ratatui::DefaultTerminal,
+    rx: &Receiver<Update>,
+) -> std::io::Result<()> {
+    let mut app = App::default();
+    loop {
+        while let Ok(update) = rx.try_recv() {
+            app.update(update);
+        }
+        terminal.draw(|frame| draw(frame, &mut app))?;
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let ctrl_c =
+            key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        if ctrl_c || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+            return Ok(());
+        }
+        if let Some(command) = app.on_key(key.code) {
+            app.message = send(&command);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_switch_pages_and_press_buttons_of_the_selected_device() {
+        let conf: KeyDeckConf = serde_yaml_ng::from_str(
+            "default:\n  Home:\n    button2: Mail\n    button1:\n      text: Web\n  Media: {}\n",
+        )
+        .unwrap();
+        let device = |sn: &str| DeviceState {
+            sn: sn.to_string(),
+            model: "Stream Deck".to_string(),
+            page: "Home".to_string(),
+            brightness: 50,
+            pinned: false,
+        };
+        let mut app = App {
+            conf: Some((1, conf)),
+            ..Default::default()
+        };
+        app.update(Update::Status(Some(DaemonStatus {
+            keydeck: "1.0.0".to_string(),
+            api: 1,
+            pid: 1,
+            uptime: 0,
+            config_generation: 1,
+            last_reload_error: None,
+            devices: vec![device("AL1"), device("AL2")],
+        })));
+
+        assert_eq!(app.on_key(KeyCode::Down), None);
+        assert_eq!(app.device().unwrap().sn, "AL2");
+        assert_eq!(app.page_list(), ["Home", "Media"]);
+        assert_eq!(
+            app.button_list(),
+            [(1, "Web".to_string()), (2, "Mail".to_string())]
+        );
+
+        app.on_key(KeyCode::Tab);
+        app.on_key(KeyCode::Down);
+        app.on_key(KeyCode::Down);
+        assert_eq!(
+            app.on_key(KeyCode::Enter),
+            Some(Command::SetPage {
+                target: "AL2".to_string(),
+                page: "Media".to_string()
+            })
+        );
+        app.on_key(KeyCode::Tab);
+        assert_eq!(app.on_key(KeyCode::Enter), None, "no button selected yet");
+        app.on_key(KeyCode::Down);
+        app.on_key(KeyCode::Down);
+        assert_eq!(
+            app.on_key(KeyCode::Enter),
+            Some(Command::Press {
+                sn: "AL2".to_string(),
+                button: 2
+            })
+        );
+        app.on_key(KeyCode::BackTab);
+        assert_eq!(app.pane(), Pane::Pages);
+
+        let backend = ratatui::backend::TestBackend::new(120, 30);
+        let mut terminal = ratatui::Terminal::new(backend).unwrap();
+        terminal.draw(|frame| draw(frame, &mut app)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("AL2  Stream Deck  Home  50%"));
+        assert!(screen.contains("  * Home") && screen.contains(">   Media"));
+    }
+}
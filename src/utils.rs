@@ -1,6 +1,76 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Log lines kept for crash reports
+const RECENT_LOG_LINES: usize = 200;
+
+static RECENT_LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Keeps `line` among the recent log lines, for the log macros, and returns it
+pub fn logged(line: String) -> String {
+    let mut recent = RECENT_LOG.lock().unwrap_or_else(|e| e.into_inner());
+    if recent.len() == RECENT_LOG_LINES {
+        recent.pop_front();
+    }
+    recent.push_back(line.clone());
+    line
+}
+
+/// The last log lines printed, oldest first
+pub fn recent_log_lines() -> Vec<String> {
+    let recent = RECENT_LOG.lock().unwrap_or_else(|e| e.into_inner());
+    recent.iter().cloned().collect()
+}
+
+/// Processes escape sequences in a string and returns the actual characters.
+///
+/// Supported escape sequences: `\n` (Enter), `\t` (Tab), `\r` (carriage
+/// return), `\\` (backslash), `\e` (Escape). Used both for keyboard input and
+/// for text rendering.
+pub fn process_escape_sequences(text: &str) -> Vec<char> {
+    let mut result = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            if let Some(&next_ch) = chars.peek() {
+                match next_ch {
+                    'n' => {
+                        chars.next();
+                        result.push('\n');
+                    }
+                    't' => {
+                        chars.next();
+                        result.push('\t');
+                    }
+                    'r' => {
+                        chars.next();
+                        result.push('\r');
+                    }
+                    '\\' => {
+                        chars.next();
+                        result.push('\\');
+                    }
+                    'e' => {
+                        chars.next();
+                        result.push('\x1b');
+                    }
+                    _ => result.push(ch),
+                }
+            } else {
+                result.push(ch);
+            }
+        } else {
+            result.push(ch);
+        }
+    }
+
+    result
+}
+
 #[macro_export]
 macro_rules! timestamp {
     () => {
@@ -11,8 +81,8 @@ macro_rules! timestamp {
 #[macro_export]
 macro_rules! verbose_log {
     ($($arg:tt)*) => {
-        if crate::VERBOSITY.load(std::sync::atomic::Ordering::Relaxed) >= 2 {
-            println!("[{}] {}", crate::timestamp!(), format!($($arg)*));
+        if $crate::VERBOSITY.load(std::sync::atomic::Ordering::Relaxed) >= 2 {
+            println!("{}", $crate::utils::logged(format!("[{}] {}", $crate::timestamp!(), format!($($arg)*))));
         }
     };
 }
@@ -20,8 +90,8 @@ macro_rules! verbose_log {
 #[macro_export]
 macro_rules! detail_log {
     ($($arg:tt)*) => {
-        if crate::VERBOSITY.load(std::sync::atomic::Ordering::Relaxed) >= 1 {
-            println!("[{}] {}", crate::timestamp!(), format!($($arg)*));
+        if $crate::VERBOSITY.load(std::sync::atomic::Ordering::Relaxed) >= 1 {
+            println!("{}", $crate::utils::logged(format!("[{}] {}", $crate::timestamp!(), format!($($arg)*))));
         }
     };
 }
@@ -29,20 +99,20 @@ macro_rules! detail_log {
 #[macro_export]
 macro_rules! error_log {
     ($($arg:tt)*) => {
-        eprintln!("[{}] ERROR: {}", crate::timestamp!(), format!($($arg)*))
+        eprintln!("{}", $crate::utils::logged(format!("[{}] ERROR: {}", $crate::timestamp!(), format!($($arg)*))))
     };
 }
 
 #[macro_export]
 macro_rules! warn_log {
     ($($arg:tt)*) => {
-        eprintln!("[{}] WARNING: {}", crate::timestamp!(), format!($($arg)*))
+        eprintln!("{}", $crate::utils::logged(format!("[{}] WARNING: {}", $crate::timestamp!(), format!($($arg)*))))
     };
 }
 
 #[macro_export]
 macro_rules! info_log {
     ($($arg:tt)*) => {
-        println!("[{}] {}", crate::timestamp!(), format!($($arg)*))
+        println!("{}", $crate::utils::logged(format!("[{}] {}", $crate::timestamp!(), format!($($arg)*))))
     };
 }
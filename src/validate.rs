@@ -1,13 +1,23 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 // Copyright (C) 2025 Panayotis Katsaloulis
 
-use crate::pages::{ButtonConfig, KeyDeckConf, KeyDeckConfLoader};
+use crate::device_manager::DeviceManager;
+use crate::config_hints;
+use crate::icon_lint;
+use crate::image_formats;
+use crate::pages::condition::Condition;
+use crate::pages::{
+    Action, ButtonConfig, ColorConfig, Enabled, KeyDeckConf, KeyDeckConfLoader, TextConfig,
+};
 use crate::{error_log, info_log, verbose_log, warn_log};
-use keydeck::get_icon_dir;
+use keydeck_render::graphics;
+use keydeck_render::shortcodes::expand_shortcodes;
+use keydeck_render::text::{emoji_font, is_emoji, EmojiFont};
+use keydeck_types::IconPaths;
 use serde::Serialize;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Serialize)]
 struct ValidationResult {
@@ -17,6 +27,9 @@ struct ValidationResult {
     errors: Vec<ValidationError>,
     warnings: Vec<ValidationWarning>,
     unreferenced_icons: Vec<String>,
+    /// Icons replaced by `--fix` with their enlarged copies
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    fixed_icons: BTreeMap<String, String>,
     services_tested: Vec<ServiceTestResult>,
 }
 
@@ -29,13 +42,57 @@ struct ConfigSummary {
     services: usize,
     colors: usize,
     image_dir: String,
+    /// Directories icons are looked up in, in order
+    icon_dirs: Vec<String>,
     tick_time: f64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Default)]
 struct ValidationError {
     category: String,
     message: String,
+    /// Line of the configuration file the error is on, from 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<usize>,
+    /// The defined name that was probably meant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggestion: Option<String>,
+}
+
+impl ValidationError {
+    /// An error about the names `path` lead to in the configuration `yaml`, suggesting
+    /// the candidate closest to the misspelled `name`
+    fn located<'a>(
+        category: &str,
+        message: String,
+        yaml: &str,
+        path: &[&str],
+        name: &str,
+        candidates: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let location = config_hints::locate(yaml, path);
+        ValidationError {
+            category: category.to_string(),
+            message,
+            line: location.map(|(line, _)| line),
+            column: location.map(|(_, column)| column),
+            suggestion: config_hints::did_you_mean(name, candidates).map(str::to_string),
+        }
+    }
+
+    /// The error as printed on the command line
+    fn describe(&self) -> String {
+        let mut text = self.message.clone();
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            text.push_str(&format!(" (line {}, column {})", line, column));
+        }
+        if let Some(suggestion) = &self.suggestion {
+            text.push_str(&format!(". Did you mean '{}'?", suggestion));
+        }
+        text
+    }
 }
 
 #[derive(Serialize)]
@@ -61,8 +118,12 @@ struct ServiceTestResult {
 /// - Service validation (actually runs them to test)
 /// - Action syntax validation (parses but doesn't execute)
 ///
+/// - Icon size and shape against the keys they are shown on (with `fix`, small icons
+///   are replaced by enlarged copies)
+/// - Emoji in key texts without an emoji font to draw them
+///
 /// Returns true if validation succeeds, false otherwise.
-pub fn validate_config(config_path: &str, json_output: bool) -> bool {
+pub fn validate_config(config_path: &str, json_output: bool, fix: bool) -> bool {
     let mut result = ValidationResult {
         success: true,
         config_path: config_path.to_string(),
@@ -70,6 +131,7 @@ pub fn validate_config(config_path: &str, json_output: bool) -> bool {
         errors: Vec::new(),
         warnings: Vec::new(),
         unreferenced_icons: Vec::new(),
+        fixed_icons: BTreeMap::new(),
         services_tested: Vec::new(),
     };
 
@@ -88,18 +150,38 @@ pub fn validate_config(config_path: &str, json_output: bool) -> bool {
         }
     };
 
-    // Parse the YAML
-    let deserializer = serde_yaml_ng::Deserializer::from_str(&data);
-    let mut conf: KeyDeckConf = match serde_path_to_error::deserialize(deserializer) {
+    // Parse the YAML, TOML or JSON
+    let mut conf: KeyDeckConf = match keydeck_types::parse_config(&path, &data) {
         Ok(conf) => conf,
         Err(e) => {
-            eprintln!("Error parsing config file: {}", path.display());
-            eprintln!();
-            eprintln!("Path: {}", e.path());
-            eprintln!("{}", e.into_inner());
+            let err_path = e.path;
+            let error = ValidationError {
+                category: "syntax".to_string(),
+                suggestion: config_hints::unknown_name_hint(&e.message),
+                message: e.message,
+                line: e.line,
+                column: e.column,
+            };
+            if json_output {
+                result.success = false;
+                result.errors.push(error);
+                if let Ok(json) = serde_json::to_string_pretty(&result) {
+                    println!("{}", json);
+                }
+            } else {
+                eprintln!("Error parsing config file: {}", path.display());
+                eprintln!();
+                eprintln!("Path: {}", err_path);
+                eprintln!("{}", error.message);
+                if let Some(suggestion) = &error.suggestion {
+                    eprintln!("Did you mean '{}'?", suggestion);
+                }
+            }
             return false;
         }
     };
+    // Before templates are merged into the pages
+    let unknown_fields = conf.unknown_fields();
 
     // Validate tick_time is within range (1-60 seconds)
     if conf.tick_time < 1.0 || conf.tick_time > 60.0 {
@@ -146,7 +228,13 @@ pub fn validate_config(config_path: &str, json_output: bool) -> bool {
                         templates_map,
                         &mut visited,
                     ) {
-                        Ok((resolved_buttons, resolved_on_tick, resolved_lock, resolved_encoders)) => {
+                        Ok((
+                            resolved_buttons,
+                            resolved_on_tick,
+                            resolved_lock,
+                            resolved_encoders,
+                            resolved_theme,
+                        )) => {
                             for (button_name, button_config) in resolved_buttons {
                                 page.buttons.entry(button_name).or_insert(button_config);
                             }
@@ -162,6 +250,9 @@ pub fn validate_config(config_path: &str, json_output: bool) -> bool {
                                     page_encoders.entry(enc_name).or_insert(enc_config);
                                 }
                             }
+                            if page.theme.is_none() && resolved_theme.is_some() {
+                                page.theme = resolved_theme;
+                            }
                         }
                         Err(e) => {
                             eprintln!("Error resolving templates for page '{}': {}", page_name, e);
@@ -173,6 +264,18 @@ pub fn validate_config(config_path: &str, json_output: bool) -> bool {
         }
     }
 
+    // Resolve buttons based on other button definitions
+    if let Err(e) = conf.resolve_button_extends() {
+        eprintln!("Error resolving button definitions: {}", e);
+        return false;
+    }
+
+    // Create the buttons of generated pages from their current sources
+    crate::page_generator::read_sources(&mut conf);
+
+    // Fill the global button style into buttons that don't override it
+    conf.apply_button_defaults();
+
     // Compute dynamic flags for all buttons after template resolution
     crate::dynamic_detection::compute_all_dynamic_flags(&mut conf);
 
@@ -181,6 +284,12 @@ pub fn validate_config(config_path: &str, json_output: bool) -> bool {
     let macro_count = conf.macros.as_ref().map(|m| m.len()).unwrap_or(0);
     let service_count = conf.services.as_ref().map(|s| s.len()).unwrap_or(0);
     let color_count = conf.colors.as_ref().map(|c| c.len()).unwrap_or(0);
+    let icons = IconPaths::new(&path, conf.icon_dirs.as_deref());
+    let icon_dirs: Vec<String> = icons
+        .dirs()
+        .iter()
+        .map(|dir| dir.display().to_string())
+        .collect();
 
     let mut total_pages = 0;
     for (_group_name, page_group) in &conf.page_groups {
@@ -195,12 +304,12 @@ pub fn validate_config(config_path: &str, json_output: bool) -> bool {
         info_log!("  Macros: {}", macro_count);
         info_log!("  Services: {}", service_count);
         info_log!("  Colors: {}", color_count);
-        info_log!("  Image directory: {}", get_icon_dir());
+        info_log!("  Icon directories: {}", icon_dirs.join(", "));
         info_log!("  Tick time: {}s", conf.tick_time);
     }
 
     // Validate page references (main_page, jump targets, etc.)
-    validate_page_references(&conf, &mut result, json_output);
+    validate_page_references(&conf, &data, &mut result, json_output);
 
     // Validate macro syntax (parameter substitution patterns)
     validate_macro_syntax(&conf, &mut result);
@@ -209,10 +318,43 @@ pub fn validate_config(config_path: &str, json_output: bool) -> bool {
     validate_services(&conf, &mut result);
 
     // Validate button definition references
-    validate_button_def_references(&conf, &mut result);
+    validate_button_def_references(&conf, &data, &mut result);
+
+    // Validate encoder widget bindings
+    validate_encoder_widgets(&conf, &mut result);
+
+    // Validate theme selections
+    validate_theme_references(&conf, &data, &mut result);
+
+    // Validate the colors of buttons
+    validate_colors(&conf, &data, &mut result, json_output);
+
+    // Validate page visibility conditions
+    validate_page_visibility(&conf, &mut result);
+
+    // Validate the tick intervals of pages
+    validate_page_ticks(&conf, &mut result);
+
+    // Validate that pages have no unknown fields
+    validate_unknown_fields(
+        &unknown_fields,
+        conf.strict,
+        &data,
+        &mut result,
+        json_output,
+    );
+
+    // Validate the syntax of conditions
+    validate_conditions(&conf, &data, &mut result, json_output);
 
     // Validate icon file existence
-    validate_icon_files(&conf, &mut result, json_output);
+    validate_icon_files(&conf, &icons, &mut result, json_output);
+
+    // Validate icon sizes against the keys they are shown on
+    validate_icon_sizes(&conf, &icons, &mut result, &path, fix, json_output);
+
+    // Validate that emoji in key texts have a font
+    validate_emoji_font(&conf, &mut result, json_output);
 
     // Populate summary
     result.summary = Some(ConfigSummary {
@@ -222,7 +364,8 @@ pub fn validate_config(config_path: &str, json_output: bool) -> bool {
         macros: macro_count,
         services: service_count,
         colors: color_count,
-        image_dir: get_icon_dir(),
+        image_dir: keydeck_types::get_icon_dir(),
+        icon_dirs,
         tick_time: conf.tick_time,
     });
 
@@ -261,8 +404,6 @@ fn validate_macro_syntax(conf: &KeyDeckConf, result: &mut ValidationResult) {
         return;
     };
 
-    let param_pattern = regex::Regex::new(r"\$\{([^}]+)\}").unwrap();
-
     for (macro_name, macro_def) in macros {
         verbose_log!("  Checking macro '{}'", macro_name);
 
@@ -273,12 +414,8 @@ fn validate_macro_syntax(conf: &KeyDeckConf, result: &mut ValidationResult) {
             .map(|p| p.keys().cloned().collect())
             .unwrap_or_default();
 
-        // Scan the raw YAML value for parameter references
-        let mut used_params = HashSet::new();
-        scan_yaml_for_params(&macro_def.actions, &param_pattern, &mut used_params);
-
         // Check for undefined parameters (used but not in defaults)
-        for param in &used_params {
+        for param in &macro_def.used_params() {
             if !default_params.contains(param) {
                 let msg = format!(
                     "Macro '{}' uses parameter '{}' but doesn't define a default value",
@@ -294,32 +431,6 @@ fn validate_macro_syntax(conf: &KeyDeckConf, result: &mut ValidationResult) {
     }
 }
 
-/// Helper to scan YAML value recursively for parameter references like ${param}
-fn scan_yaml_for_params(
-    value: &serde_yaml_ng::Value,
-    pattern: &regex::Regex,
-    params: &mut HashSet<String>,
-) {
-    match value {
-        serde_yaml_ng::Value::String(s) => {
-            for cap in pattern.captures_iter(s) {
-                params.insert(cap[1].to_string());
-            }
-        }
-        serde_yaml_ng::Value::Sequence(seq) => {
-            for item in seq {
-                scan_yaml_for_params(item, pattern, params);
-            }
-        }
-        serde_yaml_ng::Value::Mapping(map) => {
-            for (_key, val) in map {
-                scan_yaml_for_params(val, pattern, params);
-            }
-        }
-        _ => {}
-    }
-}
-
 /// Validates services by actually running them
 fn validate_services(conf: &KeyDeckConf, result: &mut ValidationResult) {
     verbose_log!("Validating services...");
@@ -371,6 +482,7 @@ fn validate_services(conf: &KeyDeckConf, result: &mut ValidationResult) {
                                 result.errors.push(ValidationError {
                                     category: "service".to_string(),
                                     message: msg.clone(),
+                                    ..Default::default()
                                 });
                                 result.services_tested.push(ServiceTestResult {
                                     name: service_name.clone(),
@@ -394,6 +506,7 @@ fn validate_services(conf: &KeyDeckConf, result: &mut ValidationResult) {
                                     result.errors.push(ValidationError {
                                         category: "service".to_string(),
                                         message: msg.clone(),
+                                        ..Default::default()
                                     });
                                     result.services_tested.push(ServiceTestResult {
                                         name: service_name.clone(),
@@ -413,6 +526,7 @@ fn validate_services(conf: &KeyDeckConf, result: &mut ValidationResult) {
                             result.errors.push(ValidationError {
                                 category: "service".to_string(),
                                 message: msg.clone(),
+                                ..Default::default()
                             });
                             result.services_tested.push(ServiceTestResult {
                                 name: service_name.clone(),
@@ -431,6 +545,7 @@ fn validate_services(conf: &KeyDeckConf, result: &mut ValidationResult) {
                 result.errors.push(ValidationError {
                     category: "service".to_string(),
                     message: msg.clone(),
+                    ..Default::default()
                 });
                 result.services_tested.push(ServiceTestResult {
                     name: service_name.clone(),
@@ -444,65 +559,418 @@ fn validate_services(conf: &KeyDeckConf, result: &mut ValidationResult) {
 }
 
 /// Validates that all button definition references exist
-fn validate_button_def_references(conf: &KeyDeckConf, result: &mut ValidationResult) {
+fn validate_button_def_references(conf: &KeyDeckConf, yaml: &str, result: &mut ValidationResult) {
     verbose_log!("Validating button definition references...");
 
     let button_defs = conf.buttons.as_ref();
-    let mut referenced_button_defs = HashSet::new();
+    // Referenced button definitions, with the first button referring to each
+    let mut referenced_button_defs: BTreeMap<&str, [&str; 3]> = BTreeMap::new();
 
     // Collect all button definition references
-    for (_group_name, page_group) in &conf.page_groups {
-        for (_page_name, page) in &page_group.pages {
-            for (_button_key, button_config) in &page.buttons {
+    for (group_name, page_group) in &conf.page_groups {
+        for (page_name, page) in &page_group.pages {
+            for (button_key, button_config) in &page.buttons {
                 // Check if this is a button definition reference
                 if let ButtonConfig::Template(ref_name) = button_config {
-                    referenced_button_defs.insert(ref_name.clone());
+                    referenced_button_defs
+                        .entry(ref_name)
+                        .or_insert([group_name, page_name, button_key]);
                 }
             }
         }
     }
 
     // Check if referenced button definitions exist
-    for button_def_name in referenced_button_defs {
-        if let Some(button_defs_map) = button_defs {
-            if !button_defs_map.contains_key(&button_def_name) {
-                let msg = format!(
-                    "Button definition '{}' is referenced but not defined",
-                    button_def_name
-                );
-                eprintln!("Error: {}", msg);
+    for (button_def_name, path) in referenced_button_defs {
+        let msg = match button_defs {
+            Some(button_defs_map) if button_defs_map.contains_key(button_def_name) => continue,
+            Some(_) => format!(
+                "Button definition '{}' is referenced but not defined",
+                button_def_name
+            ),
+            None => format!(
+                "Button definition '{}' is referenced but no button definitions exist",
+                button_def_name
+            ),
+        };
+        let error = ValidationError::located(
+            "button_definition",
+            msg,
+            yaml,
+            &path,
+            button_def_name,
+            button_defs
+                .into_iter()
+                .flat_map(|defs| defs.keys().map(String::as_str)),
+        );
+        eprintln!("Error: {}", error.describe());
+        result.errors.push(error);
+    }
+}
+
+/// Validates that the global and per-page `theme` selections name a defined theme
+fn validate_theme_references(conf: &KeyDeckConf, yaml: &str, result: &mut ValidationResult) {
+    verbose_log!("Validating theme references...");
+    let themes: Vec<&str> = conf
+        .themes
+        .iter()
+        .flat_map(|themes| themes.keys().map(String::as_str))
+        .collect();
+
+    if let Some(theme) = &conf.theme {
+        if !themes.contains(&theme.as_str()) {
+            result.errors.push(ValidationError::located(
+                "theme",
+                format!("Global theme '{}' is not defined in 'themes'", theme),
+                yaml,
+                &["theme"],
+                theme,
+                themes.iter().copied(),
+            ));
+        }
+    }
+    for (group_name, page_group) in &conf.page_groups {
+        for (page_name, page) in &page_group.pages {
+            if let Some(theme) = &page.theme {
+                if !themes.contains(&theme.as_str()) {
+                    result.errors.push(ValidationError::located(
+                        "theme",
+                        format!(
+                            "Page group '{}', page '{}': theme '{}' is not defined in 'themes'",
+                            group_name, page_name, theme
+                        ),
+                        yaml,
+                        &[group_name, page_name, "theme"],
+                        theme,
+                        themes.iter().copied(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Validates the fixed colors of buttons: a color format, or a color named in `colors`
+/// or in the colors of a theme
+fn validate_colors(
+    conf: &KeyDeckConf,
+    yaml: &str,
+    result: &mut ValidationResult,
+    json_output: bool,
+) {
+    verbose_log!("Validating colors...");
+    let mut named: Vec<&str> = Vec::new();
+    let themes = conf.themes.iter().flat_map(|themes| themes.values());
+    let theme_colors = themes.filter_map(|theme| theme.colors.as_ref());
+    for colors in conf.colors.iter().chain(theme_colors) {
+        named.extend(colors.keys().map(String::as_str));
+    }
+    let mut candidates = named.clone();
+    candidates.extend(graphics::css_color_names());
+    let is_valid = |color: &str| {
+        color.contains("${") || named.contains(&color) || graphics::parse_color(color).is_ok()
+    };
+
+    for (group_name, page_group) in &conf.page_groups {
+        for (page_name, page) in &page_group.pages {
+            for (button_key, button_config) in &page.buttons {
+                let ButtonConfig::Detailed(button) = button_config else {
+                    continue;
+                };
+                let background = button.background.as_ref().and_then(ColorConfig::as_fixed);
+                let text_color = button.text_color.as_ref().and_then(ColorConfig::as_fixed);
+                let colors = [
+                    ("background", background),
+                    ("text_color", text_color),
+                    ("outline", button.outline.as_deref()),
+                ];
+                for (field, color) in colors {
+                    let Some(color) = color.filter(|color| !is_valid(color)) else {
+                        continue;
+                    };
+                    let error = ValidationError::located(
+                        "color",
+                        format!(
+                            "Page group '{}', page '{}', {}: {} '{}' is not a color format nor a named color",
+                            group_name, page_name, button_key, field, color
+                        ),
+                        yaml,
+                        &[group_name, page_name, button_key, field],
+                        color,
+                        candidates.iter().copied(),
+                    );
+                    if !json_output {
+                        eprintln!("Error: {}", error.describe());
+                    }
+                    result.errors.push(error);
+                }
+            }
+        }
+    }
+}
+
+/// Validates the days and times of the `visible_when` conditions of pages, the keys of
+/// their `chords` and the layers of their `modifier` buttons
+fn validate_page_visibility(conf: &KeyDeckConf, result: &mut ValidationResult) {
+    verbose_log!("Validating page visibility...");
+    for (group_name, page_group) in &conf.page_groups {
+        for (page_name, page) in &page_group.pages {
+            if let Some(Err(e)) = page.visible_when.as_ref().map(|v| v.validate()) {
                 result.errors.push(ValidationError {
-                    category: "button_definition".to_string(),
-                    message: msg,
+                    category: "visible_when".to_string(),
+                    message: format!("Page group '{}', page '{}': {}", group_name, page_name, e),
+                    ..Default::default()
                 });
             }
-        } else {
-            let msg = format!(
-                "Button definition '{}' is referenced but no button definitions exist",
-                button_def_name
-            );
-            eprintln!("Error: {}", msg);
-            result.errors.push(ValidationError {
-                category: "button_definition".to_string(),
-                message: msg,
+            for chord in page.chords.iter().flatten() {
+                if let Err(e) = chord.validate() {
+                    result.errors.push(ValidationError {
+                        category: "chords".to_string(),
+                        message: format!(
+                            "Page group '{}', page '{}': {}",
+                            group_name, page_name, e
+                        ),
+                        ..Default::default()
+                    });
+                }
+            }
+            if let Err(e) = page.validate_layers() {
+                result.errors.push(ValidationError {
+                    category: "layers".to_string(),
+                    message: format!("Page group '{}', page '{}': {}", group_name, page_name, e),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+}
+
+/// Reports the keys of pages that are neither a field nor a button: errors unless the
+/// configuration sets `strict: false`
+fn validate_unknown_fields(
+    unknown: &[Vec<String>],
+    strict: Option<bool>,
+    yaml: &str,
+    result: &mut ValidationResult,
+    json_output: bool,
+) {
+    verbose_log!("Validating unknown fields...");
+    for path in unknown {
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        let message = format!(
+            "Unknown field '{}': not a field of the page nor a button ('button#' or 'r#c#')",
+            path.join(".")
+        );
+        let error = ValidationError::located("unknown_field", message, yaml, &path, "", []);
+        if strict == Some(false) {
+            if !json_output {
+                warn_log!("{}", error.describe());
+            }
+            result.warnings.push(ValidationWarning {
+                message: error.describe(),
+                category: error.category,
             });
+        } else {
+            if !json_output {
+                eprintln!("Error: {}", error.describe());
+            }
+            result.errors.push(error);
+        }
+    }
+}
+
+/// Validates that the `tick` of pages is within the range of `tick_time`
+fn validate_page_ticks(conf: &KeyDeckConf, result: &mut ValidationResult) {
+    verbose_log!("Validating page ticks...");
+    for (group_name, page_group) in &conf.page_groups {
+        for (page_name, page) in &page_group.pages {
+            if let Some(tick) = page.tick.filter(|tick| !(1.0..=60.0).contains(tick)) {
+                result.errors.push(ValidationError {
+                    category: "tick".to_string(),
+                    message: format!(
+                        "Page group '{}', page '{}': tick must be between 1 and 60 seconds, not {}",
+                        group_name, page_name, tick
+                    ),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+}
+
+/// Validates the syntax of the conditions of `if` actions, of the `enabled` state and
+/// `blink`/`pulse` gates of buttons and of the `visible_when` value of pages
+fn validate_conditions(
+    conf: &KeyDeckConf,
+    yaml: &str,
+    result: &mut ValidationResult,
+    json_output: bool,
+) {
+    verbose_log!("Validating conditions...");
+    let mut check = |condition: &str, path: &[&str], what: String| {
+        if let Err(e) = Condition::parse(condition) {
+            let message = format!("{}: invalid condition '{}': {}", what, condition, e);
+            let error = ValidationError::located("condition", message, yaml, path, "", []);
+            if !json_output {
+                eprintln!("Error: {}", error.describe());
+            }
+            result.errors.push(error);
+        }
+    };
+    for (group_name, page_group) in &conf.page_groups {
+        for (page_name, page) in &page_group.pages {
+            let place = format!("Page group '{}', page '{}'", group_name, page_name);
+            if let Some(value) = page.visible_when.as_ref().and_then(|v| v.value.as_ref()) {
+                let path = [group_name, page_name, "visible_when", "value"];
+                check(value, &path, format!("{}, visible_when", place));
+            }
+            let mut actions: Vec<(&str, &[Action])> = page
+                .on_tick
+                .iter()
+                .map(|actions| ("on_tick", actions.as_slice()))
+                .chain(
+                    page.chords
+                        .iter()
+                        .flatten()
+                        .map(|chord| ("chords", chord.actions.as_slice())),
+                )
+                .collect();
+            for (button_key, button_config) in page.all_buttons() {
+                let ButtonConfig::Detailed(button) = button_config else {
+                    continue;
+                };
+                let what = format!("{}, {}", place, button_key);
+                if let Some(Enabled::Condition(condition)) = &button.enabled {
+                    let path = [group_name, page_name, button_key, "enabled"];
+                    check(condition, &path, what.clone());
+                }
+                for (field, animation) in [("blink", &button.blink), ("pulse", &button.pulse)] {
+                    let Some(animation) = animation else {
+                        continue;
+                    };
+                    if let (Some(value), None, None) =
+                        (&animation.value, animation.above, animation.below)
+                    {
+                        let path = [group_name, page_name, button_key, field];
+                        check(value, &path, format!("{}, {}", what, field));
+                    }
+                }
+                if let Some(button_actions) = &button.actions {
+                    actions.push((button_key, button_actions));
+                }
+            }
+            while let Some((location, list)) = actions.pop() {
+                for action in list {
+                    match action {
+                        Action::If {
+                            condition,
+                            then,
+                            else_actions,
+                        } => {
+                            let path = [group_name, page_name, location];
+                            check(condition, &path, format!("{}, {}", place, location));
+                            actions.push((location, then));
+                            if let Some(else_acts) = else_actions {
+                                actions.push((location, else_acts));
+                            }
+                        }
+                        Action::Confirm {
+                            confirm_actions,
+                            cancel_actions,
+                            ..
+                        } => {
+                            actions.push((location, confirm_actions));
+                            if let Some(cancel_acts) = cancel_actions {
+                                actions.push((location, cancel_acts));
+                            }
+                        }
+                        Action::Try {
+                            try_actions,
+                            else_actions,
+                        } => {
+                            actions.push((location, try_actions));
+                            if let Some(else_acts) = else_actions {
+                                actions.push((location, else_acts));
+                            }
+                        }
+                        Action::And { and_actions } => actions.push((location, and_actions)),
+                        Action::Or { or_actions } => actions.push((location, or_actions)),
+                        Action::Random { random_actions } => {
+                            actions.push((location, random_actions))
+                        }
+                        Action::Not { not_action } => {
+                            actions.push((location, std::slice::from_ref(not_action.as_ref())))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Validates that every encoder widget binds exactly one value source and a sane range
+fn validate_encoder_widgets(conf: &KeyDeckConf, result: &mut ValidationResult) {
+    verbose_log!("Validating encoder widgets...");
+
+    for (group_name, page_group) in &conf.page_groups {
+        for (page_name, page) in &page_group.pages {
+            let encoders = match &page.encoders {
+                Some(encoders) => encoders,
+                None => continue,
+            };
+            for (encoder_key, encoder) in encoders {
+                let widget = match &encoder.widget {
+                    Some(widget) => widget,
+                    None => continue,
+                };
+                let location = format!("{}/{}/{}", group_name, page_name, encoder_key);
+                let msg = match (&widget.var, &widget.audio) {
+                    (Some(_), Some(_)) => Some(format!(
+                        "Encoder widget at {} defines both 'var' and 'audio'",
+                        location
+                    )),
+                    (None, None) => Some(format!(
+                        "Encoder widget at {} needs either 'var' or 'audio'",
+                        location
+                    )),
+                    _ if widget.max.is_nan() || widget.min.is_nan() || widget.max <= widget.min => {
+                        Some(format!(
+                            "Encoder widget at {} has max ({}) not greater than min ({})",
+                            location, widget.max, widget.min
+                        ))
+                    }
+                    _ => None,
+                };
+                if let Some(msg) = msg {
+                    eprintln!("Error: {}", msg);
+                    result.errors.push(ValidationError {
+                        category: "encoder_widget".to_string(),
+                        message: msg,
+                        ..Default::default()
+                    });
+                }
+            }
         }
     }
 }
 
 /// Validates that all icon files referenced in buttons exist
-fn validate_icon_files(conf: &KeyDeckConf, result: &mut ValidationResult, json_output: bool) {
+fn validate_icon_files(
+    conf: &KeyDeckConf,
+    icons: &IconPaths,
+    result: &mut ValidationResult,
+    json_output: bool,
+) {
     verbose_log!("Validating icon files...");
 
-    let image_dir = get_icon_dir();
-
     let mut referenced_icons = HashSet::new();
 
     // Collect all icon references from button definitions
     if let Some(button_defs) = &conf.buttons {
         for (_name, button) in button_defs {
             if let Some(icon) = &button.icon {
-                referenced_icons.insert(icon.clone());
+                referenced_icons.extend(icon.images().into_iter().map(str::to_string));
             }
         }
     }
@@ -510,73 +978,200 @@ fn validate_icon_files(conf: &KeyDeckConf, result: &mut ValidationResult, json_o
     // Collect all icon references from pages
     for (_group_name, page_group) in &conf.page_groups {
         for (_page_name, page) in &page_group.pages {
+            if let Some(wallpaper) = &page.wallpaper {
+                referenced_icons.insert(wallpaper.image().to_string());
+            }
             for (_button_key, button_config) in &page.buttons {
                 if let ButtonConfig::Detailed(button) = button_config {
                     if let Some(icon) = &button.icon {
-                        referenced_icons.insert(icon.clone());
+                        referenced_icons.extend(icon.images().into_iter().map(str::to_string));
                     }
                 }
             }
         }
     }
 
-    // Check if icon files exist
+    // Check if icon files exist, keeping the files they name
+    let mut referenced_files = HashSet::new();
     for icon_file in &referenced_icons {
-        let icon_path = PathBuf::from(&image_dir).join(icon_file);
-        if !icon_path.exists() {
+        if let Some(icon_path) = icons.resolve(icon_file) {
+            if let Some(reason) = image_formats::unsupported(&icon_path) {
+                let msg = format!("Icon file '{}' cannot be shown: {}", icon_file, reason);
+                eprintln!("Error: {}", msg);
+                result.errors.push(ValidationError {
+                    category: "icon".to_string(),
+                    message: msg,
+                    ..Default::default()
+                });
+            } else {
+                verbose_log!("  ✓ Icon file exists: {}", icon_file);
+            }
+            referenced_files.insert(fs::canonicalize(&icon_path).unwrap_or(icon_path));
+        } else {
             let msg = format!(
                 "Icon file '{}' not found at path: {}",
                 icon_file,
-                icon_path.display()
+                icons.locate(icon_file).display()
             );
             eprintln!("Error: {}", msg);
             result.errors.push(ValidationError {
                 category: "icon".to_string(),
                 message: msg,
+                ..Default::default()
             });
-        } else {
-            verbose_log!("  ✓ Icon file exists: {}", icon_file);
         }
     }
 
-    // Collect unreferenced icon files for reporting
-    let image_dir_path = PathBuf::from(&image_dir);
-    if let Ok(entries) = fs::read_dir(&image_dir_path) {
-        let mut unreferenced_icons = Vec::new();
-
+    // Collect unreferenced icon files of every icon directory for reporting. Files of
+    // the default icon dir are reported by name, others by their path.
+    let default_dir = keydeck_types::get_icon_dir_path();
+    let mut unreferenced_icons = Vec::new();
+    for dir in icons.dirs() {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
         for entry in entries.flatten() {
-            if let Ok(file_type) = entry.file_type() {
-                if file_type.is_file() {
-                    if let Some(filename) = entry.file_name().to_str() {
-                        // Check if this icon is referenced in the config
-                        if !referenced_icons.contains(filename) {
-                            unreferenced_icons.push(filename.to_string());
-                        }
-                    }
+            let path = entry.path();
+            let file = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+            if !path.is_file() || referenced_files.contains(&file) {
+                continue;
+            }
+            if *dir == default_dir {
+                if let Some(filename) = entry.file_name().to_str() {
+                    unreferenced_icons.push(filename.to_string());
                 }
+            } else {
+                unreferenced_icons.push(path.display().to_string());
+            }
+        }
+    }
+
+    // Sort and report unreferenced icons
+    if !unreferenced_icons.is_empty() {
+        unreferenced_icons.sort();
+
+        // Always populate the result for JSON output
+        result.unreferenced_icons = unreferenced_icons.clone();
+
+        // In verbose mode (non-JSON), also print to console
+        if !json_output && crate::VERBOSITY.load(std::sync::atomic::Ordering::Relaxed) >= 2 {
+            verbose_log!("\n  Unreferenced icon files:");
+            for icon in unreferenced_icons {
+                verbose_log!("  {} # icon not referenced in configuration", icon);
             }
         }
+    }
+}
 
-        // Sort and report unreferenced icons
-        if !unreferenced_icons.is_empty() {
-            unreferenced_icons.sort();
+/// Validates that icons fit the keys they are shown on, enlarging small ones with `fix`
+fn validate_icon_sizes(
+    conf: &KeyDeckConf,
+    icons: &IconPaths,
+    result: &mut ValidationResult,
+    config_path: &Path,
+    fix: bool,
+    json_output: bool,
+) {
+    verbose_log!("Validating icon sizes...");
 
-            // Always populate the result for JSON output
-            result.unreferenced_icons = unreferenced_icons.clone();
+    let mut lints = icon_lint::lint(conf, icons, &DeviceManager::connected_key_sizes());
 
-            // In verbose mode (non-JSON), also print to console
-            if !json_output && crate::VERBOSITY.load(std::sync::atomic::Ordering::Relaxed) >= 2 {
-                verbose_log!("\n  Unreferenced icon files:");
-                for icon in unreferenced_icons {
-                    verbose_log!("  {} # icon not referenced in configuration", icon);
+    if fix {
+        match icon_lint::fix(&lints, icons, config_path) {
+            Ok(renamed) => {
+                for (icon, copy) in renamed {
+                    if !json_output {
+                        info_log!("  Icon '{}' replaced by its enlarged copy '{}'", icon, copy);
+                    }
+                    result.fixed_icons.insert(icon, copy);
                 }
             }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                result.errors.push(ValidationError {
+                    category: "icon".to_string(),
+                    message: e,
+                    ..Default::default()
+                });
+            }
+        }
+        for lint in &mut lints {
+            if result.fixed_icons.contains_key(&lint.icon) {
+                lint.issues
+                    .retain(|issue| *issue != icon_lint::Issue::Small);
+            }
         }
     }
+
+    for lint in lints.iter().filter(|lint| !lint.issues.is_empty()) {
+        let msg = lint.message();
+        if !json_output {
+            warn_log!("{}", msg);
+        }
+        result.warnings.push(ValidationWarning {
+            category: "icon".to_string(),
+            message: msg,
+        });
+    }
+}
+
+/// Warns when key texts use emoji, or shortcodes of emoji, that no color emoji font can draw
+fn validate_emoji_font(conf: &KeyDeckConf, result: &mut ValidationResult, json_output: bool) {
+    verbose_log!("Validating emoji font...");
+
+    if conf.emoji == Some(false) {
+        return;
+    }
+    let defined = conf.buttons.iter().flat_map(|buttons| buttons.values());
+    let on_pages = conf
+        .page_groups
+        .values()
+        .flat_map(|pages| pages.pages.values())
+        .flat_map(|page| page.buttons.values())
+        .filter_map(|button| match button {
+            ButtonConfig::Detailed(button) => Some(button),
+            _ => None,
+        });
+    let uses_emoji = defined
+        .chain(on_pages)
+        .filter_map(|button| match &button.text {
+            Some(TextConfig::Simple(text)) | Some(TextConfig::Detailed { value: text, .. }) => {
+                Some(text)
+            }
+            None => None,
+        })
+        .any(|text| expand_shortcodes(text).chars().any(is_emoji));
+    if !uses_emoji {
+        return;
+    }
+
+    let msg = match emoji_font() {
+        EmojiFont::Color(_) => return,
+        EmojiFont::Monochrome(name) => format!(
+            "No color emoji font is installed: emoji in key texts are drawn with {} in the \
+             text color (install Noto Color Emoji, or set `emoji: false`)",
+            name
+        ),
+        EmojiFont::Missing => "No emoji font is installed: emoji in key texts are drawn as \
+             boxes (install Noto Color Emoji, or set `emoji: false`)"
+            .to_string(),
+    };
+    if !json_output {
+        warn_log!("{}", msg);
+    }
+    result.warnings.push(ValidationWarning {
+        category: "font".to_string(),
+        message: msg,
+    });
 }
 
 /// Validates page references (main_page, restore_mode, jump targets)
-fn validate_page_references(conf: &KeyDeckConf, result: &mut ValidationResult, json_output: bool) {
+fn validate_page_references(
+    conf: &KeyDeckConf,
+    yaml: &str,
+    result: &mut ValidationResult,
+    json_output: bool,
+) {
     verbose_log!("Validating page references...");
 
     for (group_name, page_group) in &conf.page_groups {
@@ -589,28 +1184,56 @@ fn validate_page_references(conf: &KeyDeckConf, result: &mut ValidationResult, j
                     main_page_name,
                     page_group.pages.keys().collect::<Vec<_>>()
                 );
+                let error = ValidationError::located(
+                    "page_reference",
+                    msg,
+                    yaml,
+                    &[group_name, "main_page"],
+                    main_page_name,
+                    page_group.pages.keys().map(String::as_str),
+                );
                 if !json_output {
-                    eprintln!("Error: {}", msg);
+                    eprintln!("Error: {}", error.describe());
+                }
+                result.errors.push(error);
+            }
+        }
+
+        // Validate the pages of the rotation
+        if let Some(rotation) = &page_group.rotation {
+            for page_name in &rotation.pages {
+                if !page_group.pages.contains_key(page_name) {
+                    let msg = format!(
+                        "Page group '{}' rotates to page '{}' but this page does not exist",
+                        group_name, page_name
+                    );
+                    let error = ValidationError::located(
+                        "page_reference",
+                        msg,
+                        yaml,
+                        &[group_name, "rotation"],
+                        page_name,
+                        page_group.pages.keys().map(String::as_str),
+                    );
+                    if !json_output {
+                        eprintln!("Error: {}", error.describe());
+                    }
+                    result.errors.push(error);
                 }
-                result.errors.push(ValidationError {
-                    category: "page_reference".to_string(),
-                    message: msg,
-                });
             }
         }
 
         // Validate jump action targets in each page
         for (page_name, page) in &page_group.pages {
-            // Check button actions for jump targets
-            for (button_key, button_config) in &page.buttons {
+            // Check button actions, including those of layers, for jump targets
+            for (button_key, button_config) in page.all_buttons() {
                 if let crate::pages::ButtonConfig::Detailed(button) = button_config {
                     if let Some(actions) = &button.actions {
                         validate_actions_page_refs(
                             actions,
-                            group_name,
-                            page_name,
-                            button_key,
+                            [group_name, page_name, button_key],
                             &page_group.pages,
+                            yaml,
                             result,
                             json_output,
                         );
@@ -622,10 +1245,21 @@ fn validate_page_references(conf: &KeyDeckConf, result: &mut ValidationResult, j
             if let Some(on_tick_actions) = &page.on_tick {
                 validate_actions_page_refs(
                     on_tick_actions,
-                    group_name,
-                    page_name,
-                    "on_tick",
+                    [group_name, page_name, "on_tick"],
                     &page_group.pages,
+                    yaml,
+                    result,
+                    json_output,
+                );
+            }
+
+            // Check chord actions for jump targets
+            for chord in page.chords.iter().flatten() {
+                validate_actions_page_refs(
+                    &chord.actions,
+                    [group_name, page_name, "chords"],
+                    &page_group.pages,
+                    yaml,
                     result,
                     json_output,
                 );
@@ -640,16 +1274,17 @@ fn validate_page_references(conf: &KeyDeckConf, result: &mut ValidationResult, j
     // So we skip detailed macro jump validation here.
 }
 
-/// Helper to validate action references to pages
+/// Helper to validate action references to pages. `path` names the page group, page
+/// and button (or `on_tick`) of the actions.
 fn validate_actions_page_refs(
     actions: &[crate::pages::Action],
-    group_name: &str,
-    page_name: &str,
-    location: &str,
+    path: [&str; 3],
     available_pages: &indexmap::IndexMap<String, crate::pages::Page>,
+    yaml: &str,
     result: &mut ValidationResult,
     json_output: bool,
 ) {
+    let [group_name, page_name, location] = path;
     for action in actions {
         match action {
             crate::pages::Action::Jump { jump: target_page } => {
@@ -662,13 +1297,67 @@ fn validate_actions_page_refs(
                         target_page,
                         available_pages.keys().collect::<Vec<_>>()
                     );
+                    let error = ValidationError::located(
+                        "page_reference",
+                        msg,
+                        yaml,
+                        &path,
+                        target_page,
+                        available_pages.keys().map(String::as_str),
+                    );
                     if !json_output {
-                        eprintln!("Error: {}", msg);
+                        eprintln!("Error: {}", error.describe());
                     }
-                    result.errors.push(ValidationError {
-                        category: "page_reference".to_string(),
-                        message: msg,
-                    });
+                    result.errors.push(error);
+                }
+            }
+            crate::pages::Action::If {
+                then, else_actions, ..
+            } => {
+                validate_actions_page_refs(then, path, available_pages, yaml, result, json_output);
+                if let Some(else_acts) = else_actions {
+                    validate_actions_page_refs(
+                        else_acts,
+                        path,
+                        available_pages,
+                        yaml,
+                        result,
+                        json_output,
+                    );
+                }
+            }
+            crate::pages::Action::Random { random_actions } => {
+                validate_actions_page_refs(
+                    random_actions,
+                    path,
+                    available_pages,
+                    yaml,
+                    result,
+                    json_output,
+                );
+            }
+            crate::pages::Action::Confirm {
+                confirm_actions,
+                cancel_actions,
+                ..
+            } => {
+                validate_actions_page_refs(
+                    confirm_actions,
+                    path,
+                    available_pages,
+                    yaml,
+                    result,
+                    json_output,
+                );
+                if let Some(cancel_acts) = cancel_actions {
+                    validate_actions_page_refs(
+                        cancel_acts,
+                        path,
+                        available_pages,
+                        yaml,
+                        result,
+                        json_output,
+                    );
                 }
             }
             crate::pages::Action::Try {
@@ -678,20 +1367,18 @@ fn validate_actions_page_refs(
                 // Recursively validate try and else blocks
                 validate_actions_page_refs(
                     try_actions,
-                    group_name,
-                    page_name,
-                    location,
+                    path,
                     available_pages,
+                    yaml,
                     result,
                     json_output,
                 );
                 if let Some(else_acts) = else_actions {
                     validate_actions_page_refs(
                         else_acts,
-                        group_name,
-                        page_name,
-                        location,
+                        path,
                         available_pages,
+                        yaml,
                         result,
                         json_output,
                     );
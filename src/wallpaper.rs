@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! The `wallpaper` of a page: one image spread across all keys of a device.
+//!
+//! The image is scaled to cover the whole key grid, including the gaps between the
+//! keys, and cropped to its middle when its shape differs. Each key shows the part of
+//! the image that lies behind it, so the picture lines up across the gaps as if it were
+//! seen through the frame of the deck. The scaled image is kept for as long as its
+//! source and the grid stay the same, since every key of the page is cut from it.
+
+use crate::image_formats;
+use image::imageops::FilterType;
+use image::{ImageResult, RgbaImage};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::SystemTime;
+
+/// Size and place of the keys of a device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyGrid {
+    pub rows: u32,
+    pub cols: u32,
+    pub key_width: u32,
+    pub key_height: u32,
+    /// Space between two keys, in pixels
+    pub gap: u32,
+}
+
+impl KeyGrid {
+    /// Grid of `rows`x`cols` keys of `key_width`x`key_height`, `gap` key widths apart
+    pub fn new(rows: u32, cols: u32, key_width: u32, key_height: u32, gap: f32) -> Self {
+        KeyGrid {
+            rows: rows.max(1),
+            cols: cols.max(1),
+            key_width,
+            key_height,
+            gap: (key_width as f32 * gap).round() as u32,
+        }
+    }
+
+    /// Size of the whole grid, gaps included
+    pub fn size(&self) -> (u32, u32) {
+        (
+            self.cols * self.key_width + (self.cols - 1) * self.gap,
+            self.rows * self.key_height + (self.rows - 1) * self.gap,
+        )
+    }
+
+    /// Top-left corner of `key`, numbered from 1 row by row, within the grid
+    pub fn key_origin(&self, key: u8) -> (u32, u32) {
+        let index = key.saturating_sub(1) as u32;
+        let (row, col) = (index / self.cols, index % self.cols);
+        (
+            col * (self.key_width + self.gap),
+            row * (self.key_height + self.gap),
+        )
+    }
+}
+
+/// The last wallpaper scaled: its path, the grid it covers, its modification time and
+/// the scaled image
+type Scaled = Option<(String, (u32, u32), Option<SystemTime>, Arc<RgbaImage>)>;
+
+static SCALED: LazyLock<Mutex<Scaled>> = LazyLock::new(|| Mutex::new(None));
+
+/// The image at `path` scaled to cover `size`, from the last one scaled while its source
+/// is unchanged
+fn scaled(path: &str, size: (u32, u32)) -> ImageResult<Arc<RgbaImage>> {
+    let modified = std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok();
+    if let Some((last_path, last_size, time, image)) = SCALED.lock().unwrap().as_ref() {
+        if last_path == path && *last_size == size && *time == modified {
+            return Ok(image.clone());
+        }
+    }
+    let (width, height) = size;
+    let image = Arc::new(
+        image_formats::open(path)?
+            .resize_to_fill(width, height, FilterType::Lanczos3)
+            .into_rgba8(),
+    );
+    *SCALED.lock().unwrap() = Some((path.to_string(), size, modified, image.clone()));
+    Ok(image)
+}
+
+/// The part of the image at `path` behind `key`, when spread across `grid`
+pub fn slice(path: &str, grid: &KeyGrid, key: u8) -> ImageResult<RgbaImage> {
+    let image = scaled(path, grid.size())?;
+    let (x, y) = grid.key_origin(key);
+    Ok(image::imageops::crop_imm(&*image, x, y, grid.key_width, grid.key_height).to_image())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn keys_show_the_part_of_the_image_behind_them() {
+        let grid = KeyGrid::new(2, 3, 10, 8, 0.2);
+        assert_eq!(grid.gap, 2);
+        assert_eq!(grid.size(), (34, 18));
+        assert_eq!(grid.key_origin(1), (0, 0));
+        assert_eq!(grid.key_origin(3), (24, 0));
+        assert_eq!(grid.key_origin(5), (12, 10));
+
+        // A horizontal gradient comes out in order across a row
+        let path =
+            std::env::temp_dir().join(format!("keydeck-wallpaper-{}.png", std::process::id()));
+        let image = RgbaImage::from_fn(34, 18, |x, _| Rgba([x as u8 * 7, 0, 0, 255]));
+        image.save(&path).unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let first = slice(&path, &grid, 4).unwrap();
+        let last = slice(&path, &grid, 6).unwrap();
+        assert_eq!(first.dimensions(), (10, 8));
+        assert_eq!(first.get_pixel(0, 0)[0], 0);
+        assert_eq!(last.get_pixel(0, 0)[0], 24 * 7);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
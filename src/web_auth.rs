@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Access control of the web deck: who may connect, with which [`WebScope`], and the
+//! TLS setup that keeps the tokens off the wire.
+//!
+//! The web deck asks an [`Authenticator`] about every request, passing the token it
+//! carried. Without `tokens` in the configuration everyone is let in: to press the keys
+//! when the web deck is bound to this computer only, and merely to view them when it
+//! reaches the network. With tokens, each one grants its own scope and requests
+//! without a valid one are refused.
+
+use crate::pages::{WebDeck, WebScope, WebTls, WebToken};
+use rustls::crypto::ring::default_provider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// A client let in by an [`Authenticator`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Access {
+    pub scope: WebScope,
+    /// Who the client is, for the log
+    pub who: String,
+    /// Whether a token vouched for the client; browsers let in without one must come
+    /// from the page of the web deck itself
+    pub vouched: bool,
+}
+
+/// Decides what the bearer of a token may do; `None` refuses the request
+pub trait Authenticator: Send + Sync {
+    fn authorize(&self, token: Option<&str>) -> Option<Access>;
+}
+
+/// Lets everyone in with the same scope
+struct OpenAccess(WebScope);
+
+impl Authenticator for OpenAccess {
+    fn authorize(&self, _token: Option<&str>) -> Option<Access> {
+        Some(Access {
+            scope: self.0,
+            who: "anonymous".to_string(),
+            vouched: false,
+        })
+    }
+}
+
+/// Lets in the bearers of the configured tokens, each with its scope
+struct TokenAccess(Vec<WebToken>);
+
+impl Authenticator for TokenAccess {
+    fn authorize(&self, token: Option<&str>) -> Option<Access> {
+        let token = token?;
+        // Every token is compared, so the time taken tells nothing about which matched
+        self.0
+            .iter()
+            .fold(None, |found, candidate| {
+                let matches = same_secret(candidate.token.as_bytes(), token.as_bytes());
+                found.or(matches.then_some(candidate))
+            })
+            .map(|candidate| Access {
+                scope: candidate.scope,
+                who: candidate
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("{:?} token", candidate.scope).to_lowercase()),
+                vouched: true,
+            })
+    }
+}
+
+/// Compares two secrets in a time that only depends on their length
+fn same_secret(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Whether the web deck only listens on this computer
+pub fn local_only(conf: &WebDeck) -> bool {
+    conf.bind.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback()) || conf.bind == "localhost"
+}
+
+/// The authenticator for the `tokens` of a web deck; without any, clients from the
+/// network may only watch
+pub fn authenticator(conf: &WebDeck) -> Arc<dyn Authenticator> {
+    let tokens: Vec<WebToken> = conf
+        .tokens
+        .iter()
+        .filter(|token| !token.token.is_empty())
+        .cloned()
+        .collect();
+    if tokens.is_empty() && local_only(conf) {
+        Arc::new(OpenAccess(WebScope::Control))
+    } else if tokens.is_empty() {
+        Arc::new(OpenAccess(WebScope::Readonly))
+    } else {
+        Arc::new(TokenAccess(tokens))
+    }
+}
+
+/// Loads the certificate and key of a web deck served over TLS
+pub fn tls_config(tls: &WebTls) -> Result<Arc<ServerConfig>, String> {
+    let certs = CertificateDer::pem_file_iter(&tls.cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("cannot read certificate {}: {}", tls.cert, e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate found in {}", tls.cert));
+    }
+    let key = PrivateKeyDer::from_pem_file(&tls.key)
+        .map_err(|e| format!("cannot read private key {}: {}", tls.key, e))?;
+    let config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("invalid certificate or key: {}", e))?;
+    Ok(Arc::new(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(token: &str, scope: WebScope) -> WebToken {
+        WebToken {
+            token: token.to_string(),
+            scope,
+            name: None,
+        }
+    }
+
+    #[test]
+    fn tokens_grant_their_scope() {
+        let conf = WebDeck {
+            bind: "0.0.0.0".to_string(),
+            port: 8765,
+            advertise: None,
+            tokens: vec![
+                token("viewer", WebScope::Readonly),
+                token("owner", WebScope::Admin),
+            ],
+            tls: None,
+        };
+        let auth = authenticator(&conf);
+        let scope = |token| auth.authorize(token).map(|access| access.scope);
+        assert_eq!(scope(Some("viewer")), Some(WebScope::Readonly));
+        assert_eq!(scope(Some("owner")), Some(WebScope::Admin));
+        assert_eq!(scope(Some("owne")), None);
+        assert_eq!(scope(None), None);
+
+        let open = authenticator(&WebDeck {
+            tokens: vec![],
+            ..conf.clone()
+        });
+        assert_eq!(open.authorize(None).unwrap().scope, WebScope::Readonly);
+        let local = authenticator(&WebDeck {
+            bind: "localhost".to_string(),
+            tokens: vec![],
+            ..conf
+        });
+        assert_eq!(local.authorize(None).unwrap().scope, WebScope::Control);
+    }
+}
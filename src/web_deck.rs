@@ -0,0 +1,775 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Companion web deck (`web_deck` in the configuration).
+//!
+//! A small HTTP server serves one page drawing the keys recorded in the [`Mirror`] as a
+//! touch grid. Its WebSocket streams every new key image to the browser, and turns
+//! touches into the same `ButtonDown`/`ButtonUp` events the hardware sends, so they run
+//! the same actions. With `readonly` in the query, touches are ignored: a live view of
+//! the deck for an OBS browser source or the configuration UI.
+//!
+//! With `tokens` configured, every request needs one (`?token=` or a bearer header),
+//! and its scope caps what the connection may do; see [`crate::web_auth`]. With `tls`,
+//! the same server speaks HTTPS and WSS instead. A WebSocket opened by a browser
+//! without a token must come from the page of the web deck itself, so other websites
+//! cannot press the keys of an open web deck.
+//!
+//! A `POST` to `/hook/<name>` runs the actions of the configured webhook of that name;
+//! it needs the control scope, comes from no other site unless it carries a token, and
+//! is answered before they run.
+//!
+//! WebSocket protocol on `/ws?device=<serial>&readonly` (the lowest serial when no
+//! device is given):
+//!
+//! ```text
+//! server → browser  text    {"device":"<serial>","rows":R,"cols":C,"keys":N}  (keys 0: no device)
+//! server → browser  binary  <key index byte><PNG image>   (index byte only: key cleared)
+//! server → browser  text    {"key":<index>,"pressed":<bool>}  (a key of the device pressed
+//!                           or released, from the [`crate::event_bus`])
+//! browser → server  text    down <index> | up <index>     (0-based key index; control)
+//! browser → server  text    reload | hotkey <combination> | setvar <key> <value> | clearvar <key>
+//!                           (admin; as on the control socket)
+//! ```
+//!
+//! The JSON messages are the [`DeckLayout`] and [`KeyPress`] of `keydeck_types::api`.
+
+use crate::event::{send, DeviceEvent};
+use crate::event_bus::{self, BusEvent, Topic};
+use crate::mirror::{FrameSink, Mirror, MirroredDevices};
+use crate::pages::{WebDeck, WebScope};
+use crate::web_auth::{authenticator, local_only, tls_config, Access, Authenticator};
+use crate::{error_log, info_log, verbose_log, warn_log};
+use image::{DynamicImage, ImageFormat};
+use keydeck_types::api::{DeckLayout, KeyPress};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use std::io::{Cursor, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::Message;
+
+const PAGE: &str = include_str!("web_deck.html");
+
+/// How long a WebSocket waits for a touch before sending pending key images
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long a new connection may take to send its request, TLS handshake included
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest request head accepted
+const MAX_REQUEST_HEAD: usize = 8192;
+
+/// Most connections served at once; more are closed as soon as they are accepted
+const MAX_CONNECTIONS: usize = 64;
+
+/// A browser connection, following the requested device or the default one
+struct Client {
+    device: Option<String>,
+    tx: Sender<Message>,
+}
+
+/// Browser connections of the web deck, fed by the mirror
+#[derive(Clone, Default)]
+struct WebClients(Arc<Mutex<Vec<Client>>>);
+
+impl WebClients {
+    /// Adds a client, starting with everything it should currently show
+    fn subscribe(&self, mirror: &Mirror, device: Option<String>, tx: Sender<Message>) {
+        mirror.with_devices(|devices| {
+            for message in snapshot(devices, &device) {
+                let _ = tx.send(message);
+            }
+            self.0.lock().unwrap().push(Client { device, tx });
+        });
+    }
+}
+
+impl FrameSink for WebClients {
+    fn devices_changed(&mut self, devices: &MirroredDevices) {
+        self.0.lock().unwrap().retain(|client| {
+            snapshot(devices, &client.device)
+                .into_iter()
+                .all(|message| client.tx.send(message).is_ok())
+        });
+    }
+
+    fn key_changed(&mut self, devices: &MirroredDevices, sn: &str, index: u8) {
+        // Encoded once, and only when someone is watching this device
+        let mut message = None;
+        self.0.lock().unwrap().retain(|client| {
+            if resolve(devices, &client.device).map(String::as_str) != Some(sn) {
+                return true;
+            }
+            let message = message.get_or_insert_with(|| {
+                let image = devices[sn].keys[index as usize].as_ref();
+                frame_message(index, image)
+            });
+            client.tx.send(message.clone()).is_ok()
+        });
+    }
+}
+
+/// Serial of the device a client asking for `device` is shown
+fn resolve<'a>(devices: &'a MirroredDevices, device: &Option<String>) -> Option<&'a String> {
+    match device {
+        Some(sn) => devices.get_key_value(sn).map(|(sn, _)| sn),
+        None => devices.keys().next(),
+    }
+}
+
+/// Layout message followed by all current key images of the device shown to a client
+fn snapshot(devices: &MirroredDevices, device: &Option<String>) -> Vec<Message> {
+    let Some((sn, state)) = resolve(devices, device).map(|sn| (sn, &devices[sn])) else {
+        return vec![layout_message("", 0, 0, 0)];
+    };
+    let mut messages = vec![layout_message(sn, state.rows, state.cols, state.keys.len())];
+    for (index, image) in state.keys.iter().enumerate() {
+        if let Some(image) = image {
+            messages.push(frame_message(index as u8, Some(image)));
+        }
+    }
+    messages
+}
+
+fn layout_message(sn: &str, rows: usize, cols: usize, keys: usize) -> Message {
+    let layout = DeckLayout {
+        device: sn.to_string(),
+        rows,
+        cols,
+        keys,
+    };
+    Message::Text(serde_json::to_string(&layout).unwrap_or_default())
+}
+
+/// The message telling that a key of the device shown to a client was pressed or released
+fn press_message(event: &BusEvent, device: &Option<String>, mirror: &Mirror) -> Option<Message> {
+    let (sn, button_id, pressed) = match event {
+        BusEvent::Device(DeviceEvent::ButtonDown { sn, button_id }) => (sn, button_id, true),
+        BusEvent::Device(DeviceEvent::ButtonUp { sn, button_id }) => (sn, button_id, false),
+        _ => return None,
+    };
+    if !mirror.with_devices(|devices| resolve(devices, device) == Some(sn)) {
+        return None;
+    }
+    let press = KeyPress {
+        key: button_id.checked_sub(1)?,
+        pressed,
+    };
+    serde_json::to_string(&press).ok().map(Message::Text)
+}
+
+fn frame_message(index: u8, image: Option<&DynamicImage>) -> Message {
+    let mut data = Cursor::new(vec![index]);
+    data.set_position(1);
+    if let Some(image) = image {
+        if let Err(e) = image.write_to(&mut data, ImageFormat::Png) {
+            error_log!("Error while encoding web deck key image: {}", e);
+        }
+    }
+    Message::Binary(data.into_inner())
+}
+
+/// Shared by all connections of the web deck
+#[derive(Clone)]
+struct WebContext {
+    mirror: Mirror,
+    clients: WebClients,
+    tx: Sender<DeviceEvent>,
+    still_active: Arc<AtomicBool>,
+    auth: Arc<dyn Authenticator>,
+    tls: Option<Arc<ServerConfig>>,
+    /// Connections currently served
+    connections: Arc<AtomicUsize>,
+}
+
+/// Counts a served connection for as long as it lives
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    /// Takes a slot, unless [`MAX_CONNECTIONS`] are already served
+    fn take(connections: &Arc<AtomicUsize>) -> Option<ConnectionSlot> {
+        connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < MAX_CONNECTIONS).then_some(count + 1)
+            })
+            .ok()
+            .map(|_| ConnectionSlot(connections.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// The byte stream of a browser connection: plain TCP, or TLS over it
+trait Transport: Read + Write + Send {
+    fn tcp(&self) -> &TcpStream;
+}
+
+impl Transport for TcpStream {
+    fn tcp(&self) -> &TcpStream {
+        self
+    }
+}
+
+impl Transport for StreamOwned<ServerConnection, TcpStream> {
+    fn tcp(&self) -> &TcpStream {
+        &self.sock
+    }
+}
+
+/// A browser connection, replaying the request head read to route it, so the
+/// WebSocket handshake still sees the whole request
+struct Connection {
+    head: Cursor<Vec<u8>>,
+    transport: Box<dyn Transport>,
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if (self.head.position() as usize) < self.head.get_ref().len() {
+            return self.head.read(buf);
+        }
+        self.transport.read(buf)
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.transport.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.transport.flush()
+    }
+}
+
+/// Starts the web deck server, showing the devices recorded in `mirror`
+pub fn start_web_deck(
+    conf: &WebDeck,
+    mirror: &Mirror,
+    tx: &Sender<DeviceEvent>,
+    still_active: &Arc<AtomicBool>,
+) {
+    let tls = match conf.tls.as_ref().map(tls_config).transpose() {
+        Ok(tls) => tls,
+        Err(e) => {
+            // Falling back to plain HTTP would send the tokens in the clear
+            error_log!("Web deck not started, TLS setup failed: {}", e);
+            return;
+        }
+    };
+    let address = format!("{}:{}", conf.bind, conf.port);
+    let listener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error_log!("Web deck cannot listen on {}: {}", address, e);
+            return;
+        }
+    };
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    info_log!("Web deck available at {}://{}/", scheme, address);
+    let local = local_only(conf);
+    if !local && conf.tokens.is_empty() {
+        warn_log!("The web deck reaches the network without tokens: it is view-only; configure tokens to press the keys");
+    } else if !local && tls.is_none() {
+        warn_log!("The web deck tokens travel unencrypted; configure tls to protect them");
+    }
+
+    let clients = WebClients::default();
+    mirror.add_sink(Box::new(clients.clone()));
+    let context = WebContext {
+        mirror: mirror.clone(),
+        clients,
+        tx: tx.clone(),
+        still_active: still_active.clone(),
+        auth: authenticator(conf),
+        tls,
+        connections: Arc::new(AtomicUsize::new(0)),
+    };
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            if !context.still_active.load(Ordering::Relaxed) {
+                break;
+            }
+            let Ok(stream) = stream else { continue };
+            let Some(slot) = ConnectionSlot::take(&context.connections) else {
+                verbose_log!("Web deck busy, connection refused");
+                continue;
+            };
+            let context = context.clone();
+            thread::spawn(move || {
+                serve_connection(stream, &context);
+                drop(slot);
+            });
+        }
+    });
+}
+
+fn serve_connection(stream: TcpStream, context: &WebContext) {
+    let peer = stream
+        .peer_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_default();
+    if stream.set_read_timeout(Some(REQUEST_TIMEOUT)).is_err() {
+        return;
+    }
+    let mut transport: Box<dyn Transport> = match &context.tls {
+        Some(tls) => match ServerConnection::new(tls.clone()) {
+            Ok(connection) => Box::new(StreamOwned::new(connection, stream)),
+            Err(e) => {
+                error_log!("Web deck cannot start TLS with {}: {}", peer, e);
+                return;
+            }
+        },
+        None => Box::new(stream),
+    };
+    let Some(head) = read_request_head(&mut transport) else {
+        verbose_log!("Web deck client {} sent no valid request", peer);
+        return;
+    };
+    let request = String::from_utf8_lossy(&head).into_owned();
+    let mut connection = Connection {
+        head: Cursor::new(head),
+        transport,
+    };
+
+    let mut lines = request.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or("/");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let params: Vec<(&str, &str)> = query
+        .split('&')
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        .collect();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, value)| *key == name && !value.is_empty())
+            .map(|(_, value)| value.to_string())
+    };
+    let headers: Vec<(&str, &str)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .collect();
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    };
+    let bearer = header("authorization").and_then(|value| {
+        value
+            .get(..7)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("bearer "))?;
+        Some(value[7..].trim())
+    });
+    let device = param("device").map(|device| percent_decode(&device));
+    let readonly = params.iter().any(|(key, _)| *key == "readonly");
+
+    let token = param("token")
+        .map(|token| percent_decode(&token))
+        .or(bearer.map(str::to_string));
+    let Some(mut access) = context.auth.authorize(token.as_deref()) else {
+        warn_log!("Web deck refused {}: missing or invalid token", peer);
+        respond(
+            &mut connection,
+            "401 Unauthorized",
+            "text/plain",
+            "Unauthorized",
+        );
+        return;
+    };
+    if readonly {
+        access.scope = access.scope.min(WebScope::Readonly);
+    }
+    let hook = path.strip_prefix("/hook/").filter(|name| !name.is_empty());
+    let guarded = path == "/ws" || hook.is_some();
+    if guarded && !access.vouched && !same_origin(header("origin"), header("host")) {
+        warn_log!("Web deck refused {}: request sent from another site", peer);
+        respond(&mut connection, "403 Forbidden", "text/plain", "Forbidden");
+        return;
+    }
+
+    match path {
+        "/ws" => serve_websocket(connection, peer, device, access, context),
+        "/" => respond(&mut connection, "200 OK", "text/html; charset=utf-8", PAGE),
+        _ => match hook {
+            Some(_) if method != "POST" => respond(
+                &mut connection,
+                "405 Method Not Allowed",
+                "text/plain",
+                "Webhooks take POST",
+            ),
+            Some(name) => webhook(&mut connection, name, &access, context),
+            None => respond(&mut connection, "404 Not Found", "text/plain", "Not found"),
+        },
+    }
+}
+
+/// Whether a request comes from the page served by the web deck, as told by its
+/// `Origin` header; clients other than browsers send none
+fn same_origin(origin: Option<&str>, host: Option<&str>) -> bool {
+    let Some(origin) = origin else {
+        return true;
+    };
+    let origin = origin
+        .strip_prefix("https://")
+        .or_else(|| origin.strip_prefix("http://"));
+    origin
+        .zip(host)
+        .is_some_and(|(origin, host)| origin.trim_end_matches('/').eq_ignore_ascii_case(host))
+}
+
+/// Decodes the `%XX` escapes and `+` of a query parameter
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (escaped, bytes[i]) {
+            (Some(byte), _) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (None, b'+') => decoded.push(b' '),
+            (None, byte) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Hands a webhook call to the event loop, which runs the actions of the webhook
+fn webhook(connection: &mut Connection, name: &str, access: &Access, context: &WebContext) {
+    if access.scope < WebScope::Control {
+        warn_log!("Web deck client {} may not call webhooks", access.who);
+        respond(connection, "403 Forbidden", "text/plain", "Forbidden");
+        return;
+    }
+    let name = name.to_string();
+    send(&context.tx, DeviceEvent::Webhook { name });
+    respond(connection, "202 Accepted", "text/plain", "Accepted");
+}
+
+/// Reads the request line and headers, up to the blank line ending them
+fn read_request_head(transport: &mut Box<dyn Transport>) -> Option<Vec<u8>> {
+    let mut head = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST_HEAD {
+            return None;
+        }
+        let read = transport.read(&mut chunk).ok().filter(|read| *read > 0)?;
+        head.extend_from_slice(&chunk[..read]);
+    }
+    Some(head)
+}
+
+fn respond(connection: &mut Connection, status: &str, content_type: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    let _ = connection.write_all(response.as_bytes());
+    let _ = connection.flush();
+}
+
+/// Streams key images to a browser; what its messages may do depends on its scope
+fn serve_websocket(
+    connection: Connection,
+    peer: String,
+    device: Option<String>,
+    access: Access,
+    context: &WebContext,
+) {
+    let mut socket = match tungstenite::accept(connection) {
+        Ok(socket) => socket,
+        Err(e) => {
+            verbose_log!("Web deck handshake with {} failed: {}", peer, e);
+            return;
+        }
+    };
+    let poll = socket
+        .get_ref()
+        .transport
+        .tcp()
+        .set_read_timeout(Some(POLL_INTERVAL));
+    if let Err(e) = poll {
+        error_log!("Web deck cannot poll {}: {}", peer, e);
+        return;
+    }
+    verbose_log!(
+        "Web deck client {} connected as {} ({:?})",
+        peer,
+        access.who,
+        access.scope
+    );
+
+    let (updates_tx, updates_rx) = channel::<Message>();
+    context
+        .clients
+        .subscribe(&context.mirror, device.clone(), updates_tx);
+    let mut presses = event_bus::subscribe(&[Topic::Input]);
+
+    while context.still_active.load(Ordering::Relaxed) {
+        match socket.read() {
+            Ok(Message::Text(text)) => command(&text, &device, &access, context),
+            Ok(Message::Close(_)) => break,
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => break,
+        }
+        let pressed = std::iter::from_fn(|| presses.try_recv())
+            .filter_map(|event| press_message(&event, &device, &context.mirror));
+        for message in updates_rx.try_iter().chain(pressed) {
+            if socket.send(message).is_err() {
+                verbose_log!("Web deck client {} disconnected", peer);
+                return;
+            }
+        }
+    }
+    verbose_log!("Web deck client {} disconnected", peer);
+}
+
+/// Runs a message of a browser, when its scope allows it
+fn command(text: &str, device: &Option<String>, access: &Access, context: &WebContext) {
+    let (kind, args) = text.trim().split_once(' ').unwrap_or((text.trim(), ""));
+    let needed = match kind {
+        "down" | "up" => WebScope::Control,
+        "reload" | "hotkey" | "setvar" | "clearvar" => WebScope::Admin,
+        _ => {
+            verbose_log!("Unknown web deck message: {:?}", text);
+            return;
+        }
+    };
+    if access.scope < needed {
+        // Read-only views send touches too; only a missing admin right is worth a warning
+        if needed == WebScope::Admin {
+            warn_log!("Web deck client {} may not run '{}'", access.who, kind);
+        }
+        return;
+    }
+    let args = args.trim();
+    match kind {
+        "down" | "up" => press(kind, args, device, context),
+        "reload" => send(&context.tx, DeviceEvent::Reload),
+        "hotkey" if !args.is_empty() => send(
+            &context.tx,
+            DeviceEvent::Hotkey {
+                combination: args.to_string(),
+            },
+        ),
+        "setvar" | "clearvar" => {
+            let (key, value) = args.split_once(' ').unwrap_or((args, ""));
+            if key.is_empty() {
+                return;
+            }
+            let value = (kind == "setvar").then(|| value.to_string());
+            let key = key.to_string();
+            send(&context.tx, DeviceEvent::SetContextVar { key, value });
+        }
+        _ => verbose_log!("Incomplete web deck message: {:?}", text),
+    }
+}
+
+/// Turns a `down <index>` / `up <index>` message into a button event
+fn press(kind: &str, index: &str, device: &Option<String>, context: &WebContext) {
+    let Some((sn, key_count)) = context.mirror.with_devices(|devices| {
+        resolve(devices, device).map(|sn| (sn.clone(), devices[sn].keys.len()))
+    }) else {
+        return;
+    };
+    let index = index.parse::<u8>().ok();
+    let Some(button_id) = index
+        .filter(|&index| (index as usize) < key_count)
+        .and_then(|index| index.checked_add(1))
+    else {
+        verbose_log!("Web deck key {:?} out of range", index);
+        return;
+    };
+    if kind == "down" {
+        send(&context.tx, DeviceEvent::ButtonDown { sn, button_id });
+    } else {
+        send(&context.tx, DeviceEvent::ButtonUp { sn, button_id });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pages::WebToken;
+    use std::sync::mpsc::Receiver;
+
+    /// A web deck open to everyone, mirroring a device of six keys, and its events
+    fn context() -> (WebContext, Receiver<DeviceEvent>) {
+        let mirror = Mirror::default();
+        mirror.add_device("SN1", 2, 3, (72, 72), 6);
+        let (tx, rx) = channel();
+        let context = WebContext {
+            mirror,
+            clients: WebClients::default(),
+            tx,
+            still_active: Arc::new(AtomicBool::new(true)),
+            auth: authenticator(&WebDeck {
+                bind: "127.0.0.1".to_string(),
+                port: 8765,
+                advertise: None,
+                tokens: vec![],
+                tls: None,
+            }),
+            tls: None,
+            connections: Arc::new(AtomicUsize::new(0)),
+        };
+        (context, rx)
+    }
+
+    /// The status line of the answer to the request line `request`
+    fn call(request: &str, context: &WebContext) -> String {
+        call_with(request, "", context)
+    }
+
+    /// The status line of the answer to the request line `request`, sent with `headers`
+    fn call_with(request: &str, headers: &str, context: &WebContext) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client
+            .write_all(format!("{}\r\nHost: keydeck\r\n{}\r\n", request, headers).as_bytes())
+            .unwrap();
+        serve_connection(listener.accept().unwrap().0, context);
+        let mut answer = String::new();
+        client.read_to_string(&mut answer).unwrap();
+        answer.lines().next().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn presses_outside_the_device_are_dropped() {
+        let (context, rx) = context();
+        let access = Access {
+            scope: WebScope::Control,
+            who: "test".to_string(),
+            vouched: false,
+        };
+        for text in ["down 5", "down 6", "up 255", "down -1", "up 5"] {
+            command(text, &None, &access, &context);
+        }
+        let sn = "SN1".to_string();
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            [
+                DeviceEvent::ButtonDown {
+                    sn: sn.clone(),
+                    button_id: 6
+                },
+                DeviceEvent::ButtonUp { sn, button_id: 6 },
+            ]
+        );
+    }
+
+    #[test]
+    fn webhook_calls_reach_the_event_loop() {
+        let (context, rx) = context();
+        assert_eq!(
+            call("POST /hook/deploy HTTP/1.1", &context),
+            "HTTP/1.1 202 Accepted"
+        );
+        assert_eq!(
+            call("POST /hook/deploy?readonly HTTP/1.1", &context),
+            "HTTP/1.1 403 Forbidden"
+        );
+        assert_eq!(
+            call("POST /hook/ HTTP/1.1", &context),
+            "HTTP/1.1 404 Not Found"
+        );
+        // A link or an image of another page sends a GET, a form of another site its origin
+        assert_eq!(
+            call("GET /hook/deploy HTTP/1.1", &context),
+            "HTTP/1.1 405 Method Not Allowed"
+        );
+        assert_eq!(
+            call_with(
+                "POST /hook/deploy HTTP/1.1",
+                "Origin: https://evil.example\r\n",
+                &context
+            ),
+            "HTTP/1.1 403 Forbidden"
+        );
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            [DeviceEvent::Webhook {
+                name: "deploy".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn websockets_from_other_sites_need_a_token() {
+        const UPGRADE: &str = "Connection: Upgrade\r\nUpgrade: websocket\r\n\
+            Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n";
+        let (mut context, _rx) = context();
+        // Stopped, so an accepted WebSocket closes right after its handshake
+        context.still_active.store(false, Ordering::Relaxed);
+        let open = |origin: &str, context: &WebContext| {
+            let headers = format!("{}Origin: {}\r\n", UPGRADE, origin);
+            call_with("GET /ws HTTP/1.1", &headers, context)
+        };
+        assert_eq!(
+            open("http://keydeck", &context),
+            "HTTP/1.1 101 Switching Protocols"
+        );
+        assert_eq!(
+            open("https://evil.example", &context),
+            "HTTP/1.1 403 Forbidden"
+        );
+        assert_eq!(open("null", &context), "HTTP/1.1 403 Forbidden");
+
+        context.auth = authenticator(&WebDeck {
+            bind: "127.0.0.1".to_string(),
+            port: 8765,
+            advertise: None,
+            tokens: vec![WebToken {
+                token: "a b/c".to_string(),
+                scope: WebScope::Control,
+                name: None,
+            }],
+            tls: None,
+        });
+        let headers = format!("{}Origin: https://obs.example\r\n", UPGRADE);
+        assert_eq!(
+            call_with("GET /ws?token=a%20b%2Fc HTTP/1.1", &headers, &context),
+            "HTTP/1.1 101 Switching Protocols"
+        );
+        assert_eq!(
+            call_with("GET /ws?token=a%20b HTTP/1.1", &headers, &context),
+            "HTTP/1.1 401 Unauthorized"
+        );
+    }
+
+    #[test]
+    fn connections_beyond_the_limit_are_refused() {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let mut slots: Vec<_> = std::iter::from_fn(|| ConnectionSlot::take(&connections))
+            .take(MAX_CONNECTIONS + 1)
+            .collect();
+        assert_eq!(slots.len(), MAX_CONNECTIONS);
+        slots.pop();
+        assert!(ConnectionSlot::take(&connections).is_some());
+    }
+}
@@ -0,0 +1,214 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Platform-independent window matching for the `focus` action.
+//!
+//! Every `set_focus` backend enumerates its windows in window-manager order and
+//! asks a [`WindowQuery`] which of them match, then activates the selected one.
+
+use crate::pages::{FocusSpec, FocusTarget};
+use std::fs;
+use std::path::PathBuf;
+
+/// A resolved window query, shared by all `set_focus` backends
+#[derive(Debug, Clone, Default)]
+pub struct WindowQuery {
+    /// Lowercased class needle (empty = any)
+    pub class: String,
+    /// Lowercased title needle (empty = any)
+    pub title: String,
+    /// Equality instead of substring matching
+    pub exact: bool,
+    /// Either class or title may match (legacy single-string `focus`)
+    pub either: bool,
+    /// Required owning process id
+    pub pid: Option<u32>,
+    /// Zero-based index into the list of matches
+    pub index: usize,
+    /// Wrap `index` around the number of matches instead of failing
+    pub wrap: bool,
+}
+
+impl WindowQuery {
+    /// Builds a query for a `focus` target. `press_count` is the number of earlier
+    /// presses of the same cycling target and is ignored for non-cycling targets.
+    pub fn from_target(target: &FocusTarget, press_count: usize) -> Result<Self, String> {
+        match target {
+            FocusTarget::Name(name) => {
+                if name.is_empty() {
+                    return Err("At least one of class or title must be specified".to_string());
+                }
+                Ok(WindowQuery {
+                    class: name.to_lowercase(),
+                    title: name.to_lowercase(),
+                    either: true,
+                    ..Default::default()
+                })
+            }
+            FocusTarget::Detailed(spec) => Self::from_spec(spec, press_count),
+        }
+    }
+
+    fn from_spec(spec: &FocusSpec, press_count: usize) -> Result<Self, String> {
+        let class = match (&spec.class, &spec.desktop) {
+            (Some(class), _) => class.clone(),
+            (None, Some(desktop)) => resolve_desktop_class(desktop)?,
+            (None, None) => String::new(),
+        };
+        let title = spec.title.clone().unwrap_or_default();
+        if class.is_empty() && title.is_empty() && spec.pid.is_none() {
+            return Err("Focus target needs a class, title, desktop or pid".to_string());
+        }
+        let (index, wrap) = if spec.cycle {
+            (press_count, true)
+        } else {
+            (spec.nth.unwrap_or(1).saturating_sub(1), false)
+        };
+        Ok(WindowQuery {
+            class: class.to_lowercase(),
+            title: title.to_lowercase(),
+            exact: spec.exact,
+            either: false,
+            pid: spec.pid,
+            index,
+            wrap,
+        })
+    }
+
+    /// Checks a window against the query. `classes` lists every class-like name the
+    /// backend knows for the window (e.g. X11 res_name and res_class).
+    pub fn matches(&self, classes: &[&str], title: &str, pid: Option<u32>) -> bool {
+        if let Some(wanted) = self.pid {
+            if pid != Some(wanted) {
+                return false;
+            }
+        }
+        let class_match =
+            !self.class.is_empty() && classes.iter().any(|c| self.text_matches(c, &self.class));
+        let title_match = !self.title.is_empty() && self.text_matches(title, &self.title);
+        if self.either {
+            class_match || title_match
+        } else {
+            (self.class.is_empty() || class_match) && (self.title.is_empty() || title_match)
+        }
+    }
+
+    fn text_matches(&self, haystack: &str, needle: &str) -> bool {
+        let haystack = haystack.to_lowercase();
+        if self.exact {
+            haystack == needle
+        } else {
+            haystack.contains(needle)
+        }
+    }
+
+    /// Picks the window to activate among `candidates` (already filtered by `matches`)
+    pub fn select<T>(&self, mut candidates: Vec<T>) -> Result<T, String> {
+        if candidates.is_empty() {
+            return Err(format!("No matching window found for {}", self.describe()));
+        }
+        let index = if self.wrap {
+            self.index % candidates.len()
+        } else if self.index < candidates.len() {
+            self.index
+        } else {
+            return Err(format!(
+                "Only {} window(s) match {}, cannot focus match #{}",
+                candidates.len(),
+                self.describe(),
+                self.index + 1
+            ));
+        };
+        Ok(candidates.swap_remove(index))
+    }
+
+    /// Human-readable description for error messages
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.class.is_empty() {
+            parts.push(format!("class '{}'", self.class));
+        }
+        if !self.title.is_empty() {
+            parts.push(format!("title '{}'", self.title));
+        }
+        if let Some(pid) = self.pid {
+            parts.push(format!("pid {}", pid));
+        }
+        parts.join(if self.either { " or " } else { " and " })
+    }
+}
+
+/// Resolves a desktop file id (or path) to the window class its application uses:
+/// `StartupWMClass` when present, otherwise the desktop id itself.
+pub fn resolve_desktop_class(desktop: &str) -> Result<String, String> {
+    let id = desktop.trim_end_matches(".desktop");
+    let file = if desktop.contains('/') {
+        Some(PathBuf::from(desktop))
+    } else {
+        desktop_dirs()
+            .into_iter()
+            .map(|dir| dir.join(format!("{}.desktop", id)))
+            .find(|path| path.is_file())
+    };
+    let contents = match file {
+        Some(path) => fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read desktop file '{}': {}", path.display(), e))?,
+        None => return Err(format!("Desktop file '{}' not found", desktop)),
+    };
+    let class = contents
+        .lines()
+        .find_map(|line| line.strip_prefix("StartupWMClass="))
+        .map(|v| v.trim().to_string())
+        .unwrap_or_else(|| id.rsplit('/').next().unwrap_or(id).to_string());
+    Ok(class)
+}
+
+/// XDG application directories, user first
+fn desktop_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    let user_data = std::env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var("HOME")
+                .ok()
+                .map(|h| PathBuf::from(h).join(".local/share"))
+        });
+    if let Some(data) = user_data {
+        dirs.push(data.join("applications"));
+    }
+    let system = std::env::var("XDG_DATA_DIRS")
+        .unwrap_or_else(|_| "/usr/local/share:/usr/share".to_string());
+    for dir in system.split(':').filter(|d| !d.is_empty()) {
+        dirs.push(PathBuf::from(dir).join("applications"));
+    }
+    dirs.push(PathBuf::from("/var/lib/flatpak/exports/share/applications"));
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_name_matches_class_or_title() {
+        let q = WindowQuery::from_target(&FocusTarget::Name("Fire".into()), 0).unwrap();
+        assert!(q.matches(&["firefox"], "", None));
+        assert!(q.matches(&["other"], "Bonfire notes", None));
+        assert!(!q.matches(&["kitty"], "shell", None));
+    }
+
+    #[test]
+    fn cycling_wraps_around_matches() {
+        let spec = FocusSpec {
+            class: Some("konsole".into()),
+            exact: true,
+            cycle: true,
+            ..Default::default()
+        };
+        let q = WindowQuery::from_target(&FocusTarget::Detailed(spec), 3).unwrap();
+        assert!(!q.matches(&["konsole-extra"], "", None));
+        assert_eq!(q.select(vec!['a', 'b']).unwrap(), 'b');
+    }
+}
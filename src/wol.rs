@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Wake-on-LAN: the magic packet is six `0xFF` bytes followed by the MAC address of the
+//! machine sixteen times, sent over UDP to the discard port of a broadcast address.
+
+use std::net::UdpSocket;
+
+/// Broadcast address of the local network
+const DEFAULT_BROADCAST: &str = "255.255.255.255";
+
+/// Port the network cards listen to, by convention the discard port
+const WOL_PORT: u16 = 9;
+
+/// Parses a MAC address written as six hex bytes, separated by `:` or `-` or not at all
+fn parse_mac(mac: &str) -> Result<[u8; 6], String> {
+    let digits: String = mac
+        .trim()
+        .chars()
+        .filter(|c| *c != ':' && *c != '-')
+        .collect();
+    if digits.len() != 12 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid MAC address '{}'", mac));
+    }
+    let mut bytes = [0u8; 6];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16)
+            .map_err(|_| format!("Invalid MAC address '{}'", mac))?;
+    }
+    Ok(bytes)
+}
+
+/// The magic packet waking the machine with this MAC address
+fn magic_packet(mac: [u8; 6]) -> Vec<u8> {
+    let mut packet = vec![0xFF; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac);
+    }
+    packet
+}
+
+/// Sends the magic packet for `mac` to `broadcast`, or to the local network
+pub fn wake(mac: &str, broadcast: Option<&str>) -> Result<(), String> {
+    let packet = magic_packet(parse_mac(mac)?);
+    let broadcast = broadcast.unwrap_or(DEFAULT_BROADCAST);
+    let socket =
+        UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("Failed to open UDP socket: {}", e))?;
+    socket
+        .set_broadcast(true)
+        .map_err(|e| format!("Failed to enable broadcast: {}", e))?;
+    socket
+        .send_to(&packet, (broadcast, WOL_PORT))
+        .map_err(|e| format!("Failed to send Wake-on-LAN packet to {}: {}", broadcast, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magic_packet_repeats_the_mac() {
+        let mac = parse_mac("aa-bb-cc-dd-ee-ff").unwrap();
+        assert_eq!(mac, parse_mac("AA:BB:CC:DD:EE:FF").unwrap());
+        assert!(parse_mac("aa:bb:cc:dd:ee").is_err());
+        assert!(parse_mac("gg:bb:cc:dd:ee:ff").is_err());
+        let packet = magic_packet(mac);
+        assert_eq!(packet.len(), 102);
+        assert_eq!(&packet[..6], &[0xFF; 6]);
+        assert_eq!(&packet[96..], &mac);
+    }
+}
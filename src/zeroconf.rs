@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (C) 2025 Panayotis Katsaloulis
+
+//! Zeroconf (mDNS/DNS-SD) discovery of the web deck.
+//!
+//! A web deck reachable from the network is advertised as a `_keydeck._tcp` service
+//! named after the computer, so other machines find it without knowing its address:
+//! `keydeck --discover` and the configuration UI list every daemon that answers.
+
+use crate::pages::WebDeck;
+use crate::web_auth::local_only;
+use crate::{error_log, info_log, warn_log};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// DNS-SD service type of the web deck
+const SERVICE_TYPE: &str = "_keydeck._tcp.local.";
+
+/// How long `--discover` listens for answers
+const DISCOVERY_TIME: Duration = Duration::from_secs(3);
+
+/// The responder advertising this daemon, kept until it exits
+static ADVERTISER: Mutex<Option<ServiceDaemon>> = Mutex::new(None);
+
+/// A daemon found on the network
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteDaemon {
+    /// Instance name, the name of its computer
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub addresses: Vec<String>,
+    /// Address of its web deck
+    pub url: String,
+}
+
+fn host_name() -> String {
+    sysinfo::System::host_name().unwrap_or_else(|| "keydeck".to_string())
+}
+
+/// Whether the web deck should be advertised: when it listens beyond this computer,
+/// unless `advertise` says otherwise
+fn should_advertise(web: &WebDeck) -> bool {
+    let loopback = local_only(web);
+    match web.advertise {
+        Some(true) if loopback => {
+            warn_log!("The web deck only listens on this computer; not advertising it");
+            false
+        }
+        Some(advertise) => advertise,
+        None => !loopback,
+    }
+}
+
+/// Advertises the web deck on the local network, when it is reachable from there
+pub fn advertise(web: &WebDeck) {
+    if !should_advertise(web) {
+        return;
+    }
+    let host = host_name();
+    let tls = if web.tls.is_some() { "true" } else { "false" };
+    let properties = [
+        ("path", "/"),
+        ("version", env!("CARGO_PKG_VERSION")),
+        ("tls", tls),
+    ];
+    // A specific address is advertised as is; otherwise every interface is
+    let info = match web.bind.parse::<IpAddr>() {
+        Ok(ip) if !ip.is_unspecified() => ServiceInfo::new(
+            SERVICE_TYPE,
+            &host,
+            &format!("{}.local.", host),
+            ip,
+            web.port,
+            &properties[..],
+        ),
+        _ => ServiceInfo::new(
+            SERVICE_TYPE,
+            &host,
+            &format!("{}.local.", host),
+            "",
+            web.port,
+            &properties[..],
+        )
+        .map(ServiceInfo::enable_addr_auto),
+    };
+    let result = ServiceDaemon::new().and_then(|mdns| {
+        mdns.register(info?)?;
+        Ok(mdns)
+    });
+    match result {
+        Ok(mdns) => {
+            info_log!("Advertising the web deck as '{}' on the network", host);
+            *ADVERTISER.lock().unwrap() = Some(mdns);
+        }
+        Err(e) => error_log!("Error while advertising the web deck: {}", e),
+    }
+}
+
+/// Withdraws the advertisement, so other machines forget this daemon at once
+pub fn stop_advertising() {
+    if let Some(mdns) = ADVERTISER.lock().unwrap().take() {
+        let _ = mdns.shutdown();
+    }
+}
+
+/// Daemons advertising their web deck on the local network, this one included
+pub fn discover(wait: Duration) -> Result<Vec<RemoteDaemon>, String> {
+    let mdns = ServiceDaemon::new().map_err(|e| e.to_string())?;
+    let events = mdns.browse(SERVICE_TYPE).map_err(|e| e.to_string())?;
+    let deadline = Instant::now() + wait;
+    let mut found = BTreeMap::new();
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        match events.recv_timeout(left) {
+            Ok(ServiceEvent::ServiceResolved(info)) => {
+                let daemon = remote_daemon(&info);
+                found.insert(info.get_fullname().to_string(), daemon);
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    let _ = mdns.shutdown();
+    Ok(found.into_values().collect())
+}
+
+fn remote_daemon(info: &ServiceInfo) -> RemoteDaemon {
+    let host = info.get_hostname().trim_end_matches('.').to_string();
+    let mut addresses: Vec<IpAddr> = info.get_addresses().iter().copied().collect();
+    // IPv4 first, as it is what people type
+    addresses.sort_by_key(|ip| (ip.is_ipv6(), *ip));
+    let url_host = match addresses.first() {
+        Some(IpAddr::V4(ip)) => ip.to_string(),
+        Some(IpAddr::V6(ip)) => format!("[{}]", ip),
+        None => host.clone(),
+    };
+    let scheme = match info.get_property_val_str("tls") {
+        Some("true") => "https",
+        _ => "http",
+    };
+    RemoteDaemon {
+        name: info
+            .get_fullname()
+            .trim_end_matches(SERVICE_TYPE)
+            .trim_end_matches('.')
+            .to_string(),
+        url: format!("{}://{}:{}/", scheme, url_host, info.get_port()),
+        host,
+        port: info.get_port(),
+        addresses: addresses.iter().map(IpAddr::to_string).collect(),
+    }
+}
+
+/// `keydeck --discover`: lists the daemons found on the network
+pub fn print_discovered(json: bool) {
+    let daemons = match discover(DISCOVERY_TIME) {
+        Ok(daemons) => daemons,
+        Err(e) => {
+            error_log!("Error while searching the network: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if json {
+        println!("{}", serde_json::to_string(&daemons).unwrap_or_default());
+    } else if daemons.is_empty() {
+        println!("No KeyDeck daemons found on the network");
+    } else {
+        for daemon in daemons {
+            println!("{:<24} {}", daemon.name, daemon.url);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn web_deck(yaml: &str) -> WebDeck {
+        serde_yaml_ng::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn only_web_decks_reachable_from_the_network_are_advertised() {
+        assert!(!should_advertise(&web_deck("{}")));
+        assert!(should_advertise(&web_deck("bind: 0.0.0.0")));
+        assert!(!should_advertise(&web_deck(
+            "bind: 0.0.0.0\nadvertise: false"
+        )));
+        assert!(!should_advertise(&web_deck(
+            "bind: localhost\nadvertise: true"
+        )));
+    }
+
+    #[test]
+    fn discovered_daemons_are_reached_over_ipv4_first() {
+        let info = ServiceInfo::new(
+            SERVICE_TYPE,
+            "studio",
+            "studio.local.",
+            "fe80::1,192.168.1.20",
+            8765,
+            &[("path", "/")][..],
+        )
+        .unwrap();
+        let daemon = remote_daemon(&info);
+        assert_eq!(daemon.name, "studio");
+        assert_eq!(daemon.host, "studio.local");
+        assert_eq!(daemon.addresses, ["192.168.1.20", "fe80::1"]);
+        assert_eq!(daemon.url, "http://192.168.1.20:8765/");
+    }
+}